@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::*;
+
+const EXPORTS: usize = 500;
+
+/// A module with `EXPORTS` exported functions.
+fn module(engine: &Engine) -> Module {
+    let mut wat = String::from("(module");
+    for i in 0..EXPORTS {
+        wat.push_str(&format!(
+            "(func (export \"f{0}\") (result i32) i32.const {0})",
+            i
+        ));
+    }
+    wat.push_str(")");
+    Module::new(engine, &wat).expect("failed to compile benchmark module")
+}
+
+fn bench_exports(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_export");
+    let engine = Engine::default();
+    let module = module(&engine);
+    let names = ["f0", "f250", "f499"];
+
+    // Looks up the same few exports over and over, as an embedder which
+    // doesn't hold on to `Func`s between calls would.
+    group.bench_function("repeated", |b| {
+        let mut store = Store::new(&engine, ());
+        let instance =
+            Instance::new(&mut store, &module, &[]).expect("failed to instantiate module");
+        b.iter(|| {
+            for name in names.iter() {
+                instance.get_func(&mut store, name).expect("missing export");
+            }
+        });
+    });
+
+    // The first lookup of each name on a fresh instance, which has to create
+    // the `Func`s.
+    group.bench_function("first", |b| {
+        b.iter_batched(
+            || {
+                let mut store = Store::new(&engine, ());
+                let instance =
+                    Instance::new(&mut store, &module, &[]).expect("failed to instantiate module");
+                (store, instance)
+            },
+            |(mut store, instance)| {
+                for name in names.iter() {
+                    instance.get_func(&mut store, name).expect("missing export");
+                }
+            },
+            criterion::BatchSize::SmallInput,
+        );
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_exports);
+criterion_main!(benches);
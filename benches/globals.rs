@@ -0,0 +1,64 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::*;
+
+const ITERATIONS: i32 = 10_000;
+
+/// A loop incrementing a global, either defined in the module or imported.
+fn module(engine: &Engine, imported: bool) -> Module {
+    let global = if imported {
+        "(global $g (import \"\" \"g\") (mut i32))"
+    } else {
+        "(global $g (mut i32) (i32.const 0))"
+    };
+    let wat = format!(
+        r#"
+            (module
+                {}
+                (func (export "run") (param i32)
+                    (loop $l
+                        global.get $g
+                        i32.const 1
+                        i32.add
+                        global.set $g
+                        local.get 0
+                        i32.const 1
+                        i32.sub
+                        local.tee 0
+                        br_if $l)))
+        "#,
+        global
+    );
+    Module::new(engine, wat).expect("failed to compile benchmark module")
+}
+
+fn bench_globals(c: &mut Criterion) {
+    let mut group = c.benchmark_group("global_increment_loop");
+    let engine = Engine::default();
+
+    for imported in [false, true].iter() {
+        let module = module(&engine, *imported);
+        let mut store = Store::new(&engine, ());
+        let imports = if *imported {
+            let ty = GlobalType::new(ValType::I32, Mutability::Var);
+            let g = Global::new(&mut store, ty, 0.into()).expect("failed to create global");
+            vec![g.into()]
+        } else {
+            Vec::new()
+        };
+        let instance =
+            Instance::new(&mut store, &module, &imports).expect("failed to instantiate module");
+        let run = instance
+            .get_typed_func::<i32, (), _>(&mut store, "run")
+            .expect("failed to find `run`");
+
+        let name = if *imported { "imported" } else { "defined" };
+        group.bench_function(name, |b| {
+            b.iter(|| run.call(&mut store, ITERATIONS).expect("failed to run"));
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_globals);
+criterion_main!(benches);
@@ -0,0 +1,76 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::*;
+
+const INSTANCES: usize = 10_000;
+const FUNCS: usize = 50;
+
+/// A module importing `FUNCS` host functions and a memory, and a linker
+/// defining them.
+fn setup(engine: &Engine) -> (Module, Linker<StoreLimits>) {
+    let mut wat = String::from("(module");
+    for i in 0..FUNCS {
+        wat.push_str(&format!("(import \"host\" \"f{}\" (func (result i32)))", i));
+    }
+    wat.push_str("(import \"host\" \"memory\" (memory 1))");
+    wat.push_str("(func (export \"run\") (result i32) call 0 call 49 i32.add))");
+    let module = Module::new(engine, &wat).expect("failed to compile benchmark module");
+
+    let mut linker = Linker::new(engine);
+    for i in 0..FUNCS {
+        let value = i as i32;
+        linker
+            .func_wrap("host", &format!("f{}", i), move || value)
+            .expect("failed to define function");
+    }
+    (module, linker)
+}
+
+/// Creates a store containing the memory to import, able to hold `INSTANCES`
+/// instances.
+fn store(engine: &Engine, linker: &mut Linker<StoreLimits>) -> Store<StoreLimits> {
+    let limits = StoreLimitsBuilder::new().instances(INSTANCES).build();
+    let mut store = Store::new(engine, limits);
+    store.limiter(|limits| limits as &mut dyn ResourceLimiter);
+    let memory = Memory::new(&mut store, MemoryType::new(Limits::new(1, None)))
+        .expect("failed to create memory");
+    linker
+        .allow_shadowing(true)
+        .define("host", "memory", memory)
+        .expect("failed to define memory");
+    store
+}
+
+fn bench_imports(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instantiate_with_imports");
+    group.sample_size(10);
+    let engine = Engine::default();
+    let (module, mut linker) = setup(&engine);
+
+    group.bench_function("linker", |b| {
+        b.iter(|| {
+            let mut store = store(&engine, &mut linker);
+            for _ in 0..INSTANCES {
+                linker
+                    .instantiate(&mut store, &module)
+                    .expect("failed to instantiate");
+            }
+        });
+    });
+
+    group.bench_function("instance_pre", |b| {
+        b.iter(|| {
+            let mut store = store(&engine, &mut linker);
+            let pre = linker
+                .instantiate_pre(&mut store, &module)
+                .expect("failed to resolve imports");
+            for _ in 0..INSTANCES {
+                pre.instantiate(&mut store).expect("failed to instantiate");
+            }
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_imports);
+criterion_main!(benches);
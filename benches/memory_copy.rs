@@ -0,0 +1,51 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use wasmtime::*;
+
+/// Buffer sizes to copy, from smaller than the per-call overhead to a few
+/// megabytes.
+const SIZES: &[usize] = &[16, 4 << 10, 1 << 20, 4 << 20];
+
+fn bench_memory_copy(c: &mut Criterion) {
+    let mut store = Store::<()>::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Limits::new(128, None)))
+        .expect("failed to create memory");
+
+    let mut group = c.benchmark_group("memory_copy");
+    for &size in SIZES {
+        group.throughput(Throughput::Bytes(size as u64));
+
+        let data = vec![0x5a; size];
+        group.bench_with_input(BenchmarkId::new("write", size), &data, |b, data| {
+            b.iter(|| memory.write(&mut store, 1, data).unwrap());
+        });
+
+        let mut buf = Vec::with_capacity(size);
+        group.bench_with_input(BenchmarkId::new("read_into", size), &size, |b, &size| {
+            b.iter(|| {
+                buf.clear();
+                memory.read_into(&store, 1, &mut buf, size).unwrap();
+            });
+        });
+
+        let words = vec![0x5a5a_5a5au32; size / 4];
+        group.bench_with_input(
+            BenchmarkId::new("write_slice_as", size),
+            &words,
+            |b, words| {
+                b.iter(|| memory.write_slice_as(&mut store, 1, words).unwrap());
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("read_slice_as", size),
+            &(size / 4),
+            |b, &count| {
+                b.iter(|| memory.read_slice_as::<u32>(&store, 1, count).unwrap());
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_memory_copy);
+criterion_main!(benches);
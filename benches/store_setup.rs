@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::*;
+
+const MODULES: usize = 10;
+
+fn modules(engine: &Engine) -> Vec<Module> {
+    (0..MODULES)
+        .map(|i| {
+            let funcs = (0..100)
+                .map(|j| {
+                    format!(
+                        "(func (export \"f{}\") (result i32) i32.const {})",
+                        j,
+                        i * 100 + j
+                    )
+                })
+                .collect::<String>();
+            Module::new(engine, format!("(module {})", funcs))
+                .expect("failed to compile benchmark module")
+        })
+        .collect()
+}
+
+fn instantiate_all(store: &mut Store<()>, modules: &[Module]) {
+    for module in modules {
+        Instance::new(&mut *store, module, &[]).expect("failed to instantiate module");
+    }
+}
+
+fn bench_store_setup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("store_setup");
+
+    let engine = Engine::default();
+    let modules = modules(&engine);
+    let prepared =
+        PreparedModules::new(&engine, &modules).expect("failed to prepare benchmark modules");
+
+    group.bench_function("register on instantiation", |b| {
+        b.iter(|| {
+            let mut store = Store::new(&engine, ());
+            instantiate_all(&mut store, &modules);
+        });
+    });
+
+    group.bench_function("prepared", |b| {
+        b.iter(|| {
+            let mut store = Store::new_with_modules(&engine, (), &prepared);
+            instantiate_all(&mut store, &modules);
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_store_setup);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::*;
+
+const TABLE_SIZE: u32 = 10_000;
+
+/// A loop counting the non-null entries of a table, optionally calling an
+/// empty function on each iteration, which keeps the table's bound from being
+/// hoisted out of the loop.
+fn module(engine: &Engine, call_in_loop: bool) -> Module {
+    let call = if call_in_loop { "call $nop" } else { "" };
+    let wat = format!(
+        r#"
+            (module
+                (table (export "table") {size} funcref)
+                (func $nop)
+                (elem (i32.const 0) $nop)
+                (func (export "run") (result i32)
+                    (local $i i32) (local $count i32)
+                    (block
+                        (loop
+                            (br_if 1 (i32.ge_u (local.get $i) (table.size 0)))
+                            {call}
+                            (if (i32.eqz (ref.is_null (table.get 0 (local.get $i))))
+                                (local.set $count (i32.add (local.get $count) (i32.const 1))))
+                            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                            (br 0)))
+                    local.get $count))
+        "#,
+        size = TABLE_SIZE,
+        call = call,
+    );
+    Module::new(engine, wat).expect("failed to compile benchmark module")
+}
+
+fn bench_table_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("table_scan");
+    let engine = Engine::default();
+
+    for call_in_loop in [false, true].iter() {
+        let module = module(&engine, *call_in_loop);
+        let mut store = Store::new(&engine, ());
+        let instance =
+            Instance::new(&mut store, &module, &[]).expect("failed to instantiate module");
+        let run = instance
+            .get_typed_func::<(), i32, _>(&mut store, "run")
+            .expect("failed to find `run`");
+
+        let name = if *call_in_loop {
+            "call_in_loop"
+        } else {
+            "fixed"
+        };
+        group.bench_function(name, |b| {
+            b.iter(|| run.call(&mut store, ()).expect("failed to run"));
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_table_scan);
+criterion_main!(benches);
@@ -0,0 +1,96 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::*;
+
+/// Compares the cost of traps with and without a backtrace, both on their own
+/// and when returned by a host function called a few frames deep in wasm, and
+/// how the cost of wasm traps scales with the number of modules in a store.
+fn bench_traps(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trap_new");
+    group.bench_function("with_backtrace", |b| {
+        b.iter(|| Trap::new("would block"));
+    });
+    group.bench_function("without_backtrace", |b| {
+        b.iter(|| Trap::new_without_backtrace("would block"));
+    });
+    group.finish();
+
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "host" (func $host))
+                (func $a (call $host))
+                (func $b (call $a))
+                (func (export "run") (call $b)))
+        "#,
+    )
+    .expect("failed to compile benchmark module");
+
+    let mut group = c.benchmark_group("host_trap");
+    for backtrace in [true, false].iter() {
+        let mut store = Store::new(&engine, ());
+        let host = if *backtrace {
+            Func::wrap(&mut store, || -> Result<(), Trap> {
+                Err(Trap::new("would block"))
+            })
+        } else {
+            Func::wrap(&mut store, || -> Result<(), Trap> {
+                Err(Trap::new_without_backtrace("would block"))
+            })
+        };
+        let instance = Instance::new(&mut store, &module, &[host.into()])
+            .expect("failed to instantiate module");
+        let run = instance
+            .get_typed_func::<(), (), _>(&mut store, "run")
+            .expect("failed to find `run`");
+
+        let name = if *backtrace {
+            "with_backtrace"
+        } else {
+            "without_backtrace"
+        };
+        group.bench_function(name, |b| {
+            b.iter(|| run.call(&mut store, ()).unwrap_err());
+        });
+    }
+    group.finish();
+
+    // Wasm traps, which look up the module of every frame, in a store with
+    // more and more other modules registered.
+    let mut group = c.benchmark_group("wasm_trap_with_modules");
+    for count in [1, 16, 256].iter() {
+        let mut store = Store::new(&engine, ());
+        let mut run = None;
+        for i in 0..*count {
+            let module = Module::new(
+                &engine,
+                format!(
+                    r#"
+                        (module
+                            (func $a (drop (i32.const {})) unreachable)
+                            (func $b (call $a))
+                            (func (export "run") (call $b)))
+                    "#,
+                    i
+                ),
+            )
+            .expect("failed to compile benchmark module");
+            let instance =
+                Instance::new(&mut store, &module, &[]).expect("failed to instantiate module");
+            run = Some(
+                instance
+                    .get_typed_func::<(), (), _>(&mut store, "run")
+                    .expect("failed to find `run`"),
+            );
+        }
+        let run = run.unwrap();
+        group.bench_function(format!("{}_modules", count), |b| {
+            b.iter(|| run.call(&mut store, ()).unwrap_err());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_traps);
+criterion_main!(benches);
@@ -4583,7 +4583,7 @@ fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
 
                 // Matches_input further obfuscates which Wasm instruction this is ultimately
                 // lowering. Check here that the types are as expected for F64x2ConvertLowI32x4U.
-                debug_assert!(input_ty == types::I32X4 || output_ty == types::F64X2);
+                debug_assert!(input_ty == types::I32X4 && output_ty == types::F64X2);
 
                 // Algorithm uses unpcklps to help create a float that is equivalent
                 // 0x1.0p52 + double(src). 0x1.0p52 is unique because at this exponent
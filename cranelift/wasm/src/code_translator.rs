@@ -72,7 +72,7 @@
 //!     ("Relax verification to allow I8X16 to act as a default vector type")
 
 use super::{hash_map, HashMap};
-use crate::environ::{FuncEnvironment, GlobalVariable, ReturnMode, WasmResult};
+use crate::environ::{FuncEnvironment, GlobalVariable, ReturnMode, WasmError, WasmResult};
 use crate::state::{ControlStackFrame, ElseData, FuncTranslationState};
 use crate::translation_utils::{
     block_with_params, blocktype_params_results, f32_translation, f64_translation,
@@ -1996,6 +1996,59 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Checks that the tables and element segments used by `table.copy` and
+/// `table.init` have matching element types.
+///
+/// The validator rejects these too, but only with a generic "type mismatch",
+/// so this runs first to produce an error naming the tables and segments
+/// involved.
+pub fn check_table_element_types(
+    validator: &FuncValidator<impl WasmModuleResources>,
+    op: &Operator,
+    offset: usize,
+) -> WasmResult<()> {
+    let resources = validator.resources();
+    let table_type = |index: u32| resources.table_at(index).map(|t| t.element_type);
+    let message = match *op {
+        Operator::TableCopy {
+            dst_table,
+            src_table,
+        } => match (table_type(dst_table), table_type(src_table)) {
+            (Some(dst_ty), Some(src_ty)) if dst_ty != src_ty => format!(
+                "type mismatch: `table.copy` from table {} of type {} to table {} of type {}",
+                src_table,
+                ref_type_name(src_ty),
+                dst_table,
+                ref_type_name(dst_ty),
+            ),
+            _ => return Ok(()),
+        },
+        Operator::TableInit { segment, table } => {
+            match (table_type(table), resources.element_type_at(segment)) {
+                (Some(table_ty), Some(segment_ty)) if table_ty != segment_ty => format!(
+                    "type mismatch: `table.init` from element segment {} of type {} to table {} of type {}",
+                    segment,
+                    ref_type_name(segment_ty),
+                    table,
+                    ref_type_name(table_ty),
+                ),
+                _ => return Ok(()),
+            }
+        }
+        _ => return Ok(()),
+    };
+    Err(WasmError::InvalidWebAssembly { message, offset })
+}
+
+/// The name of a reference type in the text format.
+fn ref_type_name(ty: wasmparser::Type) -> &'static str {
+    match ty {
+        wasmparser::Type::FuncRef => "funcref",
+        wasmparser::Type::ExternRef => "externref",
+        _ => "unknown",
+    }
+}
+
 // Clippy warns us of some fields we are deliberately ignoring
 #[cfg_attr(feature = "cargo-clippy", allow(clippy::unneeded_field_pattern))]
 /// Deals with a Wasm instruction located in an unreachable portion of the code. Most of them
@@ -2136,10 +2189,87 @@ fn get_heap_addr(
     offset: u32,
     width: u32,
     addr_ty: Type,
+    elide_guarded_bounds_checks: bool,
     builder: &mut FunctionBuilder,
 ) -> (ir::Value, i32) {
-    let offset_guard_size: u64 = builder.func.heaps[heap].offset_guard_size.into();
+    let base = if elide_guarded_bounds_checks
+        && builder.func.dfg.value_type(addr32).bits() <= addr_ty.bits()
+        && access_is_guarded(builder.func, heap, addr32, offset, width)
+    {
+        // The access can't get past the guard region, so there's nothing to
+        // check and the address is just the heap's base plus the index.
+        let base_gv = builder.func.heaps[heap].base;
+        let heap_base = builder.ins().global_value(addr_ty, base_gv);
+        let index = if builder.func.dfg.value_type(addr32) == addr_ty {
+            addr32
+        } else {
+            builder.ins().uextend(addr_ty, addr32)
+        };
+        builder.ins().iadd(heap_base, index)
+    } else {
+        let offset_guard_size = builder.func.heaps[heap].offset_guard_size.into();
+        let check_size = bounds_check_size(offset, width, offset_guard_size);
+        builder.ins().heap_addr(addr_ty, heap, addr32, check_size)
+    };
 
+    // Native load/store instructions take a signed `Offset32` immediate, so adjust the base
+    // pointer if necessary.
+    if offset > i32::MAX as u32 {
+        // Offset doesn't fit in the load/store instruction.
+        let adj = builder.ins().iadd_imm(base, i64::from(i32::MAX) + 1);
+        (adj, (offset - (i32::MAX as u32 + 1)) as i32)
+    } else {
+        (base, offset as i32)
+    }
+}
+
+/// Returns whether every byte of a `width`-byte access at `offset` from the
+/// index `addr` is either within the heap's guaranteed size or in its
+/// offset-guard region, in which case the access needs no bounds check.
+///
+/// The largest value of `addr` is known when it's a constant, and otherwise is
+/// the maximum of its type.
+fn access_is_guarded(
+    func: &ir::Function,
+    heap: ir::Heap,
+    addr: ir::Value,
+    offset: u32,
+    width: u32,
+) -> bool {
+    let heap_data = &func.heaps[heap];
+    let guaranteed_size: u64 = match heap_data.style {
+        ir::HeapStyle::Static { bound } => bound.into(),
+        ir::HeapStyle::Dynamic { .. } => heap_data.min_size.into(),
+    };
+    let addr_mask = match func.dfg.value_type(addr) {
+        I32 => u64::from(u32::MAX),
+        _ => u64::MAX,
+    };
+    let max_addr = match func.dfg.value_def(addr) {
+        ir::ValueDef::Result(inst, _) => match func.dfg[inst] {
+            ir::InstructionData::UnaryImm {
+                opcode: ir::Opcode::Iconst,
+                imm,
+            } => imm.bits() as u64 & addr_mask,
+            _ => addr_mask,
+        },
+        _ => addr_mask,
+    };
+
+    let end = match max_addr
+        .checked_add(u64::from(offset))
+        .and_then(|end| end.checked_add(u64::from(width)))
+    {
+        Some(end) => end,
+        None => return false,
+    };
+    let guarded_end = guaranteed_size.saturating_add(heap_data.offset_guard_size.into());
+    end <= guarded_end
+}
+
+/// Returns the access size to bounds check with `heap_addr` for a `width`-byte
+/// access at `offset`, relying on the heap's `offset_guard_size`.
+fn bounds_check_size(offset: u32, width: u32, offset_guard_size: u64) -> u32 {
     // How exactly the bounds check is performed here and what it's performed
     // on is a bit tricky. Generally we want to rely on access violations (e.g.
     // segfaults) to generate traps since that means we don't have to bounds
@@ -2197,18 +2327,7 @@ fn get_heap_addr(
         cmp::max(u64::from(offset) / offset_guard_size * offset_guard_size, 1)
     };
     debug_assert!(adjusted_offset > 0); // want to bounds check at least 1 byte
-    let check_size = u32::try_from(adjusted_offset).unwrap_or(u32::MAX);
-    let base = builder.ins().heap_addr(addr_ty, heap, addr32, check_size);
-
-    // Native load/store instructions take a signed `Offset32` immediate, so adjust the base
-    // pointer if necessary.
-    if offset > i32::MAX as u32 {
-        // Offset doesn't fit in the load/store instruction.
-        let adj = builder.ins().iadd_imm(base, i64::from(i32::MAX) + 1);
-        (adj, (offset - (i32::MAX as u32 + 1)) as i32)
-    } else {
-        (base, offset as i32)
-    }
+    u32::try_from(adjusted_offset).unwrap_or(u32::MAX)
 }
 
 /// Prepare for a load; factors out common functionality between load and load_extend operations.
@@ -2219,6 +2338,7 @@ fn prepare_load<FE: FuncEnvironment + ?Sized>(
     state: &mut FuncTranslationState,
     environ: &mut FE,
 ) -> WasmResult<(MemFlags, Value, Offset32)> {
+    check_unhooked_access(memarg, "vector load", environ)?;
     let addr32 = state.pop1();
 
     let heap = state.get_heap(builder.func, memarg.memory, environ)?;
@@ -2228,6 +2348,7 @@ fn prepare_load<FE: FuncEnvironment + ?Sized>(
         memarg.offset,
         loaded_bytes,
         environ.pointer_type(),
+        environ.elide_guarded_bounds_checks(),
         builder,
     );
 
@@ -2250,6 +2371,38 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     state: &mut FuncTranslationState,
     environ: &mut FE,
 ) -> WasmResult<()> {
+    // Hooked accesses are left to the environment, which produces the loaded
+    // bytes as an `i64` to be converted to the result type here.
+    if !result_ty.is_vector()
+        && environ.is_memory_access_hooked(MemoryIndex::from_u32(memarg.memory))
+    {
+        let addr32 = state.pop1();
+        let bits = environ.translate_hooked_load(
+            builder,
+            MemoryIndex::from_u32(memarg.memory),
+            addr32,
+            memarg.offset,
+            mem_op_size(opcode, result_ty),
+        )?;
+        let value = match opcode {
+            ir::Opcode::Sload8 | ir::Opcode::Sload16 | ir::Opcode::Sload32 => {
+                let narrow_ty = Type::int(8 * mem_op_size(opcode, result_ty) as u16).unwrap();
+                let narrow = builder.ins().ireduce(narrow_ty, bits);
+                builder.ins().sextend(result_ty, narrow)
+            }
+            _ => match result_ty {
+                I64 => bits,
+                F64 => builder.ins().bitcast(F64, bits),
+                F32 => {
+                    let narrow = builder.ins().ireduce(I32, bits);
+                    builder.ins().bitcast(F32, narrow)
+                }
+                _ => builder.ins().ireduce(result_ty, bits),
+            },
+        };
+        state.push1(value);
+        return Ok(());
+    }
     let (flags, base, offset) = prepare_load(
         memarg,
         mem_op_size(opcode, result_ty),
@@ -2273,6 +2426,28 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     let (addr32, val) = state.pop2();
     let val_ty = builder.func.dfg.value_type(val);
 
+    if !val_ty.is_vector() && environ.is_memory_access_hooked(MemoryIndex::from_u32(memarg.memory))
+    {
+        let bits = match val_ty {
+            I64 => val,
+            F64 => builder.ins().bitcast(I64, val),
+            F32 => {
+                let int = builder.ins().bitcast(I32, val);
+                builder.ins().uextend(I64, int)
+            }
+            _ => builder.ins().uextend(I64, val),
+        };
+        return environ.translate_hooked_store(
+            builder,
+            MemoryIndex::from_u32(memarg.memory),
+            addr32,
+            memarg.offset,
+            mem_op_size(opcode, val_ty),
+            bits,
+        );
+    }
+    check_unhooked_access(memarg, "vector store", environ)?;
+
     let heap = state.get_heap(builder.func, memarg.memory, environ)?;
     let (base, offset) = get_heap_addr(
         heap,
@@ -2280,6 +2455,7 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
         memarg.offset,
         mem_op_size(opcode, val_ty),
         environ.pointer_type(),
+        environ.elide_guarded_bounds_checks(),
         builder,
     );
     // See the comments in `prepare_load` about the flags.
@@ -2291,6 +2467,24 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Hooked memories only support the scalar accesses which `translate_load` and
+/// `translate_store` can route through the environment, so reject any other
+/// kind of access to them.
+fn check_unhooked_access<FE: FuncEnvironment + ?Sized>(
+    memarg: &MemoryImmediate,
+    kind: &str,
+    environ: &FE,
+) -> WasmResult<()> {
+    if environ.is_memory_access_hooked(MemoryIndex::from_u32(memarg.memory)) {
+        return Err(wasm_unsupported!(
+            "{} of memory {} with hooked accesses",
+            kind,
+            memarg.memory
+        ));
+    }
+    Ok(())
+}
+
 fn mem_op_size(opcode: ir::Opcode, ty: Type) -> u32 {
     match opcode {
         ir::Opcode::Istore8 | ir::Opcode::Sload8 | ir::Opcode::Uload8 => 1,
@@ -2353,6 +2547,8 @@ fn finalise_atomic_mem_addr<FE: FuncEnvironment + ?Sized>(
     state: &mut FuncTranslationState,
     environ: &mut FE,
 ) -> WasmResult<Value> {
+    check_unhooked_access(memarg, "atomic access", environ)?;
+
     // Check the alignment of `linear_mem_addr`.
     let access_ty_bytes = access_ty.bytes();
     let final_lma = builder
@@ -2379,6 +2575,7 @@ fn finalise_atomic_mem_addr<FE: FuncEnvironment + ?Sized>(
         /*offset=*/ 0,
         access_ty.bytes(),
         environ.pointer_type(),
+        environ.elide_guarded_bounds_checks(),
         builder,
     );
 
@@ -91,6 +91,17 @@ pub struct DummyModuleInfo {
 
     /// The start function.
     pub start_func: Option<FuncIndex>,
+
+    /// The heap given to functions.
+    heap: DummyHeap,
+}
+
+/// The heap given to functions by a `DummyEnvironment`.
+#[derive(Clone, Copy)]
+struct DummyHeap {
+    bound: u64,
+    offset_guard_size: u64,
+    elide_guarded_bounds_checks: bool,
 }
 
 impl DummyModuleInfo {
@@ -109,6 +120,11 @@ impl DummyModuleInfo {
             memories: PrimaryMap::new(),
             globals: PrimaryMap::new(),
             start_func: None,
+            heap: DummyHeap {
+                bound: 0x1_0000_0000,
+                offset_guard_size: 0x8000_0000,
+                elide_guarded_bounds_checks: false,
+            },
         }
     }
 }
@@ -217,6 +233,23 @@ impl DummyEnvironment {
             after_idx: 0,
         });
     }
+
+    /// Gives functions a static heap with the given bound and offset-guard
+    /// size, rather than the default 4 GiB heap with a 2 GiB guard, and sets
+    /// whether their guarded bounds checks are elided. This is used for unit
+    /// tests.
+    pub fn test_heap(
+        &mut self,
+        bound: u64,
+        offset_guard_size: u64,
+        elide_guarded_bounds_checks: bool,
+    ) {
+        self.info.heap = DummyHeap {
+            bound,
+            offset_guard_size,
+            elide_guarded_bounds_checks,
+        };
+    }
 }
 
 /// The `FuncEnvironment` implementation for use by the `DummyEnvironment`.
@@ -273,6 +306,10 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         self.return_mode
     }
 
+    fn elide_guarded_bounds_checks(&self) -> bool {
+        self.mod_info.heap.elide_guarded_bounds_checks
+    }
+
     fn make_global(
         &mut self,
         func: &mut ir::Function,
@@ -301,9 +338,9 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         Ok(func.create_heap(ir::HeapData {
             base: gv,
             min_size: 0.into(),
-            offset_guard_size: 0x8000_0000.into(),
+            offset_guard_size: self.mod_info.heap.offset_guard_size.into(),
             style: ir::HeapStyle::Static {
-                bound: 0x1_0000_0000.into(),
+                bound: self.mod_info.heap.bound.into(),
             },
             index_type: I32,
         }))
@@ -295,6 +295,69 @@ pub trait FuncEnvironment: TargetEnvironment {
         ReturnMode::NormalReturns
     }
 
+    /// Whether heap accesses which provably stay within the heap's guaranteed
+    /// size plus its offset-guard region may be translated without a bounds
+    /// check.
+    ///
+    /// Such an access can't reach past the guard pages, so any out-of-bounds
+    /// part of it will fault rather than touch other memory. This relies on
+    /// the heap's `offset_guard_size` bytes after its bound always being
+    /// inaccessible, and on the embedder turning the fault into a trap. The
+    /// address of an elided access is computed from the heap's `base` global
+    /// value and doesn't get the Spectre mitigation of `heap_addr`.
+    fn elide_guarded_bounds_checks(&self) -> bool {
+        false
+    }
+
+    /// Whether scalar loads and stores of the memory `index` should be
+    /// translated with `translate_hooked_load` and `translate_hooked_store`
+    /// rather than as direct heap accesses.
+    fn is_memory_access_hooked(&self, index: MemoryIndex) -> bool {
+        drop(index);
+        false
+    }
+
+    /// Translate a hooked scalar load of `size` bytes at `addr + offset` in
+    /// the memory `index`.
+    ///
+    /// The returned value is an `i64` holding the loaded bytes, zero-extended;
+    /// the translator narrows, sign-extends or bitcasts it to the result type
+    /// of the load. This is only called if `is_memory_access_hooked` returns
+    /// `true` for `index`.
+    fn translate_hooked_load(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        index: MemoryIndex,
+        addr: ir::Value,
+        offset: u32,
+        size: u32,
+    ) -> WasmResult<ir::Value> {
+        drop((builder, index, addr, offset, size));
+        Err(WasmError::Unsupported(
+            "hooked memory accesses are not supported".to_string(),
+        ))
+    }
+
+    /// Translate a hooked scalar store of the low `size` bytes of `value`, an
+    /// `i64`, at `addr + offset` in the memory `index`.
+    ///
+    /// This is only called if `is_memory_access_hooked` returns `true` for
+    /// `index`.
+    fn translate_hooked_store(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        index: MemoryIndex,
+        addr: ir::Value,
+        offset: u32,
+        size: u32,
+        value: ir::Value,
+    ) -> WasmResult<()> {
+        drop((builder, index, addr, offset, size, value));
+        Err(WasmError::Unsupported(
+            "hooked memory accesses are not supported".to_string(),
+        ))
+    }
+
     /// Called after the locals for a function have been parsed, and the number
     /// of variables defined by this function is provided.
     fn after_locals(&mut self, num_locals_defined: usize) {
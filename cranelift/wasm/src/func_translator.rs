@@ -4,7 +4,9 @@
 //! function to Cranelift IR guided by a `FuncEnvironment` which provides information about the
 //! WebAssembly module and the runtime environment.
 
-use crate::code_translator::{bitcast_arguments, translate_operator, wasm_param_types};
+use crate::code_translator::{
+    bitcast_arguments, check_table_element_types, translate_operator, wasm_param_types,
+};
 use crate::environ::{FuncEnvironment, ReturnMode, WasmResult};
 use crate::state::FuncTranslationState;
 use crate::translation_utils::get_vmctx_value_label;
@@ -232,6 +234,7 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
         let pos = reader.original_position();
         builder.set_srcloc(cur_srcloc(&reader));
         let op = reader.read_operator()?;
+        check_table_element_types(validator, &op, pos)?;
         validator.op(pos, &op)?;
         environ.before_translate_operator(&op, builder, state)?;
         translate_operator(validator, &op, builder, state, environ)?;
@@ -1,6 +1,6 @@
 //! Translation skeleton that traverses the whole WebAssembly module and call helper functions
 //! to deal with each part of it.
-use crate::environ::{ModuleEnvironment, WasmResult};
+use crate::environ::{ModuleEnvironment, WasmError, WasmResult};
 use crate::sections_translator::{
     parse_alias_section, parse_data_section, parse_element_section, parse_event_section,
     parse_export_section, parse_function_section, parse_global_section, parse_import_section,
@@ -10,10 +10,13 @@ use crate::sections_translator::{
 use crate::state::ModuleTranslationState;
 use cranelift_codegen::timing;
 use std::prelude::v1::*;
-use wasmparser::{NameSectionReader, Parser, Payload, Validator};
+use wasmparser::{NameSectionReader, Parser, Payload, SectionReader, Validator};
 
 /// Translate a sequence of bytes forming a valid Wasm binary into a list of valid Cranelift IR
 /// [`Function`](cranelift_codegen::ir::Function).
+///
+/// Errors about malformed or invalid input carry the offset in `data` at which the problem was
+/// found, and their message says which section was being translated.
 pub fn translate_module<'data>(
     data: &'data [u8],
     environ: &mut dyn ModuleEnvironment<'data>,
@@ -23,132 +26,229 @@ pub fn translate_module<'data>(
     let mut validator = Validator::new();
     validator.wasm_features(environ.wasm_features());
 
+    // The number of function bodies left in the code section being parsed,
+    // used to tell which section errors from the parser itself are in.
+    let mut bodies_left = 0;
+    // Where each of the modules being parsed ends, innermost last, which is
+    // where errors about a module as a whole are reported.
+    let mut module_ends = Vec::new();
+    let mut nested_module_end = None;
+
     for payload in Parser::new(0).parse_all(data) {
-        match payload? {
-            Payload::Version { num, range } => {
-                validator.version(num, &range)?;
-                environ.module_start();
-            }
-            Payload::End => {
-                validator.end()?;
-                environ.module_end();
-            }
+        let payload = match payload {
+            Ok(payload) => payload,
+            Err(e) => {
+                let section = if bodies_left > 0 {
+                    "the code section"
+                } else {
+                    "a section header"
+                };
+                return Err(in_section(e.into(), section, None));
+            }
+        };
+        let (section, mut start) = describe_section(&payload);
+        match &payload {
+            Payload::Version { .. } => {
+                module_ends.push(nested_module_end.take().unwrap_or(data.len()));
+            }
+            Payload::End => start = module_ends.pop(),
+            Payload::ModuleSectionEntry { range, .. } => nested_module_end = Some(range.end),
+            Payload::CodeSectionStart { count, .. } => bodies_left = *count,
+            Payload::CodeSectionEntry(_) => bodies_left -= 1,
+            _ => {}
+        }
+        translate_payload(
+            payload,
+            &mut validator,
+            &mut module_translation_state,
+            environ,
+        )
+        .map_err(|e| in_section(e, section, start))?;
+    }
 
-            Payload::TypeSection(types) => {
-                validator.type_section(&types)?;
-                parse_type_section(types, &mut module_translation_state, environ)?;
-            }
+    Ok(module_translation_state)
+}
 
-            Payload::ImportSection(imports) => {
-                validator.import_section(&imports)?;
-                parse_import_section(imports, environ)?;
-            }
+fn translate_payload<'data>(
+    payload: Payload<'data>,
+    validator: &mut Validator,
+    module_translation_state: &mut ModuleTranslationState,
+    environ: &mut dyn ModuleEnvironment<'data>,
+) -> WasmResult<()> {
+    match payload {
+        Payload::Version { num, range } => {
+            validator.version(num, &range)?;
+            environ.module_start();
+        }
+        Payload::End => {
+            validator.end()?;
+            environ.module_end();
+        }
 
-            Payload::FunctionSection(functions) => {
-                validator.function_section(&functions)?;
-                parse_function_section(functions, environ)?;
-            }
+        Payload::TypeSection(types) => {
+            validator.type_section(&types)?;
+            parse_type_section(types, module_translation_state, environ)?;
+        }
 
-            Payload::TableSection(tables) => {
-                validator.table_section(&tables)?;
-                parse_table_section(tables, environ)?;
-            }
+        Payload::ImportSection(imports) => {
+            validator.import_section(&imports)?;
+            parse_import_section(imports, environ)?;
+        }
 
-            Payload::MemorySection(memories) => {
-                validator.memory_section(&memories)?;
-                parse_memory_section(memories, environ)?;
-            }
+        Payload::FunctionSection(functions) => {
+            validator.function_section(&functions)?;
+            parse_function_section(functions, environ)?;
+        }
 
-            Payload::EventSection(events) => {
-                validator.event_section(&events)?;
-                parse_event_section(events, environ)?;
-            }
+        Payload::TableSection(tables) => {
+            validator.table_section(&tables)?;
+            parse_table_section(tables, environ)?;
+        }
 
-            Payload::GlobalSection(globals) => {
-                validator.global_section(&globals)?;
-                parse_global_section(globals, environ)?;
-            }
+        Payload::MemorySection(memories) => {
+            validator.memory_section(&memories)?;
+            parse_memory_section(memories, environ)?;
+        }
 
-            Payload::ExportSection(exports) => {
-                validator.export_section(&exports)?;
-                parse_export_section(exports, environ)?;
-            }
+        Payload::EventSection(events) => {
+            validator.event_section(&events)?;
+            parse_event_section(events, environ)?;
+        }
 
-            Payload::StartSection { func, range } => {
-                validator.start_section(func, &range)?;
-                parse_start_section(func, environ)?;
-            }
+        Payload::GlobalSection(globals) => {
+            validator.global_section(&globals)?;
+            parse_global_section(globals, environ)?;
+        }
 
-            Payload::ElementSection(elements) => {
-                validator.element_section(&elements)?;
-                parse_element_section(elements, environ)?;
-            }
+        Payload::ExportSection(exports) => {
+            validator.export_section(&exports)?;
+            parse_export_section(exports, environ)?;
+        }
 
-            Payload::CodeSectionStart { count, range, .. } => {
-                validator.code_section_start(count, &range)?;
-                environ.reserve_function_bodies(count, range.start as u64);
-            }
+        Payload::StartSection { func, range } => {
+            validator.start_section(func, &range)?;
+            parse_start_section(func, environ)?;
+        }
 
-            Payload::CodeSectionEntry(body) => {
-                let func_validator = validator.code_section_entry()?;
-                environ.define_function_body(func_validator, body)?;
-            }
+        Payload::ElementSection(elements) => {
+            validator.element_section(&elements)?;
+            parse_element_section(elements, environ)?;
+        }
 
-            Payload::DataSection(data) => {
-                validator.data_section(&data)?;
-                parse_data_section(data, environ)?;
-            }
+        Payload::CodeSectionStart { count, range, .. } => {
+            validator.code_section_start(count, &range)?;
+            environ.reserve_function_bodies(count, range.start as u64);
+        }
 
-            Payload::DataCountSection { count, range } => {
-                validator.data_count_section(count, &range)?;
+        Payload::CodeSectionEntry(body) => {
+            let func_validator = validator.code_section_entry()?;
+            environ.define_function_body(func_validator, body)?;
+        }
 
-                // NOTE: the count here is the total segment count, not the passive segment count
-                environ.reserve_passive_data(count)?;
-            }
+        Payload::DataSection(data) => {
+            validator.data_section(&data)?;
+            parse_data_section(data, environ)?;
+        }
 
-            Payload::InstanceSection(s) => {
-                validator.instance_section(&s)?;
-                parse_instance_section(s, environ)?;
-            }
-            Payload::AliasSection(s) => {
-                validator.alias_section(&s)?;
-                parse_alias_section(s, environ)?;
-            }
-            Payload::ModuleSectionStart {
-                count,
-                range,
-                size: _,
-            } => {
-                validator.module_section_start(count, &range)?;
-                environ.reserve_modules(count);
-            }
+        Payload::DataCountSection { count, range } => {
+            validator.data_count_section(count, &range)?;
 
-            Payload::ModuleSectionEntry { .. } => {
-                validator.module_section_entry();
-            }
+            // NOTE: the count here is the total segment count, not the passive segment count
+            environ.reserve_passive_data(count)?;
+        }
 
-            Payload::CustomSection {
-                name: "name",
-                data,
-                data_offset,
-                range: _,
-            } => {
-                let result = NameSectionReader::new(data, data_offset)
-                    .map_err(|e| e.into())
-                    .and_then(|s| parse_name_section(s, environ));
-                if let Err(e) = result {
-                    log::warn!("failed to parse name section {:?}", e);
-                }
-            }
+        Payload::InstanceSection(s) => {
+            validator.instance_section(&s)?;
+            parse_instance_section(s, environ)?;
+        }
+        Payload::AliasSection(s) => {
+            validator.alias_section(&s)?;
+            parse_alias_section(s, environ)?;
+        }
+        Payload::ModuleSectionStart {
+            count,
+            range,
+            size: _,
+        } => {
+            validator.module_section_start(count, &range)?;
+            environ.reserve_modules(count);
+        }
 
-            Payload::CustomSection { name, data, .. } => environ.custom_section(name, data)?,
+        Payload::ModuleSectionEntry { .. } => {
+            validator.module_section_entry();
+        }
 
-            Payload::UnknownSection { id, range, .. } => {
-                validator.unknown_section(id, &range)?;
-                unreachable!();
+        Payload::CustomSection {
+            name: "name",
+            data,
+            data_offset,
+            range: _,
+        } => {
+            let result = NameSectionReader::new(data, data_offset)
+                .map_err(|e| e.into())
+                .and_then(|s| parse_name_section(s, environ));
+            if let Err(e) = result {
+                log::warn!("failed to parse name section {:?}", e);
             }
         }
+
+        Payload::CustomSection { name, data, .. } => environ.custom_section(name, data)?,
+
+        Payload::UnknownSection { id, range, .. } => {
+            validator.unknown_section(id, &range)?;
+            unreachable!();
+        }
     }
+    Ok(())
+}
 
-    Ok(module_translation_state)
+/// Returns a description of the section `payload` is from, and the offset at
+/// which that section starts if it's known.
+fn describe_section(payload: &Payload) -> (&'static str, Option<usize>) {
+    match payload {
+        Payload::Version { range, .. } => ("the module header", Some(range.start)),
+        Payload::End => ("the end of the module", None),
+        Payload::TypeSection(s) => ("the type section", Some(s.range().start)),
+        Payload::ImportSection(s) => ("the import section", Some(s.range().start)),
+        Payload::FunctionSection(s) => ("the function section", Some(s.range().start)),
+        Payload::TableSection(s) => ("the table section", Some(s.range().start)),
+        Payload::MemorySection(s) => ("the memory section", Some(s.range().start)),
+        Payload::EventSection(s) => ("the event section", Some(s.range().start)),
+        Payload::GlobalSection(s) => ("the global section", Some(s.range().start)),
+        Payload::ExportSection(s) => ("the export section", Some(s.range().start)),
+        Payload::StartSection { range, .. } => ("the start section", Some(range.start)),
+        Payload::ElementSection(s) => ("the element section", Some(s.range().start)),
+        Payload::CodeSectionStart { range, .. } => ("the code section", Some(range.start)),
+        Payload::CodeSectionEntry(_) => ("the code section", None),
+        Payload::DataSection(s) => ("the data section", Some(s.range().start)),
+        Payload::DataCountSection { range, .. } => ("the data count section", Some(range.start)),
+        Payload::InstanceSection(s) => ("the instance section", Some(s.range().start)),
+        Payload::AliasSection(s) => ("the alias section", Some(s.range().start)),
+        Payload::ModuleSectionStart { range, .. } | Payload::ModuleSectionEntry { range, .. } => {
+            ("the module section", Some(range.start))
+        }
+        Payload::CustomSection { range, .. } => ("a custom section", Some(range.start)),
+        Payload::UnknownSection { range, .. } => ("an unknown section", Some(range.start)),
+    }
+}
+
+/// Says in the message of an error about invalid input which section it was
+/// found in.
+///
+/// Some errors are raised where the position in the input isn't known, and
+/// use an offset of 0 for it. As that's the magic number at the start of the
+/// module, which is never inside a section, those are reported at `start`
+/// instead, which is the start of the section, or the end of the module for
+/// errors about the module as a whole.
+fn in_section(error: WasmError, section: &str, start: Option<usize>) -> WasmError {
+    match error {
+        WasmError::InvalidWebAssembly { message, offset } => WasmError::InvalidWebAssembly {
+            message: format!("{} (while parsing {})", message, section),
+            offset: match (offset, start) {
+                (0, Some(start)) => start,
+                _ => offset,
+            },
+        },
+        error => error,
+    }
 }
@@ -59,6 +59,7 @@ fn memory(ty: MemoryType) -> Memory {
             minimum: limits.initial,
             maximum: limits.maximum,
             shared: shared,
+            memory64: false,
         },
         // FIXME(#2361)
         MemoryType::M64 { .. } => unimplemented!(),
@@ -231,6 +231,9 @@ pub struct Memory {
     pub maximum: Option<u32>,
     /// Whether the memory may be shared between multiple threads.
     pub shared: bool,
+    /// Whether the memory is indexed with 64-bit addresses, as proposed by the
+    /// memory64 proposal.
+    pub memory64: bool,
 }
 
 /// WebAssembly event.
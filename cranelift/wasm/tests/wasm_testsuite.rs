@@ -1,3 +1,4 @@
+use cranelift_codegen::ir;
 use cranelift_codegen::isa;
 use cranelift_codegen::print_errors::pretty_verifier_error;
 use cranelift_codegen::settings::{self, Flags};
@@ -177,3 +178,105 @@ fn reachability_is_correct() {
         translate_module(data.as_ref(), &mut env).unwrap();
     }
 }
+
+/// Translates `wat`, which has a single function, with a static heap of `bound`
+/// bytes followed by a `guard`-byte guard region, and returns how many bounds
+/// checking `heap_addr` instructions the function contains.
+fn count_heap_addrs(wat: &str, bound: u64, guard: u64, elide: bool) -> usize {
+    let data = wat::parse_str(wat).unwrap();
+    let flags = Flags::new(settings::builder());
+    let triple = triple!("riscv64");
+    let isa = isa::lookup(triple).unwrap().finish(flags);
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    dummy_environ.test_heap(bound, guard, elide);
+
+    translate_module(data.as_ref(), &mut dummy_environ).unwrap();
+
+    let func = dummy_environ.info.function_bodies.values().next().unwrap();
+    verifier::verify_function(func, &*isa)
+        .map_err(|errors| panic!("{}", pretty_verifier_error(func, Some(&*isa), None, errors)))
+        .unwrap();
+    func.layout
+        .blocks()
+        .flat_map(|block| func.layout.block_insts(block))
+        .filter(|&inst| func.dfg[inst].opcode() == ir::Opcode::HeapAddr)
+        .count()
+}
+
+#[test]
+fn guarded_bounds_checks_are_elided() {
+    const GIB: u64 = 1 << 30;
+
+    // A constant address whose access ends within the heap, or within the
+    // guard region, needs no check.
+    let constant_load = |offset: u32| {
+        format!(
+            r#"
+                (module
+                    (memory 1)
+                    (func (result i32)
+                        i32.const 16
+                        i32.load offset={}))
+            "#,
+            offset
+        )
+    };
+    assert_eq!(count_heap_addrs(&constant_load(8), GIB, 2 * GIB, true), 0);
+    assert_eq!(count_heap_addrs(&constant_load(8), GIB, 2 * GIB, false), 1);
+    let past_bound = (GIB + GIB / 2) as u32;
+    assert_eq!(
+        count_heap_addrs(&constant_load(past_bound), GIB, 2 * GIB, true),
+        0
+    );
+
+    // Accesses which could extend past the guard region keep their check.
+    let end_of_guard = (3 * GIB - 16 - 4) as u32;
+    assert_eq!(
+        count_heap_addrs(&constant_load(end_of_guard), GIB, 2 * GIB, true),
+        0
+    );
+    assert_eq!(
+        count_heap_addrs(&constant_load(end_of_guard + 1), GIB, 2 * GIB, true),
+        1
+    );
+    assert_eq!(
+        count_heap_addrs(&constant_load(past_bound), GIB, 0, true),
+        1
+    );
+
+    // A dynamic address can be anywhere in the 4 GiB of an `i32`, so its
+    // accesses are only unchecked when the heap and guard span that plus the
+    // offset and width.
+    let dynamic_store = |offset: u32| {
+        format!(
+            r#"
+                (module
+                    (memory 1)
+                    (func (param i32)
+                        local.get 0
+                        i64.const 0
+                        i64.store offset={}))
+            "#,
+            offset
+        )
+    };
+    assert_eq!(count_heap_addrs(&dynamic_store(8), GIB, 2 * GIB, true), 1);
+    assert_eq!(
+        count_heap_addrs(&dynamic_store(8), 3 * GIB, 2 * GIB, true),
+        0
+    );
+    assert_eq!(
+        count_heap_addrs(&dynamic_store(8), 3 * GIB, 2 * GIB, false),
+        1
+    );
+    let end_of_guard = (GIB + 1 - 8) as u32;
+    assert_eq!(
+        count_heap_addrs(&dynamic_store(end_of_guard), 3 * GIB, 2 * GIB, true),
+        0
+    );
+    assert_eq!(
+        count_heap_addrs(&dynamic_store(end_of_guard + 1), 3 * GIB, 2 * GIB, true),
+        1
+    );
+}
@@ -0,0 +1,425 @@
+//! Macros for generating typed bindings between host code and WebAssembly
+//! modules.
+//!
+//! These macros are re-exported from the `wasmtime` crate when its `bindgen`
+//! feature is enabled, and the code they generate refers to items through the
+//! `wasmtime` crate. They're not intended to be used directly from this crate.
+
+extern crate proc_macro;
+
+use heck::CamelCase;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, parenthesized, parse_macro_input, Attribute, Error, Ident, LitStr, Token, Type};
+
+/// Generates a trait describing the functions a module imports from the host,
+/// along with an `add_to_linker` function which defines all of them in a
+/// [`Linker`](https://docs.rs/wasmtime/*/wasmtime/struct.Linker.html).
+///
+/// The input is a Rust module declaration naming the wasm module that the
+/// functions are imported from, containing a function signature for each
+/// import. Parameter and result types must be core wasm types: `i32`, `i64`,
+/// `f32`, or `f64`. Multiple results are written as a tuple.
+///
+/// ```
+/// wasmtime::import_module! {
+///     /// Functions the host provides to the guest.
+///     pub mod host in "host" {
+///         fn double(x: i32) -> i32;
+///         fn log(a: i32, b: i64);
+///         fn split(x: f64) -> (i32, f32);
+///     }
+/// }
+/// ```
+///
+/// This expands to a `host` module with a `Host` trait, named after the
+/// module in CamelCase, with a method for each function. Each method takes
+/// `&mut self` and returns its results wrapped in `Result<_, Trap>`, so the
+/// host can trap if needed:
+///
+/// ```
+/// # use wasmtime::*;
+/// # wasmtime::import_module! {
+/// #     pub mod host in "host" {
+/// #         fn double(x: i32) -> i32;
+/// #     }
+/// # }
+/// struct MyHost;
+///
+/// impl host::Host for MyHost {
+///     fn double(&mut self, x: i32) -> Result<i32, Trap> {
+///         Ok(x * 2)
+///     }
+/// }
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let engine = Engine::default();
+/// let mut linker = Linker::<MyHost>::new(&engine);
+/// host::add_to_linker(&mut linker, |state| state)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// `add_to_linker` takes a closure projecting the store's data to the type
+/// implementing the trait, so the trait can be implemented by a field of a
+/// larger store data type. Because the trait is checked by the compiler, a
+/// host implementation with the wrong signature doesn't compile:
+///
+/// ```compile_fail
+/// # use wasmtime::*;
+/// wasmtime::import_module! {
+///     pub mod host in "host" {
+///         fn double(x: i32) -> i32;
+///     }
+/// }
+///
+/// struct MyHost;
+///
+/// impl host::Host for MyHost {
+///     fn double(&mut self, x: i64) -> Result<i64, Trap> {
+///         Ok(x * 2)
+///     }
+/// }
+/// ```
+///
+/// Only core wasm types are accepted in signatures:
+///
+/// ```compile_fail
+/// wasmtime::import_module! {
+///     pub mod host in "host" {
+///         fn double(x: u32) -> u32;
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn import_module(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as ImportModule);
+    expand_import_module(&input).into()
+}
+
+/// Generates a struct holding a [`TypedFunc`] for each function a module
+/// exports.
+///
+/// The input is a struct declaration containing a function signature for
+/// each export, using the same types as [`import_module!`]. A `new` function
+/// is generated which looks up every export of an instance with its expected
+/// type, failing if any is missing or has a different type.
+///
+/// ```
+/// # use wasmtime::*;
+/// wasmtime::exports! {
+///     /// Functions exported by the guest.
+///     pub struct GuestExports {
+///         fn run(x: i32) -> i32;
+///         fn reset();
+///     }
+/// }
+///
+/// # fn main() -> anyhow::Result<()> {
+/// # let engine = Engine::default();
+/// # let mut store = Store::new(&engine, ());
+/// let module = Module::new(
+///     &engine,
+///     r#"(module
+///         (func (export "run") (param i32) (result i32) local.get 0)
+///         (func (export "reset")))"#,
+/// )?;
+/// let instance = Instance::new(&mut store, &module, &[])?;
+/// let exports = GuestExports::new(&mut store, &instance)?;
+/// assert_eq!(exports.run.call(&mut store, 3)?, 3);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`TypedFunc`]: https://docs.rs/wasmtime/*/wasmtime/struct.TypedFunc.html
+#[proc_macro]
+pub fn exports(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as Exports);
+    expand_exports(&input).into()
+}
+
+/// `$(#[attr])* $vis mod $name in "module" { $(fn ...;)* }`
+struct ImportModule {
+    attrs: Vec<Attribute>,
+    vis: syn::Visibility,
+    name: Ident,
+    module: LitStr,
+    funcs: Vec<Signature>,
+}
+
+/// `$(#[attr])* $vis struct $name { $(fn ...;)* }`
+struct Exports {
+    attrs: Vec<Attribute>,
+    vis: syn::Visibility,
+    name: Ident,
+    funcs: Vec<Signature>,
+}
+
+/// `$(#[attr])* fn $name($($param: $ty),*) $(-> $result)?;`
+struct Signature {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    params: Vec<(Ident, Type)>,
+    results: Option<Type>,
+}
+
+impl Parse for ImportModule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![mod]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![in]>()?;
+        let module = input.parse()?;
+        let funcs = parse_signatures(input)?;
+        Ok(ImportModule {
+            attrs,
+            vis,
+            name,
+            module,
+            funcs,
+        })
+    }
+}
+
+impl Parse for Exports {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name = input.parse()?;
+        let funcs = parse_signatures(input)?;
+        Ok(Exports {
+            attrs,
+            vis,
+            name,
+            funcs,
+        })
+    }
+}
+
+fn parse_signatures(input: ParseStream) -> syn::Result<Vec<Signature>> {
+    let content;
+    braced!(content in input);
+    let mut funcs = Vec::new();
+    while !content.is_empty() {
+        funcs.push(content.parse()?);
+    }
+    Ok(funcs)
+}
+
+impl Parse for Signature {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        input.parse::<Token![fn]>()?;
+        let name = input.parse()?;
+
+        let content;
+        parenthesized!(content in input);
+        let params =
+            Punctuated::<(Ident, Type), Token![,]>::parse_terminated_with(&content, |input| {
+                let name = input.parse()?;
+                input.parse::<Token![:]>()?;
+                let ty = input.parse()?;
+                check_core_type(&ty)?;
+                Ok((name, ty))
+            })?;
+
+        let results = if input.peek(Token![->]) {
+            input.parse::<Token![->]>()?;
+            let ty = input.parse()?;
+            match &ty {
+                Type::Tuple(tuple) => {
+                    for elem in tuple.elems.iter() {
+                        check_core_type(elem)?;
+                    }
+                }
+                ty => check_core_type(ty)?,
+            }
+            Some(ty)
+        } else {
+            None
+        };
+        input.parse::<Token![;]>()?;
+
+        Ok(Signature {
+            attrs,
+            name,
+            params: params.into_iter().collect(),
+            results,
+        })
+    }
+}
+
+fn check_core_type(ty: &Type) -> syn::Result<()> {
+    if let Type::Path(path) = ty {
+        if path.qself.is_none() {
+            if let Some(ident) = path.path.get_ident() {
+                if ["i32", "i64", "f32", "f64"].iter().any(|t| ident == t) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Err(Error::new_spanned(
+        ty,
+        "expected a core wasm type: `i32`, `i64`, `f32`, or `f64`",
+    ))
+}
+
+impl Signature {
+    /// The Rust type used for this function's results, `()` if it has none.
+    fn results(&self) -> TokenStream {
+        match &self.results {
+            Some(ty) => quote!(#ty),
+            None => quote!(()),
+        }
+    }
+}
+
+fn expand_import_module(input: &ImportModule) -> TokenStream {
+    let ImportModule {
+        attrs,
+        vis,
+        name,
+        module,
+        funcs,
+    } = input;
+    let trait_name = Ident::new(&name.to_string().to_camel_case(), name.span());
+    let trait_doc = format!(
+        "Functions imported by WebAssembly from the `{}` module.",
+        module.value()
+    );
+    let add_to_linker_doc = format!(
+        "Defines each function of [`{}`] in `linker` under the `{}` module.",
+        trait_name,
+        module.value()
+    );
+
+    let methods = funcs.iter().map(|func| {
+        let attrs = &func.attrs;
+        let name = &func.name;
+        let params = func.params.iter().map(|(name, ty)| quote!(#name: #ty));
+        let results = func.results();
+        quote! {
+            #(#attrs)*
+            fn #name(&mut self, #(#params),*)
+                -> ::std::result::Result<#results, ::wasmtime::Trap>;
+        }
+    });
+
+    let definitions = funcs.iter().map(|func| {
+        let name = &func.name;
+        let wasm_name = LitStr::new(&name.to_string(), name.span());
+        let args = (0..func.params.len())
+            .map(|i| format_ident!("arg{}", i))
+            .collect::<Vec<_>>();
+        let tys = func.params.iter().map(|(_, ty)| ty);
+        let results = func.results();
+        quote! {
+            linker.func_wrap(
+                #module,
+                #wasm_name,
+                move |mut caller: ::wasmtime::Caller<'_, T>, #(#args: #tys),*|
+                    -> ::std::result::Result<#results, ::wasmtime::Trap>
+                {
+                    #trait_name::#name(get(caller.data_mut()), #(#args),*)
+                },
+            )?;
+        }
+    });
+
+    quote! {
+        #(#attrs)*
+        #vis mod #name {
+            #[doc = #trait_doc]
+            pub trait #trait_name {
+                #(#methods)*
+            }
+
+            #[doc = #add_to_linker_doc]
+            ///
+            /// The `get` closure projects the store's data to the value
+            /// implementing the trait.
+            pub fn add_to_linker<T, U>(
+                linker: &mut ::wasmtime::Linker<T>,
+                get: impl Fn(&mut T) -> &mut U + Send + Sync + Copy + 'static,
+            ) -> ::wasmtime::__private::anyhow::Result<()>
+            where
+                U: #trait_name,
+            {
+                #(#definitions)*
+                Ok(())
+            }
+        }
+    }
+}
+
+fn expand_exports(input: &Exports) -> TokenStream {
+    let Exports {
+        attrs,
+        vis,
+        name,
+        funcs,
+    } = input;
+
+    let fields = funcs.iter().map(|func| {
+        let attrs = &func.attrs;
+        let name = &func.name;
+        let params = params_type(func);
+        let results = func.results();
+        quote! {
+            #(#attrs)*
+            pub #name: ::wasmtime::TypedFunc<#params, #results>,
+        }
+    });
+
+    let lookups = funcs.iter().map(|func| {
+        let name = &func.name;
+        let wasm_name = LitStr::new(&name.to_string(), name.span());
+        let params = params_type(func);
+        let results = func.results();
+        quote! {
+            #name: instance.get_typed_func::<#params, #results, _>(&mut store, #wasm_name)?,
+        }
+    });
+
+    quote! {
+        #(#attrs)*
+        #vis struct #name {
+            #(#fields)*
+        }
+
+        impl #name {
+            /// Looks up each export of `instance` with its expected type.
+            ///
+            /// # Errors
+            ///
+            /// Returns an error if any export is missing or has a different
+            /// type than declared.
+            pub fn new(
+                mut store: impl ::wasmtime::AsContextMut,
+                instance: &::wasmtime::Instance,
+            ) -> ::wasmtime::__private::anyhow::Result<#name> {
+                Ok(#name {
+                    #(#lookups)*
+                })
+            }
+        }
+    }
+}
+
+/// The `Params` type of a `TypedFunc` for `func`: `()`, a single type, or a
+/// tuple of types.
+fn params_type(func: &Signature) -> TokenStream {
+    let tys = func.params.iter().map(|(_, ty)| ty);
+    match func.params.len() {
+        1 => {
+            let ty = &func.params[0].1;
+            quote!(#ty)
+        }
+        _ => quote!((#(#tys,)*)),
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use wasmtime::*;
+
+wasmtime::import_module! {
+    /// Everything the guest below imports.
+    pub mod env in "env" {
+        fn add(a: i32, b: i32) -> i32;
+        fn neg(x: i64) -> i64;
+        fn half(x: f32) -> f32;
+        fn sqrt(x: f64) -> f64;
+        fn swap(a: i32, b: i64) -> (i64, i32);
+        fn count();
+        fn get_count() -> i32;
+        fn widen(x: i32) -> i64;
+        fn pair(x: f64) -> (f64, f64);
+        fn fail(code: i32);
+    }
+}
+
+wasmtime::exports! {
+    /// Everything the guest below exports.
+    pub struct Guest {
+        fn call_add(a: i32, b: i32) -> i32;
+        fn call_neg(x: i64) -> i64;
+        fn call_half(x: f32) -> f32;
+        fn call_sqrt(x: f64) -> f64;
+        fn call_swap(a: i32, b: i64) -> (i64, i32);
+        fn call_count();
+        fn call_get_count() -> i32;
+        fn call_widen(x: i32) -> i64;
+        fn call_pair(x: f64) -> (f64, f64);
+        fn call_fail(code: i32);
+    }
+}
+
+const GUEST: &str = r#"
+    (module
+        (import "env" "add" (func $add (param i32 i32) (result i32)))
+        (import "env" "neg" (func $neg (param i64) (result i64)))
+        (import "env" "half" (func $half (param f32) (result f32)))
+        (import "env" "sqrt" (func $sqrt (param f64) (result f64)))
+        (import "env" "swap" (func $swap (param i32 i64) (result i64 i32)))
+        (import "env" "count" (func $count))
+        (import "env" "get_count" (func $get_count (result i32)))
+        (import "env" "widen" (func $widen (param i32) (result i64)))
+        (import "env" "pair" (func $pair (param f64) (result f64 f64)))
+        (import "env" "fail" (func $fail (param i32)))
+
+        (func (export "call_add") (param i32 i32) (result i32)
+            local.get 0 local.get 1 call $add)
+        (func (export "call_neg") (param i64) (result i64)
+            local.get 0 call $neg)
+        (func (export "call_half") (param f32) (result f32)
+            local.get 0 call $half)
+        (func (export "call_sqrt") (param f64) (result f64)
+            local.get 0 call $sqrt)
+        (func (export "call_swap") (param i32 i64) (result i64 i32)
+            local.get 0 local.get 1 call $swap)
+        (func (export "call_count")
+            call $count)
+        (func (export "call_get_count") (result i32)
+            call $get_count)
+        (func (export "call_widen") (param i32) (result i64)
+            local.get 0 call $widen)
+        (func (export "call_pair") (param f64) (result f64 f64)
+            local.get 0 call $pair)
+        (func (export "call_fail") (param i32)
+            local.get 0 call $fail)
+    )
+"#;
+
+#[derive(Default)]
+struct Host {
+    count: i32,
+}
+
+impl env::Env for Host {
+    fn add(&mut self, a: i32, b: i32) -> Result<i32, Trap> {
+        Ok(a + b)
+    }
+
+    fn neg(&mut self, x: i64) -> Result<i64, Trap> {
+        Ok(-x)
+    }
+
+    fn half(&mut self, x: f32) -> Result<f32, Trap> {
+        Ok(x / 2.0)
+    }
+
+    fn sqrt(&mut self, x: f64) -> Result<f64, Trap> {
+        Ok(x.sqrt())
+    }
+
+    fn swap(&mut self, a: i32, b: i64) -> Result<(i64, i32), Trap> {
+        Ok((b, a))
+    }
+
+    fn count(&mut self) -> Result<(), Trap> {
+        self.count += 1;
+        Ok(())
+    }
+
+    fn get_count(&mut self) -> Result<i32, Trap> {
+        Ok(self.count)
+    }
+
+    fn widen(&mut self, x: i32) -> Result<i64, Trap> {
+        Ok(i64::from(x) << 32)
+    }
+
+    fn pair(&mut self, x: f64) -> Result<(f64, f64), Trap> {
+        Ok((x, -x))
+    }
+
+    fn fail(&mut self, code: i32) -> Result<(), Trap> {
+        if code == 0 {
+            Ok(())
+        } else {
+            Err(Trap::new(format!("failed with {}", code)))
+        }
+    }
+}
+
+/// Store data which holds the host implementation in a field, to exercise the
+/// `get` projection passed to `add_to_linker`.
+#[derive(Default)]
+struct Ctx {
+    host: Host,
+}
+
+#[test]
+fn imports_and_exports() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, GUEST)?;
+    let mut linker = Linker::<Ctx>::new(&engine);
+    env::add_to_linker(&mut linker, |cx| &mut cx.host)?;
+
+    let mut store = Store::new(&engine, Ctx::default());
+    let instance = linker.instantiate(&mut store, &module)?;
+    let guest = Guest::new(&mut store, &instance)?;
+
+    assert_eq!(guest.call_add.call(&mut store, (1, 2))?, 3);
+    assert_eq!(guest.call_neg.call(&mut store, 5)?, -5);
+    assert_eq!(guest.call_half.call(&mut store, 3.0)?, 1.5);
+    assert_eq!(guest.call_sqrt.call(&mut store, 16.0)?, 4.0);
+    assert_eq!(guest.call_swap.call(&mut store, (1, 2))?, (2, 1));
+    guest.call_count.call(&mut store, ())?;
+    guest.call_count.call(&mut store, ())?;
+    assert_eq!(store.data().host.count, 2);
+    assert_eq!(guest.call_get_count.call(&mut store, ())?, 2);
+    assert_eq!(guest.call_widen.call(&mut store, 1)?, 1 << 32);
+    assert_eq!(guest.call_pair.call(&mut store, 2.5)?, (2.5, -2.5));
+
+    guest.call_fail.call(&mut store, 0)?;
+    let trap = guest.call_fail.call(&mut store, 3).unwrap_err();
+    assert!(trap.to_string().contains("failed with 3"), "{}", trap);
+    Ok(())
+}
+
+#[test]
+fn exports_type_mismatch() -> Result<()> {
+    wasmtime::exports! {
+        #[allow(dead_code)]
+        struct Wrong {
+            fn run(x: i64) -> i32;
+        }
+    }
+
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"(module (func (export "run") (param i32) (result i32) local.get 0))"#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    assert!(Wrong::new(&mut store, &instance).is_err());
+    Ok(())
+}
+
+#[test]
+fn imports_type_mismatch() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"(module (import "env" "add" (func (param i64 i64) (result i64))))"#,
+    )?;
+    let mut linker = Linker::<Ctx>::new(&engine);
+    env::add_to_linker(&mut linker, |cx| &mut cx.host)?;
+    let mut store = Store::new(&engine, Ctx::default());
+    assert!(linker.instantiate(&mut store, &module).is_err());
+    Ok(())
+}
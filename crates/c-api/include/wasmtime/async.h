@@ -0,0 +1,261 @@
+/**
+ * \file wasmtime/async.h
+ *
+ * \brief Wasmtime async functionality
+ *
+ * Async functionality in Wasmtime is well documented in the Rust API, see
+ * [the Rust documentation for
+ * `Config::async_support`](https://bytecodealliance.github.io/wasmtime/api/wasmtime/struct.Config.html#method.async_support).
+ * In short, when async support is enabled on a #wasm_config_t, WebAssembly
+ * executes on a separate native stack (a "fiber"), and host functions defined
+ * with #wasmtime_linker_define_async_func may suspend that fiber while they
+ * wait for their results.
+ *
+ * From C this looks like:
+ *
+ * * Async host functions receive a #wasmtime_async_continuation_t. They return
+ *   immediately and later hand the function's results, or a trap, to
+ *   #wasmtime_async_continuation_complete, possibly from another thread.
+ * * Calls into WebAssembly, with #wasmtime_func_call_async, and instantiation,
+ *   with #wasmtime_linker_instantiate_async, return a #wasmtime_call_future_t.
+ *   The embedder drives it by calling #wasmtime_call_future_poll until it
+ *   returns `true`. A poll returns `false` when the call is waiting on an
+ *   async host function, after which the embedder should poll again once it
+ *   has completed that function's continuation.
+ *
+ * Stores whose engine has async support enabled must exclusively use these
+ * async entry points rather than #wasmtime_func_call and
+ * #wasmtime_linker_instantiate.
+ */
+
+#ifndef WASMTIME_ASYNC_H
+#define WASMTIME_ASYNC_H
+
+#include <wasm.h>
+#include <wasmtime/config.h>
+#include <wasmtime/error.h>
+#include <wasmtime/func.h>
+#include <wasmtime/linker.h>
+#include <wasmtime/store.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * \brief Whether or not to enable support for asynchronous functions in
+ * Wasmtime.
+ *
+ * When enabled, stores created with this config can only call WebAssembly
+ * through the async functions in this header, and only those stores can use
+ * host functions defined with #wasmtime_linker_define_async_func.
+ *
+ * This setting is `false` by default.
+ */
+WASMTIME_CONFIG_PROP(void, async_support, bool)
+
+/**
+ * \brief Configures the size of the stacks used for asynchronous execution.
+ *
+ * This setting configures the size of the stacks that are allocated for
+ * asynchronous execution. The value cannot be less than `max_wasm_stack`, in
+ * which case `false` is returned and the setting isn't changed.
+ *
+ * The amount of stack space guaranteed for host functions is
+ * `async_stack_size - max_wasm_stack`, so take care not to set these two
+ * values close to one another.
+ *
+ * This setting is 2 MiB by default.
+ */
+WASMTIME_CONFIG_PROP(bool, async_stack_size, size_t)
+
+/**
+ * \typedef wasmtime_async_continuation_t
+ * \brief Alias to #wasmtime_async_continuation
+ *
+ * \struct wasmtime_async_continuation
+ * \brief A handle used to complete a call to an async host function.
+ *
+ * Each call of a #wasmtime_func_async_callback_t receives one of these, and
+ * ownership of it. The call is finished by passing it to
+ * #wasmtime_async_continuation_complete, which can happen on any thread, and
+ * until then the WebAssembly that made the call is suspended.
+ */
+typedef struct wasmtime_async_continuation wasmtime_async_continuation_t;
+
+/**
+ * \brief Callback signature for #wasmtime_linker_define_async_func.
+ *
+ * \param env user-provided argument passed to
+ * #wasmtime_linker_define_async_func
+ * \param caller a temporary object that can only be used during this function
+ * call, not once it has returned. Used to acquire #wasmtime_context_t or
+ * caller's state
+ * \param args the arguments provided to this function invocation
+ * \param nargs how many arguments are provided
+ * \param nresults how many results must be produced
+ * \param continuation the handle used to complete this call, which is owned
+ * by the callee
+ *
+ * The callback should start whatever work produces the function's results and
+ * return without waiting for it. The call is finished once `continuation` is
+ * passed to #wasmtime_async_continuation_complete.
+ */
+typedef void (*wasmtime_func_async_callback_t)(
+    void *env,
+    wasmtime_caller_t *caller,
+    const wasmtime_val_t *args,
+    size_t nargs,
+    size_t nresults,
+    wasmtime_async_continuation_t *continuation);
+
+/**
+ * \brief Completes a call to an async host function.
+ *
+ * \param continuation the continuation given to the host function, ownership
+ * of which is taken by this function.
+ * \param results the results of the call, which must match the function's
+ * type if `trap` is `NULL`. May be `NULL` if `nresults` is zero.
+ * \param nresults the number of results.
+ * \param trap an optional trap to raise in WebAssembly instead of returning
+ * `results`, ownership of which is taken by this function.
+ *
+ * This may be called from any thread. The #wasmtime_call_future_t of the call
+ * which is waiting on this function can make progress once this returns.
+ *
+ * Does not take ownership of #wasmtime_val_t results.
+ */
+WASM_API_EXTERN void wasmtime_async_continuation_complete(
+    wasmtime_async_continuation_t *continuation,
+    const wasmtime_val_t *results,
+    size_t nresults,
+    wasm_trap_t *trap
+);
+
+/**
+ * \brief Defines a new async function in this linker.
+ *
+ * \param linker the linker the name is being defined in.
+ * \param module the module name the item is defined under.
+ * \param module_len the byte length of `module`
+ * \param name the field name the item is defined under
+ * \param name_len the byte length of `name`
+ * \param ty the type of the function that's being defined
+ * \param cb the host callback to invoke when the function is called
+ * \param data the host-provided data to provide as the first argument to the callback
+ * \param finalizer an optional finalizer for the `data` argument.
+ *
+ * \return On success `NULL` is returned, otherwise an error is returned which
+ * describes why the definition failed. Async functions can only be defined in
+ * a linker whose engine has async support enabled.
+ *
+ * This function is the async counterpart of defining a host function created
+ * with #wasmtime_func_new in a linker. Instead of producing its results before
+ * returning, `cb` is given a #wasmtime_async_continuation_t to complete.
+ *
+ * Does not take ownership of `ty`.
+ */
+WASM_API_EXTERN wasmtime_error_t* wasmtime_linker_define_async_func(
+    wasmtime_linker_t *linker,
+    const char *module,
+    size_t module_len,
+    const char *name,
+    size_t name_len,
+    const wasm_functype_t *ty,
+    wasmtime_func_async_callback_t cb,
+    void *data,
+    void (*finalizer)(void*)
+);
+
+/**
+ * \typedef wasmtime_call_future_t
+ * \brief Alias to #wasmtime_call_future
+ *
+ * \struct wasmtime_call_future
+ * \brief An in-progress async call into WebAssembly.
+ *
+ * This is returned by #wasmtime_func_call_async and
+ * #wasmtime_linker_instantiate_async, and is driven to completion with
+ * #wasmtime_call_future_poll. The pointers passed to the function which
+ * created it, other than the arguments of a call, must stay valid until it's
+ * deleted with #wasmtime_call_future_delete, and the store can't be used for
+ * anything else in the meantime.
+ */
+typedef struct wasmtime_call_future wasmtime_call_future_t;
+
+/**
+ * \brief Executes WebAssembly in the function until it's finished or waiting
+ * on an async host function.
+ *
+ * Returns `true` once the call has finished, at which point its results, trap
+ * or error have been written to where the function which created `future`
+ * was told to write them. Returns `false` if the call is waiting on the
+ * continuation of an async host function, in which case this should be called
+ * again after that continuation has been completed.
+ *
+ * This must not be called again once it has returned `true`.
+ */
+WASM_API_EXTERN bool wasmtime_call_future_poll(wasmtime_call_future_t *future);
+
+/**
+ * \brief Frees the underlying memory for a future.
+ *
+ * A future which hasn't finished can be deleted to cancel its call, although
+ * its results aren't written in that case.
+ */
+WASM_API_EXTERN void wasmtime_call_future_delete(wasmtime_call_future_t *future);
+
+/**
+ * \brief Invokes this function with the params given, returning the results
+ * asynchronously.
+ *
+ * This function is the same as #wasmtime_func_call except that it's used for
+ * stores with async support enabled. Nothing runs until the returned future is
+ * polled with #wasmtime_call_future_poll.
+ *
+ * Once the future has finished, one of three things has happened, as for
+ * #wasmtime_func_call: `error_ret` was written with an error, `trap_ret` was
+ * written with a trap, or both are `NULL` and `results` was written.
+ *
+ * The `args` are only read by this function, but `store`, `results`,
+ * `trap_ret` and `error_ret` must stay valid until the returned future is
+ * deleted.
+ *
+ * Does not take ownership of #wasmtime_val_t arguments. Gives ownership of
+ * #wasmtime_val_t results.
+ */
+WASM_API_EXTERN wasmtime_call_future_t* wasmtime_func_call_async(
+    wasmtime_context_t *context,
+    const wasmtime_func_t *func,
+    const wasmtime_val_t *args,
+    size_t nargs,
+    wasmtime_val_t *results,
+    size_t nresults,
+    wasm_trap_t** trap_ret,
+    wasmtime_error_t** error_ret
+);
+
+/**
+ * \brief Instantiates a #wasm_module_t with the items defined in this linker
+ * for an async store.
+ *
+ * This is the same as #wasmtime_linker_instantiate but for stores with async
+ * support enabled, which is required when the linker contains async
+ * functions. Its outcome is written to `instance`, `trap_ret` or `error_ret`
+ * once the returned future has finished, and all of the pointers passed must
+ * stay valid until the future is deleted.
+ */
+WASM_API_EXTERN wasmtime_call_future_t* wasmtime_linker_instantiate_async(
+    const wasmtime_linker_t *linker,
+    wasmtime_context_t *store,
+    const wasmtime_module_t *module,
+    wasmtime_instance_t *instance,
+    wasm_trap_t** trap_ret,
+    wasmtime_error_t** error_ret
+);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif // WASMTIME_ASYNC_H
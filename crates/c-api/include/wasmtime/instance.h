@@ -113,6 +113,9 @@ WASM_API_EXTERN wasmtime_instancetype_t *wasmtime_instance_type(
  * Returns nonzero if the export was found, and `item` is filled in. Otherwise
  * returns 0.
  *
+ * The `name` is UTF-8 and doesn't need to be nul-terminated. A `name` which
+ * isn't valid UTF-8 can't be the name of an export, so 0 is returned for it.
+ *
  * Doesn't take ownership of any arguments but does return ownership of the
  * #wasmtime_extern_t.
  */
@@ -135,7 +138,12 @@ WASM_API_EXTERN bool wasmtime_instance_export_get(
  * \param item where to store the export itself
  *
  * Returns nonzero if the export was found, and `name`, `name_len`, and `item`
- * are filled in. Otherwise returns 0.
+ * are filled in. Otherwise returns 0, which happens when `index` is at least
+ * the number of exports, so exports can be listed alongside their names by
+ * calling this with increasing indices until it returns 0. The exports are in
+ * the order they're defined in the module.
+ *
+ * The returned `name` is UTF-8 and isn't nul-terminated.
  *
  * Doesn't take ownership of any arguments but does return ownership of the
  * #wasmtime_extern_t. The `name` pointer return value is owned by the `store`
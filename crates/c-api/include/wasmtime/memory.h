@@ -16,6 +16,59 @@
 extern "C" {
 #endif
 
+/**
+ * \brief Creates a new memory type from the specified parameters.
+ *
+ * \param min the minimum size of the memory, in WebAssembly pages
+ * \param max_present whether the memory has a maximum size
+ * \param max the maximum size of the memory, if `max_present` is true
+ * \param is_shared whether the memory is shared, as proposed by the threads
+ *        proposal
+ * \param is_64 whether the memory is indexed with 64-bit addresses, as
+ *        proposed by the memory64 proposal
+ *
+ * This is an extension of #wasm_memorytype_new, which can only create 32-bit
+ * memory types which aren't shared. Note that Wasmtime doesn't support memories
+ * whose limits exceed `UINT32_MAX` pages, even 64-bit ones, and `NULL` is
+ * returned if either `min` or `max` does.
+ *
+ * The returned value is owned by the caller and must be deleted with
+ * #wasm_memorytype_delete.
+ */
+WASM_API_EXTERN wasm_memorytype_t *wasmtime_memorytype_new(
+    uint64_t min,
+    bool max_present,
+    uint64_t max,
+    bool is_shared,
+    bool is_64
+);
+
+/**
+ * \brief Returns the minimum size, in WebAssembly pages, of the specified
+ * memory type.
+ */
+WASM_API_EXTERN uint64_t wasmtime_memorytype_minimum(const wasm_memorytype_t *ty);
+
+/**
+ * \brief Returns the maximum size, in WebAssembly pages, of the specified
+ * memory type.
+ *
+ * If the memory type has a maximum size then `true` is returned and `max` is
+ * filled in with the maximum size. Otherwise `false` is returned and `max` is
+ * left untouched.
+ */
+WASM_API_EXTERN bool wasmtime_memorytype_maximum(const wasm_memorytype_t *ty, uint64_t *max);
+
+/**
+ * \brief Returns whether the specified memory type is shared.
+ */
+WASM_API_EXTERN bool wasmtime_memorytype_isshared(const wasm_memorytype_t *ty);
+
+/**
+ * \brief Returns whether the specified memory type is a 64-bit memory.
+ */
+WASM_API_EXTERN bool wasmtime_memorytype_is64(const wasm_memorytype_t *ty);
+
 /**
  * \brief Creates a new WebAssembly linear memory
  *
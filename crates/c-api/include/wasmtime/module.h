@@ -157,6 +157,11 @@ WASM_API_EXTERN wasmtime_error_t* wasmtime_module_serialize(
  * This function is not safe to receive arbitrary user input. See the Rust
  * documentation for more information on what inputs are safe to pass in here
  * (e.g. only that of #wasmtime_module_serialize)
+ *
+ * \return a non-null error if `bytes` weren't produced by
+ * #wasmtime_module_serialize, or were produced by a different version of
+ * wasmtime or with compilation settings incompatible with `engine`. The
+ * error's message describes the mismatch, and `ret` isn't touched.
  */
 WASM_API_EXTERN wasmtime_error_t *wasmtime_module_deserialize(
     wasm_engine_t *engine,
@@ -0,0 +1,306 @@
+//! Support for async host functions and async calls, see `wasmtime/async.h`.
+//!
+//! Async host functions are completed through a `wasmtime_async_continuation_t`
+//! which may be fulfilled from any thread, and calls into wasm produce a
+//! `wasmtime_call_future_t` which the embedder polls until it's finished.
+//! There's no executor here: each poll drives the store's fiber as far as it
+//! can go, and when a host function is pending the embedder is expected to
+//! poll again once it has completed the continuation.
+
+use crate::{
+    bad_utf8, handle_call_error, handle_instantiate, trap_from_panic, wasm_functype_t, wasm_trap_t,
+    wasmtime_caller_t, wasmtime_error_t, wasmtime_linker_t, wasmtime_module_t, wasmtime_val_t,
+    CStoreContextMut, ForeignData,
+};
+use anyhow::anyhow;
+use std::any::Any;
+use std::ffi::c_void;
+use std::future::Future;
+use std::mem::MaybeUninit;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::ptr;
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use wasmtime::{Func, Instance, Trap, Val};
+
+#[no_mangle]
+pub extern "C" fn wasmtime_config_async_support_set(c: &mut crate::wasm_config_t, enable: bool) {
+    c.config.async_support(enable);
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_config_async_stack_size_set(
+    c: &mut crate::wasm_config_t,
+    size: usize,
+) -> bool {
+    c.config.async_stack_size(size).is_ok()
+}
+
+pub type wasmtime_func_async_callback_t = extern "C" fn(
+    *mut c_void,
+    *mut wasmtime_caller_t,
+    *const wasmtime_val_t,
+    usize,
+    usize,
+    Box<wasmtime_async_continuation_t>,
+);
+
+/// The handle given to an async host function, through which it reports its
+/// results once they're available.
+#[repr(C)]
+pub struct wasmtime_async_continuation_t {
+    completion: Arc<Completion>,
+}
+
+/// State shared between a continuation and the future that the host function
+/// call is waiting on.
+#[derive(Default)]
+struct Completion {
+    state: Mutex<CompletionState>,
+}
+
+#[derive(Default)]
+struct CompletionState {
+    result: Option<Result<Vec<Val>, Trap>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves once the continuation of a host function call is completed.
+struct CompletionFuture(Arc<Completion>);
+
+impl Future for CompletionFuture {
+    type Output = Result<Vec<Val>, Trap>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_async_continuation_complete(
+    continuation: Box<wasmtime_async_continuation_t>,
+    results: *const wasmtime_val_t,
+    nresults: usize,
+    trap: Option<Box<wasm_trap_t>>,
+) {
+    let result = match trap {
+        Some(trap) => Err(trap.trap),
+        None => Ok(crate::slice_from_raw_parts(results, nresults)
+            .iter()
+            .map(|v| v.to_val())
+            .collect()),
+    };
+    let waker = {
+        let mut state = continuation.completion.state.lock().unwrap();
+        state.result = Some(result);
+        state.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_linker_define_async_func(
+    linker: &mut wasmtime_linker_t,
+    module: *const u8,
+    module_len: usize,
+    name: *const u8,
+    name_len: usize,
+    ty: &wasm_functype_t,
+    callback: wasmtime_func_async_callback_t,
+    data: *mut c_void,
+    finalizer: Option<extern "C" fn(*mut c_void)>,
+) -> Option<Box<wasmtime_error_t>> {
+    let module = match str::from_utf8(crate::slice_from_raw_parts(module, module_len)) {
+        Ok(s) => s,
+        Err(_) => return bad_utf8(),
+    };
+    let name = match str::from_utf8(crate::slice_from_raw_parts(name, name_len)) {
+        Ok(s) => s,
+        Err(_) => return bad_utf8(),
+    };
+    let foreign = ForeignData { data, finalizer };
+    let ty = ty.ty().ty.clone();
+    let linker = &mut linker.linker;
+
+    // Defining an async function in a linker for an engine without async
+    // support panics, which can't be allowed to unwind into C.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        linker
+            .func_new_async(module, name, ty, move |caller, params, results| {
+                let params = params
+                    .iter()
+                    .cloned()
+                    .map(|p| wasmtime_val_t::from_val(p))
+                    .collect::<Vec<_>>();
+                let completion = Arc::new(Completion::default());
+                let continuation = Box::new(wasmtime_async_continuation_t {
+                    completion: completion.clone(),
+                });
+                let mut caller = wasmtime_caller_t { caller };
+                callback(
+                    foreign.data,
+                    &mut caller,
+                    params.as_ptr(),
+                    params.len(),
+                    results.len(),
+                    continuation,
+                );
+                Box::new(async move {
+                    let out = CompletionFuture(completion).await?;
+                    if out.len() != results.len() {
+                        return Err(Trap::new(
+                            "async host function completed with the wrong number of results",
+                        ));
+                    }
+                    for (slot, val) in results.iter_mut().zip(out) {
+                        *slot = val;
+                    }
+                    Ok(())
+                })
+            })
+            .map(|_| ())
+    }));
+    match result {
+        Ok(result) => crate::handle_result(result, |()| ()),
+        Err(panic) => Some(Box::new(wasmtime_error_t::from(anyhow!(
+            "{}",
+            trap_from_panic(panic)
+        )))),
+    }
+}
+
+/// An in-progress async call or instantiation, which is polled from C with
+/// `wasmtime_call_future_poll`.
+#[repr(C)]
+pub struct wasmtime_call_future_t<'a> {
+    underlying: Pin<Box<dyn Future<Output = ()> + 'a>>,
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_call_future_poll(future: &mut wasmtime_call_future_t<'_>) -> bool {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    future.underlying.as_mut().poll(&mut cx).is_ready()
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_call_future_delete(_future: Box<wasmtime_call_future_t<'_>>) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_func_call_async<'a>(
+    store: CStoreContextMut<'a>,
+    func: &Func,
+    args: *const wasmtime_val_t,
+    nargs: usize,
+    results: *mut MaybeUninit<wasmtime_val_t>,
+    nresults: usize,
+    trap_ret: &'a mut *mut wasm_trap_t,
+    error_ret: &'a mut *mut wasmtime_error_t,
+) -> Box<wasmtime_call_future_t<'a>> {
+    *trap_ret = ptr::null_mut();
+    *error_ret = ptr::null_mut();
+    let func = *func;
+    let params = crate::slice_from_raw_parts(args, nargs)
+        .iter()
+        .map(|i| i.to_val())
+        .collect::<Vec<_>>();
+    let underlying = async move {
+        if nresults != func.ty(&store).results().len() {
+            let err = wasmtime_error_t::from(anyhow!("wrong number of results provided"));
+            *error_ret = Box::into_raw(Box::new(err));
+            return;
+        }
+        match CatchUnwind(Box::pin(func.call_async(store, &params))).await {
+            Ok(Ok(out)) => {
+                let results = crate::slice_from_raw_parts_mut(results, nresults);
+                for (slot, val) in results.iter_mut().zip(out.into_vec().into_iter()) {
+                    crate::initialize(slot, wasmtime_val_t::from_val(val));
+                }
+            }
+            Ok(Err(err)) => {
+                if let Some(err) = handle_call_error(err, trap_ret) {
+                    *error_ret = Box::into_raw(err);
+                }
+            }
+            Err(panic) => {
+                let trap = wasm_trap_t::new(trap_from_panic(panic));
+                *trap_ret = Box::into_raw(Box::new(trap));
+            }
+        }
+    };
+    Box::new(wasmtime_call_future_t {
+        underlying: Box::pin(underlying),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_linker_instantiate_async<'a>(
+    linker: &'a wasmtime_linker_t,
+    store: CStoreContextMut<'a>,
+    module: &'a wasmtime_module_t,
+    instance_ret: &'a mut Instance,
+    trap_ret: &'a mut *mut wasm_trap_t,
+    error_ret: &'a mut *mut wasmtime_error_t,
+) -> Box<wasmtime_call_future_t<'a>> {
+    *trap_ret = ptr::null_mut();
+    *error_ret = ptr::null_mut();
+    let underlying = async move {
+        let instantiate = linker.linker.instantiate_async(store, &module.module);
+        match CatchUnwind(Box::pin(instantiate)).await {
+            Ok(result) => {
+                if let Some(err) = handle_instantiate(result, instance_ret, trap_ret) {
+                    *error_ret = Box::into_raw(err);
+                }
+            }
+            Err(panic) => {
+                let trap = wasm_trap_t::new(trap_from_panic(panic));
+                *trap_ret = Box::into_raw(Box::new(trap));
+            }
+        }
+    };
+    Box::new(wasmtime_call_future_t {
+        underlying: Box::pin(underlying),
+    })
+}
+
+/// Catches panics raised while polling the inner future, since they can't be
+/// allowed to unwind into C.
+struct CatchUnwind<'a, T>(Pin<Box<dyn Future<Output = T> + 'a>>);
+
+impl<T> Future for CatchUnwind<'_, T> {
+    type Output = Result<T, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.0.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(Poll::Ready(value)) => Poll::Ready(Ok(value)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+/// The C embedder polls calls again once it has completed a continuation, so
+/// there's nothing for wakers to do.
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    unsafe fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+
+    unsafe fn noop(_: *const ()) {}
+
+    unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+}
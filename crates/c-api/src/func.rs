@@ -4,6 +4,7 @@ use crate::{
     wasmtime_extern_t, wasmtime_val_t, wasmtime_val_union, CStoreContext, CStoreContextMut,
 };
 use anyhow::anyhow;
+use std::any::Any;
 use std::ffi::c_void;
 use std::mem::MaybeUninit;
 use std::panic::{self, AssertUnwindSafe};
@@ -144,14 +145,7 @@ pub unsafe extern "C" fn wasm_func_call(
             Err(err) => Box::into_raw(Box::new(wasm_trap_t::new(err.into()))),
         },
         Err(panic) => {
-            let trap = if let Some(msg) = panic.downcast_ref::<String>() {
-                Trap::new(msg)
-            } else if let Some(msg) = panic.downcast_ref::<&'static str>() {
-                Trap::new(*msg)
-            } else {
-                Trap::new("rust panic happened")
-            };
-            let trap = Box::new(wasm_trap_t::new(trap));
+            let trap = Box::new(wasm_trap_t::new(trap_from_panic(panic)));
             Box::into_raw(trap)
         }
     }
@@ -184,7 +178,7 @@ pub extern "C" fn wasm_func_as_extern_const(f: &wasm_func_t) -> &wasm_extern_t {
 
 #[repr(C)]
 pub struct wasmtime_caller_t<'a> {
-    caller: Caller<'a, crate::StoreData>,
+    pub(crate) caller: Caller<'a, crate::StoreData>,
 }
 
 #[no_mangle]
@@ -271,24 +265,38 @@ pub unsafe extern "C" fn wasmtime_func_call(
             }
             None
         }
-        Ok(Err(trap)) => match trap.downcast::<Trap>() {
-            Ok(trap) => {
-                *trap_ret = Box::into_raw(Box::new(wasm_trap_t::new(trap)));
-                None
-            }
-            Err(err) => Some(Box::new(wasmtime_error_t::from(err))),
-        },
+        Ok(Err(err)) => handle_call_error(err, trap_ret),
         Err(panic) => {
-            let trap = if let Some(msg) = panic.downcast_ref::<String>() {
-                Trap::new(msg)
-            } else if let Some(msg) = panic.downcast_ref::<&'static str>() {
-                Trap::new(*msg)
-            } else {
-                Trap::new("rust panic happened")
-            };
+            let trap = trap_from_panic(panic);
+            *trap_ret = Box::into_raw(Box::new(wasm_trap_t::new(trap)));
+            None
+        }
+    }
+}
+
+/// Splits the error of a failed call into a trap, written to `trap_ret`, or
+/// an error to return.
+pub(crate) fn handle_call_error(
+    err: anyhow::Error,
+    trap_ret: &mut *mut wasm_trap_t,
+) -> Option<Box<wasmtime_error_t>> {
+    match err.downcast::<Trap>() {
+        Ok(trap) => {
             *trap_ret = Box::into_raw(Box::new(wasm_trap_t::new(trap)));
             None
         }
+        Err(err) => Some(Box::new(wasmtime_error_t::from(err))),
+    }
+}
+
+/// Converts a panic caught while calling a function into a trap for C.
+pub(crate) fn trap_from_panic(panic: Box<dyn Any + Send>) -> Trap {
+    if let Some(msg) = panic.downcast_ref::<String>() {
+        Trap::new(msg)
+    } else if let Some(msg) = panic.downcast_ref::<&'static str>() {
+        Trap::new(*msg)
+    } else {
+        Trap::new("rust panic happened")
     }
 }
 
@@ -107,6 +107,7 @@ pub extern "C" fn wasmtime_trap_code(raw: &wasm_trap_t, code: &mut i32) -> bool
                 TrapCode::BadConversionToInteger => 8,
                 TrapCode::UnreachableCodeReached => 9,
                 TrapCode::Interrupt => 10,
+                TrapCode::OutOfFuel => 11,
                 _ => unreachable!(),
             };
             true
@@ -1,6 +1,7 @@
 use crate::{wasm_externtype_t, wasm_limits_t, CExternType};
 use once_cell::unsync::OnceCell;
-use wasmtime::MemoryType;
+use std::convert::TryFrom;
+use wasmtime::{Limits, MemoryType};
 
 #[repr(transparent)]
 #[derive(Clone)]
@@ -66,6 +67,55 @@ pub extern "C" fn wasm_memorytype_limits(mt: &wasm_memorytype_t) -> &wasm_limits
     })
 }
 
+#[no_mangle]
+pub extern "C" fn wasmtime_memorytype_new(
+    minimum: u64,
+    maximum_specified: bool,
+    maximum: u64,
+    is_shared: bool,
+    is_64: bool,
+) -> Option<Box<wasm_memorytype_t>> {
+    let minimum = u32::try_from(minimum).ok()?;
+    let maximum = if maximum_specified {
+        Some(u32::try_from(maximum).ok()?)
+    } else {
+        None
+    };
+    let limits = Limits::new(minimum, maximum);
+    let ty = if is_64 {
+        MemoryType::new64(limits)
+    } else {
+        MemoryType::new(limits)
+    };
+    Some(Box::new(wasm_memorytype_t::new(ty.with_shared(is_shared))))
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_memorytype_minimum(mt: &wasm_memorytype_t) -> u64 {
+    mt.ty().ty.limits().min().into()
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_memorytype_maximum(mt: &wasm_memorytype_t, out: &mut u64) -> bool {
+    match mt.ty().ty.limits().max() {
+        Some(max) => {
+            *out = max.into();
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_memorytype_isshared(mt: &wasm_memorytype_t) -> bool {
+    mt.ty().ty.is_shared()
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_memorytype_is64(mt: &wasm_memorytype_t) -> bool {
+    mt.ty().ty.is_64()
+}
+
 #[no_mangle]
 pub extern "C" fn wasm_memorytype_as_externtype(ty: &wasm_memorytype_t) -> &wasm_externtype_t {
     &ty.ext
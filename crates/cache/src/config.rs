@@ -390,8 +390,11 @@ impl CacheConfig {
         self.worker().on_cache_get_async(path)
     }
 
-    pub(crate) fn on_cache_update_async(&self, path: impl AsRef<Path>) {
+    pub(crate) fn on_cache_miss(&self) {
         self.state.misses.fetch_add(1, SeqCst);
+    }
+
+    pub(crate) fn on_cache_update_async(&self, path: impl AsRef<Path>) {
         self.worker().on_cache_update_async(path)
     }
 
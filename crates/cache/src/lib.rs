@@ -65,6 +65,9 @@ impl<'config> ModuleCacheEntry<'config> {
             return Ok(cached_val);
         }
         let val_to_cache = compute(state)?;
+        // Count the miss even if the entry can't be written, for example
+        // because another thread is concurrently writing the same entry.
+        inner.cache_config.on_cache_miss();
         if inner.update_data(&hash, &val_to_cache).is_some() {
             let mod_cache_path = inner.root_path.join(&hash);
             inner.cache_config.on_cache_update_async(&mod_cache_path); // call on success
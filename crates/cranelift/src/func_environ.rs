@@ -12,6 +12,7 @@ use cranelift_wasm::{
     self, FuncIndex, FuncTranslationState, GlobalIndex, GlobalVariable, MemoryIndex, TableIndex,
     TargetEnvironment, TypeIndex, WasmError, WasmResult, WasmType,
 };
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::mem;
 use wasmparser::Operator;
@@ -137,6 +138,35 @@ pub struct FuncEnvironment<'module_environment> {
     vminterrupts_ptr: cranelift_frontend::Variable,
 
     fuel_consumed: i64,
+
+    /// A function-local variable holding the value of `fuel_var` when the
+    /// fuel consumed by this function was last attributed to it, used for
+    /// per-function fuel accounting.
+    fuel_base_var: cranelift_frontend::Variable,
+
+    /// The index of the function being translated, as set by
+    /// `set_func_index`.
+    func_index: Option<FuncIndex>,
+
+    /// Whether the function being translated can't change the size, and so
+    /// the base pointer, of any table while it runs, as determined by
+    /// `analyze_table_resizing`.
+    fixed_tables: bool,
+
+    /// The builtin functions which the function being translated calls.
+    pub(crate) builtins_used: BTreeSet<BuiltinFunctionIndex>,
+
+    /// The sorted indices, among the operators of the function being
+    /// translated, of the yieldpoints set by `analyze_yieldpoints`. If this is
+    /// `None` then checks are placed at loop headers instead.
+    yieldpoints: Option<Vec<usize>>,
+
+    /// The index of the next operator to be translated.
+    operator_index: usize,
+
+    /// Set when a `loop` operator is a yieldpoint, so that the check is
+    /// emitted in its header.
+    yield_at_loop_header: bool,
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
@@ -169,7 +199,70 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             // Start with at least one fuel being consumed because even empty
             // functions should consume at least some fuel.
             fuel_consumed: 1,
+            fuel_base_var: Variable::new(0),
+            func_index: None,
+
+            fixed_tables: false,
+            builtins_used: BTreeSet::new(),
+            yieldpoints: None,
+            operator_index: 0,
+            yield_at_loop_header: false,
+        }
+    }
+
+    /// Scans `body` for anything which could resize a table while it runs,
+    /// before it's translated.
+    ///
+    /// Tables only change size through `table.grow`, or through other code
+    /// called by this function, which includes the host. If the function
+    /// neither grows a table nor calls anything, the bounds and base pointers
+    /// of its tables are loaded as read-only values, which allows Cranelift
+    /// to share them between accesses and hoist them out of loops. Anything
+    /// which isn't obviously fine, including a body which fails to parse,
+    /// keeps them reloaded at each access.
+    pub fn analyze_table_resizing(&mut self, body: &wasmparser::FunctionBody<'_>) {
+        self.fixed_tables = !may_resize_tables(body);
+    }
+
+    /// Sets the index of the function being translated, which the fuel it
+    /// consumes is attributed to when per-function fuel accounting is enabled.
+    pub fn set_func_index(&mut self, index: FuncIndex) {
+        self.func_index = Some(index);
+    }
+
+    /// Places interrupt and fuel checks at the module byte `offsets` within
+    /// `body`, and no longer at every loop header, as requested by a module's
+    /// `wasmtime.yieldpoints` section.
+    ///
+    /// Returns an error if any offset isn't the start of an operator.
+    pub fn analyze_yieldpoints(
+        &mut self,
+        body: &wasmparser::FunctionBody<'_>,
+        offsets: &[u32],
+    ) -> WasmResult<()> {
+        let mut reader = body.get_operators_reader()?;
+        let mut operator_offsets = Vec::new();
+        while !reader.eof() {
+            let (_, offset) = reader.read_with_offset()?;
+            operator_offsets.push(offset);
+        }
+        let mut yieldpoints = Vec::with_capacity(offsets.len());
+        for &offset in offsets {
+            match operator_offsets.binary_search(&(offset as usize)) {
+                Ok(index) => yieldpoints.push(index),
+                Err(_) => {
+                    return Err(WasmError::InvalidWebAssembly {
+                        message: format!(
+                            "yieldpoint at offset {:#x} isn't on an instruction boundary",
+                            offset
+                        ),
+                        offset: offset as usize,
+                    })
+                }
+            }
         }
+        self.yieldpoints = Some(yieldpoints);
+        Ok(())
     }
 
     fn pointer_type(&self) -> ir::Type {
@@ -256,6 +349,8 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         pos: &mut FuncCursor<'_>,
         callee_func_idx: BuiltinFunctionIndex,
     ) -> (ir::Value, ir::Value) {
+        self.builtins_used.insert(callee_func_idx);
+
         // We use an indirect call so that we don't have to patch the code at runtime.
         let pointer_type = self.pointer_type();
         let vmctx = self.vmctx(&mut pos.func);
@@ -310,6 +405,11 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             let offset = i32::try_from(self.offsets.vmctx_vmglobal_definition(def_index)).unwrap();
             (vmctx, offset)
         } else {
+            // The definition of an imported global is resolved once at
+            // instantiation and stored in this instance's `VMGlobalImport`, so
+            // accessing it only takes one extra load of that pointer. The
+            // pointer never changes, so the load is `readonly` which lets it
+            // be deduplicated and hoisted out of loops.
             let from_offset = self.offsets.vmctx_vmglobal_import_from(index);
             let global = func.create_global_value(ir::GlobalValueData::Load {
                 base: vmctx,
@@ -343,6 +443,9 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         // is then periodically flushed to the Store-defined location in
         // `VMInterrupts` later.
         builder.declare_var(self.fuel_var, ir::types::I64);
+        if self.tunables.per_function_fuel_accounting {
+            builder.declare_var(self.fuel_base_var, ir::types::I64);
+        }
         self.fuel_load_into_var(builder);
         self.fuel_check(builder);
     }
@@ -486,6 +589,9 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             .ins()
             .load(ir::types::I64, ir::MemFlags::trusted(), addr, offset);
         builder.def_var(self.fuel_var, fuel);
+        if self.tunables.per_function_fuel_accounting {
+            builder.def_var(self.fuel_base_var, fuel);
+        }
     }
 
     /// Stores the fuel consumption value from `self.fuel_var` into
@@ -496,6 +602,37 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         builder
             .ins()
             .store(ir::MemFlags::trusted(), fuel_consumed, addr, offset);
+        if self.tunables.per_function_fuel_accounting {
+            self.fuel_attribute(builder);
+        }
+    }
+
+    /// Reports the fuel consumed since `self.fuel_base_var` was last defined
+    /// to the store as consumed by this function.
+    fn fuel_attribute(&mut self, builder: &mut FunctionBuilder<'_>) {
+        let fuel = builder.use_var(self.fuel_var);
+        let base = builder.use_var(self.fuel_base_var);
+        let consumed = builder.ins().isub(fuel, base);
+        builder.def_var(self.fuel_base_var, fuel);
+
+        let func_index = self
+            .func_index
+            .expect("function index must be set for per-function fuel accounting");
+        let func_index = builder
+            .ins()
+            .iconst(ir::types::I32, i64::from(func_index.as_u32()));
+        let fuel_attribute_sig = self
+            .builtin_function_signatures
+            .fuel_attribute(builder.func);
+        let (vmctx, fuel_attribute) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::fuel_attribute(),
+        );
+        builder.ins().call_indirect(
+            fuel_attribute_sig,
+            fuel_attribute,
+            &[vmctx, func_index, consumed],
+        );
     }
 
     /// Returns the `(address, offset)` of the fuel consumption within
@@ -510,6 +647,46 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         )
     }
 
+    /// Emits the checks which let long-running code be stopped: the interrupt
+    /// flag, the amount of fuel remaining and the epoch deadline, whichever
+    /// are enabled.
+    fn yield_check(&mut self, builder: &mut FunctionBuilder) {
+        // If enabled check the interrupt flag to prevent long or infinite
+        // loops.
+        //
+        // For more information about this see comments in
+        // `crates/environ/src/cranelift.rs`
+        if self.tunables.interruptable {
+            let pointer_type = self.pointer_type();
+            let interrupt_ptr = builder.use_var(self.vminterrupts_ptr);
+            let interrupt = builder.ins().load(
+                pointer_type,
+                ir::MemFlags::trusted(),
+                interrupt_ptr,
+                i32::from(self.offsets.vminterrupts_stack_limit()),
+            );
+            // Note that the cast to `isize` happens first to allow sign-extension,
+            // if necessary, to `i64`.
+            let interrupted_sentinel = builder
+                .ins()
+                .iconst(pointer_type, INTERRUPTED as isize as i64);
+            let cmp = builder
+                .ins()
+                .icmp(IntCC::Equal, interrupt, interrupted_sentinel);
+            builder.ins().trapnz(cmp, ir::TrapCode::Interrupt);
+        }
+
+        // Additionally if enabled check how much fuel we have remaining to see
+        // if we've run out by this point.
+        if self.tunables.consume_fuel {
+            self.fuel_check(builder);
+        }
+
+        if self.tunables.epoch_interruption {
+            self.epoch_check(builder);
+        }
+    }
+
     /// Checks the amount of remaining, and if we've run out of fuel we call
     /// the out-of-fuel function.
     fn fuel_check(&mut self, builder: &mut FunctionBuilder) {
@@ -555,6 +732,80 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
 
         builder.switch_to_block(continuation_block);
     }
+
+    /// Checks whether the engine's epoch has reached the store's deadline, and
+    /// if so calls the new-epoch function.
+    fn epoch_check(&mut self, builder: &mut FunctionBuilder) {
+        let new_epoch_block = builder.create_block();
+        let continuation_block = builder.create_block();
+
+        // Both the epoch and the deadline are reloaded at every check. The
+        // epoch is changed by other threads and the deadline by the store
+        // whenever control leaves this function, and a load here is no more
+        // expensive than the interrupt flag check.
+        let pointer_type = self.pointer_type();
+        let interrupts_ptr = builder.use_var(self.vminterrupts_ptr);
+        let epoch_ptr = builder.ins().load(
+            pointer_type,
+            ir::MemFlags::trusted(),
+            interrupts_ptr,
+            i32::from(self.offsets.vminterrupts_epoch_ptr()),
+        );
+        let epoch = builder
+            .ins()
+            .load(ir::types::I64, ir::MemFlags::trusted(), epoch_ptr, 0);
+        let deadline = builder.ins().load(
+            ir::types::I64,
+            ir::MemFlags::trusted(),
+            interrupts_ptr,
+            i32::from(self.offsets.vminterrupts_epoch_deadline()),
+        );
+        let cmp = builder
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, epoch, deadline);
+        builder.ins().brnz(cmp, new_epoch_block, &[]);
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(new_epoch_block);
+
+        // The new-epoch function either raises a trap or moves the deadline
+        // into the future, in which case execution continues. Like the
+        // out-of-gas intrinsic it may also alter how much fuel is left, for
+        // example when wasm is being cancelled.
+        builder.switch_to_block(new_epoch_block);
+        if self.tunables.consume_fuel {
+            self.fuel_save_from_var(builder);
+        }
+        let new_epoch_sig = self.builtin_function_signatures.new_epoch(builder.func);
+        let (vmctx, new_epoch) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::new_epoch(),
+        );
+        builder
+            .ins()
+            .call_indirect(new_epoch_sig, new_epoch, &[vmctx]);
+        if self.tunables.consume_fuel {
+            self.fuel_load_into_var(builder);
+        }
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(continuation_block);
+
+        builder.switch_to_block(continuation_block);
+    }
+
+    /// Computes the address of the element at `index` of `table`, trapping if
+    /// it's out of bounds.
+    ///
+    /// Every access to a table's elements goes through this, so that their
+    /// bounds checks all compare against the table's `bound_gv`, which
+    /// Cranelift loads once for the whole function when the tables are fixed.
+    fn table_entry_addr<'f>(
+        &self,
+        ins: impl InstBuilder<'f>,
+        table: ir::Table,
+        index: ir::Value,
+    ) -> ir::Value {
+        ins.table_addr(self.pointer_type(), table, index, 0)
+    }
 }
 
 impl<'module_environment> TargetEnvironment for FuncEnvironment<'module_environment> {
@@ -574,9 +825,72 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         index >= 2
     }
 
+    fn elide_guarded_bounds_checks(&self) -> bool {
+        self.tunables.elide_guarded_bounds_checks
+    }
+
+    fn is_memory_access_hooked(&self, index: MemoryIndex) -> bool {
+        self.tunables.memory_access_hooks && !self.module.memory_plans[index].memory.memory64
+    }
+
+    fn translate_hooked_load(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        index: MemoryIndex,
+        addr: ir::Value,
+        offset: u32,
+        size: u32,
+    ) -> WasmResult<ir::Value> {
+        let func_sig = self
+            .builtin_function_signatures
+            .memory_access_load(builder.func);
+        let memory_index = builder.ins().iconst(I32, index.index() as i64);
+        let address = builder.ins().uextend(I64, addr);
+        let address = builder.ins().iadd_imm(address, i64::from(offset));
+        let size = builder.ins().iconst(I32, i64::from(size));
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::memory_access_load(),
+        );
+        let call_inst =
+            builder
+                .ins()
+                .call_indirect(func_sig, func_addr, &[vmctx, memory_index, address, size]);
+        Ok(*builder.func.dfg.inst_results(call_inst).first().unwrap())
+    }
+
+    fn translate_hooked_store(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        index: MemoryIndex,
+        addr: ir::Value,
+        offset: u32,
+        size: u32,
+        value: ir::Value,
+    ) -> WasmResult<()> {
+        let func_sig = self
+            .builtin_function_signatures
+            .memory_access_store(builder.func);
+        let memory_index = builder.ins().iconst(I32, index.index() as i64);
+        let address = builder.ins().uextend(I64, addr);
+        let address = builder.ins().iadd_imm(address, i64::from(offset));
+        let size = builder.ins().iconst(I32, i64::from(size));
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::memory_access_store(),
+        );
+        builder.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, memory_index, address, size, value],
+        );
+        Ok(())
+    }
+
     fn after_locals(&mut self, num_locals: usize) {
         self.vminterrupts_ptr = Variable::new(num_locals);
         self.fuel_var = Variable::new(num_locals + 1);
+        self.fuel_base_var = Variable::new(num_locals + 2);
     }
 
     fn make_table(&mut self, func: &mut ir::Function, index: TableIndex) -> WasmResult<ir::Table> {
@@ -612,13 +926,13 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
             base: ptr,
             offset: Offset32::new(base_offset),
             global_type: pointer_type,
-            readonly: false,
+            readonly: self.fixed_tables,
         });
         let bound_gv = func.create_global_value(ir::GlobalValueData::Load {
             base: ptr,
             offset: Offset32::new(current_elements_offset),
             global_type: self.offsets.type_of_vmtable_definition_current_elements(),
-            readonly: false,
+            readonly: self.fixed_tables,
         });
 
         let element_size = u64::from(
@@ -686,7 +1000,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         match plan.table.wasm_ty {
             WasmType::FuncRef => match plan.style {
                 TableStyle::CallerChecksSignature => {
-                    let table_entry_addr = builder.ins().table_addr(pointer_type, table, index, 0);
+                    let table_entry_addr = self.table_entry_addr(builder.ins(), table, index);
                     Ok(builder.ins().load(
                         pointer_type,
                         ir::MemFlags::trusted(),
@@ -730,7 +1044,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
                 builder.insert_block_after(continue_block, gc_block);
 
                 // Load the table element.
-                let elem_addr = builder.ins().table_addr(pointer_type, table, index, 0);
+                let elem_addr = self.table_entry_addr(builder.ins(), table, index);
                 let elem =
                     builder
                         .ins()
@@ -832,7 +1146,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         match plan.table.wasm_ty {
             WasmType::FuncRef => match plan.style {
                 TableStyle::CallerChecksSignature => {
-                    let table_entry_addr = builder.ins().table_addr(pointer_type, table, index, 0);
+                    let table_entry_addr = self.table_entry_addr(builder.ins(), table, index);
                     builder
                         .ins()
                         .store(ir::MemFlags::trusted(), value, table_entry_addr, 0);
@@ -885,7 +1199,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
                 // bounds checks. This is the first thing we do, because we
                 // don't want to modify any ref counts if this `table.set` is
                 // going to trap.
-                let table_entry_addr = builder.ins().table_addr(pointer_type, table, index, 0);
+                let table_entry_addr = self.table_entry_addr(builder.ins(), table, index);
 
                 // If value is not null, increment `value`'s ref count.
                 //
@@ -1142,8 +1456,9 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
             }
         };
 
-        // If we have a declared maximum, we can make this a "static" heap, which is
-        // allocated up front and never moved.
+        // If we have a declared maximum, or the module can never grow this
+        // memory, we can make this a "static" heap, which is allocated up front
+        // and never moved.
         let (offset_guard_size, heap_style, readonly_base) = match self.module.memory_plans[index] {
             MemoryPlan {
                 style: MemoryStyle::Dynamic,
@@ -1256,7 +1571,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     ) -> WasmResult<ir::Inst> {
         let pointer_type = self.pointer_type();
 
-        let table_entry_addr = pos.ins().table_addr(pointer_type, table, callee, 0);
+        let table_entry_addr = self.table_entry_addr(pos.ins(), table, callee);
 
         // Dereference the table entry to get the pointer to the
         // `VMCallerCheckedAnyfunc`.
@@ -1664,37 +1979,11 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     }
 
     fn translate_loop_header(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
-        // If enabled check the interrupt flag to prevent long or infinite
-        // loops.
-        //
-        // For more information about this see comments in
-        // `crates/environ/src/cranelift.rs`
-        if self.tunables.interruptable {
-            let pointer_type = self.pointer_type();
-            let interrupt_ptr = builder.use_var(self.vminterrupts_ptr);
-            let interrupt = builder.ins().load(
-                pointer_type,
-                ir::MemFlags::trusted(),
-                interrupt_ptr,
-                i32::from(self.offsets.vminterrupts_stack_limit()),
-            );
-            // Note that the cast to `isize` happens first to allow sign-extension,
-            // if necessary, to `i64`.
-            let interrupted_sentinel = builder
-                .ins()
-                .iconst(pointer_type, INTERRUPTED as isize as i64);
-            let cmp = builder
-                .ins()
-                .icmp(IntCC::Equal, interrupt, interrupted_sentinel);
-            builder.ins().trapnz(cmp, ir::TrapCode::Interrupt);
+        // Unless the module placed its checks itself with yieldpoints, check
+        // at every loop header so that long or infinite loops can be stopped.
+        if self.yieldpoints.is_none() || mem::take(&mut self.yield_at_loop_header) {
+            self.yield_check(builder);
         }
-
-        // Additionally if enabled check how much fuel we have remaining to see
-        // if we've run out by this point.
-        if self.tunables.consume_fuel {
-            self.fuel_check(builder);
-        }
-
         Ok(())
     }
 
@@ -1704,6 +1993,16 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         builder: &mut FunctionBuilder,
         state: &FuncTranslationState,
     ) -> WasmResult<()> {
+        if let Some(yieldpoints) = &self.yieldpoints {
+            let is_yieldpoint = yieldpoints.binary_search(&self.operator_index).is_ok();
+            self.operator_index += 1;
+            if is_yieldpoint && state.reachable() {
+                match op {
+                    Operator::Loop { .. } => self.yield_at_loop_header = true,
+                    _ => self.yield_check(builder),
+                }
+            }
+        }
         if self.tunables.consume_fuel {
             self.fuel_before_op(op, builder, state.reachable());
         }
@@ -1729,13 +2028,21 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     ) -> WasmResult<()> {
         // If the `vminterrupts_ptr` variable will get used then we initialize
         // it here.
-        if self.tunables.consume_fuel || self.tunables.interruptable {
+        if self.tunables.consume_fuel
+            || self.tunables.interruptable
+            || self.tunables.epoch_interruption
+        {
             self.declare_vminterrupts_ptr(builder);
         }
         // Additionally we initialize `fuel_var` if it will get used.
         if self.tunables.consume_fuel {
             self.fuel_function_entry(builder);
         }
+        // Function entry is checked against the epoch deadline like loop
+        // headers, so that recursion without loops is also interrupted.
+        if self.tunables.epoch_interruption {
+            self.epoch_check(builder);
+        }
         Ok(())
     }
 
@@ -1750,3 +2057,285 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         Ok(())
     }
 }
+
+/// Returns whether running `body` could change the size of a table, see
+/// `FuncEnvironment::analyze_table_resizing`.
+fn may_resize_tables(body: &wasmparser::FunctionBody<'_>) -> bool {
+    let mut reader = match body.get_operators_reader() {
+        Ok(reader) => reader,
+        Err(_) => return true,
+    };
+    while !reader.eof() {
+        match reader.read() {
+            Ok(Operator::Call { .. })
+            | Ok(Operator::CallIndirect { .. })
+            | Ok(Operator::ReturnCall { .. })
+            | Ok(Operator::ReturnCallIndirect { .. })
+            | Ok(Operator::TableGrow { .. })
+            | Err(_) => return true,
+            Ok(_) => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use cranelift_wasm::FuncTranslator;
+    use wasmparser::WasmFeatures;
+    use wasmtime_environ::ModuleEnvironment;
+
+    /// Translates the first defined function of `wat` to IR, the way
+    /// `compile_function` does.
+    fn translate(wat: &str, tunables: &Tunables) -> Function {
+        let isa = cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+        let wasm = wat::parse_str(wat).unwrap();
+        let (_, mut translations, types) =
+            ModuleEnvironment::new(isa.frontend_config(), tunables, &WasmFeatures::default())
+                .translate(&wasm)
+                .unwrap();
+        let translation = &mut translations[0];
+        let (index, input) = translation.function_body_inputs.iter_mut().next().unwrap();
+
+        let mut func = Function::new();
+        func.signature = crate::func_signature(
+            &*isa,
+            &translation.module,
+            &types,
+            translation.module.func_index(index),
+        );
+        let mut env = FuncEnvironment::new(&*isa, &translation.module, &types, tunables);
+        env.analyze_table_resizing(&input.body);
+        FuncTranslator::new()
+            .translate_body(
+                &mut input.validator,
+                input.body.clone(),
+                &mut func,
+                &mut env,
+            )
+            .unwrap();
+        func
+    }
+
+    /// Translates the first defined function of `wat` to IR, with tunables
+    /// which would make every memory dynamic, and returns its heaps' styles.
+    fn heap_styles(wat: &str) -> Vec<ir::HeapStyle> {
+        let tunables = Tunables {
+            static_memory_bound: 0,
+            ..Tunables::default()
+        };
+        let func = translate(wat, &tunables);
+        func.heaps.values().map(|heap| heap.style.clone()).collect()
+    }
+
+    /// Returns whether the bound of each table used by the first defined
+    /// function of `wat` is loaded as readonly, which lets it be hoisted out of
+    /// loops.
+    fn readonly_table_bounds(wat: &str) -> Vec<bool> {
+        let func = translate(wat, &Tunables::default());
+        func.tables
+            .values()
+            .map(|table| match func.global_values[table.bound_gv] {
+                ir::GlobalValueData::Load { readonly, .. } => readonly,
+                ref gv => panic!("unexpected table bound: {}", gv),
+            })
+            .collect()
+    }
+
+    fn is_dynamic(style: &ir::HeapStyle) -> bool {
+        matches!(style, ir::HeapStyle::Dynamic { .. })
+    }
+
+    #[test]
+    fn non_growing_private_memory_is_static() {
+        let styles = heap_styles(
+            r#"
+            (module
+                (memory 2 10)
+                (func (param i32) (result i32)
+                    local.get 0
+                    i32.load
+                    memory.size
+                    i32.add))
+            "#,
+        );
+        assert_eq!(styles.len(), 1);
+        match &styles[0] {
+            ir::HeapStyle::Static { bound } => {
+                assert_eq!(Into::<u64>::into(*bound), 2 * u64::from(WASM_PAGE_SIZE))
+            }
+            ir::HeapStyle::Dynamic { .. } => panic!("expected a static heap"),
+        }
+    }
+
+    #[test]
+    fn growing_memory_is_dynamic() {
+        // The `memory.grow` disqualifies the memory even though it's in a
+        // different function from the access.
+        let styles = heap_styles(
+            r#"
+            (module
+                (memory 2 10)
+                (func (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+                (func (result i32)
+                    i32.const 1
+                    memory.grow))
+            "#,
+        );
+        assert!(styles.iter().all(is_dynamic));
+    }
+
+    #[test]
+    fn exported_memory_is_dynamic() {
+        let styles = heap_styles(
+            r#"
+            (module
+                (memory (export "memory") 2 10)
+                (func (param i32) (result i32)
+                    local.get 0
+                    i32.load))
+            "#,
+        );
+        assert!(styles.iter().all(is_dynamic));
+    }
+
+    #[test]
+    fn imported_memory_is_dynamic() {
+        let styles = heap_styles(
+            r#"
+            (module
+                (import "" "memory" (memory 2 10))
+                (func (param i32) (result i32)
+                    local.get 0
+                    i32.load))
+            "#,
+        );
+        assert!(styles.iter().all(is_dynamic));
+    }
+
+    #[test]
+    fn table_scan_bound_is_readonly() {
+        let bounds = readonly_table_bounds(
+            r#"
+            (module
+                (table 10 funcref)
+                (func (result i32)
+                    (local i32 i32)
+                    (block
+                        (loop
+                            (br_if 1 (i32.ge_u (local.get 0) (table.size 0)))
+                            (if (i32.eqz (ref.is_null (table.get 0 (local.get 0))))
+                                (local.set 1 (i32.add (local.get 1) (i32.const 1))))
+                            (local.set 0 (i32.add (local.get 0) (i32.const 1)))
+                            (br 0)))
+                    local.get 1))
+            "#,
+        );
+        assert_eq!(bounds, [true]);
+    }
+
+    #[test]
+    fn table_grow_bound_is_not_readonly() {
+        let bounds = readonly_table_bounds(
+            r#"
+            (module
+                (table 1 funcref)
+                (func (param i32) (result i32)
+                    (drop (table.grow 0 (ref.null func) (i32.const 1)))
+                    (ref.is_null (table.get 0 (local.get 0)))))
+            "#,
+        );
+        assert_eq!(bounds, [false]);
+    }
+
+    #[test]
+    fn calls_make_table_bounds_not_readonly() {
+        // The callee could grow the table, whether it's called directly or
+        // indirectly.
+        let bounds = readonly_table_bounds(
+            r#"
+            (module
+                (import "" "grow" (func $grow))
+                (table 1 funcref)
+                (func (param i32) (result i32)
+                    call $grow
+                    (ref.is_null (table.get 0 (local.get 0)))))
+            "#,
+        );
+        assert_eq!(bounds, [false]);
+
+        let bounds = readonly_table_bounds(
+            r#"
+            (module
+                (type $t (func))
+                (table 1 funcref)
+                (func (param i32) (result i32)
+                    (call_indirect (type $t) (i32.const 0))
+                    (ref.is_null (table.get 0 (local.get 0)))))
+            "#,
+        );
+        assert_eq!(bounds, [false]);
+    }
+
+    /// Returns the blocks of `func` which contain a call, in layout order.
+    fn blocks_with_calls(func: &Function) -> Vec<ir::Block> {
+        func.layout
+            .blocks()
+            .filter(|block| {
+                func.layout
+                    .block_insts(*block)
+                    .any(|inst| func.dfg[inst].opcode().is_call())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn funcref_table_get_is_inline() {
+        let func = translate(
+            r#"
+            (module
+                (table 1 funcref)
+                (func (param i32) (result funcref)
+                    (table.get 0 (local.get 0))))
+            "#,
+            &Tunables::default(),
+        );
+        assert_eq!(blocks_with_calls(&func), []);
+    }
+
+    #[test]
+    fn externref_table_get_of_null_is_inline() {
+        let func = translate(
+            r#"
+            (module
+                (table 1 externref)
+                (func (param i32) (result externref)
+                    (table.get 0 (local.get 0))))
+            "#,
+            &Tunables::default(),
+        );
+
+        // The only call is the one which makes room in a full activations
+        // table, which a null element branches around before anything else.
+        let calls = blocks_with_calls(&func);
+        assert_eq!(calls.len(), 1);
+        let entry = func.layout.entry_block().unwrap();
+        let null_check = func
+            .layout
+            .block_insts(entry)
+            .find(|inst| func.dfg[*inst].opcode() == ir::Opcode::Brnz)
+            .unwrap();
+        let condition = func.dfg.inst_args(null_check)[0];
+        let is_null = func.dfg.value_def(condition).unwrap_inst();
+        assert_eq!(func.dfg[is_null].opcode(), ir::Opcode::IsNull);
+        let null_dest = func.dfg[null_check].branch_destination().unwrap();
+        assert!(!calls.contains(&entry));
+        assert!(!calls.contains(&null_dest));
+    }
+}
@@ -113,6 +113,12 @@ struct RelocSink {
 
     /// Relocations recorded for the function.
     func_relocs: Vec<Relocation>,
+
+    /// Return addresses of the calls recorded for the function.
+    call_sites: Vec<binemit::CodeOffset>,
+
+    /// Return addresses of the direct calls recorded for the function.
+    direct_call_sites: Vec<binemit::CodeOffset>,
 }
 
 impl binemit::RelocSink for RelocSink {
@@ -158,6 +164,20 @@ impl binemit::RelocSink for RelocSink {
             addend: 0,
         });
     }
+
+    fn add_call_site(
+        &mut self,
+        opcode: ir::Opcode,
+        ret_addr: binemit::CodeOffset,
+        _srcloc: ir::SourceLoc,
+    ) {
+        self.call_sites.push(ret_addr);
+        // Imported functions are called indirectly, so wasm functions called
+        // directly are always defined in the same module and instance.
+        if opcode == ir::Opcode::Call {
+            self.direct_call_sites.push(ret_addr);
+        }
+    }
 }
 
 impl RelocSink {
@@ -166,6 +186,8 @@ impl RelocSink {
         Self {
             func_index,
             func_relocs: Vec::new(),
+            call_sites: Vec::new(),
+            direct_call_sites: Vec::new(),
         }
     }
 }
@@ -362,6 +384,12 @@ impl Compiler for Cranelift {
         }
 
         let mut func_env = FuncEnvironment::new(isa, module, types, tunables);
+        func_env.set_func_index(func_index);
+        func_env.analyze_table_resizing(&input.body);
+        if let Some(yieldpoints) = &translation.yieldpoints {
+            let offsets = yieldpoints.get(&func_index).map_or(&[][..], |o| &o[..]);
+            func_env.analyze_yieldpoints(&input.body, offsets)?;
+        }
 
         // We use these as constant offsets below in
         // `stack_limit_from_arguments`, so assert their values here. This
@@ -450,6 +478,9 @@ impl Compiler for Cranelift {
             traps: trap_sink.traps,
             unwind_info,
             stack_maps: stack_map_sink.finish(),
+            call_sites: reloc_sink.call_sites,
+            direct_call_sites: reloc_sink.direct_call_sites,
+            builtins: func_env.builtins_used.into_iter().collect(),
         })
     }
 }
@@ -532,3 +563,83 @@ pub fn func_signature(
     push_types(isa, &mut sig, types, module.functions[index]);
     return sig;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use std::mem;
+    use wasmparser::WasmFeatures;
+    use wasmtime_environ::ModuleEnvironment;
+
+    #[test]
+    fn compressed_address_maps_round_trip() {
+        let isa = cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+        let tunables = Tunables::default();
+
+        let mut wat = String::from("(module (memory 1)");
+        for i in 0..5000 {
+            wat.push_str(&format!(
+                "(func (param i32) (result i32)
+                    local.get 0
+                    i32.load offset={}
+                    local.get 0
+                    i32.const {}
+                    i32.add
+                    i32.mul)",
+                i % 64,
+                i
+            ));
+        }
+        wat.push_str(")");
+        let wasm = wat::parse_str(&wat).unwrap();
+        let (_, mut translations, types) =
+            ModuleEnvironment::new(isa.frontend_config(), &tunables, &WasmFeatures::default())
+                .translate(&wasm)
+                .unwrap();
+        let mut translation = translations.pop().unwrap();
+        let inputs = mem::take(&mut translation.function_body_inputs);
+
+        let compiler = Cranelift::default();
+        let mut uncompressed = 0;
+        let mut compressed = 0;
+        for (index, input) in inputs {
+            let func = compiler
+                .compile_function(&translation, index, input, &*isa, &tunables, &types)
+                .unwrap();
+            let map = &func.address_map;
+            let compact = map.compress(true);
+            assert_eq!(
+                compact.instructions.iter().collect::<Vec<_>>(),
+                &map.instructions[..]
+            );
+            assert_eq!(compact.start_srcloc, map.start_srcloc);
+            assert_eq!(compact.end_srcloc, map.end_srcloc);
+            assert_eq!(compact.body_len, map.body_len);
+            for (i, inst) in map.instructions.iter().enumerate() {
+                // Lookups find the last of several instructions at an offset.
+                let next = map.instructions.get(i + 1);
+                if next.map_or(false, |next| next.code_offset == inst.code_offset) {
+                    continue;
+                }
+                assert_eq!(
+                    compact.instructions.lookup(inst.code_offset),
+                    Some(inst.srcloc)
+                );
+            }
+
+            uncompressed += map.instructions.len() * mem::size_of::<InstructionAddressMap>();
+            compressed += compact.instructions.encoded_len();
+        }
+
+        println!(
+            "instruction address maps: {} bytes uncompressed, {} bytes compressed ({:.1}%)",
+            uncompressed,
+            compressed,
+            100.0 * compressed as f64 / uncompressed as f64
+        );
+        assert!(compressed * 2 < uncompressed);
+    }
+}
@@ -40,6 +40,81 @@ pub fn create_gdbjit_image(
     Ok(bytes)
 }
 
+/// Checks that all code addresses in the DWARF of an image created by
+/// `create_gdbjit_image` lie within `code_region`: the ranges of functions
+/// and units, and the addresses of line program rows.
+///
+/// Addresses outside of the code make debuggers resolve breakpoints and
+/// frames to the wrong place, or not at all, so this is used to validate
+/// images in debug builds.
+pub fn check_gdbjit_image(image: &[u8], code_region: (*const u8, usize)) -> Result<(), Error> {
+    use gimli::{EndianSlice, RunTimeEndian};
+    use object::read::{File, Object, ObjectSection};
+
+    let obj = File::parse(image)?;
+    let endian = if obj.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let dwarf = gimli::Dwarf::load(|id| -> Result<_, Error> {
+        let data = match obj.section_by_name(id.name()) {
+            Some(section) => section.data()?,
+            None => &[],
+        };
+        Ok(EndianSlice::new(data, endian))
+    })?;
+
+    let start = code_region.0 as u64;
+    let end = start + code_region.1 as u64;
+    let check_range = |range: gimli::Range| -> Result<(), Error> {
+        ensure!(
+            range.begin >= start && range.end <= end,
+            "DWARF range {:#x}..{:#x} lies outside of the code at {:#x}..{:#x}",
+            range.begin,
+            range.end,
+            start,
+            end
+        );
+        Ok(())
+    };
+
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+            let mut ranges = dwarf.die_ranges(&unit, entry)?;
+            while let Some(range) = ranges.next()? {
+                check_range(range)?;
+            }
+        }
+        let mut ranges = dwarf.unit_ranges(&unit)?;
+        while let Some(range) = ranges.next()? {
+            check_range(range)?;
+        }
+
+        if let Some(program) = unit.line_program.clone() {
+            let mut rows = program.rows();
+            while let Some((_, row)) = rows.next_row()? {
+                // The end of a sequence is the address just past it.
+                ensure!(
+                    row.address() >= start && row.address() <= end,
+                    "DWARF line row address {:#x} lies outside of the code at {:#x}..{:#x}",
+                    row.address(),
+                    start,
+                    end
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 fn relocate_dwarf_sections(
     bytes: &[u8],
     defined_funcs_offset: usize,
@@ -7,6 +7,7 @@ use gimli::{
     DebuggingInformationEntry, LineEncoding, Unit,
 };
 use more_asserts::assert_le;
+use std::collections::BTreeMap;
 use wasmtime_environ::entity::EntityRef;
 use wasmtime_environ::wasm::DefinedFuncIndex;
 
@@ -28,16 +29,12 @@ enum SavedLineProgramRow {
     EndOfSequence(u64),
 }
 
-#[derive(Debug)]
-struct FuncRows {
-    index: DefinedFuncIndex,
-    sorted_rows: Vec<(u64, SavedLineProgramRow)>,
-}
-
 #[derive(Debug, Eq, PartialEq)]
 enum ReadLineProgramState {
     SequenceEnded,
-    ReadSequence(DefinedFuncIndex),
+    /// Reading a sequence, whose last row was in the given function if it was
+    /// in any.
+    ReadSequence(Option<DefinedFuncIndex>),
     IgnoreSequence,
 }
 
@@ -143,8 +140,7 @@ where
         }
 
         let mut rows = program.rows();
-        let mut func_rows = Vec::new();
-        let mut saved_rows: Vec<(u64, SavedLineProgramRow)> = Vec::new();
+        let mut func_rows = BTreeMap::new();
         let mut state = ReadLineProgramState::SequenceEnded;
         while let Some((_header, row)) = rows.next_row()? {
             if state == ReadLineProgramState::IgnoreSequence {
@@ -153,63 +149,65 @@ where
                 }
                 continue;
             }
-            let saved_row = if row.end_sequence() {
-                let index = match state {
-                    ReadLineProgramState::ReadSequence(index) => index,
-                    _ => panic!(),
-                };
-                saved_rows.sort_by_key(|r| r.0);
-                func_rows.push(FuncRows {
-                    index,
-                    sorted_rows: saved_rows,
-                });
-
-                saved_rows = Vec::new();
-                state = ReadLineProgramState::SequenceEnded;
-                SavedLineProgramRow::EndOfSequence(row.address())
-            } else {
-                if state == ReadLineProgramState::SequenceEnded {
-                    // Discard sequences for non-existent code.
-                    if row.address() == 0 {
-                        state = ReadLineProgramState::IgnoreSequence;
-                        continue;
-                    }
-                    match addr_tr.find_func_index(row.address()) {
-                        Some(index) => {
-                            state = ReadLineProgramState::ReadSequence(index);
-                        }
-                        None => {
-                            // Some non-existent address found.
-                            state = ReadLineProgramState::IgnoreSequence;
-                            continue;
-                        }
-                    }
+            if row.end_sequence() {
+                // The end of a sequence is the address just past its last
+                // row, so it ends the rows of that row's function.
+                if let ReadLineProgramState::ReadSequence(Some(index)) = state {
+                    func_rows.entry(index).or_insert_with(Vec::new).push((
+                        row.address(),
+                        SavedLineProgramRow::EndOfSequence(row.address()),
+                    ));
                 }
-                SavedLineProgramRow::Normal {
-                    address: row.address(),
-                    op_index: row.op_index(),
-                    file_index: row.file_index(),
-                    line: row.line().map(|nonzero| nonzero.get()).unwrap_or(0),
-                    column: match row.column() {
-                        gimli::ColumnType::LeftEdge => 0,
-                        gimli::ColumnType::Column(val) => val.get(),
-                    },
-                    discriminator: row.discriminator(),
-                    is_stmt: row.is_stmt(),
-                    basic_block: row.basic_block(),
-                    prologue_end: row.prologue_end(),
-                    epilogue_begin: row.epilogue_begin(),
-                    isa: row.isa(),
+                state = ReadLineProgramState::SequenceEnded;
+                continue;
+            }
+            // Discard sequences for non-existent code.
+            if state == ReadLineProgramState::SequenceEnded && row.address() == 0 {
+                state = ReadLineProgramState::IgnoreSequence;
+                continue;
+            }
+            // A sequence isn't necessarily for a single function: it can
+            // cover several, in whatever order they were placed in the code
+            // section. So each row goes with the function its own address is
+            // in, rather than with the one the sequence starts in.
+            let index = match addr_tr.find_func_index(row.address()) {
+                Some(index) => index,
+                None => {
+                    // Some non-existent address found.
+                    state = ReadLineProgramState::ReadSequence(None);
+                    continue;
                 }
             };
-            saved_rows.push((row.address(), saved_row));
+            state = ReadLineProgramState::ReadSequence(Some(index));
+            let saved_row = SavedLineProgramRow::Normal {
+                address: row.address(),
+                op_index: row.op_index(),
+                file_index: row.file_index(),
+                line: row.line().map(|nonzero| nonzero.get()).unwrap_or(0),
+                column: match row.column() {
+                    gimli::ColumnType::LeftEdge => 0,
+                    gimli::ColumnType::Column(val) => val.get(),
+                },
+                discriminator: row.discriminator(),
+                is_stmt: row.is_stmt(),
+                basic_block: row.basic_block(),
+                prologue_end: row.prologue_end(),
+                epilogue_begin: row.epilogue_begin(),
+                isa: row.isa(),
+            };
+            func_rows
+                .entry(index)
+                .or_insert_with(Vec::new)
+                .push((row.address(), saved_row));
         }
 
-        for FuncRows {
-            index,
-            sorted_rows: saved_rows,
-        } in func_rows
-        {
+        for (index, mut saved_rows) in func_rows {
+            // A function's rows can come from several sequences. Where the end
+            // of one is at the same address as a row of another, the latter
+            // is sorted last so that it's the one looked up below.
+            saved_rows.sort_by_key(|(address, row)| {
+                (*address, matches!(row, SavedLineProgramRow::Normal { .. }))
+            });
             let map = match addr_tr.map().get(index) {
                 Some(map) if map.len > 0 => map,
                 _ => {
@@ -222,16 +220,9 @@ where
             // TODO track and place function declaration line here
             let mut last_address = None;
             for addr_map in map.addresses.iter() {
-                let saved_row = match saved_rows.binary_search_by_key(&addr_map.wasm, |i| i.0) {
-                    Ok(i) => Some(&saved_rows[i].1),
-                    Err(i) => {
-                        if i > 0 {
-                            Some(&saved_rows[i - 1].1)
-                        } else {
-                            None
-                        }
-                    }
-                };
+                // The last row at or before this address applies to it.
+                let i = saved_rows.partition_point(|(address, _)| *address <= addr_map.wasm);
+                let saved_row = i.checked_sub(1).map(|i| &saved_rows[i].1);
                 if let Some(SavedLineProgramRow::Normal {
                     address,
                     op_index,
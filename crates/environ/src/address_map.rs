@@ -3,6 +3,7 @@
 
 use cranelift_codegen::ir;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 /// Single source location to generated address mapping.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -39,6 +40,205 @@ pub struct FunctionAddressMap {
     pub body_len: u32,
 }
 
+impl FunctionAddressMap {
+    /// Returns the compact form of this map which is kept with compiled code
+    /// for symbolicating backtraces.
+    ///
+    /// If `instructions` is `false` the mapping of individual instructions is
+    /// dropped and every code offset maps to the function's start location.
+    pub fn compress(&self, instructions: bool) -> CompactFunctionAddressMap {
+        let instructions = if instructions {
+            CompressedAddressMap::new(&self.instructions)
+        } else {
+            CompressedAddressMap::new(&[InstructionAddressMap {
+                srcloc: self.start_srcloc,
+                code_offset: 0,
+            }])
+        };
+        CompactFunctionAddressMap {
+            instructions,
+            start_srcloc: self.start_srcloc,
+            end_srcloc: self.end_srcloc,
+            body_len: self.body_len,
+        }
+    }
+}
+
+/// The address map of a compiled function as kept alongside its code, with
+/// the instruction mappings stored compactly.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct CompactFunctionAddressMap {
+    /// The mappings of the function's instructions, sorted by code offset.
+    pub instructions: CompressedAddressMap,
+
+    /// Function start source location (normally declaration).
+    pub start_srcloc: ir::SourceLoc,
+
+    /// Function end source location.
+    pub end_srcloc: ir::SourceLoc,
+
+    /// Generated function body length.
+    pub body_len: u32,
+}
+
+/// A list of `InstructionAddressMap`s sorted by code offset, encoded as the
+/// differences between consecutive entries in LEB128.
+///
+/// Consecutive instructions are usually only a few bytes apart in both the
+/// compiled code and the wasm binary, so most entries take two bytes instead
+/// of the eight of an `InstructionAddressMap`. Entries are decoded on demand,
+/// and to keep looking up a single code offset cheap, which is done for each
+/// frame when a backtrace is captured, the decoded state is recorded every
+/// `CHECKPOINT_INTERVAL` entries so that a lookup only decodes the entries
+/// after the nearest checkpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct CompressedAddressMap {
+    data: Box<[u8]>,
+    checkpoints: Box<[Checkpoint]>,
+}
+
+/// The number of entries between the checkpoints of a `CompressedAddressMap`.
+const CHECKPOINT_INTERVAL: usize = 64;
+
+/// An entry of a `CompressedAddressMap` along with where the entries after it
+/// start in its data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct Checkpoint {
+    code_offset: u32,
+    srcloc: u32,
+    next: u32,
+}
+
+impl CompressedAddressMap {
+    /// Encodes `instructions`, which must be sorted by code offset.
+    pub fn new(instructions: &[InstructionAddressMap]) -> CompressedAddressMap {
+        let mut data = Vec::with_capacity(instructions.len() * 2);
+        let mut checkpoints = Vec::with_capacity(instructions.len() / CHECKPOINT_INTERVAL);
+        let mut prev = InstructionAddressMap {
+            srcloc: ir::SourceLoc::new(0),
+            code_offset: 0,
+        };
+        for (i, inst) in instructions.iter().enumerate() {
+            debug_assert!(inst.code_offset >= prev.code_offset);
+            write_leb128(&mut data, inst.code_offset - prev.code_offset);
+            // Source locations may go backwards, and the default location is
+            // `!0`, so zigzag encode the wrapping difference.
+            let delta = inst.srcloc.bits().wrapping_sub(prev.srcloc.bits()) as i32;
+            write_leb128(&mut data, ((delta << 1) ^ (delta >> 31)) as u32);
+            if i % CHECKPOINT_INTERVAL == CHECKPOINT_INTERVAL - 1 {
+                checkpoints.push(Checkpoint {
+                    code_offset: inst.code_offset,
+                    srcloc: inst.srcloc.bits(),
+                    next: u32::try_from(data.len()).unwrap(),
+                });
+            }
+            prev = inst.clone();
+        }
+        CompressedAddressMap {
+            data: data.into_boxed_slice(),
+            checkpoints: checkpoints.into_boxed_slice(),
+        }
+    }
+
+    /// Returns the size of the encoding in bytes.
+    pub fn encoded_len(&self) -> usize {
+        self.data.len() + self.checkpoints.len() * std::mem::size_of::<Checkpoint>()
+    }
+
+    /// Returns whether there are no instructions in this map.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Decodes the instructions in this map, in order of code offset.
+    pub fn iter(&self) -> impl Iterator<Item = InstructionAddressMap> + '_ {
+        decode(&self.data, 0, 0)
+    }
+
+    /// Returns the source location of the instruction covering `code_offset`,
+    /// which is the last one starting at or before it.
+    ///
+    /// Returns `None` if no instruction starts at or before `code_offset`.
+    pub fn lookup(&self, code_offset: u32) -> Option<ir::SourceLoc> {
+        // Every entry before the last checkpoint at or before `code_offset`
+        // is superseded by it, so decoding starts from there.
+        let (mut found, start) = match self
+            .checkpoints
+            .partition_point(|c| c.code_offset <= code_offset)
+            .checked_sub(1)
+        {
+            Some(i) => {
+                let checkpoint = &self.checkpoints[i];
+                (Some(ir::SourceLoc::new(checkpoint.srcloc)), checkpoint)
+            }
+            None => (None, &START),
+        };
+        let data = &self.data[start.next as usize..];
+        for inst in decode(data, start.code_offset, start.srcloc) {
+            if inst.code_offset > code_offset {
+                break;
+            }
+            found = Some(inst.srcloc);
+        }
+        found
+    }
+}
+
+/// The state before the first entry of a `CompressedAddressMap`.
+const START: Checkpoint = Checkpoint {
+    code_offset: 0,
+    srcloc: 0,
+    next: 0,
+};
+
+/// Decodes the entries in `data`, whose deltas are relative to an entry at
+/// `code_offset` with source location `srcloc`.
+fn decode(
+    mut data: &[u8],
+    mut code_offset: u32,
+    mut srcloc: u32,
+) -> impl Iterator<Item = InstructionAddressMap> + '_ {
+    std::iter::from_fn(move || {
+        if data.is_empty() {
+            return None;
+        }
+        code_offset += read_leb128(&mut data);
+        let zigzag = read_leb128(&mut data);
+        let delta = ((zigzag >> 1) as i32) ^ -((zigzag & 1) as i32);
+        srcloc = srcloc.wrapping_add(delta as u32);
+        Some(InstructionAddressMap {
+            srcloc: ir::SourceLoc::new(srcloc),
+            code_offset,
+        })
+    })
+}
+
+fn write_leb128(data: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte);
+            return;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+fn read_leb128(data: &mut &[u8]) -> u32 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[0];
+        *data = &data[1..];
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
 /// Memory definition offset in the VMContext structure.
 #[derive(Debug, Clone)]
 pub enum ModuleMemoryOffset {
@@ -49,3 +249,79 @@ pub enum ModuleMemoryOffset {
     /// Offset to the imported memory.
     Imported(u32),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inst(code_offset: u32, srcloc: u32) -> InstructionAddressMap {
+        InstructionAddressMap {
+            srcloc: ir::SourceLoc::new(srcloc),
+            code_offset,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let instructions = [
+            inst(0, 10),
+            inst(3, 12),
+            inst(3, 11),
+            inst(200, 5),
+            inst(201, !0),
+            inst(100_000, 0x1234_5678),
+            inst(u32::max_value(), 0),
+        ];
+        let map = CompressedAddressMap::new(&instructions);
+        assert_eq!(map.iter().collect::<Vec<_>>(), instructions);
+        assert!(CompressedAddressMap::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn lookup() {
+        let map = CompressedAddressMap::new(&[inst(4, 10), inst(8, 20), inst(20, 15)]);
+        assert_eq!(map.lookup(0), None);
+        assert_eq!(map.lookup(4), Some(ir::SourceLoc::new(10)));
+        assert_eq!(map.lookup(7), Some(ir::SourceLoc::new(10)));
+        assert_eq!(map.lookup(8), Some(ir::SourceLoc::new(20)));
+        assert_eq!(map.lookup(19), Some(ir::SourceLoc::new(20)));
+        assert_eq!(map.lookup(1000), Some(ir::SourceLoc::new(15)));
+    }
+
+    #[test]
+    fn lookup_across_checkpoints() {
+        // Runs of instructions at the same offset straddle checkpoints, and
+        // source locations go up and down.
+        let instructions = (0..1000u32)
+            .map(|i| inst(i / 3 * 4, (i * 7919) % 1009))
+            .collect::<Vec<_>>();
+        let map = CompressedAddressMap::new(&instructions);
+        assert_eq!(map.checkpoints.len(), 1000 / CHECKPOINT_INTERVAL);
+        assert_eq!(map.iter().collect::<Vec<_>>(), instructions);
+        for code_offset in 0..1400 {
+            let expected = instructions
+                .iter()
+                .take_while(|inst| inst.code_offset <= code_offset)
+                .last()
+                .map(|inst| inst.srcloc);
+            assert_eq!(map.lookup(code_offset), expected, "{}", code_offset);
+        }
+    }
+
+    #[test]
+    fn function_granular() {
+        let map = FunctionAddressMap {
+            instructions: vec![inst(0, 10), inst(4, 12)].into(),
+            start_srcloc: ir::SourceLoc::new(8),
+            end_srcloc: ir::SourceLoc::new(14),
+            body_offset: 0,
+            body_len: 8,
+        };
+        let compact = map.compress(true);
+        assert_eq!(compact.instructions.lookup(5), Some(ir::SourceLoc::new(12)));
+        let compact = map.compress(false);
+        assert_eq!(compact.instructions.lookup(5), Some(ir::SourceLoc::new(8)));
+        assert_eq!(compact.end_srcloc, ir::SourceLoc::new(14));
+        assert_eq!(compact.body_len, 8);
+    }
+}
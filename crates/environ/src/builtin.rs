@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 /// Helper macro to iterate over all builtin functions and their signatures.
 #[macro_export]
 macro_rules! foreach_builtin_function {
@@ -45,12 +47,23 @@ macro_rules! foreach_builtin_function {
             memory_atomic_wait64(vmctx, i32, i32, i64, i64) -> (i32);
             /// Invoked when fuel has run out while executing a function.
             out_of_gas(vmctx) -> ();
+            /// Invoked to attribute fuel consumed by a function to it when
+            /// per-function fuel accounting is enabled.
+            fuel_attribute(vmctx, i32, i64) -> ();
+            /// Invoked when the engine's epoch has reached the store's epoch
+            /// deadline while executing a function.
+            new_epoch(vmctx) -> ();
+            /// Performs a scalar load from a memory whose accesses are hooked,
+            /// returning the loaded value zero-extended to 64 bits.
+            memory_access_load(vmctx, i32, i64, i32) -> (i64);
+            /// Performs a scalar store to a memory whose accesses are hooked.
+            memory_access_store(vmctx, i32, i64, i32, i64) -> ();
         }
     };
 }
 
 /// An index type for builtin functions.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct BuiltinFunctionIndex(u32);
 
 impl BuiltinFunctionIndex {
@@ -63,6 +76,22 @@ impl BuiltinFunctionIndex {
     pub const fn index(&self) -> u32 {
         self.0
     }
+
+    /// Returns the name of this builtin function, e.g. `"memory_copy"`.
+    pub fn name(&self) -> &'static str {
+        macro_rules! names {
+            (
+                $(
+                    $( #[$attr:meta] )*
+                    $name:ident( $( $param:ident ),* ) -> ( $( $result:ident ),* );
+                )*
+            ) => {
+                &[$(stringify!($name)),*]
+            };
+        }
+        let names: &[&str] = foreach_builtin_function!(names);
+        names[self.0 as usize]
+    }
 }
 
 macro_rules! declare_indexes {
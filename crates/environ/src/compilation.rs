@@ -1,7 +1,10 @@
 //! A `Compilation` contains the compiled function bodies for a WebAssembly
 //! module.
 
-use crate::{FunctionAddressMap, FunctionBodyData, ModuleTranslation, Tunables, TypeTables};
+use crate::{
+    BuiltinFunctionIndex, FunctionAddressMap, FunctionBodyData, ModuleTranslation, Tunables,
+    TypeTables,
+};
 use cranelift_codegen::{binemit, ir, isa, isa::unwind::UnwindInfo};
 use cranelift_entity::PrimaryMap;
 use cranelift_wasm::{DefinedFuncIndex, FuncIndex, WasmError};
@@ -30,6 +33,18 @@ pub struct CompiledFunction {
     pub stack_slots: ir::StackSlots,
     pub traps: Vec<TrapInformation>,
     pub stack_maps: Vec<StackMapInformation>,
+
+    /// The offsets of the return addresses of this function's calls, in
+    /// ascending order.
+    pub call_sites: Vec<binemit::CodeOffset>,
+
+    /// The offsets of the return addresses of this function's direct calls
+    /// to functions defined in the same module, in ascending order.
+    pub direct_call_sites: Vec<binemit::CodeOffset>,
+
+    /// The runtime builtin functions which this function's code may call, in
+    /// ascending order.
+    pub builtins: Vec<BuiltinFunctionIndex>,
 }
 
 /// A record of a relocation to perform.
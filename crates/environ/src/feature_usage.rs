@@ -0,0 +1,418 @@
+//! Detection of which post-MVP WebAssembly proposals a module makes use of.
+
+use cranelift_wasm::{WasmFuncType, WasmType};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasmparser::{
+    DataKind, ElementKind, FunctionBody, ImportSectionEntryType, MemoryType, Operator, Parser,
+    Payload, Result, TableType, Type, TypeDef, TypeOrFuncType,
+};
+
+/// Which WebAssembly proposals a module uses, as opposed to which ones it's
+/// allowed to use.
+///
+/// This is collected while a module is translated, and for modules which fail
+/// to validate, for example because they use a disabled proposal, by a
+/// separate scan of the binary which doesn't depend on the features enabled
+/// for compilation. A module's usage includes that of any modules nested
+/// within it with the module linking proposal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FeatureUsage {
+    /// Sign-extension operators such as `i32.extend8_s`.
+    pub sign_extension: bool,
+    /// Non-trapping float-to-int conversions such as `i32.trunc_sat_f32_s`.
+    pub saturating_float_to_int: bool,
+    /// Functions or blocks with multiple results, or blocks with parameters.
+    pub multi_value: bool,
+    /// Passive segments, or bulk memory and table operators.
+    pub bulk_memory: bool,
+    /// Reference types, multiple tables, or table and reference operators.
+    pub reference_types: bool,
+    /// The `v128` type or SIMD operators.
+    pub simd: bool,
+    /// The number of SIMD operators in function bodies.
+    pub simd_instructions: u64,
+    /// Shared memories or atomic operators.
+    pub threads: bool,
+    /// The number of atomic operators in function bodies.
+    pub atomic_instructions: u64,
+    /// More than one memory.
+    pub multi_memory: bool,
+    /// 64-bit memories.
+    pub memory64: bool,
+    /// Tail calls with `return_call` and `return_call_indirect`.
+    pub tail_call: bool,
+    /// Exception handling events and operators.
+    pub exceptions: bool,
+    /// Nested modules, instances, or single-level imports.
+    pub module_linking: bool,
+}
+
+impl FeatureUsage {
+    /// Scans the `wasm` binary for the proposals it uses.
+    ///
+    /// This is a best-effort scan of an unvalidated binary. If the binary is
+    /// malformed then the usage found up to that point is returned.
+    pub fn scan(wasm: &[u8]) -> FeatureUsage {
+        let mut scanner = Scanner::default();
+        // Errors only stop the scan early.
+        let _ = scanner.scan(wasm);
+        let mut usage = FeatureUsage::default();
+        for module in scanner.finished.iter().chain(&scanner.in_progress) {
+            usage.merge(module);
+        }
+        usage
+    }
+
+    /// Returns whether the module only uses the WebAssembly MVP.
+    pub fn is_mvp(&self) -> bool {
+        self.proposals().next().is_none()
+    }
+
+    /// Returns the names of the proposals the module uses.
+    pub fn proposals(&self) -> impl Iterator<Item = &'static str> {
+        let used = vec![
+            (self.sign_extension, "sign-extension"),
+            (self.saturating_float_to_int, "saturating float-to-int"),
+            (self.multi_value, "multi-value"),
+            (self.bulk_memory, "bulk memory"),
+            (self.reference_types, "reference types"),
+            (self.simd, "SIMD"),
+            (self.threads, "threads"),
+            (self.multi_memory, "multi-memory"),
+            (self.memory64, "memory64"),
+            (self.tail_call, "tail calls"),
+            (self.exceptions, "exceptions"),
+            (self.module_linking, "module linking"),
+        ];
+        used.into_iter()
+            .filter(|(used, _)| *used)
+            .map(|(_, name)| name)
+    }
+
+    pub(crate) fn merge(&mut self, other: &FeatureUsage) {
+        self.sign_extension |= other.sign_extension;
+        self.saturating_float_to_int |= other.saturating_float_to_int;
+        self.multi_value |= other.multi_value;
+        self.bulk_memory |= other.bulk_memory;
+        self.reference_types |= other.reference_types;
+        self.simd |= other.simd;
+        self.simd_instructions += other.simd_instructions;
+        self.threads |= other.threads;
+        self.atomic_instructions += other.atomic_instructions;
+        self.multi_memory |= other.multi_memory;
+        self.memory64 |= other.memory64;
+        self.tail_call |= other.tail_call;
+        self.exceptions |= other.exceptions;
+        self.module_linking |= other.module_linking;
+    }
+
+    fn record_type(&mut self, ty: Type) {
+        match ty {
+            Type::V128 => self.simd = true,
+            Type::FuncRef | Type::ExternRef => self.reference_types = true,
+            _ => {}
+        }
+    }
+
+    /// Records the use of `ty` during translation.
+    pub(crate) fn record_wasm_type(&mut self, ty: WasmType) {
+        match ty {
+            WasmType::V128 => self.simd = true,
+            WasmType::FuncRef | WasmType::ExternRef => self.reference_types = true,
+            _ => {}
+        }
+    }
+
+    /// Records the use of the function type `ty` during translation.
+    pub(crate) fn record_func_type(&mut self, ty: &WasmFuncType) {
+        self.multi_value |= ty.returns.len() > 1;
+        for ty in ty.params.iter().chain(ty.returns.iter()) {
+            self.record_wasm_type(*ty);
+        }
+    }
+
+    /// Records the use of a table of `ty` elements during translation.
+    pub(crate) fn record_table_element(&mut self, ty: WasmType) {
+        if ty != WasmType::FuncRef {
+            self.reference_types = true;
+        }
+    }
+
+    fn record_table(&mut self, ty: &TableType) {
+        if ty.element_type != Type::FuncRef {
+            self.reference_types = true;
+        }
+    }
+
+    /// Records the use of a memory with the given properties.
+    pub(crate) fn record_memory_kind(&mut self, shared: bool, memory64: bool) {
+        self.threads |= shared;
+        self.memory64 |= memory64;
+    }
+
+    fn record_memory(&mut self, ty: &MemoryType) {
+        match *ty {
+            MemoryType::M32 { shared, .. } => self.record_memory_kind(shared, false),
+            MemoryType::M64 { shared, .. } => self.record_memory_kind(shared, true),
+        }
+    }
+
+    /// Records the proposals implied by a module having `tables` tables and
+    /// `memories` memories, once all of them are known.
+    pub(crate) fn record_counts(&mut self, tables: usize, memories: usize) {
+        // Note that multiple tables are only one of the ways in which
+        // reference types are used.
+        self.reference_types |= tables > 1;
+        self.multi_memory |= memories > 1;
+    }
+
+    /// Records the use of the operators in `body`, a function body within
+    /// `wasm`, calling `each` with every operator read.
+    ///
+    /// This stops at the first operator which fails to parse, returning the
+    /// error, and the usage recorded up to that point is kept.
+    pub(crate) fn record_body(
+        &mut self,
+        wasm: &[u8],
+        body: &FunctionBody,
+        mut each: impl FnMut(&Operator),
+    ) -> Result<()> {
+        let mut locals = body.get_locals_reader()?;
+        for _ in 0..locals.get_count() {
+            let (_, ty) = locals.read()?;
+            self.record_type(ty);
+        }
+        let mut operators = body.get_operators_reader()?;
+        while !operators.eof() {
+            let (op, offset) = operators.read_with_offset()?;
+            self.record_operator(&op, wasm.get(offset..).unwrap_or(&[]));
+            each(&op);
+        }
+        Ok(())
+    }
+
+    fn record_block_type(&mut self, ty: TypeOrFuncType) {
+        match ty {
+            TypeOrFuncType::Type(ty) => self.record_type(ty),
+            TypeOrFuncType::FuncType(_) => self.multi_value = true,
+        }
+    }
+
+    /// Records the use of `op`, whose encoding starts with `bytes`.
+    fn record_operator(&mut self, op: &Operator, bytes: &[u8]) {
+        match bytes.first() {
+            Some(0xfd) => {
+                self.simd = true;
+                self.simd_instructions += 1;
+                return;
+            }
+            Some(0xfe) => {
+                self.threads = true;
+                self.atomic_instructions += 1;
+                return;
+            }
+            _ => {}
+        }
+        match *op {
+            Operator::I32Extend8S
+            | Operator::I32Extend16S
+            | Operator::I64Extend8S
+            | Operator::I64Extend16S
+            | Operator::I64Extend32S => self.sign_extension = true,
+
+            Operator::I32TruncSatF32S
+            | Operator::I32TruncSatF32U
+            | Operator::I32TruncSatF64S
+            | Operator::I32TruncSatF64U
+            | Operator::I64TruncSatF32S
+            | Operator::I64TruncSatF32U
+            | Operator::I64TruncSatF64S
+            | Operator::I64TruncSatF64U => self.saturating_float_to_int = true,
+
+            Operator::Block { ty }
+            | Operator::Loop { ty }
+            | Operator::If { ty }
+            | Operator::Try { ty } => {
+                self.record_block_type(ty);
+                if let Operator::Try { .. } = op {
+                    self.exceptions = true;
+                }
+            }
+
+            Operator::MemoryInit { .. }
+            | Operator::DataDrop { .. }
+            | Operator::MemoryCopy { .. }
+            | Operator::MemoryFill { .. }
+            | Operator::TableInit { .. }
+            | Operator::ElemDrop { .. }
+            | Operator::TableCopy { .. } => self.bulk_memory = true,
+
+            Operator::RefNull { .. }
+            | Operator::RefIsNull
+            | Operator::RefFunc { .. }
+            | Operator::TableGet { .. }
+            | Operator::TableSet { .. }
+            | Operator::TableGrow { .. }
+            | Operator::TableSize { .. }
+            | Operator::TableFill { .. }
+            | Operator::TypedSelect { .. } => self.reference_types = true,
+
+            Operator::CallIndirect { table_index, .. } if table_index != 0 => {
+                self.reference_types = true
+            }
+
+            Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. } => {
+                self.tail_call = true
+            }
+
+            Operator::Catch { .. }
+            | Operator::Throw { .. }
+            | Operator::Rethrow { .. }
+            | Operator::Delegate { .. }
+            | Operator::CatchAll => self.exceptions = true,
+
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for FeatureUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_mvp() {
+            return write!(f, "no proposals beyond the MVP");
+        }
+        for (i, name) in self.proposals().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of a scan of a binary, which may contain nested modules.
+#[derive(Default)]
+struct Scanner {
+    /// The usage of modules which have been fully scanned.
+    finished: Vec<FeatureUsage>,
+    /// The usage of the module being scanned, and of the modules it's nested
+    /// within.
+    in_progress: Vec<FeatureUsage>,
+    /// The number of tables and memories of the module being scanned, and of
+    /// the modules it's nested within.
+    counts: Vec<(u32, u32)>,
+}
+
+impl Scanner {
+    fn scan(&mut self, wasm: &[u8]) -> Result<()> {
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload?;
+            if let Payload::Version { .. } = payload {
+                self.in_progress.push(FeatureUsage::default());
+                self.counts.push((0, 0));
+                continue;
+            }
+            if let Payload::End = payload {
+                let usage = self.in_progress.pop().unwrap();
+                self.counts.pop();
+                if let Some(parent) = self.in_progress.last_mut() {
+                    parent.merge(&usage);
+                }
+                self.finished.push(usage);
+                continue;
+            }
+            let usage = match self.in_progress.last_mut() {
+                Some(usage) => usage,
+                None => continue,
+            };
+            let (tables, memories) = self.counts.last_mut().unwrap();
+            match payload {
+                Payload::TypeSection(reader) => {
+                    for ty in reader {
+                        match ty? {
+                            TypeDef::Func(ty) => {
+                                usage.multi_value |= ty.returns.len() > 1;
+                                for ty in ty.params.iter().chain(ty.returns.iter()) {
+                                    usage.record_type(*ty);
+                                }
+                            }
+                            TypeDef::Module(_) | TypeDef::Instance(_) => {
+                                usage.module_linking = true
+                            }
+                        }
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let import = import?;
+                        usage.module_linking |= import.field.is_none();
+                        match import.ty {
+                            ImportSectionEntryType::Function(_) => {}
+                            ImportSectionEntryType::Table(ty) => {
+                                *tables += 1;
+                                usage.record_table(&ty);
+                            }
+                            ImportSectionEntryType::Memory(ty) => {
+                                *memories += 1;
+                                usage.record_memory(&ty);
+                            }
+                            ImportSectionEntryType::Global(ty) => {
+                                usage.record_type(ty.content_type)
+                            }
+                            ImportSectionEntryType::Event(_) => usage.exceptions = true,
+                            ImportSectionEntryType::Module(_)
+                            | ImportSectionEntryType::Instance(_) => usage.module_linking = true,
+                        }
+                    }
+                }
+                Payload::TableSection(reader) => {
+                    for ty in reader {
+                        *tables += 1;
+                        usage.record_table(&ty?);
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    for ty in reader {
+                        *memories += 1;
+                        usage.record_memory(&ty?);
+                    }
+                }
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        usage.record_type(global?.ty.content_type);
+                    }
+                }
+                Payload::EventSection(_) => usage.exceptions = true,
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        match element?.kind {
+                            ElementKind::Passive => usage.bulk_memory = true,
+                            ElementKind::Declared => usage.reference_types = true,
+                            ElementKind::Active { table_index, .. } => {
+                                usage.reference_types |= table_index != 0
+                            }
+                        }
+                    }
+                }
+                Payload::DataCountSection { .. } => usage.bulk_memory = true,
+                Payload::DataSection(reader) => {
+                    for data in reader {
+                        if let DataKind::Passive = data?.kind {
+                            usage.bulk_memory = true;
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => usage.record_body(wasm, &body, |_| {})?,
+                Payload::InstanceSection(_)
+                | Payload::AliasSection(_)
+                | Payload::ModuleSectionStart { .. } => usage.module_linking = true,
+                _ => {}
+            }
+            usage.record_counts(*tables as usize, *memories as usize);
+        }
+        Ok(())
+    }
+}
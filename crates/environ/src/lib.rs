@@ -27,6 +27,7 @@ mod address_map;
 mod builtin;
 mod compilation;
 mod data_structures;
+mod feature_usage;
 mod module;
 mod module_environ;
 mod tunables;
@@ -36,6 +37,7 @@ pub use crate::address_map::*;
 pub use crate::builtin::*;
 pub use crate::compilation::*;
 pub use crate::data_structures::*;
+pub use crate::feature_usage::FeatureUsage;
 pub use crate::module::*;
 pub use crate::module_environ::*;
 pub use crate::tunables::Tunables;
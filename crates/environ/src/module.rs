@@ -1,5 +1,6 @@
 //! Data structures for representing decoded wasm modules.
 
+use crate::feature_usage::FeatureUsage;
 use crate::tunables::Tunables;
 use crate::WASM_MAX_PAGES;
 use cranelift_entity::{EntityRef, PrimaryMap};
@@ -78,7 +79,9 @@ impl MemoryPlan {
             style,
             offset_guard_size,
             pre_guard_size: if tunables.guard_before_linear_memory {
-                offset_guard_size
+                tunables
+                    .guard_before_linear_memory_size
+                    .unwrap_or(offset_guard_size)
             } else {
                 0
             },
@@ -141,6 +144,32 @@ pub enum MemoryInitialization {
 }
 
 impl MemoryInitialization {
+    /// Returns how the linear memories of instances of `module` are
+    /// initialized with this initialization.
+    pub fn strategy(&self, module: &Module) -> DataInitStrategy {
+        match self {
+            Self::Paged { .. } => DataInitStrategy::Paged,
+            Self::Segmented(initializers) => {
+                let reason = initializers
+                    .iter()
+                    .find_map(|initializer| {
+                        if module
+                            .defined_memory_index(initializer.memory_index)
+                            .is_none()
+                        {
+                            Some(SegmentedInitReason::ImportedMemory)
+                        } else if initializer.base.is_some() {
+                            Some(SegmentedInitReason::GlobalOffset)
+                        } else {
+                            None
+                        }
+                    })
+                    .unwrap_or(SegmentedInitReason::NotEnabled);
+                DataInitStrategy::Segmented(reason)
+            }
+        }
+    }
+
     /// Attempts to convert segmented memory initialization into paged initialization for the given module.
     ///
     /// Returns `None` if the initialization cannot be paged or if it is already paged.
@@ -238,6 +267,38 @@ impl MemoryInitialization {
     }
 }
 
+/// How the linear memories of an instance are initialized with the module's
+/// active data segments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataInitStrategy {
+    /// The data segments were laid out as whole WebAssembly pages when the
+    /// module was compiled, and those pages are copied (or mapped) into each
+    /// linear memory.
+    ///
+    /// Segments which overlap, aren't page-aligned, or target a memory of any
+    /// style can all be laid out this way.
+    Paged,
+    /// Each data segment is copied into its linear memory in turn when the
+    /// module is instantiated, for the given reason.
+    Segmented(SegmentedInitReason),
+}
+
+/// Why a module's data segments are processed one by one at instantiation,
+/// rather than laid out as pages ahead of time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SegmentedInitReason {
+    /// A data segment initializes an imported memory, whose contents aren't
+    /// known until instantiation.
+    ImportedMemory,
+    /// A data segment's offset is given by a global, whose value isn't known
+    /// until instantiation.
+    GlobalOffset,
+    /// The module could have been laid out as pages, but paged initialization
+    /// isn't used by this build, which requires the `uffd` feature on Linux.
+    NotEnabled,
+}
+
 impl Default for MemoryInitialization {
     fn default() -> Self {
         Self::Segmented(Vec::new())
@@ -293,7 +354,7 @@ pub struct TableInitializer {
 ///
 /// Note that each of these variants are intended to index further into a
 /// separate table.
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Hash, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub enum ModuleType {
     Function(SignatureIndex),
@@ -386,6 +447,10 @@ pub struct Module {
     /// The set of defined functions within this module which are located in
     /// element segments.
     pub possibly_exported_funcs: HashSet<DefinedFuncIndex>,
+
+    /// The WebAssembly proposals this module, and any modules nested within
+    /// it, make use of.
+    pub feature_usage: FeatureUsage,
 }
 
 /// Initialization routines for creating an instance, encompassing imports,
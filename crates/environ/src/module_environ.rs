@@ -1,12 +1,13 @@
 use crate::module::{
-    Initializer, InstanceSignature, MemoryInitialization, MemoryInitializer, MemoryPlan, Module,
-    ModuleSignature, ModuleType, ModuleUpvar, TableInitializer, TablePlan, TypeTables,
+    Initializer, InstanceSignature, MemoryInitialization, MemoryInitializer, MemoryPlan,
+    MemoryStyle, Module, ModuleSignature, ModuleType, ModuleUpvar, TableInitializer, TablePlan,
+    TypeTables,
 };
 use crate::tunables::Tunables;
 use cranelift_codegen::ir;
 use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_codegen::packed_option::ReservedValue;
-use cranelift_entity::PrimaryMap;
+use cranelift_entity::{EntityRef, PrimaryMap};
 use cranelift_wasm::{
     self, translate_module, Alias, DataIndex, DefinedFuncIndex, ElemIndex, EntityIndex, EntityType,
     FuncIndex, Global, GlobalIndex, GlobalInit, InstanceIndex, InstanceTypeIndex, Memory,
@@ -19,7 +20,7 @@ use std::mem;
 use std::path::PathBuf;
 use std::sync::Arc;
 use wasmparser::Type as WasmType;
-use wasmparser::{FuncValidator, FunctionBody, ValidatorResources, WasmFeatures};
+use wasmparser::{FuncValidator, FunctionBody, Operator, ValidatorResources, WasmFeatures};
 
 /// Object containing the standalone environment information.
 pub struct ModuleEnvironment<'data> {
@@ -48,6 +49,9 @@ pub struct ModuleEnvironment<'data> {
     target_config: TargetFrontendConfig,
     tunables: Tunables,
     first_module: bool,
+
+    /// The binary being translated.
+    data: &'data [u8],
 }
 
 /// The result of translating via `ModuleEnvironment`. Function bodies are not
@@ -81,6 +85,41 @@ pub struct ModuleTranslation<'data> {
 
     /// Same as `creation_artifacts`, but for modules instead of artifacts.
     creation_modules: Vec<ModuleUpvar>,
+
+    /// Set if any function body may contain a `memory.grow` instruction.
+    may_grow_memory: bool,
+
+    /// The locations listed in a `wasmtime.yieldpoints` custom section, keyed
+    /// by function and given as sorted module byte offsets, if the section
+    /// was present and `Tunables::yieldpoints` is enabled.
+    pub yieldpoints: Option<HashMap<FuncIndex, Vec<u32>>>,
+}
+
+impl ModuleTranslation<'_> {
+    /// Rejects yieldpoints which name a function that this module doesn't
+    /// define, since only defined functions have code to place them in.
+    fn check_yieldpoint_functions(&self) -> WasmResult<()> {
+        let yieldpoints = match &self.yieldpoints {
+            Some(yieldpoints) => yieldpoints,
+            None => return Ok(()),
+        };
+        for func in yieldpoints.keys() {
+            let defined = self
+                .module
+                .defined_func_index(*func)
+                .map_or(false, |i| i.index() < self.function_body_inputs.len());
+            if !defined {
+                return Err(WasmError::InvalidWebAssembly {
+                    message: format!(
+                        "yieldpoint for function {} which isn't defined by the module",
+                        func.as_u32()
+                    ),
+                    offset: 0,
+                });
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Contains function data: byte code and its offset in the module.
@@ -150,9 +189,19 @@ impl<'data> ModuleEnvironment<'data> {
             features: *features,
             first_module: true,
             interned_func_types: Default::default(),
+            data: &[],
         }
     }
 
+    /// Rejects the module being translated if its memories or tables exceed
+    /// the limits configured in the tunables. This is checked as each one is
+    /// declared so oversized modules fail before their code is even read.
+    fn check_module_limits(&self) -> WasmResult<()> {
+        self.tunables
+            .check_module_limits(&self.result.module)
+            .map_err(WasmError::User)
+    }
+
     fn pointer_type(&self) -> ir::Type {
         self.target_config.pointer_type()
     }
@@ -177,8 +226,12 @@ impl<'data> ModuleEnvironment<'data> {
         mut self,
         data: &'data [u8],
     ) -> WasmResult<(usize, Vec<ModuleTranslation<'data>>, TypeTables)> {
+        self.data = data;
         translate_module(data, &mut self)?;
         assert!(self.results.len() > 0);
+        for result in self.results.iter() {
+            result.check_yieldpoint_functions()?;
+        }
         Ok((self.results.len() - 1, self.results, self.types))
     }
 
@@ -190,6 +243,37 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    /// Parses the payload of a `wasmtime.yieldpoints` custom section into the
+    /// module being translated. Function indices are checked once the whole
+    /// module has been read, and offsets only when each body is compiled.
+    fn register_yieldpoints_section(&mut self, data: &[u8]) -> WasmResult<()> {
+        if self.result.yieldpoints.is_some() {
+            return Err(WasmError::InvalidWebAssembly {
+                message: "duplicate `wasmtime.yieldpoints` section".to_string(),
+                offset: 0,
+            });
+        }
+        let mut reader = wasmparser::BinaryReader::new(data);
+        let mut yieldpoints = HashMap::<FuncIndex, Vec<u32>>::new();
+        for _ in 0..reader.read_var_u32()? {
+            let func = FuncIndex::from_u32(reader.read_var_u32()?);
+            let offset = reader.read_var_u32()?;
+            yieldpoints.entry(func).or_default().push(offset);
+        }
+        if !reader.eof() {
+            return Err(WasmError::InvalidWebAssembly {
+                message: "trailing bytes in `wasmtime.yieldpoints` section".to_string(),
+                offset: reader.original_position(),
+            });
+        }
+        for offsets in yieldpoints.values_mut() {
+            offsets.sort_unstable();
+            offsets.dedup();
+        }
+        self.result.yieldpoints = Some(yieldpoints);
+        Ok(())
+    }
+
     fn register_dwarf_section(&mut self, name: &str, data: &'data [u8]) {
         if !name.starts_with(".debug_") {
             return;
@@ -249,6 +333,15 @@ impl<'data> ModuleEnvironment<'data> {
     /// logic and instead work directly with two-level imports since no
     /// instances are defined.
     fn declare_import(&mut self, module: &'data str, field: Option<&'data str>, ty: EntityType) {
+        let usage = &mut self.result.module.feature_usage;
+        usage.module_linking |= field.is_none();
+        match &ty {
+            EntityType::Table(table) => usage.record_table_element(table.wasm_ty),
+            EntityType::Memory(memory) => usage.record_memory_kind(memory.shared, memory.memory64),
+            EntityType::Global(global) => usage.record_wasm_type(global.wasm_ty),
+            EntityType::Module(_) | EntityType::Instance(_) => usage.module_linking = true,
+            EntityType::Function(_) | EntityType::Event(_) => {}
+        }
         if !self.features.module_linking {
             assert!(field.is_some());
             let index = self.push_type(ty);
@@ -366,6 +459,49 @@ impl<'data> ModuleEnvironment<'data> {
             .push(ModuleSignature { imports, exports })
     }
 
+    /// Returns the dynamic memories defined by the module being translated
+    /// which are never exported or passed to an instantiation.
+    ///
+    /// Nothing outside the module can grow these memories, so if the module
+    /// itself never executes `memory.grow` then they stay at their minimum
+    /// size forever.
+    fn private_dynamic_memories(&self) -> Vec<MemoryIndex> {
+        let module = &self.result.module;
+        let escapes = |index: MemoryIndex| {
+            let entity = EntityIndex::Memory(index);
+            module.exports.values().any(|e| *e == entity)
+                || module.initializers.iter().any(|init| match init {
+                    Initializer::Instantiate { args, .. } => args.values().any(|e| *e == entity),
+                    _ => false,
+                })
+        };
+        module
+            .memory_plans
+            .iter()
+            .filter(|(index, plan)| {
+                !module.is_imported_memory(*index)
+                    && matches!(plan.style, MemoryStyle::Dynamic)
+                    && !escapes(*index)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Gives the memories which can never grow a static style bounded by their
+    /// minimum size, so that accesses to them are bounds checked against a
+    /// constant instead of the current length loaded from the `VMContext`.
+    fn make_non_growing_memories_static(&mut self) {
+        if self.result.may_grow_memory {
+            return;
+        }
+        for index in self.private_dynamic_memories() {
+            let plan = &mut self.result.module.memory_plans[index];
+            plan.style = MemoryStyle::Static {
+                bound: plan.memory.minimum,
+            };
+        }
+    }
+
     fn flag_func_possibly_exported(&mut self, func: FuncIndex) {
         if func.is_reserved_value() {
             return;
@@ -397,6 +533,10 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn declare_type_func(&mut self, wasm: WasmFuncType) -> WasmResult<()> {
+        self.tunables
+            .check_func_type_limits(wasm.params.len(), wasm.returns.len())
+            .map_err(WasmError::User)?;
+        self.result.module.feature_usage.record_func_type(&wasm);
         // Deduplicate wasm function signatures through `interned_func_types`,
         // which also deduplicates across wasm modules with module linking.
         let sig_index = match self.interned_func_types.get(&wasm) {
@@ -419,6 +559,7 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
         declared_imports: &[(&'data str, Option<&'data str>, EntityType)],
         exports: &[(&'data str, EntityType)],
     ) -> WasmResult<()> {
+        self.result.module.feature_usage.module_linking = true;
         let mut imports = indexmap::IndexMap::new();
         let mut instance_types = HashMap::new();
         for (module, field, ty) in declared_imports {
@@ -460,6 +601,7 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn declare_type_instance(&mut self, exports: &[(&'data str, EntityType)]) -> WasmResult<()> {
+        self.result.module.feature_usage.module_linking = true;
         let exports = exports
             .iter()
             .map(|e| (e.0.to_string(), e.1.clone()))
@@ -537,7 +679,7 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
         );
         self.declare_import(module, field, EntityType::Table(table));
         self.result.module.num_imported_tables += 1;
-        Ok(())
+        self.check_module_limits()
     }
 
     fn declare_memory_import(
@@ -551,12 +693,13 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
             self.result.module.num_imported_memories,
             "Imported memories must be declared first"
         );
-        if memory.shared {
-            return Err(WasmError::Unsupported("shared memories".to_owned()));
-        }
+        self.result
+            .module
+            .feature_usage
+            .record_memory_kind(memory.shared, memory.memory64);
         self.declare_import(module, field, EntityType::Memory(memory));
         self.result.module.num_imported_memories += 1;
-        Ok(())
+        self.check_module_limits()
     }
 
     fn declare_global_import(
@@ -623,9 +766,13 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn declare_table(&mut self, table: Table) -> WasmResult<()> {
+        self.result
+            .module
+            .feature_usage
+            .record_table_element(table.wasm_ty);
         let plan = TablePlan::for_table(table, &self.tunables);
         self.result.module.table_plans.push(plan);
-        Ok(())
+        self.check_module_limits()
     }
 
     fn reserve_memories(&mut self, num: u32) -> WasmResult<()> {
@@ -637,12 +784,13 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn declare_memory(&mut self, memory: Memory) -> WasmResult<()> {
-        if memory.shared {
-            return Err(WasmError::Unsupported("shared memories".to_owned()));
-        }
+        self.result
+            .module
+            .feature_usage
+            .record_memory_kind(memory.shared, memory.memory64);
         let plan = MemoryPlan::for_memory(memory, &self.tunables);
         self.result.module.memory_plans.push(plan);
-        Ok(())
+        self.check_module_limits()
     }
 
     fn reserve_globals(&mut self, num: u32) -> WasmResult<()> {
@@ -654,6 +802,10 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn declare_global(&mut self, global: Global) -> WasmResult<()> {
+        self.result
+            .module
+            .feature_usage
+            .record_wasm_type(global.wasm_ty);
         if let GlobalInit::RefFunc(index) = global.initializer {
             self.flag_func_possibly_exported(index);
         }
@@ -716,6 +868,7 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
         offset: u32,
         elements: Box<[FuncIndex]>,
     ) -> WasmResult<()> {
+        self.result.module.feature_usage.reference_types |= table_index.index() != 0;
         for element in elements.iter() {
             self.flag_func_possibly_exported(*element);
         }
@@ -736,6 +889,7 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
         elem_index: ElemIndex,
         segments: Box<[FuncIndex]>,
     ) -> WasmResult<()> {
+        self.result.module.feature_usage.bulk_memory = true;
         for element in segments.iter() {
             self.flag_func_possibly_exported(*element);
         }
@@ -755,6 +909,7 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn declare_elements(&mut self, segments: Box<[FuncIndex]>) -> WasmResult<()> {
+        self.result.module.feature_usage.reference_types = true;
         for element in segments.iter() {
             self.flag_func_possibly_exported(*element);
         }
@@ -788,6 +943,19 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
                     params: sig.params.iter().cloned().map(|i| i.into()).collect(),
                 });
         }
+        // Read the body once to find both the proposals it uses and whether
+        // it contains a `memory.grow` instruction. Bodies which fail to parse
+        // here will fail validation later, so conservatively assume they
+        // grow.
+        let mut grows = false;
+        let parsed = self
+            .result
+            .module
+            .feature_usage
+            .record_body(self.data, &body, |op| {
+                grows |= matches!(op, Operator::MemoryGrow { .. })
+            });
+        self.result.may_grow_memory |= grows || parsed.is_err();
         self.result
             .function_body_inputs
             .push(FunctionBodyData { validator, body });
@@ -827,6 +995,8 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn reserve_passive_data(&mut self, _count: u32) -> WasmResult<()> {
+        // This is only called for the data count section.
+        self.result.module.feature_usage.bulk_memory = true;
         // Note: the count passed in here is the *total* segment count
         // There is no way to reserve for just the passive segments as they are discovered when iterating the data section entries
         // Given that the total segment count might be much larger than the passive count, do not reserve
@@ -834,6 +1004,7 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
     }
 
     fn declare_passive_data(&mut self, data_index: DataIndex, data: &'data [u8]) -> WasmResult<()> {
+        self.result.module.feature_usage.bulk_memory = true;
         let index = self.result.module.passive_data.len();
         self.result.module.passive_data.push(Arc::from(data));
         let old = self
@@ -900,6 +1071,10 @@ and for re-adding support for interface types you can see this issue:
                 .to_owned(),
             )),
 
+            "wasmtime.yieldpoints" if self.tunables.yieldpoints => {
+                self.register_yieldpoints_section(data)
+            }
+
             // skip other sections
             _ => Ok(()),
         }
@@ -910,6 +1085,7 @@ and for re-adding support for interface types you can see this issue:
     }
 
     fn reserve_modules(&mut self, amount: u32) {
+        self.result.module.feature_usage.module_linking = true;
         // Go ahead and reserve space in the final `results` array for `amount`
         // more modules.
         self.modules_to_be += amount as usize;
@@ -935,6 +1111,10 @@ and for re-adding support for interface types you can see this issue:
     }
 
     fn module_end(&mut self) {
+        self.make_non_growing_memories_static();
+        let module = &mut self.result.module;
+        let (tables, memories) = (module.table_plans.len(), module.memory_plans.len());
+        module.feature_usage.record_counts(tables, memories);
         self.result.creation_artifacts.shrink_to_fit();
         self.result.creation_modules.shrink_to_fit();
 
@@ -944,6 +1124,12 @@ and for re-adding support for interface types you can see this issue:
         };
 
         if record_initializer {
+            // A module's usage includes that of the modules nested in it.
+            self.result
+                .module
+                .feature_usage
+                .merge(&done.module.feature_usage);
+
             // Record the type of the module we just finished in our own
             // module's list of modules.
             let sig = self.gen_type_of_module(&done.module);
@@ -975,6 +1161,7 @@ and for re-adding support for interface types you can see this issue:
     }
 
     fn reserve_instances(&mut self, amt: u32) {
+        self.result.module.feature_usage.module_linking = true;
         self.result.module.instances.reserve(amt as usize);
         self.result.module.initializers.reserve(amt as usize);
     }
@@ -999,6 +1186,7 @@ and for re-adding support for interface types you can see this issue:
     }
 
     fn declare_alias(&mut self, alias: Alias) -> WasmResult<()> {
+        self.result.module.feature_usage.module_linking = true;
         match alias {
             // Types are easy, we statically know everything so we're just
             // copying some pointers from our parent module to our own module.
@@ -1,3 +1,4 @@
+use crate::Module;
 use serde::{Deserialize, Serialize};
 
 /// Tunable parameters for WebAssembly compilation.
@@ -30,12 +31,64 @@ pub struct Tunables {
     /// will be consumed every time a wasm instruction is executed.
     pub consume_fuel: bool,
 
+    /// Whether or not generated code checks the engine's epoch against the
+    /// store's epoch deadline on function entry and in loops.
+    pub epoch_interruption: bool,
+
+    /// Whether or not the fuel consumed by each function is also reported to
+    /// the store, when fuel is enabled.
+    pub per_function_fuel_accounting: bool,
+
+    /// Whether or not a `wasmtime.yieldpoints` custom section, if present,
+    /// replaces loop headers as the places where interrupt and fuel checks
+    /// are emitted.
+    pub yieldpoints: bool,
+
     /// Whether or not to treat the static memory bound as the maximum for unbounded heaps.
     pub static_memory_bound_is_maximum: bool,
 
     /// Whether or not linear memory allocations will have a guard region at the
     /// beginning of the allocation in addition to the end.
     pub guard_before_linear_memory: bool,
+
+    /// The size of the guard region at the beginning of linear memory
+    /// allocations, if it's different from that of the guard region at their
+    /// end.
+    pub guard_before_linear_memory_size: Option<u64>,
+
+    /// Whether or not compiled modules keep the wasm offset of each
+    /// instruction for backtraces, rather than just that of each function.
+    pub instruction_address_maps: bool,
+
+    /// Whether or not to omit the bounds checks of memory accesses which
+    /// provably stay within a memory's guaranteed size plus its guard region.
+    pub elide_guarded_bounds_checks: bool,
+
+    /// Whether or not scalar loads and stores of 32-bit memories are routed
+    /// through the store so that it can emulate registered virtual pages.
+    pub memory_access_hooks: bool,
+
+    /// The maximum number of memories, imported or defined, a module may
+    /// declare.
+    pub max_memories: u32,
+
+    /// The maximum number of tables, imported or defined, a module may
+    /// declare.
+    pub max_tables: u32,
+
+    /// The maximum sum of the minimum sizes, in wasm pages, of the memories a
+    /// module defines.
+    pub max_memory_pages: u64,
+
+    /// The maximum sum of the minimum sizes, in elements, of the tables a
+    /// module defines.
+    pub max_table_elements: u64,
+
+    /// The maximum number of parameters a function type may have.
+    pub max_func_params: u32,
+
+    /// The maximum number of results a function type may have.
+    pub max_func_results: u32,
 }
 
 impl Default for Tunables {
@@ -70,8 +123,102 @@ impl Default for Tunables {
             parse_wasm_debuginfo: true,
             interruptable: false,
             consume_fuel: false,
+            epoch_interruption: false,
+            per_function_fuel_accounting: false,
+            yieldpoints: false,
             static_memory_bound_is_maximum: false,
             guard_before_linear_memory: true,
+            guard_before_linear_memory_size: None,
+            instruction_address_maps: true,
+            elide_guarded_bounds_checks: false,
+            memory_access_hooks: false,
+            max_memories: u32::MAX,
+            max_tables: u32::MAX,
+            max_memory_pages: u64::MAX,
+            // Tables are allocated in full when they're created, so by default
+            // don't allow modules to ask for more elements than the JS API
+            // permits a table to have.
+            max_table_elements: 10_000_000,
+            // The same limits as the JS API, which wasmparser also enforces
+            // on the types of modules.
+            max_func_params: 1000,
+            max_func_results: 1000,
+        }
+    }
+}
+
+impl Tunables {
+    /// Checks the memories and tables declared by `module` against the
+    /// `max_*` limits above, returning a description of the first limit which
+    /// is exceeded.
+    ///
+    /// This is used both while translating a module and when loading a
+    /// previously compiled one, so the two can't disagree.
+    pub fn check_module_limits(&self, module: &Module) -> Result<(), String> {
+        let memories = module.memory_plans.len();
+        if memories > self.max_memories as usize {
+            return Err(format!(
+                "module declares {} memories, exceeding the limit of {}",
+                memories, self.max_memories
+            ));
+        }
+        let tables = module.table_plans.len();
+        if tables > self.max_tables as usize {
+            return Err(format!(
+                "module declares {} tables, exceeding the limit of {}",
+                tables, self.max_tables
+            ));
+        }
+        let memory_pages: u64 = module
+            .memory_plans
+            .values()
+            .skip(module.num_imported_memories)
+            .map(|plan| u64::from(plan.memory.minimum))
+            .sum();
+        if memory_pages > self.max_memory_pages {
+            return Err(format!(
+                "module's memories have a total minimum size of {} pages, \
+                 exceeding the limit of {}",
+                memory_pages, self.max_memory_pages
+            ));
+        }
+        let table_elements: u64 = module
+            .table_plans
+            .values()
+            .skip(module.num_imported_tables)
+            .map(|plan| u64::from(plan.table.minimum))
+            .sum();
+        if table_elements > self.max_table_elements {
+            return Err(format!(
+                "module's tables have a total minimum size of {} elements, \
+                 exceeding the limit of {}",
+                table_elements, self.max_table_elements
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks a function type with `params` parameters and `results` results
+    /// against the `max_func_*` limits above, returning a description of the
+    /// first limit which is exceeded.
+    ///
+    /// This applies to the types declared by modules as well as to the types
+    /// of host functions.
+    pub fn check_func_type_limits(&self, params: usize, results: usize) -> Result<(), String> {
+        if params > self.max_func_params as usize {
+            return Err(format!(
+                "function type has {} parameters and {} results, exceeding the \
+                 limit of {} parameters",
+                params, results, self.max_func_params
+            ));
+        }
+        if results > self.max_func_results as usize {
+            return Err(format!(
+                "function type has {} parameters and {} results, exceeding the \
+                 limit of {} results",
+                params, results, self.max_func_results
+            ));
         }
+        Ok(())
     }
 }
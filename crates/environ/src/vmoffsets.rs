@@ -490,6 +490,18 @@ impl<P: PtrSize> VMOffsets<P> {
     pub fn vminterrupts_fuel_consumed(&self) -> u8 {
         self.pointer_size()
     }
+
+    /// Return the offset of the `epoch_deadline` field of `VMInterrupts`
+    #[inline]
+    pub fn vminterrupts_epoch_deadline(&self) -> u8 {
+        self.vminterrupts_fuel_consumed() + 8
+    }
+
+    /// Return the offset of the `epoch_ptr` field of `VMInterrupts`
+    #[inline]
+    pub fn vminterrupts_epoch_ptr(&self) -> u8 {
+        self.vminterrupts_epoch_deadline() + 8
+    }
 }
 
 /// Offsets for `VMCallerCheckedAnyfunc`.
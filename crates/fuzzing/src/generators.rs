@@ -73,6 +73,7 @@ pub struct Config {
     static_memory_guard_size: Option<u32>,
     dynamic_memory_guard_size: Option<u32>,
     guard_before_linear_memory: bool,
+    elide_guarded_bounds_checks: bool,
 }
 
 impl Config {
@@ -84,6 +85,7 @@ impl Config {
             .static_memory_guard_size(self.static_memory_guard_size.unwrap_or(0).into())
             .dynamic_memory_guard_size(self.dynamic_memory_guard_size.unwrap_or(0).into())
             .guard_before_linear_memory(self.guard_before_linear_memory)
+            .elide_guarded_bounds_checks(self.elide_guarded_bounds_checks)
             .cranelift_nan_canonicalization(self.canonicalize_nans)
             .cranelift_opt_level(self.opt_level.to_wasmtime())
             .interruptable(self.interruptable)
@@ -9,13 +9,16 @@ use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 use std::mem;
 use wasmparser::WasmFeatures;
+#[cfg(feature = "cache")]
+use wasmtime_cache::CacheConfig;
 use wasmtime_debug::{emit_dwarf, DwarfSection};
 use wasmtime_environ::entity::EntityRef;
 use wasmtime_environ::isa::{TargetFrontendConfig, TargetIsa};
-use wasmtime_environ::wasm::{DefinedMemoryIndex, MemoryIndex};
+use wasmtime_environ::wasm::{DefinedFuncIndex, DefinedMemoryIndex, MemoryIndex};
 use wasmtime_environ::{
-    CompiledFunctions, Compiler as EnvCompiler, DebugInfoData, Module, ModuleMemoryOffset,
-    ModuleTranslation, Tunables, TypeTables, VMOffsets,
+    CompileError, CompiledFunction, CompiledFunctions, Compiler as EnvCompiler, DebugInfoData,
+    FunctionBodyData, Module, ModuleMemoryOffset, ModuleTranslation, Tunables, TypeTables,
+    VMOffsets,
 };
 
 /// Select which kind of compilation to use.
@@ -42,12 +45,20 @@ pub enum CompilationStrategy {
 /// TODO: Consider using cranelift-module.
 pub struct Compiler {
     isa: Box<dyn TargetIsa>,
+    trampoline_isa: Option<Box<dyn TargetIsa>>,
     compiler: Box<dyn EnvCompiler>,
     strategy: CompilationStrategy,
     tunables: Tunables,
     features: WasmFeatures,
+    #[cfg_attr(not(feature = "cache"), allow(dead_code))]
+    function_cache: Option<FunctionCacheConfig>,
 }
 
+#[cfg(feature = "cache")]
+type FunctionCacheConfig = CacheConfig;
+#[cfg(not(feature = "cache"))]
+type FunctionCacheConfig = ();
+
 impl Compiler {
     /// Construct a new `Compiler`.
     pub fn new(
@@ -58,6 +69,7 @@ impl Compiler {
     ) -> Self {
         Self {
             isa,
+            trampoline_isa: None,
             strategy,
             compiler: match strategy {
                 CompilationStrategy::Auto | CompilationStrategy::Cranelift => {
@@ -68,8 +80,29 @@ impl Compiler {
             },
             tunables,
             features,
+            function_cache: None,
         }
     }
+
+    /// Enables caching of individual compiled functions in the directory
+    /// configured by `cache_config`.
+    ///
+    /// Functions whose body and surrounding module context are unchanged are
+    /// loaded from the cache instead of being recompiled. This has no effect
+    /// if `cache_config` isn't enabled or when native debug info is generated.
+    #[cfg(feature = "cache")]
+    pub fn enable_function_cache(&mut self, cache_config: CacheConfig) {
+        self.function_cache = Some(cache_config);
+    }
+
+    /// Compiles the trampolines used to call a module's functions from the
+    /// host with `isa`, rather than the isa its functions are compiled with.
+    ///
+    /// `isa` must target the same platform with the same calling conventions;
+    /// this is meant for using different optimization settings.
+    pub fn set_trampoline_isa(&mut self, isa: Box<dyn TargetIsa>) {
+        self.trampoline_isa = Some(isa);
+    }
 }
 
 fn _assert_compiler_send_sync() {
@@ -109,6 +142,11 @@ impl Compiler {
         self.isa.as_ref()
     }
 
+    /// Return the isa trampolines are compiled with.
+    pub fn trampoline_isa(&self) -> &dyn TargetIsa {
+        self.trampoline_isa.as_deref().unwrap_or(&*self.isa)
+    }
+
     /// Return the compiler's strategy.
     pub fn strategy(&self) -> CompilationStrategy {
         self.strategy
@@ -138,16 +176,7 @@ impl Compiler {
         let functions = mem::take(&mut translation.function_body_inputs);
         let functions = functions.into_iter().collect::<Vec<_>>();
         let funcs = maybe_parallel!(functions.(into_iter | into_par_iter))
-            .map(|(index, func)| {
-                self.compiler.compile_function(
-                    translation,
-                    index,
-                    func,
-                    &*self.isa,
-                    &self.tunables,
-                    types,
-                )
-            })
+            .map(|(index, func)| self.compile_function(translation, index, func, types))
             .collect::<Result<Vec<_>, _>>()?
             .into_iter()
             .collect::<CompiledFunctions>();
@@ -163,8 +192,14 @@ impl Compiler {
             vec![]
         };
 
-        let (obj, unwind_info) =
-            build_object(&*self.isa, &translation, types, &funcs, dwarf_sections)?;
+        let (obj, unwind_info) = build_object(
+            &*self.isa,
+            self.trampoline_isa(),
+            &translation,
+            types,
+            &funcs,
+            dwarf_sections,
+        )?;
 
         Ok(Compilation {
             obj,
@@ -172,6 +207,42 @@ impl Compiler {
             funcs,
         })
     }
+
+    fn compile_function(
+        &self,
+        translation: &ModuleTranslation,
+        index: DefinedFuncIndex,
+        func: FunctionBodyData<'_>,
+        types: &TypeTables,
+    ) -> Result<CompiledFunction, CompileError> {
+        // Functions compiled with native debug info depend on more than what
+        // the function cache hashes, so always compile those from scratch.
+        #[cfg(feature = "cache")]
+        if let Some(cache_config) = &self.function_cache {
+            if !self.tunables.generate_native_debuginfo {
+                return crate::function_cache::compile_function(
+                    self,
+                    cache_config,
+                    translation,
+                    index,
+                    func,
+                    types,
+                );
+            }
+        }
+        self.compile_function_uncached(translation, index, func, types)
+    }
+
+    pub(crate) fn compile_function_uncached(
+        &self,
+        translation: &ModuleTranslation,
+        index: DefinedFuncIndex,
+        func: FunctionBodyData<'_>,
+        types: &TypeTables,
+    ) -> Result<CompiledFunction, CompileError> {
+        self.compiler
+            .compile_function(translation, index, func, &*self.isa, &self.tunables, types)
+    }
 }
 
 impl Hash for Compiler {
@@ -182,6 +253,8 @@ impl Hash for Compiler {
             isa,
             tunables,
             features,
+            trampoline_isa: _,
+            function_cache: _,
         } = self;
 
         // Hash compiler's flags: compilation strategy, isa, frontend config,
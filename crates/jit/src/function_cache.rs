@@ -0,0 +1,131 @@
+//! Per-function caching of compiled code.
+//!
+//! The module-level cache in `wasmtime-cache` is keyed on the entire wasm
+//! binary, so editing a single function in a large module recompiles
+//! everything. When enabled, this cache sits underneath that and stores each
+//! function's `CompiledFunction` individually, keyed on:
+//!
+//! * the compiler's own hash (isa, flags, tunables, features, version),
+//! * the parts of the module which affect codegen of any function body
+//!   (types, function signatures, tables, memories, globals, import counts),
+//! * the function's index and the raw bytes of its body,
+//! * the function's yieldpoints, which decide where its interrupt, fuel and
+//!   epoch checks go.
+//!
+//! Only per-function codegen is cached. Linking, trampolines, and object
+//! emission always run fresh on the collected functions.
+//!
+//! Source locations in a function's address map are offsets into the whole
+//! wasm module, which shift whenever an earlier function changes size. They're
+//! stored relative to the start of the function body and rebased when loaded.
+
+use crate::compiler::Compiler;
+use std::hash::{Hash, Hasher};
+use wasmtime_cache::{CacheConfig, ModuleCacheEntry};
+use wasmtime_environ::ir::SourceLoc;
+use wasmtime_environ::wasm::{DefinedFuncIndex, WasmError};
+use wasmtime_environ::{
+    CompileError, CompiledFunction, FunctionBodyData, ModuleTranslation, TypeTables,
+};
+
+/// Compiles `input` with `compiler`, consulting `cache_config`'s cache
+/// directory first.
+pub(crate) fn compile_function(
+    compiler: &Compiler,
+    cache_config: &CacheConfig,
+    translation: &ModuleTranslation<'_>,
+    index: DefinedFuncIndex,
+    input: FunctionBodyData<'_>,
+    types: &TypeTables,
+) -> Result<CompiledFunction, CompileError> {
+    let mut reader = input.body.get_binary_reader();
+    let offset = reader.original_position() as u32;
+    let body = reader
+        .read_bytes(reader.bytes_remaining())
+        .map_err(|e| CompileError::Wasm(WasmError::from(e)))?;
+
+    let key = FunctionKey {
+        compiler,
+        translation,
+        types,
+        index,
+        body,
+        input,
+    };
+    let mut func =
+        ModuleCacheEntry::new("wasmtime-functions", cache_config).get_data(key, |key| {
+            let offset = key.input.body.get_binary_reader().original_position() as u32;
+            let mut func = key.compiler.compile_function_uncached(
+                key.translation,
+                key.index,
+                key.input,
+                key.types,
+            )?;
+            rebase_srclocs(&mut func, |loc| loc - offset);
+            Ok::<_, CompileError>(func)
+        })?;
+    rebase_srclocs(&mut func, |loc| loc + offset);
+    Ok(func)
+}
+
+/// Everything needed to compile a single function. Only the inputs which
+/// affect the generated code participate in the hash.
+struct FunctionKey<'a, 'data> {
+    compiler: &'a Compiler,
+    translation: &'a ModuleTranslation<'data>,
+    types: &'a TypeTables,
+    index: DefinedFuncIndex,
+    body: &'data [u8],
+    input: FunctionBodyData<'data>,
+}
+
+impl Hash for FunctionKey<'_, '_> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.compiler.hash(hasher);
+
+        let module = &self.translation.module;
+        module.num_imported_funcs.hash(hasher);
+        module.num_imported_tables.hash(hasher);
+        module.num_imported_memories.hash(hasher);
+        module.num_imported_globals.hash(hasher);
+        module.types.hash(hasher);
+        module.functions.hash(hasher);
+        module.table_plans.hash(hasher);
+        module.memory_plans.hash(hasher);
+        module.globals.hash(hasher);
+        self.types.wasm_signatures.hash(hasher);
+
+        self.index.hash(hasher);
+        self.body.hash(hasher);
+
+        // Like source locations, yieldpoints are offsets into the whole module,
+        // so they're hashed relative to the start of the body.
+        let start = self.input.body.get_binary_reader().original_position() as u32;
+        self.translation
+            .yieldpoints
+            .as_ref()
+            .map(|yieldpoints| {
+                yieldpoints
+                    .get(&module.func_index(self.index))
+                    .map_or(&[][..], |offsets| &offsets[..])
+                    .iter()
+                    .map(|offset| offset.wrapping_sub(start))
+                    .collect::<Vec<_>>()
+            })
+            .hash(hasher);
+    }
+}
+
+fn rebase_srclocs(func: &mut CompiledFunction, rebase: impl Fn(u32) -> u32) {
+    let rebase = |loc: &mut SourceLoc| {
+        if !loc.is_default() {
+            *loc = SourceLoc::new(rebase(loc.bits()));
+        }
+    };
+    let map = &mut func.address_map;
+    rebase(&mut map.start_srcloc);
+    rebase(&mut map.end_srcloc);
+    for inst in map.instructions.iter_mut() {
+        rebase(&mut inst.srcloc);
+    }
+}
@@ -14,15 +14,16 @@ use serde::{Deserialize, Serialize};
 use std::ops::Range;
 use std::sync::Arc;
 use thiserror::Error;
-use wasmtime_debug::create_gdbjit_image;
+use wasmtime_debug::{check_gdbjit_image, create_gdbjit_image};
 use wasmtime_environ::entity::PrimaryMap;
 use wasmtime_environ::isa::TargetIsa;
 use wasmtime_environ::wasm::{
     DefinedFuncIndex, InstanceTypeIndex, ModuleTypeIndex, SignatureIndex, WasmFuncType,
 };
 use wasmtime_environ::{
-    CompileError, DebugInfoData, FunctionAddressMap, InstanceSignature, Module, ModuleEnvironment,
-    ModuleSignature, ModuleTranslation, StackMapInformation, TrapInformation,
+    ir, BuiltinFunctionIndex, CompactFunctionAddressMap, CompileError, DebugInfoData,
+    InstanceSignature, Module, ModuleEnvironment, ModuleSignature, ModuleTranslation, Relocation,
+    RelocationTarget, StackMapInformation, TrapInformation,
 };
 use wasmtime_profiling::ProfilingAgent;
 use wasmtime_runtime::{GdbJitImageRegistration, InstantiationError, VMFunctionBody, VMTrampoline};
@@ -47,6 +48,11 @@ pub enum SetupError {
     /// Debug information generation error occurred.
     #[error("Debug information error")]
     DebugInfo(#[from] anyhow::Error),
+
+    /// The module isn't supported by the instance allocator, for example
+    /// because it declares more memories or tables than the allocator allows.
+    #[error(transparent)]
+    Allocator(anyhow::Error),
 }
 
 /// Contains all compilation artifacts.
@@ -66,6 +72,10 @@ pub struct CompilationArtifacts {
     funcs: PrimaryMap<DefinedFuncIndex, FunctionInfo>,
 
     /// Whether or not native debug information is available in `obj`
+    ///
+    /// The DWARF sections are part of `obj`, so the image registered with
+    /// native debuggers is recreated from them when these artifacts are loaded
+    /// from the cache or deserialized.
     native_debug_info_present: bool,
 
     /// Whether or not the original wasm module contained debug information that
@@ -99,10 +109,16 @@ impl CompilationArtifacts {
     /// The `use_paged_init` argument controls whether or not an attempt is made to
     /// organize linear memory initialization data as entire pages or to leave
     /// the memory initialization data as individual segments.
+    ///
+    /// Each translated module is passed to `validate` before any of the
+    /// functions are compiled, so that modules which could never be
+    /// instantiated, such as those exceeding the limits of the instance
+    /// allocator, are rejected without paying for their compilation.
     pub fn build(
         compiler: &Compiler,
         data: &[u8],
         use_paged_mem_init: bool,
+        validate: &dyn Fn(&Module) -> anyhow::Result<()>,
     ) -> Result<(usize, Vec<CompilationArtifacts>, TypeTables), SetupError> {
         let (main_module, translations, types) = ModuleEnvironment::new(
             compiler.frontend_config(),
@@ -112,6 +128,10 @@ impl CompilationArtifacts {
         .translate(data)
         .map_err(|error| SetupError::Compile(CompileError::Wasm(error)))?;
 
+        for translation in translations.iter() {
+            validate(&translation.module).map_err(SetupError::Allocator)?;
+        }
+
         let list = maybe_parallel!(translations.(into_iter | into_par_iter))
             .map(|mut translation| {
                 let Compilation {
@@ -146,9 +166,15 @@ impl CompilationArtifacts {
                     funcs: funcs
                         .into_iter()
                         .map(|(_, func)| FunctionInfo {
+                            libcalls: libcalls(&func.relocations),
+                            builtins: func.builtins,
                             stack_maps: func.stack_maps,
+                            call_sites: func.call_sites,
+                            direct_call_sites: func.direct_call_sites,
                             traps: func.traps,
-                            address_map: func.address_map,
+                            address_map: func
+                                .address_map
+                                .compress(compiler.tunables().instruction_address_maps),
                         })
                         .collect(),
                     native_debug_info_present: compiler.tunables().generate_native_debuginfo,
@@ -171,6 +197,42 @@ impl CompilationArtifacts {
             },
         ))
     }
+
+    /// Validates `data` in exactly the same manner as
+    /// [`CompilationArtifacts::build`], including validation of all function
+    /// bodies, but without generating any code.
+    ///
+    /// Errors returned here are the same as those that `build` would return
+    /// for the same input. On success the metadata of the main module is
+    /// returned.
+    pub fn validate(compiler: &Compiler, data: &[u8]) -> Result<Module, SetupError> {
+        let (main_module, translations, _types) = ModuleEnvironment::new(
+            compiler.frontend_config(),
+            compiler.tunables(),
+            compiler.features(),
+        )
+        .translate(data)
+        .map_err(|error| SetupError::Compile(CompileError::Wasm(error)))?;
+
+        let mut main = None;
+        for (i, translation) in translations.into_iter().enumerate() {
+            let ModuleTranslation {
+                module,
+                function_body_inputs,
+                ..
+            } = translation;
+            for (_, mut input) in function_body_inputs {
+                input
+                    .validator
+                    .validate(&input.body)
+                    .map_err(|error| SetupError::Compile(CompileError::Wasm(error.into())))?;
+            }
+            if i == main_module {
+                main = Some(module);
+            }
+        }
+        Ok(main.unwrap())
+    }
 }
 
 struct FinishedFunctions(PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>);
@@ -182,8 +244,19 @@ unsafe impl Sync for FinishedFunctions {}
 #[derive(Serialize, Deserialize, Clone)]
 pub struct FunctionInfo {
     pub traps: Vec<TrapInformation>,
-    pub address_map: FunctionAddressMap,
+    pub address_map: CompactFunctionAddressMap,
     pub stack_maps: Vec<StackMapInformation>,
+    /// The offsets of the return addresses of the function's calls, in
+    /// ascending order, or nothing if the compiler didn't record them.
+    pub call_sites: Vec<u32>,
+    /// The offsets of the return addresses of the function's direct calls to
+    /// functions defined in the same module, in ascending order.
+    pub direct_call_sites: Vec<u32>,
+    /// The libcalls the function's code is linked against, without duplicates.
+    pub libcalls: Vec<ir::LibCall>,
+    /// The runtime builtin functions the function's code may call, in
+    /// ascending order.
+    pub builtins: Vec<BuiltinFunctionIndex>,
 }
 
 /// This is intended to mirror the type tables in `wasmtime_environ`, except that
@@ -217,6 +290,7 @@ pub struct CompiledModule {
     code: Arc<ModuleCode>,
     finished_functions: FinishedFunctions,
     trampolines: Vec<(SignatureIndex, VMTrampoline)>,
+    trampoline_code_size: usize,
 }
 
 impl CompiledModule {
@@ -226,32 +300,39 @@ impl CompiledModule {
         artifacts: Vec<CompilationArtifacts>,
         isa: &dyn TargetIsa,
         profiler: &dyn ProfilingAgent,
+        native_debug_symbols: bool,
     ) -> Result<Vec<Arc<Self>>, SetupError> {
         maybe_parallel!(artifacts.(into_iter | into_par_iter))
-            .map(|a| CompiledModule::from_artifacts(a, isa, profiler))
+            .map(|a| CompiledModule::from_artifacts(a, isa, profiler, native_debug_symbols))
             .collect()
     }
 
     /// Creates `CompiledModule` directly from `CompilationArtifacts`.
+    ///
+    /// If `native_debug_symbols` is set then the compiled code is registered
+    /// with native debuggers, on a best-effort basis, even if no DWARF debug
+    /// information was generated for it.
     pub fn from_artifacts(
         artifacts: CompilationArtifacts,
         isa: &dyn TargetIsa,
         profiler: &dyn ProfilingAgent,
+        native_debug_symbols: bool,
     ) -> Result<Arc<Self>, SetupError> {
         // Allocate all of the compiled functions into executable memory,
         // copying over their contents.
-        let (code_memory, code_range, finished_functions, trampolines) = build_code_memory(
-            isa,
-            &artifacts.obj,
-            &artifacts.module,
-            &artifacts.unwind_info,
-        )
-        .map_err(|message| {
-            SetupError::Instantiate(InstantiationError::Resource(anyhow::anyhow!(
-                "failed to build code memory for functions: {}",
-                message
-            )))
-        })?;
+        let (code_memory, code_range, finished_functions, trampolines, trampoline_code_size) =
+            build_code_memory(
+                isa,
+                &artifacts.obj,
+                &artifacts.module,
+                &artifacts.unwind_info,
+            )
+            .map_err(|message| {
+                SetupError::Instantiate(InstantiationError::Resource(anyhow::anyhow!(
+                    "failed to build code memory for functions: {}",
+                    message
+                )))
+            })?;
 
         // Register GDB JIT images; initialize profiler and load the wasm module.
         let dbg_jit_registration = if artifacts.native_debug_info_present {
@@ -261,12 +342,32 @@ impl CompiledModule {
                 &artifacts.module,
                 &finished_functions,
             )?;
+            if cfg!(debug_assertions) {
+                if let Err(e) = check_gdbjit_image(&bytes, code_range) {
+                    panic!("invalid DWARF in the GDB JIT image: {:#}", e);
+                }
+            }
             profiler.module_load(&artifacts.module, &finished_functions, Some(&bytes));
             let reg = GdbJitImageRegistration::register(bytes);
             Some(reg)
         } else {
             profiler.module_load(&artifacts.module, &finished_functions, None);
-            None
+
+            // Without DWARF the object still contains a symbol for each
+            // function, so debuggers can at least name the frames. This is
+            // purely a debugging aid, so any failure here is ignored.
+            if native_debug_symbols {
+                create_dbg_image(
+                    artifacts.obj.to_vec(),
+                    code_range,
+                    &artifacts.module,
+                    &finished_functions,
+                )
+                .ok()
+                .map(GdbJitImageRegistration::register)
+            } else {
+                None
+            }
         };
 
         let finished_functions = FinishedFunctions(finished_functions);
@@ -282,6 +383,7 @@ impl CompiledModule {
             }),
             finished_functions,
             trampolines,
+            trampoline_code_size,
         }))
     }
 
@@ -311,6 +413,12 @@ impl CompiledModule {
         &self.trampolines
     }
 
+    /// Returns the total size, in bytes, of the code of this module's
+    /// trampolines.
+    pub fn trampoline_code_size(&self) -> usize {
+        self.trampoline_code_size
+    }
+
     /// Returns the stack map information for all functions defined in this
     /// module.
     ///
@@ -364,6 +472,12 @@ impl CompiledModule {
         Some((index, start, end))
     }
 
+    /// Returns the function information for all functions defined in this
+    /// module.
+    pub fn funcs(&self) -> &PrimaryMap<DefinedFuncIndex, FunctionInfo> {
+        &self.artifacts.funcs
+    }
+
     /// Gets the function information for a given function index.
     pub fn func_info(&self, index: DefinedFuncIndex) -> &FunctionInfo {
         self.artifacts
@@ -463,6 +577,19 @@ impl SymbolizeContext {
     }
 }
 
+/// Returns the libcalls targeted by `relocations`, without duplicates.
+fn libcalls(relocations: &[Relocation]) -> Vec<ir::LibCall> {
+    let mut libcalls = Vec::new();
+    for r in relocations {
+        if let RelocationTarget::LibCall(libcall) = r.reloc_target {
+            if !libcalls.contains(&libcall) {
+                libcalls.push(libcall);
+            }
+        }
+    }
+    libcalls
+}
+
 fn create_dbg_image(
     obj: Vec<u8>,
     code_range: (*const u8, usize),
@@ -488,6 +615,7 @@ fn build_code_memory(
         (*const u8, usize),
         PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
         Vec<(SignatureIndex, VMTrampoline)>,
+        usize,
     ),
     String,
 > {
@@ -519,7 +647,9 @@ fn build_code_memory(
 
     // Populate the trampolines from the allocation
     let mut trampolines = Vec::with_capacity(allocation.trampolines_len());
+    let mut trampoline_code_size = 0;
     for (i, fat_ptr) in allocation.trampolines() {
+        trampoline_code_size += fat_ptr.len();
         let fnptr =
             unsafe { std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(fat_ptr.as_ptr()) };
         trampolines.push((i, fnptr));
@@ -534,7 +664,13 @@ fn build_code_memory(
     // Make all code compiled thus far executable.
     code_memory.publish(isa);
 
-    Ok((code_memory, code_range, finished_functions, trampolines))
+    Ok((
+        code_memory,
+        code_range,
+        finished_functions,
+        trampolines,
+        trampoline_code_size,
+    ))
 }
 
 impl From<DebugInfoData<'_>> for DebugInfo {
@@ -36,8 +36,11 @@ macro_rules! maybe_parallel {
 
 mod code_memory;
 mod compiler;
+#[cfg(feature = "cache")]
+mod function_cache;
 mod instantiate;
 mod link;
+mod linkable;
 mod object;
 mod unwind;
 
@@ -50,6 +53,10 @@ pub use crate::instantiate::{
     CompilationArtifacts, CompiledModule, ModuleCode, SetupError, SymbolizeContext, TypeTables,
 };
 pub use crate::link::link_module;
+pub use crate::linkable::{
+    build_linkable_object, export_symbol_name, func_symbol_name, trampoline_symbol_name,
+    LinkableObject,
+};
 pub use wasmtime_cranelift::{blank_sig, wasmtime_call_conv};
 
 /// Version number of this crate.
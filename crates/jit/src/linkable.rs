@@ -0,0 +1,331 @@
+//! Compilation of a module to a relocatable object file which can be linked
+//! into a native program without the rest of the wasmtime runtime.
+//!
+//! Every defined function is exported from the object with a global symbol:
+//!
+//! * `wasm_func_<N>` for the function with index `N` in the module's function
+//!   index space (imports included).
+//! * `wasm_trampoline_<N>` for the host-to-wasm trampoline of the signature
+//!   with index `N`.
+//! * `wasm_export_<NAME>` for each exported function, where `NAME` is the
+//!   export's name with every character other than ASCII alphanumerics
+//!   replaced by `_`.
+//!
+//! Functions are called with the native wasm calling convention: the callee's
+//! `VMContext`, the caller's `VMContext`, and then the wasm parameters. The
+//! metadata produced alongside the object describes what that `VMContext` is
+//! expected to contain.
+
+use crate::compiler::{Compilation, Compiler};
+use crate::instantiate::SetupError;
+use object::write::{Object, Symbol, SymbolSection};
+use object::{SymbolFlags, SymbolKind, SymbolScope};
+use serde::Serialize;
+use wasmtime_environ::entity::EntityRef;
+use wasmtime_environ::wasm::{EntityIndex, FuncIndex, SignatureIndex};
+use wasmtime_environ::{
+    CompileError, CompiledFunctions, Initializer, MemoryStyle, Module, ModuleEnvironment, VMOffsets,
+};
+
+/// A relocatable object file along with a description of how to use it.
+pub struct LinkableObject {
+    /// The bytes of the relocatable object file.
+    pub object: Vec<u8>,
+    /// A JSON document describing the object's symbols, the imports and
+    /// memories the module requires, the layout of its `VMContext` and the
+    /// trap tables of its functions.
+    pub metadata: String,
+}
+
+/// Returns the global symbol name of the function with `index`.
+pub fn func_symbol_name(index: FuncIndex) -> String {
+    format!("wasm_func_{}", index.index())
+}
+
+/// Returns the global symbol name of the trampoline for the signature with
+/// `index`.
+pub fn trampoline_symbol_name(index: SignatureIndex) -> String {
+    format!("wasm_trampoline_{}", index.index())
+}
+
+/// Returns the global symbol name of the function exported as `name`.
+pub fn export_symbol_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!("wasm_export_{}", name)
+}
+
+/// Compiles the wasm module `data` to a relocatable object.
+///
+/// Modules using the module linking proposal to define nested modules are
+/// not supported.
+pub fn build_linkable_object(
+    compiler: &Compiler,
+    data: &[u8],
+) -> Result<LinkableObject, SetupError> {
+    let (_, mut translations, types) = ModuleEnvironment::new(
+        compiler.frontend_config(),
+        compiler.tunables(),
+        compiler.features(),
+    )
+    .translate(data)
+    .map_err(|error| SetupError::Compile(CompileError::Wasm(error)))?;
+
+    if translations.len() != 1 {
+        return Err(SetupError::Validate(
+            "modules defining nested modules cannot be compiled to an object file".to_string(),
+        ));
+    }
+    let mut translation = translations.pop().unwrap();
+
+    let Compilation { mut obj, funcs, .. } = compiler.compile(&mut translation, &types)?;
+    let module = &translation.module;
+
+    let mut exports = Vec::new();
+    for (name, index) in module.exports.iter() {
+        if let EntityIndex::Function(index) = index {
+            exports.push((name.as_str(), *index, export_symbol_name(name)));
+        }
+    }
+    for (i, (name, _, symbol)) in exports.iter().enumerate() {
+        if let Some((other, _, _)) = exports[..i].iter().find(|(_, _, s)| s == symbol) {
+            return Err(SetupError::Validate(format!(
+                "exports `{}` and `{}` would both be named `{}` in the object file",
+                other, name, symbol
+            )));
+        }
+    }
+
+    for (index, _) in funcs.iter() {
+        let index = module.func_index(index);
+        alias(
+            &mut obj,
+            &crate::object::utils::func_symbol_name(index),
+            func_symbol_name(index),
+        );
+    }
+    for (_, sig) in module.functions.iter() {
+        alias(
+            &mut obj,
+            &crate::object::utils::trampoline_symbol_name(*sig),
+            trampoline_symbol_name(*sig),
+        );
+    }
+    for (_, index, symbol) in exports.iter() {
+        if module.defined_func_index(*index).is_some() {
+            alias(
+                &mut obj,
+                &crate::object::utils::func_symbol_name(*index),
+                symbol.clone(),
+            );
+        }
+    }
+
+    let metadata = metadata(compiler, module, &funcs, &exports, &obj);
+    let object = obj.write().map_err(|e| {
+        SetupError::Compile(CompileError::Codegen(format!(
+            "failed to write object file: {}",
+            e
+        )))
+    })?;
+
+    Ok(LinkableObject { object, metadata })
+}
+
+/// Adds a global symbol `name` at the same location as the local symbol
+/// `target`, if `target` exists.
+fn alias(obj: &mut Object, target: &str, name: String) {
+    let target = match obj.symbol_id(target.as_bytes()) {
+        Some(id) => obj.symbol(id),
+        None => return,
+    };
+    if obj.symbol_id(name.as_bytes()).is_some() {
+        return;
+    }
+    let symbol = Symbol {
+        name: name.into_bytes(),
+        value: target.value,
+        size: target.size,
+        kind: SymbolKind::Text,
+        scope: SymbolScope::Linkage,
+        weak: false,
+        section: target.section,
+        flags: SymbolFlags::None,
+    };
+    debug_assert!(matches!(symbol.section, SymbolSection::Section(_)));
+    obj.add_symbol(symbol);
+}
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    imports: Vec<ImportMetadata<'a>>,
+    exports: Vec<ExportMetadata<'a>>,
+    memories: Vec<MemoryMetadata>,
+    vmctx: VMContextMetadata,
+    traps: Vec<FunctionTraps>,
+}
+
+#[derive(Serialize)]
+struct ImportMetadata<'a> {
+    module: &'a str,
+    field: Option<&'a str>,
+    kind: &'static str,
+    index: usize,
+    vmctx_offset: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct ExportMetadata<'a> {
+    name: &'a str,
+    func: usize,
+    symbol: Option<&'a str>,
+    trampoline: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MemoryMetadata {
+    index: usize,
+    imported: bool,
+    minimum: u32,
+    maximum: Option<u32>,
+    style: &'static str,
+    bound: Option<u32>,
+    pre_guard_size: u64,
+    offset_guard_size: u64,
+    vmctx_offset: u32,
+}
+
+#[derive(Serialize)]
+struct VMContextMetadata {
+    size: u32,
+    interrupts: u32,
+    interrupts_stack_limit: u8,
+    interrupts_fuel_consumed: u8,
+}
+
+#[derive(Serialize)]
+struct FunctionTraps {
+    symbol: String,
+    traps: Vec<TrapMetadata>,
+}
+
+#[derive(Serialize)]
+struct TrapMetadata {
+    offset: u32,
+    code: String,
+}
+
+fn metadata(
+    compiler: &Compiler,
+    module: &Module,
+    funcs: &CompiledFunctions,
+    exports: &[(&str, FuncIndex, String)],
+    obj: &Object,
+) -> String {
+    let offsets = VMOffsets::new(compiler.isa().pointer_bytes(), module);
+
+    let imports = module
+        .initializers
+        .iter()
+        .filter_map(|init| match init {
+            Initializer::Import { name, field, index } => Some((name, field, index)),
+            _ => None,
+        })
+        .map(|(name, field, index)| {
+            let (kind, index, vmctx_offset) = match index {
+                EntityIndex::Function(i) => {
+                    ("func", i.index(), Some(offsets.vmctx_vmfunction_import(*i)))
+                }
+                EntityIndex::Table(i) => {
+                    ("table", i.index(), Some(offsets.vmctx_vmtable_import(*i)))
+                }
+                EntityIndex::Memory(i) => {
+                    ("memory", i.index(), Some(offsets.vmctx_vmmemory_import(*i)))
+                }
+                EntityIndex::Global(i) => {
+                    ("global", i.index(), Some(offsets.vmctx_vmglobal_import(*i)))
+                }
+                EntityIndex::Module(i) => ("module", i.index(), None),
+                EntityIndex::Instance(i) => ("instance", i.index(), None),
+            };
+            ImportMetadata {
+                module: name,
+                field: field.as_deref(),
+                kind,
+                index,
+                vmctx_offset,
+            }
+        })
+        .collect();
+
+    let exports = exports
+        .iter()
+        .map(|(name, index, symbol)| {
+            let trampoline = trampoline_symbol_name(module.functions[*index]);
+            ExportMetadata {
+                name,
+                func: index.index(),
+                symbol: module.defined_func_index(*index).map(|_| symbol.as_str()),
+                trampoline: obj.symbol_id(trampoline.as_bytes()).map(|_| trampoline),
+            }
+        })
+        .collect();
+
+    let memories = module
+        .memory_plans
+        .iter()
+        .map(|(index, plan)| {
+            let (style, bound) = match plan.style {
+                MemoryStyle::Dynamic => ("dynamic", None),
+                MemoryStyle::Static { bound } => ("static", Some(bound)),
+            };
+            MemoryMetadata {
+                index: index.index(),
+                imported: module.is_imported_memory(index),
+                minimum: plan.memory.minimum,
+                maximum: plan.memory.maximum,
+                style,
+                bound,
+                pre_guard_size: plan.pre_guard_size,
+                offset_guard_size: plan.offset_guard_size,
+                vmctx_offset: match module.defined_memory_index(index) {
+                    Some(i) => offsets.vmctx_vmmemory_definition(i),
+                    None => offsets.vmctx_vmmemory_import(index),
+                },
+            }
+        })
+        .collect();
+
+    let traps = funcs
+        .iter()
+        .map(|(index, func)| FunctionTraps {
+            symbol: func_symbol_name(module.func_index(index)),
+            traps: func
+                .traps
+                .iter()
+                .map(|trap| TrapMetadata {
+                    offset: trap.code_offset,
+                    code: trap.trap_code.to_string(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let metadata = Metadata {
+        imports,
+        exports,
+        memories,
+        vmctx: VMContextMetadata {
+            size: offsets.size_of_vmctx(),
+            interrupts: offsets.vmctx_interrupts(),
+            interrupts_stack_limit: offsets.vminterrupts_stack_limit(),
+            interrupts_fuel_consumed: offsets.vminterrupts_fuel_consumed(),
+        },
+        traps,
+    };
+    let mut out = serde_json::to_string_pretty(&metadata).unwrap();
+    out.push('\n');
+    out
+}
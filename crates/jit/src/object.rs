@@ -23,6 +23,7 @@ pub enum ObjectUnwindInfo {
 // Builds ELF image from the module `Compilation`.
 pub(crate) fn build_object(
     isa: &dyn TargetIsa,
+    trampoline_isa: &dyn TargetIsa,
     translation: &ModuleTranslation,
     types: &TypeTables,
     funcs: &CompiledFunctions,
@@ -52,8 +53,13 @@ pub(crate) fn build_object(
     let mut trampolines = Vec::with_capacity(signatures.len());
     let mut cx = FunctionBuilderContext::new();
     for i in signatures {
-        let native_sig = wasmtime_cranelift::indirect_signature(isa, &types, i);
-        let func = build_trampoline(isa, &mut cx, &native_sig, std::mem::size_of::<u128>())?;
+        let native_sig = wasmtime_cranelift::indirect_signature(trampoline_isa, &types, i);
+        let func = build_trampoline(
+            trampoline_isa,
+            &mut cx,
+            &native_sig,
+            std::mem::size_of::<u128>(),
+        )?;
         // Preserve trampoline function unwind info.
         if let Some(info) = &func.unwind_info {
             unwind_info.push(ObjectUnwindInfo::Trampoline(i, info.clone()))
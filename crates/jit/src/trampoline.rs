@@ -4,6 +4,8 @@ use crate::code_memory::CodeMemory;
 use crate::instantiate::SetupError;
 use cranelift_codegen::ir::InstBuilder;
 use cranelift_codegen::isa::TargetIsa;
+use std::cmp;
+use std::convert::TryFrom;
 use wasmtime_environ::{CompileError, CompiledFunction, Relocation, RelocationTarget};
 use wasmtime_runtime::{InstantiationError, VMFunctionBody, VMTrampoline};
 
@@ -51,6 +53,21 @@ pub(crate) fn build_trampoline(
     signature: &ir::Signature,
     value_size: usize,
 ) -> Result<CompiledFunction, SetupError> {
+    // The arguments and results are stored at `value_size` intervals in
+    // `values_vec`, so the offsets of all of them need to fit in an `i32`.
+    let params = signature.params.len() - 2;
+    let results = signature.returns.len();
+    if cmp::max(params, results)
+        .checked_mul(value_size)
+        .and_then(|len| i32::try_from(len).ok())
+        .is_none()
+    {
+        return Err(SetupError::Compile(CompileError::Codegen(format!(
+            "function type with {} parameters and {} results is too large for a trampoline",
+            params, results
+        ))));
+    }
+
     let pointer_type = isa.pointer_type();
     let mut wrapper_sig =
         wasmtime_cranelift::blank_sig(isa, wasmtime_cranelift::wasmtime_call_conv(isa));
@@ -155,9 +172,12 @@ pub(crate) fn build_trampoline(
         unwind_info,
         relocations: reloc_sink.relocs,
         stack_maps: Default::default(),
+        call_sites: Default::default(),
+        direct_call_sites: Default::default(),
         stack_slots: Default::default(),
         traps: Default::default(),
         value_labels_ranges: Default::default(),
+        builtins: Default::default(),
         address_map: Default::default(),
     })
 }
@@ -7,6 +7,8 @@ use cranelift_codegen::binemit;
 use cranelift_codegen::ir::{self, ExternalName};
 use cranelift_codegen::isa;
 use lightbeam::{CodeGenSession, NullOffsetSink, Sinks};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
 use wasmtime_environ::wasm::{
     DefinedFuncIndex, DefinedGlobalIndex, DefinedMemoryIndex, DefinedTableIndex, FuncIndex,
     GlobalIndex, MemoryIndex, TableIndex, TypeIndex,
@@ -69,10 +71,13 @@ impl Compiler for Lightbeam {
             // not implemented for lightbeam currently
             unwind_info: None,
             stack_maps: Default::default(),
+            call_sites: Default::default(),
+            direct_call_sites: Default::default(),
             stack_slots: Default::default(),
             value_labels_ranges: Default::default(),
             address_map: Default::default(),
             jt_offsets: Default::default(),
+            builtins: env.builtins_used.into_inner().into_iter().collect(),
         })
     }
 }
@@ -176,6 +181,9 @@ struct FuncEnvironment<'module_environment> {
 
     /// Offsets to struct fields accessed by JIT code.
     offsets: VMOffsets<u8>,
+
+    /// The builtin functions which the function being translated calls.
+    builtins_used: RefCell<BTreeSet<BuiltinFunctionIndex>>,
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
@@ -183,6 +191,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         Self {
             module: &translation.module,
             offsets: VMOffsets::new(pointer_bytes, &translation.module),
+            builtins_used: RefCell::new(BTreeSet::new()),
         }
     }
 }
@@ -237,8 +246,9 @@ impl lightbeam::ModuleContext for FuncEnvironment<'_> {
     }
 
     fn vmctx_builtin_function(&self, func_index: u32) -> u32 {
-        self.offsets
-            .vmctx_builtin_function(BuiltinFunctionIndex::from_u32(func_index))
+        let index = BuiltinFunctionIndex::from_u32(func_index);
+        self.builtins_used.borrow_mut().insert(index);
+        self.offsets.vmctx_builtin_function(index)
     }
 
     fn vmctx_vmfunction_import_body(&self, func_index: u32) -> u32 {
@@ -22,14 +22,14 @@
 //! up and referenced by one or more `VMExternRef`s:
 //!
 //! ```text
-//!      ,-------------------------------------------------------.
-//!      |                                                       |
-//!      V                                                       |
-//!     +----------------------------+-----------+-----------+   |
-//!     | dynamically-sized value... | ref_count | value_ptr |---'
-//!     +----------------------------+-----------+-----------+
-//!                                  | VMExternData          |
-//!                                  +-----------------------+
+//!      ,-------------------------------------------------------------------.
+//!      |                                                                   |
+//!      V                                                                   |
+//!     +----------------------------+-----------+------------+-----------+  |
+//!     | dynamically-sized value... | ref_count | weak_count | value_ptr |--'
+//!     +----------------------------+-----------+------------+-----------+
+//!                                  | VMExternData                       |
+//!                                  +------------------------------------+
 //!                                   ^
 //! +-------------+                   |
 //! | VMExternRef |-------------------+
@@ -98,6 +98,18 @@
 //! For more general information on deferred reference counting, see *An
 //! Examination of Deferred Reference Counting and Cycle Detection* by Quinane:
 //! <https://openresearch-repository.anu.edu.au/bitstream/1885/42030/2/hon-thesis.pdf>
+//!
+//! ## Weak References
+//!
+//! `VMExternWeakRef`s work like `std::sync::Weak`: they count towards the
+//! `weak_count` rather than the `ref_count`, so they don't keep the value
+//! alive, only the heap allocation. When the last strong reference goes away,
+//! whether it's dropped by the host, by compiled Wasm code, or by a GC sweeping
+//! the `VMExternRefActivationsTable`, the value is dropped but the allocation
+//! is only freed once the last weak reference is dropped too. All the strong
+//! references together hold one weak reference, so that the allocation isn't
+//! freed from under a weak reference being dropped concurrently with the last
+//! strong one.
 
 use std::any::Any;
 use std::cell::UnsafeCell;
@@ -186,6 +198,11 @@ pub(crate) struct VMExternData {
     /// currently always zero.
     ref_count: AtomicUsize,
 
+    /// The number of `VMExternWeakRef`s to this `VMExternData`, plus one if
+    /// `ref_count` isn't zero. When it reaches zero, we can safely free this
+    /// heap allocation, whose value has already been destroyed.
+    weak_count: AtomicUsize,
+
     /// Always points to the implicit, dynamically-sized `value` member that
     /// precedes this `VMExternData`.
     value_ptr: NonNull<dyn Any + Send + Sync>,
@@ -248,26 +265,46 @@ impl VMExternData {
         )
     }
 
-    /// Drop the inner value and then free this `VMExternData` heap allocation.
+    /// Drop the inner value and then free this `VMExternData` heap allocation,
+    /// unless there are still weak references to it.
     pub(crate) unsafe fn drop_and_dealloc(mut data: NonNull<VMExternData>) {
+        {
+            let data = data.as_mut();
+            debug_assert_eq!(data.ref_count.load(Ordering::SeqCst), 0);
+            ptr::drop_in_place(data.value_ptr.as_ptr());
+        }
+
+        // Release the weak reference collectively held by the strong
+        // references.
+        Self::release_weak(data);
+    }
+
+    /// Decrement the weak reference count, and free this `VMExternData` heap
+    /// allocation if that was the last one.
+    ///
+    /// The value must have already been dropped.
+    unsafe fn release_weak(data: NonNull<VMExternData>) {
+        // See `VMExternRef::drop` for the orderings.
+        if data.as_ref().weak_count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+
         // Note: we introduce a block scope so that we drop the live
         // reference to the data before we free the heap allocation it
         // resides within after this block.
         let (alloc_ptr, layout) = {
-            let data = data.as_mut();
-            debug_assert_eq!(data.ref_count.load(Ordering::SeqCst), 0);
+            let data = data.as_ref();
 
-            // Same thing, but for the dropping the reference to `value` before
-            // we drop it itself.
+            // Only the vtable of `value_ptr` is used to get the value's size
+            // and alignment here, so it doesn't matter that it's been dropped
+            // already.
             let (layout, _) = {
                 let value = data.value_ptr.as_ref();
                 Self::layout_for(mem::size_of_val(value), mem::align_of_val(value))
             };
 
-            ptr::drop_in_place(data.value_ptr.as_ptr());
-            let alloc_ptr = data.value_ptr.cast::<u8>();
-
-            (alloc_ptr, layout)
+            (data.value_ptr.cast::<u8>(), layout)
         };
 
         ptr::drop_in_place(data.as_ptr());
@@ -326,6 +363,7 @@ impl VMExternRef {
                 extern_data_ptr,
                 VMExternData {
                     ref_count: AtomicUsize::new(1),
+                    weak_count: AtomicUsize::new(1),
                     // Cast from `*mut T` to `*mut dyn Any` here.
                     value_ptr: NonNull::new_unchecked(value_ptr.as_ptr()),
                 },
@@ -402,12 +440,87 @@ impl VMExternRef {
         self.extern_data().ref_count.load(Ordering::SeqCst)
     }
 
+    /// Create a weak reference to this `VMExternRef`'s value.
+    ///
+    /// The weak reference doesn't keep the value alive, but can be upgraded
+    /// back into a `VMExternRef` for as long as something else does.
+    pub fn downgrade(&self) -> VMExternWeakRef {
+        // Relaxed for the same reasons as `increment_ref_count`.
+        self.extern_data()
+            .weak_count
+            .fetch_add(1, Ordering::Relaxed);
+        VMExternWeakRef(self.0)
+    }
+
+    #[inline]
+    fn extern_data(&self) -> &VMExternData {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+/// A weak reference to the value of a `VMExternRef`.
+///
+/// Weak references don't keep the value alive: once all `VMExternRef`s to it
+/// are gone, `upgrade` returns `None`. They do keep the heap allocation alive,
+/// so a weak reference's pointer is never reused for another value while the
+/// weak reference exists.
+#[derive(Debug)]
+pub struct VMExternWeakRef(NonNull<VMExternData>);
+
+// Like `VMExternRef`, and only the reference counts are accessed through a
+// weak reference.
+unsafe impl Send for VMExternWeakRef {}
+unsafe impl Sync for VMExternWeakRef {}
+
+impl VMExternWeakRef {
+    /// Get a `VMExternRef` to the value, if it's still alive.
+    pub fn upgrade(&self) -> Option<VMExternRef> {
+        let ref_count = &self.extern_data().ref_count;
+        let mut n = ref_count.load(Ordering::Relaxed);
+        loop {
+            // Once the count has reached zero the value has been (or is being)
+            // dropped, and it must not be resurrected.
+            if n == 0 {
+                return None;
+            }
+            match ref_count.compare_exchange_weak(n, n + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return Some(VMExternRef(self.0)),
+                Err(old) => n = old,
+            }
+        }
+    }
+
+    /// Does this weak reference point to the same allocation as `strong`?
+    #[inline]
+    pub fn ptr_eq(&self, strong: &VMExternRef) -> bool {
+        ptr::eq(self.0.as_ptr(), strong.0.as_ptr())
+    }
+
     #[inline]
     fn extern_data(&self) -> &VMExternData {
         unsafe { self.0.as_ref() }
     }
 }
 
+impl Clone for VMExternWeakRef {
+    #[inline]
+    fn clone(&self) -> VMExternWeakRef {
+        self.extern_data()
+            .weak_count
+            .fetch_add(1, Ordering::Relaxed);
+        VMExternWeakRef(self.0)
+    }
+}
+
+impl Drop for VMExternWeakRef {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            VMExternData::release_weak(self.0);
+        }
+    }
+}
+
 /// Methods that would normally be trait implementations, but aren't to avoid
 /// potential footguns around `VMExternRef`'s pointer-equality semantics.
 ///
@@ -718,6 +831,18 @@ impl VMExternRefActivationsTable {
         self.precise_stack_roots.clear();
     }
 
+    /// Drops every reference held by this table, keeping its allocations
+    /// around for reuse.
+    ///
+    /// This must not be called while wasm is running, since references on the
+    /// stack would no longer be rooted.
+    pub fn clear(&mut self) {
+        // Sweeping with no precise stack roots empties the bump chunk and
+        // leaves the over-approximated set empty too.
+        self.precise_stack_roots.clear();
+        self.sweep();
+    }
+
     /// Fetches the current value of this table's stack canary.
     ///
     /// This should only be used in conjunction with setting the stack canary
@@ -755,6 +880,14 @@ pub trait ModuleInfoLookup {
 pub trait ModuleInfo {
     /// Lookup the stack map at a program counter value.
     fn lookup_stack_map(&self, pc: usize) -> Option<&StackMap>;
+
+    /// Returns `false` if `pc` is within one of the module's functions but
+    /// isn't the return address of any of that function's calls.
+    ///
+    /// Wasm frames are always in the middle of a call while collecting
+    /// garbage, so such a `pc` means that the safepoint table doesn't cover
+    /// the frame and its stack map may be missing.
+    fn is_call_site(&self, pc: usize) -> bool;
 }
 
 #[derive(Debug, Default)]
@@ -854,6 +987,10 @@ pub unsafe fn gc(
     // Whether we have found our stack canary or not yet.
     let mut found_canary = false;
 
+    // Whether we have found a Wasm frame which the safepoint table doesn't
+    // cover, and whose stack map may therefore be missing.
+    let mut missing_stack_map = false;
+
     // The `activations_table_set` is used for `debug_assert!`s checking that
     // every reference we read out from the stack via stack maps is actually in
     // the table. If that weren't true, than either we forgot to insert a
@@ -871,7 +1008,21 @@ pub unsafe fn gc(
         let sp = frame.sp() as usize;
 
         if let Some(module_info) = module_info_lookup.lookup(pc) {
-            if let Some(stack_map) = module_info.lookup_stack_map(pc) {
+            // A Wasm frame without a stack map is treated as holding no
+            // references. Cranelift emits a safepoint before every call across
+            // which references are live, so this is the case for calls made
+            // while none are. A frame which isn't at any of its function's
+            // calls can't be described by a safepoint though, so its stack map
+            // may be missing, and it makes this collection conservative.
+            if !module_info.is_call_site(pc) {
+                log::warn!("Wasm frame at pc {:#x} isn't at a call site", pc);
+                missing_stack_map = true;
+            }
+            let stack_map = module_info.lookup_stack_map(pc);
+            if stack_map.is_none() {
+                log::trace!("no stack map for Wasm frame at pc {:#x}", pc);
+            }
+            if let Some(stack_map) = stack_map {
                 debug_assert!(sp != 0, "we should always get a valid SP for Wasm frames");
 
                 for i in 0..(stack_map.mapped_words() as usize) {
@@ -918,11 +1069,16 @@ pub unsafe fn gc(
     // roots. If we did *not* find the stack canary, then `libunwind` failed to
     // walk the whole stack, and we might be missing roots. Reseting the table
     // would free those missing roots while they are still in use, leading to
-    // use-after-free.
-    if found_canary {
+    // use-after-free. The same goes for Wasm frames which the safepoint table
+    // doesn't cover.
+    if found_canary && !missing_stack_map {
         externref_activations_table.sweep();
     } else {
-        log::warn!("did not find stack canary; skipping GC sweep");
+        if !found_canary {
+            log::warn!("did not find stack canary; skipping GC sweep");
+        } else {
+            log::warn!("found Wasm frames not at call sites; skipping GC sweep");
+        }
         externref_activations_table.precise_stack_roots.clear();
     }
 
@@ -948,6 +1104,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn weak_ref_does_not_keep_value_alive() {
+        struct CountDrops(Arc<AtomicUsize>);
+        impl Drop for CountDrops {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let strong = VMExternRef::new(CountDrops(drops.clone()));
+        let weak = strong.downgrade();
+        let weak2 = weak.clone();
+        assert!(weak.ptr_eq(&strong));
+
+        let upgraded = weak.upgrade().unwrap();
+        assert!(VMExternRef::eq(&upgraded, &strong));
+        assert_eq!(strong.strong_count(), 2);
+        drop(upgraded);
+        drop(strong);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        assert!(weak.upgrade().is_none());
+        drop(weak);
+        assert!(weak2.upgrade().is_none());
+        drop(weak2);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn ref_count_is_at_correct_offset() {
         let s = "hi";
@@ -957,6 +1142,7 @@ mod tests {
 
         let extern_data = VMExternData {
             ref_count: AtomicUsize::new(0),
+            weak_count: AtomicUsize::new(0),
             value_ptr: NonNull::new(s).unwrap(),
         };
 
@@ -215,6 +215,16 @@ impl Instance {
         }
     }
 
+    /// Get a pointer to the `VMMemoryDefinition` of a locally defined or
+    /// imported memory.
+    pub(crate) fn get_memory_ptr(&self, index: MemoryIndex) -> *mut VMMemoryDefinition {
+        if let Some(defined_index) = self.module.defined_memory_index(index) {
+            self.memory_ptr(defined_index)
+        } else {
+            self.imported_memory(index).from
+        }
+    }
+
     /// Return the indexed `VMMemoryDefinition`.
     fn memory(&self, index: DefinedMemoryIndex) -> VMMemoryDefinition {
         unsafe { *self.memory_ptr(index) }
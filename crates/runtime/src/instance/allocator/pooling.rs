@@ -15,6 +15,7 @@ use super::{
 use crate::{instance::Instance, Memory, Mmap, Table, VMContext};
 use anyhow::{anyhow, bail, Context, Result};
 use rand::Rng;
+use std::cmp;
 use std::convert::TryFrom;
 use std::marker;
 use std::mem;
@@ -388,6 +389,7 @@ impl InstancePool {
             instance,
             self.memories.get(index),
             self.memories.max_wasm_pages,
+            self.memories.memory_size,
             borrow_limiter(&mut limiter),
         )?;
 
@@ -500,6 +502,7 @@ impl InstancePool {
         instance: &mut Instance,
         mut memories: impl Iterator<Item = *mut u8>,
         max_pages: u32,
+        memory_size: usize,
         mut limiter: Option<&mut dyn ResourceLimiter>,
     ) -> Result<(), InstantiationError> {
         let module = instance.module.as_ref();
@@ -509,6 +512,21 @@ impl InstancePool {
         for plan in
             (&module.memory_plans.values().as_slice()[module.num_imported_memories..]).iter()
         {
+            // Compiled code may rely on everything up to the end of the guard
+            // region being reserved, both for accesses it bounds checks only
+            // partially and for those whose checks are elided entirely, so
+            // make sure this memory's slot covers that much.
+            if let MemoryStyle::Static { bound } = plan.style {
+                debug_assert!(
+                    u64::from(bound) * u64::from(WASM_PAGE_SIZE) + plan.offset_guard_size
+                        <= memory_size as u64,
+                    "memory plan with a bound of {} pages and a {} byte guard region \
+                     doesn't fit in a {} byte slot",
+                    bound,
+                    plan.offset_guard_size,
+                    memory_size
+                );
+            }
             let memory = unsafe {
                 std::slice::from_raw_parts_mut(
                     memories.next().unwrap(),
@@ -625,10 +643,21 @@ impl MemoryPool {
             );
         }
 
+        let pre_guard_size = if tunables.guard_before_linear_memory {
+            tunables
+                .guard_before_linear_memory_size
+                .unwrap_or(tunables.static_memory_offset_guard_size)
+        } else {
+            0
+        };
+
+        // Each memory is followed by the guard region after it, which is also
+        // the guard region before the next memory, so it needs to be large
+        // enough for both.
         let memory_size = if module_limits.memory_pages > 0 {
             usize::try_from(
                 u64::from(tunables.static_memory_bound) * u64::from(WASM_PAGE_SIZE)
-                    + tunables.static_memory_offset_guard_size,
+                    + cmp::max(tunables.static_memory_offset_guard_size, pre_guard_size),
             )
             .map_err(|_| anyhow!("memory reservation size exceeds addressable memory"))?
         } else {
@@ -643,11 +672,8 @@ impl MemoryPool {
 
         let max_instances = instance_limits.count as usize;
         let max_memories = module_limits.memories as usize;
-        let initial_memory_offset = if tunables.guard_before_linear_memory {
-            usize::try_from(tunables.static_memory_offset_guard_size).unwrap()
-        } else {
-            0
-        };
+        let initial_memory_offset = usize::try_from(pre_guard_size)
+            .map_err(|_| anyhow!("memory guard size exceeds addressable memory"))?;
 
         // The entire allocation here is the size of each memory times the
         // max memories per instance times the number of instances allowed in
@@ -661,7 +687,7 @@ impl MemoryPool {
         // specially for the first linear memory, hence the
         // `initial_memory_offset` variable here. If guards aren't specified
         // before linear memories this is set to `0`, otherwise it's set to
-        // the same size as guard regions for other memories.
+        // the size of the guard regions before memories.
         let allocation_size = memory_size
             .checked_mul(max_memories)
             .and_then(|c| c.checked_mul(max_instances))
@@ -1118,6 +1144,7 @@ mod test {
                 minimum: 0,
                 maximum: None,
                 shared: false,
+                memory64: false,
             },
             pre_guard_size: 0,
             offset_guard_size: 0,
@@ -1234,6 +1261,7 @@ mod test {
                 minimum: 0,
                 maximum: None,
                 shared: false,
+                memory64: false,
             },
             pre_guard_size: 0,
             offset_guard_size: 0,
@@ -1308,6 +1336,7 @@ mod test {
                 minimum: 6,
                 maximum: None,
                 shared: false,
+                memory64: false,
             },
             pre_guard_size: 0,
             offset_guard_size: 0,
@@ -1333,6 +1362,7 @@ mod test {
                 minimum: 1,
                 maximum: None,
                 shared: false,
+                memory64: false,
             },
             offset_guard_size: 0,
             pre_guard_size: 0,
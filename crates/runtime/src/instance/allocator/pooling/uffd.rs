@@ -500,6 +500,7 @@ mod test {
                         minimum: 2,
                         maximum: Some(2),
                         shared: false,
+                        memory64: false,
                     },
                     style: MemoryStyle::Static { bound: 1 },
                     offset_guard_size: 0,
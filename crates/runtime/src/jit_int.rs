@@ -97,6 +97,24 @@ impl Drop for GdbJitImageRegistration {
     }
 }
 
+/// Returns copies of all the images currently registered with debuggers, as a
+/// debugger walking `__jit_debug_descriptor` would find them.
+pub fn registered_gdb_jit_images() -> Vec<Vec<u8>> {
+    let _lock = GDB_REGISTRATION.lock().unwrap();
+
+    let mut images = Vec::new();
+    unsafe {
+        let mut entry = __jit_debug_descriptor.first_entry;
+        while !entry.is_null() {
+            let file =
+                std::slice::from_raw_parts((*entry).symfile_addr, (*entry).symfile_size as usize);
+            images.push(file.to_vec());
+            entry = (*entry).next_entry;
+        }
+    }
+    images
+}
+
 unsafe impl Send for GdbJitImageRegistration {}
 unsafe impl Sync for GdbJitImageRegistration {}
 
@@ -21,6 +21,9 @@
 )]
 
 use std::error::Error;
+use std::sync::Arc;
+use wasmtime_environ::wasm::FuncIndex;
+use wasmtime_environ::Module;
 
 mod export;
 mod externref;
@@ -45,13 +48,15 @@ pub use crate::instance::{
     PoolingAllocationStrategy, PoolingInstanceAllocator, ResourceLimiter, DEFAULT_INSTANCE_LIMIT,
     DEFAULT_MEMORY_LIMIT, DEFAULT_TABLE_LIMIT,
 };
-pub use crate::jit_int::GdbJitImageRegistration;
-pub use crate::memory::{Memory, RuntimeLinearMemory, RuntimeMemoryCreator};
+pub use crate::jit_int::{registered_gdb_jit_images, GdbJitImageRegistration};
+pub use crate::memory::{
+    atomic_notify, atomic_wait, Memory, RuntimeLinearMemory, RuntimeMemoryCreator,
+};
 pub use crate::mmap::Mmap;
 pub use crate::table::{Table, TableElement};
 pub use crate::traphandlers::{
     catch_traps, init_traps, raise_lib_trap, raise_user_trap, resume_panic, tls_eager_initialize,
-    SignalHandler, TlsRestore, Trap,
+    AsyncWasmCallState, PreviousAsyncWasmCallState, SignalHandler, Trap,
 };
 pub use crate::vmcontext::{
     VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport, VMGlobalDefinition,
@@ -120,4 +125,43 @@ pub unsafe trait Store {
     /// is returned that's raised as a trap. Otherwise wasm execution will
     /// continue as normal.
     fn out_of_gas(&mut self) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Callback invoked when wasm finds that the engine's epoch has reached
+    /// the store's epoch deadline. If an error is returned that's raised as a
+    /// trap. Otherwise the deadline has been moved into the future and wasm
+    /// execution will continue as normal.
+    fn new_epoch(&mut self) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Callback invoked with the `fuel` consumed by the code of the function
+    /// `func` of `module` since it last reported its fuel, when per-function
+    /// fuel accounting is enabled.
+    fn attribute_fuel(&mut self, module: &Arc<Module>, func: FuncIndex, fuel: u64);
+
+    /// Callback invoked for every hooked load or store of `size` bytes at
+    /// `address` in the memory `definition`.
+    ///
+    /// `store` is `None` for loads and the value being stored otherwise. If the
+    /// access isn't to one of the store's virtual ranges of the memory then
+    /// `VirtualMemoryAccess::NotVirtual` is returned and the access is
+    /// performed on the memory itself. If an error is returned that's raised
+    /// as a trap.
+    fn virtual_memory_access(
+        &mut self,
+        definition: *mut VMMemoryDefinition,
+        address: u64,
+        size: u8,
+        store: Option<u64>,
+    ) -> Result<VirtualMemoryAccess, Box<dyn Error + Send + Sync>>;
+}
+
+/// The outcome of `Store::virtual_memory_access`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VirtualMemoryAccess {
+    /// The access isn't virtual and should go to the memory as usual.
+    NotVirtual,
+    /// The access was handled by the store. For loads this carries the loaded
+    /// value, zero-extended to 64 bits; for stores it is ignored.
+    Handled(u64),
+    /// The access should raise an out-of-bounds trap.
+    Trap,
 }
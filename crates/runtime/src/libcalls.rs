@@ -57,13 +57,18 @@
 //!   ```
 
 use crate::externref::VMExternRef;
+use crate::instance::Instance;
 use crate::table::Table;
 use crate::traphandlers::{raise_lib_trap, Trap};
-use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext, VMMemoryDefinition};
+use crate::VirtualMemoryAccess;
 use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr::{self, NonNull};
+use std::time::Duration;
+use wasmtime_environ::ir;
 use wasmtime_environ::wasm::{
-    DataIndex, ElemIndex, GlobalIndex, MemoryIndex, TableElementType, TableIndex,
+    DataIndex, ElemIndex, FuncIndex, GlobalIndex, MemoryIndex, TableElementType, TableIndex,
 };
 
 const TOINT_32: f32 = 1.0 / f32::EPSILON;
@@ -438,51 +443,81 @@ pub unsafe extern "C" fn wasmtime_externref_global_set(
     drop(old);
 }
 
-#[derive(Debug)]
-struct Unimplemented(&'static str);
-impl std::error::Error for Unimplemented {}
-impl std::fmt::Display for Unimplemented {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(f, "unimplemented: {}", self.0)
-    }
-}
-
-/// Implementation of `memory.atomic.notify` for locally defined memories.
+/// Implementation of `memory.atomic.notify`.
 pub unsafe extern "C" fn wasmtime_memory_atomic_notify(
-    _vmctx: *mut VMContext,
-    _memory_index: u32,
-    _addr: u32,
-    _count: u32,
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    addr: u32,
+    count: u32,
 ) -> u32 {
-    raise_lib_trap(Trap::User(Box::new(Unimplemented(
-        "wasm atomics (fn wasmtime_memory_atomic_notify) unsupported",
-    ))));
+    let instance = (*vmctx).instance();
+    let memory = instance.get_memory(MemoryIndex::from_u32(memory_index));
+    match crate::memory::atomic_notify(&memory, u64::from(addr), count) {
+        Ok(woken) => woken,
+        Err(trap) => raise_lib_trap(trap),
+    }
 }
 
-/// Implementation of `memory.atomic.wait32` for locally defined memories.
+/// Implementation of `memory.atomic.wait32`.
 pub unsafe extern "C" fn wasmtime_memory_atomic_wait32(
-    _vmctx: *mut VMContext,
-    _memory_index: u32,
-    _addr: u32,
-    _expected: u32,
-    _timeout: u64,
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    addr: u32,
+    expected: u32,
+    timeout: u64,
 ) -> u32 {
-    raise_lib_trap(Trap::User(Box::new(Unimplemented(
-        "wasm atomics (fn wasmtime_memory_atomic_wait32) unsupported",
-    ))));
+    let instance = (*vmctx).instance();
+    let memory_index = MemoryIndex::from_u32(memory_index);
+    let memory = instance.get_memory(memory_index);
+    let shared = instance.module().memory_plans[memory_index].memory.shared;
+    let result = crate::memory::atomic_wait(
+        &memory,
+        u64::from(addr),
+        4,
+        shared,
+        u64::from(expected),
+        wait_timeout(timeout),
+    );
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
 }
 
-/// Implementation of `memory.atomic.wait64` for locally defined memories.
+/// Implementation of `memory.atomic.wait64`.
 pub unsafe extern "C" fn wasmtime_memory_atomic_wait64(
-    _vmctx: *mut VMContext,
-    _memory_index: u32,
-    _addr: u32,
-    _expected: u64,
-    _timeout: u64,
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    addr: u32,
+    expected: u64,
+    timeout: u64,
 ) -> u32 {
-    raise_lib_trap(Trap::User(Box::new(Unimplemented(
-        "wasm atomics (fn wasmtime_memory_atomic_wait32) unsupported",
-    ))));
+    let instance = (*vmctx).instance();
+    let memory_index = MemoryIndex::from_u32(memory_index);
+    let memory = instance.get_memory(memory_index);
+    let shared = instance.module().memory_plans[memory_index].memory.shared;
+    let result = crate::memory::atomic_wait(
+        &memory,
+        u64::from(addr),
+        8,
+        shared,
+        expected,
+        wait_timeout(timeout),
+    );
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Converts the timeout operand of `memory.atomic.wait`, in nanoseconds, to a
+/// `Duration`. Negative timeouts wait forever.
+fn wait_timeout(timeout: u64) -> Option<Duration> {
+    if (timeout as i64) < 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(timeout))
+    }
 }
 
 /// Hook for when an instance runs out of fuel.
@@ -492,3 +527,107 @@ pub unsafe extern "C" fn wasmtime_out_of_gas(vmctx: *mut VMContext) {
         Err(err) => crate::traphandlers::raise_user_trap(err),
     }
 }
+
+/// Hook for when the engine's epoch reaches the store's epoch deadline.
+pub unsafe extern "C" fn wasmtime_new_epoch(vmctx: *mut VMContext) {
+    match (*(*vmctx).instance().store()).new_epoch() {
+        Ok(()) => {}
+        Err(err) => crate::traphandlers::raise_user_trap(err),
+    }
+}
+
+/// Hook for attributing fuel consumed to the function which consumed it.
+pub unsafe extern "C" fn wasmtime_fuel_attribute(vmctx: *mut VMContext, func: u32, fuel: i64) {
+    // The fuel counter only goes up while a function runs, so this is never
+    // negative.
+    debug_assert!(fuel >= 0);
+    let instance = (*vmctx).instance();
+    (*instance.store()).attribute_fuel(instance.module(), FuncIndex::from_u32(func), fuel as u64);
+}
+
+/// Implementation of a scalar load from a memory whose accesses are hooked.
+pub unsafe extern "C" fn wasmtime_memory_access_load(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    address: u64,
+    size: u32,
+) -> u64 {
+    let instance = (*vmctx).instance();
+    let definition = instance.get_memory_ptr(MemoryIndex::from_u32(memory_index));
+    match virtual_memory_access(instance, definition, address, size, None) {
+        VirtualMemoryAccess::Handled(value) => value,
+        VirtualMemoryAccess::Trap => raise_lib_trap(Trap::wasm(ir::TrapCode::HeapOutOfBounds)),
+        VirtualMemoryAccess::NotVirtual => {
+            let bytes = match memory_access_bytes(&*definition, address, size) {
+                Some(bytes) => bytes,
+                None => raise_lib_trap(Trap::wasm(ir::TrapCode::HeapOutOfBounds)),
+            };
+            let mut value = [0; 8];
+            value[..bytes.len()].copy_from_slice(bytes);
+            u64::from_le_bytes(value)
+        }
+    }
+}
+
+/// Implementation of a scalar store to a memory whose accesses are hooked.
+pub unsafe extern "C" fn wasmtime_memory_access_store(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    address: u64,
+    size: u32,
+    value: u64,
+) {
+    let instance = (*vmctx).instance();
+    let definition = instance.get_memory_ptr(MemoryIndex::from_u32(memory_index));
+    match virtual_memory_access(instance, definition, address, size, Some(value)) {
+        VirtualMemoryAccess::Handled(_) => {}
+        VirtualMemoryAccess::Trap => raise_lib_trap(Trap::wasm(ir::TrapCode::HeapOutOfBounds)),
+        VirtualMemoryAccess::NotVirtual => {
+            let bytes = match memory_access_bytes(&*definition, address, size) {
+                Some(bytes) => bytes,
+                None => raise_lib_trap(Trap::wasm(ir::TrapCode::HeapOutOfBounds)),
+            };
+            let len = bytes.len();
+            bytes.copy_from_slice(&value.to_le_bytes()[..len]);
+        }
+    }
+}
+
+/// Asks the store of `instance` how to perform a hooked access.
+///
+/// The store runs the embedder's handler, so a panic in it is caught here to
+/// avoid unwinding past our `extern "C"` caller and is resumed on the other
+/// side of wasm instead, while an error from the store is raised as a trap.
+unsafe fn virtual_memory_access(
+    instance: &Instance,
+    definition: *mut VMMemoryDefinition,
+    address: u64,
+    size: u32,
+    store: Option<u64>,
+) -> VirtualMemoryAccess {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        (*instance.store()).virtual_memory_access(definition, address, size as u8, store)
+    }));
+    match result {
+        Ok(Ok(access)) => access,
+        Ok(Err(err)) => crate::traphandlers::raise_user_trap(err),
+        Err(panic) => crate::traphandlers::resume_panic(panic),
+    }
+}
+
+/// Returns the `size` bytes at `address` in `memory`, or `None` if they aren't
+/// all in bounds.
+unsafe fn memory_access_bytes<'a>(
+    memory: &VMMemoryDefinition,
+    address: u64,
+    size: u32,
+) -> Option<&'a mut [u8]> {
+    let end = address.checked_add(u64::from(size))?;
+    if end > memory.current_length as u64 {
+        return None;
+    }
+    Some(std::slice::from_raw_parts_mut(
+        memory.base.add(address as usize),
+        size as usize,
+    ))
+}
@@ -3,12 +3,16 @@
 //! `RuntimeLinearMemory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
 use crate::mmap::Mmap;
+use crate::traphandlers::Trap;
 use crate::vmcontext::VMMemoryDefinition;
 use crate::ResourceLimiter;
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use more_asserts::{assert_ge, assert_le};
 use std::convert::TryFrom;
-use wasmtime_environ::{MemoryPlan, MemoryStyle, WASM_MAX_PAGES, WASM_PAGE_SIZE};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering::SeqCst};
+use std::thread;
+use std::time::Duration;
+use wasmtime_environ::{ir, MemoryPlan, MemoryStyle, WASM_MAX_PAGES, WASM_PAGE_SIZE};
 
 /// A memory allocator
 pub trait RuntimeMemoryCreator: Send + Sync {
@@ -463,3 +467,71 @@ impl Default for Memory {
         }
     }
 }
+
+/// Implementation of `memory.atomic.notify` on `memory`, returning the number
+/// of waiters which were woken.
+///
+/// Traps if `addr` isn't aligned to 4 bytes or is out of bounds. Nothing can
+/// be waiting on a memory which isn't shared, and a shared memory can only be
+/// reached from the thread using the store it belongs to, which can't be
+/// waiting while it's notifying, so this otherwise always returns 0.
+pub fn atomic_notify(memory: &VMMemoryDefinition, addr: u64, _count: u32) -> Result<u32, Trap> {
+    validate_atomic_addr(memory, addr, 4)?;
+    Ok(0)
+}
+
+/// Implementation of `memory.atomic.wait32` and `memory.atomic.wait64` on
+/// `memory`, for a value of `size` bytes.
+///
+/// Traps if `addr` isn't aligned to `size` or is out of bounds, or if the
+/// memory isn't `shared`. Otherwise returns 1 if the value at `addr` isn't
+/// `expected`, and 2 once `timeout` has elapsed. Nothing else can reach the
+/// memory to change the value or notify this waiter, so waiting without a
+/// timeout traps rather than blocking forever.
+pub fn atomic_wait(
+    memory: &VMMemoryDefinition,
+    addr: u64,
+    size: u64,
+    shared: bool,
+    expected: u64,
+    timeout: Option<Duration>,
+) -> Result<u32, Trap> {
+    validate_atomic_addr(memory, addr, size)?;
+    if !shared {
+        return Err(Trap::User(
+            anyhow!("atomic wait on non-shared memory").into(),
+        ));
+    }
+    // The address was checked to be aligned and in bounds above.
+    let actual = unsafe {
+        let ptr = memory.base.add(addr as usize);
+        match size {
+            4 => u64::from(u32::from_le((*(ptr as *const AtomicU32)).load(SeqCst))),
+            _ => u64::from_le((*(ptr as *const AtomicU64)).load(SeqCst)),
+        }
+    };
+    if actual != expected {
+        return Ok(1);
+    }
+    match timeout {
+        Some(timeout) => {
+            thread::sleep(timeout);
+            Ok(2)
+        }
+        None => Err(Trap::User(
+            anyhow!("atomic wait without a timeout would never be notified").into(),
+        )),
+    }
+}
+
+/// Checks that an atomic access of `size` bytes at `addr` is aligned and in
+/// bounds of `memory`.
+fn validate_atomic_addr(memory: &VMMemoryDefinition, addr: u64, size: u64) -> Result<(), Trap> {
+    if addr % size != 0 {
+        return Err(Trap::wasm(ir::TrapCode::HeapMisaligned));
+    }
+    match addr.checked_add(size) {
+        Some(end) if end <= u64::from(memory.current_length) => Ok(()),
+        _ => Err(Trap::wasm(ir::TrapCode::HeapOutOfBounds)),
+    }
+}
@@ -5,6 +5,7 @@
 use crate::vmcontext::{VMCallerCheckedAnyfunc, VMTableDefinition};
 use crate::{ResourceLimiter, Trap, VMExternRef};
 use anyhow::{bail, Result};
+use std::alloc::{self, Layout};
 use std::convert::{TryFrom, TryInto};
 use std::ops::Range;
 use std::ptr;
@@ -129,7 +130,13 @@ impl Table {
         limiter: Option<&mut dyn ResourceLimiter>,
     ) -> Result<Self> {
         Self::limit_new(plan, limiter)?;
-        let elements = vec![0; plan.table.minimum as usize];
+        let elements = match try_zeroed_elements(plan.table.minimum as usize) {
+            Some(elements) => elements,
+            None => bail!(
+                "failed to allocate a table with a minimum size of {} elements",
+                plan.table.minimum
+            ),
+        };
         let ty = plan.table.ty.clone();
         let maximum = plan.table.maximum;
 
@@ -215,7 +222,7 @@ impl Table {
         dst: u32,
         items: impl ExactSizeIterator<Item = *mut VMCallerCheckedAnyfunc>,
     ) -> Result<(), Trap> {
-        assert!(self.element_type() == TableElementType::Func);
+        debug_assert!(self.element_type() == TableElementType::Func);
 
         let elements = match self
             .elements_mut()
@@ -307,7 +314,11 @@ impl Table {
                 *size = new_size;
             }
             Table::Dynamic { elements, .. } => {
-                elements.resize(new_size as usize, 0);
+                // Allocate the new storage up front so that failing to do so
+                // fails the grow, rather than aborting the process.
+                let mut new_elements = try_zeroed_elements(new_size as usize)?;
+                new_elements[..elements.len()].copy_from_slice(elements);
+                *elements = new_elements;
             }
         }
 
@@ -488,6 +499,25 @@ impl Table {
     }
 }
 
+/// Allocates storage for `len` null table elements, returning `None` instead
+/// of aborting the process if the allocation fails.
+///
+/// Tables can be declared with billions of elements, so unlike most
+/// allocations this one is expected to fail sometimes.
+fn try_zeroed_elements(len: usize) -> Option<Vec<usize>> {
+    if len == 0 {
+        return Some(Vec::new());
+    }
+    let layout = Layout::array::<usize>(len).ok()?;
+    unsafe {
+        let ptr = alloc::alloc_zeroed(layout) as *mut usize;
+        if ptr.is_null() {
+            return None;
+        }
+        Some(Vec::from_raw_parts(ptr, len, len))
+    }
+}
+
 impl Drop for Table {
     fn drop(&mut self) {
         let ty = self.element_type();
@@ -12,7 +12,7 @@ use std::sync::atomic::Ordering::SeqCst;
 use std::sync::Once;
 use wasmtime_environ::ir;
 
-pub use self::tls::{tls_eager_initialize, TlsRestore};
+pub use self::tls::{tls_eager_initialize, AsyncWasmCallState, PreviousAsyncWasmCallState};
 
 #[link(name = "wasmtime-helpers")]
 extern "C" {
@@ -355,7 +355,8 @@ mod tls {
     // otherwise the access may be split across two threads and cause unsafety.
     //
     // This also means that extra care is taken by the runtime to save/restore
-    // these TLS values when the runtime may have crossed threads.
+    // these TLS values when the runtime may have crossed threads, see
+    // `AsyncWasmCallState` below.
     mod raw {
         use super::CallThreadState;
         use crate::Trap;
@@ -410,41 +411,87 @@ mod tls {
 
     pub use raw::initialize as tls_eager_initialize;
 
-    /// Opaque state used to help control TLS state across stack switches for
-    /// async support.
-    pub struct TlsRestore(raw::Ptr);
+    /// The wasm activations made on a fiber which is suspended.
+    ///
+    /// Each activation is a `CallThreadState` on the fiber's own stack, and
+    /// they're linked into the TLS chain of the thread running the fiber.
+    /// When the fiber suspends they're removed from that chain as a whole, so
+    /// the polling thread's TLS is left exactly as it was before the fiber
+    /// was resumed, and they're relinked on top of the TLS of whichever thread
+    /// resumes the fiber next. That needn't be the same thread, since the
+    /// activations themselves only ever refer to each other.
+    ///
+    /// This is intended to only be used with stack switching found with
+    /// fibers and async wasmtime.
+    pub struct AsyncWasmCallState {
+        /// The most recent activation, or null if there are none.
+        top: raw::Ptr,
+        /// The oldest activation, whose `prev` is null while it's suspended.
+        bottom: raw::Ptr,
+    }
+
+    impl AsyncWasmCallState {
+        /// Returns the state of a fiber which hasn't made any activations.
+        pub fn new() -> AsyncWasmCallState {
+            AsyncWasmCallState {
+                top: ptr::null(),
+                bottom: ptr::null(),
+            }
+        }
+
+        /// Returns whether there are no activations in this state.
+        pub fn is_empty(&self) -> bool {
+            self.top.is_null()
+        }
 
-    impl TlsRestore {
-        /// Takes the TLS state that is currently configured and returns a
-        /// token that is used to replace it later.
+        /// Links these activations on top of this thread's TLS chain just
+        /// before their fiber is resumed.
         ///
-        /// This is not a safe operation since it's intended to only be used
-        /// with stack switching found with fibers and async wasmtime.
-        pub unsafe fn take() -> Result<TlsRestore, Trap> {
-            // Our tls pointer must be set at this time, and it must not be
-            // null. We need to restore the previous pointer since we're
-            // removing ourselves from the call-stack, and in the process we
-            // null out our own previous field for safety in case it's
-            // accidentally used later.
-            let raw = raw::get();
-            assert!(!raw.is_null());
-            let prev = (*raw).prev.replace(ptr::null());
-            raw::replace(prev)?;
-            Ok(TlsRestore(raw))
+        /// This thread must already be initialized to handle traps, with
+        /// `tls_eager_initialize`, since the fiber may trap as soon as it's
+        /// resumed. The returned value must be restored once the fiber has
+        /// suspended or finished, on this same thread.
+        pub unsafe fn push(self) -> PreviousAsyncWasmCallState {
+            let boundary = raw::get();
+            if !self.is_empty() {
+                assert!((*self.bottom).prev.get().is_null());
+                (*self.bottom).prev.set(boundary);
+                raw::replace(self.top).expect("tls should be previously initialized");
+            }
+            PreviousAsyncWasmCallState { boundary }
         }
+    }
+
+    /// The TLS state of a thread from before it resumed a fiber, returned by
+    /// `AsyncWasmCallState::push`.
+    pub struct PreviousAsyncWasmCallState {
+        boundary: raw::Ptr,
+    }
 
-        /// Restores a previous tls state back into this thread's TLS.
+    impl PreviousAsyncWasmCallState {
+        /// Unlinks the activations the fiber has made since it was resumed,
+        /// putting this thread's TLS back to what it was beforehand.
         ///
-        /// This is unsafe because it's intended to only be used within the
-        /// context of stack switching within wasmtime.
-        pub unsafe fn replace(self) -> Result<(), super::Trap> {
-            // We need to configure our previous TLS pointer to whatever is in
-            // TLS at this time, and then we set the current state to ourselves.
-            let prev = raw::get();
-            assert!((*self.0).prev.get().is_null());
-            (*self.0).prev.set(prev);
-            raw::replace(self.0)?;
-            Ok(())
+        /// This must be called right after the fiber has suspended or
+        /// finished, when any activations it has made are still on top of the
+        /// TLS chain.
+        pub unsafe fn restore(self) -> AsyncWasmCallState {
+            let top = raw::get();
+            if top == self.boundary {
+                return AsyncWasmCallState::new();
+            }
+            let mut bottom = top;
+            loop {
+                assert!(!bottom.is_null(), "fiber boundary missing from tls");
+                let prev = (*bottom).prev.get();
+                if prev == self.boundary {
+                    break;
+                }
+                bottom = prev;
+            }
+            (*bottom).prev.set(ptr::null());
+            raw::replace(self.boundary).expect("tls should be previously initialized");
+            AsyncWasmCallState { top, bottom }
         }
     }
 
@@ -7,7 +7,7 @@ use std::any::Any;
 use std::cell::UnsafeCell;
 use std::marker;
 use std::ptr::NonNull;
-use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::SeqCst};
 use std::u32;
 use wasmtime_environ::BuiltinFunctionIndex;
 
@@ -627,6 +627,13 @@ impl VMBuiltinFunctionsArray {
         ptrs[BuiltinFunctionIndex::memory_atomic_wait64().index() as usize] =
             wasmtime_memory_atomic_wait64 as usize;
         ptrs[BuiltinFunctionIndex::out_of_gas().index() as usize] = wasmtime_out_of_gas as usize;
+        ptrs[BuiltinFunctionIndex::fuel_attribute().index() as usize] =
+            wasmtime_fuel_attribute as usize;
+        ptrs[BuiltinFunctionIndex::new_epoch().index() as usize] = wasmtime_new_epoch as usize;
+        ptrs[BuiltinFunctionIndex::memory_access_load().index() as usize] =
+            wasmtime_memory_access_load as usize;
+        ptrs[BuiltinFunctionIndex::memory_access_store().index() as usize] =
+            wasmtime_memory_access_store as usize;
 
         if cfg!(debug_assertions) {
             for i in 0..ptrs.len() {
@@ -690,15 +697,30 @@ pub struct VMInterrupts {
     /// turning positive a wasm trap will be generated. This field is only
     /// modified if wasm is configured to consume fuel.
     pub fuel_consumed: UnsafeCell<i64>,
+
+    /// The epoch at which wasm reaches its deadline when epoch-based
+    /// interruption is enabled.
+    ///
+    /// Once the engine's epoch, pointed to by `epoch_ptr`, is at least this
+    /// value the store is notified on the next function entry or loop header.
+    /// Other threads may move the deadline to 0 to have the store notified
+    /// as soon as possible.
+    pub epoch_deadline: AtomicU64,
+
+    /// The epoch counter of the engine which the store belongs to, or null if
+    /// it isn't known. This is only read by wasm if epoch-based interruption
+    /// is enabled.
+    pub epoch_ptr: *const AtomicU64,
 }
 
 // The `VMInterrupts` type is a pod-type with no destructor, and we only access
-// `stack_limit` from other threads, so add in these trait impls which are
-// otherwise not available due to the `fuel_consumed` variable in
-// `VMInterrupts`.
+// `stack_limit` and `epoch_deadline` from other threads, so add in these trait
+// impls which are otherwise not available due to the `fuel_consumed` variable
+// and `epoch_ptr` pointer in `VMInterrupts`.
 //
 // Note that users of `fuel_consumed` understand that the unsafety encompasses
-// ensuring that it's only mutated/accessed from one thread dynamically.
+// ensuring that it's only mutated/accessed from one thread dynamically. The
+// epoch counter itself is atomic.
 unsafe impl Send for VMInterrupts {}
 unsafe impl Sync for VMInterrupts {}
 
@@ -708,6 +730,11 @@ impl VMInterrupts {
         self.stack_limit
             .store(wasmtime_environ::INTERRUPTED, SeqCst);
     }
+
+    /// Returns whether an interrupt has been flagged and not yet consumed.
+    pub fn is_interrupted(&self) -> bool {
+        self.stack_limit.load(SeqCst) == wasmtime_environ::INTERRUPTED
+    }
 }
 
 impl Default for VMInterrupts {
@@ -715,6 +742,8 @@ impl Default for VMInterrupts {
         VMInterrupts {
             stack_limit: AtomicUsize::new(usize::max_value()),
             fuel_consumed: UnsafeCell::new(0),
+            epoch_deadline: AtomicU64::new(0),
+            epoch_ptr: std::ptr::null(),
         }
     }
 }
@@ -735,6 +764,24 @@ mod test_vminterrupts {
             usize::from(offsets.vminterrupts_stack_limit())
         );
     }
+
+    #[test]
+    fn check_vminterrupts_epoch_offsets() {
+        let module = Module::new();
+        let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module);
+        assert_eq!(
+            offset_of!(VMInterrupts, fuel_consumed),
+            usize::from(offsets.vminterrupts_fuel_consumed())
+        );
+        assert_eq!(
+            offset_of!(VMInterrupts, epoch_deadline),
+            usize::from(offsets.vminterrupts_epoch_deadline())
+        );
+        assert_eq!(
+            offset_of!(VMInterrupts, epoch_ptr),
+            usize::from(offsets.vminterrupts_epoch_ptr())
+        );
+    }
 }
 
 /// The VM "context", which is pointed to by the `vmctx` arg in Cranelift.
@@ -228,8 +228,10 @@ mod wasi_tests {
             "path_filestat",
             "path_link",
             "path_open_create_existing",
+            "path_open_directory_flags",
             "path_open_dirfd_not_dir",
             "path_open_read_without_rights",
+            "path_open_rights",
             "path_rename",
             "path_rename_dir_trailing_slashes",
             "path_rename_file_trailing_slashes",
@@ -257,6 +259,7 @@ mod wasi_tests {
                 "clock_time_get" => true,
                 "sched_yield" => true,
                 "poll_oneoff_stdio" => true,
+                "stdio_filestat" => true,
                 _ => false,
             }
         } else {
@@ -269,6 +272,7 @@ mod wasi_tests {
         match testsuite {
             "wasi-cap-std-sync" | "wasi-tokio" => match name {
                 "poll_oneoff_stdio" => true,
+                "stdio_filestat" => true,
                 _ => false,
             },
             "wasi-virtfs" => false,
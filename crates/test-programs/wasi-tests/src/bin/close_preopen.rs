@@ -30,22 +30,6 @@ unsafe fn test_close_preopen(dir_fd: wasi::Fd) {
         wasi::FILETYPE_DIRECTORY,
         "expected the scratch directory to be a directory",
     );
-
-    // Try to renumber a preopened directory handle.
-    assert_errno!(
-        wasi::fd_renumber(pre_fd, dir_fd)
-            .expect_err("renumbering over a preopened file descriptor")
-            .raw_error(),
-        wasi::ERRNO_NOTSUP
-    );
-
-    // Ensure that dir_fd is still open.
-    let dir_fdstat = wasi::fd_fdstat_get(dir_fd).expect("failed fd_fdstat_get");
-    assert_eq!(
-        dir_fdstat.fs_filetype,
-        wasi::FILETYPE_DIRECTORY,
-        "expected the scratch directory to be a directory",
-    );
 }
 
 fn main() {
@@ -191,6 +191,52 @@ unsafe fn test_fd_readdir_lots(dir_fd: wasi::Fd) {
     }
 }
 
+unsafe fn test_fd_readdir_inodes(dir_fd: wasi::Fd) {
+    wasi::path_create_directory(dir_fd, "subdir").expect("create directory");
+    for path in ["file", "subdir/file"].iter() {
+        let file_fd = wasi::path_open(dir_fd, 0, path, wasi::OFLAGS_CREAT, 0, 0, 0)
+            .expect("failed to create file");
+        wasi::fd_close(file_fd).expect("closing a file");
+    }
+    let subdir_fd = wasi::path_open(
+        dir_fd,
+        0,
+        "subdir",
+        wasi::OFLAGS_DIRECTORY,
+        wasi::RIGHTS_FD_READDIR | wasi::RIGHTS_PATH_FILESTAT_GET | wasi::RIGHTS_FD_FILESTAT_GET,
+        0,
+        0,
+    )
+    .expect("failed to open directory");
+
+    // Every entry's inode number should match the one from stat, whichever
+    // directory it is reached through.
+    for (fd, prefix) in [(dir_fd, ""), (subdir_fd, "subdir/")].iter() {
+        let (dirs, eof) = exec_fd_readdir(*fd, 0);
+        assert!(eof, "expected to read the entire directory");
+        for dir in dirs.iter().filter(|d| d.name != "..") {
+            let stat = wasi::path_filestat_get(*fd, 0, &dir.name).expect("stat entry");
+            assert_eq!(dir.dirent.d_ino, stat.ino, "inode of {}", dir.name);
+            let path = format!("{}{}", prefix, dir.name);
+            let stat = wasi::path_filestat_get(dir_fd, 0, &path).expect("stat entry");
+            assert_eq!(dir.dirent.d_ino, stat.ino, "inode of {}", path);
+        }
+    }
+
+    let stat = wasi::fd_filestat_get(subdir_fd).expect("failed filestat");
+    let (dirs, _) = exec_fd_readdir(dir_fd, 0);
+    let dir = dirs
+        .iter()
+        .find(|d| d.name == "subdir")
+        .expect("subdir entry");
+    assert_eq!(dir.dirent.d_ino, stat.ino);
+
+    wasi::fd_close(subdir_fd).expect("closing a directory");
+    wasi::path_unlink_file(dir_fd, "subdir/file").expect("removing a file");
+    wasi::path_remove_directory(dir_fd, "subdir").expect("removing a directory");
+    wasi::path_unlink_file(dir_fd, "file").expect("removing a file");
+}
+
 fn main() {
     let mut args = env::args();
     let prog = args.next().unwrap();
@@ -213,4 +259,5 @@ fn main() {
     // Run the tests.
     unsafe { test_fd_readdir(dir_fd) }
     unsafe { test_fd_readdir_lots(dir_fd) }
+    unsafe { test_fd_readdir_inodes(dir_fd) }
 }
@@ -0,0 +1,118 @@
+use std::{env, process};
+use wasi_tests::{assert_errno, drop_rights, open_scratch_directory};
+
+unsafe fn test_file_sync(dir_fd: wasi::Fd) {
+    let fd = wasi::path_open(
+        dir_fd,
+        0,
+        "file",
+        wasi::OFLAGS_CREAT,
+        wasi::RIGHTS_FD_WRITE | wasi::RIGHTS_FD_SYNC | wasi::RIGHTS_FD_DATASYNC,
+        0,
+        0,
+    )
+    .expect("creating a file");
+
+    let data = [1u8, 2, 3, 4];
+    let nwritten = wasi::fd_write(
+        fd,
+        &[wasi::Ciovec {
+            buf: data.as_ptr(),
+            buf_len: data.len(),
+        }],
+    )
+    .expect("writing to the file");
+    assert_eq!(nwritten, data.len(), "nwritten bytes check");
+
+    wasi::fd_datasync(fd).expect("datasyncing the file");
+    wasi::fd_sync(fd).expect("syncing the file");
+
+    // Each call needs its own right.
+    drop_rights(fd, wasi::RIGHTS_FD_DATASYNC, 0);
+    assert_errno!(
+        wasi::fd_datasync(fd)
+            .expect_err("datasyncing without the right")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    wasi::fd_sync(fd).expect("syncing the file without the datasync right");
+    drop_rights(fd, wasi::RIGHTS_FD_SYNC, 0);
+    assert_errno!(
+        wasi::fd_sync(fd)
+            .expect_err("syncing without the right")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+
+    wasi::fd_close(fd).expect("closing the file");
+    wasi::path_unlink_file(dir_fd, "file").expect("removing the file");
+}
+
+unsafe fn test_dir_sync(dir_fd: wasi::Fd) {
+    wasi::path_create_directory(dir_fd, "dir").expect("creating a directory");
+    let fd = wasi::path_open(
+        dir_fd,
+        0,
+        "dir",
+        wasi::OFLAGS_DIRECTORY,
+        wasi::RIGHTS_PATH_OPEN
+            | wasi::RIGHTS_PATH_CREATE_FILE
+            | wasi::RIGHTS_FD_SYNC
+            | wasi::RIGHTS_FD_DATASYNC,
+        0,
+        0,
+    )
+    .expect("opening the directory");
+
+    // Syncing a directory after creating an entry in it, as databases do to
+    // make the new entry durable, succeeds wherever it's run.
+    let file_fd = wasi::path_open(fd, 0, "file", wasi::OFLAGS_CREAT, 0, 0, 0)
+        .expect("creating a file in the directory");
+    wasi::fd_close(file_fd).expect("closing the file");
+    wasi::fd_sync(fd).expect("syncing the directory");
+    wasi::fd_datasync(fd).expect("datasyncing the directory");
+
+    drop_rights(fd, wasi::RIGHTS_FD_SYNC | wasi::RIGHTS_FD_DATASYNC, 0);
+    assert_errno!(
+        wasi::fd_sync(fd)
+            .expect_err("syncing a directory without the right")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    assert_errno!(
+        wasi::fd_datasync(fd)
+            .expect_err("datasyncing a directory without the right")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+
+    wasi::fd_close(fd).expect("closing the directory");
+    wasi::path_unlink_file(dir_fd, "dir/file").expect("removing the file");
+    wasi::path_remove_directory(dir_fd, "dir").expect("removing the directory");
+}
+
+fn main() {
+    let mut args = env::args();
+    let prog = args.next().unwrap();
+    let arg = if let Some(arg) = args.next() {
+        arg
+    } else {
+        eprintln!("usage: {} <scratch directory>", prog);
+        process::exit(1);
+    };
+
+    // Open scratch directory
+    let dir_fd = match open_scratch_directory(&arg) {
+        Ok(dir_fd) => dir_fd,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    // Run the tests.
+    unsafe {
+        test_file_sync(dir_fd);
+        test_dir_sync(dir_fd);
+    }
+}
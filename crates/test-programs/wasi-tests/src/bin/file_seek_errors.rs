@@ -0,0 +1,152 @@
+use std::{env, process};
+use wasi_tests::{assert_errno, open_scratch_directory};
+
+unsafe fn write_bytes(fd: wasi::Fd, len: usize) {
+    let data = vec![0u8; len];
+    let iov = wasi::Ciovec {
+        buf: data.as_ptr() as *const _,
+        buf_len: data.len(),
+    };
+    let nwritten = wasi::fd_write(fd, &[iov]).expect("writing to a file");
+    assert_eq!(nwritten, len, "should write {} bytes to file", len);
+}
+
+unsafe fn test_seek_overflow(dir_fd: wasi::Fd) {
+    let file_fd = wasi::path_open(
+        dir_fd,
+        0,
+        "file",
+        wasi::OFLAGS_CREAT,
+        wasi::RIGHTS_FD_WRITE | wasi::RIGHTS_FD_SEEK | wasi::RIGHTS_FD_TELL,
+        0,
+        0,
+    )
+    .expect("opening a file");
+    write_bytes(file_fd, 100);
+
+    // Offsets relative to the current position or the end must not wrap
+    // around to a negative offset.
+    wasi::fd_seek(file_fd, 100, wasi::WHENCE_SET).expect("seeking to 100");
+    assert_errno!(
+        wasi::fd_seek(file_fd, i64::MAX, wasi::WHENCE_CUR)
+            .expect_err("seeking past i64::MAX from the current offset")
+            .raw_error(),
+        wasi::ERRNO_OVERFLOW
+    );
+    assert_errno!(
+        wasi::fd_seek(file_fd, i64::MAX, wasi::WHENCE_END)
+            .expect_err("seeking past i64::MAX from the end")
+            .raw_error(),
+        wasi::ERRNO_OVERFLOW
+    );
+
+    // A failed seek leaves the offset untouched.
+    let offset = wasi::fd_tell(file_fd).expect("getting file offset");
+    assert_eq!(offset, 100, "offset after failed seeks should be 100");
+
+    // Negative offsets are invalid no matter how they're reached.
+    assert_errno!(
+        wasi::fd_seek(file_fd, -1, wasi::WHENCE_SET)
+            .expect_err("seeking to a negative offset")
+            .raw_error(),
+        wasi::ERRNO_INVAL
+    );
+    assert_errno!(
+        wasi::fd_seek(file_fd, -101, wasi::WHENCE_END)
+            .expect_err("seeking before the start from the end")
+            .raw_error(),
+        wasi::ERRNO_INVAL
+    );
+
+    wasi::fd_close(file_fd).expect("closing a file");
+    wasi::path_unlink_file(dir_fd, "file").expect("deleting a file");
+}
+
+unsafe fn test_directory_seek(dir_fd: wasi::Fd) {
+    wasi::path_create_directory(dir_fd, "dir").expect("creating a directory");
+    let fd = wasi::path_open(
+        dir_fd,
+        0,
+        "dir",
+        wasi::OFLAGS_DIRECTORY,
+        wasi::RIGHTS_FD_SEEK | wasi::RIGHTS_FD_TELL,
+        0,
+        0,
+    )
+    .expect("opening a directory");
+
+    assert_errno!(
+        wasi::fd_seek(fd, 0, wasi::WHENCE_CUR)
+            .expect_err("seeking a directory")
+            .raw_error(),
+        wasi::ERRNO_BADF
+    );
+    assert_errno!(
+        wasi::fd_seek(fd, i64::MAX, wasi::WHENCE_END)
+            .expect_err("seeking a directory past i64::MAX")
+            .raw_error(),
+        wasi::ERRNO_BADF
+    );
+    assert_errno!(
+        wasi::fd_tell(fd)
+            .expect_err("telling a directory")
+            .raw_error(),
+        wasi::ERRNO_BADF
+    );
+
+    wasi::fd_close(fd).expect("closing a directory");
+    wasi::path_remove_directory(dir_fd, "dir").expect("removing a directory");
+}
+
+unsafe fn test_tell_after_append(dir_fd: wasi::Fd) {
+    let file_fd = wasi::path_open(
+        dir_fd,
+        0,
+        "file",
+        wasi::OFLAGS_CREAT,
+        wasi::RIGHTS_FD_WRITE | wasi::RIGHTS_FD_SEEK | wasi::RIGHTS_FD_TELL,
+        0,
+        wasi::FDFLAGS_APPEND,
+    )
+    .expect("opening a file in append mode");
+
+    write_bytes(file_fd, 100);
+    let offset = wasi::fd_tell(file_fd).expect("getting file offset after appending");
+    assert_eq!(offset, 100, "offset after appending should be 100");
+
+    // Appends always go to the end, regardless of the current offset.
+    wasi::fd_seek(file_fd, 0, wasi::WHENCE_SET).expect("seeking to the beginning of the file");
+    write_bytes(file_fd, 50);
+    let offset = wasi::fd_tell(file_fd).expect("getting file offset after appending again");
+    assert_eq!(offset, 150, "offset after appending again should be 150");
+
+    wasi::fd_close(file_fd).expect("closing a file");
+    wasi::path_unlink_file(dir_fd, "file").expect("deleting a file");
+}
+
+fn main() {
+    let mut args = env::args();
+    let prog = args.next().unwrap();
+    let arg = if let Some(arg) = args.next() {
+        arg
+    } else {
+        eprintln!("usage: {} <scratch directory>", prog);
+        process::exit(1);
+    };
+
+    // Open scratch directory
+    let dir_fd = match open_scratch_directory(&arg) {
+        Ok(dir_fd) => dir_fd,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    // Run the tests.
+    unsafe {
+        test_seek_overflow(dir_fd);
+        test_directory_seek(dir_fd);
+        test_tell_after_append(dir_fd);
+    }
+}
@@ -0,0 +1,89 @@
+use std::{env, process};
+use wasi_tests::{assert_errno, create_file, open_scratch_directory};
+
+unsafe fn test_path_open_directory_flags(dir_fd: wasi::Fd) {
+    create_file(dir_fd, "file");
+    wasi::path_create_directory(dir_fd, "dir").expect("creating a directory");
+
+    // Creating or truncating doesn't make sense for a directory, so these are
+    // rejected whatever the path refers to.
+    let invalid = [
+        wasi::OFLAGS_CREAT,
+        wasi::OFLAGS_TRUNC,
+        wasi::OFLAGS_EXCL,
+        wasi::OFLAGS_CREAT | wasi::OFLAGS_EXCL,
+        wasi::OFLAGS_CREAT | wasi::OFLAGS_TRUNC,
+    ];
+    for path in &["missing", "file", "dir"] {
+        for oflags in &invalid {
+            assert_errno!(
+                wasi::path_open(dir_fd, 0, path, wasi::OFLAGS_DIRECTORY | oflags, 0, 0, 0)
+                    .expect_err("opening a directory with invalid oflags")
+                    .raw_error(),
+                wasi::ERRNO_INVAL
+            );
+        }
+    }
+
+    // Nothing was created by the rejected calls, and the file wasn't
+    // truncated.
+    assert_errno!(
+        wasi::path_filestat_get(dir_fd, 0, "missing")
+            .expect_err("stat of a path which was never created")
+            .raw_error(),
+        wasi::ERRNO_NOENT
+    );
+    let stat = wasi::path_filestat_get(dir_fd, 0, "file").expect("stat of the file");
+    assert_eq!(stat.filetype, wasi::FILETYPE_REGULAR_FILE);
+
+    // On its own, the flag opens directories and nothing else.
+    let fd = wasi::path_open(dir_fd, 0, "dir", wasi::OFLAGS_DIRECTORY, 0, 0, 0)
+        .expect("opening a directory");
+    let stat = wasi::fd_filestat_get(fd).expect("stat of the opened directory");
+    assert_eq!(stat.filetype, wasi::FILETYPE_DIRECTORY);
+    wasi::fd_close(fd).expect("closing a directory");
+    assert_errno!(
+        wasi::path_open(dir_fd, 0, "file", wasi::OFLAGS_DIRECTORY, 0, 0, 0)
+            .expect_err("opening a file as a directory")
+            .raw_error(),
+        wasi::ERRNO_NOTDIR
+    );
+    assert_errno!(
+        wasi::path_open(dir_fd, 0, "missing", wasi::OFLAGS_DIRECTORY, 0, 0, 0)
+            .expect_err("opening a missing directory")
+            .raw_error(),
+        wasi::ERRNO_NOENT
+    );
+    assert_errno!(
+        wasi::path_filestat_get(dir_fd, 0, "missing")
+            .expect_err("stat of a path which was never created")
+            .raw_error(),
+        wasi::ERRNO_NOENT
+    );
+
+    wasi::path_unlink_file(dir_fd, "file").expect("removing a file");
+    wasi::path_remove_directory(dir_fd, "dir").expect("removing a directory");
+}
+
+fn main() {
+    let mut args = env::args();
+    let prog = args.next().unwrap();
+    let arg = if let Some(arg) = args.next() {
+        arg
+    } else {
+        eprintln!("usage: {} <scratch directory>", prog);
+        process::exit(1);
+    };
+
+    // Open scratch directory
+    let dir_fd = match open_scratch_directory(&arg) {
+        Ok(dir_fd) => dir_fd,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    // Run the tests.
+    unsafe { test_path_open_directory_flags(dir_fd) }
+}
@@ -0,0 +1,162 @@
+use std::{env, process};
+use wasi_tests::{assert_errno, create_file, drop_rights, fd_get_rights, open_scratch_directory};
+
+const READ_RIGHTS: wasi::Rights = wasi::RIGHTS_FD_READ
+    | wasi::RIGHTS_FD_SEEK
+    | wasi::RIGHTS_FD_TELL
+    | wasi::RIGHTS_FD_FILESTAT_GET
+    | wasi::RIGHTS_POLL_FD_READWRITE;
+const WRITE_RIGHTS: wasi::Rights = wasi::RIGHTS_FD_WRITE
+    | wasi::RIGHTS_FD_DATASYNC
+    | wasi::RIGHTS_FD_SYNC
+    | wasi::RIGHTS_FD_ALLOCATE
+    | wasi::RIGHTS_FD_FILESTAT_SET_SIZE;
+
+unsafe fn write_byte(fd: wasi::Fd) -> Result<usize, wasi::Error> {
+    let data = [1u8];
+    wasi::fd_write(
+        fd,
+        &[wasi::Ciovec {
+            buf: data.as_ptr(),
+            buf_len: data.len(),
+        }],
+    )
+}
+
+unsafe fn read_byte(fd: wasi::Fd) -> Result<usize, wasi::Error> {
+    let mut data = [0u8];
+    wasi::fd_read(
+        fd,
+        &[wasi::Iovec {
+            buf: data.as_mut_ptr(),
+            buf_len: data.len(),
+        }],
+    )
+}
+
+/// Opens "file" with `rights` as both its base and inheriting rights, and
+/// checks that it's given exactly the base rights requested and no inheriting
+/// rights, since files have no children.
+unsafe fn open_with_rights(dir_fd: wasi::Fd, rights: wasi::Rights) -> wasi::Fd {
+    let fd = wasi::path_open(dir_fd, 0, "file", 0, rights, rights, 0).expect("opening the file");
+    let (base, inheriting) = fd_get_rights(fd);
+    assert_eq!(base, rights, "base rights of the opened file");
+    assert_eq!(inheriting, 0, "inheriting rights of the opened file");
+    fd
+}
+
+unsafe fn test_path_open_rights(dir_fd: wasi::Fd) {
+    create_file(dir_fd, "file");
+
+    // The preopen allows its files to be written, but that doesn't mean they
+    // are unless it's asked for.
+    let (_, dir_inheriting) = fd_get_rights(dir_fd);
+    assert_eq!(
+        dir_inheriting & (READ_RIGHTS | WRITE_RIGHTS),
+        READ_RIGHTS | WRITE_RIGHTS,
+        "the scratch directory should allow reading and writing its files",
+    );
+
+    let fd = open_with_rights(dir_fd, READ_RIGHTS);
+    assert_eq!(read_byte(fd).expect("reading from a read-only file"), 0);
+    assert_errno!(
+        write_byte(fd)
+            .expect_err("writing to a read-only file")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    assert_errno!(
+        wasi::fd_filestat_set_size(fd, 0)
+            .expect_err("truncating a read-only file")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    wasi::fd_close(fd).expect("closing a file");
+
+    let fd = open_with_rights(dir_fd, WRITE_RIGHTS);
+    assert_eq!(write_byte(fd).expect("writing to a write-only file"), 1);
+    assert_errno!(
+        read_byte(fd)
+            .expect_err("reading from a write-only file")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    wasi::fd_close(fd).expect("closing a file");
+
+    let fd = open_with_rights(dir_fd, READ_RIGHTS | WRITE_RIGHTS);
+    assert_eq!(read_byte(fd).expect("reading from a read-write file"), 1);
+    assert_eq!(write_byte(fd).expect("writing to a read-write file"), 1);
+    wasi::fd_close(fd).expect("closing a file");
+
+    // Directory rights requested for a file are dropped.
+    let fd = wasi::path_open(
+        dir_fd,
+        0,
+        "file",
+        0,
+        READ_RIGHTS | wasi::RIGHTS_PATH_OPEN | wasi::RIGHTS_FD_READDIR,
+        0,
+        0,
+    )
+    .expect("opening the file with directory rights");
+    assert_eq!(fd_get_rights(fd), (READ_RIGHTS, 0));
+    wasi::fd_close(fd).expect("closing a file");
+
+    // Truncating and appending are writes, so they need the right to write.
+    assert_errno!(
+        wasi::path_open(dir_fd, 0, "file", wasi::OFLAGS_TRUNC, READ_RIGHTS, 0, 0)
+            .expect_err("truncating a file opened read-only")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    assert_errno!(
+        wasi::path_open(dir_fd, 0, "file", 0, READ_RIGHTS, 0, wasi::FDFLAGS_APPEND)
+            .expect_err("appending to a file opened read-only")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    let stat = wasi::path_filestat_get(dir_fd, 0, "file").expect("stat of the file");
+    assert_eq!(stat.size, 2, "the file shouldn't have been truncated");
+
+    // Rights which the directory doesn't allow its files to have aren't
+    // granted, even if they're asked for.
+    drop_rights(dir_fd, 0, wasi::RIGHTS_FD_WRITE);
+    let fd = wasi::path_open(dir_fd, 0, "file", 0, READ_RIGHTS | WRITE_RIGHTS, 0, 0)
+        .expect("opening the file");
+    assert_eq!(
+        fd_get_rights(fd),
+        (READ_RIGHTS | (WRITE_RIGHTS & !wasi::RIGHTS_FD_WRITE), 0)
+    );
+    assert_errno!(
+        write_byte(fd)
+            .expect_err("writing without the fd_write right")
+            .raw_error(),
+        wasi::ERRNO_NOTCAPABLE
+    );
+    wasi::fd_close(fd).expect("closing a file");
+
+    wasi::path_unlink_file(dir_fd, "file").expect("removing a file");
+}
+
+fn main() {
+    let mut args = env::args();
+    let prog = args.next().unwrap();
+    let arg = if let Some(arg) = args.next() {
+        arg
+    } else {
+        eprintln!("usage: {} <scratch directory>", prog);
+        process::exit(1);
+    };
+
+    // Open scratch directory
+    let dir_fd = match open_scratch_directory(&arg) {
+        Ok(dir_fd) => dir_fd,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    // Run the tests.
+    unsafe { test_path_open_rights(dir_fd) }
+}
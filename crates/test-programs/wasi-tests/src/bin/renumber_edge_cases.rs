@@ -0,0 +1,150 @@
+use more_asserts::assert_gt;
+use std::{env, process};
+use wasi_tests::{assert_errno, open_scratch_directory};
+
+unsafe fn open_file(dir_fd: wasi::Fd, name: &str) -> wasi::Fd {
+    let fd = wasi::path_open(
+        dir_fd,
+        0,
+        name,
+        wasi::OFLAGS_CREAT,
+        wasi::RIGHTS_FD_READ | wasi::RIGHTS_FD_WRITE | wasi::RIGHTS_FD_SEEK,
+        0,
+        0,
+    )
+    .expect("opening a file");
+    assert_gt!(
+        fd,
+        libc::STDERR_FILENO as wasi::Fd,
+        "file descriptor range check",
+    );
+    fd
+}
+
+unsafe fn write_all(fd: wasi::Fd, contents: &[u8]) {
+    let ciovec = wasi::Ciovec {
+        buf: contents.as_ptr(),
+        buf_len: contents.len(),
+    };
+    let nwritten = wasi::fd_write(fd, &[ciovec]).expect("writing to a file");
+    assert_eq!(nwritten, contents.len(), "nwritten bytes check");
+}
+
+unsafe fn read_from_start(fd: wasi::Fd) -> Vec<u8> {
+    let mut contents = vec![0; 64];
+    let iovec = wasi::Iovec {
+        buf: contents.as_mut_ptr(),
+        buf_len: contents.len(),
+    };
+    let nread = wasi::fd_pread(fd, &[iovec], 0).expect("reading from a file");
+    contents.truncate(nread);
+    contents
+}
+
+unsafe fn test_renumber_onto_open_fd(dir_fd: wasi::Fd) {
+    let fd_from = open_file(dir_fd, "file1");
+    let fd_to = open_file(dir_fd, "file2");
+    write_all(fd_from, b"from");
+    write_all(fd_to, b"to");
+
+    wasi::fd_renumber(fd_from, fd_to).expect("renumbering onto an open descriptor");
+
+    // `fd_from` is gone and `fd_to` now refers to file1.
+    assert_errno!(
+        wasi::fd_fdstat_get(fd_from)
+            .expect_err("fdstat on a renumbered descriptor")
+            .raw_error(),
+        wasi::ERRNO_BADF
+    );
+    assert_eq!(read_from_start(fd_to), b"from");
+
+    // Whatever was written through the replaced descriptor made it to file2.
+    let fd = open_file(dir_fd, "file2");
+    assert_eq!(read_from_start(fd), b"to");
+
+    wasi::fd_close(fd).expect("closing a file");
+    wasi::fd_close(fd_to).expect("closing a file");
+    wasi::path_unlink_file(dir_fd, "file1").expect("removing a file");
+    wasi::path_unlink_file(dir_fd, "file2").expect("removing a file");
+}
+
+unsafe fn test_renumber_self(dir_fd: wasi::Fd) {
+    let fd = open_file(dir_fd, "file");
+    write_all(fd, b"self");
+
+    // Renumbering a descriptor onto itself does nothing.
+    wasi::fd_renumber(fd, fd).expect("renumbering a descriptor onto itself");
+    assert_eq!(read_from_start(fd), b"self");
+
+    wasi::fd_close(fd).expect("closing a file");
+    wasi::path_unlink_file(dir_fd, "file").expect("removing a file");
+
+    // But the descriptor still has to exist.
+    assert_errno!(
+        wasi::fd_renumber(fd, fd)
+            .expect_err("renumbering a closed descriptor onto itself")
+            .raw_error(),
+        wasi::ERRNO_BADF
+    );
+}
+
+unsafe fn test_renumber_preopen(dir_fd: wasi::Fd) {
+    let pre_fd: wasi::Fd = (libc::STDERR_FILENO + 1) as wasi::Fd;
+    let prestat = wasi::fd_prestat_get(pre_fd).expect("fd_prestat_get on a preopen");
+
+    // Move the preopen onto a descriptor which is already open.
+    let fd = open_file(dir_fd, "file");
+    wasi::fd_renumber(pre_fd, fd).expect("renumbering a preopen");
+    assert_errno!(
+        wasi::fd_prestat_get(pre_fd)
+            .expect_err("fd_prestat_get on a renumbered preopen")
+            .raw_error(),
+        wasi::ERRNO_BADF
+    );
+
+    // The new number is still a preopen with the same name, and still a directory.
+    let renumbered = wasi::fd_prestat_get(fd).expect("fd_prestat_get on a renumbered preopen");
+    assert_eq!(prestat.tag, renumbered.tag, "prestat tag");
+    assert_eq!(
+        prestat.u.dir.pr_name_len, renumbered.u.dir.pr_name_len,
+        "prestat name length"
+    );
+    let fdstat = wasi::fd_fdstat_get(fd).expect("fd_fdstat_get on a renumbered preopen");
+    assert_eq!(
+        fdstat.fs_filetype,
+        wasi::FILETYPE_DIRECTORY,
+        "expected the renumbered preopen to be a directory",
+    );
+
+    // Move it back so the rest of the program sees the usual layout.
+    wasi::fd_renumber(fd, pre_fd).expect("renumbering a preopen back");
+    wasi::fd_prestat_get(pre_fd).expect("fd_prestat_get on a preopen");
+    wasi::path_unlink_file(dir_fd, "file").expect("removing a file");
+}
+
+fn main() {
+    let mut args = env::args();
+    let prog = args.next().unwrap();
+    let arg = if let Some(arg) = args.next() {
+        arg
+    } else {
+        eprintln!("usage: {} <scratch directory>", prog);
+        process::exit(1);
+    };
+
+    // Open scratch directory
+    let dir_fd = match open_scratch_directory(&arg) {
+        Ok(dir_fd) => dir_fd,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    // Run the tests.
+    unsafe {
+        test_renumber_onto_open_fd(dir_fd);
+        test_renumber_self(dir_fd);
+        test_renumber_preopen(dir_fd);
+    }
+}
@@ -0,0 +1,28 @@
+use wasi_tests::{STDERR_FD, STDIN_FD, STDOUT_FD};
+
+unsafe fn test_stdio_filestat() {
+    for fd in &[STDIN_FD, STDOUT_FD, STDERR_FD] {
+        let stat = wasi::fd_filestat_get(*fd).expect("fd_filestat_get on stdio");
+        assert_eq!(
+            stat.filetype,
+            wasi::FILETYPE_CHARACTER_DEVICE,
+            "stdio should be a character device"
+        );
+        assert_eq!(stat.atim, 0, "stdio atim");
+        assert_eq!(stat.mtim, 0, "stdio mtim");
+
+        // Like `futimens` on a terminal, this succeeds without effect.
+        wasi::fd_filestat_set_times(*fd, 0, 0, wasi::FSTFLAGS_ATIM_NOW | wasi::FSTFLAGS_MTIM_NOW)
+            .expect("fd_filestat_set_times to now on stdio");
+        wasi::fd_filestat_set_times(*fd, 1, 2, wasi::FSTFLAGS_ATIM | wasi::FSTFLAGS_MTIM)
+            .expect("fd_filestat_set_times on stdio");
+        let stat = wasi::fd_filestat_get(*fd).expect("fd_filestat_get on stdio");
+        assert_eq!(stat.atim, 0, "stdio atim after set_times");
+        assert_eq!(stat.mtim, 0, "stdio mtim after set_times");
+    }
+}
+
+fn main() {
+    // Run the tests.
+    unsafe { test_stdio_filestat() }
+}
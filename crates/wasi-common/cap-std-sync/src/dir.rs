@@ -1,7 +1,12 @@
 use crate::file::{filetype_from, File};
+use crate::inode::{self, Inodes, HOST_INODES};
 use cap_fs_ext::{DirEntryExt, DirExt, MetadataExt, SystemTimeSpec};
 use std::any::Any;
-use std::path::{Path, PathBuf};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Component, Path, PathBuf};
 use system_interface::fs::GetSetFdFlags;
 use wasi_common::{
     dir::{ReaddirCursor, ReaddirEntity, WasiDir},
@@ -9,11 +14,62 @@ use wasi_common::{
     Error, ErrorExt,
 };
 
-pub struct Dir(cap_std::fs::Dir);
+/// The most symlinks `Dir::resolve` replaces by their targets in one path,
+/// the same as Linux's limit. Beyond that the path is left to cap-std, which
+/// reports it as a loop.
+const MAX_SYMLINK_EXPANSIONS: usize = 40;
+
+pub struct Dir {
+    dir: cap_std::fs::Dir,
+    /// The path of this directory relative to the one passed to
+    /// `from_cap_std`, used to synthesize inode numbers on hosts which need it.
+    path: PathBuf,
+    inodes: Inodes,
+    case_insensitive: bool,
+}
 
 impl Dir {
     pub fn from_cap_std(dir: cap_std::fs::Dir) -> Self {
-        Dir(dir)
+        Dir {
+            dir,
+            path: PathBuf::new(),
+            inodes: Inodes::default(),
+            case_insensitive: false,
+        }
+    }
+
+    /// Sets whether paths opened through this directory, and the directories
+    /// opened from it, are matched against its entries ignoring case.
+    ///
+    /// Each component of a path which doesn't exist as given is looked up by
+    /// reading its parent directory and is replaced by the entry whose name
+    /// matches it ignoring case, so the case of names on disk is preserved. An
+    /// exact match always wins, and if two or more entries match a component
+    /// which has no exact match the operation fails with
+    /// [`ErrorKind::Exist`](wasi_common::ErrorKind::Exist). Components which
+    /// don't match anything are used as given, so new files are created with
+    /// the case the guest asked for. The targets of symlinks are matched the
+    /// same way, relative to the directory the symlink is in.
+    ///
+    /// Paths are still resolved by cap-std, so this doesn't give access to
+    /// anything outside of this directory.
+    ///
+    /// Every component which isn't found as given costs a read of the
+    /// directory it's in, so lookups which miss are much slower with this
+    /// enabled.
+    pub fn case_insensitive_paths(mut self, enable: bool) -> Self {
+        self.case_insensitive = enable;
+        self
+    }
+
+    /// Returns the inode number to report for `path`, relative to this
+    /// directory, given its host metadata.
+    fn inode(&self, meta: &cap_std::fs::Metadata, path: &str) -> u64 {
+        if HOST_INODES {
+            meta.ino()
+        } else {
+            self.inodes.get(&inode::join(&self.path, path))
+        }
     }
 
     pub fn open_file_(
@@ -27,6 +83,9 @@ impl Dir {
     ) -> Result<File, Error> {
         use cap_fs_ext::{FollowSymlinks, OpenOptionsFollowExt};
 
+        let path = self.resolve(path, symlink_follow)?;
+        let path = &*path;
+
         let mut opts = cap_std::fs::OpenOptions::new();
 
         if oflags.contains(OFlags::CREATE | OFlags::EXCLUSIVE) {
@@ -71,27 +130,42 @@ impl Dir {
             return Err(Error::not_supported().context("SYNC family of FdFlags"));
         }
 
-        let mut f = self.0.open_with(Path::new(path), &opts)?;
+        let mut f = self.dir.open_with(Path::new(path), &opts)?;
         // NONBLOCK does not have an OpenOption either, but we can patch that on with set_fd_flags:
         if fdflags.contains(wasi_common::file::FdFlags::NONBLOCK) {
             let set_fd_flags = f.new_set_fd_flags(system_interface::fs::FdFlags::NONBLOCK)?;
             f.set_fd_flags(set_fd_flags)?;
         }
-        Ok(File::from_cap_std(f))
+        let mut file = File::from_cap_std(f);
+        if !HOST_INODES {
+            file.set_inode(self.inodes.get(&inode::join(&self.path, path)));
+        }
+        Ok(file)
     }
 
     pub fn open_dir_(&self, symlink_follow: bool, path: &str) -> Result<Self, Error> {
-        let d = if symlink_follow {
-            self.0.open_dir(Path::new(path))?
+        let path = self.resolve(path, symlink_follow)?;
+        let path = &*path;
+        let dir = if symlink_follow {
+            self.dir.open_dir(Path::new(path))?
         } else {
-            self.0.open_dir_nofollow(Path::new(path))?
+            self.dir.open_dir_nofollow(Path::new(path))?
         };
-        Ok(Dir::from_cap_std(d))
+        Ok(Dir {
+            dir,
+            path: inode::join(&self.path, path),
+            inodes: self.inodes.clone(),
+            case_insensitive: self.case_insensitive,
+        })
     }
 
     pub fn rename_(&self, src_path: &str, dest_dir: &Self, dest_path: &str) -> Result<(), Error> {
-        self.0
-            .rename(Path::new(src_path), &dest_dir.0, Path::new(dest_path))?;
+        let src_path = self.resolve(src_path, false)?;
+        let src_path = &*src_path;
+        let dest_path = dest_dir.resolve(dest_path, false)?;
+        let dest_path = &*dest_path;
+        self.dir
+            .rename(Path::new(src_path), &dest_dir.dir, Path::new(dest_path))?;
         Ok(())
     }
     pub fn hard_link_(
@@ -100,11 +174,134 @@ impl Dir {
         target_dir: &Self,
         target_path: &str,
     ) -> Result<(), Error> {
-        let src_path = Path::new(src_path);
-        let target_path = Path::new(target_path);
-        self.0.hard_link(src_path, &target_dir.0, target_path)?;
+        let src_path = self.resolve(src_path, false)?;
+        let target_path = target_dir.resolve(target_path, false)?;
+        self.dir.hard_link(
+            Path::new(&*src_path),
+            &target_dir.dir,
+            Path::new(&*target_path),
+        )?;
         Ok(())
     }
+
+    /// Returns `path` with the case of its components adjusted to match the
+    /// entries on disk, if case-insensitive paths are enabled.
+    ///
+    /// Symlinks in the middle of `path`, and at its end if `follow` is set,
+    /// are replaced by their targets, whose components are matched the same
+    /// way relative to the directory the symlink is in.
+    fn resolve<'a>(&self, path: &'a str, follow: bool) -> Result<Cow<'a, str>, Error> {
+        if !self.case_insensitive || Path::new(path).is_absolute() {
+            return Ok(Cow::Borrowed(path));
+        }
+        let mut resolved = PathBuf::new();
+        let mut changed = false;
+        let mut symlinks_expanded = 0;
+        let mut components = Path::new(path)
+            .components()
+            .map(|c| c.as_os_str().to_owned())
+            .collect::<VecDeque<_>>();
+        while let Some(name) = components.pop_front() {
+            match Path::new(&name).components().next() {
+                Some(Component::Normal(_)) => {}
+                Some(Component::CurDir) => continue,
+                // There are no symlinks in `resolved`, so `..` can just remove
+                // the last component. Otherwise it's left to cap-std, which
+                // keeps `..` from leaving the sandbox.
+                Some(Component::ParentDir) if resolved.file_name().is_some() => {
+                    resolved.pop();
+                    continue;
+                }
+                _ => {
+                    resolved.push(name);
+                    continue;
+                }
+            }
+            let exact = resolved.join(&name);
+            let meta = match self.dir.symlink_metadata(&exact) {
+                Ok(meta) => Some(meta),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+            let (entry, meta) = match meta {
+                Some(meta) => (exact, meta),
+                None => match self.find_ignoring_case(&resolved, &name)? {
+                    Some(entry) => {
+                        changed = true;
+                        let entry = resolved.join(entry);
+                        let meta = self.dir.symlink_metadata(&entry)?;
+                        (entry, meta)
+                    }
+                    // Nothing below a missing entry exists either, so the rest
+                    // of the path is used as given.
+                    None => {
+                        resolved.push(name);
+                        resolved.extend(components);
+                        break;
+                    }
+                },
+            };
+            if !meta.file_type().is_symlink() || (!follow && components.is_empty()) {
+                resolved = entry;
+                continue;
+            }
+            let target = self.dir.read_link(&entry)?;
+            // Absolute targets are left to cap-std, which doesn't allow them,
+            // and so are chains of symlinks long enough to be a loop.
+            if target.is_absolute() || symlinks_expanded == MAX_SYMLINK_EXPANSIONS {
+                resolved = entry;
+                resolved.extend(components);
+                break;
+            }
+            symlinks_expanded += 1;
+            changed = true;
+            for component in target.components().rev() {
+                components.push_front(component.as_os_str().to_owned());
+            }
+        }
+        if !changed {
+            return Ok(Cow::Borrowed(path));
+        }
+        let mut resolved = resolved
+            .into_os_string()
+            .into_string()
+            .map_err(|_| Error::illegal_byte_sequence().context("filename"))?;
+        if resolved.is_empty() {
+            resolved.push('.');
+        }
+        if path.ends_with('/') {
+            resolved.push('/');
+        }
+        Ok(Cow::Owned(resolved))
+    }
+
+    /// Returns the name of the entry of the directory at `dir` which matches
+    /// `name` ignoring case, if there is exactly one.
+    fn find_ignoring_case(&self, dir: &Path, name: &OsStr) -> Result<Option<OsString>, Error> {
+        let name = match name.to_str() {
+            Some(name) => name.to_lowercase(),
+            None => return Ok(None),
+        };
+        let entries = if dir.as_os_str().is_empty() {
+            self.dir.entries()?
+        } else {
+            self.dir.open_dir(dir)?.entries()?
+        };
+        let mut found = None;
+        for entry in entries {
+            let entry = entry?.file_name();
+            if entry.to_str().map(str::to_lowercase).as_deref() != Some(name.as_str()) {
+                continue;
+            }
+            if found.is_some() {
+                return Err(
+                    Error::exist().context("more than one entry matches the path ignoring case")
+                );
+            }
+            found = Some(entry);
+        }
+        Ok(found)
+    }
 }
 
 #[async_trait::async_trait]
@@ -131,7 +328,8 @@ impl WasiDir for Dir {
     }
 
     async fn create_dir(&self, path: &str) -> Result<(), Error> {
-        self.0.create_dir(Path::new(path))?;
+        let path = self.resolve(path, false)?;
+        self.dir.create_dir(Path::new(&*path))?;
         Ok(())
     }
     async fn readdir(
@@ -141,29 +339,39 @@ impl WasiDir for Dir {
         // cap_std's read_dir does not include . and .., we should prepend these.
         // Why does the Ok contain a tuple? We can't construct a cap_std::fs::DirEntry, and we don't
         // have enough info to make a ReaddirEntity yet.
-        let dir_meta = self.0.dir_metadata()?;
+        let dir_meta = self.dir.dir_metadata()?;
+        let dir_inode = self.inode(&dir_meta, ".");
+        // The parent may be outside of the sandbox, so with host inode numbers
+        // `..` is reported with this directory's own number.
+        let parent_inode = self.inode(&dir_meta, "..");
+        let path = self.path.clone();
+        let inodes = self.inodes.clone();
         let rd = vec![
             {
                 let name = ".".to_owned();
-                Ok((FileType::Directory, dir_meta.ino(), name))
+                Ok((FileType::Directory, dir_inode, name))
             },
             {
                 let name = "..".to_owned();
-                Ok((FileType::Directory, dir_meta.ino(), name))
+                Ok((FileType::Directory, parent_inode, name))
             },
         ]
         .into_iter()
         .chain({
             // Now process the `DirEntry`s:
-            let entries = self.0.entries()?.map(|entry| {
+            let entries = self.dir.entries()?.map(move |entry| {
                 let entry = entry?;
                 let meta = entry.full_metadata()?;
-                let inode = meta.ino();
                 let filetype = filetype_from(&meta.file_type());
                 let name = entry
                     .file_name()
                     .into_string()
                     .map_err(|_| Error::illegal_byte_sequence().context("filename"))?;
+                let inode = if HOST_INODES {
+                    meta.ino()
+                } else {
+                    inodes.get(&path.join(&name))
+                };
                 Ok((filetype, inode, name))
             });
 
@@ -203,27 +411,31 @@ impl WasiDir for Dir {
     }
 
     async fn symlink(&self, src_path: &str, dest_path: &str) -> Result<(), Error> {
-        self.0.symlink(src_path, dest_path)?;
+        let dest_path = self.resolve(dest_path, false)?;
+        self.dir.symlink(src_path, &*dest_path)?;
         Ok(())
     }
     async fn remove_dir(&self, path: &str) -> Result<(), Error> {
-        self.0.remove_dir(Path::new(path))?;
+        let path = self.resolve(path, false)?;
+        self.dir.remove_dir(Path::new(&*path))?;
         Ok(())
     }
 
     async fn unlink_file(&self, path: &str) -> Result<(), Error> {
-        self.0.remove_file_or_symlink(Path::new(path))?;
+        let path = self.resolve(path, false)?;
+        self.dir.remove_file_or_symlink(Path::new(&*path))?;
         Ok(())
     }
     async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
-        let link = self.0.read_link(Path::new(path))?;
+        let path = self.resolve(path, false)?;
+        let link = self.dir.read_link(Path::new(&*path))?;
         Ok(link)
     }
     async fn get_filestat(&self) -> Result<Filestat, Error> {
-        let meta = self.0.dir_metadata()?;
+        let meta = self.dir.dir_metadata()?;
         Ok(Filestat {
             device_id: meta.dev(),
-            inode: meta.ino(),
+            inode: self.inode(&meta, "."),
             filetype: filetype_from(&meta.file_type()),
             nlink: meta.nlink(),
             size: meta.len(),
@@ -232,19 +444,30 @@ impl WasiDir for Dir {
             ctim: meta.created().map(|t| Some(t.into_std())).unwrap_or(None),
         })
     }
+    async fn sync(&self) -> Result<(), Error> {
+        // On Unix, an fsync of the directory itself is what makes the
+        // creation, removal and renaming of its entries durable.
+        #[cfg(unix)]
+        self.dir.open(".")?.sync_all()?;
+        // Windows has no equivalent for directories, and an entry's changes
+        // are made durable by flushing the entry itself.
+        Ok(())
+    }
     async fn get_path_filestat(
         &self,
         path: &str,
         follow_symlinks: bool,
     ) -> Result<Filestat, Error> {
+        let path = self.resolve(path, follow_symlinks)?;
+        let path = &*path;
         let meta = if follow_symlinks {
-            self.0.metadata(Path::new(path))?
+            self.dir.metadata(Path::new(path))?
         } else {
-            self.0.symlink_metadata(Path::new(path))?
+            self.dir.symlink_metadata(Path::new(path))?
         };
         Ok(Filestat {
             device_id: meta.dev(),
-            inode: meta.ino(),
+            inode: self.inode(&meta, path),
             filetype: filetype_from(&meta.file_type()),
             nlink: meta.nlink(),
             size: meta.len(),
@@ -284,14 +507,16 @@ impl WasiDir for Dir {
         mtime: Option<wasi_common::SystemTimeSpec>,
         follow_symlinks: bool,
     ) -> Result<(), Error> {
+        let path = self.resolve(path, follow_symlinks)?;
+        let path = &*path;
         if follow_symlinks {
-            self.0.set_times(
+            self.dir.set_times(
                 Path::new(path),
                 convert_systimespec(atime),
                 convert_systimespec(mtime),
             )?;
         } else {
-            self.0.set_symlink_times(
+            self.dir.set_symlink_times(
                 Path::new(path),
                 convert_systimespec(atime),
                 convert_systimespec(mtime),
@@ -389,6 +614,179 @@ mod test {
         );
     }
 
+    #[test]
+    fn readdir_inodes_match_filestat() {
+        use wasi_common::dir::{ReaddirCursor, WasiDir};
+        use wasi_common::file::{FdFlags, OFlags};
+
+        let tempdir = tempfile::Builder::new()
+            .prefix("cap-std-sync")
+            .tempdir()
+            .expect("create temporary dir");
+        let preopen_dir = cap_std::fs::Dir::open_ambient_dir(tempdir.path(), ambient_authority())
+            .expect("open ambient temporary dir");
+        let preopen_dir = Dir::from_cap_std(preopen_dir);
+
+        run(preopen_dir.create_dir("sub")).expect("create sub");
+        for path in ["file1", "sub/file2"].iter() {
+            run(preopen_dir.open_file(false, path, OFlags::CREATE, true, false, FdFlags::empty()))
+                .expect("create file");
+        }
+        let sub_dir = run(preopen_dir.open_dir(false, "sub")).expect("open sub");
+
+        for (dir, path) in [(&preopen_dir as &dyn WasiDir, ""), (&*sub_dir, "sub/")].iter() {
+            let entities = run(dir.readdir(ReaddirCursor::from(0))).expect("readdir succeeds");
+            for entity in entities {
+                let entity = entity.expect("readdir entry is valid");
+                if entity.name == ".." {
+                    continue;
+                }
+                let stat = run(dir.get_path_filestat(&entity.name, false)).expect("stat entry");
+                assert_eq!(entity.inode, stat.inode, "{}", entity.name);
+
+                // The same entry reached through another directory.
+                let full_path = format!("{}{}", path, entity.name);
+                let stat = run(preopen_dir.get_path_filestat(&full_path, false))
+                    .expect("stat entry from preopen");
+                assert_eq!(entity.inode, stat.inode, "{}", full_path);
+            }
+        }
+
+        let stat = run(sub_dir.get_filestat()).expect("stat sub");
+        let entity = run(preopen_dir.readdir(ReaddirCursor::from(0)))
+            .expect("readdir succeeds")
+            .map(|entity| entity.expect("readdir entry is valid"))
+            .find(|entity| entity.name == "sub")
+            .expect("sub entry");
+        assert_eq!(entity.inode, stat.inode);
+
+        let file = run(sub_dir.open_file(
+            false,
+            "file2",
+            OFlags::empty(),
+            true,
+            false,
+            FdFlags::empty(),
+        ))
+        .expect("open file2");
+        let stat = run(file.get_filestat()).expect("stat file2");
+        let entity = run(sub_dir.readdir(ReaddirCursor::from(0)))
+            .expect("readdir succeeds")
+            .map(|entity| entity.expect("readdir entry is valid"))
+            .find(|entity| entity.name == "file2")
+            .expect("file2 entry");
+        assert_eq!(entity.inode, stat.inode);
+    }
+
+    #[test]
+    fn case_insensitive_paths() {
+        use wasi_common::dir::WasiDir;
+        use wasi_common::file::{FdFlags, FileType, OFlags};
+        use wasi_common::ErrorKind;
+
+        let tempdir = tempfile::Builder::new()
+            .prefix("cap-std-sync")
+            .tempdir()
+            .expect("create temporary dir");
+        std::fs::create_dir(tempdir.path().join("sandbox")).expect("create sandbox");
+        std::fs::create_dir(tempdir.path().join("sandbox/Sub")).expect("create Sub");
+        std::fs::write(tempdir.path().join("sandbox/Sub/config.ini"), "").expect("create file");
+        std::fs::write(tempdir.path().join("secret"), "").expect("create secret");
+        let open = || {
+            cap_std::fs::Dir::open_ambient_dir(tempdir.path().join("sandbox"), ambient_authority())
+                .expect("open ambient temporary dir")
+        };
+        // Only check that paths are case-sensitive without the flag if the
+        // host's filesystem is.
+        let case_sensitive = !tempdir.path().join("sandbox/sub").exists();
+        let exact = Dir::from_cap_std(open());
+        let dir = Dir::from_cap_std(open()).case_insensitive_paths(true);
+        let open_file = |dir: &Dir, path: &str| {
+            run(dir.open_file(false, path, OFlags::empty(), true, false, FdFlags::empty()))
+        };
+
+        open_file(&dir, "SUB/CONFIG.INI").expect("open with the wrong case");
+        open_file(&dir, "./sub/../Sub/Config.ini").expect("open with `.` and `..`");
+        let sub = run(dir.open_dir(false, "sub/")).expect("open the directory");
+        open_file(sub.as_any().downcast_ref::<Dir>().unwrap(), "CONFIG.ini")
+            .expect("open from a directory opened with the wrong case");
+        run(dir.get_path_filestat("sub/config.INI", false)).expect("stat with the wrong case");
+        if case_sensitive {
+            assert!(open_file(&exact, "SUB/CONFIG.INI").is_err());
+        }
+
+        // New files keep the case they're created with, and existing ones the
+        // case they have on disk.
+        run(dir.open_file(
+            false,
+            "sub/New.txt",
+            OFlags::CREATE,
+            true,
+            true,
+            FdFlags::empty(),
+        ))
+        .expect("create a file");
+        assert!(tempdir.path().join("sandbox/Sub/New.txt").exists());
+        run(dir.rename("SUB/NEW.TXT", &dir, "sub/Renamed.txt")).expect("rename");
+        assert!(tempdir.path().join("sandbox/Sub/Renamed.txt").exists());
+        run(dir.unlink_file("sub/renamed.TXT")).expect("unlink with the wrong case");
+        assert!(!tempdir.path().join("sandbox/Sub/Renamed.txt").exists());
+
+        // Names which differ only by case are ambiguous unless one of them
+        // matches exactly.
+        if case_sensitive {
+            std::fs::write(tempdir.path().join("sandbox/Sub/CONFIG.ini"), "").expect("create file");
+            open_file(&dir, "sub/config.ini").expect("open the exact match");
+            open_file(&dir, "sub/CONFIG.ini").expect("open the exact match");
+            let err = open_file(&dir, "sub/Config.INI")
+                .err()
+                .expect("ambiguous path");
+            assert!(matches!(
+                err.downcast_ref::<ErrorKind>(),
+                Some(ErrorKind::Exist)
+            ));
+        }
+
+        // Paths still can't escape the sandbox.
+        for path in ["../secret", "../SECRET", "sub/../../Secret", "/secret"].iter() {
+            assert!(open_file(&dir, *path).is_err(), "{}", path);
+            assert!(run(dir.get_path_filestat(path, true)).is_err(), "{}", path);
+        }
+
+        // The targets of symlinks are matched relative to the directory the
+        // symlink is in, and can't escape the sandbox either.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::symlink;
+
+            let sandbox = tempdir.path().join("sandbox");
+            std::fs::write(sandbox.join("Sub/Target.txt"), "").expect("create file");
+            symlink("SUB", sandbox.join("link")).expect("create link");
+            symlink("target.TXT", sandbox.join("Sub/config-link")).expect("create link");
+            symlink("../link/TARGET.txt", sandbox.join("Sub/up-link")).expect("create link");
+            symlink("../secret", sandbox.join("escape")).expect("create link");
+            symlink("loop", sandbox.join("loop")).expect("create link");
+
+            open_file(&dir, "LINK/target.txt").expect("open through a link");
+            let follow = |path: &str| {
+                run(dir.open_file(true, path, OFlags::empty(), true, false, FdFlags::empty()))
+            };
+            follow("sub/CONFIG-LINK").expect("open a link");
+            follow("link/Up-Link").expect("open a link through links");
+            run(dir.get_path_filestat("Sub/config-link", true)).expect("stat a link");
+            let stat = run(dir.get_path_filestat("Sub/config-link", false)).expect("stat a link");
+            assert_eq!(stat.filetype, FileType::SymbolicLink);
+            if case_sensitive {
+                assert!(open_file(&exact, "link/Target.txt").is_err());
+            }
+
+            for path in ["escape", "ESCAPE", "escape/../secret", "loop/x"].iter() {
+                assert!(follow(path).is_err(), "{}", path);
+                assert!(run(dir.get_path_filestat(path, true)).is_err(), "{}", path);
+            }
+        }
+    }
+
     fn run<F: std::future::Future>(future: F) -> F::Output {
         use std::pin::Pin;
         use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
@@ -12,11 +12,20 @@ use wasi_common::{
     Error, ErrorExt,
 };
 
-pub struct File(cap_std::fs::File);
+pub struct File {
+    file: cap_std::fs::File,
+    /// A synthesized inode number to report instead of the host's, see the
+    /// `inode` module.
+    inode: Option<u64>,
+}
 
 impl File {
     pub fn from_cap_std(file: cap_std::fs::File) -> Self {
-        File(file)
+        File { file, inode: None }
+    }
+
+    pub(crate) fn set_inode(&mut self, inode: u64) {
+        self.inode = Some(inode);
     }
 }
 
@@ -26,19 +35,22 @@ impl WasiFile for File {
         self
     }
     async fn datasync(&self) -> Result<(), Error> {
-        self.0.sync_data()?;
+        // This is `fdatasync` where the host has it, and a full `fsync` where
+        // it doesn't, such as on macOS and some BSDs. On Windows both this
+        // and `sync` are `FlushFileBuffers`.
+        self.file.sync_data()?;
         Ok(())
     }
     async fn sync(&self) -> Result<(), Error> {
-        self.0.sync_all()?;
+        self.file.sync_all()?;
         Ok(())
     }
     async fn get_filetype(&self) -> Result<FileType, Error> {
-        let meta = self.0.metadata()?;
+        let meta = self.file.metadata()?;
         Ok(filetype_from(&meta.file_type()))
     }
     async fn get_fdflags(&self) -> Result<FdFlags, Error> {
-        let fdflags = self.0.get_fd_flags()?;
+        let fdflags = self.file.get_fd_flags()?;
         Ok(from_sysif_fdflags(fdflags))
     }
     async fn set_fdflags(&mut self, fdflags: FdFlags) -> Result<(), Error> {
@@ -49,15 +61,15 @@ impl WasiFile for File {
         ) {
             return Err(Error::invalid_argument().context("cannot set DSYNC, SYNC, or RSYNC flag"));
         }
-        let set_fd_flags = self.0.new_set_fd_flags(to_sysif_fdflags(fdflags))?;
-        self.0.set_fd_flags(set_fd_flags)?;
+        let set_fd_flags = self.file.new_set_fd_flags(to_sysif_fdflags(fdflags))?;
+        self.file.set_fd_flags(set_fd_flags)?;
         Ok(())
     }
     async fn get_filestat(&self) -> Result<Filestat, Error> {
-        let meta = self.0.metadata()?;
+        let meta = self.file.metadata()?;
         Ok(Filestat {
             device_id: meta.dev(),
-            inode: meta.ino(),
+            inode: self.inode.unwrap_or_else(|| meta.ino()),
             filetype: filetype_from(&meta.file_type()),
             nlink: meta.nlink(),
             size: meta.len(),
@@ -67,15 +79,15 @@ impl WasiFile for File {
         })
     }
     async fn set_filestat_size(&self, size: u64) -> Result<(), Error> {
-        self.0.set_len(size)?;
+        self.file.set_len(size)?;
         Ok(())
     }
     async fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<(), Error> {
-        self.0.advise(offset, len, convert_advice(advice))?;
+        self.file.advise(offset, len, convert_advice(advice))?;
         Ok(())
     }
     async fn allocate(&self, offset: u64, len: u64) -> Result<(), Error> {
-        self.0.allocate(offset, len)?;
+        self.file.allocate(offset, len)?;
         Ok(())
     }
     async fn set_times(
@@ -83,12 +95,12 @@ impl WasiFile for File {
         atime: Option<wasi_common::SystemTimeSpec>,
         mtime: Option<wasi_common::SystemTimeSpec>,
     ) -> Result<(), Error> {
-        self.0
+        self.file
             .set_times(convert_systimespec(atime), convert_systimespec(mtime))?;
         Ok(())
     }
     async fn read_vectored<'a>(&self, bufs: &mut [io::IoSliceMut<'a>]) -> Result<u64, Error> {
-        let n = self.0.read_vectored(bufs)?;
+        let n = self.file.read_vectored(bufs)?;
         Ok(n.try_into()?)
     }
     async fn read_vectored_at<'a>(
@@ -96,11 +108,11 @@ impl WasiFile for File {
         bufs: &mut [io::IoSliceMut<'a>],
         offset: u64,
     ) -> Result<u64, Error> {
-        let n = self.0.read_vectored_at(bufs, offset)?;
+        let n = self.file.read_vectored_at(bufs, offset)?;
         Ok(n.try_into()?)
     }
     async fn write_vectored<'a>(&self, bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
-        let n = self.0.write_vectored(bufs)?;
+        let n = self.file.write_vectored(bufs)?;
         Ok(n.try_into()?)
     }
     async fn write_vectored_at<'a>(
@@ -108,18 +120,18 @@ impl WasiFile for File {
         bufs: &[io::IoSlice<'a>],
         offset: u64,
     ) -> Result<u64, Error> {
-        let n = self.0.write_vectored_at(bufs, offset)?;
+        let n = self.file.write_vectored_at(bufs, offset)?;
         Ok(n.try_into()?)
     }
     async fn seek(&self, pos: std::io::SeekFrom) -> Result<u64, Error> {
-        Ok(self.0.seek(pos)?)
+        Ok(self.file.seek(pos)?)
     }
     async fn peek(&self, buf: &mut [u8]) -> Result<u64, Error> {
-        let n = self.0.peek(buf)?;
+        let n = self.file.peek(buf)?;
         Ok(n.try_into()?)
     }
     async fn num_ready_bytes(&self) -> Result<u64, Error> {
-        Ok(self.0.num_ready_bytes()?)
+        Ok(self.file.num_ready_bytes()?)
     }
     async fn readable(&self) -> Result<(), Error> {
         Err(Error::badf())
@@ -157,7 +169,7 @@ use io_lifetimes::{AsHandle, BorrowedHandle};
 #[cfg(windows)]
 impl AsHandle for File {
     fn as_handle(&self) -> BorrowedHandle<'_> {
-        self.0.as_handle()
+        self.file.as_handle()
     }
 }
 
@@ -166,7 +178,7 @@ use io_lifetimes::{AsFd, BorrowedFd};
 #[cfg(unix)]
 impl AsFd for File {
     fn as_fd(&self) -> BorrowedFd<'_> {
-        self.0.as_fd()
+        self.file.as_fd()
     }
 }
 pub fn convert_systimespec(t: Option<wasi_common::SystemTimeSpec>) -> Option<SystemTimeSpec> {
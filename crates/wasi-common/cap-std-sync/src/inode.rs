@@ -0,0 +1,58 @@
+//! Synthesized inode numbers for hosts which don't report them consistently.
+//!
+//! On Windows a file's index is only available from an open handle, so the
+//! inode numbers seen through `fd_readdir`, `path_filestat_get` and
+//! `fd_filestat_get` couldn't be relied upon to agree. On such hosts an entry
+//! is instead given an inode number the first time its path is seen, where
+//! paths are relative to the directory passed to `Dir::from_cap_std`
+//! (typically a preopen).
+//!
+//! These numbers are stable for as long as that directory is alive, which is
+//! usually the lifetime of the `WasiCtx`. They identify paths rather than
+//! files: hard links to a file get different numbers, and renaming an entry
+//! gives it a new one. On Unix the host's `st_ino` is always used.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Whether the host's inode numbers are reported rather than synthesized ones.
+pub(crate) const HOST_INODES: bool = cfg!(unix);
+
+/// The next inode number to hand out. This is shared by all roots so that
+/// entries beneath different preopens on the same device never collide.
+static NEXT_INODE: AtomicU64 = AtomicU64::new(1);
+
+/// The inode numbers given to paths beneath a root directory.
+#[derive(Clone, Default)]
+pub(crate) struct Inodes(Arc<Mutex<HashMap<PathBuf, u64>>>);
+
+impl Inodes {
+    /// Returns the inode number of `path`, which is relative to the root.
+    pub(crate) fn get(&self, path: &Path) -> u64 {
+        *self
+            .0
+            .lock()
+            .unwrap()
+            .entry(path.to_owned())
+            .or_insert_with(|| NEXT_INODE.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Lexically resolves `path` against `base`, which is relative to the root.
+///
+/// `..` at the root refers to the root itself.
+pub(crate) fn join(base: &Path, path: &str) -> PathBuf {
+    let mut joined = base.to_owned();
+    for component in Path::new(path).components() {
+        match component {
+            Component::ParentDir => {
+                joined.pop();
+            }
+            Component::Normal(name) => joined.push(name),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    joined
+}
@@ -1,5 +1,3 @@
-use crate::file::convert_systimespec;
-use fs_set_times::SetTimes;
 use io_lifetimes::AsFilelike;
 use std::any::Any;
 use std::convert::TryInto;
@@ -17,6 +15,24 @@ use wasi_common::{
     Error, ErrorExt,
 };
 
+/// The stdio streams are presented to the guest as character devices no
+/// matter what the host has them connected to, so that they behave the same
+/// on every platform. They have no meaningful size or timestamps, and setting
+/// their timestamps succeeds without doing anything, as `futimens` on a
+/// terminal does on POSIX systems.
+fn stdio_filestat() -> Filestat {
+    Filestat {
+        device_id: 0,
+        inode: 0,
+        filetype: FileType::CharacterDevice,
+        nlink: 0,
+        size: 0,
+        atim: None,
+        mtim: None,
+        ctim: None,
+    }
+}
+
 pub struct Stdin(std::io::Stdin);
 
 pub fn stdin() -> Stdin {
@@ -35,7 +51,7 @@ impl WasiFile for Stdin {
         Ok(())
     }
     async fn get_filetype(&self) -> Result<FileType, Error> {
-        Ok(FileType::Unknown)
+        Ok(FileType::CharacterDevice)
     }
     async fn get_fdflags(&self) -> Result<FdFlags, Error> {
         Ok(FdFlags::empty())
@@ -44,17 +60,7 @@ impl WasiFile for Stdin {
         Err(Error::badf())
     }
     async fn get_filestat(&self) -> Result<Filestat, Error> {
-        let meta = self.0.as_filelike_view::<File>().metadata()?;
-        Ok(Filestat {
-            device_id: 0,
-            inode: 0,
-            filetype: self.get_filetype().await?,
-            nlink: 0,
-            size: meta.len(),
-            atim: meta.accessed().ok(),
-            mtim: meta.modified().ok(),
-            ctim: meta.created().ok(),
-        })
+        Ok(stdio_filestat())
     }
     async fn set_filestat_size(&self, _size: u64) -> Result<(), Error> {
         Err(Error::badf())
@@ -94,11 +100,9 @@ impl WasiFile for Stdin {
     }
     async fn set_times(
         &self,
-        atime: Option<wasi_common::SystemTimeSpec>,
-        mtime: Option<wasi_common::SystemTimeSpec>,
+        _atime: Option<wasi_common::SystemTimeSpec>,
+        _mtime: Option<wasi_common::SystemTimeSpec>,
     ) -> Result<(), Error> {
-        self.0
-            .set_times(convert_systimespec(atime), convert_systimespec(mtime))?;
         Ok(())
     }
     async fn num_ready_bytes(&self) -> Result<u64, Error> {
@@ -138,7 +142,7 @@ macro_rules! wasi_file_write_impl {
                 Ok(())
             }
             async fn get_filetype(&self) -> Result<FileType, Error> {
-                Ok(FileType::Unknown)
+                Ok(FileType::CharacterDevice)
             }
             async fn get_fdflags(&self) -> Result<FdFlags, Error> {
                 Ok(FdFlags::APPEND)
@@ -147,17 +151,7 @@ macro_rules! wasi_file_write_impl {
                 Err(Error::badf())
             }
             async fn get_filestat(&self) -> Result<Filestat, Error> {
-                let meta = self.0.as_filelike_view::<File>().metadata()?;
-                Ok(Filestat {
-                    device_id: 0,
-                    inode: 0,
-                    filetype: self.get_filetype().await?,
-                    nlink: 0,
-                    size: meta.len(),
-                    atim: meta.accessed().ok(),
-                    mtim: meta.modified().ok(),
-                    ctim: meta.created().ok(),
-                })
+                Ok(stdio_filestat())
             }
             async fn set_filestat_size(&self, _size: u64) -> Result<(), Error> {
                 Err(Error::badf())
@@ -200,11 +194,9 @@ macro_rules! wasi_file_write_impl {
             }
             async fn set_times(
                 &self,
-                atime: Option<wasi_common::SystemTimeSpec>,
-                mtime: Option<wasi_common::SystemTimeSpec>,
+                _atime: Option<wasi_common::SystemTimeSpec>,
+                _mtime: Option<wasi_common::SystemTimeSpec>,
             ) -> Result<(), Error> {
-                self.0
-                    .set_times(convert_systimespec(atime), convert_systimespec(mtime))?;
                 Ok(())
             }
             async fn num_ready_bytes(&self) -> Result<u64, Error> {
@@ -3,6 +3,7 @@ use crate::{Error, ErrorExt, SystemTimeSpec};
 use bitflags::bitflags;
 use std::any::Any;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[wiggle::async_trait]
 pub trait WasiDir: Send + Sync {
@@ -28,6 +29,12 @@ pub trait WasiDir: Send + Sync {
     async fn unlink_file(&self, path: &str) -> Result<(), Error>;
     async fn read_link(&self, path: &str) -> Result<PathBuf, Error>;
     async fn get_filestat(&self) -> Result<Filestat, Error>;
+    /// Makes the directory's entries durable, for `fd_sync` and `fd_datasync`
+    /// on a directory. By default this does nothing and succeeds, for
+    /// implementations where it isn't meaningful.
+    async fn sync(&self) -> Result<(), Error> {
+        Ok(())
+    }
     async fn get_path_filestat(&self, path: &str, follow_symlinks: bool)
         -> Result<Filestat, Error>;
     async fn rename(
@@ -55,6 +62,9 @@ pub(crate) struct DirEntry {
     caps: DirCaps,
     file_caps: FileCaps,
     preopen_path: Option<PathBuf>, // precondition: PathBuf is valid unicode
+    // Shared by a directory and every directory opened beneath it, identifying
+    // the capability root it descends from.
+    root: Arc<()>,
     dir: Box<dyn WasiDir>,
 }
 
@@ -69,9 +79,25 @@ impl DirEntry {
             caps,
             file_caps,
             preopen_path,
+            root: Arc::new(()),
             dir,
         }
     }
+    /// A directory opened beneath this one, which descends from the same root.
+    pub fn child(&self, caps: DirCaps, file_caps: FileCaps, dir: Box<dyn WasiDir>) -> Self {
+        DirEntry {
+            caps,
+            file_caps,
+            preopen_path: None,
+            root: self.root.clone(),
+            dir,
+        }
+    }
+    /// Whether this directory and `other` descend from the same root, so that
+    /// entries may be renamed or linked between them.
+    pub fn same_root(&self, other: &DirEntry) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
     pub fn capable_of_dir(&self, caps: DirCaps) -> Result<(), Error> {
         if self.caps.contains(caps) {
             Ok(())
@@ -140,6 +166,8 @@ bitflags! {
         const PATH_FILESTAT_SET_TIMES = 0b10000000000000;
         const FILESTAT_GET            = 0b100000000000000;
         const FILESTAT_SET_TIMES      = 0b1000000000000000;
+        const DATASYNC                = 0b10000000000000000;
+        const SYNC                    = 0b100000000000000000;
     }
 }
 
@@ -69,6 +69,9 @@ pub enum ErrorKind {
     /// Errno::Spipe: Invalid seek
     #[error("Spipe: Invalid seek")]
     Spipe,
+    /// Errno::Xdev: Cross-device link
+    #[error("Xdev: Cross-device link")]
+    Xdev,
     /// Errno::NotCapable: Not capable
     #[error("Not capable")]
     NotCapable,
@@ -89,6 +92,7 @@ pub trait ErrorExt {
     fn overflow() -> Self;
     fn range() -> Self;
     fn seek_pipe() -> Self;
+    fn cross_device() -> Self;
     fn not_capable() -> Self;
 }
 
@@ -135,6 +139,9 @@ impl ErrorExt for Error {
     fn seek_pipe() -> Self {
         ErrorKind::Spipe.into()
     }
+    fn cross_device() -> Self {
+        ErrorKind::Xdev.into()
+    }
     fn not_capable() -> Self {
         ErrorKind::NotCapable.into()
     }
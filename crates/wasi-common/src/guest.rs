@@ -0,0 +1,90 @@
+//! Validation of the guest buffers handed to hostcalls.
+//!
+//! Every hostcall that takes a buffer, an array or a string from the guest
+//! goes through the helpers here to access guest memory. Buffers are validated
+//! as `GuestPtr`s, which are guest-relative offsets, and all of a hostcall's
+//! buffers are validated before any of them is accessed. They are then
+//! borrowed, at which point the offsets are checked again against the
+//! memory's base and size as they are *at that time*. The guest can't run,
+//! and so can't grow its memory, until the hostcall returns, so the borrowed
+//! slices stay valid across any I/O the hostcall awaits.
+//!
+//! All out-of-bounds and overflowing guest pointers are reported as
+//! `GuestError::PtrOutOfBounds`/`GuestError::PtrOverflow`, which both map to
+//! `EFAULT`.
+
+use crate::Error;
+use wiggle::{GuestError, GuestPtr, GuestSlice, GuestSliceMut, GuestType};
+
+/// Checks that the whole of `array` lies within guest memory and is suitably
+/// aligned for `T`.
+///
+/// This doesn't read or borrow anything, it only ensures that a subsequent
+/// access to any part of `array` can't fault, given that memory has not shrunk
+/// in the meantime (wasm memories never do).
+pub(crate) fn validate_array<'a, T>(array: &GuestPtr<'a, [T]>) -> Result<(), GuestError>
+where
+    T: GuestType<'a>,
+{
+    let len = array
+        .len()
+        .checked_mul(T::guest_size())
+        .ok_or(GuestError::PtrOverflow)?;
+    array
+        .mem()
+        .validate_size_align(array.offset_base(), T::guest_align(), len)?;
+    Ok(())
+}
+
+/// Reads an array of iovecs out of guest memory, returning the guest buffer
+/// each of them describes.
+///
+/// The iovec array is validated as a whole before any of it is read, and then
+/// every buffer is validated before any of them is returned, so a hostcall
+/// either gets a complete set of in-bounds buffers or fails before performing
+/// any I/O. `buf` extracts the buffer from a snapshot's iovec type.
+pub(crate) fn iovec_bufs<'a, I>(
+    iovs: &GuestPtr<'a, [I]>,
+    buf: impl Fn(I) -> GuestPtr<'a, [u8]>,
+) -> Result<Vec<GuestPtr<'a, [u8]>>, Error>
+where
+    I: GuestType<'a>,
+{
+    validate_array(iovs)?;
+    iovs.iter()
+        .map(|iov| {
+            let iov_buf = buf(iov?.read()?);
+            validate_array(&iov_buf)?;
+            Ok(iov_buf)
+        })
+        .collect::<Result<_, Error>>()
+}
+
+/// Borrows the contents of each of `bufs` in guest memory, for I/O to read
+/// from.
+pub(crate) fn slices<'a>(bufs: &[GuestPtr<'a, [u8]>]) -> Result<Vec<GuestSlice<'a, u8>>, Error> {
+    bufs.iter().map(|buf| Ok(buf.as_slice()?)).collect()
+}
+
+/// Mutably borrows the contents of each of `bufs` in guest memory, for I/O to
+/// write into.
+pub(crate) fn slices_mut<'a>(
+    bufs: &[GuestPtr<'a, [u8]>],
+) -> Result<Vec<GuestSliceMut<'a, u8>>, Error> {
+    bufs.iter().map(|buf| Ok(buf.as_slice_mut()?)).collect()
+}
+
+/// Copies a string out of guest memory.
+pub(crate) fn read_str(s: &GuestPtr<'_, str>) -> Result<String, Error> {
+    Ok(s.as_str()?.to_string())
+}
+
+/// Calls `f` with the contents of `buf` in guest memory, which it can only
+/// access synchronously.
+pub(crate) fn with_slice_mut<R>(
+    buf: &GuestPtr<'_, [u8]>,
+    f: impl FnOnce(&mut [u8]) -> Result<R, Error>,
+) -> Result<R, Error> {
+    let mut slice = buf.as_slice_mut()?;
+    f(&mut slice)
+}
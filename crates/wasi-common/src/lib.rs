@@ -15,10 +15,11 @@
 //! of types defined directly in the crate's source code (I decided it should
 //! NOT those generated by the `wiggle` proc macros, see snapshot architecture
 //! below), as well as the `cap_std::time` family of types.  And, importantly,
-//! `wasi-common` itself provides no implementation of `WasiDir`, and only two
+//! `wasi-common` itself provides no implementation of `WasiDir`, and only
 //! trivial implementations of `WasiFile` on the `crate::pipe::{ReadPipe,
 //! WritePipe}` types, which in turn just delegate to `std::io::{Read,
-//! Write}`. In order for `wasi-common` to access the local filesystem at all,
+//! Write}`, and on `crate::pipe::OutputPipe`, which captures stdout and
+//! stderr in memory. In order for `wasi-common` to access the local filesystem at all,
 //! you need to provide `WasiFile` and `WasiDir` impls through either the new
 //! `wasi-cap-std-sync` crate found at `crates/wasi-common/cap-std-sync` - see
 //! the section on that crate below - or by providing your own implementation
@@ -55,6 +56,7 @@ mod ctx;
 pub mod dir;
 mod error;
 pub mod file;
+mod guest;
 pub mod pipe;
 pub mod random;
 pub mod sched;
@@ -16,7 +16,7 @@ use crate::{
 use std::any::Any;
 use std::convert::TryInto;
 use std::io::{self, Read, Write};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 /// A virtual pipe read end.
 ///
@@ -175,7 +175,9 @@ impl<R: Read + Any + Send + Sync> WasiFile for ReadPipe<R> {
         atime: Option<SystemTimeSpec>,
         mtime: Option<SystemTimeSpec>,
     ) -> Result<(), Error> {
-        Err(Error::badf())
+        // Pipes have no timestamps to set, but stand in for stdio, whose
+        // timestamps can be "set" without error.
+        Ok(())
     }
     async fn num_ready_bytes(&self) -> Result<u64, Error> {
         Ok(0)
@@ -264,9 +266,11 @@ impl<W: Write + Any + Send + Sync> WasiFile for WritePipe<W> {
         self
     }
     async fn datasync(&self) -> Result<(), Error> {
+        self.borrow().flush()?;
         Ok(())
     }
     async fn sync(&self) -> Result<(), Error> {
+        self.borrow().flush()?;
         Ok(())
     }
     async fn get_filetype(&self) -> Result<FileType, Error> {
@@ -331,8 +335,338 @@ impl<W: Write + Any + Send + Sync> WasiFile for WritePipe<W> {
         atime: Option<SystemTimeSpec>,
         mtime: Option<SystemTimeSpec>,
     ) -> Result<(), Error> {
+        // Pipes have no timestamps to set, but stand in for stdio, whose
+        // timestamps can be "set" without error.
+        Ok(())
+    }
+    async fn num_ready_bytes(&self) -> Result<u64, Error> {
+        Ok(0)
+    }
+    async fn readable(&self) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn writable(&self) -> Result<(), Error> {
         Err(Error::badf())
     }
+}
+
+/// One of the standard output streams, as seen by the callbacks of an
+/// [`OutputCapture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    fn index(self) -> usize {
+        match self {
+            OutputStream::Stdout => 0,
+            OutputStream::Stderr => 1,
+        }
+    }
+}
+
+type OutputCallback = Box<dyn FnMut(OutputStream, &[u8]) + Send>;
+
+/// Captures what a guest writes to its stdout and stderr.
+///
+/// The [`OutputCapture::stdout`] and [`OutputCapture::stderr`] pipes are given
+/// to a `WasiCtx`, and what the guest writes to them is kept in an in-memory
+/// buffer for each stream and handed to any callbacks registered with
+/// [`OutputCapture::on_write`] and [`OutputCapture::on_line`].
+///
+/// Both pipes share one lock, so the callbacks see the writes to stdout and
+/// stderr in exactly the order the guest made them, and the callbacks are
+/// never run concurrently. In turn, the callbacks must not write to the pipes
+/// of the same capture themselves, since that would deadlock.
+///
+/// The pipes present themselves to the guest as character devices, like the
+/// inherited stdio streams do, so they can't be read from or seeked.
+///
+/// ```no_run
+/// use wasi_common::pipe::{OutputCapture, OutputStream};
+/// use wasi_common::{WasiCtx, Table};
+/// let output = OutputCapture::new().on_line(|stream, line| match stream {
+///     OutputStream::Stdout => print!("guest: {}", String::from_utf8_lossy(line)),
+///     OutputStream::Stderr => eprint!("guest: {}", String::from_utf8_lossy(line)),
+/// });
+/// // Bring these instances from elsewhere (e.g. wasi-cap-std-sync):
+/// let random = todo!();
+/// let clocks = todo!();
+/// let sched = todo!();
+/// let table = Table::new();
+/// let mut ctx = WasiCtx::new(random, clocks, sched, table);
+/// ctx.set_stdout(Box::new(output.stdout()));
+/// ctx.set_stderr(Box::new(output.stderr()));
+/// // use ctx in an instance, and then:
+/// output.flush_lines();
+/// println!("contents of stdout: {:?}", output.contents(OutputStream::Stdout));
+/// ```
+#[derive(Clone)]
+pub struct OutputCapture {
+    state: Arc<Mutex<CaptureState>>,
+}
+
+struct CaptureState {
+    buffering: bool,
+    contents: [Vec<u8>; 2],
+    /// The last line written to each stream, if it isn't terminated yet.
+    partial_lines: [Vec<u8>; 2],
+    on_write: Option<OutputCallback>,
+    on_line: Option<OutputCallback>,
+}
+
+impl OutputCapture {
+    /// Creates a capture which buffers both streams in memory, and has no
+    /// callbacks.
+    pub fn new() -> Self {
+        OutputCapture {
+            state: Arc::new(Mutex::new(CaptureState {
+                buffering: true,
+                contents: Default::default(),
+                partial_lines: Default::default(),
+                on_write: None,
+                on_line: None,
+            })),
+        }
+    }
+
+    /// Calls `f` with the data of every write the guest makes, as it's made.
+    ///
+    /// The data of one write is never split across calls, no matter how large
+    /// it is or how many buffers the guest wrote it from.
+    pub fn on_write(self, f: impl FnMut(OutputStream, &[u8]) + Send + 'static) -> Self {
+        self.lock().on_write = Some(Box::new(f));
+        self
+    }
+
+    /// Calls `f` with each line the guest writes, including its terminating
+    /// `\n`, as soon as the line is complete.
+    ///
+    /// Lines are collected separately for each stream, so a line which the
+    /// guest writes in pieces is delivered whole even if it wrote to the other
+    /// stream in between. An unterminated last line is delivered by
+    /// [`OutputCapture::flush_lines`], or when the capture and all of its
+    /// pipes are dropped.
+    pub fn on_line(self, f: impl FnMut(OutputStream, &[u8]) + Send + 'static) -> Self {
+        self.lock().on_line = Some(Box::new(f));
+        self
+    }
+
+    /// Stops keeping the output in memory, for when the callbacks are all
+    /// that's needed.
+    pub fn without_buffering(self) -> Self {
+        self.lock().buffering = false;
+        self
+    }
+
+    /// Returns the pipe to give to a `WasiCtx` as its stdout.
+    pub fn stdout(&self) -> OutputPipe {
+        OutputPipe {
+            stream: OutputStream::Stdout,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Returns the pipe to give to a `WasiCtx` as its stderr.
+    pub fn stderr(&self) -> OutputPipe {
+        OutputPipe {
+            stream: OutputStream::Stderr,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Returns everything written to `stream` so far.
+    ///
+    /// This is always empty if the capture was created
+    /// [`without_buffering`](OutputCapture::without_buffering).
+    pub fn contents(&self, stream: OutputStream) -> Vec<u8> {
+        self.lock().contents[stream.index()].clone()
+    }
+
+    /// Delivers the unterminated last line of each stream, if any, to the
+    /// [`on_line`](OutputCapture::on_line) callback, stdout first.
+    pub fn flush_lines(&self) {
+        self.lock().flush_lines();
+    }
+
+    fn lock(&self) -> MutexGuard<CaptureState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Default for OutputCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for OutputCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let state = self.lock();
+        f.debug_struct("OutputCapture")
+            .field("stdout_len", &state.contents[0].len())
+            .field("stderr_len", &state.contents[1].len())
+            .finish()
+    }
+}
+
+impl CaptureState {
+    fn write(&mut self, stream: OutputStream, data: &[u8]) {
+        if self.buffering {
+            self.contents[stream.index()].extend_from_slice(data);
+        }
+        if let Some(on_write) = &mut self.on_write {
+            on_write(stream, data);
+        }
+        let on_line = match &mut self.on_line {
+            Some(on_line) => on_line,
+            None => return,
+        };
+        let partial = &mut self.partial_lines[stream.index()];
+        let mut rest = data;
+        while let Some(end) = rest.iter().position(|b| *b == b'\n') {
+            let (line, tail) = rest.split_at(end + 1);
+            if partial.is_empty() {
+                on_line(stream, line);
+            } else {
+                partial.extend_from_slice(line);
+                on_line(stream, partial);
+                partial.clear();
+            }
+            rest = tail;
+        }
+        partial.extend_from_slice(rest);
+    }
+
+    fn flush_lines(&mut self) {
+        if let Some(on_line) = &mut self.on_line {
+            for stream in [OutputStream::Stdout, OutputStream::Stderr].iter() {
+                let partial = &mut self.partial_lines[stream.index()];
+                if !partial.is_empty() {
+                    on_line(*stream, partial);
+                    partial.clear();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for CaptureState {
+    fn drop(&mut self) {
+        self.flush_lines();
+    }
+}
+
+/// The write end of one of the streams of an [`OutputCapture`].
+#[derive(Clone)]
+pub struct OutputPipe {
+    stream: OutputStream,
+    state: Arc<Mutex<CaptureState>>,
+}
+
+impl OutputPipe {
+    /// Returns which stream this pipe writes to.
+    pub fn stream(&self) -> OutputStream {
+        self.stream
+    }
+}
+
+impl std::fmt::Debug for OutputPipe {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("OutputPipe")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+#[wiggle::async_trait]
+impl WasiFile for OutputPipe {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn datasync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn sync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::CharacterDevice)
+    }
+    async fn get_fdflags(&self) -> Result<FdFlags, Error> {
+        Ok(FdFlags::APPEND)
+    }
+    async fn set_fdflags(&mut self, _fdflags: FdFlags) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        Ok(Filestat {
+            device_id: 0,
+            inode: 0,
+            filetype: FileType::CharacterDevice,
+            nlink: 0,
+            size: 0,
+            atim: None,
+            mtim: None,
+            ctim: None,
+        })
+    }
+    async fn set_filestat_size(&self, _size: u64) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn allocate(&self, offset: u64, len: u64) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn read_vectored<'a>(&self, bufs: &mut [io::IoSliceMut<'a>]) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn read_vectored_at<'a>(
+        &self,
+        bufs: &mut [io::IoSliceMut<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn write_vectored<'a>(&self, bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+        // Join the buffers so that the callbacks see each write whole.
+        let data = match bufs {
+            [buf] => std::borrow::Cow::Borrowed(&buf[..]),
+            bufs => {
+                let mut data = Vec::with_capacity(bufs.iter().map(|b| b.len()).sum());
+                for buf in bufs {
+                    data.extend_from_slice(buf);
+                }
+                std::borrow::Cow::Owned(data)
+            }
+        };
+        self.state.lock().unwrap().write(self.stream, &data);
+        Ok(data.len().try_into()?)
+    }
+    async fn write_vectored_at<'a>(
+        &self,
+        bufs: &[io::IoSlice<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        Err(Error::seek_pipe())
+    }
+    async fn seek(&self, pos: std::io::SeekFrom) -> Result<u64, Error> {
+        Err(Error::seek_pipe())
+    }
+    async fn peek(&self, buf: &mut [u8]) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn set_times(
+        &self,
+        atime: Option<SystemTimeSpec>,
+        mtime: Option<SystemTimeSpec>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
     async fn num_ready_bytes(&self) -> Result<u64, Error> {
         Ok(0)
     }
@@ -1,4 +1,5 @@
 use crate::file::{FileCaps, FileEntryExt, TableFileExt};
+use crate::guest;
 use crate::sched::{
     subscription::{RwEventFlags, SubscriptionResult},
     Poll, Userdata,
@@ -465,15 +466,8 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::READ)?;
 
-        let mut guest_slices: Vec<wiggle::GuestSliceMut<u8>> = iovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Iovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice_mut()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
+        let bufs = guest::iovec_bufs(iovs, |iov: types::Iovec| iov.buf.as_array(iov.buf_len))?;
+        let mut guest_slices = guest::slices_mut(&bufs)?;
         let mut ioslices: Vec<IoSliceMut> = guest_slices
             .iter_mut()
             .map(|s| IoSliceMut::new(&mut *s))
@@ -494,15 +488,8 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::READ | FileCaps::SEEK)?;
 
-        let mut guest_slices: Vec<wiggle::GuestSliceMut<u8>> = iovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Iovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice_mut()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
+        let bufs = guest::iovec_bufs(iovs, |iov: types::Iovec| iov.buf.as_array(iov.buf_len))?;
+        let mut guest_slices = guest::slices_mut(&bufs)?;
         let mut ioslices: Vec<IoSliceMut> = guest_slices
             .iter_mut()
             .map(|s| IoSliceMut::new(&mut *s))
@@ -520,19 +507,9 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::WRITE)?;
 
-        let guest_slices: Vec<wiggle::GuestSlice<u8>> = ciovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Ciovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
-        let ioslices: Vec<IoSlice> = guest_slices
-            .iter()
-            .map(|s| IoSlice::new(s.deref()))
-            .collect();
+        let bufs = guest::iovec_bufs(ciovs, |iov: types::Ciovec| iov.buf.as_array(iov.buf_len))?;
+        let guest_slices = guest::slices(&bufs)?;
+        let ioslices: Vec<IoSlice> = guest_slices.iter().map(|s| IoSlice::new(&*s)).collect();
         let bytes_written = f.write_vectored(&ioslices).await?;
 
         Ok(types::Size::try_from(bytes_written)?)
@@ -549,19 +526,9 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::WRITE | FileCaps::SEEK)?;
 
-        let guest_slices: Vec<wiggle::GuestSlice<u8>> = ciovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Ciovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
-        let ioslices: Vec<IoSlice> = guest_slices
-            .iter()
-            .map(|s| IoSlice::new(s.deref()))
-            .collect();
+        let bufs = guest::iovec_bufs(ciovs, |iov: types::Ciovec| iov.buf.as_array(iov.buf_len))?;
+        let guest_slices = guest::slices(&bufs)?;
+        let ioslices: Vec<IoSlice> = guest_slices.iter().map(|s| IoSlice::new(&*s)).collect();
         let bytes_written = f.write_vectored_at(&ioslices, offset).await?;
 
         Ok(types::Size::try_from(bytes_written)?)
@@ -757,6 +724,10 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         if nsubscriptions == 0 {
             return Err(Error::invalid_argument().context("nsubscriptions must be nonzero"));
         }
+        // Check both arrays before waiting on anything, so that a bad pointer
+        // faults right away rather than after the wait.
+        guest::validate_array(&subs.as_array(nsubscriptions))?;
+        guest::validate_array(&events.as_array(nsubscriptions))?;
 
         // Special-case a `poll_oneoff` which is just sleeping on a single
         // relative timer event, such as what WASI libc uses to implement sleep
@@ -4,6 +4,7 @@ use crate::{
         Advice, FdFlags, FdStat, FileCaps, FileEntry, FileEntryExt, FileType, Filestat, OFlags,
         TableFileExt, WasiFile,
     },
+    guest,
     sched::{
         subscription::{RwEventFlags, SubscriptionResult},
         Poll, Userdata,
@@ -15,7 +16,7 @@ use cap_std::time::{Duration, SystemClock};
 use std::collections::HashSet;
 use std::convert::{TryFrom, TryInto};
 use std::io::{IoSlice, IoSliceMut};
-use std::ops::{Deref, DerefMut};
+use std::ops::Deref;
 use tracing::debug;
 use wiggle::GuestPtr;
 
@@ -83,6 +84,7 @@ impl From<ErrorKind> for types::Errno {
             ErrorKind::Overflow => Errno::Overflow,
             ErrorKind::Range => Errno::Range,
             ErrorKind::Spipe => Errno::Spipe,
+            ErrorKind::Xdev => Errno::Xdev,
             ErrorKind::NotCapable => Errno::Notcapable,
         }
     }
@@ -140,6 +142,7 @@ impl TryFrom<std::io::Error> for types::Errno {
                 Some(Error::OVERFLOW) => Some(types::Errno::Overflow),
                 Some(Error::ILSEQ) => Some(types::Errno::Ilseq),
                 Some(Error::NOTSUP) => Some(types::Errno::Notsup),
+                Some(Error::XDEV) => Some(types::Errno::Xdev),
                 _ => None,
             }
         }
@@ -171,6 +174,7 @@ impl TryFrom<std::io::Error> for types::Errno {
                 Some(winerror::ERROR_ALREADY_EXISTS) => Some(types::Errno::Exist),
                 Some(winerror::ERROR_STOPPED_ON_SYMLINK) => Some(types::Errno::Loop),
                 Some(winerror::ERROR_DIRECTORY_NOT_SUPPORTED) => Some(types::Errno::Isdir),
+                Some(winerror::ERROR_NOT_SAME_DEVICE) => Some(types::Errno::Xdev),
                 _ => None,
             }
         }
@@ -182,7 +186,13 @@ impl TryFrom<std::io::Error> for types::Errno {
                 std::io::ErrorKind::PermissionDenied => Ok(types::Errno::Perm),
                 std::io::ErrorKind::AlreadyExists => Ok(types::Errno::Exist),
                 std::io::ErrorKind::InvalidInput => Ok(types::Errno::Ilseq),
-                _ => Err(anyhow::anyhow!(err).context(format!("Unknown OS error"))),
+                // Only the kind and code of the error are kept: its message may
+                // name host paths, which mustn't reach the guest or embedder.
+                _ => Err(anyhow::anyhow!(
+                    "Unknown OS error: {:?} (os error {:?})",
+                    err.kind(),
+                    err.raw_os_error()
+                )),
             },
         }
     }
@@ -306,12 +316,21 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_datasync(&mut self, fd: types::Fd) -> Result<(), Error> {
-        self.table()
-            .get_file(u32::from(fd))?
-            .get_cap(FileCaps::DATASYNC)?
-            .datasync()
-            .await?;
-        Ok(())
+        let table = self.table();
+        let fd = u32::from(fd);
+        if table.is::<FileEntry>(fd) {
+            table
+                .get_file(fd)?
+                .get_cap(FileCaps::DATASYNC)?
+                .datasync()
+                .await
+        } else if table.is::<DirEntry>(fd) {
+            // A directory has no data apart from its entries, so this is the
+            // same as `fd_sync`.
+            table.get_dir(fd)?.get_cap(DirCaps::DATASYNC)?.sync().await
+        } else {
+            Err(Error::badf())
+        }
     }
 
     async fn fd_fdstat_get(&mut self, fd: types::Fd) -> Result<types::Fdstat, Error> {
@@ -444,15 +463,8 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::READ)?;
 
-        let mut guest_slices: Vec<wiggle::GuestSliceMut<u8>> = iovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Iovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice_mut()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
+        let bufs = guest::iovec_bufs(iovs, |iov: types::Iovec| iov.buf.as_array(iov.buf_len))?;
+        let mut guest_slices = guest::slices_mut(&bufs)?;
         let mut ioslices: Vec<IoSliceMut> = guest_slices
             .iter_mut()
             .map(|s| IoSliceMut::new(&mut *s))
@@ -473,15 +485,8 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::READ | FileCaps::SEEK)?;
 
-        let mut guest_slices: Vec<wiggle::GuestSliceMut<u8>> = iovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Iovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice_mut()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
+        let bufs = guest::iovec_bufs(iovs, |iov: types::Iovec| iov.buf.as_array(iov.buf_len))?;
+        let mut guest_slices = guest::slices_mut(&bufs)?;
         let mut ioslices: Vec<IoSliceMut> = guest_slices
             .iter_mut()
             .map(|s| IoSliceMut::new(&mut *s))
@@ -499,19 +504,9 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::WRITE)?;
 
-        let guest_slices: Vec<wiggle::GuestSlice<u8>> = ciovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Ciovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
-        let ioslices: Vec<IoSlice> = guest_slices
-            .iter()
-            .map(|s| IoSlice::new(s.deref()))
-            .collect();
+        let bufs = guest::iovec_bufs(ciovs, |iov: types::Ciovec| iov.buf.as_array(iov.buf_len))?;
+        let guest_slices = guest::slices(&bufs)?;
+        let ioslices: Vec<IoSlice> = guest_slices.iter().map(|s| IoSlice::new(&*s)).collect();
         let bytes_written = f.write_vectored(&ioslices).await?;
 
         Ok(types::Size::try_from(bytes_written)?)
@@ -528,19 +523,9 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::WRITE | FileCaps::SEEK)?;
 
-        let guest_slices: Vec<wiggle::GuestSlice<u8>> = ciovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Ciovec = iov_ptr.read()?;
-                Ok(iov.buf.as_array(iov.buf_len).as_slice()?)
-            })
-            .collect::<Result<_, Error>>()?;
-
-        let ioslices: Vec<IoSlice> = guest_slices
-            .iter()
-            .map(|s| IoSlice::new(s.deref()))
-            .collect();
+        let bufs = guest::iovec_bufs(ciovs, |iov: types::Ciovec| iov.buf.as_array(iov.buf_len))?;
+        let guest_slices = guest::slices(&bufs)?;
+        let ioslices: Vec<IoSlice> = guest_slices.iter().map(|s| IoSlice::new(&*s)).collect();
         let bytes_written = f.write_vectored_at(&ioslices, offset).await?;
 
         Ok(types::Size::try_from(bytes_written)?)
@@ -575,9 +560,10 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             if path_len < path_max_len as usize {
                 return Err(Error::name_too_long());
             }
-            let mut p_memory = path.as_array(path_len as u32).as_slice_mut()?;
-            p_memory.copy_from_slice(path_bytes);
-            Ok(())
+            guest::with_slice_mut(&path.as_array(path_len as u32), |p_memory| {
+                p_memory.copy_from_slice(path_bytes);
+                Ok(())
+            })
         } else {
             Err(Error::not_supported())
         }
@@ -589,12 +575,26 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         if !table.contains_key(from) {
             return Err(Error::badf());
         }
-        if table.is_preopen(from) || table.is_preopen(to) {
-            return Err(Error::not_supported().context("cannot renumber a preopen"));
+        if from == to {
+            return Ok(());
+        }
+        // Like `fd_close`, we cannot replace a preopened directory. Renumbering one is fine: its
+        // preopen path travels with the `DirEntry`, so `fd_prestat_get` works on the new number.
+        if table.is_preopen(to) {
+            return Err(Error::not_supported().context("cannot renumber onto a preopen"));
+        }
+        // The entry at `to` is closed as part of the renumber. Give it a chance to flush any
+        // pending output first, the same way a close would; errors are ignored, just as they
+        // are when the entry is dropped.
+        if table.is::<FileEntry>(to) {
+            let file = table.get_file(to)?.get_cap(FileCaps::empty())?;
+            let _ = file.datasync().await;
         }
         let from_entry = table
             .delete(from)
             .expect("we checked that table contains from");
+        // Replacing the entry drops the old `to`, so no other hostcall can observe both numbers
+        // referring to the same resource, or `to` referring to nothing.
         table.insert_at(to, from_entry);
         Ok(())
     }
@@ -605,46 +605,35 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         offset: types::Filedelta,
         whence: types::Whence,
     ) -> Result<types::Filesize, Error> {
-        use std::io::SeekFrom;
-
         let required_caps = if offset == 0 && whence == types::Whence::Cur {
             FileCaps::TELL
         } else {
             FileCaps::TELL | FileCaps::SEEK
         };
 
-        let whence = match whence {
-            types::Whence::Cur => SeekFrom::Current(offset),
-            types::Whence::End => SeekFrom::End(offset),
-            types::Whence::Set => SeekFrom::Start(offset as u64),
-        };
-        let newoffset = self
-            .table()
-            .get_file(u32::from(fd))?
-            .get_cap(required_caps)?
-            .seek(whence)
-            .await?;
-        Ok(newoffset)
+        // Directories aren't files in the table, so seeking one is `EBADF` on
+        // every platform before the host is consulted.
+        let table = self.table();
+        let file = table.get_file(u32::from(fd))?.get_cap(required_caps)?;
+        seek(file, offset, whence).await
     }
 
     async fn fd_sync(&mut self, fd: types::Fd) -> Result<(), Error> {
-        self.table()
-            .get_file(u32::from(fd))?
-            .get_cap(FileCaps::SYNC)?
-            .sync()
-            .await?;
-        Ok(())
+        let table = self.table();
+        let fd = u32::from(fd);
+        if table.is::<FileEntry>(fd) {
+            table.get_file(fd)?.get_cap(FileCaps::SYNC)?.sync().await
+        } else if table.is::<DirEntry>(fd) {
+            table.get_dir(fd)?.get_cap(DirCaps::SYNC)?.sync().await
+        } else {
+            Err(Error::badf())
+        }
     }
 
     async fn fd_tell(&mut self, fd: types::Fd) -> Result<types::Filesize, Error> {
-        // XXX should this be stream_position?
-        let offset = self
-            .table()
-            .get_file(u32::from(fd))?
-            .get_cap(FileCaps::TELL)?
-            .seek(std::io::SeekFrom::Current(0))
-            .await?;
-        Ok(offset)
+        let table = self.table();
+        let file = table.get_file(u32::from(fd))?.get_cap(FileCaps::TELL)?;
+        seek(file, 0, types::Whence::Cur).await
     }
 
     async fn fd_readdir<'a>(
@@ -654,6 +643,9 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         buf_len: types::Size,
         cookie: types::Dircookie,
     ) -> Result<types::Size, Error> {
+        // Check the entire buffer before reading the directory, so that a bad
+        // buffer faults without having written any dirents to it.
+        guest::validate_array(&buf.as_array(buf_len))?;
         let mut bufused = 0;
         let mut buf = buf.clone();
         for entity in self
@@ -709,7 +701,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::CREATE_DIRECTORY)?
-            .create_dir(path.as_str()?.deref())
+            .create_dir(&guest::read_str(path)?)
             .await
     }
 
@@ -724,7 +716,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::PATH_FILESTAT_GET)?
             .get_path_filestat(
-                path.as_str()?.deref(),
+                &guest::read_str(path)?,
                 flags.contains(types::Lookupflags::SYMLINK_FOLLOW),
             )
             .await?;
@@ -751,7 +743,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::PATH_FILESTAT_SET_TIMES)?
             .set_times(
-                path.as_str()?.deref(),
+                &guest::read_str(path)?,
                 atim,
                 mtim,
                 flags.contains(types::Lookupflags::SYMLINK_FOLLOW),
@@ -768,12 +760,14 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         target_path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
         let table = self.table();
-        let src_dir = table
-            .get_dir(u32::from(src_fd))?
-            .get_cap(DirCaps::LINK_SOURCE)?;
-        let target_dir = table
-            .get_dir(u32::from(target_fd))?
-            .get_cap(DirCaps::LINK_TARGET)?;
+        let src_entry = table.get_dir(u32::from(src_fd))?;
+        let target_entry = table.get_dir(u32::from(target_fd))?;
+        let src_dir = src_entry.get_cap(DirCaps::LINK_SOURCE)?;
+        let target_dir = target_entry.get_cap(DirCaps::LINK_TARGET)?;
+        if !src_entry.same_root(target_entry) {
+            return Err(Error::cross_device()
+                .context("cannot link between different preopened directories"));
+        }
         let symlink_follow = src_flags.contains(types::Lookupflags::SYMLINK_FOLLOW);
         if symlink_follow {
             return Err(Error::invalid_argument()
@@ -782,9 +776,9 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
 
         src_dir
             .hard_link(
-                src_path.as_str()?.deref(),
+                &guest::read_str(src_path)?,
                 target_dir.deref(),
-                target_path.as_str()?.deref(),
+                &guest::read_str(target_path)?,
             )
             .await
     }
@@ -810,7 +804,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
 
         let oflags = OFlags::from(&oflags);
         let fdflags = FdFlags::from(fdflags);
-        let path = path.as_str()?;
+        let path = guest::read_str(path)?;
         if oflags.contains(OFlags::DIRECTORY) {
             if oflags.contains(OFlags::CREATE)
                 || oflags.contains(OFlags::EXCLUSIVE)
@@ -823,9 +817,8 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             let dir = dir_entry.get_cap(DirCaps::OPEN)?;
             let child_dir = dir.open_dir(symlink_follow, path.deref()).await?;
             drop(dir);
-            let fd = table.push(Box::new(DirEntry::new(
-                dir_caps, file_caps, None, child_dir,
-            )))?;
+            let child_entry = dir_entry.child(dir_caps, file_caps, child_dir);
+            let fd = table.push(Box::new(child_entry))?;
             Ok(types::Fd::from(fd))
         } else {
             let mut required_caps = DirCaps::OPEN;
@@ -833,7 +826,11 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
                 required_caps = required_caps | DirCaps::CREATE_FILE;
             }
 
-            let file_caps = dir_entry.child_file_caps(FileCaps::from(&fs_rights_base));
+            let file_caps = opened_file_caps(
+                dir_entry.child_file_caps(FileCaps::from(&fs_rights_base)),
+                oflags,
+                fdflags,
+            )?;
             let dir = dir_entry.get_cap(required_caps)?;
             let read = file_caps.contains(FileCaps::READ);
             let write = file_caps.contains(FileCaps::WRITE)
@@ -859,7 +856,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             .table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::READLINK)?
-            .read_link(path.as_str()?.deref())
+            .read_link(&guest::read_str(path)?)
             .await?
             .into_os_string()
             .into_string()
@@ -869,8 +866,10 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         if link_len > buf_len as usize {
             return Err(Error::range());
         }
-        let mut buf = buf.as_array(link_len as u32).as_slice_mut()?;
-        buf.copy_from_slice(link_bytes);
+        guest::with_slice_mut(&buf.as_array(link_len as u32), |buf| {
+            buf.copy_from_slice(link_bytes);
+            Ok(())
+        })?;
         Ok(link_len as types::Size)
     }
 
@@ -882,7 +881,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::REMOVE_DIRECTORY)?
-            .remove_dir(path.as_str()?.deref())
+            .remove_dir(&guest::read_str(path)?)
             .await
     }
 
@@ -894,17 +893,19 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         dest_path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
         let table = self.table();
-        let src_dir = table
-            .get_dir(u32::from(src_fd))?
-            .get_cap(DirCaps::RENAME_SOURCE)?;
-        let dest_dir = table
-            .get_dir(u32::from(dest_fd))?
-            .get_cap(DirCaps::RENAME_TARGET)?;
+        let src_entry = table.get_dir(u32::from(src_fd))?;
+        let dest_entry = table.get_dir(u32::from(dest_fd))?;
+        let src_dir = src_entry.get_cap(DirCaps::RENAME_SOURCE)?;
+        let dest_dir = dest_entry.get_cap(DirCaps::RENAME_TARGET)?;
+        if !src_entry.same_root(dest_entry) {
+            return Err(Error::cross_device()
+                .context("cannot rename between different preopened directories"));
+        }
         src_dir
             .rename(
-                src_path.as_str()?.deref(),
+                &guest::read_str(src_path)?,
                 dest_dir.deref(),
-                dest_path.as_str()?.deref(),
+                &guest::read_str(dest_path)?,
             )
             .await
     }
@@ -918,7 +919,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::SYMLINK)?
-            .symlink(src_path.as_str()?.deref(), dest_path.as_str()?.deref())
+            .symlink(&guest::read_str(src_path)?, &guest::read_str(dest_path)?)
             .await
     }
 
@@ -930,7 +931,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::UNLINK_FILE)?
-            .unlink_file(path.as_str()?.deref())
+            .unlink_file(&guest::read_str(path)?)
             .await
     }
 
@@ -943,6 +944,10 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         if nsubscriptions == 0 {
             return Err(Error::invalid_argument().context("nsubscriptions must be nonzero"));
         }
+        // Check both arrays before waiting on anything, so that a bad pointer
+        // faults right away rather than after the wait.
+        guest::validate_array(&subs.as_array(nsubscriptions))?;
+        guest::validate_array(&events.as_array(nsubscriptions))?;
 
         // Special-case a `poll_oneoff` which is just sleeping on a single
         // relative timer event, such as what WASI libc uses to implement sleep
@@ -1143,9 +1148,11 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         buf: &GuestPtr<'a, u8>,
         buf_len: types::Size,
     ) -> Result<(), Error> {
-        let mut buf = buf.as_array(buf_len).as_slice_mut()?;
-        self.random.try_fill_bytes(buf.deref_mut())?;
-        Ok(())
+        let random = &mut self.random;
+        guest::with_slice_mut(&buf.as_array(buf_len), |buf| {
+            random.try_fill_bytes(buf)?;
+            Ok(())
+        })
     }
 
     async fn sock_recv<'a>(
@@ -1208,6 +1215,25 @@ impl From<&DirFdStat> for types::Fdstat {
     }
 }
 
+/// Checks the rights a file opened by `path_open` is given, which are those
+/// that were requested and that the directory allows its files to have,
+/// against the `oflags` and `fdflags` it's opened with.
+///
+/// Truncating and appending both modify the file, so they're only allowed
+/// if the file is opened with the `fd_write` right, rather than granting
+/// that right implicitly.
+fn opened_file_caps(caps: FileCaps, oflags: OFlags, fdflags: FdFlags) -> Result<FileCaps, Error> {
+    if !caps.contains(FileCaps::WRITE) {
+        if oflags.contains(OFlags::TRUNCATE) {
+            return Err(Error::not_capable().context("truncating a file requires fd_write"));
+        }
+        if fdflags.contains(FdFlags::APPEND) {
+            return Err(Error::not_capable().context("appending to a file requires fd_write"));
+        }
+    }
+    Ok(caps)
+}
+
 // FileCaps can always be represented as wasi Rights
 impl From<&FileCaps> for types::Rights {
     fn from(caps: &FileCaps) -> types::Rights {
@@ -1354,6 +1380,12 @@ impl From<&DirCaps> for types::Rights {
         if caps.contains(DirCaps::FILESTAT_SET_TIMES) {
             rights = rights | types::Rights::FD_FILESTAT_SET_TIMES;
         }
+        if caps.contains(DirCaps::DATASYNC) {
+            rights = rights | types::Rights::FD_DATASYNC;
+        }
+        if caps.contains(DirCaps::SYNC) {
+            rights = rights | types::Rights::FD_SYNC;
+        }
         rights
     }
 }
@@ -1410,6 +1442,12 @@ impl From<&types::Rights> for DirCaps {
         if rights.contains(types::Rights::FD_FILESTAT_SET_TIMES) {
             caps = caps | DirCaps::FILESTAT_SET_TIMES;
         }
+        if rights.contains(types::Rights::FD_DATASYNC) {
+            caps = caps | DirCaps::DATASYNC;
+        }
+        if rights.contains(types::Rights::FD_SYNC) {
+            caps = caps | DirCaps::SYNC;
+        }
         caps
     }
 }
@@ -1510,16 +1548,16 @@ impl From<Filestat> for types::Filestat {
             size: stat.size,
             atim: stat
                 .atim
-                .map(|t| t.duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64)
-                .unwrap_or(0),
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |t| t.as_nanos() as u64),
             mtim: stat
                 .mtim
-                .map(|t| t.duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64)
-                .unwrap_or(0),
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |t| t.as_nanos() as u64),
             ctim: stat
                 .ctim
-                .map(|t| t.duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64)
-                .unwrap_or(0),
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |t| t.as_nanos() as u64),
         }
     }
 }
@@ -1536,11 +1574,51 @@ impl TryFrom<&ReaddirEntity> for types::Dirent {
     }
 }
 
+/// Shared implementation of `fd_seek` and `fd_tell`.
+///
+/// Streams can't be seeked, so they fail with `ESPIPE` regardless of what the
+/// host would do (Linux happily seeks `/dev/null`, for example). Otherwise the
+/// new offset is computed here rather than by the host, so that offsets which
+/// don't fit in a `Filedelta` are `EOVERFLOW` and negative offsets are `EINVAL`
+/// on every platform.
+async fn seek(
+    file: &dyn WasiFile,
+    offset: types::Filedelta,
+    whence: types::Whence,
+) -> Result<types::Filesize, Error> {
+    use std::io::SeekFrom;
+
+    match file.get_filetype().await? {
+        FileType::CharacterDevice
+        | FileType::Pipe
+        | FileType::SocketDgram
+        | FileType::SocketStream => return Err(Error::seek_pipe()),
+        _ => {}
+    }
+
+    let base = match whence {
+        types::Whence::Set => 0,
+        types::Whence::Cur => file.seek(SeekFrom::Current(0)).await?,
+        types::Whence::End => file.get_filestat().await?.size,
+    };
+    if whence == types::Whence::Cur && offset == 0 {
+        return Ok(base);
+    }
+    let new = types::Filedelta::try_from(base)
+        .ok()
+        .and_then(|base| base.checked_add(offset))
+        .ok_or_else(|| Error::overflow().context("seek offset"))?;
+    if new < 0 {
+        return Err(Error::invalid_argument().context("negative seek offset"));
+    }
+    file.seek(SeekFrom::Start(new as u64)).await
+}
+
 fn dirent_bytes(dirent: types::Dirent) -> Vec<u8> {
     use wiggle::GuestType;
     assert_eq!(
         types::Dirent::guest_size(),
-        std::mem::size_of::<types::Dirent>() as _,
+        std::mem::size_of::<types::Dirent>() as u32,
         "Dirent guest repr and host repr should match"
     );
     assert_eq!(
@@ -1598,3 +1676,25 @@ fn systimespec(
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn unknown_os_error_omits_message() {
+        let err = std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "failed to rename /home/user/secret to /mnt/other/secret",
+        );
+        let err = types::Errno::try_from(Error::from(err)).unwrap_err();
+        let msg = format!("{:?}", err);
+        assert!(msg.contains("Unknown OS error"), "{}", msg);
+        assert!(!msg.contains("secret"), "{}", msg);
+    }
+
+    #[test]
+    fn cross_device_is_xdev() {
+        let errno = types::Errno::try_from(Error::cross_device()).unwrap();
+        assert_eq!(errno, types::Errno::Xdev);
+    }
+}
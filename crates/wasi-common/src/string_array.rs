@@ -1,4 +1,4 @@
-use crate::{Error, ErrorExt};
+use crate::{guest, Error, ErrorExt};
 use wiggle::GuestPtr;
 
 pub struct StringArray {
@@ -52,6 +52,8 @@ impl StringArray {
     ) -> Result<(), Error> {
         let element_heads = element_heads.as_array(self.number_elements());
         let buffer = buffer.as_array(self.cumulative_size());
+        guest::validate_array(&element_heads)?;
+        guest::validate_array(&buffer)?;
         let mut cursor = 0;
         for (elem, head) in self.elems.iter().zip(element_heads.iter()) {
             let bytes = elem.as_bytes();
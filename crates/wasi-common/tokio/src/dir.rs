@@ -13,6 +13,11 @@ impl Dir {
     pub fn from_cap_std(dir: cap_std::fs::Dir) -> Self {
         Dir(wasi_cap_std_sync::dir::Dir::from_cap_std(dir))
     }
+
+    /// See [`wasi_cap_std_sync::dir::Dir::case_insensitive_paths`].
+    pub fn case_insensitive_paths(self, enable: bool) -> Self {
+        Dir(self.0.case_insensitive_paths(enable))
+    }
 }
 
 #[wiggle::async_trait]
@@ -77,6 +82,9 @@ impl WasiDir for Dir {
     async fn get_filestat(&self) -> Result<Filestat, Error> {
         block_on_dummy_executor(|| self.0.get_filestat())
     }
+    async fn sync(&self) -> Result<(), Error> {
+        block_on_dummy_executor(|| self.0.sync())
+    }
     async fn get_path_filestat(
         &self,
         path: &str,
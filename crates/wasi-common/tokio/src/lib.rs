@@ -4,68 +4,71 @@ pub mod sched;
 pub mod stdio;
 
 use std::future::Future;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 pub use wasi_cap_std_sync::{clocks_ctx, random_ctx};
-use wasi_common::{Error, Table, WasiCtx, WasiFile};
+use wasi_common::{pipe::OutputCapture, Error, Table, WasiCtx, WasiFile};
 
 pub use dir::Dir;
 pub use file::File;
 
 use crate::sched::sched_ctx;
 
-pub struct WasiCtxBuilder(WasiCtx);
+pub struct WasiCtxBuilder {
+    ctx: WasiCtx,
+    case_insensitive_paths: bool,
+    preopens: Vec<(cap_std::fs::Dir, PathBuf)>,
+}
 
 impl WasiCtxBuilder {
     pub fn new() -> Self {
-        WasiCtxBuilder(WasiCtx::new(
-            random_ctx(),
-            clocks_ctx(),
-            sched_ctx(),
-            Table::new(),
-        ))
+        WasiCtxBuilder {
+            ctx: WasiCtx::new(random_ctx(), clocks_ctx(), sched_ctx(), Table::new()),
+            case_insensitive_paths: false,
+            preopens: Vec::new(),
+        }
     }
     pub fn env(mut self, var: &str, value: &str) -> Result<Self, wasi_common::StringArrayError> {
-        self.0.push_env(var, value)?;
+        self.ctx.push_env(var, value)?;
         Ok(self)
     }
     pub fn envs(mut self, env: &[(String, String)]) -> Result<Self, wasi_common::StringArrayError> {
         for (k, v) in env {
-            self.0.push_env(k, v)?;
+            self.ctx.push_env(k, v)?;
         }
         Ok(self)
     }
     pub fn inherit_env(mut self) -> Result<Self, wasi_common::StringArrayError> {
         for (key, value) in std::env::vars() {
-            self.0.push_env(&key, &value)?;
+            self.ctx.push_env(&key, &value)?;
         }
         Ok(self)
     }
     pub fn arg(mut self, arg: &str) -> Result<Self, wasi_common::StringArrayError> {
-        self.0.push_arg(arg)?;
+        self.ctx.push_arg(arg)?;
         Ok(self)
     }
     pub fn args(mut self, arg: &[String]) -> Result<Self, wasi_common::StringArrayError> {
         for a in arg {
-            self.0.push_arg(&a)?;
+            self.ctx.push_arg(&a)?;
         }
         Ok(self)
     }
     pub fn inherit_args(mut self) -> Result<Self, wasi_common::StringArrayError> {
         for arg in std::env::args() {
-            self.0.push_arg(&arg)?;
+            self.ctx.push_arg(&arg)?;
         }
         Ok(self)
     }
     pub fn stdin(mut self, f: Box<dyn WasiFile>) -> Self {
-        self.0.set_stdin(f);
+        self.ctx.set_stdin(f);
         self
     }
     pub fn stdout(mut self, f: Box<dyn WasiFile>) -> Self {
-        self.0.set_stdout(f);
+        self.ctx.set_stdout(f);
         self
     }
     pub fn stderr(mut self, f: Box<dyn WasiFile>) -> Self {
-        self.0.set_stderr(f);
+        self.ctx.set_stderr(f);
         self
     }
     pub fn inherit_stdin(self) -> Self {
@@ -80,17 +83,42 @@ impl WasiCtxBuilder {
     pub fn inherit_stdio(self) -> Self {
         self.inherit_stdin().inherit_stdout().inherit_stderr()
     }
+    pub fn capture_output(self, output: &OutputCapture) -> Self {
+        self.stdout(Box::new(output.stdout()))
+            .stderr(Box::new(output.stderr()))
+    }
     pub fn preopened_dir(
         mut self,
         dir: cap_std::fs::Dir,
         guest_path: impl AsRef<Path>,
     ) -> Result<Self, Error> {
-        let dir = Box::new(crate::dir::Dir::from_cap_std(dir));
-        self.0.push_preopened_dir(dir, guest_path)?;
+        self.preopens.push((dir, guest_path.as_ref().to_owned()));
         Ok(self)
     }
-    pub fn build(self) -> WasiCtx {
-        self.0
+    /// Makes paths in all preopened directories match their entries ignoring
+    /// case, the way they do on Windows, while keeping the case of names on
+    /// disk.
+    ///
+    /// A path component which doesn't exist as given is matched ignoring case
+    /// by reading its parent directory, which makes lookups which miss much
+    /// slower. If a component has no exact match and more than one entry
+    /// matches it ignoring case, the operation fails with `EEXIST`. See
+    /// [`Dir::case_insensitive_paths`](crate::Dir::case_insensitive_paths) for the details.
+    pub fn case_insensitive_paths(mut self, enable: bool) -> Self {
+        self.case_insensitive_paths = enable;
+        self
+    }
+    pub fn build(mut self) -> WasiCtx {
+        for (dir, guest_path) in self.preopens {
+            let dir = Box::new(
+                crate::dir::Dir::from_cap_std(dir)
+                    .case_insensitive_paths(self.case_insensitive_paths),
+            );
+            self.ctx
+                .push_preopened_dir(dir, guest_path)
+                .expect("a new table has room for every preopened directory");
+        }
+        self.ctx
     }
 }
 
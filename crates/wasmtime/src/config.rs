@@ -239,6 +239,19 @@ impl Default for InstanceAllocationStrategy {
     }
 }
 
+/// The largest stack size accepted by [`Config::max_wasm_stack`] and
+/// [`Config::max_native_stack`]: 1 GiB on 64-bit hosts and 256 MiB on 32-bit
+/// ones.
+///
+/// Larger limits can't be enforced meaningfully, since the stack a thread
+/// actually has runs out long before wasm would reach them, and on 32-bit
+/// hosts they could exceed the address space below the stack pointer.
+pub const MAX_STACK_LIMIT: usize = if cfg!(target_pointer_width = "64") {
+    1 << 30
+} else {
+    256 << 20
+};
+
 /// Global configuration options used to create an [`Engine`](crate::Engine)
 /// and customize its behavior.
 ///
@@ -252,16 +265,27 @@ pub struct Config {
     pub(crate) strategy: CompilationStrategy,
     #[cfg(feature = "cache")]
     pub(crate) cache_config: CacheConfig,
+    #[cfg(feature = "cache")]
+    pub(crate) function_cache: bool,
+    #[cfg(feature = "cache")]
+    pub(crate) cache_ignored_custom_sections: Vec<String>,
     pub(crate) profiler: Arc<dyn ProfilingAgent>,
     pub(crate) mem_creator: Option<Arc<dyn RuntimeMemoryCreator>>,
     pub(crate) allocation_strategy: InstanceAllocationStrategy,
     pub(crate) max_wasm_stack: usize,
+    pub(crate) max_native_stack: Option<usize>,
     pub(crate) features: WasmFeatures,
     pub(crate) wasm_backtrace_details_env_used: bool,
     #[cfg(feature = "async")]
     pub(crate) async_stack_size: usize,
     pub(crate) async_support: bool,
     pub(crate) deserialize_check_wasmtime_version: bool,
+    pub(crate) native_debug_symbols: bool,
+    pub(crate) activation_times: bool,
+    pub(crate) background_compile_threads: usize,
+    pub(crate) background_compile_queue_limit: usize,
+    pub(crate) max_concurrent_compilations: Option<usize>,
+    pub(crate) host_panic_policy: HostPanicPolicy,
 }
 
 impl Config {
@@ -288,16 +312,27 @@ impl Config {
             strategy: CompilationStrategy::Auto,
             #[cfg(feature = "cache")]
             cache_config: CacheConfig::new_cache_disabled(),
+            #[cfg(feature = "cache")]
+            function_cache: false,
+            #[cfg(feature = "cache")]
+            cache_ignored_custom_sections: Vec::new(),
             profiler: Arc::new(NullProfilerAgent),
             mem_creator: None,
             allocation_strategy: InstanceAllocationStrategy::OnDemand,
             max_wasm_stack: 1 << 20,
+            max_native_stack: None,
             wasm_backtrace_details_env_used: false,
             features: WasmFeatures::default(),
             #[cfg(feature = "async")]
             async_stack_size: 2 << 20,
             async_support: false,
             deserialize_check_wasmtime_version: true,
+            native_debug_symbols: false,
+            activation_times: false,
+            background_compile_threads: 1,
+            background_compile_queue_limit: 64,
+            max_concurrent_compilations: None,
+            host_panic_policy: HostPanicPolicy::Unwind,
         };
         ret.cranelift_debug_verifier(false);
         ret.cranelift_opt_level(OptLevel::Speed);
@@ -361,6 +396,12 @@ impl Config {
     /// resolves to `Pending` we switch away from the temporary stack back to
     /// the main stack and propagate the `Pending` status.
     ///
+    /// The futures returned by Wasmtime are `Send` when the store's data is,
+    /// and a suspended future may be resumed on a different thread from the
+    /// one which last polled it, as multi-threaded executors are wont to do.
+    /// Wasmtime carries the state it needs to handle traps over to whichever
+    /// thread polls the future next.
+    ///
     /// In general it's encouraged that the integration with `async` and
     /// wasmtime is designed early on in your embedding of Wasmtime to ensure
     /// that it's planned that WebAssembly executes in the right context of your
@@ -420,6 +461,50 @@ impl Config {
         self
     }
 
+    /// Configures whether compiled code is registered with native debuggers
+    /// through the GDB JIT interface even when [`Config::debug_info`] is
+    /// disabled.
+    ///
+    /// Frame-unwinding tables for all compiled code, including trampolines,
+    /// are always registered with the system unwinder, so native unwinders and
+    /// debuggers can walk through WebAssembly frames. This option additionally
+    /// registers an in-memory object file with synthetic symbol names, such
+    /// as `_wasm_function_3`, derived from each function's index. This allows
+    /// debuggers like gdb and lldb to name WebAssembly frames in backtraces
+    /// without the cost of generating full DWARF debug information.
+    ///
+    /// Registration is best-effort: if the object file can't be registered
+    /// on the current platform then this option is silently ignored.
+    ///
+    /// By default this option is `false`.
+    pub fn native_debug_symbols(&mut self, enable: bool) -> &mut Self {
+        self.native_debug_symbols = enable;
+        self
+    }
+
+    /// Configures what happens when a host function called from WebAssembly
+    /// panics.
+    ///
+    /// With [`HostPanicPolicy::Unwind`], the default, the panic is carried
+    /// over the WebAssembly frames on the stack and resumed where WebAssembly
+    /// was entered from the host, for example in [`Func::call`]. With
+    /// [`HostPanicPolicy::CatchAsTrap`] the panic is instead caught and turned
+    /// into a [`Trap`] carrying the panic's message and the WebAssembly
+    /// backtrace, which propagates like any other trap raised by a host
+    /// function.
+    ///
+    /// Panics are caught with [`std::panic::catch_unwind`], so in a program
+    /// built with `panic = "abort"` the process is still aborted by a
+    /// panicking host function whatever the policy; this option can't help
+    /// there.
+    ///
+    /// [`Func::call`]: crate::Func::call
+    /// [`Trap`]: crate::Trap
+    pub fn host_panic_policy(&mut self, policy: HostPanicPolicy) -> &mut Self {
+        self.host_panic_policy = policy;
+        self
+    }
+
     /// Configures whether backtraces in `Trap` will parse debug info in the wasm file to
     /// have filename/line number information.
     ///
@@ -480,6 +565,108 @@ impl Config {
         self
     }
 
+    /// Configures whether WebAssembly is interrupted when the engine's epoch
+    /// reaches the deadline of its store.
+    ///
+    /// This is an alternative to [`Config::interruptable`] which doesn't
+    /// require an [`InterruptHandle`](crate::InterruptHandle) per store.
+    /// Instead every [`Engine`](crate::Engine) has an epoch counter, which is
+    /// advanced with [`Engine::increment_epoch`](crate::Engine::increment_epoch),
+    /// typically from a background thread on a timer. Each [`Store`] has a
+    /// deadline set with [`Store::set_epoch_deadline`] as a number of epochs
+    /// beyond the current one. Generated code compares the epoch with the
+    /// deadline at the start of each function and at every loop header, and
+    /// once the deadline is reached the [`Store`] either raises a trap, which
+    /// is the default, or invokes the callback configured with
+    /// [`Store::epoch_deadline_callback`].
+    ///
+    /// Note that a [`Store`]'s deadline starts out as epoch 0, so with this
+    /// option enabled WebAssembly traps as soon as it's entered unless a
+    /// deadline is set first.
+    ///
+    /// This can be used along with [`Config::consume_fuel`], in which case
+    /// whichever of the two runs out first stops the WebAssembly.
+    ///
+    /// By default this option is `false`.
+    ///
+    /// [`Store`]: crate::Store
+    /// [`Store::set_epoch_deadline`]: crate::Store::set_epoch_deadline
+    /// [`Store::epoch_deadline_callback`]: crate::Store::epoch_deadline_callback
+    pub fn epoch_interruption(&mut self, enable: bool) -> &mut Self {
+        self.tunables.epoch_interruption = enable;
+        self
+    }
+
+    /// Configures whether stores keep track of how much fuel each wasm
+    /// function consumes, in addition to the total.
+    ///
+    /// This only has an effect if [`Config::consume_fuel`] is enabled, in
+    /// which case [`Store::function_fuel_consumed`](crate::Store::function_fuel_consumed)
+    /// returns the fuel consumed by the code of each function, not counting
+    /// the functions it calls.
+    ///
+    /// Generated code reports the fuel it consumed by calling into the runtime
+    /// on every call to another function and on every return, in addition to
+    /// when it runs out of fuel. This makes each call and return considerably
+    /// more expensive, so this option is intended for profiling rather than
+    /// for production use.
+    ///
+    /// By default this option is `false`.
+    pub fn per_function_fuel_accounting(&mut self, enable: bool) -> &mut Self {
+        self.tunables.per_function_fuel_accounting = enable;
+        self
+    }
+
+    /// Configures whether modules may choose where interrupt, fuel and epoch
+    /// checks are placed with a `wasmtime.yieldpoints` custom section.
+    ///
+    /// With [`Config::interruptable`], [`Config::consume_fuel`] or
+    /// [`Config::epoch_interruption`] enabled, generated code normally checks
+    /// whether it should stop at the start of each function and at every loop
+    /// header. When this option is enabled, a module carrying a
+    /// `wasmtime.yieldpoints` custom section instead gets those checks exactly
+    /// at the locations the section lists, in addition to function entry. Loops
+    /// which aren't listed aren't checked at all, so a module which opts in
+    /// takes responsibility for not running forever between yieldpoints.
+    /// Modules without the section are unaffected.
+    ///
+    /// The section's payload is a LEB128-encoded `u32` count followed by
+    /// that many pairs of LEB128-encoded `u32`s: the index of a function
+    /// defined by the module, and the byte offset, from the start of the
+    /// module, of an instruction in that function's body. A `loop`
+    /// instruction listed this way is checked on each iteration; any other
+    /// instruction is checked just before it executes. Compilation fails if a
+    /// function index refers to an imported or nonexistent function, or if
+    /// an offset doesn't fall on an instruction boundary.
+    ///
+    /// This option is experimental and the format of the section may change.
+    /// It's currently only implemented for the Cranelift backend. By default
+    /// this option is `false`.
+    pub fn yieldpoints_from_custom_section(&mut self, enable: bool) -> &mut Self {
+        self.tunables.yieldpoints = enable;
+        self
+    }
+
+    /// Configures whether stores measure how much time each call into
+    /// WebAssembly spends running guest code versus the host functions it
+    /// calls.
+    ///
+    /// When enabled every transition between the host and wasm reads the
+    /// wall clock and the thread's CPU clock, and the times of the most
+    /// recently finished call are available from
+    /// [`Store::last_activation_times`]. See [`ActivationTimes`] for exactly
+    /// what's measured. When disabled the cost is a single branch per
+    /// transition.
+    ///
+    /// By default this option is `false`.
+    ///
+    /// [`Store::last_activation_times`]: crate::Store::last_activation_times
+    /// [`ActivationTimes`]: crate::ActivationTimes
+    pub fn activation_times(&mut self, enable: bool) -> &mut Self {
+        self.activation_times = enable;
+        self
+    }
+
     /// Configures the maximum amount of stack space available for
     /// executing WebAssembly code.
     ///
@@ -492,6 +679,12 @@ impl Config {
     /// If a wasm call (or series of nested wasm calls) take more stack space
     /// than the `size` specified then a stack overflow trap will be raised.
     ///
+    /// This limit applies to each activation of wasm individually: when wasm
+    /// calls the host and the host calls back into wasm, the inner call is
+    /// measured from the stack pointer at which it was entered. The total
+    /// stack used by all nested activations, including host frames between
+    /// them, is separately limited by [`Config::max_native_stack`].
+    ///
     /// When the `async` feature is enabled, this value cannot exceed the
     /// `async_stack_size` option. Be careful not to set this value too close
     /// to `async_stack_size` as doing so may limit how much stack space
@@ -499,6 +692,9 @@ impl Config {
     /// on stack overflow, a host function that overflows the stack will
     /// abort the process.
     ///
+    /// The size cannot exceed [`MAX_STACK_LIMIT`], which is far more than
+    /// the stack that threads usually have.
+    ///
     /// By default this option is 1 MiB.
     pub fn max_wasm_stack(&mut self, size: usize) -> Result<&mut Self> {
         #[cfg(feature = "async")]
@@ -509,11 +705,54 @@ impl Config {
         if size == 0 {
             bail!("wasm stack size cannot be zero");
         }
+        if size > MAX_STACK_LIMIT {
+            bail!(
+                "wasm stack size cannot exceed {} bytes, got {}",
+                MAX_STACK_LIMIT,
+                size
+            );
+        }
 
         self.max_wasm_stack = size;
         Ok(self)
     }
 
+    /// Configures the maximum amount of native stack space that nested
+    /// WebAssembly activations may consume in total.
+    ///
+    /// Each time the host enters wasm it's given [`Config::max_wasm_stack`]
+    /// bytes of stack from the point at which it was entered. If that wasm
+    /// calls a host function which in turn calls wasm again, the stack used
+    /// by the outer activations, and by the host frames between them, also
+    /// counts against this budget. Once the budget runs out any further
+    /// entry into wasm raises a stack overflow trap, so deeply nested
+    /// host/wasm recursion traps cleanly instead of exhausting the native
+    /// stack.
+    ///
+    /// For async stores, where each call into wasm runs on its own fiber, the
+    /// stack used on all enclosing fibers counts against this budget too.
+    ///
+    /// By default this is the same as [`Config::max_wasm_stack`], meaning
+    /// that all nested activations share a single `max_wasm_stack` budget.
+    /// Be careful when raising this that the thread (or fiber) running wasm
+    /// actually has this much stack available. Like `max_wasm_stack`, this
+    /// cannot exceed [`MAX_STACK_LIMIT`].
+    pub fn max_native_stack(&mut self, size: usize) -> Result<&mut Self> {
+        if size == 0 {
+            bail!("native stack size cannot be zero");
+        }
+        if size > MAX_STACK_LIMIT {
+            bail!(
+                "native stack size cannot exceed {} bytes, got {}",
+                MAX_STACK_LIMIT,
+                size
+            );
+        }
+
+        self.max_native_stack = Some(size);
+        Ok(self)
+    }
+
     /// Configures the size of the stacks used for asynchronous execution.
     ///
     /// This setting configures the size of the stacks that are allocated for
@@ -740,14 +979,12 @@ impl Config {
     /// more information see the documentation of [`OptLevel`].
     ///
     /// The default value for this is `OptLevel::None`.
+    ///
+    /// Individual modules can be compiled at a different level with
+    /// [`Module::new_with_opt_level`](crate::Module::new_with_opt_level).
     pub fn cranelift_opt_level(&mut self, level: OptLevel) -> &mut Self {
-        let val = match level {
-            OptLevel::None => "none",
-            OptLevel::Speed => "speed",
-            OptLevel::SpeedAndSize => "speed_and_size",
-        };
         self.flags
-            .set("opt_level", val)
+            .set("opt_level", level.flag_value())
             .expect("should be valid flag");
         self
     }
@@ -874,6 +1111,69 @@ impl Config {
         Ok(self)
     }
 
+    /// Configures whether individual functions are cached in addition to
+    /// whole modules.
+    ///
+    /// The module cache configured with [`Config::cache_config_load`] is keyed
+    /// on the entire wasm binary, so changing a single function recompiles the
+    /// whole module. With this option enabled each function's compiled code
+    /// is also cached on its own, keyed on the function's body, the parts of
+    /// the module that affect its code generation (types, signatures, tables,
+    /// memories and globals), and the compiler's settings. Compiling a module
+    /// which differs from a previously compiled one in only a few functions
+    /// then only generates code for those functions. Linking and trampolines
+    /// are always generated fresh.
+    ///
+    /// Cached functions are stored in the same directory as cached modules,
+    /// so this has no effect unless a cache configuration is enabled. It also
+    /// has no effect when [`Config::debug_info`] is enabled.
+    ///
+    /// By default this option is `false`.
+    ///
+    /// This method is only available when the `cache` feature of this crate is
+    /// enabled.
+    #[cfg(feature = "cache")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cache")))]
+    pub fn cache_functions(&mut self, enable: bool) -> &mut Self {
+        self.function_cache = enable;
+        self
+    }
+
+    /// Excludes the contents of the named custom sections from the key
+    /// compiled modules are cached under.
+    ///
+    /// By default the module cache is keyed on every byte of the wasm binary,
+    /// so toolchains which embed something like a build timestamp in a custom
+    /// section never hit in the cache. Naming those sections here means that
+    /// modules differing only in their contents share a cache entry.
+    ///
+    /// Some custom sections do affect compilation: the `name` section is used
+    /// for function names in backtraces, and the `.debug_*` sections are
+    /// translated into native debug information when
+    /// [`Config::debug_info`] is enabled. Only name sections whose contents
+    /// don't matter to the compiled module, otherwise a module may be loaded
+    /// from the cache with names or debug information from another binary.
+    ///
+    /// Only the contents of the named top-level sections are ignored. Their
+    /// names and positions relative to other sections are still part of the
+    /// key, so adding, removing, or reordering sections still misses in the
+    /// cache. The contents of the named sections of the binary a module was
+    /// created from are available through [`Module::custom_sections`], even
+    /// when its compiled code came from the cache.
+    ///
+    /// By default no sections are ignored.
+    ///
+    /// This method is only available when the `cache` feature of this crate is
+    /// enabled.
+    ///
+    /// [`Module::custom_sections`]: crate::Module::custom_sections
+    #[cfg(feature = "cache")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cache")))]
+    pub fn cache_ignore_custom_sections(&mut self, names: &[&str]) -> &mut Self {
+        self.cache_ignored_custom_sections = names.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
     /// Sets a custom memory creator.
     ///
     /// Custom memory creators are used when creating host `Memory` objects or when
@@ -1089,10 +1389,15 @@ impl Config {
     /// won't actually ever use more memory, just use more of the address
     /// space).
     ///
+    /// Accesses which fault in the guard region before a linear memory are
+    /// reported as out-of-bounds memory traps, just like those which fault in
+    /// the guard region after it.
+    ///
     /// The size of the guard region before linear memory is the same as the
     /// guard size that comes after linear memory, which is configured by
     /// [`Config::static_memory_guard_size`] and
-    /// [`Config::dynamic_memory_guard_size`].
+    /// [`Config::dynamic_memory_guard_size`], unless it's configured with
+    /// [`Config::guard_before_linear_memory_size`].
     ///
     /// ## Default
     ///
@@ -1102,6 +1407,204 @@ impl Config {
         self
     }
 
+    /// Configures the size, in bytes, of the guard region before linear
+    /// memories when [`Config::guard_before_linear_memory`] is enabled.
+    ///
+    /// A larger guard region catches accesses further below the start of
+    /// linear memory, and a smaller one saves address space. Either way this
+    /// doesn't affect generated code, and the size is rounded up to a multiple
+    /// of the host's page size.
+    ///
+    /// ## Default
+    ///
+    /// By default the guard region before a linear memory is the same size as
+    /// the one after it.
+    pub fn guard_before_linear_memory_size(&mut self, size: u64) -> &mut Self {
+        self.tunables.guard_before_linear_memory_size = Some(round_up_to_pages(size));
+        self
+    }
+
+    /// Configures whether compiled modules record the wasm offset of every
+    /// instruction, or only of every function.
+    ///
+    /// The wasm offsets of instructions are reported by
+    /// [`FrameInfo::module_offset`](crate::FrameInfo::module_offset) for the
+    /// frames of a trap's backtrace, and they're used to look up file and line
+    /// information when [`Config::wasm_backtrace_details`] is enabled. Their
+    /// mapping from compiled code is stored compactly, but for large modules
+    /// it's still a sizable part of a compiled module, in memory and when
+    /// serialized. When this is disabled only the mapping of functions is
+    /// kept, and the offset reported for every frame is that of the start of
+    /// its function.
+    ///
+    /// ## Default
+    ///
+    /// This value defaults to `true`.
+    pub fn instruction_address_maps(&mut self, enable: bool) -> &mut Self {
+        self.tunables.instruction_address_maps = enable;
+        self
+    }
+
+    /// Configures whether the bounds checks of linear memory accesses that
+    /// can't reach past a memory's guard region are omitted.
+    ///
+    /// An access is known not to reach past the guard region when its largest
+    /// possible effective address, from its constant address or otherwise the
+    /// largest 32-bit address, plus its constant offset and width, is at most
+    /// the size of the memory's reservation plus its guard region. That's
+    /// [`Config::static_memory_maximum_size`] plus
+    /// [`Config::static_memory_guard_size`] for static memories, and just
+    /// [`Config::dynamic_memory_guard_size`] for dynamic memories. Every byte
+    /// of such an access is either in bounds or in the guard region, where it
+    /// faults and raises a trap just as the bounds check would have, so wasm
+    /// semantics are unaffected.
+    ///
+    /// With the default 4 GiB static memories this only matters for accesses
+    /// with very large offsets, as smaller ones already aren't checked, but
+    /// with smaller static memories it removes the checks of accesses to
+    /// constant addresses and, if the memory and its guard region span more
+    /// than 4 GiB, those with offsets that fit in the rest of the guard region.
+    ///
+    /// This is opt-in because the omitted accesses also don't get the Spectre
+    /// mitigation which Cranelift applies to bounds checked heap accesses, and
+    /// because it relies on a [`MemoryCreator`](crate::MemoryCreator)
+    /// reserving the guard region it's asked for.
+    ///
+    /// ## Default
+    ///
+    /// This value defaults to `false`.
+    pub fn elide_guarded_bounds_checks(&mut self, enable: bool) -> &mut Self {
+        self.tunables.elide_guarded_bounds_checks = enable;
+        self
+    }
+
+    /// Configures whether memory accesses can be intercepted by the host in
+    /// order to emulate devices which are mapped into linear memory.
+    ///
+    /// When enabled, pages of a memory can be registered as virtual with
+    /// [`Memory::register_virtual_pages`](crate::Memory::register_virtual_pages).
+    /// Scalar loads and stores which touch a virtual page don't access the
+    /// memory but instead call the handler configured with
+    /// [`Store::memory_access_handler`](crate::Store::memory_access_handler),
+    /// which can supply the value of a load or accept a store, after which
+    /// execution continues with the next instruction, or raise an
+    /// out-of-bounds trap.
+    ///
+    /// This is implemented by compiling every scalar load and store of 32-bit
+    /// memories to a call into the runtime, which is far slower than an
+    /// inline access, so this is only intended for modules which need it.
+    /// Modules which access such memories with SIMD or atomic instructions
+    /// fail to compile. Bulk memory instructions like `memory.copy` and
+    /// `memory.fill` aren't intercepted and operate on the memory itself.
+    ///
+    /// This is an experimental option which is only supported by the Cranelift
+    /// compilation strategy.
+    ///
+    /// ## Default
+    ///
+    /// This value defaults to `false`.
+    pub fn memory_access_hooks(&mut self, enable: bool) -> &mut Self {
+        self.tunables.memory_access_hooks = enable;
+        self
+    }
+
+    /// Configures the maximum number of memories a module may declare,
+    /// counting both imported and defined memories.
+    ///
+    /// Modules declaring more memories are rejected when they're compiled,
+    /// before any of their code is, with an error saying how many memories
+    /// were declared and what the limit is. Modules loaded with
+    /// [`Module::deserialize`](crate::Module::deserialize) are checked against
+    /// the same limit, regardless of the one they were compiled with.
+    ///
+    /// This is a way to enforce a policy such as "no multi-memory modules" up
+    /// front, instead of finding out when an instance fails to be created.
+    ///
+    /// ## Default
+    ///
+    /// By default there's no limit.
+    pub fn max_memories(&mut self, memories: u32) -> &mut Self {
+        self.tunables.max_memories = memories;
+        self
+    }
+
+    /// Configures the maximum number of tables a module may declare, counting
+    /// both imported and defined tables.
+    ///
+    /// This is checked in the same way as [`Config::max_memories`].
+    ///
+    /// ## Default
+    ///
+    /// By default there's no limit.
+    pub fn max_tables(&mut self, tables: u32) -> &mut Self {
+        self.tunables.max_tables = tables;
+        self
+    }
+
+    /// Configures the maximum sum of the minimum sizes, in wasm pages, of the
+    /// memories a module defines.
+    ///
+    /// This bounds the memory an instance of the module needs just to be
+    /// created, and is checked in the same way as [`Config::max_memories`].
+    /// Imported memories aren't included since they're allocated elsewhere.
+    ///
+    /// ## Default
+    ///
+    /// By default there's no limit.
+    pub fn max_memory_pages(&mut self, pages: u64) -> &mut Self {
+        self.tunables.max_memory_pages = pages;
+        self
+    }
+
+    /// Configures the maximum sum of the minimum sizes, in elements, of the
+    /// tables a module defines.
+    ///
+    /// This is the table counterpart of [`Config::max_memory_pages`]. Tables
+    /// created with [`Table::new`](crate::Table::new) are held to the same
+    /// limit, as are memories created with
+    /// [`Memory::new`](crate::Memory::new) to the limit on pages.
+    ///
+    /// ## Default
+    ///
+    /// By default tables may have up to 10,000,000 elements in total, the
+    /// most that the JS API allows a single table to have.
+    pub fn max_table_elements(&mut self, elements: u64) -> &mut Self {
+        self.tunables.max_table_elements = elements;
+        self
+    }
+
+    /// Configures the maximum number of parameters a function type may have.
+    ///
+    /// Modules declaring a function type with more parameters are rejected in
+    /// the same way as those exceeding [`Config::max_memories`], with an
+    /// error giving the numbers of parameters and results of the type. The
+    /// limit also applies to the types of host functions:
+    /// [`Linker::func_new`](crate::Linker::func_new) returns an error for
+    /// types over it and [`Func::new`](crate::Func::new) panics.
+    ///
+    /// Note that the WebAssembly binary decoder limits function types in
+    /// modules to 1000 parameters regardless of this option.
+    ///
+    /// ## Default
+    ///
+    /// By default function types may have up to 1000 parameters.
+    pub fn max_function_params(&mut self, params: u32) -> &mut Self {
+        self.tunables.max_func_params = params;
+        self
+    }
+
+    /// Configures the maximum number of results a function type may have.
+    ///
+    /// This is the result counterpart of [`Config::max_function_params`].
+    ///
+    /// ## Default
+    ///
+    /// By default function types may have up to 1000 results.
+    pub fn max_function_results(&mut self, results: u32) -> &mut Self {
+        self.tunables.max_func_results = results;
+        self
+    }
+
     /// Configure whether deserialized modules should validate version
     /// information. This only effects [`crate::Module::deserialize()`], which is
     /// used to load compiled code from trusted sources.  When true,
@@ -1116,10 +1619,86 @@ impl Config {
         self
     }
 
-    pub(crate) fn target_isa(&self) -> Box<dyn TargetIsa> {
-        self.isa_flags
-            .clone()
-            .finish(settings::Flags::new(self.flags.clone()))
+    /// Configures the maximum number of threads used by
+    /// [`Engine::compile_in_background`](crate::Engine::compile_in_background).
+    ///
+    /// Threads are only started once background compilations are queued, and
+    /// at most this many compilations run at once. Note that each compilation
+    /// may itself use multiple threads if the `parallel-compilation` feature
+    /// is enabled.
+    ///
+    /// By default this is 1.
+    pub fn background_compile_threads(&mut self, threads: usize) -> Result<&mut Self> {
+        if threads == 0 {
+            bail!("the number of background compilation threads cannot be zero");
+        }
+
+        self.background_compile_threads = threads;
+        Ok(self)
+    }
+
+    /// Configures the maximum number of compilations which may wait in the
+    /// queue of [`Engine::compile_in_background`](crate::Engine::compile_in_background)
+    /// for a thread to run them.
+    ///
+    /// Queueing more compilations than this returns an error, rather than
+    /// letting the queue grow without bound.
+    ///
+    /// By default this is 64.
+    pub fn background_compile_queue_limit(&mut self, limit: usize) -> Result<&mut Self> {
+        if limit == 0 {
+            bail!("the background compilation queue limit cannot be zero");
+        }
+
+        self.background_compile_queue_limit = limit;
+        Ok(self)
+    }
+
+    /// Configures the maximum number of compilations which the engine runs at
+    /// once, across all threads.
+    ///
+    /// This covers [`Module::new`](crate::Module::new) and the other ways of
+    /// compiling a module, including
+    /// [`Engine::precompile_module`](crate::Engine::precompile_module) and
+    /// background compilations. Compilations beyond the limit wait for a
+    /// running one to finish, and are started in the order they arrived.
+    /// [`Module::new`](crate::Module::new) blocks the calling thread while it
+    /// waits, whereas [`Module::new_async`](crate::Module::new_async) waits
+    /// without blocking and compiles on another thread.
+    ///
+    /// When the `parallel-compilation` feature is enabled the functions of a
+    /// module are compiled in parallel on a thread pool of this size owned by
+    /// the engine, rather than on the global `rayon` pool, so that the number
+    /// of threads compiling code at once never exceeds the limit either.
+    ///
+    /// The number of compilations waiting for their turn is reported by
+    /// [`Engine::compilations_waiting`](crate::Engine::compilations_waiting).
+    ///
+    /// By default the number of concurrent compilations is unlimited.
+    pub fn max_concurrent_compilations(&mut self, max: usize) -> Result<&mut Self> {
+        if max == 0 {
+            bail!("the maximum number of concurrent compilations cannot be zero");
+        }
+
+        self.max_concurrent_compilations = Some(max);
+        Ok(self)
+    }
+
+    pub(crate) fn target_isa(&self, opt_level: &OptLevel) -> Box<dyn TargetIsa> {
+        let mut flags = self.flags.clone();
+        flags
+            .set("opt_level", opt_level.flag_value())
+            .expect("should be valid flag");
+        self.isa_flags.clone().finish(settings::Flags::new(flags))
+    }
+
+    /// Returns the optimization level modules are compiled at by default.
+    pub(crate) fn opt_level(&self) -> OptLevel {
+        match settings::Flags::new(self.flags.clone()).opt_level() {
+            settings::OptLevel::None => OptLevel::None,
+            settings::OptLevel::Speed => OptLevel::Speed,
+            settings::OptLevel::SpeedAndSize => OptLevel::SpeedAndSize,
+        }
     }
 
     pub(crate) fn target_isa_with_reference_types(&self) -> Box<dyn TargetIsa> {
@@ -1128,11 +1707,25 @@ impl Config {
         self.isa_flags.clone().finish(settings::Flags::new(flags))
     }
 
-    pub(crate) fn build_compiler(&self, allocator: &dyn InstanceAllocator) -> Compiler {
-        let isa = self.target_isa();
+    pub(crate) fn build_compiler(
+        &self,
+        allocator: &dyn InstanceAllocator,
+        opt_level: &OptLevel,
+    ) -> Compiler {
+        let isa = self.target_isa(opt_level);
         let mut tunables = self.tunables.clone();
         allocator.adjust_tunables(&mut tunables);
-        Compiler::new(isa, self.strategy, tunables, self.features)
+        let mut compiler = Compiler::new(isa, self.strategy, tunables, self.features);
+        // Trampolines only move arguments and results between the host's
+        // array of values and registers, which optimizing for speed can't
+        // improve, so they're compiled at a fixed level which favors size no
+        // matter which level functions are compiled at.
+        compiler.set_trampoline_isa(self.target_isa(&OptLevel::SpeedAndSize));
+        #[cfg(feature = "cache")]
+        if self.function_cache {
+            compiler.enable_function_cache(self.cache_config.clone());
+        }
+        compiler
     }
 
     pub(crate) fn build_allocator(&self) -> Result<Box<dyn InstanceAllocator>> {
@@ -1181,12 +1774,16 @@ impl fmt::Debug for Config {
         f.debug_struct("Config")
             .field("debug_info", &self.tunables.generate_native_debuginfo)
             .field("parse_wasm_debuginfo", &self.tunables.parse_wasm_debuginfo)
+            .field("native_debug_symbols", &self.native_debug_symbols)
+            .field("host_panic_policy", &self.host_panic_policy)
+            .field("activation_times", &self.activation_times)
             .field("strategy", &self.strategy)
             .field("wasm_threads", &self.features.threads)
             .field("wasm_reference_types", &self.features.reference_types)
             .field("wasm_bulk_memory", &self.features.bulk_memory)
             .field("wasm_simd", &self.features.simd)
             .field("wasm_multi_value", &self.features.multi_value)
+            .field("wasm_multi_memory", &self.features.multi_memory)
             .field("wasm_module_linking", &self.features.module_linking)
             .field(
                 "static_memory_maximum_size",
@@ -1205,6 +1802,24 @@ impl fmt::Debug for Config {
                 "guard_before_linear_memory",
                 &self.tunables.guard_before_linear_memory,
             )
+            .field(
+                "guard_before_linear_memory_size",
+                &self.tunables.guard_before_linear_memory_size,
+            )
+            .field(
+                "instruction_address_maps",
+                &self.tunables.instruction_address_maps,
+            )
+            .field(
+                "elide_guarded_bounds_checks",
+                &self.tunables.elide_guarded_bounds_checks,
+            )
+            .field("max_memories", &self.tunables.max_memories)
+            .field("max_tables", &self.tunables.max_tables)
+            .field("max_memory_pages", &self.tunables.max_memory_pages)
+            .field("max_table_elements", &self.tunables.max_table_elements)
+            .field("max_func_params", &self.tunables.max_func_params)
+            .field("max_func_results", &self.tunables.max_func_results)
             .field(
                 "flags",
                 &settings::Flags::new(self.flags.clone()).to_string(),
@@ -1244,7 +1859,7 @@ pub enum Strategy {
 
 /// Possible optimization levels for the Cranelift codegen backend.
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub enum OptLevel {
     /// No optimizations performed, minimizes compilation time by disabling most
     /// optimizations.
@@ -1256,6 +1871,27 @@ pub enum OptLevel {
     SpeedAndSize,
 }
 
+impl OptLevel {
+    /// Returns the value of Cranelift's `opt_level` setting for this level.
+    pub(crate) fn flag_value(&self) -> &'static str {
+        match self {
+            OptLevel::None => "none",
+            OptLevel::Speed => "speed",
+            OptLevel::SpeedAndSize => "speed_and_size",
+        }
+    }
+
+    /// Parses a value of Cranelift's `opt_level` setting.
+    pub(crate) fn from_flag_value(value: &str) -> Option<OptLevel> {
+        match value {
+            "none" => Some(OptLevel::None),
+            "speed" => Some(OptLevel::Speed),
+            "speed_and_size" => Some(OptLevel::SpeedAndSize),
+            _ => None,
+        }
+    }
+}
+
 /// Select which profiling technique to support.
 #[derive(Debug, Clone, Copy)]
 pub enum ProfilingStrategy {
@@ -1285,3 +1921,17 @@ pub enum WasmBacktraceDetails {
     /// `WASMTIME_BACKTRACE_DETAILS` environment variable.
     Environment,
 }
+
+/// What happens when a host function called from WebAssembly panics.
+///
+/// This is used as an argument to the [`Config::host_panic_policy`] method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostPanicPolicy {
+    /// The panic unwinds past the WebAssembly frames on the stack and carries
+    /// on where WebAssembly was entered from the host.
+    Unwind,
+
+    /// The panic is caught where the host function returns to WebAssembly and
+    /// turned into a trap.
+    CatchAsTrap,
+}
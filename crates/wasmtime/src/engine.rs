@@ -1,12 +1,21 @@
 use crate::signatures::SignatureRegistry;
-use crate::{Config, Trap};
+use crate::{Config, Module, OptLevel, Trap};
 use anyhow::Result;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering::SeqCst};
+use std::sync::{Arc, Mutex, Weak};
 #[cfg(feature = "cache")]
 use wasmtime_cache::CacheConfig;
 use wasmtime_jit::Compiler;
 use wasmtime_runtime::{debug_builtins, InstanceAllocator};
 
+mod background;
+mod compile_limit;
+mod summary;
+
+pub use background::BackgroundCompile;
+pub use summary::ConfigSummary;
+
 /// An `Engine` which is a global context for compilation and management of wasm
 /// modules.
 ///
@@ -22,7 +31,18 @@ use wasmtime_runtime::{debug_builtins, InstanceAllocator};
 ///
 /// Using `clone` on an `Engine` is a cheap operation. It will not create an
 /// entirely new engine, but rather just a new reference to the existing engine.
-/// In other words it's a shallow copy, not a deep copy.
+/// In other words it's a shallow copy, not a deep copy. Use [`Engine::same`]
+/// to test whether two handles refer to the same engine.
+///
+/// ## Engines and threads
+///
+/// `Engine` is both `Send` and `Sync`, and all state shared between clones of
+/// an engine is internally synchronized. This means that clones of one engine
+/// may be moved to other threads and used there to concurrently compile
+/// modules with [`Module::new`] and friends, create [`Store`](crate::Store)s,
+/// and instantiate modules. When the compilation cache is enabled concurrent
+/// compilations of the same module may race to populate the cache, but each
+/// compilation still produces a valid module.
 ///
 /// ## Engines and `Default`
 ///
@@ -36,9 +56,28 @@ pub struct Engine {
 
 struct EngineInner {
     config: Config,
-    compiler: Compiler,
+    /// The optimization level of `compiler`.
+    opt_level: OptLevel,
+    compiler: Arc<Compiler>,
+    /// Compilers for the other optimization levels, built the first time a
+    /// module is compiled at that level.
+    opt_level_compilers: Mutex<HashMap<OptLevel, Arc<Compiler>>>,
     allocator: Box<dyn InstanceAllocator>,
     signatures: SignatureRegistry,
+    compile_queue: background::CompileQueue,
+    compile_limit: Arc<compile_limit::CompileLimit>,
+    /// The current epoch, advanced with `Engine::increment_epoch` and read by
+    /// wasm with epoch interruption enabled.
+    epoch: AtomicU64,
+}
+
+/// A reference to an [`Engine`] which doesn't keep it alive.
+pub(crate) struct WeakEngine(Weak<EngineInner>);
+
+impl WeakEngine {
+    pub(crate) fn upgrade(&self) -> Option<Engine> {
+        self.0.upgrade().map(|inner| Engine { inner })
+    }
 }
 
 impl Engine {
@@ -52,13 +91,20 @@ impl Engine {
         debug_builtins::ensure_exported();
         let allocator = config.build_allocator()?;
         let registry = SignatureRegistry::new();
+        let opt_level = config.opt_level();
+        let compile_limit = compile_limit::CompileLimit::new(config.max_concurrent_compilations)?;
 
         Ok(Engine {
             inner: Arc::new(EngineInner {
                 config: config.clone(),
-                compiler: config.build_compiler(allocator.as_ref()),
+                compiler: Arc::new(config.build_compiler(allocator.as_ref(), &opt_level)),
+                opt_level,
+                opt_level_compilers: Default::default(),
                 allocator,
                 signatures: registry,
+                compile_queue: Default::default(),
+                compile_limit: Arc::new(compile_limit),
+                epoch: AtomicU64::new(0),
             }),
         })
     }
@@ -90,10 +136,58 @@ impl Engine {
         &self.inner.config
     }
 
+    /// Returns a summary of the configuration this engine is actually using.
+    ///
+    /// This is meant for diagnostics, such as logging the configuration at
+    /// startup or including it in bug reports. See [`ConfigSummary`] for what
+    /// it describes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// let engine = Engine::new(Config::new().consume_fuel(true))?;
+    /// let summary = engine.config_summary();
+    /// assert!(summary.consume_fuel);
+    /// println!("{:#?}", summary);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn config_summary(&self) -> ConfigSummary {
+        ConfigSummary::new(self)
+    }
+
     pub(crate) fn compiler(&self) -> &Compiler {
         &self.inner.compiler
     }
 
+    /// Returns the optimization level modules are compiled at by default.
+    pub(crate) fn opt_level(&self) -> OptLevel {
+        self.inner.opt_level
+    }
+
+    /// Returns the compiler to use for modules compiled at `opt_level`.
+    ///
+    /// This is the same as `compiler` apart from the optimization level, so
+    /// that its cache key and serialized flags reflect the level used.
+    pub(crate) fn compiler_for(&self, opt_level: OptLevel) -> Arc<Compiler> {
+        if opt_level == self.inner.opt_level {
+            return self.inner.compiler.clone();
+        }
+        self.inner
+            .opt_level_compilers
+            .lock()
+            .unwrap()
+            .entry(opt_level)
+            .or_insert_with(|| {
+                Arc::new(
+                    self.inner
+                        .config
+                        .build_compiler(self.inner.allocator.as_ref(), &opt_level),
+                )
+            })
+            .clone()
+    }
+
     pub(crate) fn allocator(&self) -> &dyn InstanceAllocator {
         self.inner.allocator.as_ref()
     }
@@ -104,14 +198,109 @@ impl Engine {
     }
 
     /// Returns whether the engine `a` and `b` refer to the same configuration.
+    ///
+    /// This is `true` only if `a` and `b` are clones of the same engine. Two
+    /// engines created separately, even from the same [`Config`], are never
+    /// the same. Values such as a [`Module`] can only be used with stores of
+    /// the engine they were created with.
     pub fn same(a: &Engine, b: &Engine) -> bool {
         Arc::ptr_eq(&a.inner, &b.inner)
     }
 
+    /// Advances the epoch of this engine by one.
+    ///
+    /// With [`Config::epoch_interruption`] enabled this causes WebAssembly in
+    /// every [`Store`](crate::Store) of this engine whose epoch deadline has
+    /// now been reached to be interrupted, at its next function entry or loop
+    /// header. See [`Store::set_epoch_deadline`](crate::Store::set_epoch_deadline)
+    /// for how deadlines are set.
+    ///
+    /// This is a single atomic increment, so it's cheap and can be called
+    /// from any thread, for example from a background thread on a timer, or
+    /// even from a signal handler.
+    pub fn increment_epoch(&self) {
+        self.inner.epoch.fetch_add(1, SeqCst);
+    }
+
+    /// Returns the current epoch of this engine.
+    pub(crate) fn current_epoch(&self) -> u64 {
+        self.inner.epoch.load(SeqCst)
+    }
+
+    /// Returns the epoch counter which wasm reads, see
+    /// `VMInterrupts::epoch_ptr`.
+    pub(crate) fn epoch_counter(&self) -> *const AtomicU64 {
+        &self.inner.epoch
+    }
+
     pub(crate) fn signatures(&self) -> &SignatureRegistry {
         &self.inner.signatures
     }
 
+    pub(crate) fn weak(&self) -> WeakEngine {
+        WeakEngine(Arc::downgrade(&self.inner))
+    }
+
+    /// Compiles a WebAssembly module on a background thread owned by this
+    /// engine.
+    ///
+    /// The `bytes` are in any format accepted by [`Module::new`], and
+    /// `callback` is invoked exactly once with the result of compiling them,
+    /// typically on the background thread. This returns as soon as the
+    /// compilation has been queued, and the returned handle can be used to
+    /// cancel it.
+    ///
+    /// The number of threads running compilations, and the number of
+    /// compilations which may be waiting for a thread, are limited by
+    /// [`Config::background_compile_threads`] and
+    /// [`Config::background_compile_queue_limit`].
+    ///
+    /// When the last reference to this engine is dropped, compilations which
+    /// haven't started yet are cancelled, with their callbacks invoked with
+    /// an error, and the engine's background threads are shut down before the
+    /// drop returns. Note that compilations which are running hold a reference
+    /// to the engine until they finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the queue is full or a new background thread
+    /// couldn't be started. In that case `callback` is never invoked.
+    pub fn compile_in_background(
+        &self,
+        bytes: impl Into<Vec<u8>>,
+        callback: impl FnOnce(Result<Module>) + Send + 'static,
+    ) -> Result<BackgroundCompile> {
+        self.inner
+            .compile_queue
+            .push(self, bytes.into(), Box::new(callback))
+    }
+
+    /// Returns the number of compilations started with
+    /// [`Engine::compile_in_background`] which are either waiting in the
+    /// queue or currently running.
+    pub fn background_compile_queue_depth(&self) -> usize {
+        self.inner.compile_queue.depth()
+    }
+
+    pub(crate) fn compile_limit(&self) -> &Arc<compile_limit::CompileLimit> {
+        &self.inner.compile_limit
+    }
+
+    /// Returns the number of compilations which are waiting for their turn
+    /// because [`Config::max_concurrent_compilations`] compilations are
+    /// already running.
+    ///
+    /// This is always zero if there's no limit.
+    pub fn compilations_waiting(&self) -> usize {
+        self.inner.compile_limit.waiting()
+    }
+
+    /// Returns the number of compilations which this engine is currently
+    /// running.
+    pub fn compilations_running(&self) -> usize {
+        self.inner.compile_limit.running()
+    }
+
     /// Ahead-of-time (AOT) compiles a WebAssembly module.
     ///
     /// The `bytes` provided must be in one of two formats:
@@ -138,17 +327,69 @@ impl Engine {
         const USE_PAGED_MEM_INIT: bool = cfg!(all(feature = "uffd", target_os = "linux"));
 
         #[cfg(feature = "wat")]
-        let bytes = wat::parse_bytes(&bytes)?;
+        let bytes = crate::module::parse_wat(&bytes)?;
 
-        let (_, artifacts, types) = wasmtime_jit::CompilationArtifacts::build(
-            &self.inner.compiler,
-            &bytes,
-            USE_PAGED_MEM_INIT,
-        )?;
+        let _slot = self.inner.compile_limit.acquire();
+        let (_, artifacts, types) = self
+            .inner
+            .compile_limit
+            .install(|| {
+                // The module is checked against the allocator of the engine
+                // that loads it, not this one.
+                wasmtime_jit::CompilationArtifacts::build(
+                    &self.inner.compiler,
+                    &bytes,
+                    USE_PAGED_MEM_INIT,
+                    &|_| Ok(()),
+                )
+            })
+            .map_err(|e| crate::module::invalid_module(e, &bytes))?;
 
         crate::module::SerializedModule::from_artifacts(&self.inner.compiler, &artifacts, &types)
             .to_bytes()
     }
+
+    /// Ahead-of-time (AOT) compiles a WebAssembly module to a relocatable
+    /// native object file.
+    ///
+    /// Unlike [`Engine::precompile_module`] the output of this method isn't
+    /// loaded with wasmtime but is instead intended to be linked into a native
+    /// program with the system linker. The `bytes` provided are in the same
+    /// formats as accepted by [`Engine::precompile_module`].
+    ///
+    /// Returns the bytes of the object file along with a JSON document
+    /// describing it. The object defines global symbols for each function
+    /// compiled:
+    ///
+    /// * `wasm_func_<N>` for the function at index `N` of the module's
+    ///   function index space.
+    /// * `wasm_trampoline_<N>` for the host-to-wasm trampoline of the type at
+    ///   index `N`.
+    /// * `wasm_export_<NAME>` for each function exported from the module, with
+    ///   every character of the export's name that isn't ASCII alphanumeric
+    ///   replaced by `_`.
+    ///
+    /// Each function takes the `VMContext` of the callee, the `VMContext` of
+    /// the caller, and then its wasm parameters. The JSON document lists the
+    /// module's imports, exports and memories, the offsets within the
+    /// `VMContext` where the runtime state they need is expected to be found,
+    /// and the offset and trap code of each trapping instruction in each
+    /// function. Calls from the compiled code to the wasmtime runtime are
+    /// left as weak undefined symbols.
+    ///
+    /// Modules which define nested modules with the module linking proposal
+    /// are not supported.
+    pub fn precompile_module_object(&self, bytes: &[u8]) -> Result<(Vec<u8>, String)> {
+        #[cfg(feature = "wat")]
+        let bytes = crate::module::parse_wat(&bytes)?;
+
+        let _slot = self.inner.compile_limit.acquire();
+        let wasmtime_jit::LinkableObject { object, metadata } = self
+            .inner
+            .compile_limit
+            .install(|| wasmtime_jit::build_linkable_object(&self.inner.compiler, &bytes))?;
+        Ok((object, metadata))
+    }
 }
 
 impl Default for Engine {
@@ -157,9 +398,14 @@ impl Default for Engine {
     }
 }
 
+fn _assert_send_sync() {
+    fn _assert<T: Send + Sync>() {}
+    _assert::<Engine>();
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{Config, Engine, Module, OptLevel};
+    use crate::{Config, Engine, Instance, Module, OptLevel, Store};
     use anyhow::Result;
     use tempfile::TempDir;
 
@@ -226,4 +472,307 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn cache_accounts_for_per_module_opt_level() -> Result<()> {
+        let td = TempDir::new()?;
+        let config_path = td.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            &format!(
+                "
+                    [cache]
+                    enabled = true
+                    directory = '{}'
+                ",
+                td.path().join("cache").display()
+            ),
+        )?;
+        let mut cfg = Config::new();
+        cfg.cranelift_opt_level(OptLevel::Speed)
+            .cache_config_load(&config_path)?;
+        let engine = Engine::new(&cfg)?;
+        let wat = "(module (func (export \"f\") (result i32) i32.const 1 i32.const 2 i32.add))";
+
+        let fast = Module::new_with_opt_level(&engine, wat, OptLevel::None)?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 1);
+        let hot = Module::new_with_opt_level(&engine, wat, OptLevel::SpeedAndSize)?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 2);
+        let default = Module::new(&engine, wat)?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 3);
+
+        // Each level is then served from its own cache entry.
+        let cached = Module::new_with_opt_level(&engine, wat, OptLevel::None)?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 1);
+        assert_eq!(engine.config().cache_config.cache_misses(), 3);
+        let explicit = Module::new_with_opt_level(&engine, wat, OptLevel::Speed)?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 2);
+        assert_eq!(engine.config().cache_config.cache_misses(), 3);
+
+        assert_eq!(fast.opt_level(), OptLevel::None);
+        assert_eq!(hot.opt_level(), OptLevel::SpeedAndSize);
+        assert_eq!(default.opt_level(), OptLevel::Speed);
+        assert_eq!(cached.opt_level(), OptLevel::None);
+        assert_eq!(explicit.opt_level(), OptLevel::Speed);
+
+        let mut store = Store::new(&engine, ());
+        for module in [&fast, &hot, &default, &cached].iter() {
+            let instance = Instance::new(&mut store, module, &[])?;
+            let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+            assert_eq!(f.call(&mut store, ())?, 3);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_functions_only_recompiles_changed_functions() -> Result<()> {
+        let td = TempDir::new()?;
+        let config_path = td.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            &format!(
+                "
+                    [cache]
+                    enabled = true
+                    directory = '{}'
+                ",
+                td.path().join("cache").display()
+            ),
+        )?;
+        let mut cfg = Config::new();
+        cfg.cache_config_load(&config_path)?.cache_functions(true);
+        let engine = Engine::new(&cfg)?;
+
+        let wat = |changed: i32| {
+            let mut wat = String::from("(module");
+            for i in 0..500 {
+                let value = if i == 250 { changed } else { i };
+                wat.push_str(&format!(
+                    "(func (export \"f{}\") (result i32) i32.const {})",
+                    i, value
+                ));
+            }
+            wat.push_str(")");
+            wat
+        };
+
+        // One module-level miss plus one miss per function.
+        Module::new(&engine, &wat(250))?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 501);
+
+        // Changing a single function misses at the module level and for that
+        // function only. The new body is a different size, so later functions
+        // are also at different offsets in the wasm binary.
+        let module = Module::new(&engine, &wat(1_000_000))?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 499);
+        assert_eq!(engine.config().cache_config.cache_misses(), 503);
+
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        for (name, expected) in [("f249", 249), ("f250", 1_000_000), ("f251", 251)].iter() {
+            let f = instance.get_typed_func::<(), i32, _>(&mut store, name)?;
+            assert_eq!(f.call(&mut store, ())?, *expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_functions_accounts_for_yieldpoints() -> Result<()> {
+        let td = TempDir::new()?;
+        let config_path = td.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            &format!(
+                "
+                    [cache]
+                    enabled = true
+                    directory = '{}'
+                ",
+                td.path().join("cache").display()
+            ),
+        )?;
+        let mut cfg = Config::new();
+        cfg.cache_config_load(&config_path)?
+            .cache_functions(true)
+            .consume_fuel(true)
+            .yieldpoints_from_custom_section(true);
+        let engine = Engine::new(&cfg)?;
+
+        let wasm = wat::parse_str("(module (func (loop (br_if 0 (i32.const 0)))))")?;
+        let mut operators = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(&wasm) {
+            if let wasmparser::Payload::CodeSectionEntry(body) = payload? {
+                let mut reader = body.get_operators_reader()?;
+                while !reader.eof() {
+                    operators.push(reader.read_with_offset()?.1 as u32);
+                }
+            }
+        }
+        let with_yieldpoint = |offset: u32| {
+            let mut section = b"\x14wasmtime.yieldpoints\x01\x00".to_vec();
+            section.push(offset as u8);
+            let mut wasm = wasm.clone();
+            wasm.push(0);
+            wasm.push(section.len() as u8);
+            wasm.extend_from_slice(&section);
+            wasm
+        };
+
+        // Moving the yieldpoint from the `loop` to the `br_if` leaves the
+        // function's body unchanged, but not its code.
+        Module::new(&engine, &with_yieldpoint(operators[0]))?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 2);
+        Module::new(&engine, &with_yieldpoint(operators[2]))?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_ignores_named_custom_sections() -> Result<()> {
+        let td = TempDir::new()?;
+        let config_path = td.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            &format!(
+                "
+                    [cache]
+                    enabled = true
+                    directory = '{}'
+                ",
+                td.path().join("cache").display()
+            ),
+        )?;
+        let mut cfg = Config::new();
+        cfg.cache_config_load(&config_path)?
+            .cache_ignore_custom_sections(&["build-info"]);
+        let engine = Engine::new(&cfg)?;
+
+        let wasm = |sections: &[(&str, &str)]| {
+            let mut wasm = wat::parse_str("(module (func (export \"f\")))").unwrap();
+            for (name, payload) in sections {
+                let len = 1 + name.len() + payload.len();
+                assert!(len < 0x80 && name.len() < 0x80);
+                wasm.push(0);
+                wasm.push(len as u8);
+                wasm.push(name.len() as u8);
+                wasm.extend_from_slice(name.as_bytes());
+                wasm.extend_from_slice(payload.as_bytes());
+            }
+            wasm
+        };
+
+        let module = Module::new(&engine, &wasm(&[("build-info", "monday")]))?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 1);
+        assert_eq!(
+            module.custom_sections("build-info").collect::<Vec<_>>(),
+            [&b"monday"[..]]
+        );
+
+        // Only the ignored section's contents differ, so this hits, but the
+        // module still reports its own section's contents.
+        let module = Module::new(&engine, &wasm(&[("build-info", "tuesday")]))?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 1);
+        assert_eq!(engine.config().cache_config.cache_misses(), 1);
+        assert_eq!(
+            module.custom_sections("build-info").collect::<Vec<_>>(),
+            [&b"tuesday"[..]]
+        );
+
+        // Other custom sections are still part of the key.
+        Module::new(&engine, &wasm(&[("build-info", "monday"), ("other", "a")]))?;
+        assert_eq!(engine.config().cache_config.cache_misses(), 2);
+        Module::new(&engine, &wasm(&[("build-info", "monday"), ("other", "b")]))?;
+        assert_eq!(engine.config().cache_config.cache_misses(), 3);
+        Module::new(&engine, &wasm(&[("build-info", "tuesday"), ("other", "b")]))?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 2);
+
+        // So is the order of sections.
+        Module::new(&engine, &wasm(&[("other", "b"), ("build-info", "monday")]))?;
+        assert_eq!(engine.config().cache_config.cache_misses(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_compiles_with_cache() -> Result<()> {
+        let td = TempDir::new()?;
+        let config_path = td.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            &format!(
+                "
+                    [cache]
+                    enabled = true
+                    directory = '{}'
+                ",
+                td.path().join("cache").display()
+            ),
+        )?;
+        let mut cfg = Config::new();
+        cfg.cache_config_load(&config_path)?;
+        let engine = Engine::new(&cfg)?;
+
+        const THREADS: i32 = 8;
+        const MODULES: i32 = 4;
+        const LEVELS: [OptLevel; 3] = [OptLevel::None, OptLevel::Speed, OptLevel::SpeedAndSize];
+        let compile = |engine: &Engine, value: i32| {
+            let wat = format!(
+                "(module (func (export \"f\") (result i32) i32.const {}))",
+                value
+            );
+            let level = LEVELS[value as usize % LEVELS.len()];
+            Module::new_with_opt_level(engine, &wat, level)
+        };
+
+        // Every thread compiles some modules that only it uses and some which
+        // every thread uses, at a mix of optimization levels.
+        let threads = (0..THREADS)
+            .map(|thread| {
+                let engine = engine.clone();
+                std::thread::spawn(move || -> Result<()> {
+                    assert!(Engine::same(&engine, &engine.clone()));
+                    let mut store = Store::new(&engine, ());
+                    for i in 0..MODULES {
+                        for value in [i, 1000 * (thread + 1) + i].iter() {
+                            let module = compile(&engine, *value)?;
+                            let instance = Instance::new(&mut store, &module, &[])?;
+                            let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+                            assert_eq!(f.call(&mut store, ())?, *value);
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+
+        let cache = &engine.config().cache_config;
+        assert_eq!(
+            cache.cache_hits() + cache.cache_misses(),
+            (THREADS * MODULES * 2) as usize
+        );
+        assert!(cache.cache_misses() >= (THREADS * MODULES + MODULES) as usize);
+
+        // Everything is in the cache now, shared modules included.
+        let misses = cache.cache_misses();
+        for i in 0..MODULES {
+            compile(&engine, i)?;
+        }
+        assert_eq!(cache.cache_misses(), misses);
+
+        assert!(!Engine::same(&engine, &Engine::new(&cfg)?));
+        Ok(())
+    }
 }
@@ -0,0 +1,398 @@
+//! The queue of modules an [`Engine`] is compiling in the background.
+
+use super::WeakEngine;
+use crate::{Engine, Module};
+use anyhow::{anyhow, bail, Result};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::{self, JoinHandle};
+
+type Callback = Box<dyn FnOnce(Result<Module>) + Send>;
+
+struct Job {
+    bytes: Vec<u8>,
+    callback: Callback,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Compilations which haven't been picked up by a thread yet.
+    jobs: VecDeque<Job>,
+    /// Number of compilations currently being run by a thread.
+    running: usize,
+    /// Number of threads waiting for a job.
+    idle: usize,
+    /// Set once the engine is dropped, telling threads to exit.
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+/// The queue of background compilations owned by an engine, along with the
+/// threads which run them.
+///
+/// Threads only hold a weak reference to the engine while they're waiting for
+/// work, so that dropping the last `Engine` drops this queue, which cancels
+/// everything still queued and joins the threads.
+#[derive(Default)]
+pub(crate) struct CompileQueue {
+    shared: Arc<Shared>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl CompileQueue {
+    pub(crate) fn push(
+        &self,
+        engine: &Engine,
+        bytes: Vec<u8>,
+        callback: Callback,
+    ) -> Result<BackgroundCompile> {
+        let config = engine.config();
+        let mut state = self.shared.state.lock().unwrap();
+        if state.jobs.len() >= config.background_compile_queue_limit {
+            bail!(
+                "background compilation queue is full ({} compilations waiting)",
+                state.jobs.len()
+            );
+        }
+
+        // Start another thread if every existing one is busy and we're still
+        // allowed to. The new thread blocks on `state` until we're done here.
+        // Threads which exited because a callback panicked no longer count.
+        if state.idle == 0 {
+            let mut threads = self.threads.lock().unwrap();
+            threads.retain(|thread| !thread.is_finished());
+            if threads.len() < config.background_compile_threads {
+                let shared = self.shared.clone();
+                let engine = engine.weak();
+                threads.push(
+                    thread::Builder::new()
+                        .name("wasmtime-compile".to_string())
+                        .spawn(move || work(shared, engine))?,
+                );
+            }
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        state.jobs.push_back(Job {
+            bytes,
+            callback,
+            cancelled: cancelled.clone(),
+        });
+        self.shared.cond.notify_one();
+        Ok(BackgroundCompile {
+            cancelled,
+            shared: Arc::downgrade(&self.shared),
+        })
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.jobs.len() + state.running
+    }
+}
+
+impl Drop for CompileQueue {
+    fn drop(&mut self) {
+        let jobs = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.shutdown = true;
+            std::mem::take(&mut state.jobs)
+        };
+        self.shared.cond.notify_all();
+        for job in jobs {
+            (job.callback)(Err(anyhow!(
+                "engine was dropped before background compilation started"
+            )));
+        }
+
+        // Threads only hold a strong reference to the engine while running a
+        // compilation, so none can be running one now, except for the current
+        // thread if it's the one dropping the engine. That one exits on its
+        // own once it sees `shutdown`.
+        let current = thread::current().id();
+        for thread in self.threads.get_mut().unwrap().drain(..) {
+            if thread.thread().id() != current {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+fn work(shared: Arc<Shared>, engine: WeakEngine) {
+    loop {
+        let job = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.shutdown {
+                    return;
+                }
+                if let Some(job) = state.jobs.pop_front() {
+                    state.running += 1;
+                    break job;
+                }
+                state.idle += 1;
+                state = shared.cond.wait(state).unwrap();
+                state.idle -= 1;
+            }
+        };
+
+        // This is declared before `engine` so that it's dropped after it,
+        // even if the callback panics.
+        let _running = Running(&shared);
+        let engine = engine.upgrade();
+        let result = match &engine {
+            _ if job.cancelled.load(SeqCst) => Err(cancelled()),
+            Some(engine) => Module::new(engine, &job.bytes),
+            None => Err(anyhow!(
+                "engine was dropped before background compilation started"
+            )),
+        };
+
+        // If the compilation was cancelled while it was running then its
+        // result is discarded.
+        let result = if job.cancelled.load(SeqCst) {
+            Err(cancelled())
+        } else {
+            result
+        };
+        (job.callback)(result);
+
+        // Note that this may be the last reference to the engine, in which
+        // case this drops the `CompileQueue`. That has to happen without
+        // holding the lock, before `_running` is dropped.
+        drop(engine);
+    }
+}
+
+/// Marks a compilation as no longer running once dropped, including when its
+/// callback panics.
+struct Running<'a>(&'a Shared);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().running -= 1;
+    }
+}
+
+fn cancelled() -> anyhow::Error {
+    anyhow!("background compilation was cancelled")
+}
+
+/// A handle to a compilation started with
+/// [`Engine::compile_in_background`].
+///
+/// Dropping this handle doesn't cancel the compilation.
+pub struct BackgroundCompile {
+    cancelled: Arc<AtomicBool>,
+    shared: Weak<Shared>,
+}
+
+impl BackgroundCompile {
+    /// Cancels this compilation.
+    ///
+    /// If the compilation is still waiting in the queue it's removed and its
+    /// callback is invoked immediately, on the current thread, with an error.
+    /// If it's already running then it runs to completion, but its result is
+    /// discarded and the callback receives an error instead. If the callback
+    /// was already invoked this does nothing.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, SeqCst);
+        let shared = match self.shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        let job = {
+            let mut state = shared.state.lock().unwrap();
+            let pos = state
+                .jobs
+                .iter()
+                .position(|job| Arc::ptr_eq(&job.cancelled, &self.cancelled));
+            pos.and_then(|pos| state.jobs.remove(pos))
+        };
+        if let Some(job) = job {
+            (job.callback)(Err(cancelled()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Engine, Instance, Module, Store};
+    use anyhow::Result;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    fn wat(n: usize) -> String {
+        format!(
+            "(module (func (export \"f\") (result i32) i32.const {}))",
+            n
+        )
+    }
+
+    fn run(module: &Module) -> Result<i32> {
+        let mut store = Store::new(module.engine(), ());
+        let instance = Instance::new(&mut store, module, &[])?;
+        let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+        Ok(f.call(&mut store, ())?)
+    }
+
+    #[test]
+    fn queue_and_cancel() -> Result<()> {
+        let mut config = Config::new();
+        config
+            .background_compile_threads(1)?
+            .background_compile_queue_limit(3)?;
+        let engine = Engine::new(&config)?;
+        let (results_tx, results) = mpsc::channel();
+        let (started_tx, started) = mpsc::channel();
+        let (gate, gate_rx) = mpsc::channel::<()>();
+
+        // Block the only thread in the first compilation's callback so that
+        // the rest stay queued.
+        let tx = results_tx.clone();
+        engine.compile_in_background(wat(0), move |module| {
+            started_tx.send(()).unwrap();
+            gate_rx.recv().unwrap();
+            tx.send((0, module)).unwrap();
+        })?;
+        started.recv()?;
+
+        let mut handles = Vec::new();
+        for i in 1..4 {
+            let tx = results_tx.clone();
+            handles.push(engine.compile_in_background(wat(i), move |module| {
+                tx.send((i, module)).unwrap();
+            })?);
+        }
+        let err = engine
+            .compile_in_background(wat(4), |_| panic!("shouldn't be called"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("queue is full"), "{}", err);
+        assert_eq!(engine.background_compile_queue_depth(), 4);
+
+        // Cancelling a queued compilation invokes its callback right away.
+        handles[1].cancel();
+        let (i, module) = results.try_recv()?;
+        assert_eq!(i, 2);
+        let err = module.err().unwrap();
+        assert!(err.to_string().contains("cancelled"), "{}", err);
+        assert_eq!(engine.background_compile_queue_depth(), 3);
+
+        gate.send(()).unwrap();
+        let mut finished = Vec::new();
+        for _ in 0..3 {
+            let (i, module) = results.recv()?;
+            assert_eq!(run(&module?)?, i as i32);
+            finished.push(i);
+        }
+        finished.sort();
+        assert_eq!(finished, [0, 1, 3]);
+
+        // Cancelling after completion does nothing.
+        handles[0].cancel();
+        assert!(results.try_recv().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn panicking_callback() -> Result<()> {
+        let mut config = Config::new();
+        config.background_compile_threads(1)?;
+        let engine = Engine::new(&config)?;
+        engine.compile_in_background(wat(0), |_| panic!("callback panicked"))?;
+
+        // The compilation no longer counts once its callback has panicked.
+        let start = Instant::now();
+        while engine.background_compile_queue_depth() != 0 {
+            assert!(start.elapsed() < Duration::from_secs(10), "still running");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // And the thread it killed is replaced.
+        let (tx, results) = mpsc::channel();
+        engine.compile_in_background(wat(1), move |module| {
+            tx.send(module).unwrap();
+        })?;
+        let module = results.recv_timeout(Duration::from_secs(10))??;
+        assert_eq!(run(&module)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn drop_engine_joins_threads() -> Result<()> {
+        let mut config = Config::new();
+        config.background_compile_threads(2)?;
+        let engine = Engine::new(&config)?;
+        let (tx, results) = mpsc::channel();
+        let handle = engine.compile_in_background(wat(0), move |module| {
+            tx.send(module.map(|_| ())).unwrap();
+        })?;
+        results.recv()??;
+        while engine.background_compile_queue_depth() != 0 {
+            std::thread::yield_now();
+        }
+
+        // With the threads idle, dropping the engine joins them before
+        // returning.
+        drop(engine);
+        assert!(handle.shared.upgrade().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn drop_engine_with_jobs_in_flight() -> Result<()> {
+        let mut config = Config::new();
+        config.background_compile_threads(2)?;
+        let engine = Engine::new(&config)?;
+        let (results_tx, results) = mpsc::channel();
+        let (started_tx, started) = mpsc::channel();
+        let (gate, gate_rx) = mpsc::channel::<()>();
+
+        let tx = results_tx.clone();
+        let handle = engine.compile_in_background(wat(0), move |module| {
+            started_tx.send(()).unwrap();
+            gate_rx.recv().unwrap();
+            tx.send(module.map(|_| ())).unwrap();
+        })?;
+        started.recv()?;
+        for i in 1..8 {
+            let tx = results_tx.clone();
+            engine.compile_in_background(wat(i), move |module| {
+                tx.send(module.map(|_| ())).unwrap();
+            })?;
+        }
+        drop(results_tx);
+
+        // The blocked thread still holds a reference to the engine, so this
+        // doesn't drop it. Once it's released that thread drops the engine,
+        // cancelling whatever hasn't started yet.
+        drop(engine);
+        gate.send(()).unwrap();
+
+        // Every callback is invoked exactly once, after which the channel is
+        // closed.
+        let results = results.iter().collect::<Vec<_>>();
+        assert_eq!(results.len(), 8);
+        for result in results {
+            if let Err(e) = result {
+                assert!(e.to_string().contains("engine was dropped"), "{}", e);
+            }
+        }
+
+        // And all of the threads exit.
+        let start = Instant::now();
+        while handle.shared.upgrade().is_some() {
+            assert!(start.elapsed() < Duration::from_secs(10), "threads leaked");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,455 @@
+//! The limit on the number of compilations an [`Engine`](crate::Engine) runs
+//! at once, configured with
+//! [`Config::max_concurrent_compilations`](crate::Config::max_concurrent_compilations).
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+#[derive(Default)]
+struct State {
+    /// Number of compilations holding a slot.
+    running: usize,
+    /// Compilations waiting for a slot, in the order they arrived.
+    waiting: VecDeque<Waiter>,
+    next_ticket: u64,
+}
+
+struct Waiter {
+    ticket: u64,
+    /// Set for compilations waiting asynchronously, which are woken when they
+    /// reach the front of the queue.
+    #[cfg(feature = "async")]
+    waker: Option<Waker>,
+}
+
+impl State {
+    fn enqueue(&mut self) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.waiting.push_back(Waiter {
+            ticket,
+            #[cfg(feature = "async")]
+            waker: None,
+        });
+        ticket
+    }
+
+    /// Takes a slot for the compilation with `ticket` if it's first in line
+    /// and one is free.
+    fn try_take(&mut self, max: usize, ticket: u64) -> bool {
+        if self.running >= max || self.waiting.front().map(|w| w.ticket) != Some(ticket) {
+            return false;
+        }
+        self.waiting.pop_front();
+        self.running += 1;
+        true
+    }
+}
+
+/// The slots for running compilations owned by an engine, and the thread pool
+/// their functions are compiled on if there's a limit.
+pub(crate) struct CompileLimit {
+    max: usize,
+    state: Mutex<State>,
+    cond: Condvar,
+    #[cfg(feature = "parallel-compilation")]
+    pool: Option<rayon::ThreadPool>,
+}
+
+impl CompileLimit {
+    pub(crate) fn new(max: Option<usize>) -> Result<CompileLimit> {
+        Ok(CompileLimit {
+            max: max.unwrap_or(usize::MAX),
+            state: Default::default(),
+            cond: Condvar::new(),
+            #[cfg(feature = "parallel-compilation")]
+            pool: match max {
+                Some(max) => Some(
+                    rayon::ThreadPoolBuilder::new()
+                        .num_threads(max)
+                        .thread_name(|i| format!("wasmtime-compile-worker-{}", i))
+                        .build()?,
+                ),
+                None => None,
+            },
+        })
+    }
+
+    /// Blocks the current thread until a slot is free, and takes it.
+    pub(crate) fn acquire(self: &Arc<Self>) -> CompileSlot {
+        let mut state = self.state.lock().unwrap();
+        let ticket = state.enqueue();
+        while !state.try_take(self.max, ticket) {
+            state = self.cond.wait(state).unwrap();
+        }
+        self.notify(&state);
+        CompileSlot {
+            limit: self.clone(),
+        }
+    }
+
+    /// Returns a future which resolves to a slot once one is free.
+    #[cfg(feature = "async")]
+    pub(crate) fn acquire_async(self: &Arc<Self>) -> AcquireSlot {
+        AcquireSlot {
+            limit: self.clone(),
+            ticket: None,
+        }
+    }
+
+    /// Runs `f`, which compiles a module, such that the functions compiled in
+    /// parallel by it use the engine's pool, if it has one.
+    pub(crate) fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        #[cfg(feature = "parallel-compilation")]
+        {
+            if let Some(pool) = &self.pool {
+                return pool.install(f);
+            }
+        }
+        f()
+    }
+
+    /// Runs `f` on another thread, the engine's pool if it has one, and
+    /// returns a future which resolves to its result.
+    #[cfg(feature = "async")]
+    pub(crate) fn spawn<R: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> R + Send + 'static,
+    ) -> Spawned<R> {
+        let shared = Arc::new(Mutex::new(SpawnedState {
+            result: None,
+            waker: None,
+        }));
+        let job = {
+            let shared = shared.clone();
+            move || {
+                // Panics are forwarded to the future rather than unwinding
+                // out of a pool thread, which would abort the process.
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+                let mut shared = shared.lock().unwrap();
+                shared.result = Some(result);
+                if let Some(waker) = shared.waker.take() {
+                    waker.wake();
+                }
+            }
+        };
+
+        #[cfg(feature = "parallel-compilation")]
+        {
+            if let Some(pool) = &self.pool {
+                pool.spawn(job);
+                return Spawned(shared);
+            }
+        }
+        std::thread::Builder::new()
+            .name("wasmtime-compile".to_string())
+            .spawn(job)
+            .expect("failed to start a compilation thread");
+        Spawned(shared)
+    }
+
+    pub(crate) fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+
+    pub(crate) fn waiting(&self) -> usize {
+        self.state.lock().unwrap().waiting.len()
+    }
+
+    /// Lets the compilation now at the front of the queue check whether it
+    /// can take a slot.
+    #[cfg_attr(not(feature = "async"), allow(unused_variables))]
+    fn notify(&self, state: &State) {
+        self.cond.notify_all();
+        #[cfg(feature = "async")]
+        {
+            if let Some(waker) = state.waiting.front().and_then(|w| w.waker.as_ref()) {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+/// A compilation's slot, which is given back when this is dropped.
+pub(crate) struct CompileSlot {
+    limit: Arc<CompileLimit>,
+}
+
+impl Drop for CompileSlot {
+    fn drop(&mut self) {
+        let mut state = self.limit.state.lock().unwrap();
+        state.running -= 1;
+        self.limit.notify(&state);
+    }
+}
+
+/// The future returned by [`CompileLimit::acquire_async`].
+///
+/// Dropping it before it resolves gives up its place in the queue.
+#[cfg(feature = "async")]
+pub(crate) struct AcquireSlot {
+    limit: Arc<CompileLimit>,
+    ticket: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl Future for AcquireSlot {
+    type Output = CompileSlot;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<CompileSlot> {
+        let limit = self.limit.clone();
+        let mut state = limit.state.lock().unwrap();
+        let ticket = match self.ticket {
+            Some(ticket) => ticket,
+            None => {
+                let ticket = state.enqueue();
+                self.ticket = Some(ticket);
+                ticket
+            }
+        };
+        if state.try_take(limit.max, ticket) {
+            self.ticket = None;
+            limit.notify(&state);
+            return Poll::Ready(CompileSlot {
+                limit: limit.clone(),
+            });
+        }
+        let waiter = state
+            .waiting
+            .iter_mut()
+            .find(|w| w.ticket == ticket)
+            .expect("queued compilation is missing");
+        waiter.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for AcquireSlot {
+    fn drop(&mut self) {
+        let ticket = match self.ticket {
+            Some(ticket) => ticket,
+            None => return,
+        };
+        let mut state = self.limit.state.lock().unwrap();
+        state.waiting.retain(|w| w.ticket != ticket);
+        self.limit.notify(&state);
+    }
+}
+
+#[cfg(feature = "async")]
+struct SpawnedState<R> {
+    result: Option<std::thread::Result<R>>,
+    waker: Option<Waker>,
+}
+
+/// The future returned by [`CompileLimit::spawn`].
+#[cfg(feature = "async")]
+pub(crate) struct Spawned<R>(Arc<Mutex<SpawnedState<R>>>);
+
+#[cfg(feature = "async")]
+impl<R> Future for Spawned<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut shared = self.0.lock().unwrap();
+        match shared.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompileLimit;
+    use crate::{Config, Engine, Module};
+    use anyhow::Result;
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+
+    /// Spins until `f` returns `true`.
+    fn wait_until(f: impl Fn() -> bool) {
+        while !f() {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn slots_are_taken_in_order() {
+        let limit = Arc::new(CompileLimit::new(Some(1)).unwrap());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let first = limit.acquire();
+
+        let mut threads = Vec::new();
+        let mut gates = Vec::new();
+        for &name in ["a", "b", "c"].iter() {
+            let (gate, gate_rx) = mpsc::channel::<()>();
+            let thread_limit = limit.clone();
+            let log = log.clone();
+            threads.push(thread::spawn(move || {
+                let _slot = thread_limit.acquire();
+                log.lock().unwrap().push(name);
+                gate_rx.recv().unwrap();
+            }));
+            gates.push(gate);
+            let queued = threads.len();
+            wait_until(|| limit.waiting() == queued);
+        }
+        assert_eq!(limit.running(), 1);
+
+        // Each compilation only starts once the one before it finishes.
+        drop(first);
+        for (i, gate) in gates.into_iter().enumerate() {
+            wait_until(|| log.lock().unwrap().len() == i + 1);
+            assert_eq!(limit.running(), 1);
+            assert_eq!(limit.waiting(), 2 - i);
+            gate.send(()).unwrap();
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*log.lock().unwrap(), ["a", "b", "c"]);
+        assert_eq!(limit.running(), 0);
+    }
+
+    #[test]
+    fn concurrent_compilations_are_serialized() -> Result<()> {
+        let mut config = Config::new();
+        config.max_concurrent_compilations(1)?;
+        let engine = Engine::new(&config)?;
+
+        // Hold the only slot so that both compilations queue up behind it.
+        let slot = engine.compile_limit().acquire();
+        let threads = (0..2)
+            .map(|_| {
+                let engine = engine.clone();
+                thread::spawn(move || Module::new(&engine, "(module (func))").map(|_| ()))
+            })
+            .collect::<Vec<_>>();
+        wait_until(|| engine.compilations_waiting() == 2);
+
+        drop(slot);
+        let (done_tx, done) = mpsc::channel();
+        let watcher = {
+            let engine = engine.clone();
+            thread::spawn(move || {
+                let mut max = 0;
+                while done.try_recv().is_err() {
+                    max = max.max(engine.compilations_running());
+                }
+                max
+            })
+        };
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        done_tx.send(()).unwrap();
+        assert!(watcher.join().unwrap() <= 1);
+        assert_eq!(engine.compilations_waiting(), 0);
+        assert_eq!(engine.compilations_running(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn unlimited_by_default() -> Result<()> {
+        let engine = Engine::default();
+        let slots = (0..16)
+            .map(|_| engine.compile_limit().acquire())
+            .collect::<Vec<_>>();
+        assert_eq!(engine.compilations_running(), 16);
+        Module::new(&engine, "(module)")?;
+        drop(slots);
+        assert_eq!(engine.compilations_running(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_is_rejected() {
+        assert!(Config::new().max_concurrent_compilations(0).is_err());
+    }
+
+    #[cfg(feature = "async")]
+    mod not_blocking {
+        use super::*;
+        use std::future::Future;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::task::{Context, Poll, Wake, Waker};
+
+        #[derive(Default)]
+        struct CountingWaker {
+            wakes: AtomicUsize,
+        }
+
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.wakes.fetch_add(1, SeqCst);
+            }
+        }
+
+        #[test]
+        fn new_async_waits_without_blocking() -> Result<()> {
+            let mut config = Config::new();
+            config.max_concurrent_compilations(1)?;
+            let engine = Engine::new(&config)?;
+            let counter = Arc::new(CountingWaker::default());
+            let waker = Waker::from(counter.clone());
+            let mut cx = Context::from_waker(&waker);
+
+            // With the slot taken, polling returns right away on this thread
+            // and leaves the compilation queued.
+            let slot = engine.compile_limit().acquire();
+            let mut future = Box::pin(Module::new_async(&engine, "(module (func))"));
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(engine.compilations_waiting(), 1);
+            assert_eq!(counter.wakes.load(SeqCst), 0);
+
+            // Freeing the slot wakes the future, which then compiles on
+            // another thread and wakes it again when it's done.
+            drop(slot);
+            assert_eq!(counter.wakes.load(SeqCst), 1);
+            let module = loop {
+                match future.as_mut().poll(&mut cx) {
+                    Poll::Ready(module) => break module?,
+                    Poll::Pending => thread::yield_now(),
+                }
+            };
+            assert!(Engine::same(module.engine(), &engine));
+            assert_eq!(engine.compilations_running(), 0);
+            Ok(())
+        }
+
+        #[test]
+        fn dropping_queued_future_gives_up_its_place() -> Result<()> {
+            let mut config = Config::new();
+            config.max_concurrent_compilations(1)?;
+            let engine = Engine::new(&config)?;
+            let waker = Waker::from(Arc::new(CountingWaker::default()));
+            let mut cx = Context::from_waker(&waker);
+
+            let slot = engine.compile_limit().acquire();
+            let mut future = Box::pin(Module::new_async(&engine, "(module)"));
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(engine.compilations_waiting(), 1);
+            drop(future);
+            assert_eq!(engine.compilations_waiting(), 0);
+
+            // The next compilation doesn't wait for the abandoned one.
+            drop(slot);
+            Module::new(&engine, "(module)")?;
+            Ok(())
+        }
+    }
+}
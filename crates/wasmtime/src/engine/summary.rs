@@ -0,0 +1,181 @@
+//! A plain-data description of the configuration an [`Engine`] is using.
+
+use crate::{Engine, InstanceAllocationStrategy, OptLevel};
+use std::collections::BTreeMap;
+use wasmtime_environ::settings::Value;
+use wasmtime_jit::CompilationStrategy;
+
+/// The configuration an [`Engine`] is actually using, as returned by
+/// [`Engine::config_summary`].
+///
+/// This is meant to be logged at startup or attached to bug reports, so it's
+/// all plain data which can be printed with `{:#?}`, or serialized with
+/// `serde` when the `serde-config-summary` feature is enabled. Unlike the
+/// [`Debug`](std::fmt::Debug) output of a [`Config`](crate::Config) this
+/// describes what the engine resolved the configuration to: defaults are
+/// filled in, `Strategy::Auto` is replaced by the strategy which was picked,
+/// settings which the instance allocator overrides are reported as
+/// overridden, and the ISA flags are those of the target the engine compiles
+/// for, including ones which were detected from the host.
+///
+/// More fields may be added to this in the future.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-config-summary", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct ConfigSummary {
+    /// The version of Wasmtime the engine is from.
+    pub wasmtime_version: String,
+    /// The target triple code is compiled for.
+    pub target: String,
+    /// The code generator used for modules, either `cranelift` or
+    /// `lightbeam`.
+    pub strategy: String,
+    /// The optimization level modules are compiled at by default.
+    pub opt_level: OptLevel,
+    /// Cranelift's target-independent settings, by name.
+    pub flags: BTreeMap<String, String>,
+    /// Cranelift's settings for the target ISA, by name.
+    pub isa_flags: BTreeMap<String, String>,
+    /// Whether each WebAssembly proposal is enabled, by the name of its field
+    /// in `wasmparser::WasmFeatures`.
+    pub wasm_features: BTreeMap<String, bool>,
+    /// The size, in bytes, of the address space reserved for static memories.
+    pub static_memory_maximum_size: u64,
+    /// Whether memories without a maximum size are treated as static
+    /// memories of `static_memory_maximum_size` bytes, which the pooling
+    /// allocator always does.
+    pub static_memory_bound_is_maximum: bool,
+    /// The size, in bytes, of the guard region after static memories.
+    pub static_memory_guard_size: u64,
+    /// The size, in bytes, of the guard region after dynamic memories.
+    pub dynamic_memory_guard_size: u64,
+    /// Whether memories also have a guard region before them.
+    pub guard_before_linear_memory: bool,
+    /// The size, in bytes, of the guard region before static memories, or 0
+    /// if there is none.
+    pub static_memory_guard_before_size: u64,
+    /// The size, in bytes, of the guard region before dynamic memories, or 0
+    /// if there is none.
+    pub dynamic_memory_guard_before_size: u64,
+    /// Whether wasm code can be interrupted.
+    pub interruptable: bool,
+    /// Whether wasm code consumes fuel.
+    pub consume_fuel: bool,
+    /// Whether wasm code can be interrupted by the engine's epoch reaching a
+    /// store's deadline.
+    pub epoch_interruption: bool,
+    /// Whether native DWARF debug information is generated for JIT code.
+    pub debug_info: bool,
+    /// Whether the DWARF debug information of wasm modules is parsed.
+    pub parse_wasm_debuginfo: bool,
+    /// The maximum amount of stack, in bytes, that wasm code may use.
+    pub max_wasm_stack: usize,
+    /// Whether the engine supports async stores.
+    pub async_support: bool,
+    /// The instance allocation strategy, either `on-demand` or `pooling`.
+    pub allocation_strategy: String,
+    /// Whether compiled modules are cached on disk.
+    pub cache_enabled: bool,
+}
+
+impl ConfigSummary {
+    pub(crate) fn new(engine: &Engine) -> ConfigSummary {
+        let config = engine.config();
+        let compiler = engine.compiler();
+        let isa = compiler.isa();
+        let tunables = compiler.tunables();
+        let features = compiler.features();
+        // This matches the size `MemoryPlan::for_memory` picks for a memory
+        // whose guard region after it is `offset_guard_size` bytes.
+        let guard_before = |offset_guard_size| {
+            if tunables.guard_before_linear_memory {
+                tunables
+                    .guard_before_linear_memory_size
+                    .unwrap_or(offset_guard_size)
+            } else {
+                0
+            }
+        };
+
+        let wasmparser::WasmFeatures {
+            reference_types,
+            multi_value,
+            bulk_memory,
+            module_linking,
+            simd,
+            threads,
+            tail_call,
+            deterministic_only,
+            multi_memory,
+            exceptions,
+            memory64,
+        } = *features;
+        let wasm_features = [
+            ("reference_types", reference_types),
+            ("multi_value", multi_value),
+            ("bulk_memory", bulk_memory),
+            ("module_linking", module_linking),
+            ("simd", simd),
+            ("threads", threads),
+            ("tail_call", tail_call),
+            ("deterministic_only", deterministic_only),
+            ("multi_memory", multi_memory),
+            ("exceptions", exceptions),
+            ("memory64", memory64),
+        ];
+
+        ConfigSummary {
+            wasmtime_version: env!("CARGO_PKG_VERSION").to_string(),
+            target: isa.triple().to_string(),
+            strategy: match compiler.strategy() {
+                CompilationStrategy::Auto | CompilationStrategy::Cranelift => "cranelift",
+                #[cfg(feature = "lightbeam")]
+                CompilationStrategy::Lightbeam => "lightbeam",
+            }
+            .to_string(),
+            opt_level: engine.opt_level(),
+            flags: isa.flags().iter().map(flag).collect(),
+            isa_flags: isa.isa_flags().into_iter().map(flag).collect(),
+            wasm_features: wasm_features
+                .iter()
+                .map(|(name, enabled)| (name.to_string(), *enabled))
+                .collect(),
+            static_memory_maximum_size: u64::from(tunables.static_memory_bound)
+                * u64::from(wasmtime_environ::WASM_PAGE_SIZE),
+            static_memory_bound_is_maximum: tunables.static_memory_bound_is_maximum,
+            static_memory_guard_size: tunables.static_memory_offset_guard_size,
+            dynamic_memory_guard_size: tunables.dynamic_memory_offset_guard_size,
+            guard_before_linear_memory: tunables.guard_before_linear_memory,
+            static_memory_guard_before_size: guard_before(tunables.static_memory_offset_guard_size),
+            dynamic_memory_guard_before_size: guard_before(
+                tunables.dynamic_memory_offset_guard_size,
+            ),
+            interruptable: tunables.interruptable,
+            consume_fuel: tunables.consume_fuel,
+            epoch_interruption: tunables.epoch_interruption,
+            debug_info: tunables.generate_native_debuginfo,
+            parse_wasm_debuginfo: tunables.parse_wasm_debuginfo,
+            max_wasm_stack: config.max_wasm_stack,
+            async_support: config.async_support,
+            allocation_strategy: match config.allocation_strategy {
+                InstanceAllocationStrategy::OnDemand => "on-demand",
+                InstanceAllocationStrategy::Pooling { .. } => "pooling",
+            }
+            .to_string(),
+            #[cfg(feature = "cache")]
+            cache_enabled: config.cache_config.enabled(),
+            #[cfg(not(feature = "cache"))]
+            cache_enabled: false,
+        }
+    }
+}
+
+fn flag(value: Value) -> (String, String) {
+    let string = match (value.as_enum(), value.as_num(), value.as_bool()) {
+        (Some(variant), _, _) => variant.to_string(),
+        (_, Some(num), _) => num.to_string(),
+        (_, _, Some(enabled)) => enabled.to_string(),
+        _ => String::new(),
+    };
+    (value.name.to_string(), string)
+}
@@ -0,0 +1,170 @@
+use crate::Trap;
+use std::fmt;
+use wasmtime_environ::wasm::WasmError;
+use wasmtime_environ::CompileError;
+use wasmtime_jit::SetupError;
+use wasmtime_runtime::InstantiationError;
+
+/// The classification of an error from creating a [`Module`](crate::Module)
+/// or an [`Instance`](crate::Instance).
+///
+/// [`Module::new`](crate::Module::new),
+/// [`Module::deserialize`](crate::Module::deserialize),
+/// [`Instance::new`](crate::Instance::new),
+/// [`Linker::instantiate`](crate::Linker::instantiate) and the functions
+/// related to them attach this as context to the `anyhow::Error` they return,
+/// and its [`ErrorKind`] tells an embedder whether to blame the module, the
+/// host or the environment without having to match on messages.
+///
+/// Since this is only context, the error it was attached to, such as a
+/// [`Trap`] or an [`InvalidModule`](crate::InvalidModule), can still be
+/// recovered with `anyhow::Error::downcast` and friends. An `Error` displays
+/// as the message of the error it classifies, so attaching it doesn't change
+/// what the returned error prints.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let engine = Engine::default();
+/// let module = Module::new(&engine, r#"(module (import "" "" (func)))"#)?;
+/// let mut store = Store::new(&engine, ());
+/// let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+/// let error = err.downcast_ref::<wasmtime::Error>().unwrap();
+/// assert!(matches!(error.kind(), ErrorKind::IncompatibleImport));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+}
+
+/// What went wrong to cause an [`Error`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The module is malformed, fails validation, or uses a feature which
+    /// isn't enabled in the [`Config`](crate::Config).
+    Validation,
+    /// The module is valid but couldn't be compiled, for example because it
+    /// exceeds a limit of the compiler or uses a construct it doesn't support.
+    Compilation,
+    /// The module is valid but exceeds a limit on modules configured for the
+    /// engine, such as the maximum size of its memories and tables or the
+    /// maximum number of parameters and results of its function types.
+    Limit,
+    /// Memory or another resource couldn't be allocated, or a limit
+    /// configured for the engine, the instance allocator or the store was
+    /// reached.
+    ResourceExhausted,
+    /// An import is missing or doesn't have the type the module expects.
+    IncompatibleImport,
+    /// A trap occurred while the instance was being initialized, either while
+    /// initializing its memories and tables or in its start function.
+    StartTrap(Trap),
+    /// A file couldn't be read.
+    Io,
+    /// A precompiled module is corrupt, or was produced by a different version
+    /// of Wasmtime or with settings which don't match the engine's.
+    IncompatibleArtifact,
+    /// The API was misused, for example by instantiating with imports which
+    /// belong to another store.
+    Usage,
+}
+
+impl Error {
+    /// Attaches an [`Error`] of the given `kind` to `error` as context.
+    pub(crate) fn new(kind: ErrorKind, error: impl Into<anyhow::Error>) -> anyhow::Error {
+        let error = error.into();
+        let message = error.to_string();
+        error.context(Error { kind, message })
+    }
+
+    /// Creates an error of the given `kind` which has nothing else to it than
+    /// `message`.
+    pub(crate) fn msg(kind: ErrorKind, message: impl fmt::Display) -> anyhow::Error {
+        anyhow::Error::new(Error {
+            kind,
+            message: message.to_string(),
+        })
+    }
+
+    /// Attaches an [`Error`] of the given `kind` to `error` as context which
+    /// displays as `message`, like `anyhow::Context::context` would.
+    pub(crate) fn context(
+        kind: ErrorKind,
+        error: anyhow::Error,
+        message: impl fmt::Display,
+    ) -> anyhow::Error {
+        error.context(Error {
+            kind,
+            message: message.to_string(),
+        })
+    }
+
+    /// Same as [`Error::new`], unless `error` has been classified already.
+    pub(crate) fn classify(error: anyhow::Error, kind: ErrorKind) -> anyhow::Error {
+        if error.is::<Error>() {
+            error
+        } else {
+            Error::new(kind, error)
+        }
+    }
+
+    pub(crate) fn from_setup_error(error: SetupError) -> anyhow::Error {
+        Error::new(setup_error_kind(&error), error)
+    }
+
+    pub(crate) fn from_instantiation_error(error: InstantiationError) -> anyhow::Error {
+        match error {
+            InstantiationError::Trap(trap) => Error::start_trap(Trap::from_runtime(trap)),
+            InstantiationError::Resource(_) | InstantiationError::Limit(_) => {
+                Error::new(ErrorKind::ResourceExhausted, error)
+            }
+            InstantiationError::Link(_) => Error::new(ErrorKind::IncompatibleImport, error),
+        }
+    }
+
+    pub(crate) fn start_trap(trap: Trap) -> anyhow::Error {
+        Error::new(ErrorKind::StartTrap(trap.clone()), trap)
+    }
+
+    /// Returns what went wrong to cause this error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Returns the kind of error which compiling a module failed with.
+pub(crate) fn setup_error_kind(error: &SetupError) -> ErrorKind {
+    match error {
+        SetupError::Validate(_)
+        | SetupError::Compile(CompileError::Wasm(WasmError::InvalidWebAssembly { .. })) => {
+            ErrorKind::Validation
+        }
+        // The only user errors of translation are from the limits on modules.
+        SetupError::Compile(CompileError::Wasm(WasmError::User(_))) => ErrorKind::Limit,
+        SetupError::Compile(CompileError::Wasm(WasmError::Unsupported(_)))
+        | SetupError::Compile(CompileError::Wasm(WasmError::ImplLimitExceeded))
+        | SetupError::Compile(CompileError::Codegen(_))
+        | SetupError::Compile(CompileError::DebugInfoNotSupported)
+        | SetupError::DebugInfo(_) => ErrorKind::Compilation,
+        SetupError::Instantiate(InstantiationError::Resource(_))
+        | SetupError::Instantiate(InstantiationError::Limit(_))
+        | SetupError::Allocator(_) => ErrorKind::ResourceExhausted,
+        SetupError::Instantiate(InstantiationError::Link(_)) => ErrorKind::IncompatibleImport,
+        // No wasm runs while compiling, so this isn't expected to happen.
+        SetupError::Instantiate(InstantiationError::Trap(_)) => ErrorKind::Compilation,
+    }
+}
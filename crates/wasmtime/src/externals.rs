@@ -1,9 +1,9 @@
-use crate::store::{StoreData, StoreOpaque, Stored};
+use crate::store::{StoreData, StoreId, StoreOpaque, Stored};
 use crate::trampoline::{generate_global_export, generate_table_export};
-use crate::values::{from_checked_anyfunc, into_checked_anyfunc};
+use crate::values::from_checked_anyfunc;
 use crate::{
     AsContext, AsContextMut, ExternRef, ExternType, Func, GlobalType, Instance, Memory, Module,
-    Mutability, TableType, Trap, Val, ValType,
+    Mutability, TableType, Trap, Val, ValError, ValSite, ValType,
 };
 use anyhow::{anyhow, bail, Result};
 use std::mem;
@@ -137,6 +137,19 @@ impl Extern {
         }
     }
 
+    /// Returns the identifier of the [`Store`](crate::Store) which owns this
+    /// item, or `None` for modules, which don't belong to any store.
+    pub fn store_id(&self) -> Option<StoreId> {
+        match self {
+            Extern::Func(f) => Some(f.store_id()),
+            Extern::Global(g) => Some(g.store_id()),
+            Extern::Memory(m) => Some(m.store_id()),
+            Extern::Table(t) => Some(t.store_id()),
+            Extern::Instance(i) => Some(i.store_id()),
+            Extern::Module(_) => None,
+        }
+    }
+
     pub(crate) fn comes_from_same_store(&self, store: &StoreOpaque<'_>) -> bool {
         match self {
             Extern::Func(f) => f.comes_from_same_store(store),
@@ -275,6 +288,12 @@ impl Global {
         }
     }
 
+    /// Returns the identifier of the [`Store`](crate::Store) which owns this
+    /// global.
+    pub fn store_id(&self) -> StoreId {
+        self.0.store_id()
+    }
+
     /// Returns the underlying type of this `global`.
     ///
     /// # Panics
@@ -300,6 +319,7 @@ impl Global {
                 ValType::I64 => Val::from(*definition.as_i64()),
                 ValType::F32 => Val::F32(*definition.as_u32()),
                 ValType::F64 => Val::F64(*definition.as_u64()),
+                ValType::V128 => Val::V128(*definition.as_u128()),
                 ValType::ExternRef => Val::ExternRef(
                     definition
                         .as_externref()
@@ -309,7 +329,6 @@ impl Global {
                 ValType::FuncRef => {
                     from_checked_anyfunc(definition.as_anyfunc() as *mut _, &mut store.opaque())
                 }
-                ty => unimplemented!("Global::get for {:?}", ty),
             }
         }
     }
@@ -321,6 +340,7 @@ impl Global {
     /// Returns an error if this global has a different type than `Val`, if
     /// it's not a mutable global, or if `val` comes from a different store than
     /// the one provided.
+    /// The error can be downcast to a [`ValError`] describing which.
     ///
     /// # Panics
     ///
@@ -329,15 +349,20 @@ impl Global {
         let store = store.as_context_mut();
         let ty = self.ty(&store);
         if ty.mutability() != Mutability::Var {
-            bail!("immutable global cannot be set");
+            return Err(ValError::ImmutableGlobal.into());
         }
         let ty = ty.content();
         if val.ty() != *ty {
-            bail!("global of type {:?} cannot be set to {:?}", ty, val.ty());
+            return Err(ValError::TypeMismatch {
+                site: ValSite::Global,
+                expected: ty.clone(),
+                found: val.ty(),
+            }
+            .into());
         }
         let mut store = store.opaque();
         if !val.comes_from_same_store(&store) {
-            bail!("cross-`Store` values are not supported");
+            return Err(ValError::CrossStore.into());
         }
         unsafe {
             let definition = &mut *store[self.0].definition;
@@ -346,6 +371,7 @@ impl Global {
                 Val::I64(i) => *definition.as_i64_mut() = i,
                 Val::F32(f) => *definition.as_u32_mut() = f,
                 Val::F64(f) => *definition.as_u64_mut() = f,
+                Val::V128(x) => *definition.as_u128_mut() = x,
                 Val::FuncRef(f) => {
                     *definition.as_anyfunc_mut() = f.map_or(ptr::null(), |f| {
                         f.caller_checked_anyfunc(&mut store).as_ptr() as *const _
@@ -355,7 +381,6 @@ impl Global {
                     let old = mem::replace(definition.as_externref_mut(), x.map(|x| x.inner));
                     drop(old);
                 }
-                _ => unimplemented!("Global::set for {:?}", val.ty()),
             }
         }
         Ok(())
@@ -375,6 +400,21 @@ impl Global {
         &data[self.0].global
     }
 
+    /// Reads the value of this global if it's a number, without needing
+    /// mutable access to the store as reading references does.
+    pub(crate) fn get_number(&self, data: &StoreData) -> Option<Val> {
+        unsafe {
+            let definition = &*data[self.0].definition;
+            match GlobalType::from_wasmtime_global(&data[self.0].global).content() {
+                ValType::I32 => Some(Val::from(*definition.as_i32())),
+                ValType::I64 => Some(Val::from(*definition.as_i64())),
+                ValType::F32 => Some(Val::F32(*definition.as_u32())),
+                ValType::F64 => Some(Val::F64(*definition.as_u64())),
+                _ => None,
+            }
+        }
+    }
+
     pub(crate) fn vmimport(&self, store: &StoreOpaque<'_>) -> wasmtime_runtime::VMGlobalImport {
         wasmtime_runtime::VMGlobalImport {
             from: store[self.0].definition,
@@ -406,10 +446,25 @@ impl Table {
     /// * `init` - the initial value to fill all table entries with, if the
     ///   table starts with an initial size.
     ///
+    /// The null reference of a `funcref` table is `Val::FuncRef(None)` and
+    /// that of an `externref` table is `Val::ExternRef(None)`. Calling a null
+    /// `funcref` with `call_indirect` traps with
+    /// [`TrapCode::IndirectCallToNull`](crate::TrapCode::IndirectCallToNull).
+    ///
     /// # Errors
     ///
     /// Returns an error if `init` does not match the element type of the table,
-    /// or if `init` does not belong to the `store` provided.
+    /// or if `init` does not belong to the `store` provided. Values only match
+    /// the element type of a table if [`Val::ty`] is exactly that type, so for
+    /// example neither `Val::I32(0)` nor `Val::ExternRef(None)` is a null
+    /// `funcref`. The same applies to the values passed to [`Table::set`],
+    /// [`Table::fill`] and [`Table::grow`].
+    ///
+    /// An error is also returned if the minimum size of `ty` is larger than
+    /// its maximum or than [`Config::max_table_elements`], or if storage for
+    /// the table can't be allocated.
+    ///
+    /// [`Config::max_table_elements`]: crate::Config::max_table_elements
     ///
     /// # Examples
     ///
@@ -442,27 +497,12 @@ impl Table {
     }
 
     fn _new(store: &mut StoreOpaque, ty: TableType, init: Val) -> Result<Table> {
-        if init.ty() != *ty.element() {
-            bail!(
-                "table initialization value type {:?} does not have expected type {:?}",
-                init.ty(),
-                ty.element(),
-            );
+        if !ty.element().is_ref() {
+            bail!("unsupported table element type `{}`", ty.element());
         }
+        let init = init.into_table_element(store, ty.element().clone())?;
         let wasmtime_export = generate_table_export(store, &ty)?;
 
-        let init: runtime::TableElement = match ty.element() {
-            ValType::FuncRef => into_checked_anyfunc(init, store)?.into(),
-            ValType::ExternRef => init
-                .externref()
-                .ok_or_else(|| {
-                    anyhow!("table initialization value does not have expected type `externref`")
-                })?
-                .map(|x| x.inner)
-                .into(),
-            ty => bail!("unsupported table element type: {:?}", ty),
-        };
-
         // Initialize entries with the init value.
         unsafe {
             let table = Table::from_wasmtime_table(wasmtime_export, store);
@@ -474,6 +514,12 @@ impl Table {
         }
     }
 
+    /// Returns the identifier of the [`Store`](crate::Store) which owns this
+    /// table.
+    pub fn store_id(&self) -> StoreId {
+        self.0.store_id()
+    }
+
     /// Returns the underlying type of this table, including its element type as
     /// well as the maximum/minimum lower bounds.
     ///
@@ -523,6 +569,7 @@ impl Table {
     /// Returns an error if `index` is out of bounds, if `val` does not have
     /// the right type to be stored in this table, or if `val` belongs to a
     /// different store.
+    /// Problems with `val` are reported as a [`ValError`].
     ///
     /// # Panics
     ///
@@ -552,6 +599,10 @@ impl Table {
     /// Grows the size of this table by `delta` more elements, initialization
     /// all new elements to `init`.
     ///
+    /// Growing a `funcref` table with `Val::FuncRef(None)` fills the new
+    /// elements with null references, the same as the wasm `table.grow`
+    /// instruction with `ref.null func`.
+    ///
     /// Returns the previous size of this table if successful.
     ///
     /// # Errors
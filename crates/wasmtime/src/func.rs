@@ -1,11 +1,13 @@
-use crate::store::{StoreData, StoreInnermost, StoreOpaque, Stored};
+use crate::store::{StackUsage, StoreData, StoreId, StoreInnermost, StoreOpaque, Stored};
 use crate::{
-    AsContext, AsContextMut, Engine, Extern, FuncType, Instance, InterruptHandle, StoreContext,
-    StoreContextMut, Trap, Val, ValType,
+    AsContext, AsContextMut, CallHook, CancellationToken, Engine, Extern, FrameInfo, FuncType,
+    HostPanicPolicy, Instance, InterruptCause, InterruptHandle, Module, StoreContext,
+    StoreContextMut, SymbolMap, Trap, TrapCode, Val, ValError, ValSite, ValType,
 };
 use anyhow::{bail, Context as _, Result};
 use smallvec::{smallvec, SmallVec};
 use std::cmp::max;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::future::Future;
@@ -15,7 +17,7 @@ use std::pin::Pin;
 use std::ptr::NonNull;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
-use wasmtime_environ::wasm::{EntityIndex, FuncIndex};
+use wasmtime_environ::wasm::{DefinedFuncIndex, EntityIndex, FuncIndex};
 use wasmtime_runtime::{
     raise_user_trap, ExportFunction, InstanceAllocator, InstanceHandle, OnDemandInstanceAllocator,
     VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport, VMSharedSignatureIndex,
@@ -300,18 +302,38 @@ impl Func {
     ///
     /// For more information about `Send + Sync + 'static` requirements on the
     /// `func`, see [`Func::wrap`](#why-send--sync--static).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ty` has more parameters or results than allowed by
+    /// [`Config::max_function_params`](crate::Config::max_function_params) and
+    /// [`Config::max_function_results`](crate::Config::max_function_results).
+    /// Use [`Func::try_new`] to handle such types with an error instead.
     pub fn new<T>(
-        mut store: impl AsContextMut<Data = T>,
+        store: impl AsContextMut<Data = T>,
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
     ) -> Self {
+        Func::try_new(store, ty, func).expect("failed to create function")
+    }
+
+    /// Same as [`Func::new`], except an error is returned instead of panicking
+    /// if `ty` has more parameters or results than the [`Config`] allows, or
+    /// is too large for a trampoline to be created for it.
+    ///
+    /// [`Config`]: crate::Config
+    pub fn try_new<T>(
+        mut store: impl AsContextMut<Data = T>,
+        ty: FuncType,
+        func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
+    ) -> Result<Self> {
         let mut store = store.as_context_mut().opaque();
 
         // part of this unsafety is about matching the `T` to a `Store<T>`,
         // which is done through the `AsContextMut` bound above.
         unsafe {
-            let host = HostFunc::new(store.engine(), ty, func);
-            host.into_func(&mut store)
+            let host = HostFunc::try_new(store.engine(), ty, func)?;
+            Ok(host.into_func(&mut store))
         }
     }
 
@@ -640,6 +662,140 @@ impl Func {
 
     for_each_function_signature!(generate_wrap_async_func);
 
+    /// Creates a new host-defined function from a raw pointer to native code,
+    /// for bridging to functions defined in other languages without a Rust
+    /// closure in between.
+    ///
+    /// Unlike [`Func::new`] and [`Func::wrap`], WebAssembly calls `callee`
+    /// directly, so there's no overhead from Wasmtime on each call. The cost
+    /// of that is that `callee` must follow Wasmtime's internal calling
+    /// convention exactly, and that none of the guarantees Wasmtime usually
+    /// provides for host functions hold.
+    ///
+    /// * `ty` - the signature of the function. Only `i32`, `i64`, `f32` and
+    ///   `f64` parameters and at most one result of those types are
+    ///   supported, and other signatures return an error.
+    ///
+    /// * `callee` - a pointer to the native function to call.
+    ///
+    /// * `host_state` - arbitrary state which is kept alive as long as the
+    ///   function is, and which `callee` can access with
+    ///   [`Func::raw_host_state`].
+    ///
+    /// This function is only available with the `raw-funcs` feature of this
+    /// crate, which isn't enabled by default.
+    ///
+    /// # ABI
+    ///
+    /// `callee` is called with the platform's C calling convention, as if it
+    /// were an `unsafe extern "C" fn(callee_vmctx: *mut u8, caller_vmctx: *mut
+    /// u8, params...) -> result` where each of `params` and the `result` are
+    /// passed as the Rust type of the same name as their [`ValType`] (or
+    /// `u32`/`u64` for integers, the bits are the same), and where the function
+    /// returns nothing if `ty` has no result.
+    ///
+    /// `callee_vmctx` can be given to [`Func::raw_host_state`] to access
+    /// `host_state`. `caller_vmctx` is the context of the calling instance and
+    /// is only meaningful to Wasmtime. Neither pointer must be retained after
+    /// `callee` returns.
+    ///
+    /// This calling convention is an implementation detail of Wasmtime and may
+    /// change in any release, which is why it's behind a feature flag.
+    ///
+    /// # Unsafety
+    ///
+    /// This function is unsafe because Wasmtime can't check anything about
+    /// `callee`, and calling the returned function is undefined behavior unless
+    /// all of the following hold:
+    ///
+    /// * `callee` is a function which has the signature described above for
+    ///   `ty`, and it remains valid for as long as the returned [`Func`] is
+    ///   (that is, as long as `store`).
+    ///
+    /// * `callee` never unwinds, whether by panicking or through a foreign
+    ///   exception. There's no way for it to raise a trap either.
+    ///
+    /// * `callee` doesn't access the store, or call back into WebAssembly.
+    ///
+    /// Note also that none of the bookkeeping Wasmtime does when WebAssembly
+    /// calls the host happens for these functions, so for example
+    /// [`Store::entering_native_code_hook`](crate::Store::entering_native_code_hook)
+    /// isn't called, and the time spent in `callee` counts as guest time in
+    /// [`Store::last_activation_times`](crate::Store::last_activation_times).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// unsafe extern "C" fn add(_callee: *mut u8, _caller: *mut u8, a: i32, b: i32) -> i32 {
+    ///     a.wrapping_add(b)
+    /// }
+    ///
+    /// let mut store = Store::<()>::default();
+    /// let ty = FuncType::new(vec![ValType::I32, ValType::I32], vec![ValType::I32]);
+    /// let add = unsafe { Func::from_raw_parts(&mut store, ty, add as *const u8, Box::new(()))? };
+    /// let add = add.typed::<(i32, i32), i32, _>(&store)?;
+    /// assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "raw-funcs")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "raw-funcs")))]
+    pub unsafe fn from_raw_parts(
+        mut store: impl AsContextMut,
+        ty: FuncType,
+        callee: *const u8,
+        host_state: Box<dyn std::any::Any + Send + Sync>,
+    ) -> Result<Func> {
+        for param in ty.params() {
+            if !param.is_num() {
+                bail!("unsupported parameter type for a raw function: {}", param);
+            }
+        }
+        match ty.results().len() {
+            0 => {}
+            1 => {
+                let result = ty.results().next().unwrap();
+                if !result.is_num() {
+                    bail!("unsupported result type for a raw function: {}", result);
+                }
+            }
+            n => bail!("raw functions support at most one result, found {}", n),
+        }
+
+        let mut store = store.as_context_mut().opaque();
+        let (instance, trampoline) =
+            crate::trampoline::create_raw_parts_function(&ty, callee, host_state, store.engine())?;
+        let host = HostFunc::_new(store.engine(), instance, trampoline);
+        Ok(host.into_func(&mut store))
+    }
+
+    /// Returns the `host_state` given to [`Func::from_raw_parts`] for the
+    /// function which was called with `callee_vmctx`, or `None` if that
+    /// function wasn't created with [`Func::from_raw_parts`].
+    ///
+    /// This doesn't panic, since it's meant to be called from an
+    /// `extern "C"` function which must not unwind.
+    ///
+    /// # Unsafety
+    ///
+    /// `callee_vmctx` must be the first argument of a call to a function
+    /// created with [`Func::from_raw_parts`], and the returned reference must
+    /// not be used after that call returns.
+    #[cfg(feature = "raw-funcs")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "raw-funcs")))]
+    pub unsafe fn raw_host_state<'a>(
+        callee_vmctx: *mut u8,
+    ) -> Option<&'a (dyn std::any::Any + Send + Sync)> {
+        let instance = InstanceHandle::from_vmctx(callee_vmctx.cast::<VMContext>());
+        let state = instance
+            .host_state()
+            .downcast_ref::<crate::trampoline::RawPartsState>()?;
+        let state: *const (dyn std::any::Any + Send + Sync) = &*state.state;
+        Some(&*state)
+    }
+
     /// Returns the underlying wasm type that this `Func` has.
     ///
     /// # Panics
@@ -650,13 +806,11 @@ impl Func {
         // shared signatures, so we should be able to unwrap safely here.
         let store = store.as_context();
         let sig_index = unsafe { store[self.0].export().anyfunc.as_ref().type_index };
-        FuncType::from_wasm_func_type(
-            store
-                .engine()
-                .signatures()
-                .lookup_type(sig_index)
-                .expect("signature should be registered"),
-        )
+        store
+            .engine()
+            .signatures()
+            .lookup_type(sig_index)
+            .expect("signature should be registered")
     }
 
     pub(crate) fn sig_index(&self, data: &StoreData) -> VMSharedSignatureIndex {
@@ -666,9 +820,10 @@ impl Func {
     /// Invokes this function with the `params` given, returning the results and
     /// any trap, if one occurs.
     ///
-    /// The `params` here must match the type signature of this `Func`, or a
-    /// trap will occur. If a trap occurs while executing this function, then a
-    /// trap will also be returned.
+    /// The `params` here must match the type signature of this `Func`, or an
+    /// error will be returned, which is a [`ValError`](crate::ValError) if a
+    /// param has the wrong type or comes from another store. If a trap occurs
+    /// while executing this function, then a trap will also be returned.
     ///
     /// # Panics
     ///
@@ -728,6 +883,18 @@ impl Func {
         Ok(result)
     }
 
+    /// Same as [`Func::call`], except that it may also be used with async
+    /// stores from within a host function, which is already running on the
+    /// store's fiber.
+    pub(crate) fn call_nested(
+        &self,
+        mut store: impl AsContextMut,
+        params: &[Val],
+    ) -> Result<Box<[Val]>> {
+        let my_ty = self.ty(&store);
+        self.call_impl(&mut store.as_context_mut(), my_ty, params)
+    }
+
     fn call_impl<T>(
         &self,
         store: &mut StoreContextMut<'_, T>,
@@ -741,7 +908,7 @@ impl Func {
             let data = &store.0.store_data()[self.0];
             let trampoline = data.trampoline();
             let anyfunc = data.export().anyfunc;
-            invoke_wasm_and_catch_traps(store, |callee| {
+            invoke_wasm_and_catch_traps(store, anyfunc.as_ptr(), |callee| {
                 trampoline(
                     (*anyfunc.as_ptr()).vmctx,
                     callee,
@@ -767,10 +934,11 @@ impl Func {
             // this function. This involves checking to make sure we have the right
             // number and types of arguments as well as making sure everything is
             // from the same `Store`.
-            if ty.params().len() != params.len() {
+            if ty.param_arity() != params.len() {
                 bail!(
-                    "expected {} arguments, got {}",
-                    ty.params().len(),
+                    "expected {} arguments for {}, got {}",
+                    ty.param_arity(),
+                    ty,
                     params.len()
                 );
             }
@@ -778,17 +946,23 @@ impl Func {
             let mut values_vec = vec![0; max(params.len(), ty.results().len())];
 
             // Store the argument values into `values_vec`.
-            let param_tys = ty.params();
-            for ((arg, slot), ty) in params.iter().cloned().zip(&mut values_vec).zip(param_tys) {
-                if arg.ty() != ty {
-                    bail!(
-                        "argument type mismatch: found {} but expected {}",
-                        arg.ty(),
-                        ty
-                    );
+            let param_tys = ty.params().enumerate();
+            for ((arg, slot), (i, param_ty)) in
+                params.iter().cloned().zip(&mut values_vec).zip(param_tys)
+            {
+                if arg.ty() != param_ty {
+                    return Err(ValError::TypeMismatch {
+                        site: ValSite::Argument {
+                            index: i,
+                            func: ty.clone(),
+                        },
+                        expected: param_ty,
+                        found: arg.ty(),
+                    }
+                    .into());
                 }
                 if !arg.comes_from_same_store(store) {
-                    bail!("cross-`Store` values are not currently supported");
+                    return Err(ValError::CrossStore.into());
                 }
                 unsafe {
                     arg.write_value_to(store, slot);
@@ -842,6 +1016,16 @@ impl Func {
         }
     }
 
+    /// Returns the identifier of the [`Store`](crate::Store) which owns this
+    /// function.
+    ///
+    /// This can be compared with [`Store::id`](crate::Store::id) to find out
+    /// whether this function may be used with a store, without needing the
+    /// store itself.
+    pub fn store_id(&self) -> StoreId {
+        self.0.store_id()
+    }
+
     pub(crate) fn comes_from_same_store(&self, store: &StoreOpaque) -> bool {
         store.store_data().contains(self.0)
     }
@@ -852,7 +1036,26 @@ impl Func {
         values_vec: *mut u128,
         func: &dyn Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap>,
     ) -> Result<(), Trap> {
-        caller.store.0.entering_native_hook()?;
+        caller.store.0.call_hook(CallHook::CallingHost)?;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            Self::invoke_host(&mut caller, ty, values_vec, func)
+        }));
+        // The hooks run even if the host function trapped or panicked, so
+        // they see every call return. A panic is then passed on to be handled
+        // according to the `HostPanicPolicy`.
+        let exit = caller.store.0.call_hook(CallHook::ReturningFromHost);
+        match result {
+            Ok(result) => result.and(exit),
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+
+    fn invoke_host<T>(
+        caller: &mut Caller<'_, T>,
+        ty: &FuncType,
+        values_vec: *mut u128,
+        func: &dyn Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap>,
+    ) -> Result<(), Trap> {
         // We have a dynamic guarantee that `values_vec` has the right
         // number of arguments and the right types of arguments. As a result
         // we should be able to safely run through them all and read them.
@@ -892,8 +1095,6 @@ impl Func {
                 ret.write_value_to(&mut store, values_vec.add(i));
             }
         }
-
-        caller.store.0.exiting_native_hook()?;
         Ok(())
     }
 
@@ -1034,27 +1235,88 @@ impl Func {
 /// things like catch traps and set up GC properly.
 ///
 /// The `closure` provided receives a default "callee" `VMContext` parameter it
-/// can pass to the called wasm function, if desired.
+/// can pass to the called wasm function, if desired. The `entry` is the
+/// function being called, which is used to attribute frames of traps to
+/// instances.
 pub(crate) fn invoke_wasm_and_catch_traps<T>(
     store: &mut StoreContextMut<'_, T>,
+    entry: *const VMCallerCheckedAnyfunc,
     closure: impl FnMut(*mut VMContext),
 ) -> Result<(), Trap> {
     unsafe {
         let exit = enter_wasm(store)?;
 
-        if let Err(trap) = store.0.exiting_native_hook() {
+        if let Err(trap) = store.0.call_hook(CallHook::CallingWasm) {
             exit_wasm(store, exit);
             return Err(trap);
         }
+        let activation = Activation::push(store);
         let result = wasmtime_runtime::catch_traps(
-            store.0.vminterrupts(),
-            store.0.signal_handler(),
-            store.0.default_callee(),
+            activation.store.0.vminterrupts(),
+            activation.store.0.signal_handler(),
+            activation.store.0.default_callee(),
             closure,
-        );
-        exit_wasm(store, exit);
-        store.0.entering_native_hook()?;
-        result.map_err(Trap::from_runtime)
+        )
+        .map_err(Trap::from_runtime);
+        match result {
+            Err(mut trap) if trap.trap_code() == Some(TrapCode::Interrupt) => {
+                // This trap consumes the interrupt, so its cause is taken
+                // along with resetting the interrupt flag in `exit_wasm`.
+                let pending = activation.store.0.interrupt_cause();
+                let mut pending = pending.lock().unwrap();
+                exit_wasm(activation.store, exit);
+                if let Some(cause) = pending.take() {
+                    trap.set_interrupt_cause(cause);
+                }
+                drop(pending);
+                activation.pop(true);
+                store.0.call_hook(CallHook::ReturningFromWasm)?;
+                store.0.name_trap_frames(&mut trap, entry);
+                Err(trap)
+            }
+            result => {
+                exit_wasm(activation.store, exit);
+                activation.pop(result.is_err());
+                store.0.call_hook(CallHook::ReturningFromWasm)?;
+                result.map_err(|mut trap| {
+                    store.0.name_trap_frames(&mut trap, entry);
+                    trap
+                })
+            }
+        }
+    }
+}
+
+/// A wasm activation pushed onto a store, which is popped again when this is
+/// dropped.
+///
+/// Panics in host functions unwind through `invoke_wasm_and_catch_traps`, so
+/// the activation is popped on drop to keep the store's activations balanced.
+/// Its unwind callbacks are dropped without being run in that case, since
+/// running more embedder code while panicking risks aborting the process.
+struct Activation<'a, 'b, T> {
+    store: &'a mut StoreContextMut<'b, T>,
+    trapped: bool,
+}
+
+impl<'a, 'b, T> Activation<'a, 'b, T> {
+    fn push(store: &'a mut StoreContextMut<'b, T>) -> Self {
+        store.0.push_activation();
+        Activation {
+            store,
+            trapped: false,
+        }
+    }
+
+    /// Pops the activation, running its unwind callbacks if it `trapped`.
+    fn pop(mut self, trapped: bool) {
+        self.trapped = trapped;
+    }
+}
+
+impl<T> Drop for Activation<'_, '_, T> {
+    fn drop(&mut self) {
+        self.store.0.pop_activation(self.trapped);
     }
 }
 
@@ -1064,8 +1326,10 @@ pub(crate) fn invoke_wasm_and_catch_traps<T>(
 /// This function sets up various limits such as:
 ///
 /// * The stack limit. This is what ensures that we limit the stack space
-///   allocated by WebAssembly code and it's relative to the initial stack
-///   pointer that called into wasm.
+///   allocated by WebAssembly code. Each activation of wasm is given
+///   `max_wasm_stack` bytes relative to the stack pointer it was entered at,
+///   and all nested activations together are limited to `max_native_stack`
+///   bytes relative to the outermost entry.
 ///
 /// * Stack canaries for externref gc tracing. Currently the implementation
 ///   relies on walking frames but the stack walker isn't always 100% reliable,
@@ -1074,33 +1338,18 @@ pub(crate) fn invoke_wasm_and_catch_traps<T>(
 ///
 /// This function may fail if the the stack limit can't be set because an
 /// interrupt already happened.
-fn enter_wasm<T>(store: &mut StoreContextMut<'_, T>) -> Result<Option<usize>, Trap> {
-    // If this is a recursive call, e.g. our stack canary is already set, then
-    // we may be able to skip this function.
-    //
-    // For synchronous stores there's nothing else to do because all wasm calls
-    // happen synchronously and on the same stack. This means that the previous
-    // stack limit will suffice for the next recursive call.
-    //
-    // For asynchronous stores then each call happens on a separate native
-    // stack. This means that the previous stack limit is no longer relevant
-    // because we're on a separate stack. In this situation we need to
-    // update the stack limit, but we don't need to update the gc stack canary
-    // in this situation.
-    if store
-        .0
-        .externref_activations_table()
-        .stack_canary()
-        .is_some()
-        && !store.0.async_support()
-    {
-        return Ok(None);
-    }
-
+fn enter_wasm<T>(store: &mut StoreContextMut<'_, T>) -> Result<WasmEntry, Trap> {
     let stack_pointer = psm::stack_pointer() as usize;
-
-    // Determine the stack pointer where, after which, any wasm code will
-    // immediately trap. This is checked on the entry to all wasm functions.
+    let config = store.engine().config();
+    let max_wasm_stack = config.max_wasm_stack;
+    let max_native_stack = config.max_native_stack.unwrap_or(max_wasm_stack);
+
+    // Figure out how much of the total native stack budget is already in use
+    // by enclosing activations. The first activation on a native stack
+    // becomes the base that the budget is measured from. Recursive calls on
+    // the same stack measure from that base, and activations on a new fiber
+    // stack start with the usage of their enclosing fibers already recorded
+    // in `consumed`.
     //
     // Note that this isn't 100% precise. We are requested to give wasm
     // `max_wasm_stack` bytes, but what we're actually doing is giving wasm
@@ -1110,6 +1359,16 @@ fn enter_wasm<T>(store: &mut StoreContextMut<'_, T>) -> Result<Option<usize>, Tr
     // to it). In any case it's expected to be at most a few hundred bytes
     // of slop one way or another. When wasm is typically given a MB or so
     // (a million bytes) the slop shouldn't matter too much.
+    let prev_stack_usage = *store.0.stack_usage();
+    if prev_stack_usage.base == 0 {
+        store.0.stack_usage().base = stack_pointer;
+    }
+
+    // Determine the stack pointer where, after which, any wasm code will
+    // immediately trap. This is checked on the entry to all wasm functions, so
+    // if the total budget is already exhausted then the limit is the current
+    // stack pointer and the very first wasm function raises a stack overflow
+    // trap.
     //
     // After we've got the stack limit then we store it into the `stack_limit`
     // variable. Note that the store is an atomic swap to ensure that we can
@@ -1125,27 +1384,56 @@ fn enter_wasm<T>(store: &mut StoreContextMut<'_, T>) -> Result<Option<usize>, Tr
     // `InterruptHandle` sends us a signal). Due to the lack of needing to
     // synchronize with any other memory it's hoped that the choice of `Relaxed`
     // here should be correct for our use case.
-    let wasm_stack_limit = stack_pointer - store.engine().config().max_wasm_stack;
+    let wasm_stack_limit =
+        prev_stack_usage.wasm_stack_limit(stack_pointer, max_wasm_stack, max_native_stack);
     let interrupts = store.0.interrupts();
-    let prev_stack = match interrupts.stack_limit.swap(wasm_stack_limit, Relaxed) {
+    let prev_stack_limit = match interrupts.stack_limit.swap(wasm_stack_limit, Relaxed) {
         wasmtime_environ::INTERRUPTED => {
             // This means that an interrupt happened before we actually
             // called this function, which means that we're now
-            // considered interrupted.
-            interrupts.stack_limit.store(usize::max_value(), Relaxed);
-            return Err(Trap::new_wasm(
+            // considered interrupted. If this is a recursive call then the
+            // interrupt is left in place for the enclosing wasm as well,
+            // along with its cause, otherwise no wasm is running and the
+            // limit is reset.
+            let nested = store
+                .0
+                .externref_activations_table()
+                .stack_canary()
+                .is_some();
+            let pending = store.0.interrupt_cause();
+            let mut pending = pending.lock().unwrap();
+            store.0.interrupts().stack_limit.store(
+                if nested {
+                    wasmtime_environ::INTERRUPTED
+                } else {
+                    usize::max_value()
+                },
+                Relaxed,
+            );
+            let cause = if nested {
+                pending.clone()
+            } else {
+                pending.take()
+            };
+            drop(pending);
+            *store.0.stack_usage() = prev_stack_usage;
+            let mut trap = Trap::new_wasm(
                 None,
                 wasmtime_environ::ir::TrapCode::Interrupt,
                 backtrace::Backtrace::new_unresolved(),
-            ));
+            );
+            if let Some(cause) = cause {
+                trap.set_interrupt_cause(cause);
+            }
+            return Err(trap);
         }
         n => n,
     };
 
-    // The `usize::max_value()` sentinel is present on recursive calls to
-    // asynchronous stores here. In that situation we don't want to keep
-    // updating the stack canary, so only execute this once at the top.
-    if prev_stack == usize::max_value() {
+    // The `usize::max_value()` sentinel is present when no wasm is currently
+    // active in this store. Recursive calls don't keep updating the stack
+    // canary, so only execute this once at the top.
+    if prev_stack_limit == usize::max_value() {
         debug_assert!(store
             .0
             .externref_activations_table()
@@ -1157,26 +1445,48 @@ fn enter_wasm<T>(store: &mut StoreContextMut<'_, T>) -> Result<Option<usize>, Tr
             .set_stack_canary(Some(stack_pointer));
     }
 
-    Ok(Some(prev_stack))
+    Ok(WasmEntry {
+        stack_limit: wasm_stack_limit,
+        prev_stack_limit,
+        prev_stack_usage,
+    })
 }
 
-fn exit_wasm<T>(store: &mut StoreContextMut<'_, T>, prev_stack: Option<usize>) {
-    // If we don't have a previous stack pointer to restore, then there's no
-    // cleanup we need to perform here.
-    let prev_stack = match prev_stack {
-        Some(stack) => stack,
-        None => return,
-    };
+/// State saved by `enter_wasm` which is restored by `exit_wasm`.
+struct WasmEntry {
+    stack_limit: usize,
+    prev_stack_limit: usize,
+    prev_stack_usage: StackUsage,
+}
 
+fn exit_wasm<T>(store: &mut StoreContextMut<'_, T>, entry: WasmEntry) {
     // Only if we're restoring a top-level value do we clear the stack canary
     // value. Otherwise our purpose here might be restoring a recursive stack
     // limit but leaving the active canary in place.
-    if prev_stack == usize::max_value() {
+    if entry.prev_stack_limit == usize::max_value() {
         store.0.externref_activations_table().set_stack_canary(None);
+        // A cancellation applies to the outermost call into wasm, so once
+        // that's finished the store can be used again.
+        store.0.reset_cancellation();
     }
 
+    *store.0.stack_usage() = entry.prev_stack_usage;
+
     // see docs above for why this uses `Relaxed`
-    store.0.interrupts().stack_limit.store(prev_stack, Relaxed);
+    let stack_limit = &store.0.interrupts().stack_limit;
+    if entry.prev_stack_limit == usize::max_value() {
+        stack_limit.store(entry.prev_stack_limit, Relaxed);
+    } else {
+        // The enclosing wasm is still running, so an interrupt which arrived
+        // during this activation but wasn't noticed by it is left in place
+        // for the enclosing wasm to pick up.
+        let _ = stack_limit.compare_exchange(
+            entry.stack_limit,
+            entry.prev_stack_limit,
+            Relaxed,
+            Relaxed,
+        );
+    }
 }
 
 /// A trait implemented for types which can be returned from closures passed to
@@ -1571,6 +1881,58 @@ impl<T> Caller<'_, T> {
         }
     }
 
+    /// Registers `callback` to release a resource if the wasm which called
+    /// this host function traps.
+    ///
+    /// A trap unwinds all the wasm on the stack back to the host, skipping any
+    /// guest code that would have cleaned up after this call, for example by
+    /// calling a matching "release" import. Callbacks registered here run
+    /// when the wasm activation this host function was called from, that is
+    /// the most recent call into wasm from the host, finishes with a trap.
+    /// They run after the wasm has been unwound and before the trap is
+    /// returned, in the reverse of the order they were registered in. If the
+    /// activation returns normally they're dropped without being called.
+    ///
+    /// Note that a trap returned from this host function itself also unwinds
+    /// the activation, so callbacks registered before returning the trap run
+    /// as well.
+    pub fn defer_on_unwind(&mut self, callback: impl FnOnce(&mut T) + Send + Sync + 'static) {
+        self.store.0.defer_on_unwind(Box::new(callback));
+    }
+
+    /// Returns a [`Waker`](std::task::Waker) which, when woken, schedules the
+    /// asynchronous call into wasm which called this host function to be
+    /// polled again.
+    ///
+    /// This is meant for asynchronous host functions, such as those defined
+    /// with [`Func::new_async`], which need to register interest in an event
+    /// before returning their future. The future can then return
+    /// `Poll::Pending` until the event happens, without having to set up a
+    /// channel of its own to be woken through.
+    ///
+    /// The returned waker is a clone of the one in the `Context` the wasm
+    /// future is currently being polled with, so it's valid for the current
+    /// suspension and usable from any thread. It can also be kept after
+    /// this host function, or the whole call into wasm, returns, in which
+    /// case waking it follows the usual rules for wakers of futures that
+    /// have moved on: at worst, the task is polled when it didn't need to be.
+    /// Note that if the wasm future is moved to another task while it's
+    /// suspended, the old waker won't wake the new task, so futures which
+    /// stay pending across several polls should also register the waker of
+    /// each `Context` they're polled with, as futures normally do.
+    ///
+    /// Returns `None` if the store isn't configured for
+    /// [async](crate::Config::async_support), or if this host function wasn't
+    /// called from within an asynchronous call into wasm.
+    #[cfg(feature = "async")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    pub fn waker(&self) -> Option<std::task::Waker> {
+        if !self.store.0.async_support() {
+            return None;
+        }
+        self.store.0.async_cx().waker()
+    }
+
     /// Access the underlying data owned by this `Store`.
     ///
     /// Same as [`Store::data`](crate::Store::data)
@@ -1598,6 +1960,27 @@ impl<T> Caller<'_, T> {
         self.store.interrupt_handle()
     }
 
+    /// Returns an [`InterruptHandle`] which interrupts wasm execution with
+    /// `cause`.
+    ///
+    /// See
+    /// [`Store::interrupt_handle_with_cause`](crate::Store::interrupt_handle_with_cause)
+    /// for more information.
+    pub fn interrupt_handle_with_cause(
+        &self,
+        cause: impl Into<InterruptCause>,
+    ) -> Result<InterruptHandle> {
+        self.store.interrupt_handle_with_cause(cause)
+    }
+
+    /// Returns a [`CancellationToken`] to gracefully cancel wasm execution.
+    ///
+    /// See [`Store::cancellation_token`](crate::Store::cancellation_token) for
+    /// more information.
+    pub fn cancellation_token(&self) -> Result<CancellationToken> {
+        self.store.cancellation_token()
+    }
+
     /// Perform garbage collection of `ExternRef`s.
     ///
     /// Same as [`Store::gc`](crate::Store::gc).
@@ -1612,6 +1995,23 @@ impl<T> Caller<'_, T> {
         self.store.fuel_consumed()
     }
 
+    /// Returns the fuel remaining in this store.
+    ///
+    /// For more information see [`Store::fuel_remaining`](crate::Store::fuel_remaining)
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        self.store.fuel_remaining()
+    }
+
+    /// Returns the fuel consumed by each function of `module` in this store.
+    ///
+    /// For more information see [`Store::function_fuel_consumed`](crate::Store::function_fuel_consumed)
+    pub fn function_fuel_consumed(
+        &self,
+        module: &Module,
+    ) -> Option<HashMap<DefinedFuncIndex, u64>> {
+        self.store.function_fuel_consumed(module)
+    }
+
     /// Inject more fuel into this store to be consumed when executing wasm code.
     ///
     /// For more information see [`Store::add_fuel`](crate::Store::add_fuel)
@@ -1619,6 +2019,14 @@ impl<T> Caller<'_, T> {
         self.store.add_fuel(fuel)
     }
 
+    /// Consumes fuel from this store on behalf of the host function.
+    ///
+    /// For more information see
+    /// [`Store::consume_fuel`](crate::Store::consume_fuel)
+    pub fn consume_fuel(&mut self, fuel: u64) -> Result<u64> {
+        self.store.consume_fuel(fuel)
+    }
+
     /// Configures this `Store` to trap whenever fuel runs out.
     ///
     /// For more information see
@@ -1636,6 +2044,39 @@ impl<T> Caller<'_, T> {
         self.store
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
+
+    /// Configures the fuel granted to wasm after cancellation is requested.
+    ///
+    /// For more information see
+    /// [`Store::cancellation_grace_fuel`](crate::Store::cancellation_grace_fuel)
+    pub fn cancellation_grace_fuel(&mut self, fuel: u64) {
+        self.store.cancellation_grace_fuel(fuel)
+    }
+
+    /// Looks up information about the WebAssembly function containing `pc`.
+    ///
+    /// For more information see
+    /// [`Store::lookup_frame_info`](crate::Store::lookup_frame_info)
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<FrameInfo> {
+        self.store.lookup_frame_info(pc)
+    }
+
+    /// Creates a [`SymbolMap`] of all WebAssembly code in this store.
+    ///
+    /// For more information see [`Store::symbol_map`](crate::Store::symbol_map)
+    pub fn symbol_map(&self) -> SymbolMap {
+        self.store.symbol_map()
+    }
+
+    /// Creates a [`GcDiagnostics`](crate::GcDiagnostics) of all WebAssembly
+    /// code in this store.
+    ///
+    /// For more information see [`Store::gc_diagnostics`](crate::Store::gc_diagnostics)
+    #[cfg(feature = "gc-diagnostics")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "gc-diagnostics")))]
+    pub fn gc_diagnostics(&self) -> crate::GcDiagnostics {
+        self.store.gc_diagnostics()
+    }
 }
 
 impl<T> AsContext for Caller<'_, T> {
@@ -1739,16 +2180,25 @@ macro_rules! impl_into_func {
 
                         let ret = {
                             panic::catch_unwind(AssertUnwindSafe(|| {
-                                if let Err(trap) = caller.store.0.entering_native_hook() {
+                                if let Err(trap) = caller.store.0.call_hook(CallHook::CallingHost) {
                                     return R::fallible_from_trap(trap);
                                 }
                                 let mut _store = caller.sub_caller().store.opaque();
                                 $(let $args = $args::from_abi($args, &mut _store);)*
-                                let r = func(
-                                    caller.sub_caller(),
-                                    $( $args, )*
-                                );
-                                if let Err(trap) = caller.store.0.exiting_native_hook() {
+                                let r = panic::catch_unwind(AssertUnwindSafe(|| {
+                                    func(
+                                        caller.sub_caller(),
+                                        $( $args, )*
+                                    )
+                                }));
+                                // The hook runs even if `func` panicked, which
+                                // is then handled by the enclosing `catch_unwind`.
+                                let exit = caller.store.0.call_hook(CallHook::ReturningFromHost);
+                                let r = match r {
+                                    Ok(r) => r,
+                                    Err(panic) => panic::resume_unwind(panic),
+                                };
+                                if let Err(trap) = exit {
                                     return R::fallible_from_trap(trap);
                                 }
                                 r.into_fallible()
@@ -1763,7 +2213,12 @@ macro_rules! impl_into_func {
                         // abnormally from this `match`, e.g. on `Err`, on
                         // cross-store-issues, or if `Ok(Err)` is raised.
                         match ret {
-                            Err(panic) => CallResult::Panic(panic),
+                            Err(panic) => match caller.store.0.engine().config().host_panic_policy {
+                                HostPanicPolicy::Unwind => CallResult::Panic(panic),
+                                HostPanicPolicy::CatchAsTrap => {
+                                    CallResult::Trap(Box::new(Trap::from_host_panic(panic)))
+                                }
+                            },
                             Ok(ret) => {
                                 // Because the wrapped function is not `unsafe`, we
                                 // can't assume it returned a value that is
@@ -1831,7 +2286,7 @@ macro_rules! impl_into_func {
                         $(.chain(Some($args::valtype())))*
                 );
 
-                let shared_signature_id = engine.signatures().register(ty.as_wasm_func_type());
+                let shared_signature_id = engine.signatures().register(&ty);
 
                 let trampoline = host_trampoline::<$($args,)* R>;
 
@@ -1882,12 +2337,12 @@ pub(crate) struct HostFunc {
 }
 
 impl HostFunc {
-    /// Analog of [`Func::new`]
-    pub fn new<T>(
+    /// Analog of [`Func::try_new`]
+    pub fn try_new<T>(
         engine: &Engine,
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
-    ) -> Self {
+    ) -> Result<Self> {
         let ty_clone = ty.clone();
 
         // Create a trampoline that converts raw u128 values to `Val`
@@ -1897,9 +2352,8 @@ impl HostFunc {
             })
         });
 
-        let (instance, trampoline) = crate::trampoline::create_function(&ty, func, engine)
-            .expect("failed to create function");
-        HostFunc::_new(engine, instance, trampoline)
+        let (instance, trampoline) = crate::trampoline::create_function(&ty, func, engine)?;
+        Ok(HostFunc::_new(engine, instance, trampoline))
     }
 
     /// Analog of [`Func::wrap`]
@@ -1931,14 +2385,24 @@ impl HostFunc {
     /// Inserts this `HostFunc` into a `Store`, returning the `Func` pointing to
     /// it.
     ///
+    /// This is only inserted once per store, and later calls return the same
+    /// `Func`, so that using a `Linker`'s functions as the imports of many
+    /// instances doesn't grow the store for each one.
+    ///
     /// # Unsafety
     ///
     /// Can only be inserted into stores with a matching `T` relative to when
     /// this `HostFunc` was first created.
     pub unsafe fn to_func(self: &Arc<Self>, store: &mut StoreOpaque<'_>) -> Func {
+        let key = Arc::as_ptr(self) as usize;
+        if let Some(func) = store.shared_host_func(key) {
+            return func;
+        }
         self.register_trampoline(store);
         let me = self.clone();
-        Func(store.store_data_mut().insert(FuncData::SharedHost(me)))
+        let func = Func(store.store_data_mut().insert(FuncData::SharedHost(me)));
+        store.insert_shared_host_func(key, func);
+        func
     }
 
     /// Same as [`HostFunc::to_func`], different ownership.
@@ -1,7 +1,7 @@
 use super::{invoke_wasm_and_catch_traps, HostAbi};
 use crate::store::StoreOpaque;
 use crate::{AsContextMut, ExternRef, Func, StoreContextMut, Trap, ValType};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use std::marker;
 use std::mem::{self, MaybeUninit};
 use std::ptr;
@@ -139,7 +139,8 @@ where
             false,
         );
 
-        let result = invoke_wasm_and_catch_traps(store, |callee| {
+        let entry = captures.0.as_ptr();
+        let result = invoke_wasm_and_catch_traps(store, entry, |callee| {
             let (anyfunc, ret, params, returned) = &mut captures;
             let anyfunc = anyfunc.as_ref();
             let result = Params::invoke::<Results>(
@@ -352,8 +353,9 @@ macro_rules! impl_wasm_params {
                 $(
                     match params.next() {
                         Some(t) => {
+                            $t::typecheck(t)
+                                .with_context(|| format!("type mismatch at index {}", _n))?;
                             _n += 1;
-                            $t::typecheck(t)?
                         },
                         None => bail!("expected {} types, found {}", $n, params.len() + _n),
                     }
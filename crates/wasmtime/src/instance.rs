@@ -1,12 +1,13 @@
 use crate::linker::Definition;
 use crate::signatures::SignatureCollection;
-use crate::store::{InstanceId, StoreData, StoreOpaque, Stored};
+use crate::start_budget::{RunningBudget, StartBudget};
+use crate::store::{StoreData, StoreId, StoreInstanceId, StoreOpaque, Stored};
 use crate::types::matching;
 use crate::{
-    AsContext, AsContextMut, Engine, Export, Extern, ExternType, Func, Global, InstanceType,
-    Memory, Module, StoreContextMut, Table, Trap, TypedFunc,
+    AsContext, AsContextMut, Engine, ErrorKind, Export, Extern, ExternType, Func, Global,
+    InstanceType, Memory, Module, StoreContext, StoreContextMut, Table, Trap, TypedFunc,
 };
-use anyhow::{anyhow, bail, Context, Error, Result};
+use anyhow::{anyhow, Error, Result};
 use std::mem;
 use std::sync::Arc;
 use wasmtime_environ::entity::PrimaryMap;
@@ -17,8 +18,8 @@ use wasmtime_environ::wasm::{
 use wasmtime_environ::Initializer;
 use wasmtime_jit::TypeTables;
 use wasmtime_runtime::{
-    Imports, InstanceAllocationRequest, InstantiationError, VMContext, VMFunctionBody,
-    VMFunctionImport, VMGlobalImport, VMMemoryImport, VMTableImport,
+    Imports, InstanceAllocationRequest, VMContext, VMFunctionBody, VMFunctionImport,
+    VMGlobalImport, VMMemoryImport, VMTableImport,
 };
 
 /// An instantiated WebAssembly module.
@@ -38,13 +39,21 @@ use wasmtime_runtime::{
 #[repr(transparent)]
 pub struct Instance(Stored<InstanceData>);
 
+/// An identifier for an [`Instance`], unique within its [`Store`](crate::Store).
+///
+/// Ids are cheap to copy, compare, and hash, and an instance's id never
+/// changes, which makes them suitable as keys for embedder-side bookkeeping
+/// about instances.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId(Stored<InstanceData>);
+
 pub(crate) enum InstanceData {
     /// This variant is used for instances created through instantiation of a
     /// module, e.g. `Instance::new` or various linker methods.
     Instantiated {
         /// The id of the instance within the store, used to find the original
         /// `InstanceHandle`.
-        id: InstanceId,
+        id: StoreInstanceId,
         /// A lazily-populated list of exports of this instance. The order of
         /// exports here matches the order of the exports in the the original
         /// module.
@@ -88,6 +97,11 @@ impl Instance {
     /// which corresponds to that type.  These [`Extern`] values are all then
     /// collected into a list and passed to this function.
     ///
+    /// Imports are matched purely by position: the `n`th entry of `imports`
+    /// satisfies the `n`th import of the module. Names play no part, so a
+    /// module importing the same name twice is given whichever two items are
+    /// at those positions.
+    ///
     /// Note that this function is intentionally relatively low level. For an
     /// easier time passing imports by doing name-based resolution it's
     /// recommended to instead use the [`Linker`](crate::Linker) type.
@@ -106,7 +120,9 @@ impl Instance {
     ///
     /// When instantiation fails it's recommended to inspect the return value to
     /// see why it failed, or bubble it upwards. If you'd like to specifically
-    /// check for trap errors, you can use `error.downcast::<Trap>()`.
+    /// check for trap errors, you can use `error.downcast::<Trap>()`. Which of
+    /// these cases happened is also told apart by the [`ErrorKind`] of the
+    /// [`wasmtime::Error`](crate::Error) attached to the error as context.
     ///
     /// # Panics
     ///
@@ -167,10 +183,151 @@ impl Instance {
         i.run_async(&mut store.as_context_mut()).await
     }
 
+    /// Same as [`Instance::new`], except that the module's start function is
+    /// run within `budget` rather than with the store's own fuel and
+    /// interrupts.
+    ///
+    /// This is intended for instantiating untrusted modules, whose start
+    /// function may otherwise run for as long as the store allows before the
+    /// embedder gets a chance to impose limits of its own. For more
+    /// information see [`StartBudget`].
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors of [`Instance::new`], this fails with a
+    /// [`StartBudgetExceeded`](crate::StartBudgetExceeded) error if the start
+    /// function exceeds `budget`, and fails before instantiating anything if
+    /// `budget` has limits which aren't enabled in the store's configuration.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same situations as [`Instance::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::new(Config::new().consume_fuel(true))?;
+    /// let module = Module::new(&engine, "(module (func $spin (loop (br 0))) (start $spin))")?;
+    /// let mut store = Store::new(&engine, ());
+    ///
+    /// let mut budget = StartBudget::new();
+    /// budget.fuel(1000);
+    /// let err = Instance::new_with_budget(&mut store, &module, &[], &budget).unwrap_err();
+    /// let kind = err.downcast_ref::<wasmtime::Error>().unwrap().kind();
+    /// assert!(matches!(kind, ErrorKind::ResourceExhausted));
+    /// let exceeded = err.downcast_ref::<StartBudgetExceeded>().unwrap();
+    /// assert_eq!(exceeded.limit(), StartLimit::Fuel);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_budget(
+        mut store: impl AsContextMut,
+        module: &Module,
+        imports: &[Extern],
+        budget: &StartBudget,
+    ) -> Result<Instance, Error> {
+        // See `new` for unsafety comments
+        let mut i = unsafe {
+            let mut cx = store.as_context_mut().opaque();
+            typecheck_externs(&mut cx, module, imports)?;
+            Instantiator::new(&mut cx, module, ImportSource::Externs(imports))?
+                .with_budget(&cx, budget)?
+        };
+        i.run(&mut store.as_context_mut())
+    }
+
+    /// Same as [`Instance::new_async`], except that the module's start
+    /// function is run within `budget`.
+    ///
+    /// See [`Instance::new_with_budget`] for more information.
+    #[cfg(feature = "async")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    pub async fn new_async_with_budget<T>(
+        mut store: impl AsContextMut<Data = T>,
+        module: &Module,
+        imports: &[Extern],
+        budget: &StartBudget,
+    ) -> Result<Instance, Error>
+    where
+        T: Send,
+    {
+        // See `new` for unsafety comments
+        let mut i = unsafe {
+            let mut cx = store.as_context_mut().opaque();
+            typecheck_externs(&mut cx, module, imports)?;
+            Instantiator::new(&mut cx, module, ImportSource::Externs(imports))?
+                .with_budget(&cx, budget)?
+        };
+        i.run_async(&mut store.as_context_mut()).await
+    }
+
+    /// Same as [`Instance::new`], except that the new instance is labelled
+    /// with `name` for diagnostics, as with [`Instance::set_name`].
+    ///
+    /// The name is given to the instance before its start function runs, and
+    /// is included in the error if instantiating it exceeds the store's
+    /// resource limits.
+    pub fn new_with_name(
+        mut store: impl AsContextMut,
+        module: &Module,
+        imports: &[Extern],
+        name: &str,
+    ) -> Result<Instance, Error> {
+        // See `new` for unsafety comments
+        let mut i = unsafe {
+            let mut cx = store.as_context_mut().opaque();
+            typecheck_externs(&mut cx, module, imports)?;
+            Instantiator::new(&mut cx, module, ImportSource::Externs(imports))?
+        };
+        i.name = Some(name);
+        i.run(&mut store.as_context_mut())
+    }
+
     pub(crate) fn from_wasmtime(handle: InstanceData, store: &mut StoreOpaque) -> Instance {
         Instance(store.store_data_mut().insert(handle))
     }
 
+    /// Returns the identifier of this instance.
+    ///
+    /// Every instance in a store, including instances created by the module
+    /// linking proposal, has a distinct id.
+    pub fn id(&self) -> InstanceId {
+        InstanceId(self.0)
+    }
+
+    /// Labels this instance with `name` for diagnostics.
+    ///
+    /// Embedders running many instances of the same module in one store can
+    /// use this to tell them apart: frames in a [`Trap`]'s backtrace report
+    /// the name of the instance they belong to through
+    /// [`FrameInfo::instance_name`](crate::FrameInfo::instance_name), and
+    /// it's included in the trap's printed backtrace. Setting a name again
+    /// replaces the previous one. Instances can also be named as they're
+    /// created with [`Instance::new_with_name`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn set_name(&self, mut store: impl AsContextMut, name: &str) {
+        let mut store = store.as_context_mut().opaque();
+        store.store_data().assert_contains(self.0);
+        store.set_instance_name(self.id(), name);
+    }
+
+    /// Returns the name given to this instance with [`Instance::set_name`],
+    /// if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn name<'a, T: 'a>(&self, store: impl Into<StoreContext<'a, T>>) -> Option<&'a str> {
+        let store = store.into().0;
+        store.store_data().assert_contains(self.0);
+        store.instance_name(self.id())
+    }
+
     /// Returns the type signature of this instance.
     ///
     /// # Panics
@@ -200,6 +357,12 @@ impl Instance {
         &store[self.0]
     }
 
+    /// Returns the identifier of the [`Store`](crate::Store) which owns this
+    /// instance.
+    pub fn store_id(&self) -> StoreId {
+        self.0.store_id()
+    }
+
     pub(crate) fn comes_from_same_store(&self, store: &StoreOpaque) -> bool {
         store.store_data().contains(self.0)
     }
@@ -225,8 +388,8 @@ impl Instance {
         if let InstanceData::Instantiated { exports, id, .. } = &store[self.0] {
             if exports.iter().any(|e| e.is_none()) {
                 let module = Arc::clone(store.instance(*id).module());
-                for name in module.exports.keys() {
-                    self._get_export(&mut store, name);
+                for (i, index) in module.exports.values().enumerate() {
+                    self.resolve_export(&mut store, i, *index);
                 }
             }
         }
@@ -310,24 +473,40 @@ impl Instance {
             InstanceData::Synthetic(names) => names.get(name).cloned(),
 
             // Instantiated instances will lazily fill in exports, so we process
-            // all that lazy logic here.
+            // all that lazy logic here. The module's export map is ordered, so
+            // the position `name` is found at doubles as the index of its slot
+            // in `exports`, and after the first lookup of a name it's a single
+            // hash probe and a clone of the cached `Extern`.
+            InstanceData::Instantiated { id, .. } => {
+                let (i, _, index) = store.instance(*id).module().exports.get_full(name)?;
+                let index = *index;
+                Some(self.resolve_export(store, i, index))
+            }
+        }
+    }
+
+    /// Returns the `i`th export of an instantiated instance, which is
+    /// `index` in its module, creating and caching its `Extern` if this is
+    /// the first time it's been asked for.
+    fn resolve_export(&self, store: &mut StoreOpaque<'_>, i: usize, index: EntityIndex) -> Extern {
+        let id = match &store[self.0] {
             InstanceData::Instantiated { id, exports, .. } => {
-                let instance = store.instance(*id);
-                let (i, _, index) = instance.module().exports.get_full(name)?;
                 if let Some(export) = &exports[i] {
-                    return Some(export.clone());
+                    return export.clone();
                 }
-                let item = unsafe {
-                    Extern::from_wasmtime_export(instance.lookup_by_declaration(index), store)
-                };
-                let exports = match &mut store[self.0] {
-                    InstanceData::Instantiated { exports, .. } => exports,
-                    _ => unreachable!(),
-                };
-                exports[i] = Some(item.clone());
-                Some(item)
+                *id
             }
+            InstanceData::Synthetic(_) => unreachable!(),
+        };
+        let item = unsafe {
+            let export = store.instance(id).lookup_by_declaration(&index);
+            Extern::from_wasmtime_export(export, store)
+        };
+        match &mut store[self.0] {
+            InstanceData::Instantiated { exports, .. } => exports[i] = Some(item.clone()),
+            InstanceData::Synthetic(_) => unreachable!(),
         }
+        item
     }
 
     /// Looks up an exported [`Func`] value by name.
@@ -410,6 +589,9 @@ impl Instance {
 struct Instantiator<'a> {
     in_progress: Vec<ImportsBuilder<'a>>,
     cur: ImportsBuilder<'a>,
+    budget: Option<RunningBudget>,
+    /// The name to give the outermost instance.
+    name: Option<&'a str>,
 }
 
 struct ImportsBuilder<'a> {
@@ -427,7 +609,21 @@ struct ImportsBuilder<'a> {
 enum ImportSource<'a> {
     Externs(&'a [Extern]),
     Definitions(&'a [Definition]),
-    Outer { initializer: usize },
+    /// All of the module's imports, already resolved. This is only used for
+    /// modules whose only initializers are imports.
+    Template(Arc<ImportTemplate>),
+    Outer {
+        initializer: usize,
+    },
+}
+
+/// The imports of a module as resolved within a particular store, which can be
+/// copied as-is into each new instance of the module in that store.
+pub(crate) struct ImportTemplate {
+    functions: Vec<VMFunctionImport>,
+    tables: Vec<VMTableImport>,
+    memories: Vec<VMMemoryImport>,
+    globals: Vec<VMGlobalImport>,
 }
 
 impl<'a> Instantiator<'a> {
@@ -457,21 +653,41 @@ impl<'a> Instantiator<'a> {
         imports: ImportSource<'a>,
     ) -> Result<Instantiator<'a>> {
         if !Engine::same(store.engine(), module.engine()) {
-            bail!("cross-`Engine` instantiation is not currently supported");
+            return Err(usage_error(
+                "cross-`Engine` instantiation is not currently supported",
+            ));
         }
 
         Ok(Instantiator {
             in_progress: Vec::new(),
             cur: ImportsBuilder::new(module, imports),
+            budget: None,
+            name: None,
         })
     }
 
+    /// Runs all start functions within `budget`.
+    fn with_budget(mut self, store: &StoreOpaque<'_>, budget: &StartBudget) -> Result<Self> {
+        let budget = budget
+            .begin(store)
+            .map_err(|e| crate::Error::classify(e, ErrorKind::Usage))?;
+        self.budget = Some(budget);
+        Ok(self)
+    }
+
     fn run<T>(&mut self, store: &mut StoreContextMut<'_, T>) -> Result<Instance, Error> {
         assert!(
             !store.0.async_support(),
             "cannot use `new` when async support is enabled on the config"
         );
+        self.run_inline(store)
+    }
 
+    /// Runs instantiation, including start functions, on the current stack.
+    ///
+    /// For async stores this must only be used from a host function which is
+    /// already running on a fiber, so start functions can suspend it.
+    fn run_inline<T>(&mut self, store: &mut StoreContextMut<'_, T>) -> Result<Instance, Error> {
         // NB: this is the same code as `run_async`. It's intentionally
         // small but should be kept in sync (modulo the async bits).
         loop {
@@ -479,7 +695,7 @@ impl<'a> Instantiator<'a> {
                 self.step(&mut store.as_context_mut().opaque())?
             {
                 if let Some(start) = start {
-                    Instantiator::start_raw(store, instance, start)?;
+                    Instantiator::start_raw(store, instance, start, self.budget.as_mut())?;
                 }
                 if toplevel {
                     break Ok(instance);
@@ -498,15 +714,19 @@ impl<'a> Instantiator<'a> {
             "cannot use `new_async` without enabling async support on the config"
         );
 
-        // NB: this is the same code as `run`. It's intentionally
+        // NB: this is the same code as `run_inline`. It's intentionally
         // small but should be kept in sync (modulo the async bits).
         loop {
             let step = self.step(&mut store.as_context_mut().opaque())?;
             if let Some((instance, start, toplevel)) = step {
                 if let Some(start) = start {
+                    let budget = self.budget.as_mut();
                     store
-                        .on_fiber(|store| Instantiator::start_raw(store, instance, start))
-                        .await??;
+                        .on_fiber(|store| Instantiator::start_raw(store, instance, start, budget))
+                        .await
+                        // The start function's own traps are inside, so this
+                        // is a failure to set up the fiber to run it on.
+                        .map_err(|trap| crate::Error::new(ErrorKind::ResourceExhausted, trap))??;
                 }
                 if toplevel {
                     break Ok(instance);
@@ -540,7 +760,14 @@ impl<'a> Instantiator<'a> {
         store: &mut StoreOpaque<'_>,
     ) -> Result<Option<(Instance, Option<FuncIndex>, bool)>> {
         if self.cur.initializer == 0 {
-            store.bump_resource_counts(&self.cur.module)?;
+            let name = if self.in_progress.is_empty() {
+                self.name
+            } else {
+                None
+            };
+            store
+                .bump_resource_counts(&self.cur.module, name)
+                .map_err(|e| crate::Error::new(ErrorKind::ResourceExhausted, e))?;
         }
 
         // Read the current module's initializer and move forward the
@@ -555,6 +782,10 @@ impl<'a> Instantiator<'a> {
         {
             Some(Initializer::Import { name, field, .. }) => {
                 match &mut self.cur.src {
+                    // Everything was copied from the template when the
+                    // builder was created.
+                    ImportSource::Template(_) => {}
+
                     // If imports are coming from the runtime-provided list
                     // (e.g. the root module being instantiated) then we
                     // need to typecheck each item here before recording it.
@@ -690,7 +921,12 @@ impl<'a> Instantiator<'a> {
                         self.cur.instances.push(instance);
                         false
                     }
-                    None => true,
+                    None => {
+                        if let Some(name) = self.name {
+                            store.set_instance_name(instance.id(), name);
+                        }
+                        true
+                    }
                 };
                 return Ok(Some((instance, start, toplevel)));
             }
@@ -720,18 +956,18 @@ impl<'a> Instantiator<'a> {
             // this instance, so we determine what the ID is and then assert
             // it's the same later when we do actually insert it.
             let instance_to_be = store.store_data().next_id::<InstanceData>();
-            let mut instance_handle =
-                store
-                    .engine()
-                    .allocator()
-                    .allocate(InstanceAllocationRequest {
-                        module: compiled_module.module().clone(),
-                        finished_functions: compiled_module.finished_functions(),
-                        imports: self.cur.build(),
-                        shared_signatures: self.cur.module.signatures().as_module_map().into(),
-                        host_state: Box::new(Instance(instance_to_be)),
-                        store: Some(store.traitobj),
-                    })?;
+            let mut instance_handle = store
+                .engine()
+                .allocator()
+                .allocate(InstanceAllocationRequest {
+                    module: compiled_module.module().clone(),
+                    finished_functions: compiled_module.finished_functions(),
+                    imports: self.cur.build(),
+                    shared_signatures: self.cur.module.signatures().as_module_map().into(),
+                    host_state: Box::new(Instance(instance_to_be)),
+                    store: Some(store.traitobj),
+                })
+                .map_err(crate::Error::from_instantiation_error)?;
 
             // The instance still has lots of setup, for example
             // data/elements/start/etc. This can all fail, but even on failure
@@ -746,6 +982,7 @@ impl<'a> Instantiator<'a> {
             // matter in practice since initialization isn't even running any
             // code here anyway.
             let id = store.add_instance(instance_handle.clone(), false);
+            store.modules_mut().instantiated(&self.cur.module);
 
             // Additionally, before we start doing fallible instantiation, we
             // do one more step which is to insert an `InstanceData`
@@ -811,12 +1048,7 @@ impl<'a> Instantiator<'a> {
                     compiled_module.module(),
                     store.engine().config().features.bulk_memory,
                 )
-                .map_err(|e| -> Error {
-                    match e {
-                        InstantiationError::Trap(trap) => Trap::from_runtime(trap).into(),
-                        other => other.into(),
-                    }
-                })?;
+                .map_err(crate::Error::from_instantiation_error)?;
 
             Ok((instance, compiled_module.module().start_func))
         }
@@ -826,6 +1058,7 @@ impl<'a> Instantiator<'a> {
         store: &mut StoreContextMut<'_, T>,
         instance: Instance,
         start: FuncIndex,
+        budget: Option<&mut RunningBudget>,
     ) -> Result<()> {
         let id = match &store.0.store_data()[instance.0] {
             InstanceData::Instantiated { id, .. } => *id,
@@ -839,29 +1072,52 @@ impl<'a> Instantiator<'a> {
             _ => unreachable!(), // valid modules shouldn't hit this
         };
         let vmctx = instance.vmctx_ptr();
-        unsafe {
-            super::func::invoke_wasm_and_catch_traps(store, |_default_callee| {
+        let run = |store: &mut StoreContextMut<'_, T>| unsafe {
+            super::func::invoke_wasm_and_catch_traps(store, f.anyfunc.as_ptr(), |_default_callee| {
                 mem::transmute::<
                     *const VMFunctionBody,
                     unsafe extern "C" fn(*mut VMContext, *mut VMContext),
                 >(f.anyfunc.as_ref().func_ptr.as_ptr())(
                     f.anyfunc.as_ref().vmctx, vmctx
                 )
-            })?;
-        }
-        Ok(())
+            })
+        };
+        let result = match budget {
+            Some(budget) => budget.run(store, run),
+            None => run(store).map_err(anyhow::Error::from),
+        };
+        result.map_err(|e| match e.downcast::<Trap>() {
+            Ok(trap) => crate::Error::start_trap(trap),
+            // Running out of the budget is the only other way for this to
+            // fail.
+            Err(e) => crate::Error::classify(e, ErrorKind::ResourceExhausted),
+        })
     }
 }
 
 impl<'a> ImportsBuilder<'a> {
     fn new(module: &Module, src: ImportSource<'a>) -> ImportsBuilder<'a> {
         let raw = module.compiled_module().module();
+        let (functions, tables, memories, globals) = match &src {
+            ImportSource::Template(template) => (
+                template.functions.iter().copied().collect(),
+                template.tables.iter().copied().collect(),
+                template.memories.iter().copied().collect(),
+                template.globals.iter().copied().collect(),
+            ),
+            _ => (
+                PrimaryMap::with_capacity(raw.num_imported_funcs),
+                PrimaryMap::with_capacity(raw.num_imported_tables),
+                PrimaryMap::with_capacity(raw.num_imported_memories),
+                PrimaryMap::with_capacity(raw.num_imported_globals),
+            ),
+        };
         ImportsBuilder {
             src,
-            functions: PrimaryMap::with_capacity(raw.num_imported_funcs),
-            tables: PrimaryMap::with_capacity(raw.num_imported_tables),
-            memories: PrimaryMap::with_capacity(raw.num_imported_memories),
-            globals: PrimaryMap::with_capacity(raw.num_imported_globals),
+            functions,
+            tables,
+            memories,
+            globals,
             instances: PrimaryMap::with_capacity(raw.instances.len()),
             modules: PrimaryMap::with_capacity(raw.modules.len()),
             module: module.clone(),
@@ -900,6 +1156,15 @@ impl<'a> ImportsBuilder<'a> {
             functions: self.functions.values().as_slice(),
         }
     }
+
+    fn template(&self) -> ImportTemplate {
+        ImportTemplate {
+            functions: self.functions.values().as_slice().to_vec(),
+            tables: self.tables.values().as_slice().to_vec(),
+            memories: self.memories.values().as_slice().to_vec(),
+            globals: self.globals.values().as_slice().to_vec(),
+        }
+    }
 }
 
 /// An instance, pre-instantiation, that is ready to be instantiated.
@@ -911,6 +1176,12 @@ impl<'a> ImportsBuilder<'a> {
 /// Note that an `InstancePre` may not be tied to any particular [`Store`] if
 /// none of the imports it closed over are tied to any particular [`Store`].
 ///
+/// The first time an `InstancePre` is instantiated within a [`Store`] the
+/// imports it closed over are resolved within that store, and the result is
+/// kept by the store until either of them is dropped. Later instantiations
+/// within the same store copy those resolved imports rather than resolving
+/// each one again.
+///
 /// This structure is created through the [`Linker::instantiate_pre`] method,
 /// which also has some more information and examples.
 ///
@@ -918,7 +1189,12 @@ impl<'a> ImportsBuilder<'a> {
 /// [`Linker::instantiate_pre`]: crate::Linker::instantiate_pre
 pub struct InstancePre<T> {
     module: Module,
-    items: Vec<Definition>,
+    items: Arc<[Definition]>,
+    /// Whether to save the resolved imports in each store this is
+    /// instantiated in, to copy for later instantiations. This is only
+    /// possible if the module's only initializers are imports of functions,
+    /// tables, memories and globals.
+    reuse_imports: bool,
     _marker: std::marker::PhantomData<fn() -> T>,
 }
 
@@ -929,13 +1205,35 @@ impl<T> InstancePre<T> {
         items: Vec<Definition>,
     ) -> Result<InstancePre<T>> {
         typecheck_defs(store, module, &items)?;
+        let reuse_imports = module
+            .env_module()
+            .initializers
+            .iter()
+            .all(|init| match init {
+                Initializer::Import { index, .. } => match index {
+                    EntityIndex::Function(_)
+                    | EntityIndex::Table(_)
+                    | EntityIndex::Memory(_)
+                    | EntityIndex::Global(_) => true,
+                    EntityIndex::Instance(_) | EntityIndex::Module(_) => false,
+                },
+                _ => false,
+            });
         Ok(InstancePre {
             module: module.clone(),
-            items,
+            items: items.into(),
+            reuse_imports,
             _marker: std::marker::PhantomData,
         })
     }
 
+    /// Marks this as only being instantiated once, so its resolved imports
+    /// aren't worth saving in the store.
+    pub(crate) fn single_use(mut self) -> Self {
+        self.reuse_imports = false;
+        self
+    }
+
     /// Instantiates this instance, creating a new instance within the provided
     /// `store`.
     ///
@@ -956,12 +1254,8 @@ impl<T> InstancePre<T> {
         // passed in.
         let mut instantiator = unsafe {
             let mut store = store.as_context_mut().opaque();
-            self.ensure_comes_from_same_store(&store)?;
-            Instantiator::new(
-                &mut store,
-                &self.module,
-                ImportSource::Definitions(&self.items),
-            )?
+            let imports = self.imports(&mut store)?;
+            Instantiator::new(&mut store, &self.module, imports)?
         };
         instantiator.run(&mut store.as_context_mut())
     }
@@ -988,20 +1282,108 @@ impl<T> InstancePre<T> {
         // For the unsafety here see above
         let mut i = unsafe {
             let mut store = store.as_context_mut().opaque();
-            self.ensure_comes_from_same_store(&store)?;
-            Instantiator::new(
-                &mut store,
-                &self.module,
-                ImportSource::Definitions(&self.items),
-            )?
+            let imports = self.imports(&mut store)?;
+            Instantiator::new(&mut store, &self.module, imports)?
+        };
+        i.run_async(&mut store.as_context_mut()).await
+    }
+
+    /// Same as [`InstancePre::instantiate`], except that it may also be used
+    /// with async stores from within a host function, which is already running
+    /// on the store's fiber. Start functions run on that fiber, so they may
+    /// call async imports.
+    pub(crate) fn instantiate_nested(
+        &self,
+        mut store: impl AsContextMut<Data = T>,
+    ) -> Result<Instance> {
+        // For the unsafety here see above
+        let mut i = unsafe {
+            let mut store = store.as_context_mut().opaque();
+            let imports = self.imports(&mut store)?;
+            Instantiator::new(&mut store, &self.module, imports)?
+        };
+        i.run_inline(&mut store.as_context_mut())
+    }
+
+    /// Same as [`InstancePre::instantiate`], except that the module's start
+    /// function is run within `budget`.
+    ///
+    /// See [`Instance::new_with_budget`] for more information.
+    pub fn instantiate_with_budget(
+        &self,
+        mut store: impl AsContextMut<Data = T>,
+        budget: &StartBudget,
+    ) -> Result<Instance> {
+        // For the unsafety here see `instantiate`
+        let mut instantiator = unsafe {
+            let mut store = store.as_context_mut().opaque();
+            let imports = self.imports(&mut store)?;
+            Instantiator::new(&mut store, &self.module, imports)?.with_budget(&store, budget)?
+        };
+        instantiator.run(&mut store.as_context_mut())
+    }
+
+    /// Same as [`InstancePre::instantiate_async`], except that the module's
+    /// start function is run within `budget`.
+    ///
+    /// See [`Instance::new_with_budget`] for more information.
+    #[cfg(feature = "async")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    pub async fn instantiate_async_with_budget(
+        &self,
+        mut store: impl AsContextMut<Data = T>,
+        budget: &StartBudget,
+    ) -> Result<Instance>
+    where
+        T: Send,
+    {
+        // For the unsafety here see `instantiate`
+        let mut i = unsafe {
+            let mut store = store.as_context_mut().opaque();
+            let imports = self.imports(&mut store)?;
+            Instantiator::new(&mut store, &self.module, imports)?.with_budget(&store, budget)?
         };
         i.run_async(&mut store.as_context_mut()).await
     }
 
+    /// Returns where to find the imports for instantiating this module within
+    /// `store`.
+    ///
+    /// The first time this is called for a store the imports are resolved
+    /// within it, and if possible the result is saved in the store so that
+    /// later instantiations can copy it rather than resolving each import
+    /// again.
+    ///
+    /// # Unsafety
+    ///
+    /// The `T` of `store` must match this `InstancePre`, see
+    /// `Instantiator::new`.
+    unsafe fn imports(&self, store: &mut StoreOpaque<'_>) -> Result<ImportSource<'_>> {
+        // Templates are only saved once the imports were found to come from
+        // this store.
+        if let Some(template) = store.import_template(&self.items) {
+            return Ok(ImportSource::Template(template));
+        }
+        self.ensure_comes_from_same_store(store)?;
+        if !self.reuse_imports {
+            return Ok(ImportSource::Definitions(&self.items));
+        }
+        let mut builder = ImportsBuilder::new(&self.module, ImportSource::Definitions(&[]));
+        for item in self.items.iter() {
+            let item = item.to_extern(store);
+            builder.push(item, store);
+        }
+        let template = Arc::new(builder.template());
+        store.insert_import_template(&self.items, template.clone());
+        Ok(ImportSource::Template(template))
+    }
+
     fn ensure_comes_from_same_store(&self, store: &StoreOpaque<'_>) -> Result<()> {
         for import in self.items.iter() {
             if !import.comes_from_same_store(store) {
-                bail!("cross-`Store` instantiation is not currently supported");
+                return Err(usage_error(
+                    "cross-`Store` instantiation is not currently supported",
+                ));
             }
         }
         Ok(())
@@ -1011,7 +1393,9 @@ impl<T> InstancePre<T> {
 fn typecheck_externs(store: &mut StoreOpaque, module: &Module, imports: &[Extern]) -> Result<()> {
     for import in imports {
         if !import.comes_from_same_store(store) {
-            bail!("cross-`Store` instantiation is not currently supported");
+            return Err(usage_error(
+                "cross-`Store` instantiation is not currently supported",
+            ));
         }
     }
     typecheck(store, module, imports, |cx, ty, item| cx.extern_(ty, item))
@@ -1020,7 +1404,9 @@ fn typecheck_externs(store: &mut StoreOpaque, module: &Module, imports: &[Extern
 fn typecheck_defs(store: &mut StoreOpaque, module: &Module, imports: &[Definition]) -> Result<()> {
     for import in imports {
         if !import.comes_from_same_store(store) {
-            bail!("cross-`Store` instantiation is not currently supported");
+            return Err(usage_error(
+                "cross-`Store` instantiation is not currently supported",
+            ));
         }
     }
     typecheck(store, module, imports, |cx, ty, item| {
@@ -1037,7 +1423,10 @@ fn typecheck<I>(
     let env_module = module.compiled_module().module();
     let expected = env_module.imports().count();
     if expected != imports.len() {
-        bail!("expected {} imports, found {}", expected, imports.len());
+        return Err(crate::Error::msg(
+            ErrorKind::IncompatibleImport,
+            format_args!("expected {} imports, found {}", expected, imports.len()),
+        ));
     }
     let cx = matching::MatchCx {
         signatures: module.signatures(),
@@ -1046,13 +1435,20 @@ fn typecheck<I>(
         engine: store.engine(),
     };
     for ((name, field, expected_ty), actual) in env_module.imports().zip(imports) {
-        check(&cx, &expected_ty, actual).with_context(|| {
-            let extra = match field {
-                Some(name) => format!("::{}", name),
-                None => String::new(),
-            };
-            format!("incompatible import type for `{}{}`", name, extra)
+        check(&cx, &expected_ty, actual).map_err(|e| {
+            crate::Error::context(
+                ErrorKind::IncompatibleImport,
+                e,
+                format_args!(
+                    "incompatible import type for `{}`",
+                    crate::linker::describe_import(name, field)
+                ),
+            )
         })?;
     }
     Ok(())
 }
+
+fn usage_error(msg: &'static str) -> Error {
+    crate::Error::msg(ErrorKind::Usage, msg)
+}
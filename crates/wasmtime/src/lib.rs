@@ -245,7 +245,10 @@
 //!   module.
 //!
 //! * `parallel-compilation` - Enabled by default, this feature enables support
-//!   for compiling functions of a module in parallel with `rayon`.
+//!   for compiling functions of a module in parallel with `rayon`. This uses
+//!   the global `rayon` thread pool, unless
+//!   [`Config::max_concurrent_compilations`] is configured, in which case each
+//!   engine has a pool of its own.
 //!
 //! * `async` - Enabled by default, this feature enables APIs and runtime
 //!   support for defining asynchronous host functions and calling WebAssembly
@@ -268,6 +271,20 @@
 //!   all architectures for both the JIT compiler and the `wasmtime compile` CLI
 //!   command.
 //!
+//! * `bindgen` - Not enabled by default. This feature adds the
+//!   [`import_module!`] and [`exports!`] macros, which generate typed host
+//!   bindings for a module's imports and exports from a list of function
+//!   signatures.
+//!
+//! * `raw-funcs` - Not enabled by default. This feature adds
+//!   [`Func::from_raw_parts`], which creates host functions from pointers to
+//!   native code following Wasmtime's internal calling convention, which isn't
+//!   stable across releases.
+//!
+//! * `gc-diagnostics` - Not enabled by default. This feature adds
+//!   [`Store::gc_diagnostics`], which reports the stack maps the garbage
+//!   collector of `externref`s uses, for debugging embeddings which trip it up.
+//!
 //! ## Examples
 //!
 //! In addition to the examples below be sure to check out the [online embedding
@@ -369,6 +386,7 @@ mod func;
 
 mod config;
 mod engine;
+mod error;
 mod externals;
 mod instance;
 mod limits;
@@ -377,6 +395,7 @@ mod memory;
 mod module;
 mod r#ref;
 mod signatures;
+mod start_budget;
 mod store;
 mod trampoline;
 mod trap;
@@ -385,21 +404,42 @@ mod values;
 
 pub use crate::config::*;
 pub use crate::engine::*;
+pub use crate::error::{Error, ErrorKind};
 pub use crate::externals::*;
 pub use crate::func::*;
-pub use crate::instance::{Instance, InstancePre};
+pub use crate::instance::{Instance, InstanceId, InstancePre};
 pub use crate::limits::*;
 pub use crate::linker::*;
 pub use crate::memory::*;
-pub use crate::module::{FrameInfo, FrameSymbol, Module};
-pub use crate::r#ref::ExternRef;
+pub use crate::module::{
+    DataInitStrategy, DeserializeOptions, FeatureUsage, FrameInfo, FrameSymbol, InvalidModule,
+    LibCallKind, Module, PreparedModules, SegmentedInitReason, SymbolMap, SymbolMapEntry,
+};
+#[cfg(feature = "gc-diagnostics")]
+pub use crate::module::{FunctionStackMaps, GcDiagnostics, ModuleStackMaps};
+pub use crate::r#ref::{ExternRef, Scope, WeakExternRef};
+pub use crate::start_budget::{StartBudget, StartBudgetExceeded, StartLimit};
+#[cfg(feature = "async")]
+pub use crate::store::SuspendedAccess;
 pub use crate::store::{
-    AsContext, AsContextMut, InterruptHandle, Store, StoreContext, StoreContextMut,
+    ActivationTimes, AsContext, AsContextMut, CallHook, CancellationToken, FuelDecision,
+    FuelExhausted, InterruptCause, InterruptHandle, Store, StoreContext, StoreContextMut, StoreId,
 };
 pub use crate::trap::*;
 pub use crate::types::*;
 pub use crate::values::*;
 
+#[cfg(feature = "bindgen")]
+#[cfg_attr(nightlydoc, doc(cfg(feature = "bindgen")))]
+pub use wasmtime_bindgen::{exports, import_module};
+
+/// Items used by code generated by macros in this crate. Not public API.
+#[cfg(feature = "bindgen")]
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+}
+
 cfg_if::cfg_if! {
     if #[cfg(all(target_os = "macos", not(feature = "posix-signals-on-macos")))] {
         // no extensions for macOS at this time
@@ -418,6 +458,8 @@ fn _assert_send_sync() {
     _assert::<Engine>();
     _assert::<Config>();
     _assert::<InterruptHandle>();
+    _assert::<InterruptCause>();
+    _assert::<CancellationToken>();
     _assert::<(Func, TypedFunc<(), ()>, Global, Table, Memory)>();
     _assert::<Instance>();
     _assert::<Module>();
@@ -428,6 +470,7 @@ fn _assert_send_sync() {
     _assert::<Linker<()>>();
     _assert::<Linker<*mut u8>>();
     _assert::<ExternRef>();
+    _assert::<WeakExternRef>();
     _assert::<InstancePre<()>>();
     _assert::<InstancePre<*mut u8>>();
 
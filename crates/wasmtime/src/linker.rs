@@ -2,12 +2,13 @@ use crate::func::HostFunc;
 use crate::instance::{InstanceData, InstancePre};
 use crate::store::StoreOpaque;
 use crate::{
-    AsContextMut, Caller, Engine, Extern, ExternType, Func, FuncType, ImportType, Instance,
-    IntoFunc, Module, Trap, Val,
+    AsContextMut, Caller, Engine, ErrorKind, Extern, ExternType, Func, FuncType, ImportType,
+    Instance, IntoFunc, Module, Trap, Val,
 };
-use anyhow::{anyhow, bail, Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
+use indexmap::map::{Entry, IndexMap};
 use log::warn;
-use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashMap;
 #[cfg(feature = "async")]
 use std::future::Future;
 use std::marker;
@@ -37,10 +38,28 @@ use std::sync::Arc;
 /// module and then has its own name. This basically follows the wasm standard
 /// for modularization.
 ///
+/// Some toolchains instead emit imports whose module name is empty, treating
+/// just the name as significant. Items for these can be defined in a separate
+/// single-level namespace with [`Linker::define_name`]. An import with an
+/// empty module name is resolved by whatever is defined under the empty
+/// module and its name with [`Linker::define`], if anything, and otherwise by
+/// whatever is defined under just its name with [`Linker::define_name`]. An
+/// import with a non-empty module name is never resolved by a single-level
+/// name.
+///
 /// Names in a `Linker` cannot be defined twice, but allowing duplicates by
 /// shadowing the previous definition can be controlled with the
 /// [`Linker::allow_shadowing`] method.
 ///
+/// Resolution is deterministic: an import is satisfied by whatever is defined
+/// under exactly its module and name, regardless of the import's type or of
+/// what else is defined in the `Linker`. Imports are resolved in the order
+/// they're declared in the module, as returned by [`Module::imports`]. A
+/// module may import the same module and name more than once, with the same
+/// or different types, and each of those imports is given the same definition.
+/// If that definition doesn't match the type of one of them then
+/// instantiation fails, reporting the first such import in declaration order.
+///
 /// ## Commands and Reactors
 ///
 /// The [`Linker`] type provides conveniences for working with WASI Commands and
@@ -76,7 +95,7 @@ pub struct Linker<T> {
     engine: Engine,
     string2idx: HashMap<Arc<str>, usize>,
     strings: Vec<Arc<str>>,
-    map: HashMap<ImportKey, Definition>,
+    map: IndexMap<ImportKey, Definition>,
     allow_shadowing: bool,
     allow_unknown_exports: bool,
     _marker: marker::PhantomData<fn() -> T>,
@@ -153,7 +172,7 @@ impl<T> Linker<T> {
     pub fn new(engine: &Engine) -> Linker<T> {
         Linker {
             engine: engine.clone(),
-            map: HashMap::new(),
+            map: IndexMap::new(),
             string2idx: HashMap::new(),
             strings: Vec::new(),
             allow_shadowing: false,
@@ -277,9 +296,33 @@ impl<T> Linker<T> {
     /// Same as [`Linker::define`], except only the name of the import is
     /// provided, not a module name as well.
     ///
-    /// This is only relevant when working with the module linking proposal
-    /// where one-level names are allowed (in addition to two-level names).
-    /// Otherwise this method need not be used.
+    /// Single-level names live in their own namespace, separate from items
+    /// defined with [`Linker::define`] under the empty module name. They
+    /// satisfy the single-level imports of the module linking proposal, and
+    /// imports with an empty module name which nothing was defined for with
+    /// [`Linker::define`], as described in the documentation on [`Linker`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// # let mut store = Store::new(&engine, ());
+    /// let mut linker = Linker::new(&engine);
+    /// linker.define_name("log", Func::wrap(&mut store, |x: i32| println!("{}", x)))?;
+    ///
+    /// let wat = r#"
+    ///     (module
+    ///         (import "" "log" (func $log (param i32)))
+    ///         (func (export "run") (call $log (i32.const 42)))
+    ///     )
+    /// "#;
+    /// let module = Module::new(&engine, wat)?;
+    /// linker.instantiate(&mut store, &module)?;
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn define_name(&mut self, name: &str, item: impl Into<Extern>) -> Result<&mut Self> {
         let key = self.import_key(name, None);
         self.insert(key, Definition::Extern(item.into()))?;
@@ -296,7 +339,7 @@ impl<T> Linker<T> {
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
     ) -> Result<&mut Self> {
-        let func = HostFunc::new(&self.engine, ty, func);
+        let func = HostFunc::try_new(&self.engine, ty, func)?;
         let key = self.import_key(module, Some(name));
         self.insert(key, Definition::HostFunc(Arc::new(func)))?;
         Ok(self)
@@ -419,13 +462,7 @@ impl<T> Linker<T> {
     ///
     /// Returns an error if the any item is redefined twice in this linker (for
     /// example the same `module_name` was already defined) and shadowing is
-    /// disallowed, or if `instance` comes from a different
-    /// [`Store`](crate::Store) than this [`Linker`] originally was created
-    /// with.
-    ///
-    /// # Panics
-    ///
-    /// Panics if `instance` does not belong to `store`.
+    /// disallowed, or if `instance` does not belong to `store`.
     ///
     /// # Examples
     ///
@@ -465,6 +502,9 @@ impl<T> Linker<T> {
         module_name: &str,
         instance: Instance,
     ) -> Result<&mut Self> {
+        if !instance.comes_from_same_store(&store.as_context_mut().opaque()) {
+            bail!("cross-`Store` instances are not supported");
+        }
         for export in instance.exports(store.as_context_mut()) {
             let key = self.import_key(module_name, Some(export.name()));
             self.insert(key, Definition::Extern(export.into_extern()))?;
@@ -599,19 +639,110 @@ impl<T> Linker<T> {
         match ModuleKind::categorize(module)? {
             ModuleKind::Command => self.command(store, module_name, module),
             ModuleKind::Reactor => {
-                let instance = self.instantiate(&mut store, &module)?;
+                let instance = self.instantiate_reactor(&mut store, module)?;
+                self.instance(store, module_name, instance)
+            }
+        }
+    }
 
-                if let Some(export) = instance.get_export(&mut store, "_initialize") {
-                    if let Extern::Func(func) = export {
-                        func.typed::<(), (), _>(&store)
-                            .and_then(|f| f.call(&mut store, ()).map_err(Into::into))
-                            .context("calling the Reactor initialization function")?;
-                    }
-                }
+    /// Instantiates a Reactor, calling its initialization function if it has
+    /// one.
+    fn instantiate_reactor(
+        &self,
+        mut store: impl AsContextMut<Data = T>,
+        module: &Module,
+    ) -> Result<Instance> {
+        let instance = self.instantiate(&mut store, module)?;
 
-                self.instance(store, module_name, instance)
+        if let Some(export) = instance.get_export(&mut store, "_initialize") {
+            if let Extern::Func(func) = export {
+                func.typed::<(), (), _>(&store)
+                    .and_then(|f| f.call(&mut store, ()).map_err(Into::into))
+                    .context("calling the Reactor initialization function")?;
             }
         }
+
+        Ok(instance)
+    }
+
+    /// Instantiates a group of Reactor modules which import from one another,
+    /// in an order which satisfies their dependencies.
+    ///
+    /// Each entry of `modules` is a module along with the name its instance is
+    /// registered under, as with [`Linker::instance`]. A module depends on
+    /// another when it imports an item from the other's name which the other
+    /// module exports. Modules are instantiated so that their dependencies
+    /// come first, and each is initialized as a [Reactor] before its exports
+    /// are defined in this linker. Imports which no module in the group
+    /// provides are resolved from the items already defined in this linker.
+    ///
+    /// The returned instances are in the same order as `modules`.
+    ///
+    /// [Reactor]: https://github.com/WebAssembly/WASI/blob/master/design/application-abi.md#current-unstable-abi
+    ///
+    /// # Errors
+    ///
+    /// Returns an error without instantiating anything if the modules'
+    /// imports form a cycle, naming the modules along it, or if more than one
+    /// module could provide an import, naming both. Also returns an error if
+    /// a module is a Command, or for any of the reasons that
+    /// [`Linker::module`] fails, in which case the instances created so far
+    /// remain defined in this linker.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any item used to instantiate the provided modules is not
+    /// owned by `store`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// # let mut store = Store::new(&engine, ());
+    /// let mut linker = Linker::new(&engine);
+    ///
+    /// let app = Module::new(
+    ///     &engine,
+    ///     r#"(module (import "lib" "f" (func)) (func (export "run") call 0))"#,
+    /// )?;
+    /// let lib = Module::new(&engine, r#"(module (func (export "f")))"#)?;
+    ///
+    /// // `lib` is instantiated first, even though it's listed second.
+    /// let instances = linker.instantiate_graph(&mut store, &[("app", &app), ("lib", &lib)])?;
+    /// let run = instances[0].get_typed_func::<(), (), _>(&mut store, "run")?;
+    /// run.call(&mut store, ())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instantiate_graph(
+        &mut self,
+        mut store: impl AsContextMut<Data = T>,
+        modules: &[(&str, &Module)],
+    ) -> Result<Vec<Instance>> {
+        for (name, module) in modules {
+            if let ModuleKind::Command = ModuleKind::categorize(module)
+                .with_context(|| format!("failed to categorize module `{}`", name))?
+            {
+                bail!(
+                    "module `{}` is a Command and can't be part of a graph",
+                    name
+                );
+            }
+        }
+
+        let order = instantiation_order(modules)?;
+        let mut instances = vec![None; modules.len()];
+        for i in order {
+            let (name, module) = modules[i];
+            let instance = self
+                .instantiate_reactor(&mut store, module)
+                .with_context(|| format!("failed to instantiate module `{}`", name))?;
+            self.instance(&mut store, name, instance)?;
+            instances[i] = Some(instance);
+        }
+        Ok(instances.into_iter().map(Option::unwrap).collect())
     }
 
     fn command(
@@ -632,7 +763,11 @@ impl<T> Linker<T> {
                     func_ty.clone(),
                     move |mut caller, params, results| {
                         // Create a new instance for this command execution.
-                        let instance = instance_pre.instantiate(&mut caller)?;
+                        // This host function is already running on the
+                        // store's fiber if it's async, so instantiation and
+                        // the call below happen on it too, and the command's
+                        // start function and body can use async imports.
+                        let instance = instance_pre.instantiate_nested(&mut caller)?;
 
                         // `unwrap()` everything here because we know the instance contains a
                         // function export with the given name and signature because we're
@@ -642,7 +777,7 @@ impl<T> Linker<T> {
                             .unwrap()
                             .into_func()
                             .unwrap()
-                            .call(&mut caller, params)
+                            .call_nested(&mut caller, params)
                             .map_err(|error| error.downcast::<Trap>().unwrap())?;
 
                         // Copy the return values into the output slice.
@@ -750,11 +885,11 @@ impl<T> Linker<T> {
         match self.map.entry(key) {
             Entry::Occupied(_) if !self.allow_shadowing => {
                 let module = &self.strings[key.module];
-                let desc = match self.strings.get(key.name) {
-                    Some(name) => format!("{}::{}", module, name),
-                    None => module.to_string(),
-                };
-                bail!("import of `{}` defined twice", desc)
+                let name = self.strings.get(key.name).map(|s| &**s);
+                bail!(
+                    "import of `{}` defined twice",
+                    describe_import(module, name)
+                )
             }
             Entry::Occupied(mut o) => {
                 o.insert(item);
@@ -806,7 +941,9 @@ impl<T> Linker<T> {
     ///
     /// This method can fail because an import may not be found, or because
     /// instantiation itself may fail. For information on instantiation
-    /// failures see [`Instance::new`].
+    /// failures see [`Instance::new`]. It also fails if `store` has async
+    /// support enabled, in which case [`Linker::instantiate_async`] must be
+    /// used instead.
     ///
     /// # Panics
     ///
@@ -838,11 +975,28 @@ impl<T> Linker<T> {
         mut store: impl AsContextMut<Data = T>,
         module: &Module,
     ) -> Result<Instance> {
-        self.instantiate_pre(&mut store, module)?.instantiate(store)
+        if store.as_context().async_support() {
+            return Err(crate::Error::msg(
+                ErrorKind::Usage,
+                "cannot use `Linker::instantiate` with a store which has async support \
+                 enabled, use `Linker::instantiate_async` instead",
+            ));
+        }
+        self.instantiate_pre(&mut store, module)?
+            .single_use()
+            .instantiate(store)
     }
 
     /// Attempts to instantiate the `module` provided. This is the same as
     /// [`Linker::instantiate`], except for async `Store`s.
+    ///
+    /// The module's start function runs on the store's fiber, so it may call
+    /// async host functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any item used to instantiate `module` is not owned by
+    /// `store`, or if `store` doesn't have async support enabled.
     #[cfg(feature = "async")]
     #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
     pub async fn instantiate_async(
@@ -854,6 +1008,7 @@ impl<T> Linker<T> {
         T: Send,
     {
         self.instantiate_pre(&mut store, module)?
+            .single_use()
             .instantiate_async(store)
             .await
     }
@@ -922,19 +1077,25 @@ impl<T> Linker<T> {
     }
 
     fn link_error(&self, import: &ImportType) -> Error {
-        let desc = match import.name() {
-            Some(name) => format!("{}::{}", import.module(), name),
-            None => import.module().to_string(),
-        };
-        anyhow!("unknown import: `{}` has not been defined", desc)
+        crate::Error::msg(
+            ErrorKind::IncompatibleImport,
+            format_args!(
+                "unknown import: `{}` has not been defined",
+                describe_import(import.module(), import.name())
+            ),
+        )
     }
 
-    /// Returns an iterator over all items defined in this `Linker`, in
-    /// arbitrary order.
+    /// Returns an iterator over all items defined in this `Linker`, in the
+    /// order in which they were first defined.
+    ///
+    /// Redefining an item when shadowing is allowed replaces the item but
+    /// keeps its original position in this order.
     ///
     /// The iterator returned will yield 3-tuples where the first two elements
     /// are the module name and item name for the external item, and the third
-    /// item is the item itself that is defined.
+    /// item is the item itself that is defined. Items defined with
+    /// [`Linker::define_name`] are yielded with an empty module name.
     ///
     /// Note that multiple `Extern` items may be defined for the same
     /// module/name pair.
@@ -944,9 +1105,13 @@ impl<T> Linker<T> {
     ) -> impl Iterator<Item = (&str, &str, Extern)> + 'p {
         self.map.iter().map(move |(key, item)| {
             let mut store = store.as_context_mut().opaque();
+            let (module, name) = match self.strings.get(key.name) {
+                Some(name) => (&*self.strings[key.module], &**name),
+                None => ("", &*self.strings[key.module]),
+            };
             (
-                &*self.strings[key.module],
-                &*self.strings[key.name],
+                module,
+                name,
                 // Should be safe since `T` is connecting the linker and store
                 unsafe { item.to_extern(&mut store) },
             )
@@ -999,9 +1164,16 @@ impl<T> Linker<T> {
             return Some(item.clone());
         }
 
-        if import.name().is_some() {
-            return None;
-        }
+        let name = match import.name() {
+            // Imports with an empty module name fall back to single-level
+            // names, but only if nothing is defined under the empty module
+            // name itself.
+            Some(name) if import.module().is_empty() => {
+                return self._get(name, None).cloned();
+            }
+            Some(_) => return None,
+            None => import.module(),
+        };
 
         if let ExternType::Instance(t) = import.ty() {
             // This is a key location where the module linking proposal is
@@ -1021,7 +1193,7 @@ impl<T> Linker<T> {
             // suffice.
             let mut map = indexmap::IndexMap::new();
             for export in t.exports() {
-                let item = self._get(import.module(), Some(export.name()))?;
+                let item = self._get(name, Some(export.name()))?;
                 map.insert(export.name().to_string(), item.clone());
             }
             return Some(Definition::Instance(Arc::new(map)));
@@ -1108,6 +1280,91 @@ enum ModuleKind {
     Reactor,
 }
 
+/// Computes the order in which to instantiate the `modules` given to
+/// [`Linker::instantiate_graph`], as indices into `modules`.
+fn instantiation_order(modules: &[(&str, &Module)]) -> Result<Vec<usize>> {
+    // The modules in the graph which each module imports from.
+    let mut deps = Vec::with_capacity(modules.len());
+    for (name, module) in modules {
+        let mut module_deps = Vec::new();
+        for import in module.imports() {
+            let field = match import.name() {
+                Some(field) => field,
+                None => continue,
+            };
+            let mut providers = modules.iter().enumerate().filter(|(_, (other, m))| {
+                *other == import.module() && m.get_export(field).is_some()
+            });
+            let provider = match providers.next() {
+                Some((i, _)) => i,
+                None => continue,
+            };
+            if let Some((j, _)) = providers.next() {
+                bail!(
+                    "import `{}::{}` of module `{}` is ambiguous: it's exported by both \
+                     module #{} and module #{} named `{}`",
+                    import.module(),
+                    field,
+                    name,
+                    provider,
+                    j,
+                    import.module(),
+                );
+            }
+            if !module_deps.contains(&provider) {
+                module_deps.push(provider);
+            }
+        }
+        deps.push(module_deps);
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        i: usize,
+        modules: &[(&str, &Module)],
+        deps: &[Vec<usize>],
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<()> {
+        match marks[i] {
+            Mark::Done => return Ok(()),
+            Mark::Visiting => {
+                let start = path.iter().position(|&j| j == i).unwrap();
+                let cycle = path[start..]
+                    .iter()
+                    .chain(Some(&i))
+                    .map(|&j| format!("`{}`", modules[j].0))
+                    .collect::<Vec<_>>();
+                bail!("modules have a cyclic dependency: {}", cycle.join(" -> "));
+            }
+            Mark::Unvisited => {}
+        }
+        marks[i] = Mark::Visiting;
+        path.push(i);
+        for &dep in deps[i].iter() {
+            visit(dep, modules, deps, marks, path, order)?;
+        }
+        path.pop();
+        marks[i] = Mark::Done;
+        order.push(i);
+        Ok(())
+    }
+
+    let mut marks = vec![Mark::Unvisited; modules.len()];
+    let mut order = Vec::with_capacity(modules.len());
+    for i in 0..modules.len() {
+        visit(i, modules, &deps, &mut marks, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
 impl ModuleKind {
     /// Determine whether the given module is a Command or a Reactor.
     fn categorize(module: &Module) -> Result<ModuleKind> {
@@ -1140,3 +1397,13 @@ impl ModuleKind {
         }
     }
 }
+
+/// Renders the name of an import for error messages, leaving out the module
+/// name if it's empty or the import has a single-level name.
+pub(crate) fn describe_import(module: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) if module.is_empty() => name.to_string(),
+        Some(name) => format!("{}::{}", module, name),
+        None => module.to_string(),
+    }
+}
@@ -1,8 +1,12 @@
-use crate::store::{StoreData, StoreOpaque, Stored};
+use crate::store::{StoreData, StoreId, StoreOpaque, Stored};
 use crate::trampoline::generate_memory_export;
-use crate::{AsContext, AsContextMut, MemoryType, StoreContext, StoreContextMut};
+use crate::{AsContext, AsContextMut, MemoryType, StoreContext, StoreContextMut, Trap};
 use anyhow::{bail, Result};
+use std::convert::TryInto;
+use std::mem;
+use std::ops::Range;
 use std::slice;
+use std::time::Duration;
 
 /// Error for out of bounds [`Memory`] access.
 #[derive(Debug)]
@@ -20,6 +24,100 @@ impl std::fmt::Display for MemoryAccessError {
 
 impl std::error::Error for MemoryAccessError {}
 
+/// A load or store by WebAssembly of a virtual page of a [`Memory`], which is
+/// passed to the handler configured with
+/// [`Store::memory_access_handler`](crate::Store::memory_access_handler).
+///
+/// See [`Memory::register_virtual_pages`] for more information.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MemoryAccessFault {
+    /// The memory which was accessed.
+    pub memory: Memory,
+    /// The byte address of the access, including the instruction's constant
+    /// offset.
+    pub address: u64,
+    /// The number of bytes accessed, which is 1, 2, 4 or 8.
+    pub size: u8,
+    /// Whether the access is a load or a store.
+    pub kind: MemoryAccessKind,
+}
+
+/// The kind of a [`MemoryAccessFault`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryAccessKind {
+    /// A load, which needs to be resolved with [`FaultResolution::Supply`].
+    Load,
+    /// A store of the given value, zero-extended to 64 bits, which needs to be
+    /// resolved with [`FaultResolution::Accept`].
+    ///
+    /// Floating-point values are stored as their bits.
+    Store(u64),
+}
+
+/// How a [`MemoryAccessFault`] is resolved by a memory access handler.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultResolution {
+    /// Completes a load with the given value, of which only the low
+    /// [`MemoryAccessFault::size`] bytes are used, and continues execution.
+    Supply(u64),
+    /// Completes a store and continues execution.
+    Accept,
+    /// Raises an out-of-bounds trap, as if the access were outside of the
+    /// memory.
+    ///
+    /// Resolving a load with `Accept` or a store with `Supply` also traps.
+    Trap,
+}
+
+/// A scalar type which can be copied in bulk between the host and linear
+/// memory with [`Memory::read_slice_as`] and [`Memory::write_slice_as`].
+///
+/// Values are stored in linear memory in little-endian byte order, as wasm
+/// loads and stores them, whatever the host's byte order. This trait is sealed
+/// and implemented for the integer and floating-point primitives.
+pub trait MemoryElement: sealed::Sealed + Copy {}
+
+mod sealed {
+    pub trait Sealed {
+        /// Converts from exactly `size_of::<Self>()` little-endian bytes.
+        fn read_le(bytes: &[u8]) -> Self;
+        /// Converts into exactly `size_of::<Self>()` little-endian bytes.
+        fn write_le(self, bytes: &mut [u8]);
+    }
+}
+
+macro_rules! memory_elements {
+    ($($t:ty)*) => ($(
+        impl sealed::Sealed for $t {
+            #[inline]
+            fn read_le(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+            #[inline]
+            fn write_le(self, bytes: &mut [u8]) {
+                bytes.copy_from_slice(&self.to_le_bytes());
+            }
+        }
+        impl MemoryElement for $t {}
+    )*)
+}
+
+memory_elements!(u8 i8 u16 i16 u32 i32 u64 i64 f32 f64);
+
+/// The result of [`Memory::atomic_wait32`], mirroring the values returned by
+/// the `memory.atomic.wait32` instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitResult {
+    /// The waiter was woken by a notification.
+    Ok,
+    /// The value in memory didn't match the expected value, so no wait
+    /// happened.
+    Mismatch,
+    /// The timeout elapsed before the waiter was notified.
+    TimedOut,
+}
+
 /// A WebAssembly linear memory.
 ///
 /// WebAssembly memories represent a contiguous array of bytes that have a size
@@ -201,6 +299,16 @@ impl Memory {
     /// The `store` argument will be the owner of the returned [`Memory`]. All
     /// WebAssembly memory is initialized to zero.
     ///
+    /// # Errors
+    ///
+    /// Returns an error if the minimum size of `ty` is larger than its
+    /// maximum or than [`Config::max_memory_pages`], if either is more
+    /// than 65536 pages, or if the memory can't be allocated. Shared memories
+    /// additionally need a maximum and [`Config::wasm_threads`] to be enabled.
+    ///
+    /// [`Config::max_memory_pages`]: crate::Config::max_memory_pages
+    /// [`Config::wasm_threads`]: crate::Config::wasm_threads
+    ///
     /// # Examples
     ///
     /// ```
@@ -306,6 +414,131 @@ impl Memory {
         Ok(())
     }
 
+    /// Appends `len` bytes of memory at the given offset to `buffer`.
+    ///
+    /// This is like [`Memory::read`], except that the bytes are copied
+    /// straight into `buffer`'s spare capacity, without zeroing space for them
+    /// first. The bounds are checked once, so this is suited to copying large
+    /// buffers out of memory.
+    ///
+    /// If `offset + len` exceeds the current memory capacity, then `buffer` is
+    /// left untouched and a [`MemoryAccessError`] is returned.
+    ///
+    /// Note that the store is borrowed for the duration of the copy, and
+    /// stores are single-threaded, so wasm can't modify memory while it's
+    /// being copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn read_into(
+        &self,
+        store: impl AsContext,
+        offset: usize,
+        buffer: &mut Vec<u8>,
+        len: usize,
+    ) -> Result<(), MemoryAccessError> {
+        let store = store.as_context();
+        let slice = self.slice_at(&store, offset, Some(len))?;
+        buffer.extend_from_slice(slice);
+        Ok(())
+    }
+
+    /// Reads `count` values of type `T` from memory at the given offset.
+    ///
+    /// The values are converted from little-endian, wasm's byte order, so
+    /// this returns the same values as `count` consecutive wasm loads of type
+    /// `T` would. `offset` doesn't need to be aligned for `T`.
+    ///
+    /// The bounds are checked once for the whole range of `count *
+    /// size_of::<T>()` bytes. If that range doesn't fit in the current memory
+    /// capacity, or its size overflows, a [`MemoryAccessError`] is returned.
+    ///
+    /// As with [`Memory::read_into`], wasm can't modify memory while it's
+    /// being read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::<()>::default();
+    /// let memory = Memory::new(&mut store, MemoryType::new(Limits::new(1, None)))?;
+    /// memory.write_slice_as(&mut store, 3, &[1u32, 0x0102_0304])?;
+    /// assert_eq!(memory.data(&store)[7..11], [4, 3, 2, 1]);
+    /// assert_eq!(memory.read_slice_as::<u32>(&store, 3, 2)?, [1, 0x0102_0304]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_slice_as<T: MemoryElement>(
+        &self,
+        store: impl AsContext,
+        offset: usize,
+        count: usize,
+    ) -> Result<Vec<T>, MemoryAccessError> {
+        let store = store.as_context();
+        let len = count.checked_mul(mem::size_of::<T>());
+        let slice = self.slice_at(&store, offset, len)?;
+        let mut values = Vec::with_capacity(count);
+        values.extend(slice.chunks_exact(mem::size_of::<T>()).map(T::read_le));
+        Ok(values)
+    }
+
+    /// Writes `values` to memory at the given offset.
+    ///
+    /// The values are converted to little-endian, wasm's byte order, so this
+    /// stores the same bytes as consecutive wasm stores of type `T` would.
+    /// `offset` doesn't need to be aligned for `T`. Untyped bytes are written
+    /// with [`Memory::write`].
+    ///
+    /// The bounds are checked once for the whole range, and if it doesn't fit
+    /// in the current memory capacity none of `values` is written and a
+    /// [`MemoryAccessError`] is returned.
+    ///
+    /// As with [`Memory::read_into`], wasm can't observe memory while it's
+    /// being written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn write_slice_as<T: MemoryElement>(
+        &self,
+        mut store: impl AsContextMut,
+        offset: usize,
+        values: &[T],
+    ) -> Result<(), MemoryAccessError> {
+        let mut context = store.as_context_mut();
+        // `values` is already in memory, so its size can't overflow.
+        let len = values.len() * mem::size_of::<T>();
+        let slice = self
+            .data_mut(&mut context)
+            .get_mut(offset..)
+            .and_then(|s| s.get_mut(..len))
+            .ok_or(MemoryAccessError { _private: () })?;
+        for (bytes, value) in slice.chunks_exact_mut(mem::size_of::<T>()).zip(values) {
+            value.write_le(bytes);
+        }
+        Ok(())
+    }
+
+    /// Returns the `len` bytes of memory at `offset`, where a `len` of `None`
+    /// means that it overflowed.
+    fn slice_at<'a, T>(
+        &self,
+        store: &'a StoreContext<'_, T>,
+        offset: usize,
+        len: Option<usize>,
+    ) -> Result<&'a [u8], MemoryAccessError> {
+        self.data(store)
+            .get(offset..)
+            .and_then(|s| s.get(..len?))
+            .ok_or(MemoryAccessError { _private: () })
+    }
+
     /// Returns this memory as a native Rust slice.
     ///
     /// Note that this method will consider the entire store context provided as
@@ -315,8 +548,11 @@ impl Memory {
     ///
     /// Panics if this memory doesn't belong to `store`.
     pub fn data<'a, T: 'a>(&self, store: impl Into<StoreContext<'a, T>>) -> &'a [u8] {
+        self.data_in(store.into().0.store_data())
+    }
+
+    pub(crate) fn data_in<'a>(&self, store: &'a StoreData) -> &'a [u8] {
         unsafe {
-            let store = store.into();
             let definition = *store[self.0].definition;
             slice::from_raw_parts(definition.base, definition.current_length as usize)
         }
@@ -463,6 +699,128 @@ impl Memory {
         }
     }
 
+    /// Registers the WebAssembly pages in `pages` of this memory as virtual.
+    ///
+    /// Loads and stores by WebAssembly which touch any byte of a virtual page
+    /// don't access the memory. Instead they're passed to the handler
+    /// configured with
+    /// [`Store::memory_access_handler`](crate::Store::memory_access_handler),
+    /// as a [`MemoryAccessFault`], and execution continues after the access
+    /// once the handler resolves it. This can be used to emulate devices which
+    /// are mapped into linear memory. If the store has no handler then
+    /// accesses to virtual pages trap.
+    ///
+    /// Virtual pages may lie beyond the current size of the memory, in which
+    /// case accesses to them are handled rather than trapping. Accesses which
+    /// don't touch a virtual page behave as usual, including trapping if
+    /// they're out of bounds. Host accesses through methods like
+    /// [`Memory::read`], and bulk memory instructions like `memory.copy`,
+    /// always operate on the memory itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`Config::memory_access_hooks`] isn't enabled, if
+    /// this memory is a 64-bit memory, or if `pages` doesn't lie within the
+    /// 4 GiB a 32-bit memory can address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    ///
+    /// [`Config::memory_access_hooks`]: crate::Config::memory_access_hooks
+    pub fn register_virtual_pages(
+        &self,
+        mut store: impl AsContextMut,
+        pages: Range<u64>,
+    ) -> Result<()> {
+        let mut store = store.as_context_mut().opaque();
+        if !store.engine().config().tunables.memory_access_hooks {
+            bail!("memory access hooks aren't enabled in this store's `Config`");
+        }
+        if self.wasmtime_ty(store.store_data()).memory64 {
+            bail!("virtual pages aren't supported for 64-bit memories");
+        }
+        if pages.start > pages.end || pages.end > u64::from(wasmtime_environ::WASM_MAX_PAGES) {
+            bail!(
+                "virtual pages {}..{} aren't within the pages of a 32-bit memory",
+                pages.start,
+                pages.end
+            );
+        }
+        let page_size = u64::from(wasmtime_environ::WASM_PAGE_SIZE);
+        let bytes = pages.start * page_size..pages.end * page_size;
+        let definition = store[self.0].definition;
+        store.register_virtual_pages(*self, definition, bytes);
+        Ok(())
+    }
+
+    /// Performs `memory.atomic.notify` on this memory from the host, waking at
+    /// most `count` waiters on the 32-bit value at `addr`.
+    ///
+    /// Returns the number of waiters that were woken. Only shared memories
+    /// can have waiters, and a shared memory can only be used from the thread
+    /// using its store, so there is never anything to wake: this always
+    /// returns 0 when it succeeds.
+    ///
+    /// The address is checked the same way as the instruction checks it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a trap, like the instruction would, if `addr` isn't aligned to
+    /// 4 bytes or is out of bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn atomic_notify(&self, store: impl AsContext, addr: u64, count: u32) -> Result<u32, Trap> {
+        let definition = unsafe { &*store.as_context()[self.0].definition };
+        wasmtime_runtime::atomic_notify(definition, addr, count).map_err(Trap::from_runtime)
+    }
+
+    /// Performs `memory.atomic.wait32` on this memory from the host, blocking
+    /// the current thread until the 32-bit value at `addr` is notified.
+    ///
+    /// If the value at `addr` isn't `expected` this returns
+    /// [`WaitResult::Mismatch`] immediately. Otherwise, since nothing else can
+    /// reach this memory to notify the current thread, this sleeps for
+    /// `timeout` and returns [`WaitResult::TimedOut`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a trap, like the instruction would, if `addr` isn't aligned to
+    /// 4 bytes or is out of bounds, or if this memory isn't shared. Also
+    /// returns a trap rather than blocking forever if `timeout` is `None` and
+    /// the value at `addr` is `expected`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn atomic_wait32(
+        &self,
+        store: impl AsContext,
+        addr: u64,
+        expected: u32,
+        timeout: Option<Duration>,
+    ) -> Result<WaitResult, Trap> {
+        let export = &store.as_context()[self.0];
+        let definition = unsafe { &*export.definition };
+        let shared = export.memory.memory.shared;
+        let result = wasmtime_runtime::atomic_wait(
+            definition,
+            addr,
+            4,
+            shared,
+            u64::from(expected),
+            timeout,
+        )
+        .map_err(Trap::from_runtime)?;
+        Ok(match result {
+            0 => WaitResult::Ok,
+            1 => WaitResult::Mismatch,
+            _ => WaitResult::TimedOut,
+        })
+    }
+
     fn wasmtime_memory(&self, store: &mut StoreOpaque<'_>) -> *mut wasmtime_runtime::Memory {
         unsafe {
             let export = &store[self.0];
@@ -494,6 +852,12 @@ impl Memory {
         }
     }
 
+    /// Returns the identifier of the [`Store`](crate::Store) which owns this
+    /// memory.
+    pub fn store_id(&self) -> StoreId {
+        self.0.store_id()
+    }
+
     pub(crate) fn comes_from_same_store(&self, store: &StoreOpaque) -> bool {
         store.store_data().contains(self.0)
     }
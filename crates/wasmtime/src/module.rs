@@ -2,23 +2,38 @@ use crate::{
     signatures::SignatureCollection,
     types::{ExportType, ExternType, ImportType},
 };
-use crate::{Engine, ModuleType};
+use crate::{Engine, ErrorKind, ModuleType, OptLevel};
 use anyhow::{bail, Context, Result};
+use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
-use wasmparser::Validator;
 #[cfg(feature = "cache")]
 use wasmtime_cache::ModuleCacheEntry;
 use wasmtime_environ::entity::PrimaryMap;
-use wasmtime_environ::wasm::ModuleIndex;
-use wasmtime_jit::{CompilationArtifacts, CompiledModule, TypeTables};
+use wasmtime_environ::wasm::{EntityIndex, ModuleIndex};
+use wasmtime_environ::{CompileError, Initializer};
+use wasmtime_jit::{CompilationArtifacts, CompiledModule, SetupError, TypeTables};
 
+#[cfg(feature = "cache")]
+mod cache_key;
+mod code_map;
+mod libcalls;
 mod registry;
 mod serialization;
 
-pub use registry::{FrameInfo, FrameSymbol, GlobalModuleRegistry, ModuleRegistry};
-pub use serialization::SerializedModule;
+pub use libcalls::LibCallKind;
+pub(crate) use registry::RegisteredModule;
+pub use registry::{
+    FrameInfo, FrameSymbol, GlobalModuleRegistry, ModuleRegistry, PreparedModules, SymbolMap,
+    SymbolMapEntry,
+};
+#[cfg(feature = "gc-diagnostics")]
+pub use registry::{FunctionStackMaps, GcDiagnostics, ModuleStackMaps};
+pub use serialization::{DeserializeOptions, SerializedModule};
+pub use wasmtime_environ::{DataInitStrategy, FeatureUsage, SegmentedInitReason};
 
 /// A compiled WebAssembly module, ready to be instantiated.
 ///
@@ -109,6 +124,14 @@ struct ModuleInner {
     types: Arc<TypeTables>,
     /// Registered shared signature for the module.
     signatures: Arc<SignatureCollection>,
+    /// Precomputed registration of this module's code with stores, or `None`
+    /// if it has no code.
+    registration: Option<Arc<RegisteredModule>>,
+    /// The name and contents of the custom sections which were excluded from
+    /// the cache key when this module was compiled.
+    custom_sections: Vec<(String, Vec<u8>)>,
+    /// The optimization level the module's code was compiled at.
+    opt_level: OptLevel,
 }
 
 impl Module {
@@ -180,7 +203,7 @@ impl Module {
     pub fn new(engine: &Engine, bytes: impl AsRef<[u8]>) -> Result<Module> {
         let bytes = bytes.as_ref();
         #[cfg(feature = "wat")]
-        let bytes = wat::parse_bytes(bytes)?;
+        let bytes = parse_wat(bytes)?;
         Self::from_binary(engine, &bytes)
     }
 
@@ -190,14 +213,54 @@ impl Module {
     /// See [`Module::new`] for other details.
     pub fn new_with_name(engine: &Engine, bytes: impl AsRef<[u8]>, name: &str) -> Result<Module> {
         let mut module = Self::new(engine, bytes.as_ref())?;
-        Arc::get_mut(&mut Arc::get_mut(&mut module.inner).unwrap().module)
+        let inner = Arc::get_mut(&mut module.inner).unwrap();
+        // The registration refers to the compiled module too, so it's redone
+        // once the name is set.
+        inner.registration = None;
+        Arc::get_mut(&mut inner.module)
             .unwrap()
             .module_mut()
             .expect("mutable module")
             .name = Some(name.to_string());
+        inner.registration = RegisteredModule::new(engine, &inner.module, &inner.signatures);
         Ok(module)
     }
 
+    /// Creates a new WebAssembly `Module` from the given in-memory `bytes`,
+    /// compiled at `opt_level` instead of the level configured with
+    /// [`Config::cranelift_opt_level`](crate::Config::cranelift_opt_level).
+    ///
+    /// This allows one [`Engine`] to hold modules compiled at different
+    /// optimization levels, for example compiling quickly at
+    /// [`OptLevel::None`] first and then recompiling hot modules at
+    /// [`OptLevel::SpeedAndSize`]. The optimization level is part of the key
+    /// of the module cache, so each level is cached separately, and it's
+    /// reported by [`Module::opt_level`].
+    ///
+    /// See [`Module::new`] for other details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let module = Module::new_with_opt_level(&engine, "(module)", OptLevel::None)?;
+    /// assert_eq!(module.opt_level(), OptLevel::None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_opt_level(
+        engine: &Engine,
+        bytes: impl AsRef<[u8]>,
+        opt_level: OptLevel,
+    ) -> Result<Module> {
+        let bytes = bytes.as_ref();
+        #[cfg(feature = "wat")]
+        let bytes = parse_wat(bytes)?;
+        Self::compile(engine, &bytes, opt_level)
+    }
+
     /// Creates a new WebAssembly `Module` from the contents of the given
     /// `file` on disk.
     ///
@@ -227,23 +290,15 @@ impl Module {
     /// # }
     /// ```
     pub fn from_file(engine: &Engine, file: impl AsRef<Path>) -> Result<Module> {
-        match Self::new(
-            engine,
-            &fs::read(&file).with_context(|| "failed to read input file")?,
-        ) {
-            Ok(m) => Ok(m),
-            Err(e) => {
-                cfg_if::cfg_if! {
-                    if #[cfg(feature = "wat")] {
-                        let mut e = e.downcast::<wat::Error>()?;
-                        e.set_path(file);
-                        bail!(e)
-                    } else {
-                        Err(e)
-                    }
-                }
-            }
-        }
+        let bytes = fs::read(&file).map_err(|e| {
+            crate::Error::context(ErrorKind::Io, e.into(), "failed to read input file")
+        })?;
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(&bytes).map_err(|mut e| {
+            e.set_path(file);
+            crate::Error::new(ErrorKind::Validation, e)
+        })?;
+        Self::from_binary(engine, &bytes)
     }
 
     /// Creates a new WebAssembly `Module` from the given in-memory `binary`
@@ -278,6 +333,66 @@ impl Module {
     /// # }
     /// ```
     pub fn from_binary(engine: &Engine, binary: &[u8]) -> Result<Module> {
+        Self::compile(engine, binary, engine.opt_level())
+    }
+
+    /// Creates a new WebAssembly `Module` from the given in-memory `bytes`,
+    /// without blocking the current thread.
+    ///
+    /// This is the same as [`Module::new`], except that if
+    /// [`Config::max_concurrent_compilations`](crate::Config::max_concurrent_compilations)
+    /// compilations are already running then this waits asynchronously for
+    /// one of them to finish, and that the module is then compiled on another
+    /// thread. This makes it suitable for calling from an async executor,
+    /// whose threads shouldn't be blocked.
+    ///
+    /// Compilations waiting here and in [`Module::new`] share one queue, and
+    /// are started in the order they arrived. Dropping the returned future
+    /// before the compilation starts gives up its place in the queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # async fn foo() -> anyhow::Result<()> {
+    /// let engine = Engine::new(Config::new().max_concurrent_compilations(4)?)?;
+    /// let module = Module::new_async(&engine, "(module (func))").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    pub async fn new_async(engine: &Engine, bytes: impl AsRef<[u8]>) -> Result<Module> {
+        let bytes = bytes.as_ref();
+        #[cfg(feature = "wat")]
+        let bytes = parse_wat(bytes)?;
+        let bytes = bytes.to_vec();
+
+        let slot = engine.compile_limit().acquire_async().await;
+        let opt_level = engine.opt_level();
+        let thread_engine = engine.clone();
+        engine
+            .compile_limit()
+            .spawn(move || {
+                let _slot = slot;
+                Self::compile_in_slot(&thread_engine, &bytes, opt_level)
+            })
+            .await
+    }
+
+    fn compile(engine: &Engine, binary: &[u8], opt_level: OptLevel) -> Result<Module> {
+        let _slot = engine.compile_limit().acquire();
+        Self::compile_in_slot(engine, binary, opt_level)
+    }
+
+    /// Compiles `binary`, having already taken one of the engine's
+    /// compilation slots.
+    fn compile_in_slot(engine: &Engine, binary: &[u8], opt_level: OptLevel) -> Result<Module> {
+        Self::compile_unclassified(engine, binary, opt_level)
+            .map_err(|e| crate::Error::classify(e, ErrorKind::Compilation))
+    }
+
+    fn compile_unclassified(engine: &Engine, binary: &[u8], opt_level: OptLevel) -> Result<Module> {
         // Check to see that the config's target matches the host
         let target = engine.config().isa_flags.triple();
         if *target != target_lexicon::Triple::host() {
@@ -293,28 +408,70 @@ impl Module {
 
         const USE_PAGED_MEM_INIT: bool = cfg!(all(feature = "uffd", target_os = "linux"));
 
-        cfg_if::cfg_if! {
-            if #[cfg(feature = "cache")] {
-                let (main_module, artifacts, types) = ModuleCacheEntry::new(
-                    "wasmtime",
-                    engine.cache_config(),
-                )
-                .get_data((engine.compiler(), binary), |(compiler, binary)| {
-                    CompilationArtifacts::build(compiler, binary, USE_PAGED_MEM_INIT)
-                })?;
-            } else {
-                let (main_module, artifacts, types) =
-                    CompilationArtifacts::build(engine.compiler(), binary, USE_PAGED_MEM_INIT)?;
-            }
-        };
+        let compiler = engine.compiler_for(opt_level);
+
+        // Functions compiled in parallel use the engine's thread pool, if
+        // there's a limit on the number of compilations.
+        let (main_module, artifacts, types, custom_sections) =
+            engine.compile_limit().install(|| -> Result<_> {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "cache")] {
+                        let ignored = &engine.config().cache_ignored_custom_sections;
+                        let (main_module, artifacts, types) = ModuleCacheEntry::new(
+                            "wasmtime",
+                            engine.cache_config(),
+                        )
+                        .get_data(
+                            (
+                                &*compiler,
+                                cache_key::CacheKeyBinary::new(binary, ignored),
+                                cache_key::Unhashed(engine.allocator()),
+                            ),
+                            |(compiler, key, allocator)| {
+                                CompilationArtifacts::build(
+                                    compiler,
+                                    key.binary,
+                                    USE_PAGED_MEM_INIT,
+                                    &|module| allocator.0.validate(module),
+                                )
+                            },
+                        )
+                        .map_err(|e| invalid_module(e, binary))?;
+                        // The contents of ignored sections in the cached
+                        // artifacts may be from another binary, so take them
+                        // from this one.
+                        let custom_sections = cache_key::custom_sections(binary, ignored);
+                    } else {
+                        let (main_module, artifacts, types) =
+                            CompilationArtifacts::build(
+                                &compiler,
+                                binary,
+                                USE_PAGED_MEM_INIT,
+                                &|module| engine.allocator().validate(module),
+                            )
+                            .map_err(|e| invalid_module(e, binary))?;
+                        let custom_sections = Vec::new();
+                    }
+                };
+                Ok((main_module, artifacts, types, custom_sections))
+            })?;
 
         let modules = CompiledModule::from_artifacts_list(
             artifacts,
-            engine.compiler().isa(),
+            compiler.isa(),
             &*engine.config().profiler,
+            engine.config().native_debug_symbols,
         )?;
 
-        Self::from_parts(engine, modules, main_module, Arc::new(types), &[])
+        Self::from_parts(
+            engine,
+            modules,
+            main_module,
+            Arc::new(types),
+            &[],
+            custom_sections,
+            opt_level,
+        )
     }
 
     /// Deserializes an in-memory compiled module previously created with
@@ -355,12 +512,72 @@ impl Module {
     /// those defined by any version of wasmtime. (this means that if you cache
     /// blobs across versions of wasmtime you can be safely guaranteed that
     /// future versions of wasmtime will reject old cache entries).
+    ///
+    /// The compilation settings recorded in `bytes` must match those of
+    /// `engine`, with the exception of the optimization level, which may be
+    /// any level. It's reported by [`Module::opt_level`]. Limits such as
+    /// [`Config::max_memories`](crate::Config::max_memories) needn't match
+    /// either, but the module is checked against those of `engine`.
+    ///
+    /// The whole of `bytes` must be the serialized module. Any data after it
+    /// is rejected, since it's likely to be a sign of a corrupted file, but
+    /// [`Module::deserialize_with_options`] can be used to allow it.
     pub unsafe fn deserialize(engine: &Engine, bytes: impl AsRef<[u8]>) -> Result<Module> {
-        let module = SerializedModule::from_bytes(
-            bytes.as_ref(),
-            engine.config().deserialize_check_wasmtime_version,
-        )?;
-        module.into_module(engine)
+        let (module, _) =
+            Module::deserialize_with_options(engine, bytes, &DeserializeOptions::default())?;
+        Ok(module)
+    }
+
+    /// Same as [`Module::deserialize`], except that the serialized module is
+    /// read from `path`.
+    ///
+    /// This is the counterpart of writing the output of [`Module::serialize`]
+    /// or [`Engine::precompile_module`] to a file, such as a `.cwasm` file
+    /// created by `wasmtime compile`. The file is checked against `engine` in
+    /// the same way, and it's an error if it can't be read.
+    ///
+    /// # Unsafety
+    ///
+    /// This function is `unsafe` for the same reasons as
+    /// [`Module::deserialize`]. The contents of the file must have come from
+    /// [`Module::serialize`] or [`Engine::precompile_module`], and must not
+    /// have been modified since.
+    pub unsafe fn deserialize_file(engine: &Engine, path: impl AsRef<Path>) -> Result<Module> {
+        let bytes = fs::read(path.as_ref())
+            .with_context(|| format!("failed to read `{}`", path.as_ref().display()))
+            .map_err(|e| crate::Error::new(ErrorKind::Io, e))?;
+        Module::deserialize(engine, bytes)
+    }
+
+    /// Same as [`Module::deserialize`], except that `options` configures how
+    /// `bytes` are deserialized.
+    ///
+    /// Along with the module, this returns the range of `bytes` after the end
+    /// of the serialized module, which is empty unless
+    /// [`DeserializeOptions::allow_trailing_data`] is set and there's data
+    /// there. Either way the checks that the module is compatible with `engine`
+    /// are done.
+    ///
+    /// # Unsafety
+    ///
+    /// This function is `unsafe` for the same reasons as
+    /// [`Module::deserialize`]. Only the part of `bytes` before the returned
+    /// range needs to come from [`Module::serialize`] or
+    /// [`Engine::precompile_module`].
+    pub unsafe fn deserialize_with_options(
+        engine: &Engine,
+        bytes: impl AsRef<[u8]>,
+        options: &DeserializeOptions,
+    ) -> Result<(Module, Range<usize>)> {
+        let deserialize = || -> Result<_> {
+            let (module, trailing) = SerializedModule::from_bytes(
+                bytes.as_ref(),
+                engine.config().deserialize_check_wasmtime_version,
+                options,
+            )?;
+            Ok((module.into_module(engine)?, trailing))
+        };
+        deserialize().map_err(|e| crate::Error::classify(e, ErrorKind::IncompatibleArtifact))
     }
 
     fn from_parts(
@@ -369,9 +586,14 @@ impl Module {
         main_module: usize,
         types: Arc<TypeTables>,
         module_upvars: &[serialization::SerializedModuleUpvar],
+        custom_sections: Vec<(String, Vec<u8>)>,
+        opt_level: OptLevel,
     ) -> Result<Self> {
         // Validate the module can be used with the current allocator
-        engine.allocator().validate(modules[main_module].module())?;
+        engine
+            .allocator()
+            .validate(modules[main_module].module())
+            .map_err(|e| crate::Error::new(ErrorKind::ResourceExhausted, e))?;
 
         let signatures = Arc::new(SignatureCollection::new_for_module(
             engine.signatures(),
@@ -392,6 +614,7 @@ impl Module {
                     &m.artifact_upvars,
                     &m.module_upvars,
                     &signatures,
+                    opt_level,
                 )
             })
             .collect::<Result<Vec<_>>>()?;
@@ -400,10 +623,13 @@ impl Module {
             inner: Arc::new(ModuleInner {
                 engine: engine.clone(),
                 types,
+                registration: RegisteredModule::new(engine, &module, &signatures),
                 module,
                 artifact_upvars: modules,
                 module_upvars,
                 signatures,
+                custom_sections,
+                opt_level,
             }),
         });
 
@@ -415,6 +641,7 @@ impl Module {
             artifact_upvars: &[usize],
             module_upvars: &[serialization::SerializedModuleUpvar],
             signatures: &Arc<SignatureCollection>,
+            opt_level: OptLevel,
         ) -> Result<Module> {
             Ok(Module {
                 inner: Arc::new(ModuleInner {
@@ -436,10 +663,18 @@ impl Module {
                                 &m.artifact_upvars,
                                 &m.module_upvars,
                                 signatures,
+                                opt_level,
                             )
                         })
                         .collect::<Result<Vec<_>>>()?,
                     signatures: signatures.clone(),
+                    registration: RegisteredModule::new(
+                        engine,
+                        &artifacts[module_index],
+                        signatures,
+                    ),
+                    custom_sections: Vec::new(),
+                    opt_level,
                 }),
             })
         }
@@ -455,19 +690,32 @@ impl Module {
     /// configuration for WebAssembly features, for example, which are used to
     /// indicate what should be valid and what shouldn't be.
     ///
-    /// Validation automatically happens as part of [`Module::new`].
+    /// Validation automatically happens as part of [`Module::new`], and this
+    /// function performs exactly the same checks that [`Module::from_binary`]
+    /// would, including validation of all function bodies and limits imposed
+    /// by the engine's instance allocator. Code generation, however, is
+    /// skipped entirely which makes this significantly faster than creating a
+    /// [`Module`] when only the validity of a module is of interest.
     ///
     /// # Errors
     ///
     /// If validation fails for any reason (type check error, usage of a feature
     /// that wasn't enabled, etc) then an error with a description of the
-    /// validation issue will be returned.
+    /// validation issue will be returned. This error is the same as the one
+    /// that [`Module::from_binary`] would return for the same input.
+    ///
+    /// Note that a module which passes validation may still fail to compile
+    /// if code generation itself fails, for example due to a construct that's
+    /// not supported by the compiler backend in use.
     ///
     /// [binary]: https://webassembly.github.io/spec/core/binary/index.html
     pub fn validate(engine: &Engine, binary: &[u8]) -> Result<()> {
-        let mut validator = Validator::new();
-        validator.wasm_features(engine.config().features);
-        validator.validate_all(binary)?;
+        let module = CompilationArtifacts::validate(engine.compiler(), binary)
+            .map_err(|e| invalid_module(e, binary))?;
+        engine
+            .allocator()
+            .validate(&module)
+            .map_err(|e| crate::Error::new(ErrorKind::ResourceExhausted, e))?;
         Ok(())
     }
 
@@ -544,6 +792,13 @@ impl Module {
                     })
                     .collect(),
                 signatures: self.inner.signatures.clone(),
+                registration: RegisteredModule::new(
+                    &self.inner.engine,
+                    &self.inner.artifact_upvars[artifact_index],
+                    &self.inner.signatures,
+                ),
+                custom_sections: Vec::new(),
+                opt_level: self.inner.opt_level,
             }),
         }
     }
@@ -552,6 +807,10 @@ impl Module {
         &self.inner.module
     }
 
+    pub(crate) fn registration(&self) -> Option<&Arc<RegisteredModule>> {
+        self.inner.registration.as_ref()
+    }
+
     pub(crate) fn env_module(&self) -> &wasmtime_environ::Module {
         self.compiled_module().module()
     }
@@ -564,6 +823,90 @@ impl Module {
         &self.inner.signatures
     }
 
+    /// Returns the optimization level this module's code was compiled at.
+    ///
+    /// This is the level passed to [`Module::new_with_opt_level`], or
+    /// otherwise the level configured for the engine with
+    /// [`Config::cranelift_opt_level`](crate::Config::cranelift_opt_level).
+    /// Modules loaded from the cache or with [`Module::deserialize`] report
+    /// the level their code was originally compiled at.
+    pub fn opt_level(&self) -> OptLevel {
+        self.inner.opt_level
+    }
+
+    /// Returns the total size, in bytes, of the code of the trampolines this
+    /// module uses to call its functions from the host.
+    ///
+    /// One trampoline is generated for each distinct signature of a function
+    /// which can be called from the host. They're compiled so as to favor
+    /// size regardless of [`Module::opt_level`], and this is included in the
+    /// ranges returned by [`Module::jit_code_ranges`].
+    pub fn trampoline_code_size(&self) -> usize {
+        self.compiled_module().trampoline_code_size()
+    }
+
+    /// Returns the runtime functions which the compiled code of this module's
+    /// functions calls, without duplicates.
+    ///
+    /// This only covers the module's own functions, and not those of any
+    /// modules nested in it with the module linking proposal. Which functions
+    /// are used depends on the compilation settings, and notably on the target
+    /// CPU's features. Builtins are reported if code calling them was
+    /// generated, even if it can't actually be reached. See
+    /// [`Module::libcalls_used_by_functions`] for which function uses what.
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::default();
+    /// let module = Module::new(&engine, r#"
+    ///     (module
+    ///         (memory 1)
+    ///         (func (param i32 i32 i32)
+    ///             (memory.copy (local.get 0) (local.get 1) (local.get 2))))
+    /// "#)?;
+    /// let used = module.libcalls_used().collect::<Vec<_>>();
+    /// assert!(used.contains(&LibCallKind::Builtin("memory_copy")));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn libcalls_used(&self) -> impl Iterator<Item = LibCallKind> {
+        let mut used = self
+            .libcalls_used_by_functions()
+            .into_iter()
+            .flat_map(|(_, libcalls)| libcalls)
+            .collect::<Vec<_>>();
+        used.sort();
+        used.dedup();
+        used.into_iter()
+    }
+
+    /// Returns the runtime functions which each function defined in this
+    /// module calls, as in [`Module::libcalls_used`].
+    ///
+    /// Functions are identified by their index in the module's function index
+    /// space, which includes imported functions, and functions which don't
+    /// call any runtime functions are omitted.
+    pub fn libcalls_used_by_functions(&self) -> Vec<(u32, Vec<LibCallKind>)> {
+        let compiled = self.compiled_module();
+        let module = compiled.module();
+        compiled
+            .funcs()
+            .iter()
+            .filter(|(_, info)| !info.libcalls.is_empty() || !info.builtins.is_empty())
+            .map(|(index, info)| {
+                let mut used = info
+                    .libcalls
+                    .iter()
+                    .map(|l| LibCallKind::from_libcall(*l))
+                    .chain(info.builtins.iter().map(|b| LibCallKind::from_builtin(*b)))
+                    .collect::<Vec<_>>();
+                used.sort();
+                (module.func_index(index).as_u32(), used)
+            })
+            .collect()
+    }
+
     /// Looks up the module upvar value at the `index` specified.
     ///
     /// Note that this panics if `index` is out of bounds since this should
@@ -600,6 +943,72 @@ impl Module {
         self.compiled_module().module().name.as_deref()
     }
 
+    /// Returns which WebAssembly proposals this module makes use of.
+    ///
+    /// This is recorded when the module is compiled, so it's also available
+    /// for modules loaded with [`Module::deserialize`]. For modules which fail
+    /// to compile see [`InvalidModule::feature_usage`].
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let wat = "(module (func (param i32) (result i32) (i32.extend8_s (local.get 0))))";
+    /// let module = Module::new(&engine, wat)?;
+    /// let usage = module.feature_usage();
+    /// assert!(usage.sign_extension);
+    /// assert_eq!(usage.proposals().collect::<Vec<_>>(), ["sign-extension"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn feature_usage(&self) -> FeatureUsage {
+        self.env_module().feature_usage
+    }
+
+    /// Returns how instantiating this module initializes its linear memories
+    /// with its data segments.
+    ///
+    /// This is decided when the module is compiled and is the same for
+    /// modules loaded with [`Module::deserialize`]. Modules whose data can be
+    /// laid out as pages ahead of time report [`DataInitStrategy::Paged`],
+    /// and the others the first reason they can't be.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let wat = r#"(module (import "" "g" (global i32)) (memory 1) (data (global.get 0) "x"))"#;
+    /// let module = Module::new(&engine, wat)?;
+    /// assert_eq!(
+    ///     module.data_init_strategy(),
+    ///     DataInitStrategy::Segmented(SegmentedInitReason::GlobalOffset),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn data_init_strategy(&self) -> DataInitStrategy {
+        let module = self.env_module();
+        module.memory_initialization.strategy(module)
+    }
+
+    /// Returns the contents of each top-level custom section named `name` in
+    /// the binary this module was compiled from.
+    ///
+    /// Only sections named with [`Config::cache_ignore_custom_sections`] are
+    /// retained, and nothing is returned for modules created with
+    /// [`Module::deserialize`].
+    ///
+    /// [`Config::cache_ignore_custom_sections`]: crate::Config::cache_ignore_custom_sections
+    pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [u8]> + 'a {
+        self.inner
+            .custom_sections
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, data)| &data[..])
+    }
+
     /// Returns the list of imports that this [`Module`] has and must be
     /// satisfied.
     ///
@@ -610,7 +1019,9 @@ impl Module {
     /// entries in the array returned here.
     ///
     /// The imports returned reflect the order of the imports in the wasm module
-    /// itself, and note that no form of deduplication happens.
+    /// itself, and note that no form of deduplication happens. This order is
+    /// guaranteed and will not change in future versions, so it may be relied
+    /// upon to, for example, record how a module's imports were satisfied.
     ///
     /// # Examples
     ///
@@ -655,8 +1066,18 @@ impl Module {
         let module = self.compiled_module().module();
         let types = self.types();
         module
-            .imports()
-            .map(move |(module, field, ty)| ImportType::new(module, field, ty, types))
+            .initializers
+            .iter()
+            .filter_map(move |init| match init {
+                Initializer::Import { name, field, index } => Some(ImportType::new(
+                    name,
+                    field.as_deref(),
+                    module.type_of(*index),
+                    *index,
+                    types,
+                )),
+                _ => None,
+            })
             .collect::<Vec<_>>()
             .into_iter()
     }
@@ -721,7 +1142,13 @@ impl Module {
         let module = self.compiled_module().module();
         let types = self.types();
         module.exports.iter().map(move |(name, entity_index)| {
-            ExportType::new(name, module.type_of(*entity_index), types)
+            ExportType::new(
+                name,
+                module.type_of(*entity_index),
+                *entity_index,
+                is_imported_entity(module, *entity_index),
+                types,
+            )
         })
     }
 
@@ -777,12 +1204,96 @@ impl Module {
         ))
     }
 
+    /// Returns the ranges of memory, as `(start, end)` address pairs, which
+    /// contain this module's compiled code.
+    ///
+    /// These ranges include both the WebAssembly functions defined by this
+    /// module and the trampolines used to enter them. This can be used, for
+    /// example, to classify native program counters found while walking a
+    /// stack as belonging to this module.
+    pub fn jit_code_ranges<'module>(
+        &'module self,
+    ) -> impl Iterator<Item = (usize, usize)> + 'module {
+        self.compiled_module().jit_code_ranges()
+    }
+
     /// Returns the [`Engine`] that this [`Module`] was compiled by.
     pub fn engine(&self) -> &Engine {
         &self.inner.engine
     }
 }
 
+/// Context attached to the error returned when a WebAssembly binary fails to
+/// validate or translate.
+///
+/// This carries the [`FeatureUsage`] of the binary, which is useful for
+/// diagnosing modules that use a proposal that isn't enabled in the
+/// [`Config`](crate::Config). It can be recovered from the `anyhow::Error`
+/// returned by [`Module::new`] and friends with `downcast_ref`, and as it's
+/// only context the original error can still be recovered the same way.
+#[derive(Debug)]
+pub struct InvalidModule {
+    message: String,
+    feature_usage: FeatureUsage,
+}
+
+impl InvalidModule {
+    /// Returns which WebAssembly proposals the invalid module makes use of.
+    ///
+    /// As the module failed to validate this is determined on a best-effort
+    /// basis, and only reflects the part of a malformed binary that could be
+    /// parsed.
+    pub fn feature_usage(&self) -> FeatureUsage {
+        self.feature_usage
+    }
+}
+
+impl fmt::Display for InvalidModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for InvalidModule {}
+
+/// Classifies `error`, attaching the feature usage of `binary` to it if it's
+/// a translation error.
+pub(crate) fn invalid_module(error: SetupError, binary: &[u8]) -> anyhow::Error {
+    match error {
+        SetupError::Compile(CompileError::Wasm(_)) => {
+            let kind = crate::error::setup_error_kind(&error);
+            let context = InvalidModule {
+                message: error.to_string(),
+                feature_usage: FeatureUsage::scan(binary),
+            };
+            crate::Error::new(kind, anyhow::Error::new(error).context(context))
+        }
+        error => crate::Error::from_setup_error(error),
+    }
+}
+
+/// Parses `bytes` as the text format if they aren't a binary module.
+#[cfg(feature = "wat")]
+pub(crate) fn parse_wat(bytes: &[u8]) -> Result<std::borrow::Cow<'_, [u8]>> {
+    wat::parse_bytes(bytes).map_err(|e| crate::Error::new(ErrorKind::Validation, e))
+}
+
+/// Returns whether `index` refers to one of `module`'s imports.
+fn is_imported_entity(module: &wasmtime_environ::Module, index: EntityIndex) -> bool {
+    match index {
+        EntityIndex::Function(i) => module.is_imported_function(i),
+        EntityIndex::Table(i) => module.is_imported_table(i),
+        EntityIndex::Memory(i) => module.is_imported_memory(i),
+        EntityIndex::Global(i) => module.is_imported_global(i),
+        EntityIndex::Module(_) | EntityIndex::Instance(_) => {
+            module.initializers.iter().any(|init| match init {
+                Initializer::Import { index: i, .. } => *i == index,
+                _ => false,
+            })
+        }
+    }
+}
+
 fn _assert_send_sync() {
     fn _assert<T: Send + Sync>() {}
     _assert::<Module>();
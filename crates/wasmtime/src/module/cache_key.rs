@@ -0,0 +1,108 @@
+//! Hashing of wasm binaries for the module cache, optionally ignoring the
+//! contents of some custom sections.
+
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use wasmparser::{Parser, Payload};
+
+/// A wasm binary as hashed into the key of the module cache.
+///
+/// Top-level custom sections whose name is in `ignored` contribute only their
+/// name to the hash, not their size or contents. Every other byte of the
+/// binary is hashed, so reordering, adding, or removing sections still
+/// changes the key.
+pub(crate) struct CacheKeyBinary<'a> {
+    pub binary: &'a [u8],
+    ignored: &'a [String],
+}
+
+impl<'a> CacheKeyBinary<'a> {
+    pub fn new(binary: &'a [u8], ignored: &'a [String]) -> CacheKeyBinary<'a> {
+        CacheKeyBinary { binary, ignored }
+    }
+}
+
+impl Hash for CacheKeyBinary<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut prev = 0;
+        for section in ignored_sections(self.binary, self.ignored) {
+            // Skip the section's size as well as its contents, since the size
+            // changes along with the contents.
+            let size_start = leb_start(self.binary, section.range.start);
+            self.binary[prev..size_start].hash(state);
+            section.name.hash(state);
+            prev = section.range.end;
+        }
+        self.binary[prev..].hash(state);
+    }
+}
+
+/// State passed to a cached computation which doesn't affect its result, and
+/// so doesn't contribute to the cache key.
+pub(crate) struct Unhashed<T>(pub T);
+
+impl<T> Hash for Unhashed<T> {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+/// Returns the name and contents of each top-level custom section in
+/// `binary` whose name is in `names`.
+pub(crate) fn custom_sections(binary: &[u8], names: &[String]) -> Vec<(String, Vec<u8>)> {
+    ignored_sections(binary, names)
+        .into_iter()
+        .map(|s| (s.name.to_string(), s.data.to_vec()))
+        .collect()
+}
+
+struct IgnoredSection<'a> {
+    name: &'a str,
+    data: &'a [u8],
+    /// The range of the section's name and data within the binary.
+    range: Range<usize>,
+}
+
+/// Returns the top-level custom sections of `binary` whose name is in `names`,
+/// in the order they appear.
+///
+/// Parsing stops at the first error; whatever follows is hashed as-is and will
+/// fail validation when compiled anyway.
+fn ignored_sections<'a>(binary: &'a [u8], names: &[String]) -> Vec<IgnoredSection<'a>> {
+    let mut sections = Vec::new();
+    if names.is_empty() {
+        return sections;
+    }
+    let mut depth = 0;
+    for payload in Parser::new(0).parse_all(binary) {
+        match payload {
+            Ok(Payload::CustomSection {
+                name, data, range, ..
+            }) if depth == 0 && names.iter().any(|n| n == name) => {
+                sections.push(IgnoredSection {
+                    name,
+                    data,
+                    range: range.start..range.end,
+                });
+            }
+            Ok(Payload::ModuleSectionEntry { .. }) => depth += 1,
+            Ok(Payload::End) => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    sections
+}
+
+/// Returns the offset of the start of the LEB128-encoded integer which ends
+/// just before `end` in `binary`.
+fn leb_start(binary: &[u8], end: usize) -> usize {
+    let mut start = end - 1;
+    while start > 0 && binary[start - 1] & 0x80 != 0 {
+        start -= 1;
+    }
+    start
+}
@@ -0,0 +1,277 @@
+//! A map from ranges of JIT code addresses to whatever describes that code.
+
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// A map from non-overlapping ranges of code addresses to `T`.
+///
+/// This is what module registries use to find the module a program counter is
+/// in, which happens for every trap signal and every frame of a backtrace, so
+/// lookups need to stay cheap even with many modules registered. Ranges are
+/// kept in a `Vec` sorted by address which is binary searched, and the index of
+/// the range most recently found is remembered and checked first since
+/// consecutive lookups, such as for the frames of one backtrace or a guest
+/// trapping over and over, tend to land in the same module.
+///
+/// Ranges may be inserted in any order, but inserting a range which overlaps
+/// one already in the map panics since that means code was registered twice or
+/// the registry is otherwise confused.
+pub(crate) struct CodeMap<T> {
+    /// Sorted by `start`, with no two ranges overlapping.
+    entries: Vec<Entry<T>>,
+    /// The index in `entries` of the last range a lookup found.
+    ///
+    /// This may be stale after a removal, so it's only ever a hint which is
+    /// checked before being used.
+    last_hit: AtomicUsize,
+}
+
+struct Entry<T> {
+    start: usize,
+    /// Inclusive end of the range, as the last byte of code may be a valid pc.
+    end: usize,
+    value: T,
+}
+
+impl<T> Entry<T> {
+    fn contains(&self, pc: usize) -> bool {
+        self.start <= pc && pc <= self.end
+    }
+}
+
+impl<T> Default for CodeMap<T> {
+    fn default() -> CodeMap<T> {
+        CodeMap {
+            entries: Vec::new(),
+            last_hit: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T> CodeMap<T> {
+    /// Returns the value whose range contains `pc`, if any.
+    pub(crate) fn get(&self, pc: usize) -> Option<&T> {
+        let index = self.index_of(pc)?;
+        Some(&self.entries[index].value)
+    }
+
+    /// Returns the value whose range contains `pc`, if any.
+    pub(crate) fn get_mut(&mut self, pc: usize) -> Option<&mut T> {
+        let index = self.index_of(pc)?;
+        Some(&mut self.entries[index].value)
+    }
+
+    fn index_of(&self, pc: usize) -> Option<usize> {
+        let last = self.last_hit.load(Relaxed);
+        if let Some(entry) = self.entries.get(last) {
+            if entry.contains(pc) {
+                return Some(last);
+            }
+        }
+
+        let index = match self.entries.binary_search_by_key(&pc, |e| e.start) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        if !self.entries[index].contains(pc) {
+            return None;
+        }
+        self.last_hit.store(index, Relaxed);
+        Some(index)
+    }
+
+    /// Inserts `value` for the inclusive range `start..=end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range overlaps any range already in this map.
+    pub(crate) fn insert(&mut self, start: usize, end: usize, value: T) {
+        self.assert_disjoint(start, end);
+        let index = match self.entries.binary_search_by_key(&start, |e| e.start) {
+            Ok(_) => unreachable!(),
+            Err(index) => index,
+        };
+        self.entries.insert(index, Entry { start, end, value });
+    }
+
+    /// Panics if the inclusive range `start..=end` overlaps any range in this
+    /// map.
+    pub(crate) fn assert_disjoint(&self, start: usize, end: usize) {
+        assert!(start <= end, "invalid code range {:#x}..={:#x}", start, end);
+
+        // The only ranges which could overlap are the last one starting at or
+        // before `end` and, if that one ends before `start`, none at all.
+        let index = match self.entries.binary_search_by_key(&end, |e| e.start) {
+            Ok(index) => Some(index),
+            Err(0) => None,
+            Err(index) => Some(index - 1),
+        };
+        if let Some(entry) = index.map(|i| &self.entries[i]) {
+            assert!(
+                entry.end < start,
+                "code range {:#x}..={:#x} overlaps already-registered code range {:#x}..={:#x}",
+                start,
+                end,
+                entry.start,
+                entry.end,
+            );
+        }
+    }
+
+    /// Removes the range containing `pc`, returning its value.
+    pub(crate) fn remove(&mut self, pc: usize) -> Option<T> {
+        let index = self.index_of(pc)?;
+        Some(self.entries.remove(index).value)
+    }
+
+    /// Removes every range for which `keep`, given the start of the range and
+    /// its value, returns `false`, returning how many were removed.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(usize, &T) -> bool) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|e| keep(e.start, &e.value));
+        before - self.entries.len()
+    }
+
+    /// Returns all values in this map, sorted by the address of their range.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().map(|e| &e.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CodeMap;
+
+    const RANGES: usize = 1000;
+    const SIZE: usize = 0x100;
+    const GAP: usize = 0x10;
+
+    fn range(i: usize) -> (usize, usize) {
+        let start = 0x1000 + i * (SIZE + GAP);
+        (start, start + SIZE - 1)
+    }
+
+    /// Inserts `RANGES` ranges, separated by gaps, in a scrambled order.
+    fn map() -> CodeMap<usize> {
+        let mut map = CodeMap::default();
+        // 7919 is prime, so this visits every index exactly once.
+        for i in (0..RANGES).map(|i| i * 7919 % RANGES) {
+            let (start, end) = range(i);
+            map.insert(start, end, i);
+        }
+        map
+    }
+
+    #[test]
+    fn lookups_at_boundaries() {
+        let map = map();
+        assert_eq!(
+            map.values().copied().collect::<Vec<_>>(),
+            (0..RANGES).collect::<Vec<_>>()
+        );
+
+        assert_eq!(map.get(0), None);
+        assert_eq!(map.get(range(0).0 - 1), None);
+        for i in 0..RANGES {
+            let (start, end) = range(i);
+            assert_eq!(map.get(start), Some(&i));
+            assert_eq!(map.get(start + 1), Some(&i));
+            assert_eq!(map.get(end - 1), Some(&i));
+            assert_eq!(map.get(end), Some(&i));
+            assert_eq!(map.get(end + 1), None);
+            assert_eq!(map.get(end + GAP), None);
+        }
+        assert_eq!(map.get(usize::MAX), None);
+    }
+
+    #[test]
+    fn cached_lookups() {
+        let map = map();
+        let (start, end) = range(500);
+
+        // Hit the same range repeatedly, then miss just outside of it, then go
+        // somewhere else entirely.
+        for pc in start..=end {
+            assert_eq!(map.get(pc), Some(&500));
+        }
+        assert_eq!(map.get(start - 1), None);
+        assert_eq!(map.get(end + 1), None);
+        assert_eq!(map.get(range(3).0), Some(&3));
+        assert_eq!(map.get(end), Some(&500));
+    }
+
+    #[test]
+    fn removal() {
+        let mut map = map();
+        let (start, end) = range(500);
+        assert_eq!(map.get(start), Some(&500));
+        assert_eq!(map.remove(end), Some(500));
+        assert_eq!(map.remove(end), None);
+
+        // The cached index is now stale and points to the next range.
+        assert_eq!(map.get(start), None);
+        assert_eq!(map.get(end), None);
+        assert_eq!(map.get(range(501).0), Some(&501));
+        assert_eq!(map.get(range(499).1), Some(&499));
+
+        // The range can be registered again afterwards.
+        map.insert(start, end, 500);
+        assert_eq!(map.get(start), Some(&500));
+    }
+
+    #[test]
+    fn retain() {
+        let mut map = map();
+        assert_eq!(map.get(range(501).0), Some(&501));
+        assert_eq!(map.retain(|_, i| i % 2 == 0), RANGES / 2);
+        assert_eq!(map.values().count(), RANGES / 2);
+        for i in 0..RANGES {
+            let expected = if i % 2 == 0 { Some(&i) } else { None };
+            assert_eq!(map.get(range(i).0), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps already-registered code range")]
+    fn overlapping_start() {
+        let mut map = map();
+        let (_, end) = range(10);
+        map.insert(end, end + 1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps already-registered code range")]
+    fn overlapping_end() {
+        let mut map = map();
+        let (start, _) = range(10);
+        map.insert(start - 1, start, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps already-registered code range")]
+    fn overlapping_enclosing() {
+        let mut map = map();
+        let (start, _) = range(10);
+        let (_, end) = range(12);
+        map.insert(start - 1, end + 1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps already-registered code range")]
+    fn duplicate() {
+        let mut map = map();
+        let (start, end) = range(10);
+        map.insert(start, end, 0);
+    }
+
+    #[test]
+    fn adjacent_ranges() {
+        let mut map = map();
+        let (_, end) = range(10);
+        map.insert(end + 1, end + GAP, 1234);
+        assert_eq!(map.get(end), Some(&10));
+        assert_eq!(map.get(end + 1), Some(&1234));
+        assert_eq!(map.get(end + GAP), Some(&1234));
+        assert_eq!(map.get(end + GAP + 1), Some(&11));
+    }
+}
@@ -0,0 +1,46 @@
+//! Reporting of the runtime functions which compiled code calls.
+
+use wasmtime_environ::ir;
+use wasmtime_environ::BuiltinFunctionIndex;
+
+/// A function provided by the Wasmtime runtime which a module's compiled code
+/// calls, as reported by [`Module::libcalls_used`](crate::Module::libcalls_used).
+///
+/// Each of these is an interface between compiled code and the runtime which
+/// the code trusts, so this is useful to audit what a module depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum LibCallKind {
+    /// A function which the code generator calls for an operation the target
+    /// doesn't have an instruction for, such as rounding floats on x86_64
+    /// without SSE4.1. This is named after the runtime's implementation, for
+    /// example `"wasmtime_f64_floor"`.
+    Codegen(&'static str),
+
+    /// A function which implements all or part of a wasm instruction in the
+    /// runtime, such as `memory.copy` or `table.grow`. This is named after
+    /// the builtin, for example `"memory_copy"`.
+    Builtin(&'static str),
+}
+
+impl LibCallKind {
+    // The list of libcalls has duplicates.
+    #[allow(unreachable_patterns)]
+    pub(crate) fn from_libcall(libcall: ir::LibCall) -> LibCallKind {
+        macro_rules! name {
+            [$(($libcall:ident, $export:ident)),*] => {
+                match libcall {
+                    $(ir::LibCall::$libcall => stringify!($export),)*
+                    // Code calling any other libcall would have failed to
+                    // link, so it can't be in a module.
+                    _ => unreachable!("unsupported libcall {:?}", libcall),
+                }
+            };
+        }
+        LibCallKind::Codegen(wasmtime_environ::for_each_libcall!(name))
+    }
+
+    pub(crate) fn from_builtin(builtin: BuiltinFunctionIndex) -> LibCallKind {
+        LibCallKind::Builtin(builtin.name())
+    }
+}
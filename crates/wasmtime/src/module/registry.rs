@@ -1,15 +1,16 @@
 //! Implements a registry of modules for a store.
 
-use crate::{signatures::SignatureCollection, Module};
-use std::{
-    collections::BTreeMap,
-    sync::{Arc, RwLock},
-};
+use super::code_map::CodeMap;
+use crate::{signatures::SignatureCollection, Engine, Module};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
 use wasmtime_environ::{
     entity::EntityRef,
     ir::{self, StackMap},
     wasm::DefinedFuncIndex,
-    FunctionAddressMap, TrapInformation,
+    TrapInformation,
 };
 use wasmtime_jit::CompiledModule;
 use wasmtime_runtime::{ModuleInfo, VMCallerCheckedAnyfunc, VMTrampoline};
@@ -23,16 +24,102 @@ fn func_by_pc(module: &CompiledModule, pc: usize) -> Option<(DefinedFuncIndex, u
     Some((index, (pc - start) as u32))
 }
 
-/// Used for registering modules with a store.
+/// Fetches frame information about a program counter within `module`.
 ///
-/// The map is from the ending (exclusive) address for the module code to
-/// the registered module.
+/// Returns an object if this `pc` is known to `module`, or returns `None` if
+/// no information can be found.
+fn lookup_frame_info(module: &CompiledModule, pc: usize) -> Option<FrameInfo> {
+    let (index, offset) = func_by_pc(module, pc)?;
+    let info = module.func_info(index);
+    let instr = info.address_map.instructions.lookup(offset);
+
+    // In debug mode for now assert that we found a mapping for `pc` within
+    // the function, because otherwise something is buggy along the way and
+    // not accounting for all the instructions. This isn't super critical
+    // though so we can omit this check in release mode.
+    debug_assert!(instr.is_some(), "failed to find instruction for {:x}", pc);
+
+    let instr = instr.unwrap_or(info.address_map.start_srcloc);
+
+    // Use our wasm-relative pc to symbolize this frame. If there's a
+    // symbolication context (dwarf debug info) available then we can try to
+    // look this up there.
+    //
+    // Note that dwarf pcs are code-section-relative, hence the subtraction
+    // from the location of `instr`. Also note that all errors are ignored
+    // here for now since technically wasm modules can always have any
+    // custom section contents.
+    let mut symbols = Vec::new();
+
+    if let Some(s) = &module.symbolize_context().ok().and_then(|c| c) {
+        let to_lookup = (instr.bits() as u64) - s.code_section_offset();
+        if let Ok(mut frames) = s.addr2line().find_frames(to_lookup) {
+            while let Ok(Some(frame)) = frames.next() {
+                symbols.push(FrameSymbol {
+                    name: frame
+                        .function
+                        .as_ref()
+                        .and_then(|l| l.raw_name().ok())
+                        .map(|s| s.to_string()),
+                    file: frame
+                        .location
+                        .as_ref()
+                        .and_then(|l| l.file)
+                        .map(|s| s.to_string()),
+                    line: frame.location.as_ref().and_then(|l| l.line),
+                    column: frame.location.as_ref().and_then(|l| l.column),
+                });
+            }
+        }
+    }
+
+    let module = module.module();
+    let index = module.func_index(index);
+
+    Some(FrameInfo {
+        pc,
+        func_addr: pc - offset as usize,
+        entered: false,
+        direct_caller: false,
+        module_name: module.name.clone(),
+        instance_name: None,
+        func_index: index.index() as u32,
+        func_name: module.func_names.get(&index).cloned(),
+        instr,
+        func_start: info.address_map.start_srcloc,
+        symbols,
+    })
+}
+
+type ModuleMap = CodeMap<Arc<RegisteredModule>>;
+
+/// Used for registering modules with a store.
 ///
-/// The `BTreeMap` is used to quickly locate a module based on a program counter value.
+/// The maps are from the range of addresses of each module's code to the
+/// registered module, and are used to quickly locate a module based on a
+/// program counter value.
 #[derive(Default)]
-pub struct ModuleRegistry(BTreeMap<usize, Arc<RegisteredModule>>);
+pub struct ModuleRegistry {
+    /// Modules registered with this store as they're instantiated.
+    modules: ModuleMap,
+    /// Modules registered ahead of time with [`PreparedModules`], shared with
+    /// every other store created from the same set.
+    prepared: Option<Arc<ModuleMap>>,
+    /// The start of the code of each module in `modules` which an instance in
+    /// the store was created from.
+    instantiated: HashSet<usize>,
+}
 
 impl ModuleRegistry {
+    /// Creates a registry which already has all of `prepared` registered.
+    pub(crate) fn new(prepared: &PreparedModules) -> ModuleRegistry {
+        ModuleRegistry {
+            modules: ModuleMap::default(),
+            prepared: Some(prepared.modules.clone()),
+            instantiated: HashSet::new(),
+        }
+    }
+
     /// Fetches information about a registered module given a program counter value.
     pub fn lookup_module(&self, pc: usize) -> Option<Arc<dyn ModuleInfo>> {
         self.module(pc)
@@ -40,55 +127,149 @@ impl ModuleRegistry {
     }
 
     fn module(&self, pc: usize) -> Option<&Arc<RegisteredModule>> {
-        let (end, info) = self.0.range(pc..).next()?;
-        if pc < info.start || *end < pc {
-            return None;
-        }
-
-        Some(info)
+        self.prepared
+            .as_ref()
+            .and_then(|prepared| prepared.get(pc))
+            .or_else(|| self.modules.get(pc))
     }
 
     /// Registers a new module with the registry.
+    ///
+    /// Everything registered is precomputed when the module is created, so
+    /// this only inserts a reference to it.
     pub fn register(&mut self, module: &Module) {
-        let compiled_module = module.compiled_module();
-        let (start, end) = compiled_module.code().range();
-
         // Ignore modules with no code or finished functions
-        if start == end || compiled_module.finished_functions().is_empty() {
-            return;
-        }
-
-        // The module code range is exclusive for end, so make it inclusive as it
-        // may be a valid PC value
-        let end = end - 1;
+        let registered = match module.registration() {
+            Some(registered) => registered,
+            None => return,
+        };
+        let (start, end) = (registered.start, registered.end);
 
         // Ensure the module isn't already present in the registry
-        // This is expected when a module is instantiated multiple times in the same store
-        if let Some(m) = self.0.get(&end) {
+        // This is expected when a module is instantiated multiple times in the
+        // same store, or when it's one of the prepared modules.
+        if let Some(m) = self.module(end) {
             assert_eq!(m.start, start);
             return;
         }
 
-        // Assert that this module's code doesn't collide with any other registered modules
-        if let Some((_, prev)) = self.0.range(end..).next() {
-            assert!(prev.start > end);
+        // Assert that this module's code doesn't collide with any other
+        // registered modules. Inserting checks the modules registered here, so
+        // only the prepared ones need checking separately.
+        if let Some(prepared) = &self.prepared {
+            prepared.assert_disjoint(start, end);
         }
+        self.modules.insert(start, end, registered.clone());
+    }
 
-        if let Some((prev_end, _)) = self.0.range(..=start).next_back() {
-            assert!(*prev_end < start);
+    /// Records that an instance of `module`, which must already be registered,
+    /// has been added to the store.
+    ///
+    /// Instances live as long as their store, so from then on the module's
+    /// registration is kept for as long as this registry.
+    pub(crate) fn instantiated(&mut self, module: &Module) {
+        if let Some(registered) = module.registration() {
+            self.instantiated.insert(registered.start);
         }
+    }
 
-        let prev = self.0.insert(
-            end,
-            Arc::new(RegisteredModule {
-                start,
-                module: compiled_module.clone(),
-                signatures: module.signatures().clone(),
-            }),
-        );
-        assert!(prev.is_none());
+    /// Drops the registrations of modules which no instance in the store was
+    /// created from, returning how many were dropped.
+    ///
+    /// These are modules which were registered for an instantiation that
+    /// failed before its instance was created, so none of their code can be
+    /// running or referenced by anything in the store.
+    pub(crate) fn prune(&mut self) -> usize {
+        let instantiated = &self.instantiated;
+        self.modules
+            .retain(|start, _| instantiated.contains(&start))
+    }
+
+    /// Drops the registrations of all modules, except those registered ahead of
+    /// time with [`PreparedModules`].
+    pub(crate) fn clear(&mut self) {
+        self.modules = ModuleMap::default();
+        self.instantiated.clear();
+    }
+
+    /// Fetches the module whose code contains a program counter, if it's
+    /// registered here.
+    pub(crate) fn lookup_env_module(&self, pc: usize) -> Option<&Arc<wasmtime_environ::Module>> {
+        Some(self.module(pc)?.module.module())
+    }
+
+    /// Fetches frame information about a program counter, if it's within the
+    /// code of any module registered here.
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<FrameInfo> {
+        lookup_frame_info(&self.module(pc)?.module, pc)
+    }
 
-        GLOBAL_MODULES.write().unwrap().register(start, end, module);
+    /// Creates a snapshot of the code address ranges of every function in
+    /// every module registered here.
+    pub fn symbol_map(&self) -> SymbolMap {
+        let mut entries = Vec::new();
+        let prepared = self.prepared.iter().flat_map(|p| p.values());
+        for registered in prepared.chain(self.modules.values()) {
+            let module = registered.module.module();
+            for (index, body) in registered.module.finished_functions() {
+                let (start, end) = unsafe {
+                    let ptr = (**body).as_ptr();
+                    let len = (**body).len();
+                    (ptr as usize, ptr as usize + len)
+                };
+                let index = module.func_index(index);
+                entries.push(SymbolMapEntry {
+                    module_name: module.name.clone(),
+                    func_index: index.index() as u32,
+                    func_name: module.func_names.get(&index).cloned(),
+                    start,
+                    end,
+                });
+            }
+        }
+        entries.sort_by_key(|e| e.start);
+        SymbolMap { entries }
+    }
+
+    /// Creates a snapshot of the GC safepoints of every function in every
+    /// module registered here.
+    #[cfg(feature = "gc-diagnostics")]
+    pub fn gc_diagnostics(&self) -> GcDiagnostics {
+        let mut modules = Vec::new();
+        let prepared = self.prepared.iter().flat_map(|p| p.values());
+        for registered in prepared.chain(self.modules.values()) {
+            let module = registered.module.module();
+            let mut functions = Vec::new();
+            for (index, body) in registered.module.finished_functions() {
+                let info = registered.module.func_info(index);
+                if info.stack_maps.is_empty() {
+                    continue;
+                }
+                let (start, end) = unsafe {
+                    let ptr = (**body).as_ptr();
+                    let len = (**body).len();
+                    (ptr as usize, ptr as usize + len)
+                };
+                functions.push(FunctionStackMaps {
+                    func_index: module.func_index(index).index() as u32,
+                    start,
+                    end,
+                    safepoints: info
+                        .stack_maps
+                        .iter()
+                        .map(|s| start + s.code_offset as usize)
+                        .collect(),
+                });
+            }
+            modules.push(ModuleStackMaps {
+                module_name: module.name.clone(),
+                start: registered.start,
+                end: registered.end + 1,
+                functions,
+            });
+        }
+        modules.sort_by_key(|m| m.start);
+        GcDiagnostics { modules }
     }
 
     /// Looks up a trampoline from an anyfunc.
@@ -98,40 +279,115 @@ impl ModuleRegistry {
     }
 }
 
-impl Drop for ModuleRegistry {
-    fn drop(&mut self) {
-        let mut info = GLOBAL_MODULES.write().unwrap();
-        for end in self.0.keys() {
-            info.unregister(*end);
+/// A set of modules registered ahead of time, to be shared by many stores.
+///
+/// Every store needs to know about the modules instantiated within it so it
+/// can resolve trap frames, stack maps for GC, and host trampolines. Normally
+/// each module is registered with a store when it's first instantiated there.
+/// Creating stores with [`Store::new_with_modules`] instead starts them off
+/// with this whole set registered, at the cost of cloning one `Arc`, which
+/// helps embeddings that create a short-lived store per request for the same
+/// handful of modules.
+///
+/// Instantiating a module which isn't part of the set in such a store is fine,
+/// it's registered with that store as usual.
+///
+/// [`Store::new_with_modules`]: crate::Store::new_with_modules
+#[derive(Clone)]
+pub struct PreparedModules {
+    engine: Engine,
+    modules: Arc<ModuleMap>,
+}
+
+impl PreparedModules {
+    /// Prepares `modules` to be registered with stores using `engine`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of `modules` was created with a different
+    /// engine.
+    pub fn new<'a>(
+        engine: &Engine,
+        modules: impl IntoIterator<Item = &'a Module>,
+    ) -> Result<PreparedModules> {
+        let mut registry = ModuleRegistry::default();
+        for module in modules {
+            if !Engine::same(engine, module.engine()) {
+                bail!("cross-`Engine` module preparation is not supported");
+            }
+            registry.register(module);
         }
+        Ok(PreparedModules {
+            engine: engine.clone(),
+            modules: Arc::new(registry.modules),
+        })
+    }
+
+    /// Returns the engine these modules were prepared for.
+    pub fn engine(&self) -> &Engine {
+        &self.engine
     }
 }
 
-struct RegisteredModule {
+/// Everything a store needs to know about a module's code, computed once when
+/// the module is created and shared by every store it's registered with.
+pub(crate) struct RegisteredModule {
     start: usize,
+    /// Inclusive end of the module's code.
+    end: usize,
     module: Arc<CompiledModule>,
     signatures: Arc<SignatureCollection>,
 }
 
 impl RegisteredModule {
-    fn instr_pos(offset: u32, addr_map: &FunctionAddressMap) -> Option<usize> {
-        // Use our relative position from the start of the function to find the
-        // machine instruction that corresponds to `pc`, which then allows us to
-        // map that to a wasm original source location.
-        match addr_map
-            .instructions
-            .binary_search_by_key(&offset, |map| map.code_offset)
-        {
-            // Exact hit!
-            Ok(pos) => Some(pos),
+    /// Creates the registration for `module`, registering it with the global
+    /// registry for as long as the returned value is alive.
+    ///
+    /// Returns `None` for modules with no code, which never need to be
+    /// registered.
+    pub(crate) fn new(
+        engine: &Engine,
+        module: &Arc<CompiledModule>,
+        signatures: &Arc<SignatureCollection>,
+    ) -> Option<Arc<RegisteredModule>> {
+        let (start, end) = module.code().range();
+        if start == end || module.finished_functions().is_empty() {
+            return None;
+        }
 
-            // This *would* be at the first slot in the array, so no
-            // instructions cover `pc`.
-            Err(0) => None,
+        // Functions are found by binary searching the module's table of them
+        // by address, which relies on them being laid out in memory in index
+        // order.
+        debug_assert!(module
+            .finished_functions()
+            .values()
+            .zip(module.finished_functions().values().skip(1))
+            .all(|(a, b)| unsafe {
+                (**a).as_ptr() as usize + (**a).len() <= (**b).as_ptr() as usize
+            }));
 
-            // This would be at the `nth` slot, so we're at the `n-1`th slot.
-            Err(n) => Some(n - 1),
-        }
+        // The module code range is exclusive for end, so make it inclusive as it
+        // may be a valid PC value
+        let end = end - 1;
+
+        GLOBAL_MODULES.write().unwrap().register(
+            start,
+            end,
+            module,
+            engine.config().wasm_backtrace_details_env_used,
+        );
+        Some(Arc::new(RegisteredModule {
+            start,
+            end,
+            module: module.clone(),
+            signatures: signatures.clone(),
+        }))
+    }
+}
+
+impl Drop for RegisteredModule {
+    fn drop(&mut self) {
+        GLOBAL_MODULES.write().unwrap().unregister(self.end);
     }
 }
 
@@ -201,6 +457,17 @@ impl ModuleInfo for RegisteredModule {
 
         Some(&info.stack_maps[index].stack_map)
     }
+
+    fn is_call_site(&self, pc: usize) -> bool {
+        let (index, offset) = match func_by_pc(&self.module, pc) {
+            Some(func) => func,
+            // Trampolines are outside of the module's functions, and have no
+            // references to report.
+            None => return true,
+        };
+        let call_sites = &self.module.func_info(index).call_sites;
+        call_sites.is_empty() || call_sites.binary_search(&offset).is_ok()
+    }
 }
 
 // Counterpart to `RegisteredModule`, but stored in the global registry.
@@ -208,15 +475,16 @@ struct GlobalRegisteredModule {
     start: usize,
     module: Arc<CompiledModule>,
     wasm_backtrace_details_env_used: bool,
-    /// Note that modules can be instantiated in many stores, so the purpose of
-    /// this field is to keep track of how many stores have registered a
-    /// module. Information is only removed from the global registry when this
+    /// Note that the same code can be shared by many `Module` values (for
+    /// example submodules created during instantiation), so the purpose of
+    /// this field is to keep track of how many `RegisteredModule`s refer to
+    /// it. Information is only removed from the global registry when this
     /// reference count reaches 0.
     references: usize,
 }
 
 /// This is the global module registry that stores information for all modules
-/// that are currently in use by any `Store`.
+/// that are currently alive.
 ///
 /// The purpose of this map is to be called from signal handlers to determine
 /// whether a program counter is a wasm trap or not. Specifically macOS has
@@ -224,12 +492,11 @@ struct GlobalRegisteredModule {
 /// for global state rather than using thread local state.
 ///
 /// This is similar to `ModuleRegistry` except that it has less information and
-/// supports removal. Any time anything is registered with a `ModuleRegistry`
-/// it is also automatically registered with the singleton global module
-/// registry. When a `ModuleRegistry` is destroyed then all of its entries
-/// are removed from the global module registry.
+/// supports removal. Every `RegisteredModule`, which is what a
+/// `ModuleRegistry` holds, is registered with the singleton global module
+/// registry when it's created and removed from it when it's destroyed.
 #[derive(Default)]
-pub struct GlobalModuleRegistry(BTreeMap<usize, GlobalRegisteredModule>);
+pub struct GlobalModuleRegistry(CodeMap<GlobalRegisteredModule>);
 
 impl GlobalModuleRegistry {
     /// Returns whether the `pc`, according to globally registered information,
@@ -241,7 +508,7 @@ impl GlobalModuleRegistry {
             Some(entry) => match func_by_pc(&entry.module, pc) {
                 Some((index, offset)) => {
                     let info = entry.module.func_info(index);
-                    RegisteredModule::instr_pos(offset, &info.address_map).is_some()
+                    info.address_map.instructions.lookup(offset).is_some()
                 }
                 None => false,
             },
@@ -250,11 +517,7 @@ impl GlobalModuleRegistry {
     }
 
     fn module(&self, pc: usize) -> Option<&GlobalRegisteredModule> {
-        let (end, info) = self.0.range(pc..).next()?;
-        if pc < info.start || *end < pc {
-            return None;
-        }
-        Some(info)
+        self.0.get(pc)
     }
 
     // Work with the global instance of `GlobalModuleRegistry`. Note that only
@@ -287,33 +550,62 @@ impl GlobalModuleRegistry {
         self.module(pc)?.lookup_trap_info(pc)
     }
 
+    /// Returns whether `ret_addr`, the return address of a frame in a
+    /// backtrace, is that of a direct call to a function defined in the same
+    /// module, which therefore runs in the same instance as its caller.
+    pub(crate) fn is_direct_call(&self, ret_addr: usize) -> bool {
+        let module = match self.module(ret_addr) {
+            Some(module) => module,
+            None => return false,
+        };
+        match func_by_pc(&module.module, ret_addr) {
+            Some((index, offset)) => module
+                .module
+                .func_info(index)
+                .direct_call_sites
+                .binary_search(&offset)
+                .is_ok(),
+            None => false,
+        }
+    }
+
     /// Registers a new region of code, described by `(start, end)` and with
     /// the given function information, with the global information.
-    fn register(&mut self, start: usize, end: usize, module: &Module) {
-        let info = self.0.entry(end).or_insert_with(|| GlobalRegisteredModule {
-            start,
-            module: module.compiled_module().clone(),
-            wasm_backtrace_details_env_used: module
-                .engine()
-                .config()
-                .wasm_backtrace_details_env_used,
-            references: 0,
-        });
-
+    fn register(
+        &mut self,
+        start: usize,
+        end: usize,
+        module: &Arc<CompiledModule>,
+        wasm_backtrace_details_env_used: bool,
+    ) {
         // Note that ideally we'd debug_assert that the information previously
         // stored, if any, matches the `functions` we were given, but for now we
         // just do some simple checks to hope it's the same.
-        assert_eq!(info.start, start);
-        info.references += 1;
+        if let Some(info) = self.0.get_mut(end) {
+            assert_eq!(info.start, start);
+            info.references += 1;
+            return;
+        }
+
+        self.0.insert(
+            start,
+            end,
+            GlobalRegisteredModule {
+                start,
+                module: module.clone(),
+                wasm_backtrace_details_env_used,
+                references: 1,
+            },
+        );
     }
 
     /// Unregisters a region of code (keyed by the `end` address) from the
     /// global information.
     fn unregister(&mut self, end: usize) {
-        let info = self.0.get_mut(&end).unwrap();
+        let info = self.0.get_mut(end).unwrap();
         info.references -= 1;
         if info.references == 0 {
-            self.0.remove(&end);
+            self.0.remove(end);
         }
     }
 }
@@ -329,64 +621,7 @@ impl GlobalRegisteredModule {
     /// Returns an object if this `pc` is known to this module, or returns `None`
     /// if no information can be found.
     pub fn lookup_frame_info(&self, pc: usize) -> Option<FrameInfo> {
-        let (index, offset) = func_by_pc(&self.module, pc)?;
-        let info = self.module.func_info(index);
-        let pos = RegisteredModule::instr_pos(offset, &info.address_map);
-
-        // In debug mode for now assert that we found a mapping for `pc` within
-        // the function, because otherwise something is buggy along the way and
-        // not accounting for all the instructions. This isn't super critical
-        // though so we can omit this check in release mode.
-        debug_assert!(pos.is_some(), "failed to find instruction for {:x}", pc);
-
-        let instr = match pos {
-            Some(pos) => info.address_map.instructions[pos].srcloc,
-            None => info.address_map.start_srcloc,
-        };
-
-        // Use our wasm-relative pc to symbolize this frame. If there's a
-        // symbolication context (dwarf debug info) available then we can try to
-        // look this up there.
-        //
-        // Note that dwarf pcs are code-section-relative, hence the subtraction
-        // from the location of `instr`. Also note that all errors are ignored
-        // here for now since technically wasm modules can always have any
-        // custom section contents.
-        let mut symbols = Vec::new();
-
-        if let Some(s) = &self.module.symbolize_context().ok().and_then(|c| c) {
-            let to_lookup = (instr.bits() as u64) - s.code_section_offset();
-            if let Ok(mut frames) = s.addr2line().find_frames(to_lookup) {
-                while let Ok(Some(frame)) = frames.next() {
-                    symbols.push(FrameSymbol {
-                        name: frame
-                            .function
-                            .as_ref()
-                            .and_then(|l| l.raw_name().ok())
-                            .map(|s| s.to_string()),
-                        file: frame
-                            .location
-                            .as_ref()
-                            .and_then(|l| l.file)
-                            .map(|s| s.to_string()),
-                        line: frame.location.as_ref().and_then(|l| l.line),
-                        column: frame.location.as_ref().and_then(|l| l.column),
-                    });
-                }
-            }
-        }
-
-        let module = self.module.module();
-        let index = module.func_index(index);
-
-        Some(FrameInfo {
-            module_name: module.name.clone(),
-            func_index: index.index() as u32,
-            func_name: module.func_names.get(&index).cloned(),
-            instr,
-            func_start: info.address_map.start_srcloc,
-            symbols,
-        })
+        lookup_frame_info(&self.module, pc)
     }
 
     /// Fetches trap information about a program counter in a backtrace.
@@ -410,7 +645,12 @@ impl GlobalRegisteredModule {
 /// [`Trap`]: crate::Trap
 #[derive(Debug)]
 pub struct FrameInfo {
+    pc: usize,
+    func_addr: usize,
+    entered: bool,
+    direct_caller: bool,
     module_name: Option<String>,
+    instance_name: Option<Arc<str>>,
     func_index: u32,
     func_name: Option<String>,
     func_start: ir::SourceLoc,
@@ -440,6 +680,49 @@ impl FrameInfo {
         self.module_name.as_deref()
     }
 
+    /// Returns the name of the instance that this frame is for, if it's known.
+    ///
+    /// Instances are named by the embedder with
+    /// [`Instance::set_name`](crate::Instance::set_name). This returns `None`
+    /// for unnamed instances, and for frames which couldn't be attributed to a
+    /// single instance. Frames are only attributed to instances in traps
+    /// returned from calls into wasm, not in
+    /// [`Store::lookup_frame_info`](crate::Store::lookup_frame_info).
+    pub fn instance_name(&self) -> Option<&str> {
+        self.instance_name.as_deref()
+    }
+
+    pub(crate) fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub(crate) fn set_instance_name(&mut self, name: Arc<str>) {
+        self.instance_name = Some(name);
+    }
+
+    /// The address of the start of this frame's function.
+    pub(crate) fn func_addr(&self) -> usize {
+        self.func_addr
+    }
+
+    /// Whether this frame was called from outside of wasm, making it the
+    /// oldest frame of a call into wasm. Only known for frames of a trap.
+    pub(crate) fn entered(&self) -> bool {
+        self.entered
+    }
+
+    /// Whether this frame was called by the next older frame with a direct
+    /// call, so that both run in the same instance. Only known for frames of
+    /// a trap.
+    pub(crate) fn direct_caller(&self) -> bool {
+        self.direct_caller
+    }
+
+    pub(crate) fn set_caller(&mut self, entered: bool, direct_caller: bool) {
+        self.entered = entered;
+        self.direct_caller = direct_caller;
+    }
+
     /// Returns a descriptive name of the function for this frame, if one is
     /// available.
     ///
@@ -540,6 +823,226 @@ impl FrameSymbol {
     }
 }
 
+/// A snapshot of where the JIT code of every WebAssembly function within a
+/// [`Store`](crate::Store) lives in memory.
+///
+/// This is created with [`Store::symbol_map`](crate::Store::symbol_map) and is
+/// intended for crash reporters and other out-of-band symbolizers which need to
+/// map native program counters to WebAssembly functions without access to the
+/// [`Store`](crate::Store) itself. The map can be serialized with `serde` to be
+/// handed to an out-of-process symbolizer.
+///
+/// Note that the addresses in this map are only valid as long as the
+/// originating store (and the modules within it) are alive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolMap {
+    entries: Vec<SymbolMapEntry>,
+}
+
+impl SymbolMap {
+    /// Returns all entries of this map, sorted by their starting address.
+    pub fn entries(&self) -> &[SymbolMapEntry] {
+        &self.entries
+    }
+
+    /// Looks up the function whose code contains `pc`.
+    ///
+    /// This performs a binary search over the entries of this map and does
+    /// not allocate or take any locks, so it's suitable to call from contexts
+    /// such as signal handlers where [`Store::lookup_frame_info`] is not.
+    ///
+    /// [`Store::lookup_frame_info`]: crate::Store::lookup_frame_info
+    pub fn lookup(&self, pc: usize) -> Option<&SymbolMapEntry> {
+        let idx = match self.entries.binary_search_by_key(&pc, |e| e.start) {
+            Ok(idx) => idx,
+            Err(0) => return None,
+            Err(idx) => idx - 1,
+        };
+        let entry = &self.entries[idx];
+        if pc < entry.end {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+}
+
+/// An entry within a [`SymbolMap`] describing the code of one WebAssembly
+/// function.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolMapEntry {
+    module_name: Option<String>,
+    func_index: u32,
+    func_name: Option<String>,
+    start: usize,
+    end: usize,
+}
+
+impl SymbolMapEntry {
+    /// Returns the name of the module this function is defined in, if any.
+    ///
+    /// See [`FrameInfo::module_name`] for more information.
+    pub fn module_name(&self) -> Option<&str> {
+        self.module_name.as_deref()
+    }
+
+    /// Returns the index of this function in its module's function index
+    /// space.
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    /// Returns the name of this function, if one is available.
+    ///
+    /// See [`FrameInfo::func_name`] for more information.
+    pub fn func_name(&self) -> Option<&str> {
+        self.func_name.as_deref()
+    }
+
+    /// Returns the address of the first byte of this function's code.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the address just past the last byte of this function's code.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+/// A snapshot of the GC safepoints registered with a store, which the
+/// garbage collector uses to find the `externref`s held by WebAssembly frames
+/// on the stack.
+///
+/// This is created with [`Store::gc_diagnostics`] and is meant for debugging
+/// embeddings which trip up the collector. Lookups in it perform no
+/// allocation, so it can be captured ahead of time and used from a panic hook
+/// or crash reporter.
+///
+/// [`Store::gc_diagnostics`]: crate::Store::gc_diagnostics
+#[cfg(feature = "gc-diagnostics")]
+#[derive(Debug, Clone)]
+pub struct GcDiagnostics {
+    modules: Vec<ModuleStackMaps>,
+}
+
+#[cfg(feature = "gc-diagnostics")]
+impl GcDiagnostics {
+    /// Returns the safepoints of each module registered with the store,
+    /// sorted by the address of their code.
+    pub fn modules(&self) -> &[ModuleStackMaps] {
+        &self.modules
+    }
+
+    /// Returns the total number of safepoints across all modules.
+    pub fn safepoints(&self) -> usize {
+        self.modules.iter().map(|m| m.safepoints()).sum()
+    }
+
+    /// Returns whether the garbage collector finds a stack map for a
+    /// WebAssembly frame whose program counter is `pc`.
+    ///
+    /// The collector uses the closest safepoint at or before `pc` within the
+    /// same function, since a frame's program counter is the return address
+    /// of the call it's in the middle of, which comes somewhat after the
+    /// safepoint preceding that call. Frames without a stack map are treated
+    /// as holding no `externref`s, which is the case when none are live
+    /// across the call.
+    pub fn has_stack_map_for(&self, pc: usize) -> bool {
+        let module = match self.modules.binary_search_by_key(&pc, |m| m.start) {
+            Ok(i) => &self.modules[i],
+            Err(0) => return false,
+            Err(i) => &self.modules[i - 1],
+        };
+        if pc >= module.end {
+            return false;
+        }
+        let func = match module.functions.binary_search_by_key(&pc, |f| f.start) {
+            Ok(i) => &module.functions[i],
+            Err(0) => return false,
+            Err(i) => &module.functions[i - 1],
+        };
+        pc < func.end && func.safepoints[0] <= pc
+    }
+}
+
+/// The GC safepoints within the code of one module, as part of
+/// [`GcDiagnostics`].
+#[cfg(feature = "gc-diagnostics")]
+#[derive(Debug, Clone)]
+pub struct ModuleStackMaps {
+    module_name: Option<String>,
+    start: usize,
+    end: usize,
+    functions: Vec<FunctionStackMaps>,
+}
+
+#[cfg(feature = "gc-diagnostics")]
+impl ModuleStackMaps {
+    /// Returns the name of this module, if any.
+    pub fn module_name(&self) -> Option<&str> {
+        self.module_name.as_deref()
+    }
+
+    /// Returns the address of the first byte of this module's code.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the address just past the last byte of this module's code.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the functions of this module which have any safepoints,
+    /// sorted by the address of their code.
+    pub fn functions(&self) -> &[FunctionStackMaps] {
+        &self.functions
+    }
+
+    /// Returns the number of safepoints in this module's code.
+    pub fn safepoints(&self) -> usize {
+        self.functions.iter().map(|f| f.safepoints.len()).sum()
+    }
+}
+
+/// The GC safepoints within the code of one function, as part of
+/// [`GcDiagnostics`].
+#[cfg(feature = "gc-diagnostics")]
+#[derive(Debug, Clone)]
+pub struct FunctionStackMaps {
+    func_index: u32,
+    start: usize,
+    end: usize,
+    safepoints: Vec<usize>,
+}
+
+#[cfg(feature = "gc-diagnostics")]
+impl FunctionStackMaps {
+    /// Returns the index of this function in its module's function index
+    /// space.
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    /// Returns the address of the first byte of this function's code.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the address just past the last byte of this function's code.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the addresses of this function's safepoints, in ascending
+    /// order. Each has a stack map describing which of the frame's stack
+    /// slots hold `externref`s.
+    pub fn safepoints(&self) -> &[usize] {
+        &self.safepoints
+    }
+}
+
 #[test]
 fn test_frame_info() -> Result<(), anyhow::Error> {
     use crate::*;
@@ -576,3 +1079,109 @@ fn test_frame_info() -> Result<(), anyhow::Error> {
     });
     Ok(())
 }
+
+#[test]
+fn test_store_lookup_frame_info() -> Result<(), anyhow::Error> {
+    use crate::*;
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module $calc
+                (func $add (export "add") (param i32 i32) (result i32)
+                    (i32.add (local.get 0) (local.get 1)))
+                (func $sub (export "sub") (param i32 i32) (result i32)
+                    (i32.sub (local.get 0) (local.get 1)))
+            )
+         "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let map = store.symbol_map();
+    assert_eq!(map.entries().len(), 2);
+
+    for (index, name) in ["add", "sub"].iter().enumerate() {
+        let func = instance.get_func(&mut store, name).unwrap();
+        let pc = unsafe {
+            func.caller_checked_anyfunc(store.as_context().0)
+                .as_ref()
+                .func_ptr
+                .as_ptr() as usize
+        };
+
+        let frame = store.lookup_frame_info(pc).unwrap();
+        assert_eq!(frame.module_name(), Some("calc"));
+        assert_eq!(frame.func_index(), index as u32);
+        assert_eq!(frame.func_name(), Some(*name));
+
+        let entry = map.lookup(pc).unwrap();
+        assert_eq!(entry.module_name(), Some("calc"));
+        assert_eq!(entry.func_index(), index as u32);
+        assert_eq!(entry.func_name(), Some(*name));
+        assert_eq!(entry.start(), pc);
+    }
+
+    let host = Func::wrap(&mut store, || {});
+    let pc = unsafe {
+        host.caller_checked_anyfunc(store.as_context().0)
+            .as_ref()
+            .func_ptr
+            .as_ptr() as usize
+    };
+    assert!(store.lookup_frame_info(pc).is_none());
+    assert!(map.lookup(pc).is_none());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "gc-diagnostics")]
+fn test_gc_diagnostics_match_stack_map_lookup() -> Result<(), anyhow::Error> {
+    use crate::*;
+    let engine = Engine::new(Config::new().wasm_reference_types(true))?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "" (func $gc))
+                (func (export "f") (param externref externref) (result externref)
+                    call $gc
+                    local.get 0
+                    call $g
+                    drop
+                    local.get 1)
+                (func $g (param externref) (result externref)
+                    call $gc
+                    local.get 0)
+                (func (export "no_refs") (param i32) (result i32)
+                    call $gc
+                    local.get 0)
+            )
+        "#,
+    )?;
+    let gc = Func::wrap(&mut store, |mut caller: Caller<'_, ()>| caller.gc());
+    Instance::new(&mut store, &module, &[gc.into()])?;
+
+    let diagnostics = store.gc_diagnostics();
+    assert_eq!(diagnostics.modules().len(), 1);
+    assert!(diagnostics.safepoints() > 0);
+
+    let cx = store.as_context_mut();
+    let registry = cx.0.modules_mut();
+    for (_, body) in module.compiled_module().finished_functions() {
+        let (start, end) = unsafe {
+            let ptr = (**body).as_ptr();
+            let len = (**body).len();
+            (ptr as usize, ptr as usize + len)
+        };
+        for pc in start..end {
+            let registered = registry
+                .lookup_module(pc)
+                .unwrap()
+                .lookup_stack_map(pc)
+                .is_some();
+            assert_eq!(diagnostics.has_stack_map_for(pc), registered);
+        }
+    }
+    assert!(!diagnostics.has_stack_map_for(0));
+    Ok(())
+}
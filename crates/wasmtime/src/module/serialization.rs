@@ -1,11 +1,13 @@
 //! Implements module serialization.
 
-use crate::{Engine, Module, OptLevel};
+use crate::{Engine, ErrorKind, Module, OptLevel};
 use anyhow::{anyhow, bail, Context, Result};
 use bincode::Options;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::fmt;
+use std::ops::Range;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::{collections::HashMap, fmt::Display};
@@ -16,6 +18,23 @@ use wasmtime_jit::{
 
 const HEADER: &[u8] = b"\0wasmtime-aot";
 
+/// Options for [`Module::deserialize_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct DeserializeOptions {
+    /// Whether data after the end of the serialized module is allowed, rather
+    /// than being rejected as a sign of corruption.
+    ///
+    /// This is for embedders which intentionally store data of their own after
+    /// a serialized module. Defaults to `false`.
+    pub allow_trailing_data: bool,
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
 fn bincode_options() -> impl Options {
     // Use a variable-length integer encoding instead of fixed length. The
     // module shown on #2318 gets compressed from ~160MB to ~110MB simply using
@@ -206,7 +225,7 @@ pub struct SerializedModule<'a> {
 
 impl<'a> SerializedModule<'a> {
     pub fn new(module: &'a Module) -> Self {
-        let compiler = module.engine().compiler();
+        let compiler = module.engine().compiler_for(module.opt_level());
         let artifacts = module
             .inner
             .artifact_upvars
@@ -224,7 +243,7 @@ impl<'a> SerializedModule<'a> {
             .collect::<Vec<_>>();
 
         Self::with_data(
-            compiler,
+            &compiler,
             artifacts,
             module_upvars,
             MyCow::Borrowed(module.types()),
@@ -274,35 +293,60 @@ impl<'a> SerializedModule<'a> {
     }
 
     pub fn into_module(mut self, engine: &Engine) -> Result<Module> {
-        let compiler = engine.compiler();
+        // Modules may have been compiled at any optimization level, so check
+        // the rest of the flags against the engine's compiler for that level.
+        let opt_level = match self.shared_flags.get("opt_level") {
+            Some(FlagValue::Enum(level)) => {
+                OptLevel::from_flag_value(level).unwrap_or_else(|| engine.opt_level())
+            }
+            _ => engine.opt_level(),
+        };
+        let compiler = engine.compiler_for(opt_level);
         let isa = compiler.isa();
 
         self.check_triple(isa)?;
         self.check_shared_flags(isa)?;
         self.check_isa_flags(isa)?;
-        self.check_strategy(compiler)?;
-        self.check_tunables(compiler)?;
-        self.check_features(compiler)?;
+        self.check_strategy(&compiler)?;
+        self.check_tunables(&compiler)?;
+        self.check_features(&compiler)?;
 
         let modules = CompiledModule::from_artifacts_list(
             self.artifacts
                 .into_iter()
                 .map(|i| i.unwrap_owned())
                 .collect(),
-            engine.compiler().isa(),
+            compiler.isa(),
             &*engine.config().profiler,
+            engine.config().native_debug_symbols,
         )?;
 
         assert!(!modules.is_empty());
 
+        for module in modules.iter() {
+            compiler
+                .tunables()
+                .check_module_limits(module.module())
+                .map_err(|e| crate::Error::msg(ErrorKind::Limit, e))?;
+        }
+        let types = self.types.unwrap_owned();
+        for ty in types.wasm_signatures.values() {
+            compiler
+                .tunables()
+                .check_func_type_limits(ty.params.len(), ty.returns.len())
+                .map_err(|e| crate::Error::msg(ErrorKind::Limit, e))?;
+        }
+
         let main_module = modules.len() - 1;
 
         Module::from_parts(
             engine,
             modules,
             main_module,
-            Arc::new(self.types.unwrap_owned()),
+            Arc::new(types),
             &self.module_upvars,
+            Vec::new(),
+            opt_level,
         )
     }
 
@@ -324,29 +368,44 @@ impl<'a> SerializedModule<'a> {
 
         bytes.write_all(version.as_bytes())?;
 
+        // Then the length of the serialized data, so that where it ends is
+        // known without deserializing it, and its checksum, so that damage to
+        // it is detected before it's used.
+        let len_offset = bytes.len();
+        let start = len_offset + 8 + 4;
+        bytes.resize(start, 0);
         bincode_options().serialize_into(&mut bytes, self)?;
+        let len = (bytes.len() - start) as u64;
+        let sum = checksum(&bytes[start..]);
+        bytes[len_offset..len_offset + 8].copy_from_slice(&len.to_le_bytes());
+        bytes[len_offset + 8..start].copy_from_slice(&sum.to_le_bytes());
 
         Ok(bytes)
     }
 
-    pub fn from_bytes(bytes: &[u8], check_version: bool) -> Result<Self> {
+    /// Deserializes a module from the start of `bytes`, returning it along
+    /// with the range of `bytes` after its end.
+    pub fn from_bytes(
+        bytes: &[u8],
+        check_version: bool,
+        options: &DeserializeOptions,
+    ) -> Result<(Self, Range<usize>)> {
         if !bytes.starts_with(HEADER) {
             bail!("bytes are not a compatible serialized wasmtime module");
         }
 
-        let bytes = &bytes[HEADER.len()..];
-
-        if bytes.is_empty() {
+        let version_start = HEADER.len() + 1;
+        if bytes.len() < version_start {
             bail!("serialized data data is empty");
         }
 
-        let version_len = bytes[0] as usize;
-        if bytes.len() < version_len + 1 {
+        let version_end = version_start + bytes[HEADER.len()] as usize;
+        if bytes.len() < version_end {
             bail!("serialized data is malformed");
         }
 
         if check_version {
-            let version = std::str::from_utf8(&bytes[1..1 + version_len])?;
+            let version = std::str::from_utf8(&bytes[version_start..version_end])?;
             if version != env!("CARGO_PKG_VERSION") {
                 bail!(
                     "Module was compiled with incompatible Wasmtime version '{}'",
@@ -355,9 +414,33 @@ impl<'a> SerializedModule<'a> {
             }
         }
 
-        Ok(bincode_options()
-            .deserialize::<SerializedModule<'_>>(&bytes[1 + version_len..])
-            .context("deserialize compilation artifacts")?)
+        let start = version_end + 8 + 4;
+        if bytes.len() < start {
+            bail!("serialized data is malformed");
+        }
+        let mut len = [0; 8];
+        len.copy_from_slice(&bytes[version_end..version_end + 8]);
+        let mut expected = [0; 4];
+        expected.copy_from_slice(&bytes[version_end + 8..start]);
+        let end = usize::try_from(u64::from_le_bytes(len))
+            .ok()
+            .and_then(|len| start.checked_add(len))
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| anyhow!("serialized data is truncated"))?;
+        if end < bytes.len() && !options.allow_trailing_data {
+            bail!(
+                "unexpected data at offset {} after the end of the serialized module",
+                end
+            );
+        }
+        if checksum(&bytes[start..end]) != u32::from_le_bytes(expected) {
+            bail!("serialized data is corrupted");
+        }
+
+        let module = bincode_options()
+            .deserialize::<SerializedModule<'_>>(&bytes[start..end])
+            .context("deserialize compilation artifacts")?;
+        Ok((module, end..bytes.len()))
     }
 
     fn check_triple(&self, isa: &dyn TargetIsa) -> Result<()> {
@@ -494,8 +577,26 @@ impl<'a> SerializedModule<'a> {
             parse_wasm_debuginfo,
             interruptable,
             consume_fuel,
+            epoch_interruption,
+            per_function_fuel_accounting,
+            yieldpoints,
             static_memory_bound_is_maximum,
             guard_before_linear_memory,
+            guard_before_linear_memory_size,
+            instruction_address_maps,
+            elide_guarded_bounds_checks,
+            memory_access_hooks,
+
+            // Limits on declared memories, tables and function types don't
+            // affect the code that was generated, so instead of requiring them
+            // to match the modules are checked against this engine's limits
+            // once loaded.
+            max_memories: _,
+            max_tables: _,
+            max_memory_pages: _,
+            max_table_elements: _,
+            max_func_params: _,
+            max_func_results: _,
         } = self.tunables;
 
         let other = compiler.tunables();
@@ -527,6 +628,17 @@ impl<'a> SerializedModule<'a> {
         )?;
         Self::check_bool(interruptable, other.interruptable, "interruption support")?;
         Self::check_bool(consume_fuel, other.consume_fuel, "fuel support")?;
+        Self::check_bool(
+            epoch_interruption,
+            other.epoch_interruption,
+            "epoch interruption support",
+        )?;
+        Self::check_bool(
+            per_function_fuel_accounting,
+            other.per_function_fuel_accounting,
+            "per-function fuel accounting",
+        )?;
+        Self::check_bool(yieldpoints, other.yieldpoints, "custom yieldpoints")?;
         Self::check_bool(
             static_memory_bound_is_maximum,
             other.static_memory_bound_is_maximum,
@@ -537,6 +649,30 @@ impl<'a> SerializedModule<'a> {
             other.guard_before_linear_memory,
             "guard before linear memory",
         )?;
+        let describe = |size: Option<u64>| match size {
+            Some(size) => format!("{} bytes", size),
+            None => "the size of the guard after it".to_string(),
+        };
+        Self::check_int(
+            describe(guard_before_linear_memory_size),
+            describe(other.guard_before_linear_memory_size),
+            "guard before linear memory size",
+        )?;
+        Self::check_bool(
+            instruction_address_maps,
+            other.instruction_address_maps,
+            "instruction address maps",
+        )?;
+        Self::check_bool(
+            elide_guarded_bounds_checks,
+            other.elide_guarded_bounds_checks,
+            "guarded bounds check elision",
+        )?;
+        Self::check_bool(
+            memory_access_hooks,
+            other.memory_access_hooks,
+            "memory access hooks",
+        )?;
 
         Ok(())
     }
@@ -658,21 +794,42 @@ mod test {
 
         let mut serialized = SerializedModule::new(&module);
         serialized.shared_flags.insert(
-            "opt_level".to_string(),
-            FlagValue::Enum(Cow::Borrowed("none")),
+            "enable_nan_canonicalization".to_string(),
+            FlagValue::Bool(true),
         );
 
         match serialized.into_module(&engine) {
             Ok(_) => unreachable!(),
             Err(e) => assert_eq!(
                 e.to_string(),
-                "Module was compiled with a different 'opt_level' setting: expected 'none' but host has 'speed'"
+                "Module was compiled with a different 'enable_nan_canonicalization' setting: expected 'true' but host has 'false'"
             ),
         }
 
         Ok(())
     }
 
+    #[test]
+    fn test_opt_level_may_differ() -> Result<()> {
+        let engine = Engine::default();
+        let module = Module::new_with_opt_level(&engine, "(module)", OptLevel::None)?;
+
+        let serialized = SerializedModule::new(&module);
+        assert!(matches!(
+            serialized.shared_flags.get("opt_level"),
+            Some(FlagValue::Enum(level)) if level == "none"
+        ));
+
+        let bytes = serialized.to_bytes()?;
+        let (serialized, _) =
+            SerializedModule::from_bytes(&bytes, true, &DeserializeOptions::default())?;
+        let module = serialized.into_module(&engine)?;
+        assert_eq!(module.opt_level(), OptLevel::None);
+        assert_eq!(engine.opt_level(), OptLevel::Speed);
+
+        Ok(())
+    }
+
     #[test]
     fn test_isa_flags_mismatch() -> Result<()> {
         let engine = Engine::default();
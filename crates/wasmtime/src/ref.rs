@@ -1,7 +1,10 @@
 #![allow(missing_docs)]
 
+use crate::Trap;
 use std::any::Any;
-use wasmtime_runtime::VMExternRef;
+use std::marker;
+use std::sync::{Arc, RwLock};
+use wasmtime_runtime::{VMExternRef, VMExternWeakRef};
 
 /// Represents an opaque reference to any data within WebAssembly.
 #[derive(Clone, Debug)]
@@ -21,10 +24,48 @@ impl ExternRef {
     }
 
     /// Get the underlying data for this `ExternRef`.
+    ///
+    /// Note that for references created with [`Scope::externref`] this is an
+    /// opaque value, and [`ExternRef::with_scoped`] must be used to get at the
+    /// borrowed data instead.
     pub fn data(&self) -> &dyn Any {
         &*self.inner
     }
 
+    /// Calls `f` with the data borrowed by a reference created with
+    /// [`Scope::externref`].
+    ///
+    /// Returns `Ok(None)` if this reference wasn't created by a [`Scope`] or
+    /// doesn't borrow a `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an "expired reference" trap if the scope this reference was
+    /// created in has ended, so host functions can propagate the error to
+    /// fail the wasm which handed them the reference.
+    ///
+    /// Note that the scope can't end while `f` runs, so `f` shouldn't wait on
+    /// another thread which is ending it.
+    pub fn with_scoped<T, R>(&self, f: impl FnOnce(&T) -> R) -> Result<Option<R>, Trap>
+    where
+        T: Any,
+    {
+        let scoped = match self.data().downcast_ref::<ScopedData>() {
+            Some(scoped) => scoped,
+            None => return Ok(None),
+        };
+        // Holding the lock keeps the scope from ending while `f` runs.
+        let live = scoped.live.read().unwrap();
+        if !*live {
+            return Err(Trap::new(
+                "expired reference: the scope this `externref` was created in has ended",
+            ));
+        }
+        // Safety: the scope is live, so the data it borrows is too.
+        let data: &dyn Any = unsafe { &*scoped.data };
+        Ok(data.downcast_ref::<T>().map(f))
+    }
+
     /// Get the strong reference count for this `ExternRef`.
     ///
     /// Note that this loads the reference count with a `SeqCst` ordering to
@@ -40,4 +81,143 @@ impl ExternRef {
     pub fn ptr_eq(&self, other: &ExternRef) -> bool {
         VMExternRef::eq(&self.inner, &other.inner)
     }
+
+    /// Creates a [`WeakExternRef`] to this reference's data.
+    pub fn downgrade(&self) -> WeakExternRef {
+        WeakExternRef {
+            inner: self.inner.downgrade(),
+        }
+    }
+}
+
+/// A weak reference to the data of an [`ExternRef`], created with
+/// [`ExternRef::downgrade`].
+///
+/// Weak references don't keep the data alive, so they're useful for host-side
+/// caches keyed by references handed to wasm: once wasm and the host have
+/// dropped all their `ExternRef`s, the data is dropped and
+/// [`WeakExternRef::upgrade`] returns `None`.
+///
+/// Note that references which wasm has dropped may still be held by the store
+/// until its next garbage collection, which can be forced with
+/// [`Store::gc`](crate::Store::gc).
+#[derive(Clone, Debug)]
+pub struct WeakExternRef {
+    inner: VMExternWeakRef,
+}
+
+impl WeakExternRef {
+    /// Gets an [`ExternRef`] to the data, if it's still alive.
+    ///
+    /// The returned reference is [`ExternRef::ptr_eq`] to the one this was
+    /// downgraded from.
+    pub fn upgrade(&self) -> Option<ExternRef> {
+        self.inner.upgrade().map(|inner| ExternRef { inner })
+    }
+
+    /// Does this weak reference point to the same data as `other`?
+    ///
+    /// Like [`ExternRef::ptr_eq`], this is *only* pointer equality. It stays
+    /// accurate after the data has been dropped: the address of the data isn't
+    /// reused while weak references to it remain.
+    pub fn ptr_eq(&self, other: &ExternRef) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+}
+
+/// A scope within which `externref`s can borrow host data rather than own it.
+///
+/// [`ExternRef::new`] requires `'static` data, so data which only lives for
+/// the duration of a call would otherwise have to be cloned or put in an `Arc`
+/// before it can be handed to wasm. References created with
+/// [`Scope::externref`] instead borrow their data, and are only valid until
+/// the scope ends. Once it has, the data is no longer accessible through any
+/// reference created in the scope, wherever wasm has put it (tables, globals,
+/// or the stack), and [`ExternRef::with_scoped`] returns an "expired
+/// reference" trap instead.
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::<()>::default();
+/// let len = Func::wrap(&mut store, |r: Option<ExternRef>| -> Result<u32, Trap> {
+///     let len = r.unwrap().with_scoped(|s: &String| s.len() as u32)?;
+///     Ok(len.unwrap_or(0))
+/// });
+/// let len = len.typed::<Option<ExternRef>, u32, _>(&store)?;
+///
+/// let name = String::from("borrowed");
+/// let r = Scope::run(|scope| -> anyhow::Result<ExternRef> {
+///     let r = scope.externref(&name);
+///     assert_eq!(len.call(&mut store, Some(r.clone()))?, 8);
+///     Ok(r)
+/// })?;
+///
+/// // The scope has ended, so the reference can't be used anymore.
+/// let err = len.call(&mut store, Some(r)).unwrap_err();
+/// assert!(err.to_string().contains("expired reference"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Costs
+///
+/// Creating a scoped reference allocates just like [`ExternRef::new`], and
+/// reference counts the scope's state. Nothing is swept when a scope ends:
+/// instead every reference checks that its scope is live when it's accessed,
+/// which takes a read lock on the scope's state for the duration of the
+/// access, and ending a scope takes the write lock. References which outlive
+/// their scope keep that small allocation alive until they're collected, but
+/// never the borrowed data.
+pub struct Scope<'a> {
+    live: Arc<RwLock<bool>>,
+    _borrows: marker::PhantomData<fn(&'a ()) -> &'a ()>,
+}
+
+/// The payload of references created by [`Scope::externref`].
+struct ScopedData {
+    live: Arc<RwLock<bool>>,
+    data: *const (dyn Any + Sync),
+}
+
+// Safety: the data is only accessed through shared references, and is `Sync`.
+unsafe impl Send for ScopedData {}
+unsafe impl Sync for ScopedData {}
+
+impl<'a> Scope<'a> {
+    /// Runs `f` with a new scope, which ends once `f` returns (or panics).
+    ///
+    /// Scopes are only handed out by reference to `f` so that they can't be
+    /// leaked, which would leave references to borrowed data valid after the
+    /// data is gone.
+    pub fn run<R>(f: impl FnOnce(&Scope<'a>) -> R) -> R {
+        let scope = Scope {
+            live: Arc::new(RwLock::new(true)),
+            _borrows: marker::PhantomData,
+        };
+        f(&scope)
+    }
+
+    /// Creates an `externref` which borrows `value` for as long as this
+    /// scope lasts.
+    ///
+    /// The data can be accessed with [`ExternRef::with_scoped`] until the
+    /// scope ends.
+    pub fn externref<T>(&self, value: &'a T) -> ExternRef
+    where
+        T: Any + Sync,
+    {
+        // Note that the raw pointer doesn't carry the lifetime of the borrow,
+        // which `live` tracks instead.
+        ExternRef::new(ScopedData {
+            live: self.live.clone(),
+            data: value,
+        })
+    }
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        *self.live.write().unwrap() = false;
+    }
 }
@@ -1,6 +1,10 @@
 //! Implement a registry of function signatures, for fast indirect call
 //! signature checking.
+//!
+//! The registry also interns the signatures' types: it keeps one `FuncType`
+//! for each registered signature, and hands out clones of it.
 
+use crate::FuncType;
 use std::{
     collections::{hash_map::Entry, HashMap},
     sync::RwLock,
@@ -77,12 +81,12 @@ impl Drop for SignatureCollection {
 #[derive(Debug)]
 struct RegistryEntry {
     references: usize,
-    ty: WasmFuncType,
+    ty: FuncType,
 }
 
 #[derive(Debug, Default)]
 struct SignatureRegistryInner {
-    map: HashMap<WasmFuncType, VMSharedSignatureIndex>,
+    map: HashMap<FuncType, VMSharedSignatureIndex>,
     entries: Vec<Option<RegistryEntry>>,
     free: Vec<VMSharedSignatureIndex>,
 }
@@ -100,7 +104,7 @@ impl SignatureRegistryInner {
         let mut map = HashMap::default();
 
         for (_, ty) in signatures.iter() {
-            sigs.push(self.register(ty));
+            sigs.push(self.register(&FuncType::from_wasm_func_type(ty.clone())));
         }
 
         for (index, trampoline) in trampolines {
@@ -110,7 +114,7 @@ impl SignatureRegistryInner {
         (sigs, map)
     }
 
-    fn register(&mut self, ty: &WasmFuncType) -> VMSharedSignatureIndex {
+    fn register(&mut self, ty: &FuncType) -> VMSharedSignatureIndex {
         let len = self.map.len();
 
         let index = match self.map.entry(ty.clone()) {
@@ -221,7 +225,10 @@ impl SignatureRegistry {
     }
 
     /// Looks up a function type from a shared signature index.
-    pub fn lookup_type(&self, index: VMSharedSignatureIndex) -> Option<WasmFuncType> {
+    ///
+    /// All lookups of the same signature return clones of the same interned
+    /// type.
+    pub fn lookup_type(&self, index: VMSharedSignatureIndex) -> Option<FuncType> {
         self.0
             .read()
             .unwrap()
@@ -232,8 +239,9 @@ impl SignatureRegistry {
 
     /// Registers a single function with the collection.
     ///
-    /// Returns the shared signature index for the function.
-    pub fn register(&self, ty: &WasmFuncType) -> VMSharedSignatureIndex {
+    /// Returns the shared signature index for the function. If the signature
+    /// isn't registered yet, `ty` becomes its interned type.
+    pub fn register(&self, ty: &FuncType) -> VMSharedSignatureIndex {
         self.0.write().unwrap().register(ty)
     }
 
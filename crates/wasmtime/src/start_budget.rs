@@ -0,0 +1,306 @@
+use crate::store::StoreInnermost;
+use crate::{InterruptCause, InterruptHandle, StoreContextMut, Trap, TrapCode};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Limits on the execution of a module's start function which apply instead
+/// of the limits of the store it's instantiated in.
+///
+/// A start function runs as part of instantiation, before the embedder has a
+/// chance to impose any per-call limits of its own, so an untrusted module can
+/// otherwise spin in its start function for as long as the store's fuel and
+/// interrupts allow. A budget is passed to [`Instance::new_with_budget`] and
+/// [`InstancePre::instantiate_with_budget`] to bound the start function
+/// separately, and if it's exceeded instantiation fails with a
+/// [`StartBudgetExceeded`] error.
+///
+/// The budget covers all start functions run by an instantiation, including
+/// those of instances created with the module linking proposal.
+///
+/// [`Instance::new_with_budget`]: crate::Instance::new_with_budget
+/// [`InstancePre::instantiate_with_budget`]: crate::InstancePre::instantiate_with_budget
+#[derive(Debug, Clone, Default)]
+pub struct StartBudget {
+    fuel: Option<u64>,
+    time_limit: Option<Duration>,
+}
+
+impl StartBudget {
+    /// Creates a budget with no limits, in which case start functions run as
+    /// they would with [`Instance::new`](crate::Instance::new).
+    pub fn new() -> StartBudget {
+        StartBudget::default()
+    }
+
+    /// Gives start functions `fuel` to run with.
+    ///
+    /// While a start function runs the store's own fuel is set aside and
+    /// only the budget's fuel is available. Running out of it traps, even if
+    /// the store is configured to do something else when it runs out of fuel.
+    /// Afterwards the store's fuel is restored to what it was before
+    /// instantiation, and only the fuel consumed from the budget is added to
+    /// [`Store::fuel_consumed`](crate::Store::fuel_consumed).
+    ///
+    /// Note that fuel added to the store by host functions called from a
+    /// start function is added to the budget, and so is discarded afterwards.
+    ///
+    /// This requires [`Config::consume_fuel`](crate::Config::consume_fuel) to
+    /// be enabled.
+    pub fn fuel(&mut self, fuel: u64) -> &mut Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Limits the wall-clock time start functions may run for.
+    ///
+    /// The limit is measured from the start of instantiation, and once it
+    /// passes the running start function is interrupted. The limits of all
+    /// instantiations are watched by a single thread, which is spawned the
+    /// first time a time limit is used.
+    ///
+    /// This requires [`Config::interruptable`](crate::Config::interruptable)
+    /// to be enabled.
+    pub fn time_limit(&mut self, limit: Duration) -> &mut Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Checks that this budget can be applied in `store` and starts the clock
+    /// on its time limit.
+    pub(crate) fn begin(&self, store: &StoreInnermost) -> Result<RunningBudget> {
+        if self.fuel.is_some() && !store.engine().config().tunables.consume_fuel {
+            bail!("fuel is not configured in this store");
+        }
+        let deadline = match self.time_limit {
+            Some(limit) => {
+                let cause = InterruptCause::Label(DEADLINE_CAUSE.into());
+                Some(Deadline {
+                    at: Instant::now() + limit,
+                    handle: Arc::new(store.interrupt_handle(Some(cause.clone()))?),
+                    cause,
+                })
+            }
+            None => None,
+        };
+        Ok(RunningBudget {
+            fuel: self.fuel,
+            deadline,
+        })
+    }
+}
+
+/// The cause of the interrupts sent when a start function's time limit runs
+/// out.
+const DEADLINE_CAUSE: &str = "start function time limit";
+
+/// The error returned when instantiation fails because a start function
+/// exceeded its [`StartBudget`].
+#[derive(Debug)]
+pub struct StartBudgetExceeded {
+    limit: StartLimit,
+    trap: Trap,
+}
+
+/// Which limit of a [`StartBudget`] was exceeded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StartLimit {
+    /// The start function ran out of fuel.
+    Fuel,
+    /// The start function ran past its time limit.
+    Time,
+}
+
+impl StartBudgetExceeded {
+    /// Returns which limit the start function exceeded.
+    pub fn limit(&self) -> StartLimit {
+        self.limit
+    }
+
+    /// Returns the trap which aborted the start function.
+    pub fn trap(&self) -> &Trap {
+        &self.trap
+    }
+}
+
+impl fmt::Display for StartBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            StartLimit::Fuel => f.write_str("start function exceeded its fuel budget"),
+            StartLimit::Time => f.write_str("start function exceeded its time limit"),
+        }
+    }
+}
+
+impl Error for StartBudgetExceeded {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.trap)
+    }
+}
+
+/// A [`StartBudget`] which is being applied to an instantiation.
+pub(crate) struct RunningBudget {
+    /// The fuel left in the budget.
+    fuel: Option<u64>,
+    deadline: Option<Deadline>,
+}
+
+struct Deadline {
+    at: Instant,
+    cause: InterruptCause,
+    handle: Arc<InterruptHandle>,
+}
+
+impl RunningBudget {
+    /// Runs the start function invoked by `start` within what's left of this
+    /// budget.
+    pub(crate) fn run<T>(
+        &mut self,
+        store: &mut StoreContextMut<'_, T>,
+        start: impl FnOnce(&mut StoreContextMut<'_, T>) -> Result<(), Trap>,
+    ) -> Result<()> {
+        let saved_fuel = self.fuel.map(|fuel| store.0.begin_fuel_budget(fuel));
+        let prev_stack_limit = store.0.interrupts().stack_limit.load(Relaxed);
+        let timer = self
+            .deadline
+            .as_ref()
+            .map(|d| Timer::start(d.at, d.handle.clone()));
+
+        let result = start(store);
+
+        let fired = timer.map_or(false, |timer| timer.finish());
+        let fuel_left = saved_fuel.map(|saved| store.0.end_fuel_budget(saved));
+        if let Some(left) = fuel_left {
+            self.fuel = Some(u64::try_from(left).unwrap_or(0));
+        }
+
+        let deadline = self.deadline.as_ref();
+        let timed_out = |trap: &Trap| fired && trap.interrupt_cause() == deadline.map(|d| &d.cause);
+        match result {
+            Err(trap) if timed_out(&trap) => Err(StartBudgetExceeded {
+                limit: StartLimit::Time,
+                trap,
+            }
+            .into()),
+            result => {
+                // The start function finished, or trapped for some other
+                // reason, before it noticed the interrupt, so make sure that
+                // the interrupt doesn't leak out into whatever runs next in
+                // this store.
+                if fired {
+                    clear_stale_interrupt(store.0, prev_stack_limit, &deadline.unwrap().cause);
+                }
+                match result {
+                    Err(trap)
+                        if self.fuel.is_some() && trap.trap_code() == Some(TrapCode::OutOfFuel) =>
+                    {
+                        Err(StartBudgetExceeded {
+                            limit: StartLimit::Fuel,
+                            trap,
+                        }
+                        .into())
+                    }
+                    result => Ok(result?),
+                }
+            }
+        }
+    }
+}
+
+/// Resets the interrupt flag of `store` to `stack_limit`, its value before
+/// the start function ran, if the pending interrupt is the deadline's.
+fn clear_stale_interrupt(store: &StoreInnermost, stack_limit: usize, cause: &InterruptCause) {
+    let pending = store.interrupt_cause();
+    let mut pending = pending.lock().unwrap();
+    if store.interrupts().is_interrupted() && pending.as_ref() == Some(cause) {
+        store.interrupts().stack_limit.store(stack_limit, Relaxed);
+        *pending = None;
+    }
+}
+
+/// A pending interrupt of wasm once a deadline passes.
+///
+/// All timers share a single thread, which is started the first time a
+/// timer is needed and then waits for whichever deadline is due next.
+struct Timer {
+    id: u64,
+}
+
+#[derive(Default)]
+struct Timers {
+    next_id: u64,
+    pending: HashMap<u64, PendingTimer>,
+    thread_started: bool,
+}
+
+struct PendingTimer {
+    deadline: Instant,
+    handle: Arc<InterruptHandle>,
+    fired: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref TIMERS: (Mutex<Timers>, Condvar) = Default::default();
+}
+
+impl Timer {
+    fn start(deadline: Instant, handle: Arc<InterruptHandle>) -> Timer {
+        let (lock, cvar) = &*TIMERS;
+        let mut timers = lock.lock().unwrap();
+        if !timers.thread_started {
+            thread::Builder::new()
+                .name("wasmtime-start-budget".to_string())
+                .spawn(run_timers)
+                .expect("failed to spawn the start budget timer thread");
+            timers.thread_started = true;
+        }
+        let id = timers.next_id;
+        timers.next_id += 1;
+        timers.pending.insert(
+            id,
+            PendingTimer {
+                deadline,
+                handle,
+                fired: false,
+            },
+        );
+        cvar.notify_one();
+        Timer { id }
+    }
+
+    /// Stops the timer, returning whether it interrupted wasm.
+    fn finish(self) -> bool {
+        let (lock, _) = &*TIMERS;
+        let timer = lock.lock().unwrap().pending.remove(&self.id);
+        timer.map_or(false, |timer| timer.fired)
+    }
+}
+
+/// The body of the thread shared by all timers, which interrupts wasm for
+/// each timer whose deadline has passed.
+fn run_timers() {
+    let (lock, cvar) = &*TIMERS;
+    let mut timers = lock.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let mut next = None;
+        for timer in timers.pending.values_mut().filter(|t| !t.fired) {
+            if timer.deadline <= now {
+                timer.fired = true;
+                timer.handle.interrupt();
+            } else if next.map_or(true, |next| timer.deadline < next) {
+                next = Some(timer.deadline);
+            }
+        }
+        timers = match next {
+            Some(next) => cvar.wait_timeout(timers, next - now).unwrap().0,
+            None => cvar.wait(timers).unwrap(),
+        };
+    }
+}
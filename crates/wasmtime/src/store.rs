@@ -1,6 +1,13 @@
-use crate::{module::ModuleRegistry, Engine, Module, Trap};
+use crate::instance::ImportTemplate;
+use crate::linker::Definition;
+use crate::{
+    module::ModuleRegistry, Engine, FaultResolution, FrameInfo, Func, HostPanicPolicy, Instance,
+    InstanceId, Memory, MemoryAccessFault, MemoryAccessKind, Module, PreparedModules, SymbolMap,
+    Trap,
+};
 use anyhow::{bail, Result};
 use std::cell::UnsafeCell;
+use std::cmp;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::error::Error;
@@ -8,21 +15,32 @@ use std::fmt;
 use std::future::Future;
 use std::marker;
 use std::mem::ManuallyDrop;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
+use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
 use std::ptr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use std::sync::{Arc, Mutex, Weak};
 use std::task::{Context, Poll};
+use wasmtime_environ::wasm::{DefinedFuncIndex, FuncIndex};
 use wasmtime_runtime::{
     InstanceAllocationRequest, InstanceAllocator, InstanceHandle, ModuleInfo,
     OnDemandInstanceAllocator, SignalHandler, VMCallerCheckedAnyfunc, VMContext, VMExternRef,
-    VMExternRefActivationsTable, VMInterrupts, VMSharedSignatureIndex, VMTrampoline,
+    VMExternRefActivationsTable, VMInterrupts, VMMemoryDefinition, VMSharedSignatureIndex,
+    VMTrampoline, VirtualMemoryAccess,
 };
 
+mod activation_times;
+use self::activation_times::ActivationClock;
+pub use self::activation_times::ActivationTimes;
 mod context;
 pub use self::context::*;
 mod data;
 pub use self::data::*;
+#[cfg(feature = "async")]
+mod suspended;
+#[cfg(feature = "async")]
+pub use self::suspended::SuspendedAccess;
 
 /// A [`Store`] is a collection of WebAssembly instances and host-defined state.
 ///
@@ -106,6 +124,15 @@ pub struct StoreInner<T> {
     limiter: Option<Box<dyn FnMut(&mut T) -> &mut (dyn crate::ResourceLimiter) + Send + Sync>>,
     entering_native_hook: Option<Box<dyn FnMut(&mut T) -> Result<(), crate::Trap> + Send + Sync>>,
     exiting_native_hook: Option<Box<dyn FnMut(&mut T) -> Result<(), crate::Trap> + Send + Sync>>,
+    call_hook: Option<Box<dyn FnMut(&mut T, CallHook) -> Result<(), crate::Trap> + Send + Sync>>,
+    memory_access_handler:
+        Option<Box<dyn FnMut(&mut T, MemoryAccessFault) -> FaultResolution + Send + Sync>>,
+    /// Called when wasm reaches the epoch deadline, returning how many epochs
+    /// to extend it by. Wasm traps at the deadline if this isn't set.
+    epoch_deadline_callback: Option<Box<dyn FnMut(&mut T) -> Result<u64, Trap> + Send + Sync>>,
+    /// Callbacks registered with `Caller::defer_on_unwind`, one list for each
+    /// wasm activation currently on the stack, innermost last.
+    unwind_callbacks: Vec<Vec<Box<dyn FnOnce(&mut T) + Send + Sync>>>,
     // for comments about `ManuallyDrop`, see `Store::into_data`
     data: ManuallyDrop<T>,
 }
@@ -128,11 +155,28 @@ impl<T> DerefMut for StoreInner<T> {
 pub struct StoreInnermost {
     engine: Engine,
     interrupts: Arc<VMInterrupts>,
+    /// The cause of the pending interrupt, if any, shared with this store's
+    /// `InterruptHandle`s.
+    interrupt_cause: Arc<Mutex<Option<InterruptCause>>>,
     instances: Vec<StoreInstance>,
     signal_handler: Option<Box<SignalHandler<'static>>>,
     externref_activations_table: VMExternRefActivationsTable,
     modules: ModuleRegistry,
+    /// Names given to instances with `Instance::set_name`.
+    instance_names: HashMap<InstanceId, Arc<str>>,
+    /// The pages registered with `Memory::register_virtual_pages`, keyed by
+    /// the address of each memory's `VMMemoryDefinition`.
+    virtual_pages: HashMap<usize, VirtualPages>,
     host_trampolines: HashMap<VMSharedSignatureIndex, VMTrampoline>,
+    /// The `Func` created in this store for each host function defined in a
+    /// `Linker`, keyed by the address of the `HostFunc`. Each `Func` keeps its
+    /// `HostFunc` alive, so addresses aren't reused while they're in here.
+    shared_host_funcs: HashMap<usize, Func>,
+    /// The resolved imports of each `InstancePre` instantiated in this store,
+    /// keyed by the address of its list of imports. The list is only held
+    /// weakly, which keeps its address from being reused, and its entry is
+    /// removed once the `InstancePre` has been dropped.
+    import_templates: HashMap<usize, (Weak<[Definition]>, Arc<ImportTemplate>)>,
     // Numbers of resources instantiated in this store, and their limits
     instance_count: usize,
     instance_limit: usize,
@@ -143,9 +187,22 @@ pub struct StoreInnermost {
     /// An adjustment to add to the fuel consumed value in `interrupts` above
     /// to get the true amount of fuel consumed.
     fuel_adj: i64,
+    /// The fuel consumed by each function with per-function fuel accounting,
+    /// by the address of its module and its index within that module. The
+    /// module is kept alive so that its address isn't reused by another one.
+    function_fuel: HashMap<
+        usize,
+        (
+            Arc<wasmtime_environ::Module>,
+            HashMap<DefinedFuncIndex, u64>,
+        ),
+    >,
     #[cfg(feature = "async")]
     async_state: AsyncState,
     out_of_gas_behavior: OutOfGas,
+    cancellation: Cancellation,
+    stack_usage: StackUsage,
+    activation_clock: ActivationClock,
     store_data: StoreData,
     default_callee: InstanceHandle,
 }
@@ -155,6 +212,15 @@ struct AsyncState {
     current_suspend:
         UnsafeCell<*const wasmtime_fiber::Suspend<Result<(), Trap>, (), Result<(), Trap>>>,
     current_poll_cx: UnsafeCell<*mut Context<'static>>,
+    /// The number of polls of futures running wasm on a fiber which are in
+    /// progress.
+    polls_in_progress: UnsafeCell<usize>,
+    /// Whether wasm suspended at a fuel yield point and the suspended access
+    /// hook hasn't run for that suspension yet.
+    yielded_for_fuel: UnsafeCell<bool>,
+    /// Whether a `SuspendedAccess` to the store currently exists.
+    in_suspended_access: UnsafeCell<bool>,
+    suspended_access_hook: UnsafeCell<Option<Box<dyn FnMut(&SuspendedAccess<'_>) + Send + Sync>>>,
 }
 
 // Lots of pesky unsafe cells and pointers in this structure. This means we need
@@ -174,13 +240,168 @@ struct StoreInstance {
     ondemand: bool,
 }
 
-#[derive(Copy, Clone)]
+/// State backing this store's [`CancellationToken`]s.
+struct Cancellation {
+    requested: Arc<AtomicBool>,
+    /// The amount of fuel granted to wasm once it's first noticed that
+    /// cancellation was requested.
+    grace_fuel: u64,
+    /// Whether `grace_fuel` has already been handed out.
+    grace_granted: bool,
+    /// The epoch deadline set by the embedder, which is overridden while a
+    /// cancellation is in progress.
+    epoch_deadline: u64,
+}
+
+/// The error with which wasm traps once it's been cancelled and has used up
+/// its grace period.
+#[derive(Debug)]
+struct CancelledError;
+
+impl fmt::Display for CancelledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebAssembly execution was cancelled")
+    }
+}
+
+impl Error for CancelledError {}
+
+/// Native stack accounting for nested wasm activations.
+///
+/// Each entry into wasm gets its own `max_wasm_stack` worth of stack, but all
+/// activations together (along with the host frames between them) are also
+/// limited to `max_native_stack`. This records where that total budget is
+/// measured from.
+#[derive(Copy, Clone, Default)]
+pub(crate) struct StackUsage {
+    /// The stack pointer of the outermost wasm activation on the current
+    /// native stack, or 0 if wasm hasn't been entered on this stack.
+    pub(crate) base: usize,
+    /// Stack already consumed by enclosing activations running on other
+    /// native stacks, e.g. the fiber which called into an async host function
+    /// that is now re-entering wasm on a fresh fiber.
+    pub(crate) consumed: usize,
+}
+
+impl StackUsage {
+    /// Returns how much of the total native stack budget is used by the
+    /// enclosing activations, when wasm is entered at `stack_pointer`.
+    pub(crate) fn used_at(&self, stack_pointer: usize) -> usize {
+        let on_this_stack = match self.base {
+            0 => 0,
+            base => base.saturating_sub(stack_pointer),
+        };
+        self.consumed.saturating_add(on_this_stack)
+    }
+
+    /// Returns the stack limit for wasm entered at `stack_pointer`, beyond
+    /// which it raises a stack overflow trap.
+    ///
+    /// The limit is at most `max_wasm_stack` bytes below `stack_pointer`,
+    /// and less if that would take the total stack used beyond
+    /// `max_native_stack`. If the budget is already exhausted the limit is
+    /// `stack_pointer` itself, so the first wasm function traps. None of
+    /// this can wrap around, even for limits bigger than the address space
+    /// below the stack pointer, and the limit is never below
+    /// `MIN_WASM_STACK_LIMIT`, as a limit of zero would never be reached.
+    pub(crate) fn wasm_stack_limit(
+        &self,
+        stack_pointer: usize,
+        max_wasm_stack: usize,
+        max_native_stack: usize,
+    ) -> usize {
+        let remaining = max_native_stack.saturating_sub(self.used_at(stack_pointer));
+        let limit = stack_pointer.saturating_sub(cmp::min(max_wasm_stack, remaining));
+        cmp::max(limit, MIN_WASM_STACK_LIMIT)
+    }
+}
+
+/// The lowest stack limit given to wasm.
+///
+/// Neither Linux nor Windows map anything in the first 64 KiB of the address
+/// space by default, so no stack extends down into it and a limit there is
+/// as far as wasm could ever get anyway.
+const MIN_WASM_STACK_LIMIT: usize = 64 * 1024;
+
+/// The fuel state of a store which is set aside while wasm runs with a
+/// separate fuel budget, see `StoreInnermost::begin_fuel_budget`.
+pub(crate) struct FuelBudget {
+    remaining: i64,
+    out_of_gas_behavior: OutOfGas,
+}
+
 enum OutOfGas {
     Trap,
     InjectFuel {
         injection_count: u64,
         fuel_to_inject: u64,
     },
+    Callback {
+        callback: Box<dyn FnMut(&FuelExhausted) -> FuelDecision + Send + Sync>,
+        injections: u64,
+    },
+}
+
+/// Information about WebAssembly running out of fuel, passed to the callback
+/// configured with [`Store::out_of_fuel_async_yield_with_callback`].
+#[derive(Debug)]
+pub struct FuelExhausted {
+    fuel_consumed: u64,
+    injections: u64,
+}
+
+impl FuelExhausted {
+    /// Returns the total fuel consumed by the store so far.
+    pub fn fuel_consumed(&self) -> u64 {
+        self.fuel_consumed
+    }
+
+    /// Returns how many times fuel was previously injected by the callback.
+    pub fn injections(&self) -> u64 {
+        self.injections
+    }
+}
+
+/// What to do when WebAssembly runs out of fuel, as decided by the callback
+/// configured with [`Store::out_of_fuel_async_yield_with_callback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuelDecision {
+    /// Yield to the future's caller, then continue with this much more fuel
+    /// once the future is polled again.
+    InjectAndYield(u64),
+    /// Continue immediately with this much more fuel, without yielding.
+    InjectWithoutYield(u64),
+    /// Trap, as if no fuel were injected.
+    Trap,
+}
+
+/// Which way execution is crossing between the host and WebAssembly, passed
+/// to the hook configured with [`Store::call_hook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallHook {
+    /// The host is calling a WebAssembly function.
+    CallingWasm,
+    /// A WebAssembly function called by the host is returning, or trapped.
+    ReturningFromWasm,
+    /// WebAssembly is calling a host function.
+    CallingHost,
+    /// A host function called by WebAssembly is returning, or trapped.
+    ReturningFromHost,
+}
+
+impl CallHook {
+    /// Returns whether execution is moving from WebAssembly to the host.
+    pub fn entering_host(&self) -> bool {
+        match self {
+            CallHook::ReturningFromWasm | CallHook::CallingHost => true,
+            CallHook::CallingWasm | CallHook::ReturningFromHost => false,
+        }
+    }
+
+    /// Returns whether execution is moving from the host to WebAssembly.
+    pub fn exiting_host(&self) -> bool {
+        !self.entering_host()
+    }
 }
 
 impl<T> Store<T> {
@@ -218,12 +439,20 @@ impl<T> Store<T> {
             _marker: marker::PhantomPinned,
             inner: StoreInnermost {
                 engine: engine.clone(),
-                interrupts: Default::default(),
+                interrupts: Arc::new(VMInterrupts {
+                    epoch_ptr: engine.epoch_counter(),
+                    ..Default::default()
+                }),
+                interrupt_cause: Default::default(),
                 instances: Vec::new(),
                 signal_handler: None,
                 externref_activations_table: VMExternRefActivationsTable::new(),
                 modules: ModuleRegistry::default(),
+                instance_names: HashMap::new(),
+                virtual_pages: HashMap::new(),
                 host_trampolines: HashMap::default(),
+                shared_host_funcs: HashMap::new(),
+                import_templates: HashMap::new(),
                 instance_count: 0,
                 instance_limit: wasmtime_runtime::DEFAULT_INSTANCE_LIMIT,
                 memory_count: 0,
@@ -235,14 +464,31 @@ impl<T> Store<T> {
                 async_state: AsyncState {
                     current_suspend: UnsafeCell::new(ptr::null()),
                     current_poll_cx: UnsafeCell::new(ptr::null_mut()),
+                    polls_in_progress: UnsafeCell::new(0),
+                    yielded_for_fuel: UnsafeCell::new(false),
+                    in_suspended_access: UnsafeCell::new(false),
+                    suspended_access_hook: UnsafeCell::new(None),
                 },
                 out_of_gas_behavior: OutOfGas::Trap,
+                function_fuel: HashMap::new(),
+                cancellation: Cancellation {
+                    requested: Arc::new(AtomicBool::new(false)),
+                    grace_fuel: 0,
+                    grace_granted: false,
+                    epoch_deadline: 0,
+                },
+                stack_usage: StackUsage::default(),
+                activation_clock: ActivationClock::new(engine.config().activation_times),
                 store_data: StoreData::new(),
                 default_callee,
             },
             limiter: None,
             entering_native_hook: None,
             exiting_native_hook: None,
+            call_hook: None,
+            memory_access_handler: None,
+            epoch_deadline_callback: None,
+            unwind_callbacks: Vec::new(),
             data: ManuallyDrop::new(data),
         });
 
@@ -257,7 +503,54 @@ impl<T> Store<T> {
         }
     }
 
+    /// Creates a new [`Store`], like [`Store::new`], which starts out with
+    /// all of `modules` already registered.
+    ///
+    /// Instantiating any of `modules` in the returned store then skips
+    /// registering the module with the store, which is otherwise done the
+    /// first time each module is instantiated. See [`PreparedModules`] for
+    /// more information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `modules` were prepared for a different [`Engine`] than
+    /// `engine`.
+    pub fn new_with_modules(engine: &Engine, data: T, modules: &PreparedModules) -> Self {
+        assert!(
+            Engine::same(engine, modules.engine()),
+            "cross-`Engine` store creation is not supported"
+        );
+        let mut store = Store::new(engine, data);
+        store.inner.modules = ModuleRegistry::new(modules);
+        store
+    }
+
     /// Access the underlying data owned by this `Store`.
+    ///
+    /// This is the `data` given to [`Store::new`], whose type is part of the
+    /// type of the store. It's the place for state of the embedding which host
+    /// functions need, and they can access it with
+    /// [`Caller::data`](crate::Caller::data) rather than capturing it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// struct Counter {
+    ///     calls: u32,
+    /// }
+    ///
+    /// let engine = Engine::default();
+    /// let mut store = Store::new(&engine, Counter { calls: 0 });
+    /// let count = Func::wrap(&mut store, |mut caller: Caller<'_, Counter>| {
+    ///     caller.data_mut().calls += 1;
+    /// });
+    /// count.typed::<(), (), _>(&store)?.call(&mut store, ())?;
+    /// assert_eq!(store.data().calls, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
     #[inline]
     pub fn data(&self) -> &T {
         self.inner.data()
@@ -379,11 +672,98 @@ impl<T> Store<T> {
         self.inner.exiting_native_hook = Some(Box::new(hook));
     }
 
+    /// Configure a function that runs each time execution crosses between the
+    /// host and WebAssembly, in either direction.
+    ///
+    /// The hook is given this store's data and which of the four transitions
+    /// is happening, as a [`CallHook`]. Unless the hook itself returns a trap
+    /// for a call, every [`CallHook::CallingWasm`] is paired with a
+    /// [`CallHook::ReturningFromWasm`], even when the wasm traps, and every
+    /// [`CallHook::CallingHost`] with a [`CallHook::ReturningFromHost`], even
+    /// when the host function returns a trap or panics, whatever the
+    /// [`HostPanicPolicy`]. This covers
+    /// [`Func::call`](crate::Func::call),
+    /// [`Func::call_async`](crate::Func::call_async), typed calls,
+    /// instantiation's start function and every kind of host function.
+    ///
+    /// The hook only has access to the store's data, not the store, so it
+    /// can't call into WebAssembly itself and is never called recursively.
+    ///
+    /// This function may return a [`Trap`], which is handled as it is for
+    /// [`Store::entering_native_code_hook`] and
+    /// [`Store::exiting_native_code_hook`]. Those hooks run at the same points
+    /// as this one, which is called first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::default();
+    /// let mut store = Store::new(&engine, Vec::new());
+    /// store.call_hook(|log, hook| {
+    ///     log.push(hook);
+    ///     Ok(())
+    /// });
+    /// let f = Func::wrap(&mut store, || {});
+    /// let module = Module::new(
+    ///     &engine,
+    ///     r#"(module (import "" "" (func $f)) (func (export "run") call $f))"#,
+    /// )?;
+    /// let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    /// instance.get_typed_func::<(), (), _>(&mut store, "run")?.call(&mut store, ())?;
+    /// assert_eq!(
+    ///     store.data(),
+    ///     &[
+    ///         CallHook::CallingWasm,
+    ///         CallHook::CallingHost,
+    ///         CallHook::ReturningFromHost,
+    ///         CallHook::ReturningFromWasm,
+    ///     ],
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_hook(
+        &mut self,
+        hook: impl FnMut(&mut T, CallHook) -> Result<(), Trap> + Send + Sync + 'static,
+    ) {
+        self.inner.call_hook = Some(Box::new(hook));
+    }
+
+    /// Configures the handler for WebAssembly loads and stores of the pages
+    /// registered with [`Memory::register_virtual_pages`].
+    ///
+    /// The handler is given this store's data and the access, and decides
+    /// whether the access completes, in which case execution continues with
+    /// the next instruction, or traps. See [`FaultResolution`] for details.
+    ///
+    /// Accesses to virtual pages trap if no handler is configured. If the
+    /// handler panics, the panic is handled according to the engine's
+    /// [`HostPanicPolicy`], as for host functions.
+    ///
+    /// [`Memory::register_virtual_pages`]: crate::Memory::register_virtual_pages
+    pub fn memory_access_handler(
+        &mut self,
+        handler: impl FnMut(&mut T, MemoryAccessFault) -> FaultResolution + Send + Sync + 'static,
+    ) {
+        self.inner.memory_access_handler = Some(Box::new(handler));
+    }
+
     /// Returns the [`Engine`] that this store is associated with.
     pub fn engine(&self) -> &Engine {
         self.inner.engine()
     }
 
+    /// Returns the identifier of this store, which is unique among all of the
+    /// stores created by this process.
+    ///
+    /// Objects owned by this store, such as [`Func`](crate::Func)s, report
+    /// the same identifier from their `store_id` methods.
+    pub fn id(&self) -> StoreId {
+        self.inner.store_data().id()
+    }
+
     /// Creates an [`InterruptHandle`] which can be used to interrupt the
     /// execution of instances within this `Store`.
     ///
@@ -464,7 +844,190 @@ impl<T> Store<T> {
     /// # }
     /// ```
     pub fn interrupt_handle(&self) -> Result<InterruptHandle> {
-        self.inner.interrupt_handle()
+        self.inner.interrupt_handle(None)
+    }
+
+    /// Creates an [`InterruptHandle`] which records `cause` as the reason for
+    /// its interrupts.
+    ///
+    /// Any number of handles, with different causes, can be created for a
+    /// store, for example one for a deadline and another for an administrative
+    /// kill. They all set the same interrupt flag, so wasm is interrupted in
+    /// exactly the same way as with [`Store::interrupt_handle`], but the
+    /// interrupt trap additionally reports which cause fired through
+    /// [`Trap::interrupt_cause`](crate::Trap::interrupt_cause).
+    ///
+    /// If several handles interrupt before wasm notices, the interrupt is
+    /// only delivered once and the trap reports the cause of the handle which
+    /// interrupted *first*; the others are consumed along with it. Handles
+    /// created with [`Store::interrupt_handle`] don't have a cause, and when
+    /// they're the only ones to have interrupted the trap has no cause either.
+    ///
+    /// Like [`Store::interrupt_handle`] this returns an error if interrupts
+    /// aren't enabled for this store.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # use wasmtime::*;
+    /// # fn main() -> Result<()> {
+    /// let engine = Engine::new(Config::new().interruptable(true))?;
+    /// let mut store = Store::new(&engine, ());
+    /// let deadline = store.interrupt_handle_with_cause("deadline")?;
+    /// let drain = store.interrupt_handle_with_cause("drain")?;
+    ///
+    /// let module = Module::new(&engine, r#"
+    ///     (func (export "run") (loop br 0))
+    /// "#)?;
+    /// let instance = Instance::new(&mut store, &module, &[])?;
+    /// let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    ///
+    /// deadline.interrupt();
+    /// drain.interrupt();
+    /// let trap = run.call(&mut store, ()).unwrap_err();
+    /// assert_eq!(trap.interrupt_cause(), Some(&InterruptCause::from("deadline")));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn interrupt_handle_with_cause(
+        &self,
+        cause: impl Into<InterruptCause>,
+    ) -> Result<InterruptHandle> {
+        self.inner.interrupt_handle(Some(cause.into()))
+    }
+
+    /// Returns a [`CancellationToken`] which can be used to gracefully cancel
+    /// the execution of WebAssembly within this `Store`.
+    ///
+    /// Cancellation happens in two phases. Once [`CancellationToken::cancel`]
+    /// has been called the token reports itself as cancelled, which host
+    /// functions can observe (for example to report cancellation to the guest
+    /// so it may run its own cleanup, or to release host resources). At its
+    /// next function entry or loop header WebAssembly then notices the
+    /// cancellation and is left with exactly the grace budget configured with
+    /// [`Store::cancellation_grace_fuel`] to finish up. If that budget is also
+    /// exhausted, or if there is none, then a trap is raised, unwinding all
+    /// WebAssembly frames in the same manner as dropping an in-progress
+    /// future does.
+    ///
+    /// Cancellation is noticed through the epoch deadline check and bounded
+    /// by fuel, so this returns an error unless both
+    /// [`Config::epoch_interruption`](crate::Config::epoch_interruption) and
+    /// [`Config::consume_fuel`](crate::Config::consume_fuel) are enabled.
+    ///
+    /// All tokens returned from this method share the same state. A
+    /// cancellation applies to the outermost call into WebAssembly which is
+    /// running when it's requested, or to the next one if none is, and is
+    /// cleared once that call returns, trapped or not. The store, along with
+    /// its tokens, can then be used for further calls. [`Store::reset`] clears
+    /// a pending cancellation as well.
+    ///
+    /// Dropping the future of an async call cancels it abruptly, without a
+    /// grace period, since the future can't be driven any further once it's
+    /// dropped. Host code run while its frames unwind sees the token as
+    /// cancelled.
+    pub fn cancellation_token(&self) -> Result<CancellationToken> {
+        self.inner.cancellation_token()
+    }
+
+    /// Configures the amount of fuel WebAssembly is given to wind down after
+    /// cancellation has been requested with a [`CancellationToken`].
+    ///
+    /// The default is 0, meaning WebAssembly traps as soon as it runs out of
+    /// fuel after cancellation was requested. For more information see
+    /// [`Store::cancellation_token`].
+    pub fn cancellation_grace_fuel(&mut self, fuel: u64) {
+        self.inner.cancellation_grace_fuel(fuel)
+    }
+
+    /// Looks up information about the WebAssembly function whose compiled code
+    /// contains the native program counter `pc`.
+    ///
+    /// This can be used to symbolize native stack traces containing JIT code,
+    /// for example from a crash reporter walking the stack of a host bug while
+    /// WebAssembly frames are active. Only modules which have been
+    /// instantiated within this store are searched, and `None` is returned if
+    /// `pc` isn't within the code of any of them.
+    ///
+    /// Note that this function allocates, so it's not suitable to call from
+    /// within a signal handler. In those contexts a [`SymbolMap`] should be
+    /// captured ahead of time with [`Store::symbol_map`] and used instead.
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<FrameInfo> {
+        self.inner.lookup_frame_info(pc)
+    }
+
+    /// Creates a [`SymbolMap`] describing the code address ranges of all
+    /// WebAssembly functions instantiated within this store so far.
+    ///
+    /// The returned map is a snapshot: modules instantiated after this call
+    /// won't be present in it. Lookups in the map itself perform no allocation
+    /// which makes it suitable for use in crash reporting contexts, and it may
+    /// also be serialized and handed to an out-of-process symbolizer.
+    pub fn symbol_map(&self) -> SymbolMap {
+        self.inner.symbol_map()
+    }
+
+    /// Creates a [`GcDiagnostics`](crate::GcDiagnostics) describing the GC
+    /// safepoints of all WebAssembly functions instantiated within this store
+    /// so far.
+    ///
+    /// When a garbage collection of `externref`s walks the stack it looks up
+    /// a stack map for each WebAssembly frame to find the references the frame
+    /// holds, and this reports where those stack maps are. Like
+    /// [`Store::symbol_map`] the returned value is a snapshot which doesn't
+    /// include modules instantiated after this call.
+    ///
+    /// This method is only available with the `gc-diagnostics` feature of
+    /// this crate.
+    #[cfg(feature = "gc-diagnostics")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "gc-diagnostics")))]
+    pub fn gc_diagnostics(&self) -> crate::GcDiagnostics {
+        self.inner.gc_diagnostics()
+    }
+
+    /// Returns how much time the most recently finished call into
+    /// WebAssembly in this store spent running guest code and host functions.
+    ///
+    /// Calls into wasm include those made by [`Func::call`] and friends, and
+    /// running a start function during instantiation. If a host function
+    /// called back into wasm then that nested call finishes first, so after a
+    /// call from the host returns this describes that outermost call. See
+    /// [`ActivationTimes`] for details.
+    ///
+    /// Returns `None` if [`Config::activation_times`] isn't enabled, or no
+    /// call has finished yet.
+    ///
+    /// [`Config::activation_times`]: crate::Config::activation_times
+    pub fn last_activation_times(&self) -> Option<ActivationTimes> {
+        self.inner.last_activation_times()
+    }
+
+    /// Discards everything created within this store, so it can be reused as
+    /// if it were freshly created.
+    ///
+    /// All instances are deallocated, and all functions, memories, tables,
+    /// globals and `externref`s created within the store are dropped along with
+    /// the modules registered with it. The amount of fuel consumed and
+    /// remaining goes back to zero, as does the epoch deadline, and a pending
+    /// request from a [`CancellationToken`] is cleared. This is cheaper than
+    /// creating a new [`Store`] for each of many short-lived invocations, since
+    /// the store keeps the memory backing its own bookkeeping, such as its
+    /// table of `externref`s held by wasm frames, for reuse.
+    ///
+    /// The configuration of the store is kept: its data, [`Engine`],
+    /// [`Store::limiter`] and the limits it set, call hooks, signal handler,
+    /// out-of-fuel behavior and epoch deadline behavior stay as they are,
+    /// modules it was created with in [`Store::new_with_modules`] remain
+    /// registered, and handles from [`Store::interrupt_handle`] and
+    /// [`Store::cancellation_token`] stay connected to it.
+    ///
+    /// Objects created before the reset, such as [`Instance`]s and [`Func`]s,
+    /// no longer belong to this store, and using them with it panics as it
+    /// would with any other store. To that end the store gets a new
+    /// [`Store::id`].
+    pub fn reset(&mut self) {
+        self.inner.reset()
     }
 
     /// Perform garbage collection of `ExternRef`s.
@@ -476,6 +1039,27 @@ impl<T> Store<T> {
         self.inner.gc()
     }
 
+    /// Releases modules which this store holds on to but which no instance in
+    /// it was created from, returning how many were released.
+    ///
+    /// A store keeps each module instantiated within it registered, which
+    /// keeps its compiled code alive, for as long as the store itself. Instances
+    /// are only deallocated when their store is dropped, and until then their
+    /// functions may be called, appear in backtraces, or have their stack maps
+    /// needed for GC. A module is registered just before its instance is
+    /// allocated, however, and if allocation fails, for example because a
+    /// [`ResourceLimiter`](crate::ResourceLimiter) rejected the size of one of
+    /// its memories, the module would otherwise stay registered even though
+    /// nothing in the store uses it. Long-lived stores which attempt to
+    /// instantiate many distinct modules can call this periodically to
+    /// release those.
+    ///
+    /// Note that a module's code is only freed once the [`Module`] itself,
+    /// and every other store it's registered with, have been dropped too.
+    pub fn prune_unused_modules(&mut self) -> usize {
+        self.inner.modules_mut().prune()
+    }
+
     /// Returns the amount of fuel consumed by this store's execution so far.
     ///
     /// If fuel consumption is not enabled via
@@ -486,6 +1070,57 @@ impl<T> Store<T> {
         self.inner.fuel_consumed()
     }
 
+    /// Returns the amount of fuel left in this store for wasm to consume.
+    ///
+    /// This is all the fuel added with [`Store::add_fuel`], including fuel
+    /// injected by [`Store::out_of_fuel_async_yield`], less
+    /// [`Store::fuel_consumed`], and is kept up to date while wasm runs so
+    /// host functions can call it too. If wasm ran out of fuel this returns
+    /// 0.
+    ///
+    /// If fuel consumption is not enabled via
+    /// [`Config::consume_fuel`](crate::Config::consume_fuel) then this
+    /// function will return `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::new(Config::new().consume_fuel(true))?;
+    /// let mut store = Store::new(&engine, ());
+    /// store.add_fuel(100)?;
+    /// store.consume_fuel(30)?;
+    /// assert_eq!(store.fuel_remaining(), Some(70));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        self.inner.fuel_remaining()
+    }
+
+    /// Returns the amount of fuel consumed by each function of `module`
+    /// executed in this store so far.
+    ///
+    /// Functions are identified by their index among the functions defined,
+    /// rather than imported, by `module`. Every instance of `module` in this
+    /// store contributes to the same entries, but functions of other modules
+    /// never do, even if they're compiled from the same binary. The fuel
+    /// consumed by a function doesn't include the fuel consumed by the
+    /// functions it calls, so when all fuel is consumed by wasm the entries of
+    /// all modules add up to [`Store::fuel_consumed`]. Fuel consumed with
+    /// [`Store::consume_fuel`] isn't attributed to any function.
+    ///
+    /// If either [`Config::consume_fuel`](crate::Config::consume_fuel) or
+    /// [`Config::per_function_fuel_accounting`](crate::Config::per_function_fuel_accounting)
+    /// is not enabled then this function will return `None`.
+    pub fn function_fuel_consumed(
+        &self,
+        module: &Module,
+    ) -> Option<HashMap<DefinedFuncIndex, u64>> {
+        self.inner.function_fuel_consumed(module)
+    }
+
     /// Adds fuel to this [`Store`] for wasm to consume while executing.
     ///
     /// For this method to work fuel consumption must be enabled via
@@ -510,6 +1145,46 @@ impl<T> Store<T> {
         self.inner.add_fuel(fuel)
     }
 
+    /// Consumes `fuel` from this [`Store`] on behalf of work done by the host,
+    /// returning the fuel which remains afterwards.
+    ///
+    /// This is intended for host functions whose cost should be metered along
+    /// with the wasm calling them. The fuel consumed here counts towards
+    /// [`Store::fuel_consumed`], and once it's used up wasm traps the next
+    /// time it checks for fuel, as if it had consumed the fuel itself.
+    ///
+    /// If less than `fuel` remains then this does what wasm does when it runs
+    /// out of fuel, as configured with [`Store::out_of_fuel_trap`] and
+    /// [`Store::out_of_fuel_async_yield`], before trying again. By default
+    /// that means this fails with a [`Trap`] without consuming anything,
+    /// which a host function can return to trap the wasm calling it. Yielding
+    /// is only possible from a host function called by wasm running
+    /// asynchronously; elsewhere this fails instead.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store's
+    /// [`Config`](crate::Config) doesn't have fuel consumption enabled, or if
+    /// there isn't enough fuel as described above.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::new(Config::new().consume_fuel(true))?;
+    /// let mut store = Store::new(&engine, ());
+    /// store.add_fuel(100)?;
+    /// assert_eq!(store.consume_fuel(30)?, 70);
+    /// assert_eq!(store.fuel_consumed(), Some(30));
+    /// assert!(store.consume_fuel(71).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn consume_fuel(&mut self, fuel: u64) -> Result<u64> {
+        self.inner.consume_fuel(fuel)
+    }
+
     /// Configures a [`Store`] to generate a [`Trap`] whenever it runs out of
     /// fuel.
     ///
@@ -562,6 +1237,165 @@ impl<T> Store<T> {
         self.inner
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
+
+    /// Configures this `Store` to ask `callback` what to do each time
+    /// WebAssembly runs out of fuel.
+    ///
+    /// This is a dynamic version of [`Store::out_of_fuel_async_yield`]: rather
+    /// than injecting a fixed amount of fuel a fixed number of times, the
+    /// callback is given a [`FuelExhausted`] describing the fuel consumed and
+    /// injected so far and returns a [`FuelDecision`]. It can yield and then
+    /// inject fuel, inject fuel without yielding, or trap to terminate the
+    /// WebAssembly.
+    ///
+    /// The callback is invoked synchronously from the WebAssembly which ran
+    /// out of fuel, before any yield happens. It has no access to the store,
+    /// so it can't call back into this store's WebAssembly, and it shouldn't
+    /// block or panic either, since there's WebAssembly on the stack beneath
+    /// it. Any state it needs, like the priority of the guest, should be
+    /// shared with it through something like an `Arc`.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if it is not called on a store associated with an [async
+    /// config](crate::Config::async_support).
+    pub fn out_of_fuel_async_yield_with_callback(
+        &mut self,
+        callback: impl FnMut(&FuelExhausted) -> FuelDecision + Send + Sync + 'static,
+    ) {
+        self.inner
+            .out_of_fuel_async_yield_with_callback(Box::new(callback))
+    }
+
+    /// Sets the epoch deadline of this store to `ticks_beyond_current` epochs
+    /// after the current epoch of its [`Engine`].
+    ///
+    /// With [`Config::epoch_interruption`] enabled, WebAssembly running in
+    /// this store is interrupted once the engine's epoch reaches the deadline,
+    /// as the engine's epoch is advanced by [`Engine::increment_epoch`]. What
+    /// happens then is configured with [`Store::epoch_deadline_trap`], the
+    /// default, and [`Store::epoch_deadline_callback`]. A deadline of 0 ticks
+    /// beyond the current epoch is reached as soon as WebAssembly next checks
+    /// it.
+    ///
+    /// The deadline starts out as epoch 0, which means that WebAssembly is
+    /// interrupted as soon as it's entered until a deadline has been set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::new(Config::new().epoch_interruption(true))?;
+    /// let module = Module::new(&engine, r#"(module (func (export "run") (loop br 0)))"#)?;
+    /// let mut store = Store::new(&engine, ());
+    /// store.set_epoch_deadline(1);
+    ///
+    /// // Interrupt the infinite loop below shortly after it starts.
+    /// let engine2 = engine.clone();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_millis(100));
+    ///     engine2.increment_epoch();
+    /// });
+    ///
+    /// let instance = Instance::new(&mut store, &module, &[])?;
+    /// let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    /// let trap = run.call(&mut store, ()).unwrap_err();
+    /// assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Config::epoch_interruption`]: crate::Config::epoch_interruption
+    pub fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        self.inner.set_epoch_deadline(ticks_beyond_current)
+    }
+
+    /// Configures this store to raise a [`Trap`] whenever WebAssembly reaches
+    /// its epoch deadline.
+    ///
+    /// The trap's [`Trap::trap_code`] is [`TrapCode::Interrupt`]. This is the
+    /// default behavior when the epoch deadline is reached, see
+    /// [`Store::set_epoch_deadline`].
+    ///
+    /// [`TrapCode::Interrupt`]: crate::TrapCode::Interrupt
+    pub fn epoch_deadline_trap(&mut self) {
+        self.inner.epoch_deadline_callback = None;
+    }
+
+    /// Configures this store to invoke `callback` whenever WebAssembly reaches
+    /// its epoch deadline.
+    ///
+    /// The callback is given the store's data. If it returns an error that's
+    /// raised as a trap in the WebAssembly. Otherwise it returns a number of
+    /// ticks: the deadline is set that many epochs beyond the current one, as
+    /// with [`Store::set_epoch_deadline`], and the WebAssembly continues. The
+    /// callback can use this to, for example, count how many times a guest's
+    /// time slice was used up, and trap once it's been given enough of them.
+    ///
+    /// The callback runs synchronously with WebAssembly on the stack beneath
+    /// it, so it can't call back into this store.
+    pub fn epoch_deadline_callback(
+        &mut self,
+        callback: impl FnMut(&mut T) -> Result<u64, Trap> + Send + Sync + 'static,
+    ) {
+        self.inner.epoch_deadline_callback = Some(Box::new(callback));
+    }
+
+    /// Configures a function that's called each time WebAssembly in this
+    /// store yields to the host because it ran out of fuel, as configured
+    /// with [`Store::out_of_fuel_async_yield`].
+    ///
+    /// The hook runs on the thread polling the future which was executing
+    /// WebAssembly, after the WebAssembly has been suspended and before the
+    /// poll returns. It's given a [`SuspendedAccess`] with read-only access to
+    /// the memories and globals of the store, which allows inspecting the
+    /// guest's progress without resuming it. See its documentation for what
+    /// the guest's state looks like at that point.
+    ///
+    /// The hook doesn't run when the yield happens while a host function is
+    /// itself blocked on another future running WebAssembly in this store,
+    /// since the outer host function may be in the middle of using the store.
+    /// It's then called once the outermost future has been suspended.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if it is not called on a store associated with an [async
+    /// config](crate::Config::async_support).
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    #[cfg(feature = "async")]
+    pub fn suspended_access_hook(
+        &mut self,
+        hook: impl FnMut(&SuspendedAccess<'_>) + Send + Sync + 'static,
+    ) {
+        assert!(
+            self.inner.async_support(),
+            "cannot use `suspended_access_hook` without enabling async support in the config"
+        );
+        unsafe {
+            *self.inner.async_state.suspended_access_hook.get() = Some(Box::new(hook));
+        }
+    }
+
+    /// Calls `f` with read-only access to the memories and globals of this
+    /// store, provided that no WebAssembly is actively running in it.
+    ///
+    /// WebAssembly suspended at an async yield point, or not running at all,
+    /// is fine, and this is what [`Store::suspended_access_hook`] uses to
+    /// inspect a suspended guest. See [`SuspendedAccess`] for what the guest's
+    /// state looks like then.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this store doesn't have [async
+    /// support](crate::Config::async_support), if a future running
+    /// WebAssembly in this store is being polled, which includes calls from
+    /// host functions, or if it's called re-entrantly from within `f`.
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    #[cfg(feature = "async")]
+    pub fn with_suspended_access<R>(&self, f: impl FnOnce(&SuspendedAccess<'_>) -> R) -> Result<R> {
+        self.inner.with_suspended_access(f)
+    }
 }
 
 impl<'a, T> StoreContext<'a, T> {
@@ -569,16 +1403,51 @@ impl<'a, T> StoreContext<'a, T> {
         self.0.async_support()
     }
 
+    /// Calls `f` with read-only access to the memories and globals of this
+    /// store, provided that no WebAssembly is actively running in it.
+    ///
+    /// For more information see [`Store::with_suspended_access`].
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    #[cfg(feature = "async")]
+    pub fn with_suspended_access<R>(&self, f: impl FnOnce(&SuspendedAccess<'_>) -> R) -> Result<R> {
+        self.0.with_suspended_access(f)
+    }
+
     /// Returns the underlying [`Engine`] this store is connected to.
     pub fn engine(&self) -> &Engine {
         self.0.engine()
     }
 
+    /// Returns the identifier of this store.
+    ///
+    /// Same as [`Store::id`].
+    pub fn id(&self) -> StoreId {
+        self.0.store_data().id()
+    }
+
     /// Returns an [`InterruptHandle`] to interrupt wasm execution.
     ///
     /// See [`Store::interrupt_handle`] for more information.
     pub fn interrupt_handle(&self) -> Result<InterruptHandle> {
-        self.0.interrupt_handle()
+        self.0.interrupt_handle(None)
+    }
+
+    /// Returns an [`InterruptHandle`] which interrupts wasm execution with
+    /// `cause`.
+    ///
+    /// See [`Store::interrupt_handle_with_cause`] for more information.
+    pub fn interrupt_handle_with_cause(
+        &self,
+        cause: impl Into<InterruptCause>,
+    ) -> Result<InterruptHandle> {
+        self.0.interrupt_handle(Some(cause.into()))
+    }
+
+    /// Returns a [`CancellationToken`] to gracefully cancel wasm execution.
+    ///
+    /// See [`Store::cancellation_token`] for more information.
+    pub fn cancellation_token(&self) -> Result<CancellationToken> {
+        self.0.cancellation_token()
     }
 
     /// Access the underlying data owned by this `Store`.
@@ -594,6 +1463,54 @@ impl<'a, T> StoreContext<'a, T> {
     pub fn fuel_consumed(&self) -> Option<u64> {
         self.0.fuel_consumed()
     }
+
+    /// Returns the fuel remaining in this store.
+    ///
+    /// For more information see [`Store::fuel_remaining`].
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        self.0.fuel_remaining()
+    }
+
+    /// Returns the fuel consumed by each function of `module` in this store.
+    ///
+    /// For more information see [`Store::function_fuel_consumed`].
+    pub fn function_fuel_consumed(
+        &self,
+        module: &Module,
+    ) -> Option<HashMap<DefinedFuncIndex, u64>> {
+        self.0.function_fuel_consumed(module)
+    }
+
+    /// Looks up information about the WebAssembly function containing `pc`.
+    ///
+    /// For more information see [`Store::lookup_frame_info`].
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<FrameInfo> {
+        self.0.lookup_frame_info(pc)
+    }
+
+    /// Creates a [`SymbolMap`] of all WebAssembly code in this store.
+    ///
+    /// For more information see [`Store::symbol_map`].
+    pub fn symbol_map(&self) -> SymbolMap {
+        self.0.symbol_map()
+    }
+
+    /// Creates a [`GcDiagnostics`](crate::GcDiagnostics) of all WebAssembly
+    /// code in this store.
+    ///
+    /// For more information see [`Store::gc_diagnostics`].
+    #[cfg(feature = "gc-diagnostics")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "gc-diagnostics")))]
+    pub fn gc_diagnostics(&self) -> crate::GcDiagnostics {
+        self.0.gc_diagnostics()
+    }
+
+    /// Returns the times of the most recently finished call into WebAssembly.
+    ///
+    /// Same as [`Store::last_activation_times`].
+    pub fn last_activation_times(&self) -> Option<ActivationTimes> {
+        self.0.last_activation_times()
+    }
 }
 
 impl<'a, T> StoreContextMut<'a, T> {
@@ -616,11 +1533,36 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.engine()
     }
 
+    /// Returns the identifier of this store.
+    ///
+    /// Same as [`Store::id`].
+    pub fn id(&self) -> StoreId {
+        self.0.store_data().id()
+    }
+
     /// Returns an [`InterruptHandle`] to interrupt wasm execution.
     ///
     /// See [`Store::interrupt_handle`] for more information.
     pub fn interrupt_handle(&self) -> Result<InterruptHandle> {
-        self.0.interrupt_handle()
+        self.0.interrupt_handle(None)
+    }
+
+    /// Returns an [`InterruptHandle`] which interrupts wasm execution with
+    /// `cause`.
+    ///
+    /// See [`Store::interrupt_handle_with_cause`] for more information.
+    pub fn interrupt_handle_with_cause(
+        &self,
+        cause: impl Into<InterruptCause>,
+    ) -> Result<InterruptHandle> {
+        self.0.interrupt_handle(Some(cause.into()))
+    }
+
+    /// Returns a [`CancellationToken`] to gracefully cancel wasm execution.
+    ///
+    /// See [`Store::cancellation_token`] for more information.
+    pub fn cancellation_token(&self) -> Result<CancellationToken> {
+        self.0.cancellation_token()
     }
 
     /// Perform garbage collection of `ExternRef`s.
@@ -630,6 +1572,13 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.gc()
     }
 
+    /// Releases modules which no instance in this store was created from.
+    ///
+    /// Same as [`Store::prune_unused_modules`].
+    pub fn prune_unused_modules(&mut self) -> usize {
+        self.0.modules_mut().prune()
+    }
+
     /// Returns the fuel consumed by this store.
     ///
     /// For more information see [`Store::fuel_consumed`].
@@ -637,6 +1586,23 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.fuel_consumed()
     }
 
+    /// Returns the fuel remaining in this store.
+    ///
+    /// For more information see [`Store::fuel_remaining`].
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        self.0.fuel_remaining()
+    }
+
+    /// Returns the fuel consumed by each function of `module` in this store.
+    ///
+    /// For more information see [`Store::function_fuel_consumed`].
+    pub fn function_fuel_consumed(
+        &self,
+        module: &Module,
+    ) -> Option<HashMap<DefinedFuncIndex, u64>> {
+        self.0.function_fuel_consumed(module)
+    }
+
     /// Inject more fuel into this store to be consumed when executing wasm code.
     ///
     /// For more information see [`Store::add_fuel`]
@@ -644,6 +1610,13 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.add_fuel(fuel)
     }
 
+    /// Consumes fuel from this store on behalf of the host.
+    ///
+    /// For more information see [`Store::consume_fuel`]
+    pub fn consume_fuel(&mut self, fuel: u64) -> Result<u64> {
+        self.0.consume_fuel(fuel)
+    }
+
     /// Configures this `Store` to trap whenever fuel runs out.
     ///
     /// For more information see [`Store::out_of_fuel_trap`]
@@ -659,6 +1632,81 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
+
+    /// Configures this `Store` to ask `callback` what to do each time
+    /// WebAssembly runs out of fuel.
+    ///
+    /// For more information see [`Store::out_of_fuel_async_yield_with_callback`]
+    pub fn out_of_fuel_async_yield_with_callback(
+        &mut self,
+        callback: impl FnMut(&FuelExhausted) -> FuelDecision + Send + Sync + 'static,
+    ) {
+        self.0
+            .out_of_fuel_async_yield_with_callback(Box::new(callback))
+    }
+
+    /// Sets the epoch deadline of this store relative to the current epoch.
+    ///
+    /// For more information see [`Store::set_epoch_deadline`]
+    pub fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        self.0.set_epoch_deadline(ticks_beyond_current)
+    }
+
+    /// Configures this `Store` to trap whenever the epoch deadline is reached.
+    ///
+    /// For more information see [`Store::epoch_deadline_trap`]
+    pub fn epoch_deadline_trap(&mut self) {
+        self.0.epoch_deadline_callback = None;
+    }
+
+    /// Configures this `Store` to invoke `callback` whenever the epoch
+    /// deadline is reached.
+    ///
+    /// For more information see [`Store::epoch_deadline_callback`]
+    pub fn epoch_deadline_callback(
+        &mut self,
+        callback: impl FnMut(&mut T) -> Result<u64, Trap> + Send + Sync + 'static,
+    ) {
+        self.0.epoch_deadline_callback = Some(Box::new(callback));
+    }
+
+    /// Configures the fuel granted to wasm after cancellation is requested.
+    ///
+    /// For more information see [`Store::cancellation_grace_fuel`]
+    pub fn cancellation_grace_fuel(&mut self, fuel: u64) {
+        self.0.cancellation_grace_fuel(fuel)
+    }
+
+    /// Looks up information about the WebAssembly function containing `pc`.
+    ///
+    /// For more information see [`Store::lookup_frame_info`].
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<FrameInfo> {
+        self.0.lookup_frame_info(pc)
+    }
+
+    /// Creates a [`SymbolMap`] of all WebAssembly code in this store.
+    ///
+    /// For more information see [`Store::symbol_map`].
+    pub fn symbol_map(&self) -> SymbolMap {
+        self.0.symbol_map()
+    }
+
+    /// Creates a [`GcDiagnostics`](crate::GcDiagnostics) of all WebAssembly
+    /// code in this store.
+    ///
+    /// For more information see [`Store::gc_diagnostics`].
+    #[cfg(feature = "gc-diagnostics")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "gc-diagnostics")))]
+    pub fn gc_diagnostics(&self) -> crate::GcDiagnostics {
+        self.0.gc_diagnostics()
+    }
+
+    /// Returns the times of the most recently finished call into WebAssembly.
+    ///
+    /// Same as [`Store::last_activation_times`].
+    pub fn last_activation_times(&self) -> Option<ActivationTimes> {
+        self.0.last_activation_times()
+    }
 }
 
 impl<T> StoreInner<T> {
@@ -677,39 +1725,153 @@ impl<T> StoreInner<T> {
         Some(accessor(&mut self.data))
     }
 
-    pub fn entering_native_hook(&mut self) -> Result<(), Trap> {
-        if let Some(hook) = &mut self.entering_native_hook {
-            hook(&mut self.data)
+    /// Runs the hooks for execution crossing between the host and wasm as
+    /// described by `s`.
+    pub fn call_hook(&mut self, s: CallHook) -> Result<(), Trap> {
+        if s.entering_host() {
+            self.inner.activation_clock.switch(false);
+        }
+        if let Some(hook) = &mut self.call_hook {
+            hook(&mut self.data, s)?;
+        }
+        if s.entering_host() {
+            if let Some(hook) = &mut self.entering_native_hook {
+                hook(&mut self.data)?;
+            }
         } else {
-            Ok(())
+            if let Some(hook) = &mut self.exiting_native_hook {
+                hook(&mut self.data)?;
+            }
+            self.inner.activation_clock.switch(true);
         }
+        Ok(())
     }
 
-    pub fn exiting_native_hook(&mut self) -> Result<(), Trap> {
-        if let Some(hook) = &mut self.exiting_native_hook {
-            hook(&mut self.data)
-        } else {
-            Ok(())
+    /// Called when a new wasm activation starts.
+    pub fn push_activation(&mut self) {
+        self.unwind_callbacks.push(Vec::new());
+        self.inner.activation_clock.begin();
+    }
+
+    /// Called when the innermost wasm activation finishes. If it trapped then
+    /// its unwind callbacks are run, most recently registered first, and
+    /// otherwise they're dropped.
+    pub fn pop_activation(&mut self, trapped: bool) {
+        self.inner.activation_clock.end();
+        let callbacks = self
+            .unwind_callbacks
+            .pop()
+            .expect("no wasm activation to pop");
+        if trapped {
+            for callback in callbacks.into_iter().rev() {
+                callback(&mut self.data);
+            }
+        }
+    }
+
+    pub fn defer_on_unwind(&mut self, callback: Box<dyn FnOnce(&mut T) + Send + Sync>) {
+        self.unwind_callbacks
+            .last_mut()
+            .expect("no wasm activation to defer a callback in")
+            .push(callback);
+    }
+
+    fn consume_fuel(&mut self, fuel: u64) -> Result<u64> {
+        anyhow::ensure!(
+            self.engine().config().tunables.consume_fuel,
+            "fuel is not configured in this store"
+        );
+
+        // As in `add_fuel`, more than `i64::max_value()` can't be represented,
+        // and can never be available either.
+        let fuel = i64::try_from(fuel).unwrap_or(i64::max_value());
+        loop {
+            if let Some(remaining) = self.deduct_fuel(fuel) {
+                return Ok(remaining);
+            }
+            // Yielding requires a fiber to suspend, so it isn't possible when
+            // this isn't called from wasm.
+            let may_yield = !matches!(self.out_of_gas_behavior, OutOfGas::Trap);
+            if may_yield && self.async_support() && !self.on_fiber() {
+                bail!("not enough fuel remaining to consume {}", fuel);
+            }
+            wasmtime_runtime::Store::out_of_gas(self).map_err(Trap::from)?;
+        }
+    }
+}
+
+/// Finds the frames in `frames` of a call into wasm of the function at
+/// `entry_addr`, returning the indices of its youngest and oldest frames.
+///
+/// The frames of the `named_calls` calls into wasm which the trap has already
+/// left come first and are skipped over. Returns `None` if the remaining
+/// frames don't start with such a call.
+fn call_frames(
+    frames: &[FrameInfo],
+    named_calls: usize,
+    entry_addr: usize,
+) -> Option<(usize, usize)> {
+    let start = match named_calls {
+        0 => 0,
+        n => {
+            let (i, _) = frames
+                .iter()
+                .enumerate()
+                .filter(|(_, frame)| frame.entered())
+                .nth(n - 1)?;
+            i + 1
         }
+    };
+    let oldest = start + frames[start..].iter().position(|f| f.entered())?;
+    if frames[oldest].func_addr() == entry_addr {
+        Some((start, oldest))
+    } else {
+        None
     }
 }
 
 impl StoreInnermost {
-    pub fn bump_resource_counts(&mut self, module: &Module) -> Result<()> {
-        fn bump(slot: &mut usize, max: usize, amt: usize, desc: &str) -> Result<()> {
+    /// Counts the resources of a new instance of `module`, labelled `name`,
+    /// against the store's limits.
+    pub fn bump_resource_counts(&mut self, module: &Module, name: Option<&str>) -> Result<()> {
+        let module = module.env_module();
+        let bump = |slot: &mut usize, max: usize, amt: usize, desc: &str| -> Result<()> {
             let new = slot.saturating_add(amt);
             if new > max {
-                bail!(
-                    "resource limit exceeded: {} count too high at {}",
-                    desc,
-                    new
-                );
+                match (name, &module.name) {
+                    (Some(name), Some(module)) => bail!(
+                        "resource limit exceeded: {} count too high at {} \
+                         while instantiating instance `{}` of module `{}`",
+                        desc,
+                        new,
+                        name,
+                        module
+                    ),
+                    (Some(name), None) => bail!(
+                        "resource limit exceeded: {} count too high at {} \
+                         while instantiating instance `{}`",
+                        desc,
+                        new,
+                        name
+                    ),
+                    (None, Some(module)) => bail!(
+                        "resource limit exceeded: {} count too high at {} \
+                         while instantiating module `{}`",
+                        desc,
+                        new,
+                        module
+                    ),
+                    (None, None) => bail!(
+                        "resource limit exceeded: {} count too high at {}",
+                        desc,
+                        new
+                    ),
+                }
             }
             *slot = new;
             Ok(())
-        }
+        };
 
-        let module = module.env_module();
         let memories = module.memory_plans.len() - module.num_imported_memories;
         let tables = module.table_plans.len() - module.num_imported_tables;
 
@@ -738,6 +1900,25 @@ impl StoreInnermost {
         &self.store_data
     }
 
+    pub(crate) fn register_virtual_pages(
+        &mut self,
+        memory: Memory,
+        definition: *mut VMMemoryDefinition,
+        bytes: Range<u64>,
+    ) {
+        if bytes.start == bytes.end {
+            return;
+        }
+        self.virtual_pages
+            .entry(definition as usize)
+            .or_insert_with(|| VirtualPages {
+                memory,
+                ranges: Vec::new(),
+            })
+            .ranges
+            .push(bytes);
+    }
+
     pub fn store_data_mut(&mut self) -> &mut StoreData {
         &mut self.store_data
     }
@@ -750,37 +1931,265 @@ impl StoreInnermost {
         self.host_trampolines.insert(idx, trampoline);
     }
 
-    pub fn interrupt_handle(&self) -> Result<InterruptHandle> {
+    pub(crate) fn shared_host_func(&self, key: usize) -> Option<Func> {
+        self.shared_host_funcs.get(&key).copied()
+    }
+
+    pub(crate) fn insert_shared_host_func(&mut self, key: usize, func: Func) {
+        self.shared_host_funcs.insert(key, func);
+    }
+
+    /// Returns the imports resolved from `items` in this store, if they've been
+    /// resolved before.
+    pub(crate) fn import_template(&self, items: &Arc<[Definition]>) -> Option<Arc<ImportTemplate>> {
+        let key = items.as_ptr() as usize;
+        self.import_templates
+            .get(&key)
+            .map(|(_, template)| template.clone())
+    }
+
+    /// Saves the imports resolved from `items` in this store, and forgets
+    /// those of any `InstancePre` which has been dropped since.
+    pub(crate) fn insert_import_template(
+        &mut self,
+        items: &Arc<[Definition]>,
+        template: Arc<ImportTemplate>,
+    ) {
+        self.import_templates
+            .retain(|_, (items, _)| items.strong_count() > 0);
+        let key = items.as_ptr() as usize;
+        self.import_templates
+            .insert(key, (Arc::downgrade(items), template));
+    }
+
+    pub fn interrupt_handle(&self, cause: Option<InterruptCause>) -> Result<InterruptHandle> {
         if self.engine.config().tunables.interruptable {
             Ok(InterruptHandle {
                 interrupts: self.interrupts.clone(),
+                pending_cause: self.interrupt_cause.clone(),
+                cause,
             })
         } else {
             bail!("interrupts aren't enabled for this `Store`")
         }
     }
 
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<FrameInfo> {
+        self.modules.lookup_frame_info(pc)
+    }
+
+    pub fn symbol_map(&self) -> SymbolMap {
+        self.modules.symbol_map()
+    }
+
+    #[cfg(feature = "gc-diagnostics")]
+    pub fn gc_diagnostics(&self) -> crate::GcDiagnostics {
+        self.modules.gc_diagnostics()
+    }
+
+    fn reset(&mut self) {
+        self.deallocate_instances();
+        self.instances.clear();
+        self.store_data = StoreData::new();
+        self.externref_activations_table.clear();
+        self.modules.clear();
+        self.instance_names.clear();
+        self.virtual_pages.clear();
+        self.host_trampolines.clear();
+        self.shared_host_funcs.clear();
+        self.import_templates.clear();
+        self.instance_count = 0;
+        self.memory_count = 0;
+        self.table_count = 0;
+        unsafe {
+            *self.interrupts.fuel_consumed.get() = 0;
+        }
+        self.fuel_adj = 0;
+        self.function_fuel.clear();
+        self.cancellation.epoch_deadline = 0;
+        self.reset_cancellation();
+        self.activation_clock = ActivationClock::new(self.engine.config().activation_times);
+    }
+
+    fn deallocate_instances(&mut self) {
+        let allocator = self.engine.allocator();
+        let ondemand = OnDemandInstanceAllocator::default();
+        for instance in self.instances.iter() {
+            unsafe {
+                if instance.ondemand {
+                    ondemand.deallocate(&instance.handle);
+                } else {
+                    allocator.deallocate(&instance.handle);
+                }
+            }
+        }
+    }
+
+    pub fn last_activation_times(&self) -> Option<ActivationTimes> {
+        self.activation_clock.last()
+    }
+
+    pub fn cancellation_token(&self) -> Result<CancellationToken> {
+        let tunables = &self.engine.config().tunables;
+        if !tunables.consume_fuel {
+            bail!("fuel consumption isn't enabled for this `Store`")
+        }
+        if !tunables.epoch_interruption {
+            bail!("epoch interruption isn't enabled for this `Store`")
+        }
+        Ok(CancellationToken {
+            requested: self.cancellation.requested.clone(),
+            interrupts: self.interrupts.clone(),
+        })
+    }
+
+    /// Clears a request for cancellation, allowing wasm to run again.
+    ///
+    /// A cancellation applies to the outermost call into wasm, so this is
+    /// called once that's finished, as well as when the store is reset.
+    pub(crate) fn reset_cancellation(&mut self) {
+        let cancellation = &mut self.cancellation;
+        cancellation.requested.store(false, SeqCst);
+        cancellation.grace_granted = false;
+        // A cancellation overrides the epoch deadline, so put back the
+        // embedder's own.
+        self.interrupts
+            .epoch_deadline
+            .store(cancellation.epoch_deadline, SeqCst);
+    }
+
+    /// Called when wasm notices that it's been cancelled, either because its
+    /// epoch deadline was moved up by the cancellation or because it ran out
+    /// of fuel.
+    ///
+    /// The first time this happens wasm is given the grace fuel, and only
+    /// that, to wind down. After that it's forcibly aborted.
+    fn cancelled(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let cancellation = &mut self.cancellation;
+        if !cancellation.grace_granted && cancellation.grace_fuel > 0 {
+            cancellation.grace_granted = true;
+            let grace = cancellation.grace_fuel;
+            let remaining = self.fuel_remaining().unwrap();
+            if remaining > grace {
+                let excess = i64::try_from(remaining - grace).unwrap_or(i64::max_value());
+                self.deduct_fuel(excess);
+            } else {
+                self.add_fuel(grace - remaining).unwrap();
+            }
+        }
+        if !self.cancellation.grace_granted {
+            return Err(Box::new(CancelledError));
+        }
+        // The grace period is only bounded by fuel, so the epoch doesn't
+        // interrupt it.
+        self.interrupts
+            .epoch_deadline
+            .store(u64::max_value(), SeqCst);
+        if self.fuel_remaining() == Some(0) {
+            return Err(Box::new(CancelledError));
+        }
+        Ok(())
+    }
+
+    fn cancellation_grace_fuel(&mut self, fuel: u64) {
+        self.cancellation.grace_fuel = fuel;
+    }
+
     #[inline]
     pub(crate) fn modules_mut(&mut self) -> &mut ModuleRegistry {
         &mut self.modules
     }
 
-    pub unsafe fn add_instance(&mut self, handle: InstanceHandle, ondemand: bool) -> InstanceId {
+    pub unsafe fn add_instance(
+        &mut self,
+        handle: InstanceHandle,
+        ondemand: bool,
+    ) -> StoreInstanceId {
         self.instances.push(StoreInstance {
             handle: handle.clone(),
             ondemand,
         });
-        InstanceId(self.instances.len() - 1)
+        StoreInstanceId(self.instances.len() - 1)
     }
 
-    pub fn instance(&self, id: InstanceId) -> &InstanceHandle {
+    pub fn instance(&self, id: StoreInstanceId) -> &InstanceHandle {
         &self.instances[id.0].handle
     }
 
-    pub fn instance_mut(&mut self, id: InstanceId) -> &mut InstanceHandle {
+    pub fn instance_mut(&mut self, id: StoreInstanceId) -> &mut InstanceHandle {
         &mut self.instances[id.0].handle
     }
 
+    pub fn set_instance_name(&mut self, id: InstanceId, name: &str) {
+        self.instance_names.insert(id, name.into());
+    }
+
+    pub fn instance_name(&self, id: InstanceId) -> Option<&str> {
+        self.instance_names.get(&id).map(|name| &**name)
+    }
+
+    /// Fills in the instance names of frames in `trap`'s backtrace which
+    /// belong to named instances in this store, as the trap leaves the call
+    /// into wasm of `entry`.
+    ///
+    /// A frame's program counter only tells us which module it's in, which is
+    /// enough if the store has just one instance of that module. Otherwise the
+    /// oldest frame of this call is known to be `entry`'s, and so are the
+    /// frames it calls directly, since those stay within its instance. Frames
+    /// which can't be attributed to a single instance are left unnamed.
+    pub(crate) fn name_trap_frames(&self, trap: &mut Trap, entry: *const VMCallerCheckedAnyfunc) {
+        if self.instance_names.is_empty() {
+            return;
+        }
+        let (entry_vmctx, entry_addr) =
+            unsafe { ((*entry).vmctx, (*entry).func_ptr.as_ptr() as usize) };
+        let name_of = |handle: &InstanceHandle| {
+            let instance = handle.host_state().downcast_ref::<Instance>()?;
+            self.instance_names.get(&instance.id()).cloned()
+        };
+        trap.name_frames(|frames, named_calls| {
+            if let Some((start, oldest)) = call_frames(frames, *named_calls, entry_addr) {
+                *named_calls += 1;
+                let entry = self
+                    .instances
+                    .iter()
+                    .find(|i| i.handle.vmctx_ptr() == entry_vmctx)
+                    .and_then(|i| name_of(&i.handle));
+                if let Some(name) = entry {
+                    let mut i = oldest;
+                    loop {
+                        if frames[i].instance_name().is_none() {
+                            frames[i].set_instance_name(name.clone());
+                        }
+                        if i == start || !frames[i - 1].direct_caller() {
+                            break;
+                        }
+                        i -= 1;
+                    }
+                }
+            }
+
+            for frame in frames.iter_mut() {
+                if frame.instance_name().is_some() {
+                    continue;
+                }
+                let module = match self.modules.lookup_env_module(frame.pc()) {
+                    Some(module) => module,
+                    None => continue,
+                };
+                let mut candidates = self
+                    .instances
+                    .iter()
+                    .filter(|i| Arc::ptr_eq(i.handle.module(), module));
+                if let (Some(only), None) = (candidates.next(), candidates.next()) {
+                    if let Some(name) = name_of(&only.handle) {
+                        frame.set_instance_name(name);
+                    }
+                }
+            }
+        });
+    }
+
     #[cfg_attr(not(target_os = "linux"), allow(dead_code))] // not used on all platforms
     pub fn set_signal_handler(&mut self, handler: Option<Box<SignalHandler<'static>>>) {
         self.signal_handler = handler;
@@ -791,11 +2200,27 @@ impl StoreInnermost {
         &self.interrupts
     }
 
+    /// Returns the cause of the pending interrupt, which is locked by
+    /// `InterruptHandle::interrupt` while it sets the interrupt flag.
+    ///
+    /// Whoever consumes an interrupt takes its cause while holding this lock
+    /// and resets the flag before releasing it, so that a concurrent interrupt
+    /// is either merged into the consumed one or is left pending along with
+    /// its own cause.
+    pub(crate) fn interrupt_cause(&self) -> Arc<Mutex<Option<InterruptCause>>> {
+        self.interrupt_cause.clone()
+    }
+
     #[inline]
     pub fn externref_activations_table(&mut self) -> &mut VMExternRefActivationsTable {
         &mut self.externref_activations_table
     }
 
+    #[inline]
+    pub(crate) fn stack_usage(&mut self) -> &mut StackUsage {
+        &mut self.stack_usage
+    }
+
     pub fn gc(&mut self) {
         // For this crate's API, we ensure that `set_stack_canary` invariants
         // are upheld for all host-->Wasm calls.
@@ -834,10 +2259,49 @@ impl StoreInnermost {
         Some(u64::try_from(self.fuel_adj + consumed).unwrap())
     }
 
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        if !self.engine.config().tunables.consume_fuel {
+            return None;
+        }
+        // The counter goes above zero when wasm runs out of fuel.
+        let consumed = unsafe { *self.interrupts.fuel_consumed.get() };
+        Some(u64::try_from(consumed.saturating_neg()).unwrap_or(0))
+    }
+
+    pub fn function_fuel_consumed(
+        &self,
+        module: &Module,
+    ) -> Option<HashMap<DefinedFuncIndex, u64>> {
+        let tunables = &self.engine.config().tunables;
+        if !tunables.consume_fuel || !tunables.per_function_fuel_accounting {
+            return None;
+        }
+        let module = module.compiled_module().module();
+        match self.function_fuel.get(&(Arc::as_ptr(module) as usize)) {
+            Some((_, consumed)) => Some(consumed.clone()),
+            None => Some(HashMap::new()),
+        }
+    }
+
     fn out_of_fuel_trap(&mut self) {
         self.out_of_gas_behavior = OutOfGas::Trap;
     }
 
+    fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        let deadline = self
+            .engine
+            .current_epoch()
+            .saturating_add(ticks_beyond_current);
+        self.cancellation.epoch_deadline = deadline;
+        self.interrupts.epoch_deadline.store(deadline, SeqCst);
+        // A `CancellationToken` moves the deadline up after flagging the
+        // cancellation, so if that happened concurrently with the store above
+        // then the cancellation is seen here.
+        if self.cancellation.requested.load(SeqCst) && !self.cancellation.grace_granted {
+            self.interrupts.epoch_deadline.store(0, SeqCst);
+        }
+    }
+
     fn out_of_fuel_async_yield(&mut self, injection_count: u64, fuel_to_inject: u64) {
         assert!(
             self.async_support(),
@@ -849,6 +2313,56 @@ impl StoreInnermost {
         };
     }
 
+    fn out_of_fuel_async_yield_with_callback(
+        &mut self,
+        callback: Box<dyn FnMut(&FuelExhausted) -> FuelDecision + Send + Sync>,
+    ) {
+        assert!(
+            self.async_support(),
+            "cannot use `out_of_fuel_async_yield_with_callback` without enabling async support in the config"
+        );
+        self.out_of_gas_behavior = OutOfGas::Callback {
+            callback,
+            injections: 0,
+        };
+    }
+
+    /// Called when all wasm in this store has been suspended, to run the
+    /// suspended access hook if the suspension was a fuel yield.
+    #[cfg(feature = "async")]
+    unsafe fn all_wasm_suspended(&self) {
+        let yielded_for_fuel = &mut *self.async_state.yielded_for_fuel.get();
+        if !std::mem::replace(yielded_for_fuel, false) {
+            return;
+        }
+        if let Some(hook) = &mut *self.async_state.suspended_access_hook.get() {
+            // No poll is in progress and the hook can't reach the store, so
+            // this can't fail.
+            self.with_suspended_access(|access| hook(access)).unwrap();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn with_suspended_access<R>(&self, f: impl FnOnce(&SuspendedAccess<'_>) -> R) -> Result<R> {
+        if !self.async_support() {
+            bail!(
+                "cannot use `with_suspended_access` without enabling async support in the config"
+            );
+        }
+        unsafe {
+            if *self.async_state.polls_in_progress.get() > 0 {
+                bail!("cannot access a store's suspended state while WebAssembly is running in it");
+            }
+            let in_access = self.async_state.in_suspended_access.get();
+            if *in_access {
+                bail!("cannot access a store's suspended state re-entrantly");
+            }
+            let _reset = Reset(in_access, false);
+            *in_access = true;
+            Ok(f(&SuspendedAccess { store: self }))
+        }
+    }
+
     /// Yields execution to the caller on out-of-gas
     ///
     /// This only works on async futures and stores, and assumes that we're
@@ -880,7 +2394,12 @@ impl StoreInnermost {
         }
 
         let mut future = Yield::default();
-        let result = unsafe { self.async_cx().block_on(Pin::new_unchecked(&mut future)) };
+        let result = unsafe {
+            let yielded_for_fuel = self.async_state.yielded_for_fuel.get();
+            let _reset = Reset(yielded_for_fuel, false);
+            *yielded_for_fuel = true;
+            self.async_cx().block_on(Pin::new_unchecked(&mut future))
+        };
         match result {
             // If this finished successfully then we were resumed normally via a
             // `poll`, so inject some more fuel and keep going.
@@ -895,6 +2414,44 @@ impl StoreInnermost {
         }
     }
 
+    /// Gives wasm `fuel` to run with in place of the fuel remaining in the
+    /// store, trapping once it runs out regardless of how running out of fuel
+    /// is otherwise configured.
+    ///
+    /// The store's own fuel is set aside until `end_fuel_budget` is called
+    /// with the returned value, while fuel consumed from the budget counts
+    /// towards the store's total consumption as usual.
+    pub(crate) fn begin_fuel_budget(&mut self, fuel: u64) -> FuelBudget {
+        let fuel = i64::try_from(fuel).unwrap_or(i64::max_value());
+        FuelBudget {
+            remaining: self.replace_fuel_remaining(fuel),
+            out_of_gas_behavior: std::mem::replace(&mut self.out_of_gas_behavior, OutOfGas::Trap),
+        }
+    }
+
+    /// Restores the fuel set aside by `begin_fuel_budget`, returning how much
+    /// of the budget was left over. This is zero or negative if wasm trapped
+    /// because the budget ran out.
+    ///
+    /// Note that any fuel added while the budget was in place is added to the
+    /// budget, and so is discarded here.
+    pub(crate) fn end_fuel_budget(&mut self, saved: FuelBudget) -> i64 {
+        self.out_of_gas_behavior = saved.out_of_gas_behavior;
+        self.replace_fuel_remaining(saved.remaining)
+    }
+
+    /// Sets the fuel remaining for wasm to `fuel` without changing the amount
+    /// consumed so far, returning the amount that was remaining before.
+    fn replace_fuel_remaining(&mut self, fuel: i64) -> i64 {
+        let consumed_ptr = unsafe { &mut *self.interrupts.fuel_consumed.get() };
+        let remaining = -*consumed_ptr;
+        // As in `add_fuel`, saturating here is equivalent to having been
+        // given infinite fuel.
+        self.fuel_adj = (self.fuel_adj + *consumed_ptr).saturating_add(fuel);
+        *consumed_ptr = -fuel;
+        remaining
+    }
+
     fn add_fuel(&mut self, fuel: u64) -> Result<()> {
         anyhow::ensure!(
             self.engine().config().tunables.consume_fuel,
@@ -929,6 +2486,33 @@ impl StoreInnermost {
         Ok(())
     }
 
+    /// Deducts `fuel` from the fuel remaining and returns what's left, or
+    /// returns `None` without deducting anything if there isn't enough.
+    fn deduct_fuel(&mut self, fuel: i64) -> Option<u64> {
+        // The fuel remaining is the negation of the consumed counter, so
+        // `fuel_adj` stays as it is for the total consumed to include `fuel`.
+        let consumed_ptr = unsafe { &mut *self.interrupts.fuel_consumed.get() };
+        match consumed_ptr.checked_add(fuel) {
+            Some(consumed) if consumed <= 0 => {
+                *consumed_ptr = consumed;
+                Some(u64::try_from(-consumed).unwrap())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether this is being called on a fiber running wasm for this
+    /// store, which can be suspended.
+    #[cfg(feature = "async")]
+    fn on_fiber(&self) -> bool {
+        unsafe { !(*self.async_state.current_suspend.get()).is_null() }
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn on_fiber(&self) -> bool {
+        false
+    }
+
     #[inline]
     pub fn signal_handler(&self) -> Option<*const SignalHandler<'static>> {
         let handler = self.signal_handler.as_ref()?;
@@ -983,7 +2567,17 @@ impl<T> StoreContextMut<'_, T> {
                 .allocate_fiber_stack()
                 .map_err(|e| Trap::from(anyhow::Error::from(e)))?;
 
+            // Wasm running on the new fiber starts a fresh native stack, so
+            // carry over how much of the total stack budget the activations
+            // on this stack have used so far.
+            let stack_usage: *mut StackUsage = self.0.stack_usage();
+            let fiber_stack_usage = StackUsage {
+                base: 0,
+                consumed: unsafe { (*stack_usage).used_at(psm::stack_pointer() as usize) },
+            };
+
             let engine = self.engine().clone();
+            let store: *const StoreInnermost = &**self.0;
             let slot = &mut slot;
             let fiber = wasmtime_fiber::Fiber::new(stack, move |keep_going, suspend| {
                 // First check and see if we were interrupted/dropped, and only
@@ -1001,6 +2595,8 @@ impl<T> StoreContextMut<'_, T> {
                 unsafe {
                     let _reset = Reset(current_suspend, *current_suspend);
                     *current_suspend = suspend;
+                    let _reset_stack = Reset(stack_usage, *stack_usage);
+                    *stack_usage = fiber_stack_usage;
 
                     *slot = Some(func(self));
                     Ok(())
@@ -1013,8 +2609,10 @@ impl<T> StoreContextMut<'_, T> {
             // translation from the future protocol to our fiber API.
             FiberFuture {
                 fiber,
+                state: Some(wasmtime_runtime::AsyncWasmCallState::new()),
                 current_poll_cx,
                 engine,
+                store,
             }
         };
         future.await?;
@@ -1023,8 +2621,12 @@ impl<T> StoreContextMut<'_, T> {
 
         struct FiberFuture<'a> {
             fiber: wasmtime_fiber::Fiber<'a, Result<(), Trap>, (), Result<(), Trap>>,
+            /// The wasm activations on the fiber while it's suspended, which
+            /// are only in TLS while the fiber is running.
+            state: Option<wasmtime_runtime::AsyncWasmCallState>,
             current_poll_cx: *mut *mut Context<'static>,
             engine: Engine,
+            store: *const StoreInnermost,
         }
 
         // This is surely the most dangerous `unsafe impl Send` in the entire
@@ -1075,9 +2677,14 @@ impl<T> StoreContextMut<'_, T> {
         // What this all boils down to is that we, as the authors of Wasmtime,
         // need to be extremely careful that on the async fiber stack we only
         // store Send things. For example we can't start using `Rc` willy nilly
-        // by accident and leave a copy in TLS somewhere. (similarly we have to
-        // be ready for TLS to change while we're executing wasm code between
-        // suspension points).
+        // by accident and leave a copy in TLS somewhere. Similarly the TLS
+        // state of wasm activations, which the runtime uses to handle traps,
+        // must never be left behind on a thread the fiber has suspended from.
+        // That's handled by `FiberFuture::resume`, which takes the fiber's
+        // activations out of TLS whenever it suspends and puts them back in
+        // the TLS of whichever thread resumes it. Nothing else is kept in TLS
+        // across suspension points: `current_poll_cx` and `current_suspend`
+        // live in the store and are set afresh by each poll and fiber.
         //
         // While somewhat onerous it shouldn't be too too hard (the TLS bit is
         // the hardest bit so far). This does mean, though, that no user should
@@ -1090,6 +2697,37 @@ impl<T> StoreContextMut<'_, T> {
         // correct. That's what `unsafe` in Rust is all about, though, right?
         unsafe impl Send for FiberFuture<'_> {}
 
+        impl FiberFuture<'_> {
+            /// Resumes the fiber with its wasm activations linked into this
+            /// thread's TLS, unlinking them again once it suspends or
+            /// finishes.
+            fn resume(&mut self, val: Result<(), Trap>) -> Result<Result<(), Trap>, ()> {
+                struct Restore<'a, 'b> {
+                    future: &'a mut FiberFuture<'b>,
+                    prev: Option<wasmtime_runtime::PreviousAsyncWasmCallState>,
+                }
+
+                impl Drop for Restore<'_, '_> {
+                    fn drop(&mut self) {
+                        let prev = self.prev.take().unwrap();
+                        self.future.state = Some(unsafe { prev.restore() });
+                    }
+                }
+
+                // This may be a thread which has never run wasm before, and
+                // the fiber could trap as soon as it's resumed.
+                if let Err(e) = wasmtime_runtime::tls_eager_initialize() {
+                    return Ok(Err(Trap::from_runtime(e)));
+                }
+                let prev = unsafe { self.state.take().unwrap().push() };
+                let restore = Restore {
+                    future: self,
+                    prev: Some(prev),
+                };
+                restore.future.fiber.resume(val)
+            }
+        }
+
         impl Future for FiberFuture<'_> {
             type Output = Result<(), Trap>;
 
@@ -1120,9 +2758,31 @@ impl<T> StoreContextMut<'_, T> {
                     // `Err` with the payload passed to `suspend`, which in our case
                     // is `()`. If `Err` is returned that means the fiber polled a
                     // future but it said "Pending", so we propagate that here.
-                    match self.fiber.resume(Ok(())) {
-                        Ok(result) => Poll::Ready(result),
-                        Err(()) => Poll::Pending,
+                    let async_state = &(*self.store).async_state;
+                    let result = {
+                        let polls = async_state.polls_in_progress.get();
+                        let _reset = Reset(polls, *polls);
+                        *polls += 1;
+                        self.resume(Ok(()))
+                    };
+                    match result {
+                        Ok(result) => {
+                            // All of the fiber's activations have returned by
+                            // the time it finishes.
+                            debug_assert!(
+                                !self.fiber.done() || self.state.as_ref().unwrap().is_empty()
+                            );
+                            Poll::Ready(result)
+                        }
+                        Err(()) => {
+                            // If no other poll is in progress, which could be
+                            // using the store, then all wasm in this store is
+                            // now suspended.
+                            if *async_state.polls_in_progress.get() == 0 {
+                                (*self.store).all_wasm_suspended();
+                            }
+                            Poll::Pending
+                        }
                     }
                 }
             }
@@ -1146,7 +2806,13 @@ impl<T> StoreContextMut<'_, T> {
         impl Drop for FiberFuture<'_> {
             fn drop(&mut self) {
                 if !self.fiber.done() {
-                    let result = self.fiber.resume(Err(Trap::new("future dropped")));
+                    // Dropping the future cancels the call, so host code run
+                    // while unwinding sees the store's `CancellationToken` as
+                    // cancelled.
+                    unsafe {
+                        (*self.store).cancellation.requested.store(true, SeqCst);
+                    }
+                    let result = self.resume(Err(Trap::new("future dropped")));
                     // This resumption with an error should always complete the
                     // fiber. While it's technically possible for host code to catch
                     // the trap and re-resume, we'd ideally like to signal that to
@@ -1172,6 +2838,23 @@ pub struct AsyncCx {
 
 #[cfg(feature = "async")]
 impl AsyncCx {
+    /// Returns a clone of the waker of the task polling the asynchronous call
+    /// into wasm that's currently running, or `None` if there's no such call
+    /// being polled right now, for example while a future is being polled by
+    /// `block_on`.
+    pub fn waker(&self) -> Option<std::task::Waker> {
+        // The polling context is only set while `FiberFuture::poll` is on the
+        // stack, so it's alive for as long as we're looking at it here.
+        unsafe {
+            let poll_cx = *self.current_poll_cx;
+            if poll_cx.is_null() {
+                None
+            } else {
+                Some((*poll_cx).waker().clone())
+            }
+        }
+    }
+
     /// Blocks on the asynchronous computation represented by `future` and
     /// produces the result here, in-line.
     ///
@@ -1228,14 +2911,30 @@ impl AsyncCx {
                 Poll::Pending => {}
             }
 
-            let before = wasmtime_runtime::TlsRestore::take().map_err(Trap::from_runtime)?;
-            let res = (*suspend).suspend(());
-            before.replace().map_err(Trap::from_runtime)?;
-            res?;
+            // Note that the wasm activations on this fiber are taken out of
+            // TLS while it's suspended and put back by whichever thread
+            // resumes it, which may not be this one, see `FiberFuture`.
+            (*suspend).suspend(())?;
         }
     }
 }
 
+/// The virtual pages of one memory, as byte ranges.
+struct VirtualPages {
+    memory: Memory,
+    ranges: Vec<Range<u64>>,
+}
+
+impl VirtualPages {
+    /// Whether the `size` bytes at `address` touch any virtual page.
+    fn contains(&self, address: u64, size: u8) -> bool {
+        let end = address.saturating_add(u64::from(size));
+        self.ranges
+            .iter()
+            .any(|range| address < range.end && range.start < end)
+    }
+}
+
 unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
     fn vminterrupts(&self) -> *mut VMInterrupts {
         <StoreInnermost>::vminterrupts(self)
@@ -1255,16 +2954,134 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
         <Self>::limiter(self)
     }
 
+    fn virtual_memory_access(
+        &mut self,
+        definition: *mut VMMemoryDefinition,
+        address: u64,
+        size: u8,
+        store: Option<u64>,
+    ) -> Result<VirtualMemoryAccess, Box<dyn Error + Send + Sync>> {
+        let memory = match self.inner.virtual_pages.get(&(definition as usize)) {
+            Some(pages) if pages.contains(address, size) => pages.memory,
+            _ => return Ok(VirtualMemoryAccess::NotVirtual),
+        };
+        let panic_policy = self.engine().config().host_panic_policy;
+        let handler = match &mut self.memory_access_handler {
+            Some(handler) => handler,
+            None => return Ok(VirtualMemoryAccess::Trap),
+        };
+        let kind = match store {
+            Some(value) => MemoryAccessKind::Store(value),
+            None => MemoryAccessKind::Load,
+        };
+        let fault = MemoryAccessFault {
+            memory,
+            address,
+            size,
+            kind,
+        };
+        let data = &mut self.data;
+        let resolution = match panic::catch_unwind(AssertUnwindSafe(|| handler(data, fault))) {
+            Ok(resolution) => resolution,
+            Err(panic) => match panic_policy {
+                HostPanicPolicy::CatchAsTrap => return Err(Box::new(Trap::from_host_panic(panic))),
+                HostPanicPolicy::Unwind => panic::resume_unwind(panic),
+            },
+        };
+        Ok(match (resolution, kind) {
+            (FaultResolution::Supply(value), MemoryAccessKind::Load) => {
+                let mask = u64::MAX >> (64 - 8 * u32::from(size));
+                VirtualMemoryAccess::Handled(value & mask)
+            }
+            (FaultResolution::Accept, MemoryAccessKind::Store(_)) => {
+                VirtualMemoryAccess::Handled(0)
+            }
+            _ => VirtualMemoryAccess::Trap,
+        })
+    }
+
+    fn new_epoch(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.cancellation.requested.load(SeqCst) {
+            return self.inner.cancelled();
+        }
+        let callback = match &mut self.epoch_deadline_callback {
+            Some(callback) => callback,
+            None => {
+                return Err(Box::new(Trap::new_wasm(
+                    None,
+                    wasmtime_environ::ir::TrapCode::Interrupt,
+                    backtrace::Backtrace::new_unresolved(),
+                )))
+            }
+        };
+        let ticks = callback(&mut self.data)?;
+        self.inner.set_epoch_deadline(ticks);
+        Ok(())
+    }
+
+    fn attribute_fuel(
+        &mut self,
+        module: &Arc<wasmtime_environ::Module>,
+        func: FuncIndex,
+        fuel: u64,
+    ) {
+        // Only defined functions run wasm code, so they're the only ones which
+        // report the fuel they consume. This is called from wasm, so don't
+        // panic if that's somehow not the case.
+        let index = match module.defined_func_index(func) {
+            Some(index) => index,
+            None => return,
+        };
+        let (_, consumed) = self
+            .function_fuel
+            .entry(Arc::as_ptr(module) as usize)
+            .or_insert_with(|| (module.clone(), HashMap::new()));
+        *consumed.entry(index).or_insert(0) += fuel;
+    }
+
     fn out_of_gas(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        return match &mut self.out_of_gas_behavior {
-            OutOfGas::Trap => Err(Box::new(OutOfGasError)),
+        // If cancellation has been requested then the normal out-of-gas
+        // behavior no longer applies.
+        if self.cancellation.requested.load(SeqCst) {
+            return self.inner.cancelled();
+        }
+
+        let fuel_consumed = self.fuel_consumed().unwrap();
+        match &mut self.out_of_gas_behavior {
+            OutOfGas::Trap => Err(Box::new(Trap::out_of_fuel())),
+            OutOfGas::Callback {
+                callback,
+                injections,
+            } => {
+                let decision = callback(&FuelExhausted {
+                    fuel_consumed,
+                    injections: *injections,
+                });
+                if decision != FuelDecision::Trap {
+                    *injections += 1;
+                }
+                match decision {
+                    FuelDecision::Trap => Err(Box::new(Trap::out_of_fuel())),
+                    #[cfg(feature = "async")]
+                    FuelDecision::InjectAndYield(fuel) => {
+                        StoreContextMut(self).opaque().out_of_gas_yield(fuel)?;
+                        Ok(())
+                    }
+                    #[cfg(not(feature = "async"))]
+                    FuelDecision::InjectAndYield(_) => unreachable!(),
+                    FuelDecision::InjectWithoutYield(fuel) => {
+                        self.add_fuel(fuel).unwrap();
+                        Ok(())
+                    }
+                }
+            }
             #[cfg(feature = "async")]
             OutOfGas::InjectFuel {
                 injection_count,
                 fuel_to_inject,
             } => {
                 if *injection_count == 0 {
-                    return Err(Box::new(OutOfGasError));
+                    return Err(Box::new(Trap::out_of_fuel()));
                 }
                 *injection_count -= 1;
                 let fuel = *fuel_to_inject;
@@ -1273,18 +3090,7 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
             }
             #[cfg(not(feature = "async"))]
             OutOfGas::InjectFuel { .. } => unreachable!(),
-        };
-
-        #[derive(Debug)]
-        struct OutOfGasError;
-
-        impl fmt::Display for OutOfGasError {
-            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("all fuel consumed by WebAssembly")
-            }
         }
-
-        impl std::error::Error for OutOfGasError {}
     }
 }
 
@@ -1319,17 +3125,9 @@ impl Drop for StoreInnermost {
         // NB it's important that this destructor does not access `self.data`.
         // That is deallocated by `Drop for Store<T>` above.
 
-        let allocator = self.engine.allocator();
+        self.deallocate_instances();
         unsafe {
-            let ondemand = OnDemandInstanceAllocator::default();
-            for instance in self.instances.iter() {
-                if instance.ondemand {
-                    ondemand.deallocate(&instance.handle);
-                } else {
-                    allocator.deallocate(&instance.handle);
-                }
-            }
-            ondemand.deallocate(&self.default_callee);
+            OnDemandInstanceAllocator::default().deallocate(&self.default_callee);
         }
     }
 }
@@ -1343,10 +3141,13 @@ impl wasmtime_runtime::ModuleInfoLookup for ModuleRegistry {
 /// A threadsafe handle used to interrupt instances executing within a
 /// particular `Store`.
 ///
-/// This structure is created by the [`Store::interrupt_handle`] method.
+/// This structure is created by the [`Store::interrupt_handle`] and
+/// [`Store::interrupt_handle_with_cause`] methods.
 #[derive(Debug)]
 pub struct InterruptHandle {
     interrupts: Arc<VMInterrupts>,
+    pending_cause: Arc<Mutex<Option<InterruptCause>>>,
+    cause: Option<InterruptCause>,
 }
 
 impl InterruptHandle {
@@ -1357,8 +3158,99 @@ impl InterruptHandle {
     /// rather it will interrupt wasm execution of loop headers and wasm
     /// execution of function entries. For more information see
     /// [`Store::interrupt_handle`].
+    ///
+    /// If this handle was created with a cause, and no other handle's
+    /// interrupt is already pending, the resulting trap reports this handle's
+    /// cause.
     pub fn interrupt(&self) {
-        self.interrupts.interrupt()
+        // Handles without a cause don't take the lock, so they remain usable
+        // from contexts such as signal handlers.
+        let cause = match &self.cause {
+            Some(cause) => cause,
+            None => return self.interrupts.interrupt(),
+        };
+        let mut pending = self.pending_cause.lock().unwrap();
+        // A cause recorded while the flag is no longer set belonged to an
+        // interrupt which was dropped without a trap (e.g. because wasm was
+        // already returning), so it's replaced rather than kept.
+        if pending.is_none() || !self.interrupts.is_interrupted() {
+            *pending = Some(cause.clone());
+        }
+        self.interrupts.interrupt();
+    }
+
+    /// Returns the cause this handle was created with, if any.
+    pub fn cause(&self) -> Option<&InterruptCause> {
+        self.cause.as_ref()
+    }
+}
+
+/// The reason for interrupting wasm, given to
+/// [`Store::interrupt_handle_with_cause`] and reported by
+/// [`Trap::interrupt_cause`](crate::Trap::interrupt_cause).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InterruptCause {
+    /// A textual label, such as `"deadline"`.
+    Label(Arc<str>),
+    /// A numeric code, whose meaning is up to the embedder.
+    Code(u64),
+}
+
+impl From<&str> for InterruptCause {
+    fn from(label: &str) -> InterruptCause {
+        InterruptCause::Label(label.into())
+    }
+}
+
+impl From<String> for InterruptCause {
+    fn from(label: String) -> InterruptCause {
+        InterruptCause::Label(label.into())
+    }
+}
+
+impl From<u64> for InterruptCause {
+    fn from(code: u64) -> InterruptCause {
+        InterruptCause::Code(code)
+    }
+}
+
+impl fmt::Display for InterruptCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterruptCause::Label(label) => write!(f, "{}", label),
+            InterruptCause::Code(code) => write!(f, "{}", code),
+        }
+    }
+}
+
+/// A threadsafe handle used to gracefully cancel execution of WebAssembly
+/// within a particular `Store`.
+///
+/// This structure is created by the [`Store::cancellation_token`] method, see
+/// its documentation for more information about how cancellation proceeds.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    requested: Arc<AtomicBool>,
+    interrupts: Arc<VMInterrupts>,
+}
+
+impl CancellationToken {
+    /// Requests that execution within this token's original [`Store`] be
+    /// cancelled.
+    ///
+    /// Running WebAssembly notices this at its next function entry or loop
+    /// header.
+    pub fn cancel(&self) {
+        self.requested.store(true, SeqCst);
+        // Moving the epoch deadline up has wasm check in with the store at
+        // its next opportunity.
+        self.interrupts.epoch_deadline.store(0, SeqCst);
+    }
+
+    /// Returns whether cancellation has been requested with
+    /// [`CancellationToken::cancel`].
+    pub fn is_cancelled(&self) -> bool {
+        self.requested.load(SeqCst)
     }
 }
 
@@ -1371,3 +3263,101 @@ impl<T: Copy> Drop for Reset<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{StackUsage, MIN_WASM_STACK_LIMIT};
+    use crate::{Engine, Func, Linker, Module, Store};
+
+    const MIB: usize = 1 << 20;
+
+    #[test]
+    fn stack_limit_first_activation() {
+        let usage = StackUsage::default();
+        assert_eq!(usage.used_at(0x8000_0000), 0);
+        assert_eq!(
+            usage.wasm_stack_limit(0x8000_0000, MIB, 2 * MIB),
+            0x8000_0000 - MIB
+        );
+        // The total budget limits the activation if it's smaller.
+        assert_eq!(
+            usage.wasm_stack_limit(0x8000_0000, MIB, MIB / 2),
+            0x8000_0000 - MIB / 2
+        );
+    }
+
+    #[test]
+    fn stack_limit_nested_activation() {
+        let usage = StackUsage {
+            base: 0x8000_0000,
+            consumed: MIB / 4,
+        };
+        let sp = 0x8000_0000 - MIB / 2;
+        assert_eq!(usage.used_at(sp), MIB / 4 + MIB / 2);
+        assert_eq!(usage.wasm_stack_limit(sp, MIB, MIB), sp - MIB / 4);
+
+        // With the budget used up, the limit is the stack pointer itself.
+        assert_eq!(usage.wasm_stack_limit(sp, MIB, MIB / 2), sp);
+    }
+
+    #[test]
+    fn stack_limit_doesnt_wrap() {
+        // A stack low in a 32-bit address space, with a limit bigger than the
+        // space below it, is clamped to the lowest limit rather than wrapping
+        // around to a limit above the stack pointer or going down to zero.
+        let usage = StackUsage::default();
+        let sp = 0x0010_0000usize;
+        assert_eq!(
+            usage.wasm_stack_limit(sp, 256 * MIB, 256 * MIB),
+            MIN_WASM_STACK_LIMIT
+        );
+        assert_eq!(
+            usage.wasm_stack_limit(sp, usize::MAX, usize::MAX),
+            MIN_WASM_STACK_LIMIT
+        );
+        assert_eq!(
+            usage.wasm_stack_limit(sp, sp - 1, usize::MAX),
+            MIN_WASM_STACK_LIMIT
+        );
+
+        // Usage which would overflow saturates, which exhausts the budget.
+        let usage = StackUsage {
+            base: usize::MAX,
+            consumed: usize::MAX - 10,
+        };
+        assert_eq!(usage.used_at(sp), usize::MAX);
+        assert_eq!(usage.wasm_stack_limit(sp, MIB, usize::MAX - 1), sp);
+
+        // A stack pointer above the base, which happens if the stack was
+        // switched, counts as nothing used on this stack.
+        let usage = StackUsage {
+            base: 0x1000,
+            consumed: 0,
+        };
+        assert_eq!(usage.used_at(0x2000), 0);
+    }
+
+    #[test]
+    fn import_templates_are_dropped_with_instance_pre() -> anyhow::Result<()> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, r#"(module (import "" "f" (func)))"#)?;
+        let mut store = Store::new(&engine, ());
+        let mut linker = Linker::new(&engine);
+        linker.define("", "f", Func::wrap(&mut store, || {}))?;
+
+        let pre = linker.instantiate_pre(&mut store, &module)?;
+        pre.instantiate(&mut store)?;
+        pre.instantiate(&mut store)?;
+        assert_eq!(store.inner.import_templates.len(), 1);
+
+        // The template of a dropped `InstancePre` goes away the next time a
+        // template is saved.
+        drop(pre);
+        for _ in 0..10 {
+            let pre = linker.instantiate_pre(&mut store, &module)?;
+            pre.instantiate(&mut store)?;
+            assert_eq!(store.inner.import_templates.len(), 1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! Accounting of the time spent in each wasm activation, enabled with
+//! [`Config::activation_times`](crate::Config::activation_times).
+
+use std::time::{Duration, Instant};
+
+/// How much time a call into WebAssembly spent running guest code and running
+/// the host functions that the guest called.
+///
+/// This is returned by
+/// [`Store::last_activation_times`](crate::Store::last_activation_times) when
+/// [`Config::activation_times`](crate::Config::activation_times) is enabled.
+///
+/// Time is measured at each transition between the host and wasm, so the
+/// guest time also includes the brief work Wasmtime does around calls, such as
+/// converting arguments and running call hooks.
+///
+/// When a host function itself calls back into wasm, that nested call is a
+/// separate activation with its own times, and the time spent in it is
+/// counted in neither the guest nor the host time of the outer activation.
+/// The times of an activation therefore only cover what happened while it was
+/// the innermost one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActivationTimes {
+    guest: Duration,
+    guest_cpu: Duration,
+    host: Duration,
+    host_cpu: Duration,
+}
+
+impl ActivationTimes {
+    /// Returns the wall-clock time spent running guest code.
+    pub fn guest_time(&self) -> Duration {
+        self.guest
+    }
+
+    /// Returns the CPU time the current thread spent running guest code.
+    ///
+    /// This is measured with the thread's CPU clock, so it's only meaningful
+    /// if the activation ran on one thread from start to finish, which isn't
+    /// necessarily the case for async stores. It's always zero on platforms
+    /// without a per-thread CPU clock.
+    pub fn guest_cpu_time(&self) -> Duration {
+        self.guest_cpu
+    }
+
+    /// Returns the wall-clock time spent in host functions called by the
+    /// guest.
+    pub fn host_time(&self) -> Duration {
+        self.host
+    }
+
+    /// Returns the CPU time the current thread spent in host functions called
+    /// by the guest.
+    ///
+    /// The same caveats as for [`ActivationTimes::guest_cpu_time`] apply.
+    pub fn host_cpu_time(&self) -> Duration {
+        self.host_cpu
+    }
+}
+
+/// The state of the wasm activations currently running in a store.
+///
+/// Activations are begun and ended by the store pushing and popping them,
+/// which is done with a guard that also ends an activation when a panic
+/// unwinds through it. Otherwise a stale activation would be left on top of
+/// `running` and be charged with the time of the ones enclosing it.
+#[derive(Default)]
+pub(crate) struct ActivationClock {
+    enabled: bool,
+    /// One entry for each activation on the stack, innermost last.
+    running: Vec<RunningActivation>,
+    last: Option<ActivationTimes>,
+}
+
+struct RunningActivation {
+    times: ActivationTimes,
+    /// Whether the activation is currently running guest code, as opposed to
+    /// a host function.
+    in_guest: bool,
+    /// When the activation last switched between guest and host code.
+    since: Instant,
+    since_cpu: Duration,
+}
+
+impl RunningActivation {
+    /// Adds the time since the activation last switched to the guest or host
+    /// bucket, and restarts the clock.
+    fn tick(&mut self) {
+        let now = Instant::now();
+        let now_cpu = thread_cpu_time();
+        let wall = now - self.since;
+        let cpu = now_cpu.checked_sub(self.since_cpu).unwrap_or_default();
+        if self.in_guest {
+            self.times.guest += wall;
+            self.times.guest_cpu += cpu;
+        } else {
+            self.times.host += wall;
+            self.times.host_cpu += cpu;
+        }
+        self.since = now;
+        self.since_cpu = now_cpu;
+    }
+}
+
+impl ActivationClock {
+    pub(crate) fn new(enabled: bool) -> ActivationClock {
+        ActivationClock {
+            enabled,
+            ..ActivationClock::default()
+        }
+    }
+
+    /// Called when a wasm activation starts.
+    #[inline]
+    pub(crate) fn begin(&mut self) {
+        if self.enabled {
+            self.begin_slow();
+        }
+    }
+
+    fn begin_slow(&mut self) {
+        if let Some(outer) = self.running.last_mut() {
+            outer.tick();
+        }
+        self.running.push(RunningActivation {
+            times: ActivationTimes::default(),
+            in_guest: true,
+            since: Instant::now(),
+            since_cpu: thread_cpu_time(),
+        });
+    }
+
+    /// Called when the innermost wasm activation finishes.
+    #[inline]
+    pub(crate) fn end(&mut self) {
+        if self.enabled {
+            self.end_slow();
+        }
+    }
+
+    fn end_slow(&mut self) {
+        let mut activation = self.running.pop().expect("no wasm activation to end");
+        activation.tick();
+        self.last = Some(activation.times);
+        // Restart the clock of the enclosing activation so that the time spent
+        // in this one isn't counted for it too.
+        if let Some(outer) = self.running.last_mut() {
+            outer.since = Instant::now();
+            outer.since_cpu = thread_cpu_time();
+        }
+    }
+
+    /// Called when wasm calls a host function, or `in_guest` is `false`, and
+    /// when the host function returns, or `in_guest` is `true`.
+    #[inline]
+    pub(crate) fn switch(&mut self, in_guest: bool) {
+        if self.enabled {
+            self.switch_slow(in_guest);
+        }
+    }
+
+    fn switch_slow(&mut self, in_guest: bool) {
+        if let Some(activation) = self.running.last_mut() {
+            if activation.in_guest != in_guest {
+                activation.tick();
+                activation.in_guest = in_guest;
+            }
+        }
+    }
+
+    pub(crate) fn last(&self) -> Option<ActivationTimes> {
+        self.last
+    }
+}
+
+/// Returns the CPU time consumed by the current thread so far.
+#[cfg(unix)]
+fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) } != 0 {
+        return Duration::default();
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Returns the CPU time consumed by the current thread so far.
+#[cfg(windows)]
+fn thread_cpu_time() -> Duration {
+    use winapi::shared::minwindef::FILETIME;
+    use winapi::um::processthreadsapi::{GetCurrentThread, GetThreadTimes};
+
+    let mut times = [FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    }; 4];
+    let [creation, exit, kernel, user] = &mut times;
+    if unsafe { GetThreadTimes(GetCurrentThread(), creation, exit, kernel, user) } == 0 {
+        return Duration::default();
+    }
+    // Each of these is a count of 100ns intervals.
+    let ticks = |t: &FILETIME| (u64::from(t.dwHighDateTime) << 32) | u64::from(t.dwLowDateTime);
+    Duration::from_nanos((ticks(kernel) + ticks(user)) * 100)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn thread_cpu_time() -> Duration {
+    Duration::default()
+}
@@ -1,6 +1,7 @@
 use crate::store::StoreOpaque;
 use crate::{StoreContext, StoreContextMut};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::marker;
 use std::num::NonZeroU64;
 use std::ops::{Index, IndexMut};
@@ -11,10 +12,29 @@ use std::sync::atomic::{AtomicU64, Ordering::SeqCst};
 // crate-private-type-in-public-interface errors that aren't really too
 // interesting to deal with.
 #[derive(Copy, Clone)]
-pub struct InstanceId(pub(super) usize);
+pub struct StoreInstanceId(pub(super) usize);
+
+/// An identifier for a [`Store`](crate::Store), unique among all of the
+/// stores created by this process.
+///
+/// Everything owned by a store, such as a [`Func`](crate::Func) or a
+/// [`Memory`](crate::Memory), remembers the identifier of its store, so for
+/// example comparing [`Func::store_id`](crate::Func::store_id) with
+/// [`Store::id`](crate::Store::id) tells whether that function can be used
+/// with that store. This is mostly useful for debugging mix-ups between
+/// stores.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct StoreId(NonZeroU64);
+
+impl fmt::Display for StoreId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
 pub struct StoreData {
-    id: NonZeroU64,
+    id: StoreId,
     funcs: Vec<crate::func::FuncData>,
     tables: Vec<wasmtime_runtime::ExportTable>,
     globals: Vec<wasmtime_runtime::ExportGlobal>,
@@ -59,7 +79,7 @@ impl StoreData {
         }
 
         StoreData {
-            id: NonZeroU64::new(id + 1).unwrap(),
+            id: StoreId(NonZeroU64::new(id + 1).unwrap()),
             funcs: Vec::new(),
             tables: Vec::new(),
             globals: Vec::new(),
@@ -68,6 +88,10 @@ impl StoreData {
         }
     }
 
+    pub fn id(&self) -> StoreId {
+        self.id
+    }
+
     pub fn insert<T>(&mut self, data: T) -> Stored<T>
     where
         T: StoredData,
@@ -99,6 +123,19 @@ impl StoreData {
         }
         true
     }
+
+    /// Panics, naming both stores involved, if `id` isn't owned by this
+    /// store.
+    #[inline]
+    pub fn assert_contains<T>(&self, id: Stored<T>) {
+        assert!(
+            id.store_id() == self.id,
+            "object used with the wrong store: it belongs to store {} but was \
+             used with store {}",
+            id.store_id(),
+            self.id,
+        );
+    }
 }
 
 impl<T> Index<Stored<T>> for StoreData
@@ -109,10 +146,7 @@ where
 
     #[inline]
     fn index(&self, index: Stored<T>) -> &Self::Output {
-        assert!(
-            index.store_id() == self.id,
-            "object used with the wrong store"
-        );
+        self.assert_contains(index);
         &T::list(self)[index.index()]
     }
 }
@@ -123,10 +157,7 @@ where
 {
     #[inline]
     fn index_mut(&mut self, index: Stored<T>) -> &mut Self::Output {
-        assert!(
-            index.store_id() == self.id,
-            "object used with the wrong store"
-        );
+        self.assert_contains(index);
         &mut T::list_mut(self)[index.index()]
     }
 }
@@ -181,13 +212,13 @@ where
 
 #[repr(C)] // used by reference in the C API
 pub struct Stored<T> {
-    store_id: NonZeroU64,
+    store_id: StoreId,
     index: usize,
     _marker: marker::PhantomData<fn() -> T>,
 }
 
 impl<T> Stored<T> {
-    fn new(store_id: NonZeroU64, index: usize) -> Stored<T> {
+    fn new(store_id: StoreId, index: usize) -> Stored<T> {
         Stored {
             store_id,
             index,
@@ -195,7 +226,7 @@ impl<T> Stored<T> {
         }
     }
 
-    fn store_id(&self) -> NonZeroU64 {
+    pub fn store_id(&self) -> StoreId {
         self.store_id
     }
 
@@ -210,6 +241,15 @@ impl<T> PartialEq for Stored<T> {
     }
 }
 
+impl<T> Eq for Stored<T> {}
+
+impl<T> Hash for Stored<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.store_id.hash(state);
+        self.index.hash(state);
+    }
+}
+
 impl<T> Copy for Stored<T> {}
 
 impl<T> Clone for Stored<T> {
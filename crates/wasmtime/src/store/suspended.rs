@@ -0,0 +1,48 @@
+use crate::store::StoreInnermost;
+use crate::{Global, Memory, Val};
+
+/// Read-only access to a [`Store`](crate::Store) whose WebAssembly is
+/// suspended at an async yield point.
+///
+/// This is handed to the hook configured with
+/// [`Store::suspended_access_hook`](crate::Store::suspended_access_hook),
+/// which is how a host can inspect the state of a guest, for example a
+/// progress counter it keeps in linear memory, without resuming it, and is
+/// also available through
+/// [`Store::with_suspended_access`](crate::Store::with_suspended_access).
+///
+/// # Memory model
+///
+/// While this exists no WebAssembly is executing in the store, so the
+/// contents of memories and globals can't change underneath it. The
+/// WebAssembly isn't finished, though: whatever it has stored so far is
+/// visible, but any invariants it maintains across several stores may be
+/// halfway through being updated. Readers should only rely on values which
+/// the guest updates in a single store, like a counter.
+///
+/// Slices of memory returned here are only valid for as long as this
+/// exists, since the guest may grow its memory, moving it, once it resumes.
+pub struct SuspendedAccess<'a> {
+    pub(super) store: &'a StoreInnermost,
+}
+
+impl<'a> SuspendedAccess<'a> {
+    /// Returns the contents of `memory`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `memory` doesn't belong to this store.
+    pub fn memory_data(&self, memory: &Memory) -> &[u8] {
+        memory.data_in(self.store.store_data())
+    }
+
+    /// Returns the current value of `global`, or `None` if it holds a
+    /// reference, which can't be read while the store is suspended.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `global` doesn't belong to this store.
+    pub fn global_get(&self, global: &Global) -> Option<Val> {
+        global.get_number(self.store.store_data())
+    }
+}
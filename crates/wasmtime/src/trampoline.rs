@@ -8,10 +8,12 @@ mod table;
 pub(crate) use memory::MemoryCreatorProxy;
 
 pub use self::func::{create_function, create_raw_function};
+#[cfg(feature = "raw-funcs")]
+pub use self::func::{create_raw_parts_function, RawPartsState};
 use self::global::create_global;
 use self::memory::create_memory;
 use self::table::create_table;
-use crate::store::{InstanceId, StoreOpaque};
+use crate::store::{StoreInstanceId, StoreOpaque};
 use crate::{GlobalType, MemoryType, TableType, Val};
 use anyhow::Result;
 use std::any::Any;
@@ -29,7 +31,7 @@ fn create_handle(
     host_state: Box<dyn Any + Send + Sync>,
     func_imports: &[VMFunctionImport],
     shared_signature_id: Option<VMSharedSignatureIndex>,
-) -> Result<InstanceId> {
+) -> Result<StoreInstanceId> {
     let mut imports = Imports::default();
     imports.functions = func_imports;
 
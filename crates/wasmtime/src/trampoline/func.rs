@@ -1,9 +1,10 @@
 //! Support for a calling of an imported function.
 
-use crate::{Engine, FuncType, Trap};
-use anyhow::Result;
+use crate::{Engine, FuncType, HostPanicPolicy, Trap};
+use anyhow::{anyhow, Result};
 use std::any::Any;
 use std::cmp;
+use std::convert::TryFrom;
 use std::mem;
 use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
@@ -26,6 +27,7 @@ use wasmtime_runtime::{
 
 struct TrampolineState {
     func: Box<dyn Fn(*mut VMContext, *mut u128) -> Result<(), Trap> + Send + Sync>,
+    panic_policy: HostPanicPolicy,
     #[allow(dead_code)]
     code_memory: CodeMemory,
 }
@@ -62,10 +64,25 @@ unsafe extern "C" fn stub_fn(
         // crate.
         Ok(Err(trap)) => wasmtime_runtime::raise_user_trap(Box::new(trap)),
 
-        // And finally if the imported function panicked, then we trigger the
-        // form of unwinding that's safe to jump over wasm code on all
-        // platforms.
-        Err(panic) => wasmtime_runtime::resume_panic(panic),
+        // And finally if the imported function panicked, then depending on
+        // the configured policy we either raise the panic as a trap or we
+        // trigger the form of unwinding that's safe to jump over wasm code on
+        // all platforms.
+        Err(panic) => match panic_policy(vmctx) {
+            HostPanicPolicy::CatchAsTrap => {
+                wasmtime_runtime::raise_user_trap(Box::new(Trap::from_host_panic(panic)))
+            }
+            HostPanicPolicy::Unwind => wasmtime_runtime::resume_panic(panic),
+        },
+    }
+
+    unsafe fn panic_policy(vmctx: *mut VMContext) -> HostPanicPolicy {
+        let instance = InstanceHandle::from_vmctx(vmctx);
+        instance
+            .host_state()
+            .downcast_ref::<TrampolineState>()
+            .expect("state")
+            .panic_policy
     }
 
     unsafe fn call_stub(
@@ -82,12 +99,31 @@ unsafe extern "C" fn stub_fn(
     }
 }
 
+/// Computes the size of the values vector of a trampoline for a function with
+/// `params` parameters and `results` results.
+///
+/// Offsets into it are `i32`s, so its size has to fit in one.
+fn values_vec_len(params: usize, results: usize) -> Result<u32> {
+    cmp::max(params, results)
+        .checked_mul(mem::size_of::<u128>())
+        .and_then(|len| i32::try_from(len).ok())
+        .map(|len| len as u32)
+        .ok_or_else(|| {
+            anyhow!(
+                "function type with {} parameters and {} results is too large for a trampoline",
+                params,
+                results
+            )
+        })
+}
+
 /// Create a trampoline for invoking a function.
 fn make_trampoline(
     isa: &dyn TargetIsa,
     code_memory: &mut CodeMemory,
     fn_builder_ctx: &mut FunctionBuilderContext,
     signature: &ir::Signature,
+    values_vec_len: u32,
 ) -> *mut [VMFunctionBody] {
     // Mostly reverse copy of the similar method from wasmtime's
     // wasmtime-jit/src/compiler.rs.
@@ -97,11 +133,7 @@ fn make_trampoline(
     // Add the `values_vec` parameter.
     stub_sig.params.push(ir::AbiParam::new(pointer_type));
 
-    // Compute the size of the values vector. The vmctx and caller vmctx are passed separately.
     let value_size = mem::size_of::<u128>();
-    let values_vec_len = ((value_size as usize)
-        * cmp::max(signature.params.len() - 2, signature.returns.len()))
-        as u32;
 
     let mut context = Context::new();
     context.func = Function::with_name_signature(ExternalName::user(0, 0), signature.clone());
@@ -190,9 +222,12 @@ fn make_trampoline(
             relocations: Default::default(),
             address_map: Default::default(),
             stack_maps: Default::default(),
+            call_sites: Default::default(),
+            direct_call_sites: Default::default(),
             stack_slots: Default::default(),
             traps: Default::default(),
             value_labels_ranges: Default::default(),
+            builtins: Default::default(),
         })
         .expect("allocate_for_function")
 }
@@ -205,23 +240,28 @@ pub fn create_function(
     // Note that we specifically enable reference types here in our ISA because
     // `Func::new` is intended to be infallible, but our signature may use
     // reference types which requires safepoints.
+    engine
+        .config()
+        .tunables
+        .check_func_type_limits(ft.param_arity(), ft.result_arity())
+        .map_err(anyhow::Error::msg)?;
+    // The vmctx and caller vmctx are passed separately from the values vector.
+    // This is checked before building the signature, which is large for the
+    // types that fail.
+    let values_vec_len = values_vec_len(ft.param_arity(), ft.result_arity())?;
     let isa = engine.config().target_isa_with_reference_types();
-
-    let mut sig = blank_sig(&*isa, wasmtime_call_conv(&*isa));
-    sig.params.extend(
-        ft.params()
-            .map(|p| ir::AbiParam::new(p.get_wasmtime_type())),
-    );
-    sig.returns.extend(
-        ft.results()
-            .map(|p| ir::AbiParam::new(p.get_wasmtime_type())),
-    );
+    let sig = native_signature(&*isa, ft);
 
     let mut fn_builder_ctx = FunctionBuilderContext::new();
     let mut code_memory = CodeMemory::new();
 
-    let wasm_trampoline =
-        make_trampoline(isa.as_ref(), &mut code_memory, &mut fn_builder_ctx, &sig);
+    let wasm_trampoline = make_trampoline(
+        isa.as_ref(),
+        &mut code_memory,
+        &mut fn_builder_ctx,
+        &sig,
+        values_vec_len,
+    );
 
     // ... and then we also need a trampoline with the standard "trampoline ABI"
     // which enters into the ABI specified by `ft`. Note that this is only used
@@ -236,18 +276,77 @@ pub fn create_function(
 
     code_memory.publish(isa.as_ref());
 
-    let sig = engine.signatures().register(ft.as_wasm_func_type());
+    let sig = engine.signatures().register(ft);
 
     unsafe {
         let instance = create_raw_function(
             wasm_trampoline,
             sig,
-            Box::new(TrampolineState { func, code_memory }),
+            Box::new(TrampolineState {
+                func,
+                panic_policy: engine.config().host_panic_policy,
+                code_memory,
+            }),
         )?;
         Ok((instance, host_trampoline))
     }
 }
 
+/// Host state of functions created with [`create_raw_parts_function`].
+#[cfg(feature = "raw-funcs")]
+pub struct RawPartsState {
+    pub state: Box<dyn Any + Send + Sync>,
+    #[allow(dead_code)]
+    code_memory: CodeMemory,
+}
+
+/// Creates a function whose body is the native code at `callee`, which must
+/// follow the wasm calling convention for `ft`.
+///
+/// Only the trampoline used to call the function from the host is generated,
+/// wasm calls `callee` directly.
+#[cfg(feature = "raw-funcs")]
+pub unsafe fn create_raw_parts_function(
+    ft: &FuncType,
+    callee: *const u8,
+    state: Box<dyn Any + Send + Sync>,
+    engine: &Engine,
+) -> Result<(InstanceHandle, VMTrampoline)> {
+    let isa = engine.config().target_isa_with_reference_types();
+    let sig = native_signature(&*isa, ft);
+
+    let mut fn_builder_ctx = FunctionBuilderContext::new();
+    let mut code_memory = CodeMemory::new();
+    let host_trampoline = trampoline::make_trampoline(
+        &*isa,
+        &mut code_memory,
+        &mut fn_builder_ctx,
+        &sig,
+        mem::size_of::<u128>(),
+    )?;
+    code_memory.publish(isa.as_ref());
+
+    let sig = engine.signatures().register(ft);
+    let func = std::ptr::slice_from_raw_parts_mut(callee as *mut VMFunctionBody, 0);
+    let instance = create_raw_function(func, sig, Box::new(RawPartsState { state, code_memory }))?;
+    Ok((instance, host_trampoline))
+}
+
+/// Returns the native signature of functions of type `ft`, including the
+/// leading callee and caller vmctx parameters.
+fn native_signature(isa: &dyn TargetIsa, ft: &FuncType) -> ir::Signature {
+    let mut sig = blank_sig(isa, wasmtime_call_conv(isa));
+    sig.params.extend(
+        ft.params()
+            .map(|p| ir::AbiParam::new(p.get_wasmtime_type())),
+    );
+    sig.returns.extend(
+        ft.results()
+            .map(|p| ir::AbiParam::new(p.get_wasmtime_type())),
+    );
+    sig
+}
+
 pub unsafe fn create_raw_function(
     func: *mut [VMFunctionBody],
     sig: VMSharedSignatureIndex,
@@ -1,4 +1,4 @@
-use crate::store::{InstanceId, StoreOpaque};
+use crate::store::{StoreInstanceId, StoreOpaque};
 use crate::trampoline::create_handle;
 use crate::{GlobalType, Mutability, Val};
 use anyhow::Result;
@@ -9,7 +9,11 @@ use wasmtime_environ::{
 };
 use wasmtime_runtime::VMFunctionImport;
 
-pub fn create_global(store: &mut StoreOpaque<'_>, gt: &GlobalType, val: Val) -> Result<InstanceId> {
+pub fn create_global(
+    store: &mut StoreOpaque<'_>,
+    gt: &GlobalType,
+    val: Val,
+) -> Result<StoreInstanceId> {
     let mut module = Module::new();
     let mut func_imports = Vec::new();
     let mut externref_init = None;
@@ -27,6 +31,7 @@ pub fn create_global(store: &mut StoreOpaque<'_>, gt: &GlobalType, val: Val) ->
             Val::I64(i) => wasm::GlobalInit::I64Const(i),
             Val::F32(f) => wasm::GlobalInit::F32Const(f),
             Val::F64(f) => wasm::GlobalInit::F64Const(f),
+            Val::V128(x) => wasm::GlobalInit::V128Const(x.to_le_bytes()[..].into()),
             Val::ExternRef(None) | Val::FuncRef(None) => wasm::GlobalInit::RefNullConst,
             Val::ExternRef(Some(x)) => {
                 // There is no `GlobalInit` variant for using an existing
@@ -61,7 +66,6 @@ pub fn create_global(store: &mut StoreOpaque<'_>, gt: &GlobalType, val: Val) ->
 
                 wasm::GlobalInit::RefFunc(func_index)
             }
-            _ => unimplemented!("create_global for {:?}", gt),
         },
     };
 
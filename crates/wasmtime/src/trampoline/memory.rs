@@ -1,22 +1,40 @@
 use crate::memory::{LinearMemory, MemoryCreator};
-use crate::store::{InstanceId, StoreOpaque};
+use crate::store::{StoreInstanceId, StoreOpaque};
 use crate::trampoline::create_handle;
 use crate::{Limits, MemoryType};
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use wasmtime_environ::entity::PrimaryMap;
-use wasmtime_environ::{wasm, MemoryPlan, MemoryStyle, Module, WASM_PAGE_SIZE};
+use wasmtime_environ::{wasm, MemoryPlan, MemoryStyle, Module, WASM_MAX_PAGES, WASM_PAGE_SIZE};
 use wasmtime_runtime::{RuntimeLinearMemory, RuntimeMemoryCreator, VMMemoryDefinition};
 
 use std::convert::TryFrom;
 use std::sync::Arc;
 
-pub fn create_memory(store: &mut StoreOpaque<'_>, memory: &MemoryType) -> Result<InstanceId> {
+pub fn create_memory(store: &mut StoreOpaque<'_>, memory: &MemoryType) -> Result<StoreInstanceId> {
     let mut module = Module::new();
 
+    if memory.is_shared() {
+        if !store.engine().config().features.threads {
+            bail!("shared memories require the threads proposal to be enabled");
+        }
+        if memory.limits().max().is_none() {
+            bail!("shared memories must have a maximum size");
+        }
+    }
+    // Memories can't have more than `WASM_MAX_PAGES` pages, even as their
+    // maximum, and their minimum is otherwise held to the same limit as those
+    // modules define.
+    memory
+        .limits()
+        .check_creatable("pages", u64::from(WASM_MAX_PAGES))?;
+    memory
+        .limits()
+        .check_min("pages", store.engine().config().tunables.max_memory_pages)?;
     let memory = wasm::Memory {
         minimum: memory.limits().min(),
         maximum: memory.limits().max(),
-        shared: false, // TODO
+        shared: memory.is_shared(),
+        memory64: memory.is_64(),
     };
 
     let memory_plan =
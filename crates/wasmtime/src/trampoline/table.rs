@@ -1,13 +1,22 @@
-use crate::store::{InstanceId, StoreOpaque};
+use crate::store::{StoreInstanceId, StoreOpaque};
 use crate::trampoline::create_handle;
 use crate::{TableType, ValType};
 use anyhow::{bail, Result};
 use wasmtime_environ::entity::PrimaryMap;
 use wasmtime_environ::{wasm, Module};
 
-pub fn create_table(store: &mut StoreOpaque<'_>, table: &TableType) -> Result<InstanceId> {
+pub fn create_table(store: &mut StoreOpaque<'_>, table: &TableType) -> Result<StoreInstanceId> {
     let mut module = Module::new();
 
+    // Any maximum is valid, but the minimum is held to the same limit as
+    // that of the tables modules define.
+    table
+        .limits()
+        .check_creatable("elements", u64::from(u32::MAX))?;
+    table.limits().check_min(
+        "elements",
+        store.engine().config().tunables.max_table_elements,
+    )?;
     let table = wasm::Table {
         wasm_ty: table.element().to_wasm_type(),
         minimum: table.limits().min(),
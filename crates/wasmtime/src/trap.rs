@@ -1,6 +1,7 @@
 use crate::module::GlobalModuleRegistry;
-use crate::FrameInfo;
+use crate::{ErrorKind, FrameInfo, InterruptCause};
 use backtrace::Backtrace;
+use std::any::Any;
 use std::fmt;
 use std::sync::Arc;
 use wasmtime_environ::ir;
@@ -85,6 +86,11 @@ pub enum TrapCode {
 
     /// Execution has potentially run too long and may be interrupted.
     Interrupt,
+
+    /// WebAssembly ran out of fuel, see [`Config::consume_fuel`].
+    ///
+    /// [`Config::consume_fuel`]: crate::Config::consume_fuel
+    OutOfFuel,
 }
 
 impl TrapCode {
@@ -122,6 +128,7 @@ impl fmt::Display for TrapCode {
             BadConversionToInteger => "invalid conversion to integer",
             UnreachableCodeReached => "unreachable",
             Interrupt => "interrupt",
+            OutOfFuel => "all fuel consumed by WebAssembly",
         };
         write!(f, "{}", desc)
     }
@@ -132,6 +139,10 @@ struct TrapInner {
     wasm_trace: Vec<FrameInfo>,
     native_trace: Backtrace,
     hint_wasm_backtrace_details_env: bool,
+    interrupt_cause: Option<InterruptCause>,
+    /// The number of calls into wasm, youngest first, whose frames in
+    /// `wasm_trace` have been attributed to instances.
+    named_calls: usize,
 }
 
 fn _assert_trap_is_sync_and_send(t: &Trap) -> (&dyn Sync, &dyn Send) {
@@ -151,6 +162,37 @@ impl Trap {
         Trap::new_with_trace(None, reason, Backtrace::new_unresolved())
     }
 
+    /// Creates a new `Trap` with `message`, without capturing a backtrace.
+    ///
+    /// Capturing a backtrace dominates the cost of [`Trap::new`], so this is
+    /// useful for host functions which routinely return errors to wasm, for
+    /// example to signal that an operation would block. The [`trace`] of the
+    /// returned trap is always empty, and it will stay empty when it's
+    /// returned from a host function back through wasm frames. Traps raised by
+    /// wasm itself, such as out-of-bounds memory accesses, still capture a
+    /// backtrace.
+    ///
+    /// [`trace`]: Trap::trace
+    ///
+    /// # Example
+    /// ```
+    /// let trap = wasmtime::Trap::new_without_backtrace("would block");
+    /// assert_eq!(trap.to_string(), "would block");
+    /// assert!(trap.trace().is_empty());
+    /// ```
+    pub fn new_without_backtrace<I: Into<String>>(message: I) -> Self {
+        Trap {
+            inner: Arc::new(TrapInner {
+                reason: TrapReason::Message(message.into()),
+                wasm_trace: Vec::new(),
+                native_trace: Backtrace::from(Vec::new()),
+                hint_wasm_backtrace_details_env: false,
+                interrupt_cause: None,
+                named_calls: 0,
+            }),
+        }
+    }
+
     /// Creates a new `Trap` representing an explicit program exit with a classic `i32`
     /// exit status value.
     #[cold] // see Trap::new
@@ -162,6 +204,27 @@ impl Trap {
         )
     }
 
+    /// Creates a trap out of the payload of a panic in a host function, for
+    /// [`HostPanicPolicy::CatchAsTrap`](crate::HostPanicPolicy::CatchAsTrap).
+    #[cold] // see Trap::new
+    pub(crate) fn from_host_panic(panic: Box<dyn Any + Send>) -> Self {
+        let message = if let Some(s) = panic.downcast_ref::<&str>() {
+            s
+        } else if let Some(s) = panic.downcast_ref::<String>() {
+            s.as_str()
+        } else {
+            "Box<dyn Any>"
+        };
+        Trap::new(format!("host function panicked: {}", message))
+    }
+
+    /// Creates the trap raised when WebAssembly runs out of fuel.
+    #[cold] // see Trap::new
+    pub(crate) fn out_of_fuel() -> Self {
+        let reason = TrapReason::InstructionTrap(TrapCode::OutOfFuel);
+        Trap::new_with_trace(None, reason, Backtrace::new_unresolved())
+    }
+
     #[cold] // see Trap::new
     pub(crate) fn from_runtime(runtime_trap: wasmtime_runtime::Trap) -> Self {
         match runtime_trap {
@@ -216,10 +279,13 @@ impl Trap {
     ///   occurred, and this will iterate over the frames to find frames that
     ///   lie in wasm jit code.
     fn new_with_trace(trap_pc: Option<usize>, reason: TrapReason, native_trace: Backtrace) -> Self {
-        let mut wasm_trace = Vec::new();
+        let mut wasm_trace: Vec<FrameInfo> = Vec::new();
         let mut hint_wasm_backtrace_details_env = false;
 
         GlobalModuleRegistry::with(|registry| {
+            // The last frame pushed onto `wasm_trace`, whose caller is the
+            // next frame.
+            let mut callee: Option<usize> = None;
             for frame in native_trace.frames() {
                 let pc = frame.ip() as usize;
                 if pc == 0 {
@@ -239,6 +305,11 @@ impl Trap {
                 if let Some((info, has_unparsed_debuginfo, wasm_backtrace_details_env_used)) =
                     registry.lookup_frame_info(pc_to_lookup)
                 {
+                    if let Some(callee) = callee.take() {
+                        let direct = registry.is_direct_call(pc);
+                        wasm_trace[callee].set_caller(false, direct);
+                    }
+                    callee = Some(wasm_trace.len());
                     wasm_trace.push(info);
 
                     // If this frame has unparsed debug information and the
@@ -250,8 +321,13 @@ impl Trap {
                     if has_unparsed_debuginfo && wasm_backtrace_details_env_used {
                         hint_wasm_backtrace_details_env = true;
                     }
+                } else if let Some(callee) = callee.take() {
+                    wasm_trace[callee].set_caller(true, false);
                 }
             }
+            if let Some(callee) = callee {
+                wasm_trace[callee].set_caller(true, false);
+            }
         });
         Trap {
             inner: Arc::new(TrapInner {
@@ -259,10 +335,33 @@ impl Trap {
                 wasm_trace,
                 native_trace,
                 hint_wasm_backtrace_details_env,
+                interrupt_cause: None,
+                named_calls: 0,
             }),
         }
     }
 
+    /// Lets `name` fill in the instance names of the frames in this trap's
+    /// backtrace, along with the number of calls into wasm, youngest first,
+    /// whose frames it has already attributed to instances.
+    ///
+    /// Frames are left alone if this trap has already been shared.
+    pub(crate) fn name_frames(&mut self, name: impl FnOnce(&mut [FrameInfo], &mut usize)) {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            name(&mut inner.wasm_trace, &mut inner.named_calls);
+        }
+    }
+
+    /// Records the cause of the interrupt which this trap consumed, unless a
+    /// cause was already recorded by a nested activation it passed through.
+    ///
+    /// Nothing is recorded if this trap has already been shared.
+    pub(crate) fn set_interrupt_cause(&mut self, cause: InterruptCause) {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.interrupt_cause.get_or_insert(cause);
+        }
+    }
+
     /// If the trap was the result of an explicit program exit with a classic
     /// `i32` exit status value, return the value, otherwise return `None`.
     pub fn i32_exit_status(&self) -> Option<i32> {
@@ -284,6 +383,8 @@ impl Trap {
 
     /// Returns a list of function frames in WebAssembly code that led to this
     /// trap happening.
+    ///
+    /// This is empty for traps created with [`Trap::new_without_backtrace`].
     pub fn trace(&self) -> &[FrameInfo] {
         &self.inner.wasm_trace
     }
@@ -296,6 +397,17 @@ impl Trap {
             _ => None,
         }
     }
+
+    /// The cause of the interrupt that this trap resulted from.
+    ///
+    /// This is only `Some` for [`TrapCode::Interrupt`] traps where the
+    /// interrupt was requested through a handle created with
+    /// [`Store::interrupt_handle_with_cause`](crate::Store::interrupt_handle_with_cause).
+    /// When several handles interrupted before wasm noticed, this is the cause
+    /// of the first of them.
+    pub fn interrupt_cause(&self) -> Option<&InterruptCause> {
+        self.inner.interrupt_cause.as_ref()
+    }
 }
 
 impl fmt::Debug for Trap {
@@ -304,6 +416,7 @@ impl fmt::Debug for Trap {
             .field("reason", &self.inner.reason)
             .field("wasm_trace", &self.inner.wasm_trace)
             .field("native_trace", &self.inner.native_trace)
+            .field("interrupt_cause", &self.inner.interrupt_cause)
             .finish()
     }
 }
@@ -319,6 +432,9 @@ impl fmt::Display for Trap {
         for (i, frame) in self.trace().iter().enumerate() {
             let name = frame.module_name().unwrap_or("<unknown>");
             write!(f, "  {:>3}: {:#6x} - ", i, frame.module_offset())?;
+            if let Some(instance) = frame.instance_name() {
+                write!(f, "[{}] ", instance)?;
+            }
 
             let demangle =
                 |f: &mut fmt::Formatter<'_>, name: &str| match rustc_demangle::try_demangle(name) {
@@ -382,7 +498,17 @@ impl std::error::Error for Trap {
 
 impl From<anyhow::Error> for Trap {
     fn from(e: anyhow::Error) -> Trap {
-        Box::<dyn std::error::Error + Send + Sync>::from(e).into()
+        // An instance's start function having trapped is just that trap.
+        let kind = e.downcast_ref::<crate::Error>().map(|e| e.kind());
+        if let Some(ErrorKind::StartTrap(trap)) = kind {
+            return trap.clone();
+        }
+        // Converting to a boxed error would hide a trap behind anyhow's own
+        // wrapper, so pull it out here instead.
+        match e.downcast::<Trap>() {
+            Ok(trap) => trap,
+            Err(e) => Box::<dyn std::error::Error + Send + Sync>::from(e).into(),
+        }
     }
 }
 
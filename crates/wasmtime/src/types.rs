@@ -1,9 +1,15 @@
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use wasmtime_environ::wasm::{EntityType, WasmFuncType};
 use wasmtime_environ::{ir, wasm};
 use wasmtime_jit::TypeTables;
 
 pub(crate) mod matching;
+mod parse;
+
+pub use parse::TypeParseError;
 
 // Type Representations
 
@@ -48,6 +54,60 @@ impl Limits {
     pub fn max(&self) -> Option<u32> {
         self.max
     }
+
+    /// Checks that a table or memory with these limits, measured in `unit`s,
+    /// may be created by the host: the minimum can't exceed the maximum, and
+    /// neither the minimum nor the maximum can exceed `cap`.
+    pub(crate) fn check_creatable(&self, unit: &str, cap: u64) -> anyhow::Result<()> {
+        if let Some(max) = self.max {
+            if self.min > max {
+                anyhow::bail!(
+                    "minimum size of {} {} exceeds the maximum of {}",
+                    self.min,
+                    unit,
+                    max
+                );
+            }
+        }
+        self.check_min(unit, cap)?;
+        if let Some(max) = self.max {
+            if u64::from(max) > cap {
+                anyhow::bail!(
+                    "maximum size of {} {} exceeds the limit of {}",
+                    max,
+                    unit,
+                    cap
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the minimum of these limits, measured in `unit`s, doesn't
+    /// exceed `cap`.
+    pub(crate) fn check_min(&self, unit: &str, cap: u64) -> anyhow::Result<()> {
+        if u64::from(self.min) > cap {
+            anyhow::bail!(
+                "minimum size of {} {} exceeds the limit of {}",
+                self.min,
+                unit,
+                cap
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Formats these limits as they're written in the WebAssembly text format:
+/// the minimum, followed by the maximum if there is one.
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.min)?;
+        if let Some(max) = self.max {
+            write!(f, " {}", max)?;
+        }
+        Ok(())
+    }
 }
 
 // Value Types
@@ -258,12 +318,41 @@ impl From<InstanceType> for ExternType {
     }
 }
 
+/// Formats this type as it would be written in the WebAssembly text format,
+/// delegating to the `Display` implementation of the underlying type.
+impl fmt::Display for ExternType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExternType::Func(ty) => ty.fmt(f),
+            ExternType::Global(ty) => ty.fmt(f),
+            ExternType::Table(ty) => ty.fmt(f),
+            ExternType::Memory(ty) => ty.fmt(f),
+            ExternType::Instance(ty) => ty.fmt(f),
+            ExternType::Module(ty) => ty.fmt(f),
+        }
+    }
+}
+
 /// A descriptor for a function in a WebAssembly module.
 ///
 /// WebAssembly functions can have 0 or more parameters and results.
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+///
+/// A `FuncType` is a reference-counted handle, so cloning one doesn't copy the
+/// lists of parameters and results. Types registered with an
+/// [`Engine`](crate::Engine), for example by creating a [`Func`](crate::Func)
+/// with them, are interned in it, and the types it hands back, such as those
+/// returned by [`Func::ty`](crate::Func::ty), share a single allocation per
+/// signature. Comparing and hashing those is then constant-time, and other
+/// types fall back to comparing their parameters and results.
+#[derive(Clone)]
 pub struct FuncType {
-    sig: WasmFuncType,
+    sig: Arc<InternedFuncType>,
+}
+
+struct InternedFuncType {
+    /// The hash of `ty`, computed once when the type is created.
+    hash: u64,
+    ty: WasmFuncType,
 }
 
 impl FuncType {
@@ -275,31 +364,103 @@ impl FuncType {
         params: impl IntoIterator<Item = ValType>,
         results: impl IntoIterator<Item = ValType>,
     ) -> FuncType {
-        FuncType {
-            sig: WasmFuncType {
-                params: params.into_iter().map(|t| t.to_wasm_type()).collect(),
-                returns: results.into_iter().map(|t| t.to_wasm_type()).collect(),
-            },
-        }
+        FuncType::from_wasm_func_type(WasmFuncType {
+            params: params.into_iter().map(|t| t.to_wasm_type()).collect(),
+            returns: results.into_iter().map(|t| t.to_wasm_type()).collect(),
+        })
     }
 
     /// Returns the list of parameter types for this function.
     pub fn params(&self) -> impl ExactSizeIterator<Item = ValType> + '_ {
-        self.sig.params.iter().map(ValType::from_wasm_type)
+        self.sig.ty.params.iter().map(ValType::from_wasm_type)
     }
 
     /// Returns the list of result types for this function.
     pub fn results(&self) -> impl ExactSizeIterator<Item = ValType> + '_ {
-        self.sig.returns.iter().map(ValType::from_wasm_type)
+        self.sig.ty.returns.iter().map(ValType::from_wasm_type)
+    }
+
+    /// Returns the number of parameters this function takes.
+    pub fn param_arity(&self) -> usize {
+        self.sig.ty.params.len()
+    }
+
+    /// Returns the number of results this function returns.
+    pub fn result_arity(&self) -> usize {
+        self.sig.ty.returns.len()
+    }
+
+    pub(crate) fn from_wasm_func_type(ty: wasm::WasmFuncType) -> FuncType {
+        // `DefaultHasher::new` always uses the same keys, so equal types get
+        // equal hashes, whichever engine they come from.
+        let mut hasher = DefaultHasher::new();
+        ty.hash(&mut hasher);
+        FuncType {
+            sig: Arc::new(InternedFuncType {
+                hash: hasher.finish(),
+                ty,
+            }),
+        }
+    }
+
+    /// Returns whether `self` and `other` share the same interned allocation.
+    #[cfg(test)]
+    pub(crate) fn ptr_eq(&self, other: &FuncType) -> bool {
+        Arc::ptr_eq(&self.sig, &other.sig)
+    }
+}
+
+impl PartialEq for FuncType {
+    fn eq(&self, other: &FuncType) -> bool {
+        Arc::ptr_eq(&self.sig, &other.sig)
+            || (self.sig.hash == other.sig.hash && self.sig.ty == other.sig.ty)
+    }
+}
+
+impl Eq for FuncType {}
+
+impl Hash for FuncType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.sig.hash);
+    }
+}
+
+impl fmt::Debug for FuncType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FuncType")
+            .field("sig", &self.sig.ty)
+            .finish()
     }
+}
 
-    pub(crate) fn as_wasm_func_type(&self) -> &wasm::WasmFuncType {
-        &self.sig
+/// Formats this type as it would be written in the WebAssembly text format,
+/// for example `(func (param i32 i64) (result f32))`.
+///
+/// Empty `param` and `result` lists are omitted, so a function with no
+/// parameters or results is `(func)`. The output can be parsed back into an
+/// equal `FuncType` with [`str::parse`].
+impl fmt::Display for FuncType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(func")?;
+        write_val_types(f, "param", self.params())?;
+        write_val_types(f, "result", self.results())?;
+        write!(f, ")")
     }
+}
 
-    pub(crate) fn from_wasm_func_type(sig: wasm::WasmFuncType) -> FuncType {
-        Self { sig }
+fn write_val_types(
+    f: &mut fmt::Formatter,
+    keyword: &str,
+    tys: impl ExactSizeIterator<Item = ValType>,
+) -> fmt::Result {
+    if tys.len() == 0 {
+        return Ok(());
+    }
+    write!(f, " ({}", keyword)?;
+    for ty in tys {
+        write!(f, " {}", ty)?;
     }
+    write!(f, ")")
 }
 
 // Global Types
@@ -348,6 +509,17 @@ impl GlobalType {
     }
 }
 
+/// Formats this type as it would be written in the WebAssembly text format,
+/// for example `(global i32)` or `(global (mut i64))`.
+impl fmt::Display for GlobalType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mutability {
+            Mutability::Const => write!(f, "(global {})", self.content),
+            Mutability::Var => write!(f, "(global (mut {}))", self.content),
+        }
+    }
+}
+
 // Table Types
 
 /// A descriptor for a table in a WebAssembly module.
@@ -392,6 +564,14 @@ impl TableType {
     }
 }
 
+/// Formats this type as it would be written in the WebAssembly text format,
+/// for example `(table 1 funcref)` or `(table 1 10 externref)`.
+impl fmt::Display for TableType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(table {} {})", self.limits, self.element)
+    }
+}
+
 // Memory Types
 
 /// A descriptor for a WebAssembly memory type.
@@ -401,13 +581,42 @@ impl TableType {
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct MemoryType {
     limits: Limits,
+    shared: bool,
+    memory64: bool,
 }
 
 impl MemoryType {
     /// Creates a new descriptor for a WebAssembly memory given the specified
     /// limits of the memory.
     pub fn new(limits: Limits) -> MemoryType {
-        MemoryType { limits }
+        MemoryType {
+            limits,
+            shared: false,
+            memory64: false,
+        }
+    }
+
+    /// Creates a new descriptor for a 64-bit WebAssembly memory, as proposed
+    /// by the [memory64 proposal][proposal], given the specified limits of the
+    /// memory.
+    ///
+    /// Note that while the memory64 proposal allows page counts beyond
+    /// `u32::MAX`, Wasmtime doesn't support memories that large.
+    ///
+    /// [proposal]: https://github.com/WebAssembly/memory64
+    pub fn new64(limits: Limits) -> MemoryType {
+        MemoryType {
+            memory64: true,
+            ..MemoryType::new(limits)
+        }
+    }
+
+    /// Returns this memory type with the shared flag of the [threads
+    /// proposal][proposal] set to `shared`.
+    ///
+    /// [proposal]: https://github.com/WebAssembly/threads
+    pub fn with_shared(self, shared: bool) -> MemoryType {
+        MemoryType { shared, ..self }
     }
 
     /// Returns the limits (in pages) that are configured for this memory.
@@ -415,8 +624,40 @@ impl MemoryType {
         &self.limits
     }
 
+    /// Returns whether this is a shared memory, which may be accessed by
+    /// multiple threads.
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Returns whether this is a 64-bit memory, which is indexed with `i64`
+    /// addresses rather than `i32` ones.
+    pub fn is_64(&self) -> bool {
+        self.memory64
+    }
+
     pub(crate) fn from_wasmtime_memory(memory: &wasm::Memory) -> MemoryType {
-        MemoryType::new(Limits::new(memory.minimum, memory.maximum))
+        MemoryType {
+            limits: Limits::new(memory.minimum, memory.maximum),
+            shared: memory.shared,
+            memory64: memory.memory64,
+        }
+    }
+}
+
+/// Formats this type as it would be written in the WebAssembly text format,
+/// for example `(memory 1)`, `(memory i64 1 2)` or `(memory 1 2 shared)`.
+impl fmt::Display for MemoryType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(memory ")?;
+        if self.memory64 {
+            write!(f, "i64 ")?;
+        }
+        write!(f, "{}", self.limits)?;
+        if self.shared {
+            write!(f, " shared")?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -459,6 +700,7 @@ impl ModuleType {
             module: name,
             name: field.as_deref(),
             ty: EntityOrExtern::Extern(ty),
+            index: None,
         })
     }
 
@@ -467,6 +709,8 @@ impl ModuleType {
         self.exports.iter().map(|(name, ty)| ExportType {
             name,
             ty: EntityOrExtern::Extern(ty),
+            index: None,
+            imported: false,
         })
     }
 
@@ -489,6 +733,31 @@ impl ModuleType {
     }
 }
 
+/// Formats this type as it would be written in the text format of the
+/// module-linking proposal, for example
+/// `(module (import "a" "b" (func)) (export "c" (memory 1)))`.
+impl fmt::Display for ModuleType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(module")?;
+        for (module, field, ty) in self.imports.iter() {
+            write!(f, " (import {:?}", module)?;
+            if let Some(field) = field {
+                write!(f, " {:?}", field)?;
+            }
+            write!(f, " {})", ty)?;
+        }
+        write_exports(f, &self.exports)?;
+        write!(f, ")")
+    }
+}
+
+fn write_exports(f: &mut fmt::Formatter, exports: &[(String, ExternType)]) -> fmt::Result {
+    for (name, ty) in exports {
+        write!(f, " (export {:?} {})", name, ty)?;
+    }
+    Ok(())
+}
+
 // Instance Types
 
 /// A descriptor for a WebAssembly instance type.
@@ -519,6 +788,8 @@ impl InstanceType {
         self.exports.iter().map(|(name, ty)| ExportType {
             name,
             ty: EntityOrExtern::Extern(ty),
+            index: None,
+            imported: false,
         })
     }
 
@@ -536,6 +807,16 @@ impl InstanceType {
     }
 }
 
+/// Formats this type as it would be written in the text format of the
+/// module-linking proposal, for example `(instance (export "a" (func)))`.
+impl fmt::Display for InstanceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(instance")?;
+        write_exports(f, &self.exports)?;
+        write!(f, ")")
+    }
+}
+
 // Import Types
 
 /// A descriptor for an imported value into a wasm module.
@@ -554,6 +835,9 @@ pub struct ImportType<'module> {
 
     /// The type of the import.
     ty: EntityOrExtern<'module>,
+
+    /// The index of the imported item in its index space, if known.
+    index: Option<u32>,
 }
 
 #[derive(Clone)]
@@ -569,12 +853,14 @@ impl<'module> ImportType<'module> {
         module: &'module str,
         name: Option<&'module str>,
         ty: EntityType,
+        index: wasm::EntityIndex,
         types: &'module TypeTables,
     ) -> ImportType<'module> {
         ImportType {
             module,
             name,
             ty: EntityOrExtern::Entity(ty, types),
+            index: Some(entity_index(index)),
         }
     }
 
@@ -600,6 +886,29 @@ impl<'module> ImportType<'module> {
             EntityOrExtern::Extern(e) => (*e).clone(),
         }
     }
+
+    /// Returns the index that the imported item is given in the index space
+    /// of its kind (functions, globals, tables, ...) within the module.
+    ///
+    /// For functions this is the same index as reported by
+    /// [`FrameInfo::func_index`](crate::FrameInfo::func_index).
+    ///
+    /// This is `None` for imports listed by a [`ModuleType`], which describes
+    /// a module's signature without its index spaces.
+    pub fn index(&self) -> Option<u32> {
+        self.index
+    }
+}
+
+fn entity_index(index: wasm::EntityIndex) -> u32 {
+    match index {
+        wasm::EntityIndex::Function(i) => i.as_u32(),
+        wasm::EntityIndex::Table(i) => i.as_u32(),
+        wasm::EntityIndex::Memory(i) => i.as_u32(),
+        wasm::EntityIndex::Global(i) => i.as_u32(),
+        wasm::EntityIndex::Module(i) => i.as_u32(),
+        wasm::EntityIndex::Instance(i) => i.as_u32(),
+    }
 }
 
 impl<'module> fmt::Debug for ImportType<'module> {
@@ -627,6 +936,12 @@ pub struct ExportType<'module> {
 
     /// The type of the export.
     ty: EntityOrExtern<'module>,
+
+    /// The index of the exported item in its index space, if known.
+    index: Option<u32>,
+
+    /// Whether the exported item is one of the module's imports.
+    imported: bool,
 }
 
 impl<'module> ExportType<'module> {
@@ -635,11 +950,15 @@ impl<'module> ExportType<'module> {
     pub(crate) fn new(
         name: &'module str,
         ty: EntityType,
+        index: wasm::EntityIndex,
+        imported: bool,
         types: &'module TypeTables,
     ) -> ExportType<'module> {
         ExportType {
             name,
             ty: EntityOrExtern::Entity(ty, types),
+            index: Some(entity_index(index)),
+            imported,
         }
     }
 
@@ -655,6 +974,29 @@ impl<'module> ExportType<'module> {
             EntityOrExtern::Extern(e) => (*e).clone(),
         }
     }
+
+    /// Returns the index of the exported item in the index space of its kind
+    /// (functions, globals, tables, ...) within the module.
+    ///
+    /// Index spaces begin with a module's imports, so an export of an
+    /// imported item has the same index as reported by
+    /// [`ImportType::index`]. For functions this is the same index as
+    /// reported by [`FrameInfo::func_index`](crate::FrameInfo::func_index).
+    ///
+    /// This is `None` for exports listed by a [`ModuleType`] or
+    /// [`InstanceType`], which don't describe index spaces.
+    pub fn index(&self) -> Option<u32> {
+        self.index
+    }
+
+    /// Returns whether the exported item is one of the module's imports being
+    /// re-exported, rather than an item defined by the module itself.
+    ///
+    /// This is `false` for exports listed by a [`ModuleType`] or
+    /// [`InstanceType`].
+    pub fn is_imported_entity(&self) -> bool {
+        self.imported
+    }
 }
 
 impl<'module> fmt::Debug for ExportType<'module> {
@@ -665,3 +1007,130 @@ impl<'module> fmt::Debug for ExportType<'module> {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let func = |params: &[ValType], results: &[ValType]| {
+            FuncType::new(params.iter().cloned(), results.iter().cloned()).to_string()
+        };
+        assert_eq!(func(&[], &[]), "(func)");
+        assert_eq!(func(&[ValType::I32], &[]), "(func (param i32))");
+        assert_eq!(func(&[], &[ValType::V128]), "(func (result v128))");
+        assert_eq!(
+            func(&[ValType::I32, ValType::I64], &[ValType::F32]),
+            "(func (param i32 i64) (result f32))"
+        );
+        assert_eq!(
+            func(
+                &[ValType::ExternRef, ValType::FuncRef],
+                &[ValType::F64, ValType::F64]
+            ),
+            "(func (param externref funcref) (result f64 f64))"
+        );
+
+        assert_eq!(
+            GlobalType::new(ValType::I32, Mutability::Const).to_string(),
+            "(global i32)"
+        );
+        assert_eq!(
+            GlobalType::new(ValType::I64, Mutability::Var).to_string(),
+            "(global (mut i64))"
+        );
+
+        assert_eq!(
+            TableType::new(ValType::FuncRef, Limits::at_least(1)).to_string(),
+            "(table 1 funcref)"
+        );
+        assert_eq!(
+            TableType::new(ValType::ExternRef, Limits::new(0, Some(10))).to_string(),
+            "(table 0 10 externref)"
+        );
+
+        assert_eq!(
+            MemoryType::new(Limits::at_least(1)).to_string(),
+            "(memory 1)"
+        );
+        assert_eq!(
+            MemoryType::new(Limits::new(1, Some(2))).to_string(),
+            "(memory 1 2)"
+        );
+
+        let mut instance = InstanceType::new();
+        assert_eq!(instance.to_string(), "(instance)");
+        instance.add_named_export("f", FuncType::new(None, None).into());
+        instance.add_named_export("m", MemoryType::new(Limits::at_least(0)).into());
+        assert_eq!(
+            instance.to_string(),
+            r#"(instance (export "f" (func)) (export "m" (memory 0)))"#
+        );
+
+        let mut module = ModuleType::new();
+        assert_eq!(module.to_string(), "(module)");
+        module.add_named_import(
+            "a",
+            Some("b"),
+            GlobalType::new(ValType::F32, Mutability::Const).into(),
+        );
+        module.add_named_import("c", None, instance.into());
+        module.add_named_export("d", FuncType::new(Some(ValType::I32), None).into());
+        assert_eq!(
+            module.to_string(),
+            r#"(module (import "a" "b" (global f32)) (import "c" (instance (export "f" (func)) (export "m" (memory 0)))) (export "d" (func (param i32))))"#
+        );
+
+        let ty: ExternType = MemoryType::new(Limits::at_least(3)).into();
+        assert_eq!(ty.to_string(), "(memory 3)");
+        let ty: ExternType = FuncType::new(None, Some(ValType::I32)).into();
+        assert_eq!(ty.to_string(), "(func (result i32))");
+    }
+
+    #[test]
+    fn arity() {
+        let ty = FuncType::new([ValType::I32, ValType::I64].iter().cloned(), None);
+        assert_eq!(ty.param_arity(), 2);
+        assert_eq!(ty.result_arity(), 0);
+    }
+
+    #[test]
+    fn interned_func_types() {
+        use crate::{Func, Store};
+
+        let new = || FuncType::new([ValType::I32, ValType::I64].iter().cloned(), None);
+        let hash = |ty: &FuncType| {
+            let mut hasher = DefaultHasher::new();
+            ty.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // Types which aren't interned are still compared by their contents.
+        let (a, b) = (new(), new());
+        assert!(!a.ptr_eq(&b));
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(a, FuncType::new(None, None));
+        assert_ne!(
+            a,
+            FuncType::new([ValType::I64, ValType::I32].iter().cloned(), None)
+        );
+
+        // The first type registered for a signature is the one the engine
+        // hands back for every function with it.
+        let mut store = Store::<()>::default();
+        let f = Func::new(&mut store, a.clone(), |_, _, _| Ok(()));
+        let g = Func::new(&mut store, b.clone(), |_, _, _| Ok(()));
+        assert!(f.ty(&store).ptr_eq(&a));
+        assert!(g.ty(&store).ptr_eq(&a));
+        assert!(!g.ty(&store).ptr_eq(&b));
+
+        // Types from different engines are only equal structurally.
+        let mut other = Store::<()>::default();
+        let h = Func::new(&mut other, new(), |_, _, _| Ok(()));
+        assert!(!h.ty(&other).ptr_eq(&f.ty(&store)));
+        assert_eq!(h.ty(&other), f.ty(&store));
+        assert_eq!(hash(&h.ty(&other)), hash(&f.ty(&store)));
+    }
+}
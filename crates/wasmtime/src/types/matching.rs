@@ -1,7 +1,9 @@
 use crate::instance::InstanceData;
 use crate::linker::Definition;
 use crate::store::StoreInnermost;
-use crate::{signatures::SignatureCollection, Engine, Extern};
+use crate::{
+    signatures::SignatureCollection, Engine, Extern, FuncType, GlobalType, MemoryType, TableType,
+};
 use anyhow::{bail, Context, Result};
 use wasmtime_environ::wasm::{
     EntityType, Global, InstanceTypeIndex, Memory, ModuleTypeIndex, SignatureIndex, Table,
@@ -28,7 +30,11 @@ impl MatchCx<'_> {
         {
             Ok(())
         } else {
-            bail!("global types incompatible")
+            bail!(
+                "global types incompatible: expected {} but found {}",
+                GlobalType::from_wasmtime_global(expected),
+                GlobalType::from_wasmtime_global(actual)
+            )
         }
     }
 
@@ -50,7 +56,11 @@ impl MatchCx<'_> {
         {
             Ok(())
         } else {
-            bail!("table types incompatible")
+            bail!(
+                "table types incompatible: expected {} but found {}",
+                TableType::from_wasmtime_table(expected),
+                TableType::from_wasmtime_table(actual)
+            )
         }
     }
 
@@ -60,6 +70,7 @@ impl MatchCx<'_> {
 
     fn memory_ty(&self, expected: &Memory, actual: &Memory) -> Result<()> {
         if expected.shared == actual.shared
+            && expected.memory64 == actual.memory64
             && expected.minimum <= actual.minimum
             && match expected.maximum {
                 Some(expected) => match actual.maximum {
@@ -71,7 +82,11 @@ impl MatchCx<'_> {
         {
             Ok(())
         } else {
-            bail!("memory types incompatible")
+            bail!(
+                "memory types incompatible: expected {} but found {}",
+                MemoryType::from_wasmtime_memory(expected),
+                MemoryType::from_wasmtime_memory(actual)
+            )
         }
     }
 
@@ -99,9 +114,16 @@ impl MatchCx<'_> {
             None => false,
         };
         if matches {
-            Ok(())
-        } else {
-            bail!("function types incompatible")
+            return Ok(());
+        }
+        let expected = FuncType::from_wasm_func_type(self.types.wasm_signatures[expected].clone());
+        match self.engine.signatures().lookup_type(actual) {
+            Some(actual) => bail!(
+                "function types incompatible: expected {} but found {}",
+                expected,
+                actual
+            ),
+            None => bail!("function types incompatible: expected {}", expected),
         }
     }
 
@@ -256,11 +278,16 @@ impl MatchCx<'_> {
             },
             EntityType::Function(expected) => match *actual_ty {
                 EntityType::Function(actual) => {
-                    if self.types.wasm_signatures[*expected] == actual_types.wasm_signatures[actual]
-                    {
+                    let expected = &self.types.wasm_signatures[*expected];
+                    let actual = &actual_types.wasm_signatures[actual];
+                    if expected == actual {
                         Ok(())
                     } else {
-                        bail!("function types incompatible")
+                        bail!(
+                            "function types incompatible: expected {} but found {}",
+                            FuncType::from_wasm_func_type(expected.clone()),
+                            FuncType::from_wasm_func_type(actual.clone())
+                        )
                     }
                 }
                 _ => bail!("expected function, but found {}", actual_desc),
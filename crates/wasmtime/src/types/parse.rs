@@ -0,0 +1,415 @@
+//! Parsing of value and function types from the text format, the inverse of
+//! their `Display` implementations.
+
+use crate::{FuncType, ValType};
+use std::str::FromStr;
+
+/// The error returned when parsing a [`ValType`] or [`FuncType`] from a
+/// string fails.
+///
+/// This records the byte offset in the string of the token which couldn't be
+/// parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message} at offset {offset}")]
+pub struct TypeParseError {
+    offset: usize,
+    message: String,
+}
+
+impl TypeParseError {
+    /// Returns the byte offset of the offending token, or the length of the
+    /// string if it ended too early.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns a description of the error, without its offset.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Parses a value type written as in the text format, such as `i32` or
+/// `externref`.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// let ty: ValType = "funcref".parse()?;
+/// assert_eq!(ty, ValType::FuncRef);
+/// assert_eq!(ty.to_string().parse::<ValType>()?, ty);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+impl FromStr for ValType {
+    type Err = TypeParseError;
+
+    fn from_str(s: &str) -> Result<ValType, TypeParseError> {
+        let mut parser = Parser::new(s);
+        let (offset, token) = parser.expect_atom("a value type")?;
+        let ty = val_type(offset, token)?;
+        parser.expect_end()?;
+        Ok(ty)
+    }
+}
+
+/// Parses a function type written as in the text format, such as
+/// `(func (param i32 i64) (result f32))`, which is what its `Display`
+/// implementation produces.
+///
+/// The surrounding `(func ...)` may be left out, so `(param i32) (result
+/// i32)` is accepted too. As in the text format, parameters and results may
+/// be split over several `param` and `result` lists, which must have all of
+/// the parameters before the results, and a `param` list with a single type
+/// may name it, as in `(param $x i32)`.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// let ty: FuncType = "(param i32 i64) (result i32)".parse()?;
+/// assert_eq!(ty, FuncType::new(vec![ValType::I32, ValType::I64], vec![ValType::I32]));
+/// assert_eq!(ty.to_string(), "(func (param i32 i64) (result i32))");
+///
+/// let err = "(func (param i32 i33))".parse::<FuncType>().unwrap_err();
+/// assert_eq!(err.offset(), 17);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+impl FromStr for FuncType {
+    type Err = TypeParseError;
+
+    fn from_str(s: &str) -> Result<FuncType, TypeParseError> {
+        let mut parser = Parser::new(s);
+        let wrapped = parser.peek_keyword_list("func");
+        if wrapped {
+            parser.expect_lparen()?;
+            parser.expect_atom("`func`")?;
+        }
+
+        let mut params = Vec::new();
+        let mut results = Vec::new();
+        let mut seen_result = false;
+        while let Some(Token::LParen) = parser.peek() {
+            parser.expect_lparen()?;
+            let (offset, keyword) = parser.expect_atom("`param` or `result`")?;
+            match keyword {
+                "param" if seen_result => {
+                    return Err(error(offset, "parameters must come before results"))
+                }
+                "param" => {
+                    if let Some(Token::Atom(name)) = parser.peek() {
+                        if name.starts_with('$') {
+                            parser.next();
+                            let (offset, token) = parser.expect_atom("a value type")?;
+                            params.push(val_type(offset, token)?);
+                            parser.expect_rparen()?;
+                            continue;
+                        }
+                    }
+                    parser.val_types(&mut params)?;
+                }
+                "result" => {
+                    seen_result = true;
+                    parser.val_types(&mut results)?;
+                }
+                other => {
+                    return Err(error(
+                        offset,
+                        format!("expected `param` or `result`, found `{}`", other),
+                    ))
+                }
+            }
+            parser.expect_rparen()?;
+        }
+
+        if wrapped {
+            parser.expect_rparen()?;
+        }
+        parser.expect_end()?;
+        Ok(FuncType::new(params, results))
+    }
+}
+
+fn error(offset: usize, message: impl Into<String>) -> TypeParseError {
+    TypeParseError {
+        offset,
+        message: message.into(),
+    }
+}
+
+fn val_type(offset: usize, token: &str) -> Result<ValType, TypeParseError> {
+    Ok(match token {
+        "i32" => ValType::I32,
+        "i64" => ValType::I64,
+        "f32" => ValType::F32,
+        "f64" => ValType::F64,
+        "v128" => ValType::V128,
+        "externref" => ValType::ExternRef,
+        "funcref" => ValType::FuncRef,
+        other => return Err(error(offset, format!("unknown value type `{}`", other))),
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token<'a> {
+    LParen,
+    RParen,
+    Atom(&'a str),
+}
+
+impl Token<'_> {
+    fn describe(&self) -> String {
+        match self {
+            Token::LParen => "`(`".to_string(),
+            Token::RParen => "`)`".to_string(),
+            Token::Atom(atom) => format!("`{}`", atom),
+        }
+    }
+}
+
+/// Splits a string into parentheses and the atoms between them, remembering
+/// the offset of each.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    pos: usize,
+    len: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Parser<'a> {
+        let mut tokens = Vec::new();
+        let mut chars = s.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '(' => tokens.push((offset, Token::LParen)),
+                ')' => tokens.push((offset, Token::RParen)),
+                c if c.is_whitespace() => {}
+                _ => {
+                    let mut end = offset + c.len_utf8();
+                    while let Some(&(i, c)) = chars.peek() {
+                        if c == '(' || c == ')' || c.is_whitespace() {
+                            break;
+                        }
+                        end = i + c.len_utf8();
+                        chars.next();
+                    }
+                    tokens.push((offset, Token::Atom(&s[offset..end])));
+                }
+            }
+        }
+        Parser {
+            tokens,
+            pos: 0,
+            len: s.len(),
+        }
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).map(|(_, token)| *token)
+    }
+
+    fn next(&mut self) -> Option<(usize, Token<'a>)> {
+        let token = self.tokens.get(self.pos).copied();
+        self.pos += 1;
+        token
+    }
+
+    /// Returns whether the next tokens are `(` followed by `keyword`.
+    fn peek_keyword_list(&self, keyword: &str) -> bool {
+        self.peek() == Some(Token::LParen)
+            && self.tokens.get(self.pos + 1).map(|(_, t)| *t) == Some(Token::Atom(keyword))
+    }
+
+    fn unexpected(
+        &self,
+        offset_and_token: Option<(usize, Token<'_>)>,
+        expected: &str,
+    ) -> TypeParseError {
+        match offset_and_token {
+            Some((offset, token)) => error(
+                offset,
+                format!("expected {}, found {}", expected, token.describe()),
+            ),
+            None => error(
+                self.len,
+                format!("unexpected end of input, expected {}", expected),
+            ),
+        }
+    }
+
+    fn expect_lparen(&mut self) -> Result<(), TypeParseError> {
+        match self.next() {
+            Some((_, Token::LParen)) => Ok(()),
+            other => Err(self.unexpected(other, "`(`")),
+        }
+    }
+
+    fn expect_rparen(&mut self) -> Result<(), TypeParseError> {
+        match self.next() {
+            Some((_, Token::RParen)) => Ok(()),
+            other => Err(self.unexpected(other, "`)`")),
+        }
+    }
+
+    fn expect_atom(&mut self, expected: &str) -> Result<(usize, &'a str), TypeParseError> {
+        match self.next() {
+            Some((offset, Token::Atom(atom))) => Ok((offset, atom)),
+            other => Err(self.unexpected(other, expected)),
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), TypeParseError> {
+        match self.next() {
+            None => Ok(()),
+            other => Err(self.unexpected(other, "the end of the type")),
+        }
+    }
+
+    /// Parses value types up to the next `)`, which is left in place.
+    fn val_types(&mut self, tys: &mut Vec<ValType>) -> Result<(), TypeParseError> {
+        while let Some((offset, Token::Atom(atom))) = self.tokens.get(self.pos).copied() {
+            self.pos += 1;
+            tys.push(val_type(offset, atom)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VAL_TYPES: [ValType; 7] = [
+        ValType::I32,
+        ValType::I64,
+        ValType::F32,
+        ValType::F64,
+        ValType::V128,
+        ValType::ExternRef,
+        ValType::FuncRef,
+    ];
+
+    #[test]
+    fn val_type_round_trip() {
+        for ty in VAL_TYPES.iter() {
+            assert_eq!(ty.to_string().parse::<ValType>().unwrap(), *ty);
+            assert_eq!(format!("  {}\n", ty).parse::<ValType>().unwrap(), *ty);
+        }
+    }
+
+    #[test]
+    fn func_type_round_trip() {
+        // A small xorshift generator, so that the types are random but the
+        // same on every run.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as usize
+        };
+        for _ in 0..1000 {
+            let params = (0..next(6))
+                .map(|_| VAL_TYPES[next(VAL_TYPES.len())].clone())
+                .collect::<Vec<_>>();
+            let results = (0..next(6))
+                .map(|_| VAL_TYPES[next(VAL_TYPES.len())].clone())
+                .collect::<Vec<_>>();
+            let ty = FuncType::new(params, results);
+            let text = ty.to_string();
+            let parsed = text.parse::<FuncType>().unwrap();
+            assert_eq!(parsed, ty, "{}", text);
+            assert_eq!(parsed.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn func_type_forms() {
+        let parse = |s: &str| s.parse::<FuncType>().unwrap();
+        let expected = FuncType::new(
+            vec![ValType::I32, ValType::I64],
+            vec![ValType::I32, ValType::F64],
+        );
+        assert_eq!(parse("(func (param i32 i64) (result i32 f64))"), expected);
+        assert_eq!(parse("(param i32 i64) (result i32 f64)"), expected);
+        assert_eq!(
+            parse("(func(param i32)(param $x i64)(result i32)(result)(result f64))"),
+            expected
+        );
+        assert_eq!(parse("(func)"), FuncType::new(None, None));
+        assert_eq!(parse(""), FuncType::new(None, None));
+        assert_eq!(parse("  (param) "), FuncType::new(None, None));
+    }
+
+    #[test]
+    fn errors() {
+        let err = |s: &str| {
+            let err = s.parse::<FuncType>().unwrap_err();
+            (err.offset(), err.to_string())
+        };
+        assert_eq!(
+            err("(param i32 i33)"),
+            (11, "unknown value type `i33` at offset 11".to_string())
+        );
+        assert_eq!(
+            err("(func (param i32)"),
+            (
+                17,
+                "unexpected end of input, expected `)` at offset 17".to_string()
+            )
+        );
+        assert_eq!(
+            err("param i32"),
+            (
+                0,
+                "expected the end of the type, found `param` at offset 0".to_string()
+            )
+        );
+        assert_eq!(
+            err("(params i32)"),
+            (
+                1,
+                "expected `param` or `result`, found `params` at offset 1".to_string()
+            )
+        );
+        assert_eq!(
+            err("(result i32) (param i32)"),
+            (
+                14,
+                "parameters must come before results at offset 14".to_string()
+            )
+        );
+        assert_eq!(
+            err("(func (param i32)) (result i32)"),
+            (
+                19,
+                "expected the end of the type, found `(` at offset 19".to_string()
+            )
+        );
+        assert_eq!(
+            err("(param $x i32 i64)"),
+            (14, "expected `)`, found `i64` at offset 14".to_string())
+        );
+        assert_eq!(
+            err("(param $x)"),
+            (
+                9,
+                "expected a value type, found `)` at offset 9".to_string()
+            )
+        );
+        assert_eq!(
+            err("(func (param i32) extra)"),
+            (18, "expected `)`, found `extra` at offset 18".to_string())
+        );
+
+        let err = "i32 i64".parse::<ValType>().unwrap_err();
+        assert_eq!(err.offset(), 4);
+        let err = "".parse::<ValType>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected end of input, expected a value type at offset 0"
+        );
+        let err = "anyref".parse::<ValType>().unwrap_err();
+        assert_eq!(err.message(), "unknown value type `anyref`");
+    }
+}
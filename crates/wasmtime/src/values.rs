@@ -1,7 +1,7 @@
 use crate::r#ref::ExternRef;
 use crate::store::StoreOpaque;
-use crate::{Func, ValType};
-use anyhow::{bail, Result};
+use crate::{Func, FuncType, ValType};
+use anyhow::Result;
 use std::ptr;
 use wasmtime_runtime::{self as runtime, VMExternRef};
 
@@ -185,7 +185,7 @@ impl Val {
         match (self, ty) {
             (Val::FuncRef(Some(f)), ValType::FuncRef) => {
                 if !f.comes_from_same_store(store) {
-                    bail!("cross-`Store` values are not supported in tables");
+                    return Err(ValError::CrossStore.into());
                 }
                 Ok(runtime::TableElement::FuncRef(
                     f.caller_checked_anyfunc(store).as_ptr(),
@@ -200,7 +200,12 @@ impl Val {
             (Val::ExternRef(None), ValType::ExternRef) => {
                 Ok(runtime::TableElement::ExternRef(None))
             }
-            _ => bail!("value does not match table element type"),
+            (val, ty) => Err(ValError::TypeMismatch {
+                site: ValSite::TableElement,
+                expected: ty,
+                found: val.ty(),
+            }
+            .into()),
         }
     }
 
@@ -270,18 +275,72 @@ impl From<Func> for Val {
     }
 }
 
-pub(crate) fn into_checked_anyfunc(
-    val: Val,
-    store: &mut StoreOpaque,
-) -> Result<*mut wasmtime_runtime::VMCallerCheckedAnyfunc> {
-    if !val.comes_from_same_store(store) {
-        bail!("cross-`Store` values are not supported");
+/// An error for a [`Val`] which can't be used where it was provided.
+///
+/// This is the error returned by [`Global::set`](crate::Global::set),
+/// [`Table::set`](crate::Table::set), [`Table::fill`](crate::Table::fill) and
+/// [`Table::grow`](crate::Table::grow) when their value is rejected, and by
+/// [`Func::call`] when an argument is. These methods return an
+/// [`anyhow::Error`], from which this can be recovered with
+/// [`downcast_ref`](anyhow::Error::downcast_ref).
+#[derive(Debug, Clone, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValError {
+    /// The value doesn't have the type expected where it was provided.
+    #[error("{}", type_mismatch_message(.site, .expected, .found))]
+    TypeMismatch {
+        /// Where the value was provided.
+        site: ValSite,
+        /// The type expected there.
+        expected: ValType,
+        /// The type of the value provided.
+        found: ValType,
+    },
+
+    /// The value was provided for an immutable global.
+    #[error("immutable global cannot be set")]
+    ImmutableGlobal,
+
+    /// The value is a reference to an item owned by a different store.
+    #[error("cross-`Store` values are not supported")]
+    CrossStore,
+}
+
+/// Where a [`Val`] with the wrong type was provided, as reported by
+/// [`ValError::TypeMismatch`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ValSite {
+    /// The value of a global.
+    Global,
+
+    /// An element of a table.
+    TableElement,
+
+    /// An argument of a function call.
+    Argument {
+        /// The index of the argument.
+        index: usize,
+        /// The type of the function called.
+        func: FuncType,
+    },
+}
+
+fn type_mismatch_message(site: &ValSite, expected: &ValType, found: &ValType) -> String {
+    match site {
+        ValSite::Global => format!(
+            "global of type `{}` cannot be set to a value of type `{}`",
+            expected, found
+        ),
+        ValSite::TableElement => format!(
+            "value of type `{}` does not match table element type `{}`",
+            found, expected
+        ),
+        ValSite::Argument { index, func } => format!(
+            "argument type mismatch: found {} but expected {} for argument {} of {}",
+            found, expected, index, func
+        ),
     }
-    Ok(match val {
-        Val::FuncRef(None) => ptr::null_mut(),
-        Val::FuncRef(Some(f)) => f.caller_checked_anyfunc(store).as_ptr(),
-        _ => bail!("val is not funcref"),
-    })
 }
 
 pub(crate) unsafe fn from_checked_anyfunc(
@@ -3,6 +3,12 @@ use wasmtime::*;
 
 /// Return an instance implementing the "spectest" interface used in the
 /// spec testsuite.
+///
+/// This defines the items of the spec interpreter's "spectest" module and
+/// nothing else. The "shared_memory" which the threads testsuite imports is
+/// only defined when the store's engine has the threads proposal enabled.
+/// Tests which need items beyond these can define them with
+/// [`WastContext::register_module_imports`](crate::WastContext::register_module_imports).
 pub fn link_spectest<T>(linker: &mut Linker<T>, store: &mut Store<T>) -> Result<()> {
     linker.func_wrap("spectest", "print", || {})?;
     linker.func_wrap("spectest", "print_i32", |val: i32| println!("{}: i32", val))?;
@@ -42,5 +48,11 @@ pub fn link_spectest<T>(linker: &mut Linker<T>, store: &mut Store<T>) -> Result<
     let memory = Memory::new(&mut *store, ty)?;
     linker.define("spectest", "memory", memory)?;
 
+    if store.engine().config_summary().wasm_features["threads"] {
+        let ty = MemoryType::new(Limits::new(1, Some(2))).with_shared(true);
+        let memory = Memory::new(&mut *store, ty)?;
+        linker.define("spectest", "shared_memory", memory)?;
+    }
+
     Ok(())
 }
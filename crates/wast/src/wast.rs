@@ -94,11 +94,39 @@ impl<T> WastContext<T> {
     }
 
     /// Register "spectest" which is used by the spec testsuite.
+    ///
+    /// See [`link_spectest`] for which items this defines.
     pub fn register_spectest(&mut self) -> Result<()> {
         link_spectest(&mut self.linker, &mut self.store)?;
         Ok(())
     }
 
+    /// Defines each of `imports` under the module name `name`, so that they
+    /// can be imported by the modules of the scripts run afterwards.
+    ///
+    /// This allows tests to use host functions and other entities beyond the
+    /// ones of the spec testsuite. Items which are already defined, including
+    /// the ones added by [`WastContext::register_spectest`], are replaced, so
+    /// calling this after it overrides parts of the "spectest" module.
+    ///
+    /// Each of the `imports` must belong to this context's store, which is
+    /// available through [`WastContext::store_mut`].
+    pub fn register_module_imports(
+        &mut self,
+        name: &str,
+        imports: &[(&str, Extern)],
+    ) -> Result<()> {
+        for (field, item) in imports {
+            self.linker.define(name, field, item.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the store that this context instantiates modules in.
+    pub fn store_mut(&mut self) -> &mut Store<T> {
+        &mut self.store
+    }
+
     /// Perform the action portion of a command.
     fn perform_execute(&mut self, exec: wast::WastExecute<'_>) -> Result<Outcome> {
         match exec {
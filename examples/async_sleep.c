@@ -0,0 +1,206 @@
+/*
+Example of defining an async host function with the C API and calling into
+WebAssembly which waits on it.
+
+You can compile and run this example on Linux with:
+
+   cargo build --release -p wasmtime-c-api
+   cc examples/async_sleep.c \
+       -I crates/c-api/include \
+       -I crates/c-api/wasm-c-api/include \
+       target/release/libwasmtime.a \
+       -lpthread -ldl -lm \
+       -o async_sleep
+   ./async_sleep
+
+Note that on Windows and macOS the command will be similar, but you'll need
+to tweak the `-lpthread` and such annotations as well as the name of the
+`libwasmtime.a` file on Windows.
+*/
+
+#include <assert.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <wasm.h>
+#include <wasmtime.h>
+
+// The call to `sleep` which is currently in progress, if any.
+typedef struct {
+  wasmtime_async_continuation_t *continuation;
+  int32_t ms;
+} pending_sleep;
+
+static pending_sleep pending = {NULL, 0};
+
+static void finish_sleep(pending_sleep *sleep) {
+  wasmtime_val_t result;
+  result.kind = WASMTIME_I32;
+  result.of.i32 = sleep->ms;
+  printf("> Woke up after %d ms\n", sleep->ms);
+  wasmtime_async_continuation_complete(sleep->continuation, &result, 1, NULL);
+}
+
+#ifdef _WIN32
+#include <windows.h>
+
+static void run_pending_sleep() {
+  Sleep(pending.ms);
+  finish_sleep(&pending);
+  pending.continuation = NULL;
+}
+#else
+#include <pthread.h>
+#include <time.h>
+
+// Sleeps on a separate thread and completes the call from there, as an event
+// loop or thread pool in a real embedding would.
+static void* sleeper(void *arg) {
+  pending_sleep *sleep = arg;
+  struct timespec sleep_dur;
+  sleep_dur.tv_sec = sleep->ms / 1000;
+  sleep_dur.tv_nsec = (sleep->ms % 1000) * 1000000;
+  nanosleep(&sleep_dur, NULL);
+  finish_sleep(sleep);
+  return NULL;
+}
+
+static void run_pending_sleep() {
+  pthread_t child;
+  int rc = pthread_create(&child, NULL, sleeper, &pending);
+  assert(rc == 0);
+  rc = pthread_join(child, NULL);
+  assert(rc == 0);
+  pending.continuation = NULL;
+}
+#endif
+
+static void exit_with_error(const char *message, wasmtime_error_t *error, wasm_trap_t *trap);
+
+// The async `sleep` import, which returns right away and is completed later
+// by `run_pending_sleep`.
+static void sleep_callback(
+    void *env,
+    wasmtime_caller_t *caller,
+    const wasmtime_val_t *args,
+    size_t nargs,
+    size_t nresults,
+    wasmtime_async_continuation_t *continuation
+) {
+  assert(nargs == 1 && nresults == 1);
+  assert(pending.continuation == NULL);
+  printf("> Sleeping for %d ms\n", args[0].of.i32);
+  pending.continuation = continuation;
+  pending.ms = args[0].of.i32;
+}
+
+// Polls `future` until it's finished, running any sleep it's waiting on.
+static void run_to_completion(wasmtime_call_future_t *future) {
+  while (!wasmtime_call_future_poll(future)) {
+    assert(pending.continuation != NULL);
+    run_pending_sleep();
+  }
+  wasmtime_call_future_delete(future);
+}
+
+int main() {
+  // Async host functions can only be used with an engine which has async
+  // support enabled.
+  printf("Initializing...\n");
+  wasm_config_t *config = wasm_config_new();
+  assert(config != NULL);
+  wasmtime_config_async_support_set(config, true);
+  wasm_engine_t *engine = wasm_engine_new_with_config(config);
+  assert(engine != NULL);
+  wasmtime_store_t *store = wasmtime_store_new(engine, NULL, NULL);
+  assert(store != NULL);
+  wasmtime_context_t *context = wasmtime_store_context(store);
+
+  // Read our input file, which in this case is a wasm text file.
+  FILE* file = fopen("examples/async_sleep.wat", "r");
+  assert(file != NULL);
+  fseek(file, 0L, SEEK_END);
+  size_t file_size = ftell(file);
+  fseek(file, 0L, SEEK_SET);
+  wasm_byte_vec_t wat;
+  wasm_byte_vec_new_uninitialized(&wat, file_size);
+  assert(fread(wat.data, file_size, 1, file) == 1);
+  fclose(file);
+
+  // Parse the wat into the binary wasm format
+  wasm_byte_vec_t wasm;
+  wasmtime_error_t *error = wasmtime_wat2wasm(wat.data, wat.size, &wasm);
+  if (error != NULL)
+    exit_with_error("failed to parse wat", error, NULL);
+  wasm_byte_vec_delete(&wat);
+
+  // Compile our module
+  printf("Compiling module...\n");
+  wasmtime_module_t *module = NULL;
+  error = wasmtime_module_new(engine, (uint8_t*) wasm.data, wasm.size, &module);
+  wasm_byte_vec_delete(&wasm);
+  if (error != NULL)
+    exit_with_error("failed to compile module", error, NULL);
+
+  // Define our async `sleep` import in a linker
+  printf("Defining async import...\n");
+  wasmtime_linker_t *linker = wasmtime_linker_new(engine);
+  wasm_functype_t *sleep_ty = wasm_functype_new_1_1(
+      wasm_valtype_new_i32(), wasm_valtype_new_i32());
+  error = wasmtime_linker_define_async_func(
+      linker, "host", 4, "sleep", 5, sleep_ty, sleep_callback, NULL, NULL);
+  wasm_functype_delete(sleep_ty);
+  if (error != NULL)
+    exit_with_error("failed to define async function", error, NULL);
+
+  // Instantiation must be async as well, although the module doesn't have a
+  // start function which would call `sleep`.
+  printf("Instantiating module...\n");
+  wasmtime_instance_t instance;
+  wasm_trap_t *trap = NULL;
+  run_to_completion(wasmtime_linker_instantiate_async(
+      linker, context, module, &instance, &trap, &error));
+  if (error != NULL || trap != NULL)
+    exit_with_error("failed to instantiate", error, trap);
+
+  // Lookup our `run` export function
+  printf("Extracting export...\n");
+  wasmtime_extern_t run;
+  bool ok = wasmtime_instance_export_get(context, &instance, "run", 3, &run);
+  assert(ok);
+  assert(run.kind == WASMTIME_EXTERN_FUNC);
+
+  // And call it, running each sleep whenever the call is waiting on one.
+  printf("Calling export...\n");
+  wasmtime_val_t result;
+  run_to_completion(wasmtime_func_call_async(
+      context, &run.of.func, NULL, 0, &result, 1, &trap, &error));
+  if (error != NULL || trap != NULL)
+    exit_with_error("failed to call function", error, trap);
+  assert(result.kind == WASMTIME_I32);
+  printf("> Slept for %d ms in total\n", result.of.i32);
+  assert(result.of.i32 == 300);
+
+  // Clean up after ourselves at this point
+  printf("All finished!\n");
+
+  wasmtime_linker_delete(linker);
+  wasmtime_module_delete(module);
+  wasmtime_store_delete(store);
+  wasm_engine_delete(engine);
+  return 0;
+}
+
+static void exit_with_error(const char *message, wasmtime_error_t *error, wasm_trap_t *trap) {
+  fprintf(stderr, "error: %s\n", message);
+  wasm_byte_vec_t error_message;
+  if (error != NULL) {
+    wasmtime_error_message(error, &error_message);
+    wasmtime_error_delete(error);
+  } else {
+    wasm_trap_message(trap, &error_message);
+    wasm_trap_delete(trap);
+  }
+  fprintf(stderr, "%.*s\n", (int) error_message.size, error_message.data);
+  wasm_byte_vec_delete(&error_message);
+  exit(1);
+}
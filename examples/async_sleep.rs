@@ -0,0 +1,52 @@
+//! Example of defining an async host function and calling into WebAssembly
+//! which waits on it. This is the Rust counterpart of `async_sleep.c`.
+
+// You can execute this example with `cargo run --example async_sleep`
+
+use anyhow::Result;
+use std::time::Duration;
+use wasmtime::*;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Async host functions can only be used with an engine which has async
+    // support enabled.
+    println!("Initializing...");
+    let mut config = Config::new();
+    config.async_support(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+
+    println!("Compiling module...");
+    let module = Module::from_file(&engine, "examples/async_sleep.wat")?;
+
+    // Define our async `sleep` import in a linker. The WebAssembly calling it
+    // is suspended until the future it returns completes, while the executor
+    // is free to run other tasks.
+    println!("Defining async import...");
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap1_async("host", "sleep", |_caller: Caller<'_, ()>, ms: i32| {
+        Box::new(async move {
+            println!("> Sleeping for {} ms", ms);
+            tokio::time::sleep(Duration::from_millis(ms as u64)).await;
+            println!("> Woke up after {} ms", ms);
+            ms
+        })
+    })?;
+
+    // Instantiation must be async as well, although the module doesn't have a
+    // start function which would call `sleep`.
+    println!("Instantiating module...");
+    let instance = linker.instantiate_async(&mut store, &module).await?;
+
+    println!("Extracting export...");
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+
+    println!("Calling export...");
+    let slept = run.call_async(&mut store, ()).await?;
+    println!("> Slept for {} ms in total", slept);
+    assert_eq!(slept, 300);
+
+    println!("All finished!");
+    Ok(())
+}
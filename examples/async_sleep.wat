@@ -0,0 +1,7 @@
+(module
+  (import "host" "sleep" (func $sleep (param i32) (result i32)))
+  (func (export "run") (result i32)
+    (i32.add
+      (call $sleep (i32.const 100))
+      (call $sleep (i32.const 200))))
+)
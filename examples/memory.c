@@ -192,6 +192,17 @@ int main(int argc, const char* argv[]) {
   assert(ok && item.kind == WASMTIME_EXTERN_FUNC);
   store_func = item.of.func;
 
+  // Check that lookups which don't find an export fail cleanly.
+  printf("Checking export lookups...\n");
+  char *name;
+  size_t name_len;
+  check(wasmtime_instance_export_nth(context, &instance, 0, &name, &name_len, &item));
+  check(name_len == strlen("memory") && memcmp(name, "memory", name_len) == 0);
+  check(item.kind == WASMTIME_EXTERN_MEMORY);
+  check(!wasmtime_instance_export_nth(context, &instance, 4, &name, &name_len, &item));
+  check(!wasmtime_instance_export_get(context, &instance, "missing", strlen("missing"), &item));
+  check(!wasmtime_instance_export_get(context, &instance, "\xff", 1, &item));
+
   // Check initial memory.
   printf("Checking memory...\n");
   check(wasmtime_memory_size(context, &memory) == 2);
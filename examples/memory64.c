@@ -0,0 +1,177 @@
+/*
+An example of how to describe 64-bit and shared memory types.
+
+You can compile and run this example on Linux with:
+
+   cargo build --release -p wasmtime-c-api
+   cc examples/memory64.c \
+       -I crates/c-api/include \
+       -I crates/c-api/wasm-c-api/include \
+       target/release/libwasmtime.a \
+       -lpthread -ldl -lm \
+       -o memory64
+   ./memory64
+
+Note that on Windows and macOS the command will be similar, but you'll need
+to tweak the `-lpthread` and such annotations.
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <wasm.h>
+#include <wasmtime.h>
+
+static void exit_with_error(const char *message, wasmtime_error_t *error, wasm_trap_t *trap);
+
+void check(bool success) {
+  if (!success) {
+    printf("> Error, expected success\n");
+    exit(1);
+  }
+}
+
+int main() {
+  // Initialize.
+  printf("Initializing...\n");
+  wasm_engine_t *engine = wasm_engine_new();
+  wasmtime_store_t *store = wasmtime_store_new(engine, NULL, NULL);
+  wasmtime_context_t *context = wasmtime_store_context(store);
+
+  // Construct memory types, and check that their accessors round-trip.
+  printf("Constructing memory types...\n");
+  wasm_memorytype_t *memory64 = wasmtime_memorytype_new(1, true, 0x10000, false, true);
+  check(memory64 != NULL);
+  check(wasmtime_memorytype_minimum(memory64) == 1);
+  uint64_t max = 0;
+  check(wasmtime_memorytype_maximum(memory64, &max));
+  check(max == 0x10000);
+  check(!wasmtime_memorytype_isshared(memory64));
+  check(wasmtime_memorytype_is64(memory64));
+
+  wasm_memorytype_t *shared = wasmtime_memorytype_new(2, false, 0, true, false);
+  check(shared != NULL);
+  check(wasmtime_memorytype_minimum(shared) == 2);
+  check(!wasmtime_memorytype_maximum(shared, &max));
+  check(wasmtime_memorytype_isshared(shared));
+  check(!wasmtime_memorytype_is64(shared));
+
+  // The standard constructor still creates 32-bit memory types which aren't
+  // shared.
+  wasm_limits_t limits = {3, wasm_limits_max_default};
+  wasm_memorytype_t *memory32 = wasm_memorytype_new(&limits);
+  check(wasmtime_memorytype_minimum(memory32) == 3);
+  check(!wasmtime_memorytype_maximum(memory32, &max));
+  check(!wasmtime_memorytype_isshared(memory32));
+  check(!wasmtime_memorytype_is64(memory32));
+  check(wasm_memorytype_limits(memory32)->min == 3);
+  wasm_memorytype_delete(memory32);
+
+  // Limits beyond what Wasmtime supports are rejected.
+  check(wasmtime_memorytype_new(1ull << 32, false, 0, false, true) == NULL);
+
+  // Shared memories can't be created at this time.
+  wasmtime_memory_t memory;
+  wasmtime_error_t *error = wasmtime_memory_new(context, shared, &memory);
+  check(error != NULL);
+  wasmtime_error_delete(error);
+  wasm_memorytype_delete(shared);
+
+  // Load our input file to parse it next
+  FILE* file = fopen("examples/memory64.wat", "r");
+  if (!file) {
+    printf("> Error loading file!\n");
+    return 1;
+  }
+  fseek(file, 0L, SEEK_END);
+  size_t file_size = ftell(file);
+  fseek(file, 0L, SEEK_SET);
+  wasm_byte_vec_t wat;
+  wasm_byte_vec_new_uninitialized(&wat, file_size);
+  if (fread(wat.data, file_size, 1, file) != 1) {
+    printf("> Error loading module!\n");
+    return 1;
+  }
+  fclose(file);
+
+  // Parse the wat into the binary wasm format
+  wasm_byte_vec_t binary;
+  error = wasmtime_wat2wasm(wat.data, wat.size, &binary);
+  if (error != NULL)
+    exit_with_error("failed to parse wat", error, NULL);
+  wasm_byte_vec_delete(&wat);
+
+  // Compile.
+  printf("Compiling module...\n");
+  wasmtime_module_t *module = NULL;
+  error = wasmtime_module_new(engine, (uint8_t*) binary.data, binary.size, &module);
+  if (error)
+    exit_with_error("failed to compile module", error, NULL);
+  wasm_byte_vec_delete(&binary);
+
+  // Reflect on the type of the memory the module imports.
+  printf("Inspecting imports...\n");
+  wasmtime_moduletype_t *module_type = wasmtime_module_type(module);
+  wasm_importtype_vec_t imports;
+  wasmtime_moduletype_imports(module_type, &imports);
+  check(imports.size == 1);
+  const wasm_memorytype_t *import_type =
+    wasm_externtype_as_memorytype_const(wasm_importtype_type(imports.data[0]));
+  check(import_type != NULL);
+  check(wasmtime_memorytype_minimum(import_type) == 1);
+  check(!wasmtime_memorytype_maximum(import_type, &max));
+  check(!wasmtime_memorytype_isshared(import_type));
+  check(!wasmtime_memorytype_is64(import_type));
+  wasm_importtype_vec_delete(&imports);
+  wasmtime_moduletype_delete(module_type);
+
+  // Create a 64-bit memory, whose type is reported as such...
+  printf("Creating a 64-bit memory...\n");
+  error = wasmtime_memory_new(context, memory64, &memory);
+  if (error != NULL)
+    exit_with_error("failed to create memory", error, NULL);
+  wasm_memorytype_delete(memory64);
+  wasm_memorytype_t *memory_type = wasmtime_memory_type(context, &memory);
+  check(wasmtime_memorytype_is64(memory_type));
+  check(wasmtime_memorytype_minimum(memory_type) == 1);
+  wasm_memorytype_delete(memory_type);
+
+  // ... and which can't satisfy the module's import of a 32-bit memory.
+  printf("Instantiating module...\n");
+  wasmtime_extern_t import;
+  import.kind = WASMTIME_EXTERN_MEMORY;
+  import.of.memory = memory;
+  wasmtime_instance_t instance;
+  wasm_trap_t *trap = NULL;
+  error = wasmtime_instance_new(context, module, &import, 1, &instance, &trap);
+  check(error != NULL && trap == NULL);
+  wasm_byte_vec_t error_message;
+  wasmtime_error_message(error, &error_message);
+  printf("> %.*s\n", (int) error_message.size, error_message.data);
+  wasm_byte_vec_delete(&error_message);
+  wasmtime_error_delete(error);
+
+  // Shut down.
+  printf("Shutting down...\n");
+  wasmtime_module_delete(module);
+  wasmtime_store_delete(store);
+  wasm_engine_delete(engine);
+
+  // All done.
+  printf("Done.\n");
+  return 0;
+}
+
+static void exit_with_error(const char *message, wasmtime_error_t *error, wasm_trap_t *trap) {
+  fprintf(stderr, "error: %s\n", message);
+  wasm_byte_vec_t error_message;
+  if (error != NULL) {
+    wasmtime_error_message(error, &error_message);
+    wasmtime_error_delete(error);
+  } else {
+    wasm_trap_message(trap, &error_message);
+    wasm_trap_delete(trap);
+  }
+  fprintf(stderr, "%.*s\n", (int) error_message.size, error_message.data);
+  wasm_byte_vec_delete(&error_message);
+  exit(1);
+}
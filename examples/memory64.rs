@@ -0,0 +1,51 @@
+//! An example of how to describe 64-bit and shared memory types.
+//!
+//! Here a 64-bit memory is created on the host and offered to a module which
+//! imports a 32-bit memory, which fails as the two types don't match.
+
+// You can execute this example with `cargo run --example memory64`
+
+use anyhow::Result;
+use wasmtime::*;
+
+fn main() -> Result<()> {
+    let mut store: Store<()> = Store::default();
+
+    println!("Constructing memory types...");
+    let memory64 = MemoryType::new64(Limits::new(1, Some(0x10000)));
+    assert!(memory64.is_64());
+    assert!(!memory64.is_shared());
+    assert_eq!(memory64.limits().min(), 1);
+    assert_eq!(memory64.limits().max(), Some(0x10000));
+
+    let shared = MemoryType::new(Limits::new(2, None)).with_shared(true);
+    assert!(shared.is_shared());
+    assert!(!shared.is_64());
+
+    // Shared memories can't be created at this time.
+    assert!(Memory::new(&mut store, shared).is_err());
+
+    println!("Compiling module...");
+    let module = Module::from_file(store.engine(), "examples/memory64.wat")?;
+
+    println!("Inspecting imports...");
+    let import = module.imports().next().unwrap();
+    let import_ty = import.ty().memory().cloned().unwrap();
+    assert!(!import_ty.is_64());
+    assert!(!import_ty.is_shared());
+    assert_eq!(import_ty.limits().min(), 1);
+    assert_eq!(import_ty.limits().max(), None);
+
+    // Create a 64-bit memory, whose type is reported as such...
+    println!("Creating a 64-bit memory...");
+    let memory = Memory::new(&mut store, memory64)?;
+    assert!(memory.ty(&store).is_64());
+
+    // ... and which can't satisfy the module's import of a 32-bit memory.
+    println!("Instantiating module...");
+    let err = Instance::new(&mut store, &module, &[memory.into()]).unwrap_err();
+    println!("> {:?}", err);
+
+    println!("Done.");
+    Ok(())
+}
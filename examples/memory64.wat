@@ -0,0 +1,5 @@
+(module
+  (import "host" "memory" (memory 1))
+  (func (export "size") (result i32)
+    (memory.size))
+)
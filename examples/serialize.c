@@ -1,17 +1,17 @@
 /*
-Example of instantiating of the WebAssembly module and invoking its exported
-function.
+Example of compiling a WebAssembly module ahead of time, serializing the
+compiled artifacts, and later deserializing and instantiating them.
 
 You can compile and run this example on Linux with:
 
    cargo build --release -p wasmtime-c-api
-   cc examples/hello.c \
+   cc examples/serialize.c \
        -I crates/c-api/include \
        -I crates/c-api/wasm-c-api/include \
        target/release/libwasmtime.a \
        -lpthread -ldl -lm \
-       -o hello
-   ./hello
+       -o serialize
+   ./serialize
 
 Note that on Windows and macOS the command will be similar, but you'll need
 to tweak the `-lpthread` and such annotations as well as the name of the
@@ -147,6 +147,38 @@ int deserialize(wasm_byte_vec_t* buffer) {
   return 0;
 }
 
+int deserialize_incompatible(const wasm_byte_vec_t* buffer) {
+  printf("Initializing...\n");
+  wasm_engine_t *engine = wasm_engine_new();
+  assert(engine != NULL);
+
+  // Serialized modules start with a header identifying the version of
+  // wasmtime which produced them and the settings they were compiled with.
+  // Artifacts which don't match the engine they're loaded into are rejected
+  // with an error rather than being run, which we simulate here by
+  // corrupting the header.
+  printf("Deserialize incompatible module...\n");
+  wasm_byte_vec_t corrupt;
+  wasm_byte_vec_copy(&corrupt, buffer);
+  corrupt.data[0] ^= 0xff;
+  wasmtime_module_t *module = NULL;
+  wasmtime_error_t *error = wasmtime_module_deserialize(engine, (uint8_t*) corrupt.data, corrupt.size, &module);
+  wasm_byte_vec_delete(&corrupt);
+  if (error == NULL) {
+    fprintf(stderr, "error: deserialized an incompatible module\n");
+    return 1;
+  }
+
+  wasm_byte_vec_t error_message;
+  wasmtime_error_message(error, &error_message);
+  wasmtime_error_delete(error);
+  printf("> %.*s\n", (int) error_message.size, error_message.data);
+  wasm_byte_vec_delete(&error_message);
+
+  wasm_engine_delete(engine);
+  return 0;
+}
+
 int main() {
   wasm_byte_vec_t buffer;
   if (serialize(&buffer)) {
@@ -155,6 +187,9 @@ int main() {
   if (deserialize(&buffer)) {
     return 1;
   }
+  if (deserialize_incompatible(&buffer)) {
+    return 1;
+  }
   wasm_byte_vec_delete(&buffer);
   return 0;
 }
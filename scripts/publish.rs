@@ -53,8 +53,9 @@ const CRATES_TO_PUBLISH: &[&str] = &[
     "wasmtime-obj",
     "wasmtime-cranelift",
     "wasmtime-lightbeam",
-    "wasmtime-jit",
     "wasmtime-cache",
+    "wasmtime-jit",
+    "wasmtime-bindgen",
     "wasmtime",
     // wasi-common/wiggle
     "wiggle",
@@ -27,7 +27,11 @@ lazy_static::lazy_static! {
             \n\
             Compiling for a specific platform (Linux) and CPU preset (Skylake):\n\
             \n  \
-            wasmtime compile --target x86_64-unknown-linux --cranelift-enable skylake foo.wasm\n",
+            wasmtime compile --target x86_64-unknown-linux --cranelift-enable skylake foo.wasm\n\
+            \n\
+            Compiling to a relocatable object file for linking into a native program:\n\
+            \n  \
+            wasmtime compile --emit-obj foo.o foo.wasm\n",
             crate::FLAG_EXPLANATIONS.as_str()
         )
     };
@@ -57,6 +61,16 @@ pub struct CompileCommand {
     #[structopt(short = "o", long, value_name = "OUTPUT", parse(from_os_str))]
     output: Option<PathBuf>,
 
+    /// Write a relocatable object file to this path instead of a compiled
+    /// module, along with a JSON description of it to <PATH>.json
+    #[structopt(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with = "output"
+    )]
+    emit_obj: Option<PathBuf>,
+
     /// The path of the WebAssembly to compile
     #[structopt(index = 1, value_name = "MODULE", parse(from_os_str))]
     module: PathBuf,
@@ -86,13 +100,25 @@ impl CompileCommand {
 
         let input = fs::read(&self.module).with_context(|| "failed to read input file")?;
 
+        if let Some(path) = self.emit_obj.take() {
+            let (object, metadata) = engine.precompile_module_object(&input)?;
+            fs::write(&path, object)?;
+            let mut metadata_path = path.clone().into_os_string();
+            metadata_path.push(".json");
+            fs::write(metadata_path, metadata)?;
+            return Ok(());
+        }
+
         let output = self.output.take().unwrap_or_else(|| {
             let mut output: PathBuf = self.module.file_name().unwrap().into();
             output.set_extension("cwasm");
             output
         });
 
-        fs::write(output, engine.precompile_module(&input)?)?;
+        let compiled = engine
+            .precompile_module(&input)
+            .map_err(|e| self.common.suggest_wasm_features(e))?;
+        fs::write(output, compiled)?;
 
         Ok(())
     }
@@ -136,6 +162,103 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_emit_obj_metadata_path() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let input_path = dir.path().join("empty.wat");
+        fs::write(&input_path, "(module)")?;
+        let obj_path = dir.path().join("out.json");
+
+        let command = CompileCommand::from_iter_safe(vec![
+            "compile",
+            "--disable-logging",
+            "--emit-obj",
+            obj_path.to_str().unwrap(),
+            input_path.to_str().unwrap(),
+        ])?;
+
+        command.execute()?;
+
+        // The metadata doesn't overwrite an object whose name ends in `.json`.
+        assert!(!fs::read(&obj_path)?.starts_with(b"{"));
+        let metadata = fs::read_to_string(dir.path().join("out.json.json"))?;
+        assert!(metadata.starts_with("{"));
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_emit_obj_links_with_c() -> Result<()> {
+        const HARNESS: &str = r#"
+            #include <stdint.h>
+            #include <stdio.h>
+
+            struct interrupts {
+                uintptr_t stack_limit;
+                int64_t fuel_consumed;
+            };
+
+            extern int32_t wasm_export_add(void *vmctx, void *caller_vmctx, int32_t a, int32_t b);
+
+            int main() {
+                struct interrupts interrupts = {0, 0};
+                void *vmctx[64] = {&interrupts};
+                printf("%d\n", wasm_export_add(vmctx, vmctx, 2, 3));
+                return 0;
+            }
+        "#;
+
+        let dir = tempfile::TempDir::new()?;
+        let input_path = dir.path().join("add.wat");
+        fs::write(
+            &input_path,
+            "(module (func (export \"add\") (param i32 i32) (result i32) \
+             local.get 0 local.get 1 i32.add))",
+        )?;
+        let obj_path = dir.path().join("add.o");
+
+        let command = CompileCommand::from_iter_safe(vec![
+            "compile",
+            "--disable-logging",
+            "--emit-obj",
+            obj_path.to_str().unwrap(),
+            input_path.to_str().unwrap(),
+        ])?;
+
+        command.execute()?;
+
+        // The harness assumes the interrupts pointer is the first field of
+        // the vmctx.
+        let metadata = fs::read_to_string(dir.path().join("add.o.json"))?;
+        assert!(metadata.contains("\"symbol\": \"wasm_export_add\""));
+        assert!(metadata.contains("\"interrupts\": 0,"));
+
+        let harness_path = dir.path().join("main.c");
+        fs::write(&harness_path, HARNESS)?;
+        let exe_path = dir.path().join("main");
+        let status = cc::Build::new()
+            .opt_level(0)
+            .cargo_metadata(false)
+            .target(env!("TARGET"))
+            .host(env!("TARGET"))
+            .warnings(false)
+            .get_compiler()
+            .to_command()
+            .arg(&harness_path)
+            .arg(&obj_path)
+            .arg("-o")
+            .arg(&exe_path)
+            .status()?;
+        assert!(status.success());
+
+        let output = std::process::Command::new(&exe_path).output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "5\n");
+
+        Ok(())
+    }
+
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_x64_flags_compile() -> Result<()> {
@@ -3,10 +3,11 @@
 use crate::{CommonOptions, WasiModules};
 use anyhow::{anyhow, bail, Context as _, Result};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use std::{
     ffi::{OsStr, OsString},
-    path::{Component, PathBuf},
+    fs,
+    path::{Component, Path, PathBuf},
     process,
 };
 use structopt::{clap::AppSettings, StructOpt};
@@ -87,6 +88,16 @@ pub struct RunCommand {
     #[structopt(long = "env", number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
     vars: Vec<(String, String)>,
 
+    /// Pass all of wasmtime's own environment variables to the program
+    #[structopt(long = "inherit-env")]
+    inherit_env: bool,
+
+    /// Pass wasmtime's own environment variables whose names match PATTERN
+    /// to the program, even without `--inherit-env`; PATTERN is a variable
+    /// name, optionally ending in `*` to match any name with that prefix
+    #[structopt(long = "env-filter", number_of_values = 1, value_name = "PATTERN")]
+    env_filters: Vec<String>,
+
     /// The name of the function to run
     #[structopt(long, value_name = "FUNCTION")]
     invoke: Option<String>,
@@ -121,6 +132,21 @@ pub struct RunCommand {
     )]
     wasm_timeout: Option<Duration>,
 
+    /// Keep running, and run the module again whenever it changes, until
+    /// interrupted with Ctrl-C
+    #[structopt(long = "watch")]
+    watch: bool,
+
+    /// With `--watch`, also run the module again whenever anything in the
+    /// given directory changes
+    #[structopt(
+        long = "watch-dir",
+        number_of_values = 1,
+        value_name = "DIRECTORY",
+        requires = "watch"
+    )]
+    watch_dirs: Vec<PathBuf>,
+
     // NOTE: this must come last for trailing varargs
     /// The arguments to pass to the module
     #[structopt(value_name = "ARGS")]
@@ -137,13 +163,36 @@ impl RunCommand {
             config.interruptable(true);
         }
         let engine = Engine::new(&config)?;
-        let mut store = Store::new(&engine, Host::default());
+
+        if self.watch {
+            return self.watch(&engine);
+        }
+
+        if let Err(e) = self.run(&engine) {
+            // If the program exited because of a trap, including exiting
+            // through WASI with a status, exit with the corresponding status.
+            if let Some((status, report)) = e.downcast_ref::<Trap>().and_then(trap_exit_status) {
+                if report {
+                    eprintln!("Error: {:?}", e);
+                }
+                process::exit(status);
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Runs the module once, in a fresh `Store` with its own WASI context.
+    fn run(&self, engine: &Engine) -> Result<()> {
+        let mut store = Store::new(engine, Host::default());
 
         // Make wasi available by default.
         let preopen_dirs = self.compute_preopen_dirs()?;
         let argv = self.compute_argv();
+        let vars = self.compute_env();
 
-        let mut linker = Linker::new(&engine);
+        let mut linker = Linker::new(engine);
         linker.allow_unknown_exports(self.allow_unknown_exports);
 
         populate_with_wasi(
@@ -151,14 +200,15 @@ impl RunCommand {
             &mut linker,
             preopen_dirs,
             &argv,
-            &self.vars,
+            &vars,
             &self.common.wasi_modules.unwrap_or(WasiModules::default()),
         )?;
 
         // Load the preload wasm modules.
         for (name, path) in self.preloads.iter() {
             // Read the wasm module binary either as `*.wat` or a raw binary
-            let module = Module::from_file(&engine, path)?;
+            let module = Module::from_file(engine, path)
+                .map_err(|e| self.common.suggest_wasm_features(e))?;
 
             // Add the module's functions to the linker.
             linker.module(&mut store, name, &module).context(format!(
@@ -169,44 +219,89 @@ impl RunCommand {
         }
 
         // Load the main wasm module.
-        match self
-            .load_main_module(&mut store, &mut linker)
+        self.load_main_module(&mut store, &mut linker)
             .with_context(|| format!("failed to run main module `{}`", self.module.display()))
-        {
-            Ok(()) => (),
-            Err(e) => {
-                // If the program exited because of a non-zero exit status, print
-                // a message and exit.
-                if let Some(trap) = e.downcast_ref::<Trap>() {
-                    // Print the error message in the usual way.
-                    if let Some(status) = trap.i32_exit_status() {
-                        // On Windows, exit status 3 indicates an abort (see below),
-                        // so return 1 indicating a non-zero status to avoid ambiguity.
-                        if cfg!(windows) && status >= 3 {
-                            process::exit(1);
-                        }
-                        process::exit(status);
-                    }
-
-                    eprintln!("Error: {:?}", e);
+    }
 
-                    // If the program exited because of a trap, return an error code
-                    // to the outside environment indicating a more severe problem
-                    // than a simple failure.
-                    if cfg!(unix) {
-                        // On Unix, return the error code of an abort.
-                        process::exit(128 + libc::SIGABRT);
-                    } else if cfg!(windows) {
-                        // On Windows, return 3.
-                        // https://docs.microsoft.com/en-us/cpp/c-runtime-library/reference/abort?view=vs-2019
-                        process::exit(3);
+    /// Runs the module, and then runs it again each time it, or anything in
+    /// a `--watch-dir`, changes. Failures, including failing to compile the
+    /// module, are reported without stopping; this only returns early if it
+    /// can't start watching.
+    fn watch(&self, engine: &Engine) -> Result<()> {
+        // Watching only ends with Ctrl-C, at which point exit the way a
+        // process interrupted by it does, whatever the most recent run's
+        // status was.
+        ctrlc::set_handler(|| process::exit(INTERRUPTED_STATUS))
+            .context("failed to install a Ctrl-C handler")?;
+
+        let mut snapshot = None;
+        let mut run = 0;
+        loop {
+            run += 1;
+            snapshot = Some(self.wait_for_changes(snapshot.as_ref()));
+            eprintln!(
+                "[wasmtime] run #{} of `{}` {}",
+                run,
+                self.module.display(),
+                "-".repeat(20)
+            );
+            let status = match self.run(engine) {
+                Ok(()) => 0,
+                Err(e) => {
+                    let (status, report) = e
+                        .downcast_ref::<Trap>()
+                        .and_then(trap_exit_status)
+                        .unwrap_or((1, true));
+                    if report {
+                        eprintln!("Error: {:?}", e);
                     }
+                    status
                 }
-                return Err(e);
+            };
+            eprintln!(
+                "[wasmtime] run #{} exited with status {}, waiting for changes",
+                run, status
+            );
+        }
+    }
+
+    /// Returns the state of the watched files once they're worth running the
+    /// module for: the module exists, and if there was a `last` run they've
+    /// changed since then and stayed unchanged for `WATCH_DEBOUNCE`.
+    ///
+    /// Waiting for changes to settle means that a build writing several files,
+    /// or replacing the module by renaming a new one over it, leads to a
+    /// single run once it's done.
+    fn wait_for_changes(&self, last: Option<&WatchSnapshot>) -> WatchSnapshot {
+        let mut current = self.watch_snapshot();
+        let mut changed_at = Instant::now();
+        let mut settled = last.is_none();
+        loop {
+            if settled && current.module.is_some() && Some(&current) != last {
+                return current;
+            }
+            thread::sleep(WATCH_POLL_INTERVAL);
+            let next = self.watch_snapshot();
+            if next != current {
+                current = next;
+                changed_at = Instant::now();
+                settled = false;
+            } else if changed_at.elapsed() >= WATCH_DEBOUNCE {
+                settled = true;
             }
         }
+    }
 
-        Ok(())
+    fn watch_snapshot(&self) -> WatchSnapshot {
+        let mut files = Vec::new();
+        for dir in self.watch_dirs.iter() {
+            collect_file_stamps(dir, &mut files);
+        }
+        files.sort();
+        WatchSnapshot {
+            module: file_stamp(&self.module),
+            files,
+        }
     }
 
     fn compute_preopen_dirs(&self) -> Result<Vec<(String, Dir)>> {
@@ -254,6 +349,48 @@ impl RunCommand {
         result
     }
 
+    /// Computes the environment of the program: the host variables selected
+    /// by `--inherit-env` and `--env-filter`, followed by everything given
+    /// with `--env`, which takes precedence over inherited variables of the
+    /// same name.
+    fn compute_env(&self) -> Vec<(String, String)> {
+        let mut result = Vec::new();
+
+        if self.inherit_env || !self.env_filters.is_empty() {
+            for (key, value) in std::env::vars_os() {
+                let name = key.to_string_lossy().into_owned();
+                let overridden = self.vars.iter().any(|(k, _)| *k == name);
+                if !self.env_filter_selects(&name) || overridden {
+                    continue;
+                }
+                match (key.into_string(), value.into_string()) {
+                    (Ok(key), Ok(value)) => result.push((key, value)),
+                    _ => eprintln!(
+                        "warning: not passing environment variable `{}` to the program \
+                         because it isn't valid UTF-8",
+                        name
+                    ),
+                }
+            }
+        }
+
+        result.extend(self.vars.iter().cloned());
+        result
+    }
+
+    /// Returns whether the host environment variable `name` is selected by
+    /// `--env-filter`, which selects everything if no filters were given.
+    fn env_filter_selects(&self, name: &str) -> bool {
+        self.env_filters.is_empty()
+            || self
+                .env_filters
+                .iter()
+                .any(|filter| match filter.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => name == filter,
+                })
+    }
+
     fn load_main_module(&self, store: &mut Store<Host>, linker: &mut Linker<Host>) -> Result<()> {
         if let Some(timeout) = self.wasm_timeout {
             let handle = store.interrupt_handle()?;
@@ -265,7 +402,8 @@ impl RunCommand {
 
         // Read the wasm module binary either as `*.wat` or a raw binary.
         // Use "" as a default module name.
-        let module = Module::from_file(linker.engine(), &self.module)?;
+        let module = Module::from_file(linker.engine(), &self.module)
+            .map_err(|e| self.common.suggest_wasm_features(e))?;
         linker
             .module(&mut *store, "", &module)
             .context(format!("failed to instantiate {:?}", self.module))?;
@@ -361,6 +499,91 @@ impl RunCommand {
     }
 }
 
+/// Returns the status to exit with when running a module failed with `trap`,
+/// and whether the trap should be reported, or `None` if the trap should be
+/// returned as an error like any other.
+fn trap_exit_status(trap: &Trap) -> Option<(i32, bool)> {
+    // A WASI exit with a status isn't an error worth reporting.
+    if let Some(status) = trap.i32_exit_status() {
+        // On Windows, exit status 3 indicates an abort (see below),
+        // so return 1 indicating a non-zero status to avoid ambiguity.
+        if cfg!(windows) && status >= 3 {
+            return Some((1, false));
+        }
+        return Some((status, false));
+    }
+
+    // If the program exited because of a trap, return an error code
+    // to the outside environment indicating a more severe problem
+    // than a simple failure.
+    if cfg!(unix) {
+        // On Unix, return the error code of an abort.
+        Some((128 + libc::SIGABRT, true))
+    } else if cfg!(windows) {
+        // On Windows, return 3.
+        // https://docs.microsoft.com/en-us/cpp/c-runtime-library/reference/abort?view=vs-2019
+        Some((3, true))
+    } else {
+        None
+    }
+}
+
+/// How often `--watch` checks the watched files for changes.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the watched files have to stay unchanged after changing before
+/// `--watch` runs the module again.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The status `--watch` exits with once interrupted by Ctrl-C, which is the
+/// one shells report for a process killed by it.
+#[cfg(unix)]
+const INTERRUPTED_STATUS: i32 = 128 + libc::SIGINT;
+#[cfg(windows)]
+const INTERRUPTED_STATUS: i32 = 0xC000013A_u32 as i32; // STATUS_CONTROL_C_EXIT
+
+/// When a file was last modified, and its size.
+type FileStamp = (SystemTime, u64);
+
+/// What the files watched by `--watch` looked like at some point. Comparing
+/// modification times and sizes, rather than relying on notifications from
+/// the OS, keeps working when the module is replaced by renaming a new file
+/// over it, or is briefly missing in between.
+#[derive(PartialEq)]
+struct WatchSnapshot {
+    /// The module being run, if it exists.
+    module: Option<FileStamp>,
+    /// Every file found under a `--watch-dir`, sorted by path.
+    files: Vec<(PathBuf, FileStamp)>,
+}
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Adds the stamps of all of the files under `dir` to `files`, skipping any
+/// which can't be read since they may be in the middle of being replaced.
+///
+/// Symlinks are stamped with what they point to but never descended into,
+/// since symlinks to directories may form a cycle.
+fn collect_file_stamps(dir: &Path, files: &mut Vec<(PathBuf, FileStamp)>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        // Unlike `Path::is_dir`, the entry's file type describes the symlink
+        // itself rather than its target.
+        if entry.file_type().map_or(false, |ty| ty.is_dir()) {
+            collect_file_stamps(&path, files);
+        } else if let Some(stamp) = file_stamp(&path) {
+            files.push((path, stamp));
+        }
+    }
+}
+
 #[derive(Default)]
 struct Host {
     wasi: Option<wasmtime_wasi::WasiCtx>,
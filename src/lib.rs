@@ -23,24 +23,80 @@
     )
 )]
 
-const SUPPORTED_WASM_FEATURES: &[(&str, &str)] = &[
+/// A WebAssembly feature which can be enabled or disabled with
+/// `--wasm-features`.
+struct WasmFeature {
+    name: &'static str,
+    description: &'static str,
+    /// The field of `wasmparser::WasmFeatures` this feature is parsed into.
+    field: fn(&mut wasmparser::WasmFeatures) -> &mut bool,
+    /// The `Config` method which enables or disables this feature.
+    configure: fn(&mut Config, bool) -> &mut Config,
+    /// Whether a module's `FeatureUsage` says it uses this feature.
+    used: fn(&FeatureUsage) -> bool,
+}
+
+/// Every feature accepted by `--wasm-features`.
+///
+/// This is the only list of features, from which both the accepted values of
+/// `--wasm-features` and the `Config` it produces are derived, so new `Config`
+/// features only need to be added here.
+const SUPPORTED_WASM_FEATURES: &[WasmFeature] = &[
+    WasmFeature {
+        name: "bulk-memory",
+        description: "enables support for bulk memory instructions",
+        field: |f| &mut f.bulk_memory,
+        configure: Config::wasm_bulk_memory,
+        used: |u| u.bulk_memory,
+    },
+    WasmFeature {
+        name: "module-linking",
+        description: "enables support for the module-linking proposal",
+        field: |f| &mut f.module_linking,
+        configure: Config::wasm_module_linking,
+        used: |u| u.module_linking,
+    },
+    WasmFeature {
+        name: "multi-memory",
+        description: "enables support for the multi-memory proposal",
+        field: |f| &mut f.multi_memory,
+        configure: Config::wasm_multi_memory,
+        used: |u| u.multi_memory,
+    },
+    WasmFeature {
+        name: "multi-value",
+        description: "enables support for multi-value functions",
+        field: |f| &mut f.multi_value,
+        configure: Config::wasm_multi_value,
+        used: |u| u.multi_value,
+    },
+    WasmFeature {
+        name: "reference-types",
+        description: "enables support for reference types",
+        field: |f| &mut f.reference_types,
+        configure: Config::wasm_reference_types,
+        used: |u| u.reference_types,
+    },
+    WasmFeature {
+        name: "simd",
+        description: "enables support for proposed SIMD instructions",
+        field: |f| &mut f.simd,
+        configure: Config::wasm_simd,
+        used: |u| u.simd,
+    },
+    WasmFeature {
+        name: "threads",
+        description: "enables support for WebAssembly threads",
+        field: |f| &mut f.threads,
+        configure: Config::wasm_threads,
+        used: |u| u.threads,
+    },
+];
+
+/// Values of `--wasm-features` which apply to every feature at once.
+const WASM_FEATURE_GROUPS: &[(&str, &str)] = &[
     ("all", "enables all supported WebAssembly features"),
-    (
-        "bulk-memory",
-        "enables support for bulk memory instructions",
-    ),
-    (
-        "module-linking",
-        "enables support for the module-linking proposal",
-    ),
-    (
-        "multi-memory",
-        "enables support for the multi-memory proposal",
-    ),
-    ("multi-value", "enables support for multi-value functions"),
-    ("reference-types", "enables support for reference types"),
-    ("simd", "enables support for proposed SIMD instructions"),
-    ("threads", "enables support for WebAssembly threads"),
+    ("default", "resets all features to their default"),
 ];
 
 const SUPPORTED_WASI_MODULES: &[(&str, &str)] = &[
@@ -71,11 +127,18 @@ lazy_static::lazy_static! {
         // Explain --wasm-features.
         writeln!(&mut s, "Supported values for `--wasm-features`:").unwrap();
         writeln!(&mut s).unwrap();
-        let max = SUPPORTED_WASM_FEATURES.iter().max_by_key(|(name, _)| name.len()).unwrap();
-        for (name, desc) in SUPPORTED_WASM_FEATURES.iter() {
+        let features = WASM_FEATURE_GROUPS
+            .iter()
+            .copied()
+            .chain(SUPPORTED_WASM_FEATURES.iter().map(|f| (f.name, f.description)))
+            .collect::<Vec<_>>();
+        let max = features.iter().max_by_key(|(name, _)| name.len()).unwrap();
+        for (name, desc) in features.iter() {
             writeln!(&mut s, "{:width$} {}", name, desc, width = max.0.len() + 2).unwrap();
         }
         writeln!(&mut s).unwrap();
+        writeln!(&mut s, "WebAssembly features are applied in the order they're listed.").unwrap();
+        writeln!(&mut s).unwrap();
 
         // Explain --wasi-modules.
         writeln!(&mut s, "Supported values for `--wasi-modules`:").unwrap();
@@ -96,11 +159,10 @@ pub mod commands;
 mod obj;
 
 use anyhow::{bail, Result};
-use std::collections::HashMap;
 use std::path::PathBuf;
 use structopt::StructOpt;
 use target_lexicon::Triple;
-use wasmtime::{Config, ProfilingStrategy, Strategy};
+use wasmtime::{Config, FeatureUsage, InvalidModule, ProfilingStrategy, Strategy};
 
 pub use obj::compile_to_obj;
 
@@ -279,6 +341,11 @@ struct CommonOptions {
     /// Enable Cranelift's internal NaN canonicalization
     #[structopt(long)]
     enable_cranelift_nan_canonicalization: bool,
+
+    /// Print the configuration, including which WebAssembly features are
+    /// enabled, once all other options have been applied
+    #[structopt(long)]
+    print_config: bool,
 }
 
 impl CommonOptions {
@@ -347,28 +414,64 @@ impl CommonOptions {
             config.dynamic_memory_guard_size(size);
         }
 
+        if self.print_config {
+            eprintln!("{:#?}", config);
+        }
+
         Ok(config)
     }
 
+    /// Returns the WebAssembly features enabled by `--wasm-features` and the
+    /// deprecated `--enable-*` flags.
+    fn wasm_features(&self) -> wasmparser::WasmFeatures {
+        let mut features = self.wasm_features.unwrap_or_default();
+        if self.enable_all {
+            for feature in SUPPORTED_WASM_FEATURES {
+                *(feature.field)(&mut features) = true;
+            }
+        }
+        features.simd |= self.enable_simd;
+        features.bulk_memory |= self.enable_bulk_memory;
+        features.reference_types |= self.enable_reference_types;
+        features.multi_value |= self.enable_multi_value;
+        features.threads |= self.enable_threads;
+        features.multi_memory |= self.enable_multi_memory;
+        features.module_linking |= self.enable_module_linking;
+        features
+    }
+
     fn enable_wasm_features(&self, config: &mut Config) {
-        let features = self.wasm_features.unwrap_or_default();
+        let mut features = self.wasm_features();
+        for feature in SUPPORTED_WASM_FEATURES {
+            (feature.configure)(config, *(feature.field)(&mut features));
+        }
+    }
 
-        config
-            .wasm_simd(features.simd || self.enable_simd || self.enable_all)
-            .wasm_bulk_memory(features.bulk_memory || self.enable_bulk_memory || self.enable_all)
-            .wasm_reference_types(
-                features.reference_types || self.enable_reference_types || self.enable_all,
-            )
-            .wasm_multi_value(features.multi_value || self.enable_multi_value || self.enable_all)
-            .wasm_threads(features.threads || self.enable_threads || self.enable_all)
-            .wasm_multi_memory(features.multi_memory || self.enable_multi_memory || self.enable_all)
-            .wasm_module_linking(
-                features.module_linking || self.enable_module_linking || self.enable_all,
-            );
+    /// Adds a suggestion of what to pass to `--wasm-features` to `err` if it's
+    /// from compiling a module which uses WebAssembly features that aren't
+    /// enabled.
+    fn suggest_wasm_features(&self, err: anyhow::Error) -> anyhow::Error {
+        let usage = match err.downcast_ref::<InvalidModule>() {
+            Some(invalid) => invalid.feature_usage(),
+            None => return err,
+        };
+        let mut enabled = self.wasm_features();
+        let disabled = SUPPORTED_WASM_FEATURES
+            .iter()
+            .filter(|f| (f.used)(&usage) && !*(f.field)(&mut enabled))
+            .map(|f| f.name)
+            .collect::<Vec<_>>();
+        if disabled.is_empty() {
+            return err;
+        }
+        err.context(format!(
+            "module uses disabled WebAssembly features; try `--wasm-features={}`",
+            disabled.join(",")
+        ))
     }
 
     fn opt_level(&self) -> wasmtime::OptLevel {
-        match (self.optimize, self.opt_level.clone()) {
+        match (self.optimize, self.opt_level) {
             (true, _) => wasmtime::OptLevel::Speed,
             (false, other) => other.unwrap_or(wasmtime::OptLevel::Speed),
         }
@@ -389,56 +492,46 @@ fn parse_opt_level(opt_level: &str) -> Result<wasmtime::OptLevel> {
 }
 
 fn parse_wasm_features(features: &str) -> Result<wasmparser::WasmFeatures> {
-    let features = features.trim();
-
-    let mut all = None;
-    let mut values: HashMap<_, _> = SUPPORTED_WASM_FEATURES
-        .iter()
-        .map(|(name, _)| (name.to_string(), None))
-        .collect();
-
-    if features == "all" {
-        all = Some(true);
-    } else if features == "-all" {
-        all = Some(false);
-    } else {
-        for feature in features.split(',') {
-            let feature = feature.trim();
+    let mut result = wasmparser::WasmFeatures::default();
 
-            if feature.is_empty() {
-                continue;
-            }
+    for feature in features.split(',') {
+        let feature = feature.trim();
 
-            let (feature, value) = if feature.starts_with('-') {
-                (&feature[1..], false)
-            } else {
-                (feature, true)
-            };
+        if feature.is_empty() {
+            continue;
+        }
 
-            if feature == "all" {
-                bail!("'all' cannot be specified with other WebAssembly features");
-            }
+        let (feature, value) = if feature.starts_with('-') {
+            (&feature[1..], false)
+        } else {
+            (feature, true)
+        };
 
-            match values.get_mut(feature) {
-                Some(v) => *v = Some(value),
-                None => bail!("unsupported WebAssembly feature '{}'", feature),
+        match feature {
+            "all" => {
+                for feature in SUPPORTED_WASM_FEATURES {
+                    *(feature.field)(&mut result) = value;
+                }
             }
+            "default" if value => result = wasmparser::WasmFeatures::default(),
+            "default" => bail!("'default' cannot be disabled"),
+            _ => match SUPPORTED_WASM_FEATURES.iter().find(|f| f.name == feature) {
+                Some(f) => *(f.field)(&mut result) = value,
+                None => bail!(
+                    "unsupported WebAssembly feature '{}', expected one of: {}",
+                    feature,
+                    WASM_FEATURE_GROUPS
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .chain(SUPPORTED_WASM_FEATURES.iter().map(|f| f.name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
         }
     }
 
-    Ok(wasmparser::WasmFeatures {
-        reference_types: all.unwrap_or(values["reference-types"].unwrap_or(true)),
-        multi_value: all.unwrap_or(values["multi-value"].unwrap_or(true)),
-        bulk_memory: all.unwrap_or(values["bulk-memory"].unwrap_or(true)),
-        module_linking: all.unwrap_or(values["module-linking"].unwrap_or(false)),
-        simd: all.unwrap_or(values["simd"].unwrap_or(false)),
-        threads: all.unwrap_or(values["threads"].unwrap_or(false)),
-        tail_call: false,
-        deterministic_only: false,
-        multi_memory: all.unwrap_or(values["multi-memory"].unwrap_or(false)),
-        exceptions: false,
-        memory64: false,
-    })
+    Ok(result)
 }
 
 fn parse_wasi_modules(modules: &str) -> Result<WasiModules> {
@@ -676,6 +769,59 @@ mod test {
     feature_test!(test_threads_feature, threads, "threads");
     feature_test!(test_multi_memory_feature, multi_memory, "multi-memory");
 
+    #[test]
+    fn test_features_applied_in_order() -> Result<()> {
+        let options =
+            CommonOptions::from_iter_safe(vec!["foo", "--wasm-features=all,-threads,-simd"])?;
+        let features = options.wasm_features.unwrap();
+        assert!(features.module_linking);
+        assert!(features.multi_memory);
+        assert!(!features.threads);
+        assert!(!features.simd);
+
+        let options = CommonOptions::from_iter_safe(vec![
+            "foo",
+            "--wasm-features=-all,simd,default,threads",
+        ])?;
+        let features = options.wasm_features.unwrap();
+        assert!(features.reference_types);
+        assert!(features.multi_value);
+        assert!(features.bulk_memory);
+        assert!(!features.simd);
+        assert!(features.threads);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_feature() {
+        let err = match CommonOptions::from_iter_safe(vec!["foo", "--wasm-features=simd,foo"]) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e.to_string(),
+        };
+        assert!(
+            err.contains("unsupported WebAssembly feature 'foo', expected one of: all, default, bulk-memory,"),
+            "{}",
+            err
+        );
+        assert!(CommonOptions::from_iter_safe(vec!["foo", "--wasm-features=-default"]).is_err());
+    }
+
+    #[test]
+    fn test_features_configure_config() -> Result<()> {
+        let options = CommonOptions::from_iter_safe(vec![
+            "foo",
+            "--disable-cache",
+            "--wasm-features=simd,-multi-value",
+            "--enable-threads",
+        ])?;
+        let config = format!("{:?}", options.config(None)?);
+        assert!(config.contains("wasm_simd: true"), "{}", config);
+        assert!(config.contains("wasm_multi_value: false"), "{}", config);
+        assert!(config.contains("wasm_threads: true"), "{}", config);
+        assert!(config.contains("wasm_module_linking: false"), "{}", config);
+        Ok(())
+    }
+
     #[test]
     fn test_default_modules() {
         let options = CommonOptions::from_iter_safe(vec!["foo", "--wasi-modules=default"]).unwrap();
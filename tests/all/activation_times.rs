@@ -0,0 +1,150 @@
+use anyhow::Result;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (import "" "host" (func $host))
+        (func $spin (export "spin") (param i32)
+            (loop
+                (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                (br_if 0 (local.get 0))))
+        (func (export "spin_then_call_host") (param i32)
+            (call $spin (local.get 0))
+            (call $host)))
+"#;
+
+const SLEEP: Duration = Duration::from_millis(50);
+
+/// Finds a number of iterations of `spin` which take at least `SLEEP`.
+fn calibrate(store: &mut Store<()>, spin: &TypedFunc<i32, ()>) -> Result<i32> {
+    let mut iters = 1 << 20;
+    loop {
+        let start = Instant::now();
+        spin.call(&mut *store, iters)?;
+        if start.elapsed() >= SLEEP || iters >= 1 << 30 {
+            return Ok(iters);
+        }
+        iters *= 2;
+    }
+}
+
+#[test]
+fn guest_and_host_time() -> Result<()> {
+    let engine = Engine::new(Config::new().activation_times(true))?;
+    let mut store = Store::new(&engine, ());
+    assert_eq!(store.last_activation_times(), None);
+
+    let module = Module::new(&engine, WAT)?;
+    let host = Func::wrap(&mut store, || std::thread::sleep(SLEEP));
+    let instance = Instance::new(&mut store, &module, &[host.into()])?;
+    let spin = instance.get_typed_func::<i32, (), _>(&mut store, "spin")?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "spin_then_call_host")?;
+    let iters = calibrate(&mut store, &spin)?;
+
+    let start = Instant::now();
+    run.call(&mut store, iters)?;
+    let elapsed = start.elapsed();
+
+    let times = store.last_activation_times().unwrap();
+    assert!(times.host_time() >= SLEEP);
+    assert!(times.guest_time() >= SLEEP / 2);
+    assert!(times.guest_time() + times.host_time() <= elapsed);
+    if cfg!(any(unix, windows)) {
+        // Sleeping takes hardly any CPU time, but spinning does.
+        assert!(times.host_cpu_time() < times.guest_cpu_time());
+        assert!(times.guest_cpu_time() <= times.guest_time() + Duration::from_millis(10));
+    }
+    Ok(())
+}
+
+#[test]
+fn nested_activations_are_excluded() -> Result<()> {
+    let engine = Engine::new(Config::new().activation_times(true))?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, WAT)?;
+
+    // The host function of the outer call spins in a nested call, and then
+    // sleeps.
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap("", "host", |mut caller: Caller<'_, ()>| {
+        let spin = caller
+            .get_export("spin")
+            .and_then(|e| e.into_func())
+            .unwrap()
+            .typed::<i32, (), _>(&caller)
+            .unwrap();
+        spin.call(&mut caller, 1 << 26).unwrap();
+        let inner = caller.as_context().last_activation_times().unwrap();
+        assert!(inner.host_time() < Duration::from_millis(10));
+        std::thread::sleep(SLEEP);
+    })?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "spin_then_call_host")?;
+
+    let start = Instant::now();
+    run.call(&mut store, 1)?;
+    let elapsed = start.elapsed();
+
+    // Only the sleep counts as host time for the outer call, and the spinning
+    // doesn't count as its guest time either.
+    let outer = store.last_activation_times().unwrap();
+    assert!(outer.host_time() >= SLEEP);
+    assert!(outer.guest_time() < SLEEP / 2);
+    assert!(outer.host_time() + outer.guest_time() < elapsed);
+    Ok(())
+}
+
+#[test]
+fn panics_in_nested_activations() -> Result<()> {
+    let engine = Engine::new(Config::new().activation_times(true))?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "host" (func $host))
+                (import "" "panic" (func $panic))
+                (func (export "run") (call $host))
+                (func (export "panic") (call $panic)))
+        "#,
+    )?;
+
+    // The host function of the outer call sleeps, and then makes a nested
+    // call which panics.
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap("", "panic", || panic!("host panic"))?;
+    linker.func_wrap("", "host", |mut caller: Caller<'_, ()>| {
+        std::thread::sleep(SLEEP);
+        let inner = caller
+            .get_export("panic")
+            .and_then(|e| e.into_func())
+            .unwrap()
+            .typed::<(), (), _>(&caller)
+            .unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| inner.call(&mut caller, ())));
+        assert!(result.is_err());
+    })?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+
+    // The nested activation ended when the panic unwound through it, so the
+    // times are still those of the outer call.
+    let outer = store.last_activation_times().unwrap();
+    assert!(outer.host_time() >= SLEEP);
+    Ok(())
+}
+
+#[test]
+fn disabled_by_default() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), WAT)?;
+    let host = Func::wrap(&mut store, || {});
+    let instance = Instance::new(&mut store, &module, &[host.into()])?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "spin_then_call_host")?;
+    run.call(&mut store, 1)?;
+    assert_eq!(store.last_activation_times(), None);
+    Ok(())
+}
@@ -388,6 +388,169 @@ fn iloop_with_fuel() {
     }
 }
 
+#[test]
+fn fuel_yields_only_at_yieldpoints() {
+    use crate::fuel::{loop_offsets, with_yieldpoints, COUNTDOWN_LOOPS};
+
+    let wasm = wat::parse_str(COUNTDOWN_LOOPS).unwrap();
+    let annotated_loop = loop_offsets(&wasm)[0];
+    let wasm = with_yieldpoints(wasm, &[annotated_loop]);
+    let engine = Engine::new(
+        Config::new()
+            .async_support(true)
+            .consume_fuel(true)
+            .yieldpoints_from_custom_section(true),
+    )
+    .unwrap();
+    let module = Module::new(&engine, &wasm).unwrap();
+
+    // Runs `name` to completion on a fresh store, returning how many times it
+    // yielded along the way.
+    let yields = |name: &str| {
+        let mut store = Store::new(&engine, ());
+        store.add_fuel(1_000).unwrap();
+        store.out_of_fuel_async_yield(u64::max_value(), 1_000);
+        let instance = run(Instance::new_async(&mut store, &module, &[])).unwrap();
+        let func = instance
+            .get_typed_func::<i32, (), _>(&mut store, name)
+            .unwrap();
+        let mut f = Pin::from(Box::new(func.call_async(&mut store, 100_000)));
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut yields = 0;
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => break result.map(|()| yields).unwrap(),
+                Poll::Pending => yields += 1,
+            }
+        }
+    };
+
+    assert!(yields("annotated") > 100);
+    assert_eq!(yields("hot"), 0);
+}
+
+#[test]
+fn host_fuel_consumption_yields() {
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true)).unwrap();
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(100).unwrap();
+    store.out_of_fuel_async_yield(u64::max_value(), 100);
+    let charge = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ()>| -> Result<(), Trap> {
+            caller.consume_fuel(250)?;
+            Ok(())
+        },
+    );
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "" (func $charge))
+                (func (export "run") call $charge))
+        "#,
+    )
+    .unwrap();
+    let instance = run(Instance::new_async(&mut store, &module, &[charge.into()])).unwrap();
+    let func = instance
+        .get_typed_func::<(), (), _>(&mut store, "run")
+        .unwrap();
+
+    // The host function yields until enough fuel was injected for it.
+    let mut f = Pin::from(Box::new(func.call_async(&mut store, ())));
+    let waker = dummy_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut yields = 0;
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => break result.unwrap(),
+            Poll::Pending => yields += 1,
+        }
+    }
+    drop(f);
+    assert_eq!(yields, 2);
+    assert!(store.fuel_consumed().unwrap() >= 250);
+
+    // Outside of wasm there's nothing to yield from.
+    assert!(store.consume_fuel(1_000).is_err());
+}
+
+#[test]
+fn fuel_remaining_includes_injected_fuel() {
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true)).unwrap();
+    let mut store = Store::new(&engine, Vec::new());
+    store.add_fuel(10).unwrap();
+    store.out_of_fuel_async_yield(u64::max_value(), 10);
+    let check = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, Vec<u64>>| -> Result<(), Trap> {
+            caller.consume_fuel(3)?;
+            let total = caller.fuel_remaining().unwrap() + caller.fuel_consumed().unwrap();
+            caller.data_mut().push(total);
+            Ok(())
+        },
+    );
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "" (func $check))
+                (func (export "run")
+                    (local i32)
+                    i32.const 20
+                    local.set 0
+                    (loop
+                        call $check
+                        local.get 0
+                        i32.const -1
+                        i32.add
+                        local.tee 0
+                        br_if 0)))
+        "#,
+    )
+    .unwrap();
+    let instance = run(Instance::new_async(&mut store, &module, &[check.into()])).unwrap();
+    let func = instance
+        .get_typed_func::<(), (), _>(&mut store, "run")
+        .unwrap();
+
+    let mut f = Pin::from(Box::new(func.call_async(&mut store, ())));
+    let waker = dummy_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut yields = 0;
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => break result.unwrap(),
+            Poll::Pending => yields += 1,
+        }
+    }
+    drop(f);
+    assert!(yields >= 5, "{}", yields);
+
+    // Remaining and consumed fuel add up to the fuel added and injected so
+    // far, both while wasm is running and afterwards. The exception is wasm
+    // which ran out of fuel since its last fuel check, which has consumed
+    // more than that.
+    let total = 10 + yields * 10;
+    let remaining = store.fuel_remaining().unwrap();
+    let consumed = store.fuel_consumed().unwrap();
+    if remaining > 0 {
+        assert_eq!(remaining + consumed, total);
+    } else {
+        assert!(consumed >= total, "{} < {}", consumed, total);
+    }
+    let totals = store.data();
+    assert_eq!(totals.len(), 20);
+    for pair in totals.windows(2) {
+        assert!(pair[0] <= pair[1]);
+    }
+    for seen in totals {
+        assert_eq!(seen % 10, 0);
+        assert!(*seen <= total);
+    }
+}
+
 #[test]
 fn fuel_eventually_finishes() {
     let engine = Engine::new(Config::new().async_support(true).consume_fuel(true)).unwrap();
@@ -611,6 +774,131 @@ fn resume_separate_thread3() {
     assert!(f.call(&mut store, &[]).is_err());
 }
 
+#[test]
+fn resume_separate_thread_nested() {
+    // Like `resume_separate_thread2`, except the async host function which
+    // suspends is called by wasm which was in turn called by an async host
+    // function. Both calls into wasm must be carried over to the other thread
+    // for the trap after the outer one to be caught there.
+    execute_across_threads(async {
+        let mut store = async_store();
+        let pending = Func::wrap0_async(&mut store, |_| {
+            Box::new(async { PendingOnce::default().await })
+        });
+        let inner = Module::new(
+            store.engine(),
+            r#"(module (import "" "" (func)) (func (export "inner") call 0))"#,
+        )
+        .unwrap();
+        let inner = Instance::new_async(&mut store, &inner, &[pending.into()])
+            .await
+            .unwrap();
+        let inner = inner
+            .get_typed_func::<(), (), _>(&mut store, "inner")
+            .unwrap();
+        let reenter = Func::wrap0_async(&mut store, move |mut caller| {
+            Box::new(async move { inner.call_async(&mut caller, ()).await })
+        });
+        let outer = Module::new(
+            store.engine(),
+            r#"(module (import "" "" (func)) (func (export "outer") call 0 unreachable))"#,
+        )
+        .unwrap();
+        let outer = Instance::new_async(&mut store, &outer, &[reenter.into()])
+            .await
+            .unwrap();
+        let outer = outer
+            .get_typed_func::<(), (), _>(&mut store, "outer")
+            .unwrap();
+        let trap = outer.call_async(&mut store, ()).await.unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    });
+}
+
+#[test]
+fn resume_on_many_threads() {
+    // Simulates a multi-threaded executor, polling the futures of several
+    // stores on a different thread each time. The threads also run unrelated
+    // wasm which traps while those futures are suspended, which must neither
+    // see nor disturb the suspended calls.
+    let engine = Engine::new(Config::new().async_support(true)).unwrap();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "" (func $pending))
+                (func (export "run") (param i32)
+                    loop
+                        call $pending
+                        (local.tee 0 (i32.sub (local.get 0) (i32.const 1)))
+                        br_if 0
+                    end
+                    unreachable))
+        "#,
+    )
+    .unwrap();
+    let trapping = Module::new(
+        &Engine::default(),
+        r#"(module (func (export "trap") unreachable))"#,
+    )
+    .unwrap();
+
+    let mut futures = (0..4)
+        .map(|_| {
+            let engine = engine.clone();
+            let module = module.clone();
+            Box::pin(async move {
+                let mut store = Store::new(&engine, ());
+                let pending = Func::wrap0_async(&mut store, |_| {
+                    Box::new(async { PendingOnce::default().await })
+                });
+                let instance = Instance::new_async(&mut store, &module, &[pending.into()]).await?;
+                let run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+                run.call_async(&mut store, 10).await?;
+                Ok::<(), anyhow::Error>(())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut finished = 0;
+    while !futures.is_empty() {
+        let threads = futures
+            .drain(..)
+            .map(|mut future| {
+                let trapping = trapping.clone();
+                std::thread::spawn(move || {
+                    let poll = future
+                        .as_mut()
+                        .poll(&mut Context::from_waker(&dummy_waker()));
+
+                    let mut store = Store::new(trapping.engine(), ());
+                    let trap = Instance::new(&mut store, &trapping, &[])
+                        .unwrap()
+                        .get_typed_func::<(), (), _>(&mut store, "trap")
+                        .unwrap()
+                        .call(&mut store, ())
+                        .unwrap_err();
+                    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+
+                    (future, poll)
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            match thread.join().unwrap() {
+                (future, Poll::Pending) => futures.push(future),
+                (_, Poll::Ready(result)) => {
+                    let err = result.unwrap_err();
+                    let trap = err.downcast_ref::<Trap>().unwrap();
+                    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+                    finished += 1;
+                }
+            }
+        }
+    }
+    assert_eq!(finished, 4);
+}
+
 #[test]
 fn recursive_async() -> Result<()> {
     let mut store = async_store();
@@ -639,3 +927,648 @@ fn recursive_async() -> Result<()> {
     run(f2.call_async(&mut store, &[]))?;
     Ok(())
 }
+
+#[test]
+fn async_host_wasm_ping_pong_traps() -> Result<()> {
+    let depth = |max_native_stack: usize| -> Result<usize> {
+        let mut config = Config::new();
+        config
+            .async_support(true)
+            .max_wasm_stack(64 << 10)?
+            .max_native_stack(max_native_stack)?;
+        let engine = Engine::new(&config)?;
+        let module = Module::new(
+            &engine,
+            r#"
+                (module
+                    (import "" "" (func $host (param i32)))
+                    (func $run (export "run") (param i32)
+                        local.get 0
+                        i32.const 1
+                        i32.add
+                        call $host)
+                )
+            "#,
+        )?;
+        let mut store = Store::new(&engine, 0);
+        let host = Func::wrap1_async(&mut store, |mut caller: Caller<'_, usize>, depth: i32| {
+            Box::new(async move {
+                *caller.data_mut() = depth as usize;
+                let run = caller
+                    .get_export("run")
+                    .unwrap()
+                    .into_func()
+                    .unwrap()
+                    .typed::<i32, (), _>(&caller)?;
+                run.call_async(&mut caller, depth).await
+            })
+        });
+        let instance = run(Instance::new_async(&mut store, &module, &[host.into()]))?;
+        let func = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+        let trap = run(func.call_async(&mut store, 0)).unwrap_err();
+        let depth = *store.data();
+
+        assert_eq!(trap.trap_code(), Some(TrapCode::StackOverflow), "{}", trap);
+        assert!(trap.trace().len() >= depth);
+        for frame in trap.trace() {
+            assert_eq!(frame.func_name(), Some("run"));
+        }
+        Ok(depth)
+    };
+
+    // Each nested call runs on its own fiber, but the stack used on the
+    // enclosing fibers still counts against the total budget.
+    let shallow = depth(64 << 10)?;
+    assert!(shallow > 1);
+    let deeper = depth(512 << 10)?;
+    assert!(deeper > shallow * 4, "{} vs {}", deeper, shallow);
+    Ok(())
+}
+
+#[derive(Default)]
+struct CancelState {
+    ticks: u32,
+    cleaned_up: bool,
+}
+
+fn cancellation_engine() -> Engine {
+    let mut config = Config::new();
+    config
+        .async_support(true)
+        .consume_fuel(true)
+        .epoch_interruption(true);
+    Engine::new(&config).unwrap()
+}
+
+fn cancellation_store(grace_fuel: u64) -> (Store<CancelState>, Vec<Extern>) {
+    let mut store = Store::new(&cancellation_engine(), CancelState::default());
+    store.out_of_fuel_async_yield(u64::max_value(), 100);
+    store.set_epoch_deadline(u64::max_value());
+    store.cancellation_grace_fuel(grace_fuel);
+
+    let tick = Func::wrap(&mut store, |mut caller: Caller<'_, CancelState>| {
+        caller.data_mut().ticks += 1;
+        if caller.data().ticks == 10 {
+            caller.cancellation_token().unwrap().cancel();
+        }
+    });
+    let cancelled = Func::wrap(&mut store, |caller: Caller<'_, CancelState>| {
+        caller.cancellation_token().unwrap().is_cancelled() as i32
+    });
+    let cleanup = Func::wrap(&mut store, |mut caller: Caller<'_, CancelState>| {
+        caller.data_mut().cleaned_up = true;
+    });
+    (store, vec![tick.into(), cancelled.into(), cleanup.into()])
+}
+
+const CANCELLATION_GUEST: &str = r#"
+    (module
+        (import "" "tick" (func $tick))
+        (import "" "cancelled" (func $cancelled (result i32)))
+        (import "" "cleanup" (func $cleanup))
+
+        ;; Checks for cancellation on each iteration and, once cancelled,
+        ;; performs some work which takes more fuel than is injected for each
+        ;; async yield before reporting that cleanup has finished.
+        (func (export "cooperative")
+            (local i32)
+            (loop $l
+                call $tick
+                (if (call $cancelled)
+                    (then
+                        i32.const 1000
+                        local.set 0
+                        (loop
+                            local.get 0
+                            i32.const -1
+                            i32.add
+                            local.tee 0
+                            br_if 0)
+                        call $cleanup
+                        return))
+                br $l))
+
+        ;; Never checks for cancellation.
+        (func (export "uncooperative")
+            (loop $l
+                call $tick
+                br $l))
+    )
+"#;
+
+#[test]
+fn cancellation_graceful() -> Result<()> {
+    let (mut store, imports) = cancellation_store(10_000);
+    let module = Module::new(store.engine(), CANCELLATION_GUEST)?;
+    let instance = run(Instance::new_async(&mut store, &module, &imports))?;
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "cooperative")?;
+    run(func.call_async(&mut store, ()))?;
+    assert!(store.data().cleaned_up);
+
+    // The cancellation is cleared once the call returns, so the store can be
+    // used again, and the next cancellation gets its own grace period.
+    let token = store.cancellation_token()?;
+    assert!(!token.is_cancelled());
+    *store.data_mut() = CancelState::default();
+    run(func.call_async(&mut store, ()))?;
+    assert!(store.data().cleaned_up);
+    assert!(!token.is_cancelled());
+    Ok(())
+}
+
+#[test]
+fn cancellation_cleared_after_forced_cancel() -> Result<()> {
+    let (mut store, imports) = cancellation_store(10_000);
+    let module = Module::new(store.engine(), CANCELLATION_GUEST)?;
+    let instance = run(Instance::new_async(&mut store, &module, &imports))?;
+    let uncooperative = instance.get_typed_func::<(), (), _>(&mut store, "uncooperative")?;
+    run(uncooperative.call_async(&mut store, ())).unwrap_err();
+    assert!(!store.cancellation_token()?.is_cancelled());
+
+    // Without another request the next call runs to completion.
+    let cooperative = instance.get_typed_func::<(), (), _>(&mut store, "cooperative")?;
+    *store.data_mut() = CancelState::default();
+    run(cooperative.call_async(&mut store, ()))?;
+    assert!(store.data().cleaned_up);
+    Ok(())
+}
+
+#[test]
+fn cancellation_before_call_and_reset() -> Result<()> {
+    let (mut store, imports) = cancellation_store(0);
+    let module = Module::new(store.engine(), CANCELLATION_GUEST)?;
+    let instance = run(Instance::new_async(&mut store, &module, &imports))?;
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "uncooperative")?;
+
+    // Cancelling while no wasm is running applies to the next call.
+    let token = store.cancellation_token()?;
+    token.cancel();
+    let trap = run(func.call_async(&mut store, ())).unwrap_err();
+    assert!(trap.to_string().contains("cancelled"), "bad trap: {}", trap);
+    assert!(store.data().ticks < 10);
+    assert!(!token.is_cancelled());
+
+    // Resetting the store clears a pending cancellation too.
+    token.cancel();
+    store.reset();
+    assert!(!token.is_cancelled());
+    Ok(())
+}
+
+#[test]
+fn cancellation_requires_fuel_and_epochs() -> Result<()> {
+    let store = Store::new(&Engine::default(), ());
+    assert!(store.cancellation_token().is_err());
+    let store = Store::new(&Engine::new(Config::new().consume_fuel(true))?, ());
+    assert!(store.cancellation_token().is_err());
+    let store = Store::new(&Engine::new(Config::new().epoch_interruption(true))?, ());
+    assert!(store.cancellation_token().is_err());
+    Ok(())
+}
+
+#[test]
+fn cancellation_noticed_before_fuel_runs_out() -> Result<()> {
+    let (mut store, imports) = cancellation_store(100);
+    // Enough fuel that it never runs out on its own.
+    store.add_fuel(1 << 40)?;
+    let module = Module::new(store.engine(), CANCELLATION_GUEST)?;
+    let instance = run(Instance::new_async(&mut store, &module, &imports))?;
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "uncooperative")?;
+    let trap = run(func.call_async(&mut store, ())).unwrap_err();
+    assert!(trap.to_string().contains("cancelled"), "bad trap: {}", trap);
+    // Only the grace fuel was left once the cancellation was noticed.
+    assert!(store.data().ticks > 10);
+    assert!(
+        store.data().ticks < 100,
+        "ran for {} ticks",
+        store.data().ticks
+    );
+    Ok(())
+}
+
+#[test]
+fn cancellation_seen_while_unwinding_dropped_future() -> Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    struct Guard(CancellationToken, Arc<AtomicBool>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.1.store(self.0.is_cancelled(), SeqCst);
+        }
+    }
+
+    let mut store = Store::new(&cancellation_engine(), ());
+    store.add_fuel(10_000)?;
+    store.set_epoch_deadline(u64::max_value());
+    let seen = Arc::new(AtomicBool::new(false));
+    let seen2 = seen.clone();
+    let pending = Func::wrap0_async(&mut store, move |caller| {
+        let guard = Guard(caller.cancellation_token().unwrap(), seen2.clone());
+        Box::new(async move {
+            let _guard = guard;
+            std::future::pending::<()>().await
+        })
+    });
+    let module = Module::new(
+        store.engine(),
+        r#"(module (import "" "" (func)) (func (export "run") call 0))"#,
+    )?;
+    let instance = run(Instance::new_async(&mut store, &module, &[pending.into()]))?;
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    {
+        let mut future = Pin::from(Box::new(func.call_async(&mut store, ())));
+        let poll = future
+            .as_mut()
+            .poll(&mut Context::from_waker(&dummy_waker()));
+        assert!(poll.is_pending());
+    }
+    assert!(seen.load(SeqCst));
+
+    // The cancellation only applied to the dropped call.
+    assert!(!store.cancellation_token()?.is_cancelled());
+    Ok(())
+}
+
+#[test]
+fn cancellation_without_grace_aborts_cleanup() -> Result<()> {
+    let (mut store, imports) = cancellation_store(0);
+    let module = Module::new(store.engine(), CANCELLATION_GUEST)?;
+    let instance = run(Instance::new_async(&mut store, &module, &imports))?;
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "cooperative")?;
+    let trap = run(func.call_async(&mut store, ())).unwrap_err();
+    assert!(trap.to_string().contains("cancelled"), "bad trap: {}", trap);
+    assert!(!store.data().cleaned_up);
+    Ok(())
+}
+
+#[test]
+fn cancellation_forced_after_grace() -> Result<()> {
+    let (mut store, imports) = cancellation_store(10_000);
+    let module = Module::new(store.engine(), CANCELLATION_GUEST)?;
+    let instance = run(Instance::new_async(&mut store, &module, &imports))?;
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "uncooperative")?;
+    let trap = run(func.call_async(&mut store, ())).unwrap_err();
+    assert!(trap.to_string().contains("cancelled"), "bad trap: {}", trap);
+    assert!(store.data().ticks > 10);
+    assert!(!store.data().cleaned_up);
+    Ok(())
+}
+
+#[test]
+fn suspended_access_reads_memory() -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.out_of_fuel_async_yield(u64::max_value(), 100);
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "run")
+                    (loop $l
+                        (i32.store (i32.const 0)
+                            (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+                        (br_if $l (i32.lt_u (i32.load (i32.const 0)) (i32.const 10000))))
+                )
+            )
+        "#,
+    )?;
+    let instance = run(Instance::new_async(&mut store, &module, &[]))?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let run_func = instance.get_func(&mut store, "run").unwrap();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen2 = seen.clone();
+    store.suspended_access_hook(move |access| {
+        let data = access.memory_data(&memory);
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&data[0..4]);
+        seen2.lock().unwrap().push(i32::from_le_bytes(bytes));
+    });
+    run(run_func.call_async(&mut store, &[]))?;
+
+    let seen = seen.lock().unwrap();
+    assert!(!seen.is_empty());
+    assert!(seen.windows(2).all(|w| w[0] < w[1]), "{:?}", seen);
+    assert!(*seen.last().unwrap() <= 10000);
+    assert_eq!(&memory.data(&store)[0..4], &10000i32.to_le_bytes());
+    Ok(())
+}
+
+#[test]
+fn fuel_callback_decreasing_injections() -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events2 = events.clone();
+    store.out_of_fuel_async_yield_with_callback(move |event| {
+        let mut events = events2.lock().unwrap();
+        events.push((event.injections(), event.fuel_consumed()));
+        // Grant 1000 fuel, halving it each time, until less than 100 would
+        // be granted.
+        let fuel = 1000 >> event.injections();
+        if fuel < 100 {
+            FuelDecision::Trap
+        } else {
+            FuelDecision::InjectAndYield(fuel)
+        }
+    });
+    let module = Module::new(
+        &engine,
+        "
+            (module
+                (func (export \"run\") (loop br 0))
+            )
+        ",
+    )?;
+    let instance = run(Instance::new_async(&mut store, &module, &[]))?;
+    let func = instance.get_func(&mut store, "run").unwrap();
+
+    let mut future = Pin::from(Box::new(func.call_async(&mut store, &[])));
+    let waker = dummy_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut pending = 0;
+    let trap = loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => break result.unwrap_err(),
+            Poll::Pending => pending += 1,
+        }
+    };
+    drop(future);
+
+    // 1000, 500, 250, and 125 fuel are injected, with a yield before each.
+    assert_eq!(pending, 4);
+    assert!(
+        trap.to_string().contains("all fuel consumed"),
+        "bad trap: {}",
+        trap
+    );
+    let events = events.lock().unwrap();
+    let injections = events.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+    assert_eq!(injections, [0, 1, 2, 3, 4]);
+    assert!(events.windows(2).all(|w| w[0].1 <= w[1].1));
+    Ok(())
+}
+
+#[test]
+fn fuel_callback_inject_without_yield() -> Result<()> {
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.out_of_fuel_async_yield_with_callback(|event| {
+        if event.injections() < 10 {
+            FuelDecision::InjectWithoutYield(100)
+        } else {
+            FuelDecision::Trap
+        }
+    });
+    let module = Module::new(
+        &engine,
+        "
+            (module
+                (func (export \"run\") (loop br 0))
+            )
+        ",
+    )?;
+    let instance = run(Instance::new_async(&mut store, &module, &[]))?;
+    let func = instance.get_func(&mut store, "run").unwrap();
+
+    let mut future = Pin::from(Box::new(func.call_async(&mut store, &[])));
+    let waker = dummy_waker();
+    let mut cx = Context::from_waker(&waker);
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(result) => assert!(result.is_err()),
+        Poll::Pending => panic!("should not have yielded"),
+    }
+    drop(future);
+    assert!(store.fuel_consumed().unwrap() >= 1000);
+    Ok(())
+}
+
+#[test]
+fn caller_waker_wakes_wasm_future() -> Result<()> {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::time::Duration;
+
+    /// Pending until another thread sets `ready`, without registering the
+    /// waker of its own `Context`.
+    struct WaitFor(Arc<AtomicBool>);
+
+    impl Future for WaitFor {
+        type Output = Result<(), Trap>;
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.0.load(SeqCst) {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    struct ThreadWaker {
+        thread: std::thread::Thread,
+        wakes: AtomicUsize,
+    }
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.wakes.fetch_add(1, SeqCst);
+            self.thread.unpark();
+        }
+    }
+
+    let mut store = async_store();
+    let wait = Func::wrap0_async(&mut store, |caller| {
+        let waker = caller.waker().expect("called from an async call");
+        let ready = Arc::new(AtomicBool::new(false));
+        std::thread::spawn({
+            let ready = ready.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(50));
+                ready.store(true, SeqCst);
+                waker.wake();
+            }
+        });
+        Box::new(WaitFor(ready))
+    });
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "" "wait" (func $wait))
+                (func (export "run") (result i32)
+                    call $wait
+                    i32.const 42))
+        "#,
+    )?;
+    let instance = run(Instance::new_async(&mut store, &module, &[wait.into()]))?;
+    let func = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+
+    let waker = Arc::new(ThreadWaker {
+        thread: std::thread::current(),
+        wakes: AtomicUsize::new(0),
+    });
+    let mut future = Pin::from(Box::new(func.call_async(&mut store, ())));
+    let task_waker = Waker::from(waker.clone());
+    let mut cx = Context::from_waker(&task_waker);
+    let mut polls = 0;
+    let result = loop {
+        polls += 1;
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => break result?,
+            // Only the waker can end this wait in time, and a spurious wakeup
+            // just polls again.
+            Poll::Pending => std::thread::park_timeout(Duration::from_secs(10)),
+        }
+    };
+    drop(future);
+    assert_eq!(result, 42);
+    assert!(polls >= 2);
+    assert_eq!(waker.wakes.load(SeqCst), 1);
+
+    // Synchronous stores have no waker to give out.
+    let mut store = Store::<()>::default();
+    let func = Func::wrap(&mut store, |caller: Caller<'_, ()>| {
+        assert!(caller.waker().is_none());
+    });
+    func.call(&mut store, &[])?;
+    Ok(())
+}
+
+#[test]
+fn linker_instantiate_async_start_awaits_import() -> Result<()> {
+    let mut store = async_store();
+    let mut linker = Linker::new(store.engine());
+    linker.func_wrap0_async("host", "wait", |_caller| {
+        Box::new(async {
+            PendingOnce::default().await;
+            Ok(7)
+        })
+    })?;
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "host" "wait" (func $wait (result i32)))
+                (global (export "g") (mut i32) (i32.const 0))
+                (func $start (global.set 0 (call $wait)))
+                (start $start))
+        "#,
+    )?;
+
+    let instance = run(linker.instantiate_async(&mut store, &module))?;
+    let g = instance.get_global(&mut store, "g").unwrap();
+    assert_eq!(g.get(&mut store).i32(), Some(7));
+
+    // The synchronous version can't run the start function, and says so
+    // rather than panicking.
+    let err = linker.instantiate(&mut store, &module).unwrap_err();
+    assert!(
+        err.to_string().contains("use `Linker::instantiate_async`"),
+        "bad error: {}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn linked_command_instantiates_on_fiber() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.out_of_fuel_async_yield(u64::max_value(), 100);
+
+    let waits = Arc::new(AtomicUsize::new(0));
+    let mut linker = Linker::new(&engine);
+    let counter = waits.clone();
+    linker.func_wrap0_async("host", "wait", move |_caller| {
+        let counter = counter.clone();
+        Box::new(async move {
+            PendingOnce::default().await;
+            counter.fetch_add(1, SeqCst);
+            Ok(())
+        })
+    })?;
+
+    // Both the start function, which runs each time the command is
+    // instantiated, and `_start` itself await the import, and `_start` runs
+    // long enough to run out of fuel and yield several times.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "host" "wait" (func $wait))
+                (global $started (mut i32) (i32.const 0))
+                (func $init
+                    call $wait
+                    (global.set $started (i32.add (global.get $started) (i32.const 1))))
+                (start $init)
+                (func (export "_start") (result i32)
+                    (local $i i32)
+                    (loop
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if 0 (i32.lt_u (local.get $i) (i32.const 1000))))
+                    call $wait
+                    global.get $started))
+        "#,
+    )?;
+    linker.module(&mut store, "cmd", &module)?;
+    let start = linker
+        .get(&mut store, "cmd", Some("_start"))
+        .unwrap()
+        .into_func()
+        .unwrap()
+        .typed::<(), i32, _>(&store)?;
+
+    // Each call gets a fresh instance, whose start function ran once.
+    for i in 1..=3 {
+        assert_eq!(run(start.call_async(&mut store, ()))?, 1);
+        assert_eq!(waits.load(SeqCst), 2 * i);
+    }
+    Ok(())
+}
+
+#[test]
+fn suspended_access_requires_no_running_wasm() -> Result<()> {
+    let engine = Engine::new(Config::new().async_support(true))?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "" (func $host))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\2a")
+                (func (export "run") call $host)
+            )
+        "#,
+    )?;
+    let host = Func::wrap(&mut store, |caller: Caller<'_, ()>| {
+        assert!(caller.as_context().with_suspended_access(|_| ()).is_err());
+    });
+    let instance = run(Instance::new_async(&mut store, &module, &[host.into()]))?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let run_func = instance.get_func(&mut store, "run").unwrap();
+    run(run_func.call_async(&mut store, &[]))?;
+
+    // Without any WebAssembly running the store can be accessed, but not
+    // re-entrantly.
+    let byte = store.with_suspended_access(|access| access.memory_data(&memory)[0])?;
+    assert_eq!(byte, 42);
+    let nested = store.with_suspended_access(|_| store.with_suspended_access(|_| ()).is_err())?;
+    assert!(nested);
+
+    // Stores without async support can't be suspended.
+    let store = Store::new(&Engine::default(), ());
+    assert!(store.with_suspended_access(|_| ()).is_err());
+    Ok(())
+}
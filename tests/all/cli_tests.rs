@@ -4,8 +4,8 @@ use std::path::Path;
 use std::process::{Command, Output};
 use tempfile::NamedTempFile;
 
-// Run the wasmtime CLI with the provided args and return the `Output`.
-fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
+// Creates a `Command` which runs the wasmtime CLI.
+fn wasmtime_command() -> Result<Command> {
     let runner = std::env::vars()
         .filter(|(k, _v)| k.starts_with("CARGO_TARGET") && k.ends_with("RUNNER"))
         .next();
@@ -17,7 +17,7 @@ fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
     // If we're running tests with a "runner" then we might be doing something
     // like cross-emulation, so spin up the emulator rather than the tests
     // itself, which may not be natively executable.
-    let mut cmd = if let Some((_, runner)) = runner {
+    let cmd = if let Some((_, runner)) = runner {
         let mut parts = runner.split_whitespace();
         let mut cmd = Command::new(parts.next().unwrap());
         for arg in parts {
@@ -28,7 +28,12 @@ fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
     } else {
         Command::new(&me)
     };
-    cmd.args(args).output().map_err(Into::into)
+    Ok(cmd)
+}
+
+// Run the wasmtime CLI with the provided args and return the `Output`.
+fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
+    wasmtime_command()?.args(args).output().map_err(Into::into)
 }
 
 // Run the wasmtime CLI with the provided args and, if it succeeds, return
@@ -381,3 +386,280 @@ fn exit_with_saved_fprs() -> Result<()> {
     assert!(output.stdout.is_empty());
     Ok(())
 }
+
+// Run `print_env.wat` with the given arguments, adding `vars` to wasmtime's own
+// environment, and return the lines it printed, sorted.
+fn run_print_env(args: &[&str], vars: &[(&str, &str)]) -> Result<Vec<String>> {
+    let wasm = build_wasm("tests/wasm/print_env.wat")?;
+    let output = wasmtime_command()?
+        .args(&["run", "--disable-cache"])
+        .args(args)
+        .arg(wasm.path())
+        .envs(vars.iter().cloned())
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "Failed to execute wasmtime with: {:?}\n{}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(sorted_lines(&String::from_utf8(output.stdout)?))
+}
+
+fn sorted_lines(s: &str) -> Vec<String> {
+    let mut lines = s.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn env_explicit() -> Result<()> {
+    let vars = [("WASMTIME_ENV_TEST_A", "a")];
+    assert_eq!(run_print_env(&[], &vars)?, Vec::<String>::new());
+    assert_eq!(
+        run_print_env(&["--env", "FOO=bar", "--env", "BAZ="], &vars)?,
+        ["BAZ=", "FOO=bar"]
+    );
+    Ok(())
+}
+
+#[test]
+fn env_filter() -> Result<()> {
+    let vars = [
+        ("WASMTIME_ENV_TEST_A", "a"),
+        ("WASMTIME_ENV_TEST_B", "b"),
+        ("WASMTIME_ENV_OTHER", "other"),
+    ];
+    assert_eq!(
+        run_print_env(&["--env-filter", "WASMTIME_ENV_TEST_*"], &vars)?,
+        ["WASMTIME_ENV_TEST_A=a", "WASMTIME_ENV_TEST_B=b"]
+    );
+
+    // Patterns without a `*` match names exactly, and filters are unioned.
+    assert_eq!(
+        run_print_env(
+            &[
+                "--env-filter",
+                "WASMTIME_ENV_TEST_",
+                "--env-filter",
+                "WASMTIME_ENV_OTHER",
+                "--env-filter",
+                "WASMTIME_ENV_TEST_B",
+            ],
+            &vars
+        )?,
+        ["WASMTIME_ENV_OTHER=other", "WASMTIME_ENV_TEST_B=b"]
+    );
+
+    // Explicit variables win over inherited ones, and aren't filtered.
+    assert_eq!(
+        run_print_env(
+            &[
+                "--env-filter",
+                "WASMTIME_ENV_TEST_*",
+                "--env",
+                "WASMTIME_ENV_TEST_B=override",
+                "--env",
+                "FOO=bar",
+            ],
+            &vars
+        )?,
+        [
+            "FOO=bar",
+            "WASMTIME_ENV_TEST_A=a",
+            "WASMTIME_ENV_TEST_B=override"
+        ]
+    );
+
+    // Filters still apply with `--inherit-env`.
+    assert_eq!(
+        run_print_env(
+            &["--inherit-env", "--env-filter", "WASMTIME_ENV_OTHER"],
+            &vars
+        )?,
+        ["WASMTIME_ENV_OTHER=other"]
+    );
+    Ok(())
+}
+
+#[test]
+fn env_inherit() -> Result<()> {
+    let vars = [("WASMTIME_ENV_TEST_A", "a"), ("WASMTIME_ENV_TEST_B", "b")];
+    let lines = run_print_env(
+        &["--inherit-env", "--env", "WASMTIME_ENV_TEST_B=override"],
+        &vars,
+    )?;
+
+    // Everything wasmtime sees is passed along, except where overridden.
+    let mut expected = String::new();
+    for (key, value) in std::env::vars_os() {
+        if let (Some(key), Some(value)) = (key.to_str(), value.to_str()) {
+            if !key.starts_with("WASMTIME_ENV_TEST_") {
+                expected.push_str(&format!("{}={}\n", key, value));
+            }
+        }
+    }
+    expected.push_str("WASMTIME_ENV_TEST_A=a\n");
+    expected.push_str("WASMTIME_ENV_TEST_B=override\n");
+    assert_eq!(lines, sorted_lines(&expected));
+    Ok(())
+}
+
+// Enable a proposal with `--wasm-features`.
+#[test]
+fn wasm_features_enable_simd() -> Result<()> {
+    let wasm = build_wasm("tests/wasm/simd.wat")?;
+    let args = [
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--invoke",
+        "run",
+        "--disable-cache",
+    ];
+    assert!(run_wasmtime(&args).is_err());
+
+    let stdout = run_wasmtime(&[&args[..], &["--wasm-features=simd"]].concat())?;
+    assert_eq!(stdout, "42\n");
+    Ok(())
+}
+
+// Disabling a proposal the module uses suggests how to enable it.
+#[test]
+fn wasm_features_disable_multi_value() -> Result<()> {
+    let wasm = build_wasm("tests/wasm/multi-value.wat")?;
+    let output = run_wasmtime_for_output(&[
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--invoke",
+        "run",
+        "--disable-cache",
+        "--wasm-features=-multi-value",
+    ])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "module uses disabled WebAssembly features; try `--wasm-features=multi-value`"
+        ),
+        "bad stderr: {}",
+        stderr
+    );
+
+    let output = run_wasmtime_for_output(&[
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--disable-cache",
+        "--wasm-features=foo",
+    ])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("expected one of: all, default,"),
+        "bad stderr: {}",
+        stderr
+    );
+    Ok(())
+}
+
+// `--print-config` shows the features in effect after all flags.
+#[test]
+fn print_config() -> Result<()> {
+    let wasm = build_wasm("tests/wasm/simple.wat")?;
+    let output = run_wasmtime_for_output(&[
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--invoke",
+        "get_f32",
+        "--disable-cache",
+        "--wasm-features=all,-simd",
+        "--enable-simd",
+        "--print-config",
+    ])?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "100\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("wasm_simd: true"), "bad stderr: {}", stderr);
+    assert!(
+        stderr.contains("wasm_threads: true"),
+        "bad stderr: {}",
+        stderr
+    );
+    assert!(
+        stderr.contains("static_memory_maximum_size:"),
+        "bad stderr: {}",
+        stderr
+    );
+    Ok(())
+}
+
+// With `--watch` the module is run again each time it changes, and once
+// interrupted wasmtime exits with the interrupted status rather than the
+// status of the last run. A symlink cycle under `--watch-dir` doesn't stop it
+// from watching.
+#[cfg(unix)]
+#[test]
+fn run_watch_reruns_on_change() -> Result<()> {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    fn exits_with(status: i32) -> String {
+        format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+                (memory (export "memory") 1)
+                (func (export "_start") i32.const {} call $exit))"#,
+            status
+        )
+    }
+
+    let dir = tempfile::tempdir()?;
+    let module = dir.path().join("exit.wat");
+    std::fs::write(&module, exits_with(3))?;
+    let watched = dir.path().join("watched");
+    std::fs::create_dir_all(watched.join("sub"))?;
+    std::os::unix::fs::symlink("..", watched.join("sub").join("parent"))?;
+
+    let mut child = wasmtime_command()?
+        .args(&["run", "--watch", "--disable-cache", "--watch-dir"])
+        .arg(&watched)
+        .arg(&module)
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Read stderr on another thread so that a run which doesn't print what's
+    // expected fails the test rather than blocking it forever.
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in stderr.lines() {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let wait_for = |needle: &str| -> Result<()> {
+        loop {
+            match rx.recv_timeout(std::time::Duration::from_secs(60)) {
+                Ok(line) => {
+                    if line?.contains(needle) {
+                        return Ok(());
+                    }
+                }
+                Err(_) => bail!("wasmtime didn't print `{}`", needle),
+            }
+        }
+    };
+
+    let result = wait_for("run #1 exited with status 3").and_then(|()| {
+        std::fs::write(&module, exits_with(42))?;
+        wait_for("run #2 exited with status 42")
+    });
+
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+    let status = child.wait()?;
+    result?;
+    assert_eq!(status.code(), Some(128 + libc::SIGINT));
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn defaults() -> Result<()> {
+    let summary = Engine::default().config_summary();
+    assert_eq!(summary.wasmtime_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(summary.target, target_lexicon::Triple::host().to_string());
+    assert_eq!(summary.strategy, "cranelift");
+    assert_eq!(summary.opt_level, OptLevel::Speed);
+    assert_eq!(summary.flags["opt_level"], "speed");
+    assert_eq!(summary.wasm_features["simd"], false);
+    assert_eq!(summary.wasm_features["bulk_memory"], true);
+    assert!(!summary.interruptable);
+    assert!(!summary.consume_fuel);
+    assert!(!summary.static_memory_bound_is_maximum);
+    assert!(summary.guard_before_linear_memory);
+    assert_eq!(
+        summary.static_memory_guard_before_size,
+        summary.static_memory_guard_size
+    );
+    assert_eq!(
+        summary.dynamic_memory_guard_before_size,
+        summary.dynamic_memory_guard_size
+    );
+    assert_eq!(summary.allocation_strategy, "on-demand");
+    Ok(())
+}
+
+#[test]
+fn reflects_config() -> Result<()> {
+    let mut config = Config::new();
+    config
+        .interruptable(true)
+        .consume_fuel(true)
+        .wasm_simd(true)
+        .cranelift_opt_level(OptLevel::None)
+        .static_memory_maximum_size(1 << 20)
+        .static_memory_guard_size(1 << 16)
+        .guard_before_linear_memory_size(1 << 17)
+        .allocation_strategy(InstanceAllocationStrategy::Pooling {
+            strategy: PoolingAllocationStrategy::default(),
+            module_limits: ModuleLimits {
+                memory_pages: 16,
+                ..ModuleLimits::default()
+            },
+            instance_limits: InstanceLimits { count: 1 },
+        });
+    let summary = Engine::new(&config)?.config_summary();
+    assert!(summary.interruptable);
+    assert!(summary.consume_fuel);
+    assert_eq!(summary.wasm_features["simd"], true);
+    assert_eq!(summary.opt_level, OptLevel::None);
+    assert_eq!(summary.flags["opt_level"], "none");
+    assert_eq!(summary.static_memory_maximum_size, 1 << 20);
+    assert_eq!(summary.static_memory_guard_size, 1 << 16);
+    assert_eq!(summary.static_memory_guard_before_size, 1 << 17);
+    assert_eq!(summary.dynamic_memory_guard_before_size, 1 << 17);
+    assert_eq!(summary.allocation_strategy, "pooling");
+
+    // This isn't set in the config, but the pooling allocator always treats
+    // memories as static ones.
+    assert!(summary.static_memory_bound_is_maximum);
+    Ok(())
+}
+
+#[test]
+fn no_guard_before_linear_memory() -> Result<()> {
+    let mut config = Config::new();
+    config
+        .guard_before_linear_memory(false)
+        .guard_before_linear_memory_size(1 << 17);
+    let summary = Engine::new(&config)?.config_summary();
+    assert!(!summary.guard_before_linear_memory);
+    assert_eq!(summary.static_memory_guard_before_size, 0);
+    assert_eq!(summary.dynamic_memory_guard_before_size, 0);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "serde-config-summary")]
+fn serializes_to_json() -> Result<()> {
+    let summary = Engine::new(Config::new().consume_fuel(true))?.config_summary();
+    let json: serde_json::Value = serde_json::to_value(&summary)?;
+    assert_eq!(json["consume_fuel"], true);
+    assert_eq!(json["opt_level"], "Speed");
+    assert_eq!(json["wasm_features"]["multi_value"], true);
+    assert_eq!(json["flags"]["opt_level"], "speed");
+    assert_eq!(json["allocation_strategy"], "on-demand");
+    assert!(json["isa_flags"].is_object());
+    Ok(())
+}
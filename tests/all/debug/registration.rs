@@ -0,0 +1,223 @@
+#![allow(dead_code)]
+
+use anyhow::Result;
+use gimli::{EndianSlice, LittleEndian};
+use object::read::{File, Object, ObjectSection};
+use wasmtime::{Config, Engine, Module};
+
+/// Finds the addresses of rows in the line programs of `image` which are for
+/// `line` of `file`, along with the address range of the code in `image`.
+fn lookup_line(image: &[u8], file: &str, line: u64) -> Result<(Vec<u64>, (u64, u64))> {
+    let obj = File::parse(image)?;
+    let text = obj.section_by_name(".text").unwrap();
+    let code = (text.address(), text.address() + text.size());
+
+    let dwarf = gimli::Dwarf::load(|id| -> Result<_> {
+        let data = match obj.section_by_name(id.name()) {
+            Some(section) => section.data()?,
+            None => &[],
+        };
+        Ok(EndianSlice::new(data, LittleEndian))
+    })?;
+
+    let mut addresses = Vec::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let program = match unit.line_program.clone() {
+            Some(program) => program,
+            None => continue,
+        };
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            if row.end_sequence() || row.line().map(|l| l.get()) != Some(line) {
+                continue;
+            }
+            let row_file = match row.file(header) {
+                Some(row_file) => row_file,
+                None => continue,
+            };
+            let name = dwarf.attr_string(&unit, row_file.path_name())?;
+            if name.to_string_lossy().ends_with(file) {
+                addresses.push(row.address());
+            }
+        }
+    }
+    Ok((addresses, code))
+}
+
+#[test]
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn debug_info_survives_serialization() -> Result<()> {
+    let mut config = Config::new();
+    config.debug_info(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::from_file(&engine, "tests/all/debug/testsuite/fib-wasm.wasm")?;
+    let bytes = module.serialize()?;
+    drop(module);
+
+    let module = unsafe { Module::deserialize(&engine, &bytes)? };
+
+    // The deserialized module is registered with debuggers, and a breakpoint
+    // on the body of the loop in `fib` resolves into its code.
+    let mut found = false;
+    for image in wasmtime_runtime::registered_gdb_jit_images() {
+        let (addresses, (start, end)) = lookup_line(&image, "fib-wasm.c", 11)?;
+        for address in addresses {
+            assert!(start <= address && address < end);
+            found = true;
+        }
+    }
+    assert!(
+        found,
+        "no registered image has a line row for fib-wasm.c:11"
+    );
+
+    drop(module);
+    Ok(())
+}
+
+/// Returns the offsets, relative to the code section as DWARF addresses for
+/// wasm are, of the first instruction of each function body in `wasm`, along
+/// with the offset of each body's end.
+fn function_body_offsets(wasm: &[u8]) -> Result<Vec<(u64, u64)>> {
+    let mut code_start = 0;
+    let mut bodies = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            wasmparser::Payload::CodeSectionStart { range, .. } => code_start = range.start,
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                let mut ops = body.get_operators_reader()?;
+                let (_, first) = ops.read_with_offset()?;
+                bodies.push((
+                    (first - code_start) as u64,
+                    (body.range().end - code_start) as u64,
+                ));
+            }
+            _ => {}
+        }
+    }
+    Ok(bodies)
+}
+
+fn write_u32_leb128(out: &mut Vec<u8>, mut n: u32) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[test]
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn line_rows_of_functions_sharing_a_sequence() -> Result<()> {
+    use gimli::write::{
+        Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections,
+    };
+
+    let mut wasm = wat::parse_str(
+        r#"
+            (module
+                (func (export "a") (result i32)
+                    i32.const 1)
+                (func (export "b") (result i32)
+                    i32.const 2))
+        "#,
+    )?;
+    let bodies = function_body_offsets(&wasm)?;
+    let ((a, _), (b, end)) = (bodies[0], bodies[1]);
+
+    // A single sequence covers both functions, with line 10 in `a` and line
+    // 20 in `b`.
+    let encoding = gimli::Encoding {
+        format: gimli::Format::Dwarf32,
+        version: 4,
+        address_size: 4,
+    };
+    let mut program = LineProgram::new(
+        encoding,
+        gimli::LineEncoding::default(),
+        LineString::String(b"/tmp".to_vec()),
+        LineString::String(b"shared-sequence.c".to_vec()),
+        None,
+    );
+    let file = program.add_file(
+        LineString::String(b"shared-sequence.c".to_vec()),
+        program.default_directory(),
+        None,
+    );
+    program.begin_sequence(Some(Address::Constant(a)));
+    for (address, line) in [(a, 10), (b, 20)].iter() {
+        program.row().address_offset = address - a;
+        program.row().file = file;
+        program.row().line = *line;
+        program.generate_row();
+    }
+    program.end_sequence(end - a);
+
+    let mut dwarf = DwarfUnit::new(encoding);
+    dwarf.unit.line_program = program;
+    let name = dwarf.strings.add("shared-sequence.c");
+    let comp_dir = dwarf.strings.add("/tmp");
+    let root = dwarf.unit.get_mut(dwarf.unit.root());
+    root.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+    root.set(gimli::DW_AT_comp_dir, AttributeValue::StringRef(comp_dir));
+    root.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(0)),
+    );
+    let mut sections = Sections::new(EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections)?;
+    sections.for_each(|id, data| -> Result<()> {
+        let data = data.slice();
+        if data.is_empty() {
+            return Ok(());
+        }
+        // A custom section: its id, size, and then the name and contents.
+        let name = id.name().as_bytes();
+        let mut payload = Vec::new();
+        write_u32_leb128(&mut payload, name.len() as u32);
+        payload.extend_from_slice(name);
+        payload.extend_from_slice(data);
+        wasm.push(0);
+        write_u32_leb128(&mut wasm, payload.len() as u32);
+        wasm.extend_from_slice(&payload);
+        Ok(())
+    })?;
+
+    let mut config = Config::new();
+    config.debug_info(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, &wasm)?;
+
+    // Both functions' lines resolve into the code, not only the lines of the
+    // function the sequence starts in.
+    for line in [10, 20].iter() {
+        let mut found = false;
+        for image in wasmtime_runtime::registered_gdb_jit_images() {
+            let (addresses, (start, end)) = lookup_line(&image, "shared-sequence.c", *line)?;
+            for address in addresses {
+                assert!(start <= address && address < end);
+                found = true;
+            }
+        }
+        assert!(
+            found,
+            "no registered image has a line row for shared-sequence.c:{}",
+            line
+        );
+    }
+
+    drop(module);
+    Ok(())
+}
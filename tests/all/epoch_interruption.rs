@@ -0,0 +1,122 @@
+use anyhow::Result;
+use wasmtime::*;
+
+// Each module imports `tick`, which advances the epoch of the engine by one
+// and counts the ticks in the store's data, so that the tests don't depend on
+// timing.
+fn build(config: &mut Config, wat: &str) -> Result<(Store<u64>, TypedFunc<(), ()>)> {
+    let engine = Engine::new(config.epoch_interruption(true))?;
+    let module = Module::new(&engine, wat)?;
+    let mut store = Store::new(&engine, 0);
+    let tick = Func::wrap(&mut store, |mut caller: Caller<'_, u64>| {
+        caller.engine().increment_epoch();
+        *caller.data_mut() += 1;
+    });
+    let instance = Instance::new(&mut store, &module, &[tick.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    Ok((store, run))
+}
+
+const TICKING_LOOP: &str = r#"
+    (module
+        (import "" "tick" (func $tick))
+        (func (export "run")
+            (loop
+                call $tick
+                br 0)))
+"#;
+
+#[test]
+fn traps_at_deadline() -> Result<()> {
+    let (mut store, run) = build(&mut Config::new(), TICKING_LOOP)?;
+    store.set_epoch_deadline(5);
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+
+    // The loop header is checked on every iteration, so the loop stops as
+    // soon as the deadline is reached.
+    assert_eq!(*store.data(), 5);
+    Ok(())
+}
+
+#[test]
+fn deadline_starts_reached() -> Result<()> {
+    let (mut store, run) = build(
+        &mut Config::new(),
+        r#"(module (import "" "tick" (func)) (func (export "run")))"#,
+    )?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+
+    store.set_epoch_deadline(1);
+    run.call(&mut store, ())?;
+    Ok(())
+}
+
+#[test]
+fn recursion_is_checked_on_entry() -> Result<()> {
+    let (mut store, run) = build(
+        &mut Config::new(),
+        r#"
+            (module
+                (import "" "tick" (func $tick))
+                (func $run (export "run")
+                    call $tick
+                    call $run))
+        "#,
+    )?;
+    store.set_epoch_deadline(10);
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    assert_eq!(*store.data(), 10);
+    Ok(())
+}
+
+#[test]
+fn callback_extends_deadline() -> Result<()> {
+    let (mut store, run) = build(&mut Config::new(), TICKING_LOOP)?;
+    store.epoch_deadline_callback(|ticks| {
+        if *ticks == 9 {
+            return Err(Trap::new("out of time slices"));
+        }
+        Ok(3)
+    });
+    store.set_epoch_deadline(3);
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(
+        trap.to_string().contains("out of time slices"),
+        "bad trap: {}",
+        trap
+    );
+    assert_eq!(*store.data(), 9);
+
+    // Trapping can be configured again.
+    store.epoch_deadline_trap();
+    store.set_epoch_deadline(1);
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    assert_eq!(*store.data(), 10);
+    Ok(())
+}
+
+#[test]
+fn coexists_with_fuel() -> Result<()> {
+    let (mut store, run) = build(Config::new().consume_fuel(true), TICKING_LOOP)?;
+
+    // Fuel runs out before the deadline is reached...
+    store.set_epoch_deadline(u64::MAX);
+    store.add_fuel(1_000)?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(
+        trap.to_string().contains("all fuel consumed"),
+        "bad trap: {}",
+        trap
+    );
+
+    // ... and the deadline is reached before fuel runs out.
+    store.set_epoch_deadline(5);
+    store.add_fuel(1_000_000)?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    Ok(())
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use wasmtime::*;
+
+fn kind(err: &anyhow::Error) -> &ErrorKind {
+    err.downcast_ref::<wasmtime::Error>()
+        .unwrap_or_else(|| panic!("not a `wasmtime::Error`: {:?}", err))
+        .kind()
+}
+
+#[test]
+fn invalid_modules_are_validation_errors() -> Result<()> {
+    let engine = Engine::default();
+    let err = Module::new(&engine, "(module").err().unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Validation));
+
+    let err = Module::new(&engine, b"\0asm\x01\0\0\0\x01").err().unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Validation));
+
+    let err = Module::new(&engine, "(module (func (result i32)))")
+        .err()
+        .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Validation));
+
+    // Using a proposal which isn't enabled is also a validation error, and the
+    // `InvalidModule` is still available.
+    let engine = Engine::new(Config::new().wasm_simd(false))?;
+    let err = Module::new(&engine, "(module (func (param v128)))")
+        .err()
+        .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Validation));
+    assert!(err.is::<InvalidModule>());
+    // The original error is still the root of the chain.
+    assert!(matches!(
+        err.downcast_ref::<wasmtime_jit::SetupError>(),
+        Some(wasmtime_jit::SetupError::Compile(
+            wasmtime_environ::CompileError::Wasm(_)
+        ))
+    ));
+    assert!(
+        Module::validate(&engine, &wat::parse_str("(module (func (param v128)))")?)
+            .unwrap_err()
+            .is::<wasmtime::Error>()
+    );
+    Ok(())
+}
+
+#[test]
+fn classification_is_context() -> Result<()> {
+    let engine = Engine::default();
+    let err = Module::new(&engine, "(module (func (result i32)))")
+        .err()
+        .unwrap();
+    let error = err.downcast_ref::<wasmtime::Error>().unwrap();
+    let chain = err.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+    let inner = err.downcast_ref::<InvalidModule>().unwrap().to_string();
+    assert_eq!(chain[0], error.to_string());
+    assert_eq!(chain[1], inner);
+    assert!(chain.len() > 2);
+
+    // Classifying doesn't change what the error displays as.
+    assert_eq!(err.to_string(), inner);
+
+    // The error which the classification is attached to can still be
+    // recovered by value.
+    let module = Module::new(&engine, "(module (func $f unreachable) (start $f))")?;
+    let mut store = Store::new(&engine, ());
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    let trap = err.downcast::<Trap>()?;
+    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    Ok(())
+}
+
+#[test]
+fn unsupported_operators_are_compilation_errors() -> Result<()> {
+    let engine = Engine::new(Config::new().wasm_simd(true))?;
+    let err = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (param v128) (result v128)
+                    local.get 0
+                    i16x8.extadd_pairwise_i8x16_s))
+        "#,
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Compilation));
+    assert!(
+        format!("{:?}", err).contains("proposed simd operator"),
+        "bad error: {:?}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn missing_files_are_io_errors() {
+    let engine = Engine::default();
+    let err = Module::from_file(&engine, "/path/to/nowhere.wasm")
+        .err()
+        .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Io));
+    assert!(err
+        .chain()
+        .any(|e| e.downcast_ref::<std::io::Error>().is_some()));
+}
+
+#[test]
+fn bad_artifacts_are_incompatible() -> Result<()> {
+    let engine = Engine::default();
+    let err = unsafe { Module::deserialize(&engine, b"not a module") }
+        .err()
+        .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::IncompatibleArtifact));
+
+    // A module compiled with other settings isn't compatible either.
+    let other = Engine::new(Config::new().consume_fuel(true))?;
+    let bytes = other.precompile_module(b"(module)")?;
+    let err = unsafe { Module::deserialize(&engine, &bytes) }
+        .err()
+        .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::IncompatibleArtifact));
+    Ok(())
+}
+
+#[test]
+fn missing_and_mismatched_imports() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, r#"(module (import "" "f" (func (param i32))))"#)?;
+    let mut store = Store::new(&engine, ());
+
+    let err = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::IncompatibleImport));
+
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::IncompatibleImport));
+
+    let f = Func::wrap(&mut store, || {});
+    let err = Instance::new(&mut store, &module, &[f.into()]).unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::IncompatibleImport));
+    assert!(err.to_string().contains("incompatible import type"));
+    Ok(())
+}
+
+#[test]
+fn start_traps_carry_the_trap() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, "(module (func $f unreachable) (start $f))")?;
+    let mut store = Store::new(&engine, ());
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    match kind(&err) {
+        ErrorKind::StartTrap(trap) => {
+            assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached))
+        }
+        kind => panic!("unexpected kind {:?}", kind),
+    }
+
+    // So do traps while initializing tables and memories.
+    let module = Module::new(
+        &engine,
+        r#"(module (memory 1) (data (i32.const 65536) "x"))"#,
+    )?;
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::StartTrap(_)));
+
+    // A host function which instantiates and propagates the error with `?`
+    // traps with the start function's trap.
+    assert_eq!(
+        Trap::from(err).trap_code(),
+        Some(TrapCode::MemoryOutOfBounds)
+    );
+    Ok(())
+}
+
+#[test]
+fn limits_are_resource_exhaustion() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, "(module (memory 2))")?;
+    let mut store = Store::new(&engine, StoreLimitsBuilder::new().memory_pages(1).build());
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::ResourceExhausted));
+    // The classified error is still the root of the chain.
+    assert!(err
+        .downcast_ref::<wasmtime_runtime::InstantiationError>()
+        .is_some());
+
+    let module = Module::new(&engine, "(module)")?;
+    let mut store = Store::new(&engine, StoreLimitsBuilder::new().instances(1).build());
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    Instance::new(&mut store, &module, &[])?;
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::ResourceExhausted));
+    Ok(())
+}
+
+#[test]
+fn module_limits_are_limit_errors() -> Result<()> {
+    let engine = Engine::new(Config::new().max_memory_pages(1).max_function_params(2))?;
+    let err = Module::new(&engine, "(module (memory 2))").err().unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Limit));
+    let err = Module::new(&engine, "(module (func (param i32 i32 i32)))")
+        .err()
+        .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Limit));
+
+    // Precompiled modules are held to the same limits.
+    let bytes = Engine::default().precompile_module(b"(module (func (param i32 i32 i32)))")?;
+    let err = unsafe { Module::deserialize(&engine, &bytes) }
+        .err()
+        .unwrap();
+    assert!(matches!(kind(&err), ErrorKind::Limit), "{:?}", err);
+    Ok(())
+}
+
+#[test]
+fn misuse_is_a_usage_error() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, "(module)")?;
+    let mut store = Store::new(&Engine::default(), ());
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::Usage));
+
+    let module = Module::new(&engine, r#"(module (import "" "" (func)))"#)?;
+    let mut other = Store::new(&engine, ());
+    let f = Func::wrap(&mut other, || {});
+    let mut store = Store::new(&engine, ());
+    let err = Instance::new(&mut store, &module, &[f.into()]).unwrap_err();
+    assert!(matches!(kind(&err), ErrorKind::Usage));
+    Ok(())
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+use wasmtime::*;
+
+fn module_usage(config: &Config, wat: &str) -> Result<FeatureUsage> {
+    let engine = Engine::new(config)?;
+    let module = Module::new(&engine, wat)?;
+    Ok(module.feature_usage())
+}
+
+fn invalid_usage(config: &Config, wat: &str) -> Result<FeatureUsage> {
+    let engine = Engine::new(config)?;
+    let err = Module::new(&engine, wat).err().unwrap();
+    let invalid = err
+        .downcast_ref::<InvalidModule>()
+        .expect("translation errors should be an `InvalidModule`");
+    Ok(invalid.feature_usage())
+}
+
+fn proposals(usage: FeatureUsage) -> Vec<&'static str> {
+    usage.proposals().collect()
+}
+
+#[test]
+fn mvp() -> Result<()> {
+    let usage = module_usage(
+        &Config::new(),
+        r#"
+            (module
+                (import "" "f" (func (param i32)))
+                (memory 1)
+                (table 1 funcref)
+                (global (mut i64) (i64.const 0))
+                (elem (i32.const 0) 0)
+                (data (i32.const 0) "x")
+                (func (param f32) (result i32)
+                    (call_indirect (type 0) (i32.const 0) (i32.const 0))
+                    (i32.trunc_f32_s (local.get 0))))
+        "#,
+    )?;
+    assert!(usage.is_mvp());
+    assert_eq!(usage, FeatureUsage::default());
+    assert_eq!(usage.to_string(), "no proposals beyond the MVP");
+    Ok(())
+}
+
+#[test]
+fn sign_extension() -> Result<()> {
+    let usage = module_usage(
+        &Config::new(),
+        "(module (func (param i64) (result i64) (i64.extend32_s (local.get 0))))",
+    )?;
+    assert!(usage.sign_extension);
+    assert_eq!(proposals(usage), ["sign-extension"]);
+    Ok(())
+}
+
+#[test]
+fn saturating_float_to_int() -> Result<()> {
+    let usage = module_usage(
+        &Config::new(),
+        "(module (func (param f64) (result i32) (i32.trunc_sat_f64_u (local.get 0))))",
+    )?;
+    assert!(usage.saturating_float_to_int);
+    assert_eq!(proposals(usage), ["saturating float-to-int"]);
+    Ok(())
+}
+
+#[test]
+fn multi_value() -> Result<()> {
+    let usage = module_usage(
+        &Config::new(),
+        "(module (func (result i32 i32) (i32.const 0) (i32.const 1)))",
+    )?;
+    assert!(usage.multi_value);
+    assert_eq!(proposals(usage), ["multi-value"]);
+
+    let usage = module_usage(
+        &Config::new(),
+        r#"
+            (module
+                (func (result i32)
+                    (i32.const 1)
+                    (block (param i32) (result i32)
+                        (i32.add (i32.const 1)))))
+        "#,
+    )?;
+    assert_eq!(proposals(usage), ["multi-value"]);
+    Ok(())
+}
+
+#[test]
+fn bulk_memory() -> Result<()> {
+    let usage = module_usage(
+        &Config::new(),
+        r#"
+            (module
+                (memory 1)
+                (func (memory.fill (i32.const 0) (i32.const 0) (i32.const 10))))
+        "#,
+    )?;
+    assert!(usage.bulk_memory);
+    assert_eq!(proposals(usage), ["bulk memory"]);
+
+    let usage = module_usage(&Config::new(), r#"(module (memory 1) (data "passive"))"#)?;
+    assert_eq!(proposals(usage), ["bulk memory"]);
+    Ok(())
+}
+
+#[test]
+fn reference_types() -> Result<()> {
+    let usage = module_usage(
+        &Config::new(),
+        "(module (func (param externref) (result i32) (ref.is_null (local.get 0))))",
+    )?;
+    assert!(usage.reference_types);
+    assert_eq!(proposals(usage), ["reference types"]);
+
+    let usage = module_usage(
+        &Config::new(),
+        "(module (table 1 funcref) (table 1 funcref))",
+    )?;
+    assert_eq!(proposals(usage), ["reference types"]);
+    Ok(())
+}
+
+#[test]
+fn simd() -> Result<()> {
+    let wat = r#"
+        (module
+            (func (param v128 v128) (result v128)
+                (i32x4.mul (i32x4.add (local.get 0) (local.get 1)) (local.get 1))))
+    "#;
+    let usage = module_usage(Config::new().wasm_simd(true), wat)?;
+    assert!(usage.simd);
+    assert_eq!(usage.simd_instructions, 2);
+    assert_eq!(proposals(usage), ["SIMD"]);
+
+    // The usage is still reported when SIMD isn't enabled.
+    assert_eq!(invalid_usage(Config::new().wasm_simd(false), wat)?, usage);
+    Ok(())
+}
+
+#[test]
+fn threads() -> Result<()> {
+    let usage = invalid_usage(
+        Config::new().wasm_threads(false),
+        r#"
+            (module
+                (memory 1)
+                (func (result i32) (i32.atomic.load (i32.const 0))))
+        "#,
+    )?;
+    assert!(usage.threads);
+    assert_eq!(usage.atomic_instructions, 1);
+    assert_eq!(proposals(usage), ["threads"]);
+
+    let usage = invalid_usage(&Config::new(), "(module (memory 1 1 shared))")?;
+    assert_eq!(usage.atomic_instructions, 0);
+    assert_eq!(proposals(usage), ["threads"]);
+    Ok(())
+}
+
+#[test]
+fn multi_memory() -> Result<()> {
+    let usage = module_usage(
+        Config::new().wasm_multi_memory(true),
+        "(module (memory 1) (memory 1))",
+    )?;
+    assert!(usage.multi_memory);
+    assert_eq!(proposals(usage), ["multi-memory"]);
+    Ok(())
+}
+
+#[test]
+fn memory64() -> Result<()> {
+    let usage = invalid_usage(&Config::new(), "(module (memory i64 1))")?;
+    assert!(usage.memory64);
+    assert_eq!(proposals(usage), ["memory64"]);
+    Ok(())
+}
+
+#[test]
+fn tail_call() -> Result<()> {
+    let usage = invalid_usage(&Config::new(), "(module (func (return_call 0)))")?;
+    assert!(usage.tail_call);
+    assert_eq!(proposals(usage), ["tail calls"]);
+    Ok(())
+}
+
+#[test]
+fn exceptions() -> Result<()> {
+    let usage = invalid_usage(&Config::new(), "(module (event $e) (func (throw $e)))")?;
+    assert!(usage.exceptions);
+    assert_eq!(proposals(usage), ["exceptions"]);
+    Ok(())
+}
+
+#[test]
+fn module_linking() -> Result<()> {
+    let wat = r#"
+        (module
+            (module $m (func (export "f")))
+            (instance (instantiate $m)))
+    "#;
+    let usage = module_usage(Config::new().wasm_module_linking(true), wat)?;
+    assert!(usage.module_linking);
+    assert_eq!(proposals(usage), ["module linking"]);
+    Ok(())
+}
+
+#[test]
+fn nested_module_usage_is_included() -> Result<()> {
+    let usage = module_usage(
+        Config::new().wasm_module_linking(true).wasm_simd(true),
+        r#"
+            (module
+                (module
+                    (func (param v128) (result v128)
+                        (i8x16.neg (local.get 0)))))
+        "#,
+    )?;
+    assert_eq!(usage.simd_instructions, 1);
+    assert_eq!(proposals(usage), ["SIMD", "module linking"]);
+    assert_eq!(usage.to_string(), "SIMD, module linking");
+    Ok(())
+}
+
+#[test]
+fn usage_survives_serialization() -> Result<()> {
+    let engine = Engine::new(Config::new().wasm_simd(true))?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (param v128) (result v128 i32)
+                    (i8x16.abs (local.get 0))
+                    (i32.extend8_s (i32.const 0))))
+        "#,
+    )?;
+    let usage = module.feature_usage();
+    assert_eq!(proposals(usage), ["sign-extension", "multi-value", "SIMD"]);
+
+    let module = unsafe { Module::deserialize(&engine, module.serialize()?)? };
+    assert_eq!(module.feature_usage(), usage);
+    Ok(())
+}
+
+#[test]
+fn invalid_module_error_message() -> Result<()> {
+    let engine = Engine::new(Config::new().wasm_simd(false))?;
+    let err = Module::new(&engine, "(module (func (param v128)))")
+        .err()
+        .unwrap();
+    assert!(err.is::<InvalidModule>());
+    let chain = format!("{:?}", err);
+    assert!(chain.contains("WebAssembly failed to compile"), "{}", chain);
+    assert!(chain.contains("SIMD support is not enabled"), "{}", chain);
+
+    // Errors other than translation errors aren't wrapped.
+    let err = Module::new(&engine, "(module").err().unwrap();
+    assert!(!err.is::<InvalidModule>());
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use wasmtime::*;
+use wasmtime_environ::wasm::DefinedFuncIndex;
 use wast::parser::{self, Parse, ParseBuffer, Parser};
 
 mod kw {
@@ -120,5 +121,356 @@ fn iloop() {
             "bad error: {}",
             error
         );
+        let trap = error.downcast::<Trap>().unwrap();
+        assert_eq!(trap.trap_code(), Some(TrapCode::OutOfFuel));
     }
 }
+
+/// Two functions which each count their argument down to zero in a loop.
+pub(crate) const COUNTDOWN_LOOPS: &str = r#"
+    (module
+        (func (export "annotated") (param i32)
+            (loop $l
+                local.get 0
+                i32.const 1
+                i32.sub
+                local.tee 0
+                br_if $l))
+        (func (export "hot") (param i32)
+            (loop $l
+                local.get 0
+                i32.const 1
+                i32.sub
+                local.tee 0
+                br_if $l))
+    )
+"#;
+
+/// Returns the function index and module byte offset of every `loop`
+/// instruction in `wasm`, which must not import any functions.
+pub(crate) fn loop_offsets(wasm: &[u8]) -> Vec<(u32, u32)> {
+    let mut offsets = Vec::new();
+    let mut func = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let wasmparser::Payload::CodeSectionEntry(body) = payload.unwrap() {
+            let mut reader = body.get_operators_reader().unwrap();
+            while !reader.eof() {
+                let (op, offset) = reader.read_with_offset().unwrap();
+                if let wasmparser::Operator::Loop { .. } = op {
+                    offsets.push((func, offset as u32));
+                }
+            }
+            func += 1;
+        }
+    }
+    offsets
+}
+
+/// Appends a `wasmtime.yieldpoints` custom section listing `yieldpoints` to
+/// `wasm`.
+pub(crate) fn with_yieldpoints(mut wasm: Vec<u8>, yieldpoints: &[(u32, u32)]) -> Vec<u8> {
+    fn leb(dst: &mut Vec<u8>, mut n: u32) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                dst.push(byte);
+                break;
+            }
+            dst.push(byte | 0x80);
+        }
+    }
+
+    let name = "wasmtime.yieldpoints";
+    let mut payload = Vec::new();
+    leb(&mut payload, name.len() as u32);
+    payload.extend_from_slice(name.as_bytes());
+    leb(&mut payload, yieldpoints.len() as u32);
+    for (func, offset) in yieldpoints {
+        leb(&mut payload, *func);
+        leb(&mut payload, *offset);
+    }
+    wasm.push(0);
+    leb(&mut wasm, payload.len() as u32);
+    wasm.extend_from_slice(&payload);
+    wasm
+}
+
+#[test]
+fn yieldpoints_replace_loop_header_checks() -> Result<()> {
+    let wasm = wat::parse_str(COUNTDOWN_LOOPS)?;
+    let annotated_loop = loop_offsets(&wasm)[0];
+    let wasm = with_yieldpoints(wasm, &[annotated_loop]);
+
+    let mut config = Config::new();
+    config
+        .consume_fuel(true)
+        .yieldpoints_from_custom_section(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, &wasm)?;
+
+    // The unannotated loop is never checked, so it runs to completion even
+    // though it burns through far more fuel than it was given.
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(10_000)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let hot = instance.get_typed_func::<i32, (), _>(&mut store, "hot")?;
+    hot.call(&mut store, 100_000)?;
+    assert!(store.fuel_consumed().unwrap() > 100_000);
+
+    // ... while the annotated one stops once fuel runs out.
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(10_000)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let annotated = instance.get_typed_func::<i32, (), _>(&mut store, "annotated")?;
+    let trap = annotated.call(&mut store, 100_000).unwrap_err();
+    assert!(trap.to_string().contains("all fuel consumed"), "{}", trap);
+
+    // Without opting in, the section is ignored and every loop is checked.
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let module = Module::new(&engine, &wasm)?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(10_000)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let hot = instance.get_typed_func::<i32, (), _>(&mut store, "hot")?;
+    let trap = hot.call(&mut store, 100_000).unwrap_err();
+    assert!(trap.to_string().contains("all fuel consumed"), "{}", trap);
+    Ok(())
+}
+
+#[test]
+fn invalid_yieldpoints_rejected() -> Result<()> {
+    let wasm = wat::parse_str(COUNTDOWN_LOOPS)?;
+    let (func, offset) = loop_offsets(&wasm)[0];
+    let mut config = Config::new();
+    config
+        .consume_fuel(true)
+        .yieldpoints_from_custom_section(true);
+    let engine = Engine::new(&config)?;
+
+    // `loop` is followed by its block type, so the byte after it isn't the
+    // start of an instruction.
+    let err = Module::new(
+        &engine,
+        &with_yieldpoints(wasm.clone(), &[(func, offset + 1)]),
+    )
+    .err()
+    .unwrap();
+    assert!(
+        format!("{:?}", err).contains("isn't on an instruction boundary"),
+        "{:?}",
+        err
+    );
+
+    let err = Module::new(&engine, &with_yieldpoints(wasm, &[(2, offset)]))
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("isn't defined by the module"),
+        "{:?}",
+        err
+    );
+    Ok(())
+}
+
+const METERED_HOST: &str = r#"
+    (module
+        (import "" "charge" (func $charge (param i64) (result i64)))
+        (import "" "drain" (func $drain))
+        (func (export "charge") (param i64) (result i64)
+            local.get 0
+            call $charge)
+        (func (export "drain_then_spin") (param i32)
+            call $drain
+            (loop
+                local.get 0
+                i32.const -1
+                i32.add
+                local.tee 0
+                br_if 0)))
+"#;
+
+fn metered_host_imports(store: &mut Store<()>) -> [Extern; 2] {
+    let charge = Func::wrap(
+        &mut *store,
+        |mut caller: Caller<'_, ()>, fuel: i64| -> Result<i64, Trap> {
+            Ok(caller.consume_fuel(fuel as u64)? as i64)
+        },
+    );
+    let drain = Func::wrap(
+        &mut *store,
+        |mut caller: Caller<'_, ()>| -> Result<(), Trap> {
+            let remaining = caller.consume_fuel(0)?;
+            assert_eq!(caller.consume_fuel(remaining)?, 0);
+            Ok(())
+        },
+    );
+    [charge.into(), drain.into()]
+}
+
+#[test]
+fn host_functions_consume_fuel() -> Result<()> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let module = Module::new(&engine, METERED_HOST)?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(10_000)?;
+    let imports = metered_host_imports(&mut store);
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    let charge = instance.get_typed_func::<i64, i64, _>(&mut store, "charge")?;
+    let drain_then_spin = instance.get_typed_func::<i32, (), _>(&mut store, "drain_then_spin")?;
+
+    // Fuel consumed by the host is counted along with the wasm calling it.
+    let remaining = charge.call(&mut store, 5_000)? as u64;
+    let consumed = store.fuel_consumed().unwrap();
+    assert!(consumed >= 5_000 && consumed < 5_010, "{}", consumed);
+    assert!(remaining >= 10_000 - consumed && remaining <= 5_000);
+
+    // Asking for more fuel than remains traps without consuming it.
+    let trap = charge.call(&mut store, 10_000).unwrap_err();
+    assert!(trap.to_string().contains("all fuel consumed"), "{}", trap);
+    assert!(store.fuel_consumed().unwrap() < 5_020);
+
+    // Once the host used up all the fuel, wasm traps when it next checks.
+    let trap = drain_then_spin.call(&mut store, 1_000).unwrap_err();
+    assert!(trap.to_string().contains("all fuel consumed"), "{}", trap);
+    assert!(store.fuel_consumed().unwrap() >= 10_000);
+    Ok(())
+}
+
+#[test]
+fn consume_fuel_outside_wasm() -> Result<()> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(100)?;
+    assert_eq!(store.consume_fuel(40)?, 60);
+    assert_eq!(store.consume_fuel(0)?, 60);
+    assert!(store.consume_fuel(61).is_err());
+    assert!(store.consume_fuel(u64::max_value()).is_err());
+    assert_eq!(store.consume_fuel(60)?, 0);
+    assert_eq!(store.fuel_consumed(), Some(100));
+
+    // Fuel added without limit can still be consumed from.
+    store.add_fuel(u64::max_value())?;
+    assert!(store.consume_fuel(1)? > u64::from(u32::max_value()));
+    assert_eq!(store.fuel_consumed(), Some(101));
+
+    let mut store = Store::new(&Engine::default(), ());
+    assert!(store.consume_fuel(1).is_err());
+    Ok(())
+}
+
+#[test]
+fn fuel_remaining() -> Result<()> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let module = Module::new(&engine, METERED_HOST)?;
+    let mut store = Store::new(&engine, ());
+    assert_eq!(store.fuel_remaining(), Some(0));
+    store.add_fuel(10_000)?;
+    assert_eq!(store.fuel_remaining(), Some(10_000));
+    let imports = metered_host_imports(&mut store);
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    let charge = instance.get_typed_func::<i64, i64, _>(&mut store, "charge")?;
+    let drain_then_spin = instance.get_typed_func::<i32, (), _>(&mut store, "drain_then_spin")?;
+
+    let remaining = charge.call(&mut store, 5_000)? as u64;
+    let consumed = store.fuel_consumed().unwrap();
+    assert_eq!(store.fuel_remaining(), Some(10_000 - consumed));
+    assert!(store.fuel_remaining().unwrap() <= remaining);
+
+    // Wasm overdrawing its fuel leaves none, rather than a negative amount.
+    drain_then_spin.call(&mut store, 1_000).unwrap_err();
+    assert_eq!(store.fuel_remaining(), Some(0));
+    store.add_fuel(u64::max_value())?;
+    assert!(store.fuel_remaining().unwrap() > u64::from(u32::max_value()));
+
+    let store = Store::new(&Engine::default(), ());
+    assert_eq!(store.fuel_remaining(), None);
+    Ok(())
+}
+
+#[test]
+fn function_fuel_consumed() -> Result<()> {
+    let wat = r#"
+        (module
+            (func $leaf (param i32)
+                (loop
+                    (br_if 0 (local.tee 0 (i32.sub (local.get 0) (i32.const 1))))))
+            (func $mid (param i32)
+                (call $leaf (local.get 0))
+                (call $leaf (local.get 0)))
+            (func (export "run") (param i32)
+                (call $mid (local.get 0))
+                (call $leaf (i32.const 1)))
+            (func (export "spin")
+                (loop (br 0))))
+    "#;
+    let mut config = Config::new();
+    config.consume_fuel(true).per_function_fuel_accounting(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, wat)?;
+    let mut store = Store::new(&engine, ());
+    assert_eq!(
+        store.function_fuel_consumed(&module),
+        Some(Default::default())
+    );
+    store.add_fuel(100_000)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+    let spin = instance.get_typed_func::<(), (), _>(&mut store, "spin")?;
+
+    run.call(&mut store, 100)?;
+    let consumed = store.function_fuel_consumed(&module).unwrap();
+    assert_eq!(consumed.len(), 3);
+    assert!(consumed[&DefinedFuncIndex::from_u32(0)] > consumed[&DefinedFuncIndex::from_u32(1)]);
+    assert!(consumed[&DefinedFuncIndex::from_u32(0)] > consumed[&DefinedFuncIndex::from_u32(2)]);
+    assert_eq!(
+        consumed.values().sum::<u64>(),
+        store.fuel_consumed().unwrap()
+    );
+
+    // Fuel consumed up to running out is attributed as well.
+    spin.call(&mut store, ()).unwrap_err();
+    let consumed = store.function_fuel_consumed(&module).unwrap();
+    assert!(consumed[&DefinedFuncIndex::from_u32(3)] > 0);
+    assert_eq!(
+        consumed.values().sum::<u64>(),
+        store.fuel_consumed().unwrap()
+    );
+
+    // Functions of other modules with the same indices have their own
+    // entries, even when they're compiled from the same binary.
+    let other = Module::new(&engine, wat)?;
+    assert_eq!(
+        store.function_fuel_consumed(&other),
+        Some(Default::default())
+    );
+    let before = store.function_fuel_consumed(&module).unwrap();
+    store.add_fuel(100_000)?;
+    let instance = Instance::new(&mut store, &other, &[])?;
+    let other_run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+    other_run.call(&mut store, 100)?;
+    assert_eq!(store.function_fuel_consumed(&module).unwrap(), before);
+    let consumed = store.function_fuel_consumed(&other).unwrap();
+    assert_eq!(consumed.len(), 3);
+    assert_eq!(
+        before.values().sum::<u64>() + consumed.values().sum::<u64>(),
+        store.fuel_consumed().unwrap()
+    );
+
+    // Nothing is reported unless both fuel and per-function accounting are
+    // enabled.
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let module = Module::new(&engine, wat)?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(100_000)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+    run.call(&mut store, 100)?;
+    assert_eq!(store.function_fuel_consumed(&module), None);
+
+    let engine = Engine::new(Config::new().per_function_fuel_accounting(true))?;
+    let module = Module::new(&engine, wat)?;
+    let store = Store::new(&engine, ());
+    assert_eq!(store.function_fuel_consumed(&module), None);
+    Ok(())
+}
@@ -594,6 +594,51 @@ fn typed_multiple_results() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(not(feature = "old-x86-backend"))]
+fn typed_three_mixed_results() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (func (export "f") (param i64 f32) (result f64 i32 i64)
+                    f64.const 1.5
+                    i32.const -2
+                    local.get 0)
+                (func (export "g") (result f32 i64 f64)
+                    f32.const 4.25
+                    i64.const 5
+                    f64.const 6.5)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let f = instance.get_typed_func::<(i64, f32), (f64, i32, i64), _>(&mut store, "f")?;
+    assert_eq!(f.call(&mut store, (i64::MAX, 0.))?, (1.5, -2, i64::MAX));
+    let g = instance.get_typed_func::<(), (f32, i64, f64), _>(&mut store, "g")?;
+    assert_eq!(g.call(&mut store, ())?, (4.25, 5, 6.5));
+
+    // Mismatches are reported when the typed function is created, along with
+    // which result doesn't match.
+    let err = instance
+        .get_typed_func::<(i64, f32), (f64, i32, f64), _>(&mut store, "f")
+        .err()
+        .unwrap();
+    let msg = format!("{:?}", err);
+    assert!(msg.contains("type mismatch with results"), "{}", msg);
+    assert!(msg.contains("type mismatch at index 2"), "{}", msg);
+    assert!(msg.contains("expected f64 found i64"), "{}", msg);
+
+    let err = instance
+        .get_typed_func::<(), (f32, i64), _>(&mut store, "g")
+        .err()
+        .unwrap();
+    let msg = format!("{:?}", err);
+    assert!(msg.contains("expected 2 types, found 3"), "{}", msg);
+    Ok(())
+}
+
 #[test]
 fn trap_doesnt_leak() -> anyhow::Result<()> {
     #[derive(Default)]
@@ -920,3 +965,197 @@ fn typed_funcs_count_params_correctly_in_error_messages() -> anyhow::Result<()>
 
     Ok(())
 }
+
+#[test]
+fn call_argument_val_errors() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let f = Func::wrap(&mut store, |_: i32, _: Option<Func>| {});
+
+    let err = f.call(&mut store, &[Val::I32(0), Val::I64(0)]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "argument type mismatch: found i64 but expected funcref for argument 1 of \
+         (func (param i32 funcref))"
+    );
+    match err.downcast_ref::<ValError>() {
+        Some(ValError::TypeMismatch {
+            site: ValSite::Argument { index: 1, func },
+            expected: ValType::FuncRef,
+            found: ValType::I64,
+        }) => assert_eq!(*func, f.ty(&store)),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let mut other_store = Store::new(store.engine(), ());
+    let other = Func::wrap(&mut other_store, || {});
+    let err = f
+        .call(&mut store, &[Val::I32(0), other.into()])
+        .unwrap_err();
+    assert!(err.to_string().contains("cross-`Store`"));
+    assert!(matches!(
+        err.downcast_ref::<ValError>(),
+        Some(ValError::CrossStore)
+    ));
+    Ok(())
+}
+
+#[cfg(feature = "raw-funcs")]
+mod raw_parts {
+    use std::sync::atomic::{AtomicU32, Ordering::SeqCst};
+    use std::sync::Arc;
+    use wasmtime::*;
+
+    unsafe extern "C" fn add(_callee: *mut u8, _caller: *mut u8, a: i32, b: i64) -> i64 {
+        i64::from(a) + b
+    }
+
+    unsafe extern "C" fn scale(_callee: *mut u8, _caller: *mut u8, a: f32, b: f64) -> f64 {
+        f64::from(a) * b
+    }
+
+    // These must not panic, since unwinding out of an `extern "C"` function
+    // is undefined behavior.
+    unsafe fn counter<'a>(callee: *mut u8) -> Option<&'a AtomicU32> {
+        Func::raw_host_state(callee)?
+            .downcast_ref::<Arc<AtomicU32>>()
+            .map(|c| &**c)
+    }
+
+    unsafe extern "C" fn count(callee: *mut u8, _caller: *mut u8) {
+        if let Some(calls) = counter(callee) {
+            calls.fetch_add(1, SeqCst);
+        }
+    }
+
+    unsafe extern "C" fn calls(callee: *mut u8, _caller: *mut u8) -> i32 {
+        match counter(callee) {
+            Some(calls) => calls.load(SeqCst) as i32,
+            None => -1,
+        }
+    }
+
+    #[test]
+    fn called_from_wasm() -> anyhow::Result<()> {
+        let mut store = Store::<()>::default();
+        let add = unsafe {
+            Func::from_raw_parts(
+                &mut store,
+                FuncType::new(vec![ValType::I32, ValType::I64], vec![ValType::I64]),
+                add as *const u8,
+                Box::new(()),
+            )?
+        };
+        let scale = unsafe {
+            Func::from_raw_parts(
+                &mut store,
+                FuncType::new(vec![ValType::F32, ValType::F64], vec![ValType::F64]),
+                scale as *const u8,
+                Box::new(()),
+            )?
+        };
+        let module = Module::new(
+            store.engine(),
+            r#"
+                (module
+                    (import "" "add" (func $add (param i32 i64) (result i64)))
+                    (import "" "scale" (func $scale (param f32 f64) (result f64)))
+                    (table funcref (elem $add))
+                    (func (export "add") (param i32 i64) (result i64)
+                        (call $add (local.get 0) (local.get 1)))
+                    (func (export "add_indirect") (param i32 i64) (result i64)
+                        (call_indirect (param i32 i64) (result i64)
+                            (local.get 0) (local.get 1) (i32.const 0)))
+                    (func (export "scale") (param f32 f64) (result f64)
+                        (call $scale (local.get 0) (local.get 1))))
+            "#,
+        )?;
+        let instance = Instance::new(&mut store, &module, &[add.into(), scale.into()])?;
+
+        let run = instance.get_typed_func::<(i32, i64), i64, _>(&mut store, "add")?;
+        assert_eq!(run.call(&mut store, (-1, 1 << 40))?, (1 << 40) - 1);
+        let run = instance.get_typed_func::<(i32, i64), i64, _>(&mut store, "add_indirect")?;
+        assert_eq!(run.call(&mut store, (2, 3))?, 5);
+        let run = instance.get_typed_func::<(f32, f64), f64, _>(&mut store, "scale")?;
+        assert_eq!(run.call(&mut store, (1.5, 3.0))?, 4.5);
+        Ok(())
+    }
+
+    #[test]
+    fn called_from_host() -> anyhow::Result<()> {
+        let mut store = Store::<()>::default();
+        let add = unsafe {
+            Func::from_raw_parts(
+                &mut store,
+                FuncType::new(vec![ValType::I32, ValType::I64], vec![ValType::I64]),
+                add as *const u8,
+                Box::new(()),
+            )?
+        };
+        assert_eq!(
+            add.ty(&store).params().collect::<Vec<_>>(),
+            [ValType::I32, ValType::I64]
+        );
+        let results = add.call(&mut store, &[Val::I32(4), Val::I64(5)])?;
+        assert_eq!(results[0].unwrap_i64(), 9);
+        let add = add.typed::<(i32, i64), i64, _>(&store)?;
+        assert_eq!(add.call(&mut store, (6, 7))?, 13);
+        Ok(())
+    }
+
+    #[test]
+    fn host_state() -> anyhow::Result<()> {
+        let mut store = Store::<()>::default();
+        let counter = Arc::new(AtomicU32::new(100));
+        let count = unsafe {
+            Func::from_raw_parts(
+                &mut store,
+                FuncType::new(None, None),
+                count as *const u8,
+                Box::new(counter.clone()),
+            )?
+        };
+        let calls = unsafe {
+            Func::from_raw_parts(
+                &mut store,
+                FuncType::new(None, Some(ValType::I32)),
+                calls as *const u8,
+                Box::new(counter.clone()),
+            )?
+        };
+        let count = count.typed::<(), (), _>(&store)?;
+        let calls = calls.typed::<(), i32, _>(&store)?;
+        assert_eq!(calls.call(&mut store, ())?, 100);
+        count.call(&mut store, ())?;
+        count.call(&mut store, ())?;
+        assert_eq!(calls.call(&mut store, ())?, 102);
+        assert_eq!(counter.load(SeqCst), 102);
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_types() {
+        let mut store = Store::<()>::default();
+        let mut create = |params: Vec<ValType>, results: Vec<ValType>| unsafe {
+            Func::from_raw_parts(
+                &mut store,
+                FuncType::new(params, results),
+                add as *const u8,
+                Box::new(()),
+            )
+            .unwrap_err()
+            .to_string()
+        };
+        assert_eq!(
+            create(vec![ValType::ExternRef], vec![]),
+            "unsupported parameter type for a raw function: externref"
+        );
+        assert_eq!(
+            create(vec![], vec![ValType::V128]),
+            "unsupported result type for a raw function: v128"
+        );
+        assert_eq!(
+            create(vec![], vec![ValType::I32, ValType::I32]),
+            "raw functions support at most one result, found 2"
+        );
+    }
+}
@@ -5,7 +5,14 @@
 //! use the Wasm binary by including it via
 //! `include_bytes!("./fuzzing/some-descriptive-name.wasm")`.
 
-use wasmtime::{Config, Strategy};
+use target_lexicon::PointerWidth;
+use wasmtime::{Config, Engine, Module, Strategy};
+use wasmtime_environ::isa::{CallConv, TargetFrontendConfig};
+use wasmtime_environ::wasm::wasmparser::{FuncValidator, FunctionBody, ValidatorResources};
+use wasmtime_environ::wasm::{
+    translate_module, DataIndex, ElemIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex,
+    ModuleEnvironment, Table, TableIndex, TargetEnvironment, TypeIndex, WasmFuncType, WasmResult,
+};
 use wasmtime_fuzzing::oracles::{self, Timeout};
 
 #[test]
@@ -27,3 +34,158 @@ fn instantiate_module_that_compiled_to_x64_has_register_32() {
     let data = wat::parse_str(include_str!("./fuzzing/issue694.wat")).unwrap();
     oracles::instantiate_with_config(&data, true, config, Timeout::None);
 }
+
+/// A module environment which only counts the entities handed to it.
+///
+/// Translating a module hands each entity in it to the environment once as
+/// it's parsed, so these counts bound how much of the input was parsed,
+/// including any parts which were parsed more than once.
+#[derive(Default)]
+struct CountingEnvironment {
+    entities: usize,
+    custom_sections: usize,
+    function_bodies: usize,
+}
+
+impl CountingEnvironment {
+    fn declare(&mut self) -> WasmResult<()> {
+        self.entities += 1;
+        Ok(())
+    }
+}
+
+impl TargetEnvironment for CountingEnvironment {
+    fn target_config(&self) -> TargetFrontendConfig {
+        TargetFrontendConfig {
+            default_call_conv: CallConv::SystemV,
+            pointer_width: PointerWidth::U64,
+        }
+    }
+}
+
+impl<'data> ModuleEnvironment<'data> for CountingEnvironment {
+    fn declare_type_func(&mut self, _: WasmFuncType) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_func_import(&mut self, _: TypeIndex, _: &str, _: Option<&str>) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_table_import(&mut self, _: Table, _: &str, _: Option<&str>) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_memory_import(&mut self, _: Memory, _: &str, _: Option<&str>) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_global_import(&mut self, _: Global, _: &str, _: Option<&str>) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_func_type(&mut self, _: TypeIndex) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_table(&mut self, _: Table) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_memory(&mut self, _: Memory) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_global(&mut self, _: Global) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_func_export(&mut self, _: FuncIndex, _: &str) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_table_export(&mut self, _: TableIndex, _: &str) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_memory_export(&mut self, _: MemoryIndex, _: &str) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_global_export(&mut self, _: GlobalIndex, _: &str) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_start_func(&mut self, _: FuncIndex) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_table_elements(
+        &mut self,
+        _: TableIndex,
+        _: Option<GlobalIndex>,
+        _: u32,
+        _: Box<[FuncIndex]>,
+    ) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_passive_element(&mut self, _: ElemIndex, _: Box<[FuncIndex]>) -> WasmResult<()> {
+        self.declare()
+    }
+    fn declare_passive_data(&mut self, _: DataIndex, _: &[u8]) -> WasmResult<()> {
+        self.declare()
+    }
+    fn define_function_body(
+        &mut self,
+        _: FuncValidator<ValidatorResources>,
+        _: FunctionBody<'data>,
+    ) -> WasmResult<()> {
+        self.function_bodies += 1;
+        self.declare()
+    }
+    fn declare_data_initialization(
+        &mut self,
+        _: MemoryIndex,
+        _: Option<GlobalIndex>,
+        _: u32,
+        _: &[u8],
+    ) -> WasmResult<()> {
+        self.declare()
+    }
+    fn custom_section(&mut self, _: &str, _: &[u8]) -> WasmResult<()> {
+        self.custom_sections += 1;
+        Ok(())
+    }
+}
+
+/// Asserts that `wasm` is rejected, and returns what its translation handed
+/// to the module environment before it stopped.
+fn assert_rejected(wasm: &[u8]) -> CountingEnvironment {
+    let result = Module::new(&Engine::default(), wasm);
+    assert!(result.is_err(), "malformed module was accepted");
+    let mut environ = CountingEnvironment::default();
+    let _ = translate_module(wasm, &mut environ);
+    environ
+}
+
+// Malformed inputs must be rejected with work linear in their size. Parsing
+// is done in a single pass, so these check that each entity before the
+// malformed part was seen exactly once rather than timing the rejection.
+
+#[test]
+fn reject_truncated_data_segment() {
+    let environ = assert_rejected(include_bytes!("./fuzzing/truncated-data-segment.wasm"));
+    // Only the memory is declared before the data section is found to be
+    // cut short.
+    assert_eq!(environ.entities, 1);
+}
+
+#[test]
+fn reject_truncated_custom_section() {
+    let environ = assert_rejected(include_bytes!("./fuzzing/truncated-custom-section.wasm"));
+    assert_eq!(environ.custom_sections, 0);
+}
+
+#[test]
+fn reject_many_sections_then_garbage() {
+    // 4096 tiny custom sections named "", followed by a section whose size
+    // runs past the end of the input.
+    let environ = assert_rejected(include_bytes!("./fuzzing/many-sections-then-garbage.wasm"));
+    assert_eq!(environ.custom_sections, 4096);
+}
+
+#[test]
+fn reject_function_body_without_end() {
+    // A body of 4095 `nop`s which is missing its final `end`. It's only
+    // rejected once the body is validated, so translating the module itself
+    // succeeds.
+    let environ = assert_rejected(include_bytes!("./fuzzing/function-body-without-end.wasm"));
+    assert_eq!(environ.function_bodies, 1);
+    assert_eq!(environ.entities, 3);
+}
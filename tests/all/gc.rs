@@ -424,3 +424,164 @@ fn global_init_no_leak() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn weak_externref_cache() -> anyhow::Result<()> {
+    let (mut store, module) = ref_types_module(
+        r#"
+            (module
+                (import "" "" (func $make (result externref)))
+                (global $g (mut externref) (ref.null extern))
+                (func (export "drop") call $make drop)
+                (func (export "keep") call $make global.set $g)
+                (func (export "get") (result externref) global.get $g)
+            )
+        "#,
+    )?;
+
+    // A host-side cache of the objects handed to wasm, which shouldn't keep
+    // them alive itself.
+    let cache = Arc::new(std::sync::Mutex::new(Vec::<WeakExternRef>::new()));
+    let dropped = Arc::new(AtomicBool::new(false));
+    let make = {
+        let cache = cache.clone();
+        let dropped = dropped.clone();
+        Func::wrap(&mut store, move || {
+            let r = ExternRef::new(SetFlagOnDrop(dropped.clone()));
+            cache.lock().unwrap().push(r.downgrade());
+            Some(r)
+        })
+    };
+    let instance = Instance::new(&mut store, &module, &[make.into()])?;
+
+    // Wasm drops its reference, so after a GC the cache can't find the object
+    // anymore.
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "drop")?;
+    run.call(&mut store, ())?;
+    store.gc();
+    let weak = cache.lock().unwrap().pop().unwrap();
+    assert!(weak.upgrade().is_none());
+    assert!(dropped.load(SeqCst));
+
+    // Wasm keeps its reference in a global, so the object survives a GC and
+    // the cache finds the same reference wasm holds.
+    dropped.store(false, SeqCst);
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "keep")?;
+    run.call(&mut store, ())?;
+    store.gc();
+    let weak = cache.lock().unwrap().pop().unwrap();
+    let upgraded = weak.upgrade().unwrap();
+    assert!(!dropped.load(SeqCst));
+    let get = instance.get_typed_func::<(), Option<ExternRef>, _>(&mut store, "get")?;
+    let held = get.call(&mut store, ())?.unwrap();
+    assert!(upgraded.ptr_eq(&held));
+    assert!(weak.ptr_eq(&held));
+    assert!(upgraded.data().is::<SetFlagOnDrop>());
+    drop((upgraded, held));
+
+    // Once the store, and with it the global, is gone, so is the object.
+    drop(store);
+    assert!(weak.upgrade().is_none());
+    assert!(dropped.load(SeqCst));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "gc-diagnostics")]
+fn gc_diagnostics_report_safepoints() -> anyhow::Result<()> {
+    let (mut store, module) = ref_types_module(
+        r#"
+            (module
+                (import "" "" (func $gc))
+                (func (export "f") (param externref) (result externref)
+                    call $gc
+                    local.get 0)
+                (func (export "g") (param i32) (result i32)
+                    call $gc
+                    local.get 0)
+            )
+        "#,
+    )?;
+    assert_eq!(store.gc_diagnostics().safepoints(), 0);
+
+    let gc = Func::wrap(&mut store, |mut caller: Caller<'_, ()>| caller.gc());
+    Instance::new(&mut store, &module, &[gc.into()])?;
+
+    let diagnostics = store.gc_diagnostics();
+    assert_eq!(diagnostics.modules().len(), 1);
+    let module = &diagnostics.modules()[0];
+    assert!(module.safepoints() > 0);
+    assert_eq!(module.safepoints(), diagnostics.safepoints());
+
+    // Only `f` has references live across a call.
+    assert_eq!(module.functions().len(), 1);
+    let func = &module.functions()[0];
+    assert_eq!(func.func_index(), 1);
+    assert!(module.start() <= func.start() && func.end() <= module.end());
+    for &pc in func.safepoints() {
+        assert!(func.start() <= pc && pc < func.end());
+        assert!(diagnostics.has_stack_map_for(pc));
+    }
+    assert!(!diagnostics.has_stack_map_for(func.start()));
+    assert!(!diagnostics.has_stack_map_for(func.end()));
+    Ok(())
+}
+
+#[test]
+fn refs_are_reclaimed_during_long_running_call() -> anyhow::Result<()> {
+    let (mut store, module) = ref_types_module(
+        r#"
+            (module
+                (import "" "make_ref" (func $make_ref (result externref)))
+                (import "" "check" (func $check))
+                (func (export "run") (param i32)
+                    (loop $l
+                        call $make_ref
+                        drop
+                        local.get 0
+                        i32.const 1
+                        i32.sub
+                        local.tee 0
+                        br_if $l)
+                    call $check)
+            )
+        "#,
+    )?;
+
+    const NUM_REFS: usize = 20_000;
+
+    let num_refs_dropped = Arc::new(AtomicUsize::new(0));
+    let make_ref = Func::wrap(&mut store, {
+        let num_refs_dropped = num_refs_dropped.clone();
+        move || Some(ExternRef::new(CountDrops(num_refs_dropped.clone())))
+    });
+
+    // None of the references are live across calls, so filling up the
+    // activations table collects all but the last few of them while wasm is
+    // still running.
+    let check = Func::wrap(&mut store, {
+        let num_refs_dropped = num_refs_dropped.clone();
+        move || {
+            let dropped = num_refs_dropped.load(SeqCst);
+            assert!(dropped > NUM_REFS / 2, "only {} refs dropped", dropped);
+        }
+    });
+
+    let instance = Instance::new(&mut store, &module, &[make_ref.into(), check.into()])?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+    run.call(&mut store, NUM_REFS as i32)?;
+
+    store.gc();
+    assert_eq!(num_refs_dropped.load(SeqCst), NUM_REFS);
+
+    return Ok(());
+
+    struct CountDrops(Arc<AtomicUsize>);
+
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+}
@@ -89,3 +89,154 @@ fn use_after_drop() -> anyhow::Result<()> {
     assert_eq!(g.get(&mut store).i32(), Some(101));
     Ok(())
 }
+
+#[test]
+fn imported_global_mutation_is_shared() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (global $g (import "" "g") (mut i32))
+                (func (export "get") (result i32) global.get $g)
+                (func (export "set") (param i32) local.get 0 global.set $g)
+                (func (export "bump") (param i32)
+                    (loop $l
+                        global.get $g
+                        i32.const 1
+                        i32.add
+                        global.set $g
+                        local.get 0
+                        i32.const 1
+                        i32.sub
+                        local.tee 0
+                        br_if $l)))
+        "#,
+    )?;
+    let g = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Var),
+        1.into(),
+    )?;
+    let instance = Instance::new(&mut store, &module, &[g.into()])?;
+    let get = instance.get_typed_func::<(), i32, _>(&mut store, "get")?;
+    let set = instance.get_typed_func::<i32, (), _>(&mut store, "set")?;
+    let bump = instance.get_typed_func::<i32, (), _>(&mut store, "bump")?;
+
+    // Host writes are seen by wasm...
+    assert_eq!(get.call(&mut store, ())?, 1);
+    g.set(&mut store, 2.into())?;
+    assert_eq!(get.call(&mut store, ())?, 2);
+
+    // ... and wasm writes are seen by the host, including those made in a loop.
+    set.call(&mut store, 3)?;
+    assert_eq!(g.get(&mut store).i32(), Some(3));
+    bump.call(&mut store, 10)?;
+    assert_eq!(g.get(&mut store).i32(), Some(13));
+    g.set(&mut store, 0.into())?;
+    bump.call(&mut store, 5)?;
+    assert_eq!(get.call(&mut store, ())?, 5);
+    Ok(())
+}
+
+#[test]
+fn global_mutation_is_shared_between_instances() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let exporter = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (global $g (export "g") (mut i64) (i64.const 10))
+                (func (export "get") (result i64) global.get $g)
+                (func (export "set") (param i64) local.get 0 global.set $g))
+        "#,
+    )?;
+    let importer = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (global $g (import "" "g") (mut i64))
+                (func (export "get") (result i64) global.get $g)
+                (func (export "set") (param i64) local.get 0 global.set $g))
+        "#,
+    )?;
+    let a = Instance::new(&mut store, &exporter, &[])?;
+    let g = a.get_global(&mut store, "g").unwrap();
+    let b = Instance::new(&mut store, &importer, &[g.into()])?;
+    let a_get = a.get_typed_func::<(), i64, _>(&mut store, "get")?;
+    let a_set = a.get_typed_func::<i64, (), _>(&mut store, "set")?;
+    let b_get = b.get_typed_func::<(), i64, _>(&mut store, "get")?;
+    let b_set = b.get_typed_func::<i64, (), _>(&mut store, "set")?;
+
+    assert_eq!(b_get.call(&mut store, ())?, 10);
+    a_set.call(&mut store, 11)?;
+    assert_eq!(b_get.call(&mut store, ())?, 11);
+    b_set.call(&mut store, 12)?;
+    assert_eq!(a_get.call(&mut store, ())?, 12);
+    assert_eq!(g.get(&mut store).i64(), Some(12));
+    Ok(())
+}
+
+#[test]
+fn set_errors() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let g = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Const),
+        0.into(),
+    )?;
+    let err = g.set(&mut store, 1.into()).unwrap_err();
+    assert_eq!(err.to_string(), "immutable global cannot be set");
+    assert!(matches!(
+        err.downcast_ref::<ValError>(),
+        Some(ValError::ImmutableGlobal)
+    ));
+
+    let g = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Var),
+        0.into(),
+    )?;
+    let err = g.set(&mut store, 1i64.into()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "global of type `i32` cannot be set to a value of type `i64`"
+    );
+    match err.downcast_ref::<ValError>() {
+        Some(ValError::TypeMismatch {
+            site: ValSite::Global,
+            expected: ValType::I32,
+            found: ValType::I64,
+        }) => {}
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let g = Global::new(
+        &mut store,
+        GlobalType::new(ValType::FuncRef, Mutability::Var),
+        Val::FuncRef(None),
+    )?;
+    let mut other_store = Store::new(store.engine(), ());
+    let f = Func::wrap(&mut other_store, || {});
+    let err = g.set(&mut store, f.into()).unwrap_err();
+    assert_eq!(err.to_string(), "cross-`Store` values are not supported");
+    assert!(matches!(
+        err.downcast_ref::<ValError>(),
+        Some(ValError::CrossStore)
+    ));
+    Ok(())
+}
+
+#[test]
+fn v128() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let g = Global::new(
+        &mut store,
+        GlobalType::new(ValType::V128, Mutability::Var),
+        Val::V128(1),
+    )?;
+    assert_eq!(g.get(&mut store).v128(), Some(1));
+    g.set(&mut store, Val::V128(u128::max_value()))?;
+    assert_eq!(g.get(&mut store).v128(), Some(u128::max_value()));
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Mutex;
 use wasmtime::*;
 
 fn interruptable_store() -> Store<()> {
@@ -134,3 +135,165 @@ fn function_interrupt_from_afar() -> anyhow::Result<()> {
     );
     Ok(())
 }
+
+/// A module with an infinite loop, `loop`, which calls its import on each
+/// iteration, and a finite one, `count`.
+fn interrupt_causes_module(engine: &Engine) -> anyhow::Result<Module> {
+    Module::new(
+        engine,
+        r#"
+            (import "" "" (func))
+
+            (func (export "loop")
+                (loop
+                    call 0
+                    br 0)
+            )
+            (func (export "count") (param i32)
+                (loop
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    local.tee 0
+                    br_if 0)
+            )
+        "#,
+    )
+}
+
+/// Interrupts with `first` from one thread and then with `second` from
+/// another.
+fn interrupt_from_two_threads(first: InterruptHandle, second: InterruptHandle) {
+    std::thread::spawn(move || first.interrupt())
+        .join()
+        .unwrap();
+    std::thread::spawn(move || second.interrupt())
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn interrupt_causes_before_entry() -> anyhow::Result<()> {
+    let mut store = interruptable_store();
+    let module = interrupt_causes_module(store.engine())?;
+    let func = Func::wrap(&mut store, || {});
+    let instance = Instance::new(&mut store, &module, &[func.into()])?;
+    let iloop = instance.get_typed_func::<(), (), _>(&mut store, "loop")?;
+    let count = instance.get_typed_func::<i32, (), _>(&mut store, "count")?;
+
+    // The earliest interrupt wins, and the later one is consumed with it.
+    let deadline = store.interrupt_handle_with_cause("deadline")?;
+    let drain = store.interrupt_handle_with_cause("drain")?;
+    interrupt_from_two_threads(
+        store.interrupt_handle_with_cause("deadline")?,
+        store.interrupt_handle_with_cause(7u64)?,
+    );
+    let trap = iloop.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    assert_eq!(
+        trap.interrupt_cause(),
+        Some(&InterruptCause::from("deadline"))
+    );
+    count.call(&mut store, 1000)?;
+
+    // Re-arming with another cause reports that cause.
+    drain.interrupt();
+    let trap = iloop.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.interrupt_cause(), Some(&InterruptCause::from("drain")));
+    count.call(&mut store, 1000)?;
+
+    // Handles without a cause don't take precedence over ones with a cause,
+    // and on their own produce a trap without a cause.
+    interrupt_from_two_threads(store.interrupt_handle()?, deadline);
+    let trap = iloop.call(&mut store, ()).unwrap_err();
+    assert_eq!(
+        trap.interrupt_cause(),
+        Some(&InterruptCause::from("deadline"))
+    );
+    store.interrupt_handle()?.interrupt();
+    let trap = iloop.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    assert_eq!(trap.interrupt_cause(), None);
+    count.call(&mut store, 1000)?;
+    Ok(())
+}
+
+#[test]
+fn interrupt_causes_while_running() -> anyhow::Result<()> {
+    let mut store = interruptable_store();
+    let module = interrupt_causes_module(store.engine())?;
+
+    // The first time the loop calls out, interrupt it from two other threads.
+    let first = store.interrupt_handle_with_cause(1u64)?;
+    let second = store.interrupt_handle_with_cause(2u64)?;
+    let handles = Mutex::new(Some((first, second)));
+    let func = Func::wrap(&mut store, move || {
+        if let Some((first, second)) = handles.lock().unwrap().take() {
+            interrupt_from_two_threads(first, second);
+        }
+    });
+    let instance = Instance::new(&mut store, &module, &[func.into()])?;
+    let iloop = instance.get_typed_func::<(), (), _>(&mut store, "loop")?;
+    let count = instance.get_typed_func::<i32, (), _>(&mut store, "count")?;
+
+    let trap = iloop.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    assert_eq!(trap.interrupt_cause(), Some(&InterruptCause::Code(1)));
+    count.call(&mut store, 1000)?;
+
+    let handle = store.interrupt_handle_with_cause("admin")?;
+    handle.interrupt();
+    let trap = iloop.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.interrupt_cause(), Some(&InterruptCause::from("admin")));
+    count.call(&mut store, 1000)?;
+    Ok(())
+}
+
+#[test]
+fn interrupt_in_nested_activation_reaches_caller() -> anyhow::Result<()> {
+    let mut store = interruptable_store();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (import "" "nested" (func $nested))
+            (import "" "interrupt" (func $interrupt))
+
+            (func (export "outer")
+                call $nested
+                call $check)
+            (func $check
+                call $nop)
+            (func $nop)
+
+            ;; Returns without passing through another interrupt check once
+            ;; the interrupt has been sent.
+            (func (export "inner")
+                call $interrupt)
+        "#,
+    )?;
+    let nested = Func::wrap(&mut store, |mut caller: Caller<'_, ()>| {
+        let inner = caller
+            .get_export("inner")
+            .unwrap()
+            .into_func()
+            .unwrap()
+            .typed::<(), (), _>(&caller)?;
+        inner.call(&mut caller, ())
+    });
+    let handle = store.interrupt_handle()?;
+    let interrupt = Func::wrap(&mut store, move || handle.interrupt());
+    let instance = Instance::new(&mut store, &module, &[nested.into(), interrupt.into()])?;
+    let outer = instance.get_typed_func::<(), (), _>(&mut store, "outer")?;
+
+    // The nested activation returning restores the outer activation's stack
+    // limit, which must not clobber the pending interrupt.
+    let trap = outer.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+
+    // Without any wasm left running to notice it, an interrupt sent by a
+    // top-level activation is dropped when it returns.
+    let inner = instance.get_typed_func::<(), (), _>(&mut store, "inner")?;
+    inner.call(&mut store, ())?;
+    inner.call(&mut store, ())?;
+    Ok(())
+}
@@ -73,3 +73,79 @@ fn linear_memory_limits() -> Result<()> {
         Ok(())
     }
 }
+
+#[test]
+fn many_exports() -> Result<()> {
+    // Modules may have at most 100 tables.
+    const N: usize = 100;
+    let mut wat = String::from("(module");
+    for i in 0..N {
+        wat.push_str(&format!(
+            "(func (export \"f{0}\") (result i32) i32.const {0})
+             (global (export \"g{0}\") i32 (i32.const {1}))
+             (table (export \"t{0}\") {0} funcref)",
+            i,
+            i * 2,
+        ));
+    }
+    // The same entities exported under a second name.
+    wat.push_str("(export \"f7_again\" (func 7))");
+    wat.push_str("(export \"g7_again\" (global 7))");
+    wat.push_str(")");
+
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), &wat)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+
+    // Look everything up twice, in different orders, so the second round is
+    // served from the instance's cache.
+    for round in 0..2 {
+        for j in 0..N {
+            let i = if round == 0 { j } else { N - 1 - j };
+            let f = instance.get_typed_func::<(), i32, _>(&mut store, &format!("f{}", i))?;
+            assert_eq!(f.call(&mut store, ())?, i as i32);
+            let g = instance.get_global(&mut store, &format!("g{}", i)).unwrap();
+            assert_eq!(g.get(&mut store).i32(), Some(i as i32 * 2));
+            let t = instance.get_table(&mut store, &format!("t{}", i)).unwrap();
+            assert_eq!(t.size(&store), i as u32);
+
+            // Each name only resolves to the kind it was exported as.
+            assert!(instance.get_func(&mut store, &format!("g{}", i)).is_none());
+            assert!(instance
+                .get_global(&mut store, &format!("t{}", i))
+                .is_none());
+            assert!(instance.get_table(&mut store, &format!("f{}", i)).is_none());
+        }
+    }
+    assert!(instance
+        .get_export(&mut store, &format!("f{}", N))
+        .is_none());
+
+    let f = instance.get_typed_func::<(), i32, _>(&mut store, "f7_again")?;
+    assert_eq!(f.call(&mut store, ())?, 7);
+    let g = instance.get_global(&mut store, "g7_again").unwrap();
+    assert_eq!(g.get(&mut store).i32(), Some(14));
+
+    // Listing all exports agrees with looking them up one at a time.
+    let listed = instance
+        .exports(&mut store)
+        .map(|e| (e.name().to_string(), e.into_extern()))
+        .collect::<Vec<_>>();
+    assert_eq!(listed.len(), 3 * N + 2);
+    for (name, listed) in listed {
+        let export = instance.get_export(&mut store, &name).unwrap();
+        match (export, listed) {
+            (Extern::Func(a), Extern::Func(b)) => {
+                let a = a.typed::<(), i32, _>(&store)?.call(&mut store, ())?;
+                let b = b.typed::<(), i32, _>(&store)?.call(&mut store, ())?;
+                assert_eq!(a, b);
+            }
+            (Extern::Global(a), Extern::Global(b)) => {
+                assert_eq!(a.get(&mut store).i32(), b.get(&mut store).i32());
+            }
+            (Extern::Table(a), Extern::Table(b)) => assert_eq!(a.size(&store), b.size(&store)),
+            _ => panic!("export `{}` changed kind", name),
+        }
+    }
+    Ok(())
+}
@@ -371,3 +371,355 @@ fn test_custom_limiter() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_declared_limits() -> Result<()> {
+    const TWO_MEMORIES: &str = r#"(module (memory 1) (memory 2))"#;
+    const TWO_TABLES: &str = r#"(module (table 1 funcref) (table 2 funcref))"#;
+
+    let mut config = Config::new();
+    config.wasm_multi_memory(true);
+    config.wasm_reference_types(true);
+    config.max_memories(1).max_tables(1);
+    let engine = Engine::new(&config)?;
+
+    let err = Module::new(&engine, TWO_MEMORIES).err().unwrap();
+    assert!(
+        format!("{:?}", err).contains("module declares 2 memories, exceeding the limit of 1"),
+        "bad error: {:?}",
+        err
+    );
+    let err = Module::new(&engine, TWO_TABLES).err().unwrap();
+    assert!(
+        format!("{:?}", err).contains("module declares 2 tables, exceeding the limit of 1"),
+        "bad error: {:?}",
+        err
+    );
+
+    // Imports count towards the limits as well.
+    assert!(Module::new(&engine, r#"(module (import "" "" (memory 1)) (memory 1))"#).is_err());
+
+    config.max_memories(2).max_tables(2);
+    config.max_memory_pages(2).max_table_elements(2);
+    let engine = Engine::new(&config)?;
+
+    let err = Module::new(&engine, TWO_MEMORIES).err().unwrap();
+    assert!(
+        format!("{:?}", err).contains("total minimum size of 3 pages, exceeding the limit of 2"),
+        "bad error: {:?}",
+        err
+    );
+    let err = Module::new(&engine, TWO_TABLES).err().unwrap();
+    assert!(
+        format!("{:?}", err).contains("total minimum size of 3 elements, exceeding the limit of 2"),
+        "bad error: {:?}",
+        err
+    );
+
+    // Imported memories and tables aren't counted towards the sizes.
+    Module::new(
+        &engine,
+        r#"(module (import "" "" (memory 10)) (memory 2) (table 2 funcref))"#,
+    )?;
+
+    config.max_memory_pages(3).max_table_elements(3);
+    let engine = Engine::new(&config)?;
+    Module::new(&engine, TWO_MEMORIES)?;
+    Module::new(&engine, TWO_TABLES)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_declared_limits_deserialize() -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_multi_memory(true);
+    let module = Module::new(&Engine::new(&config)?, r#"(module (memory 1) (memory 1))"#)?;
+    let bytes = module.serialize()?;
+
+    config.max_memories(1);
+    let err = unsafe { Module::deserialize(&Engine::new(&config)?, &bytes) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("module declares 2 memories, exceeding the limit of 1"),
+        "bad error: {:?}",
+        err
+    );
+
+    config.max_memories(2);
+    unsafe { Module::deserialize(&Engine::new(&config)?, &bytes)? };
+
+    Ok(())
+}
+
+fn declared_limits_config(memories: u32, tables: u32) -> Config {
+    let mut config = Config::new();
+    config.wasm_multi_memory(true);
+    config.wasm_reference_types(true);
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::NextAvailable,
+        module_limits: ModuleLimits {
+            memories,
+            tables,
+            memory_pages: 2,
+            table_elements: 2,
+            ..Default::default()
+        },
+        instance_limits: InstanceLimits { count: 1 },
+    });
+    config
+}
+
+#[test]
+fn test_declared_limits_pooling() -> Result<()> {
+    const TWO_MEMORIES: &str = r#"(module (memory 1) (memory 2))"#;
+    const TWO_TABLES: &str = r#"(module (table 1 funcref) (table 2 funcref))"#;
+
+    let engine = Engine::new(&declared_limits_config(1, 1))?;
+
+    let err = Module::new(&engine, TWO_MEMORIES).err().unwrap();
+    assert!(
+        format!("{:?}", err).contains("defined memories count of 2 exceeds the limit of 1"),
+        "bad error: {:?}",
+        err
+    );
+    let err = Module::new(&engine, TWO_TABLES).err().unwrap();
+    assert!(
+        format!("{:?}", err).contains("defined tables count of 2 exceeds the limit of 1"),
+        "bad error: {:?}",
+        err
+    );
+    let err = Module::new(&engine, r#"(module (memory 3))"#)
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err)
+            .contains("memory index 0 has a minimum page size of 3 which exceeds the limit of 2"),
+        "bad error: {:?}",
+        err
+    );
+
+    // The pooling allocator's limits are also checked before any functions
+    // are compiled, so this module fails on its memories even though its
+    // function can't be compiled.
+    let err = Module::new(
+        &engine,
+        r#"(module (memory 1) (memory 1) (func (drop (i8x16.splat (i32.const 0)))))"#,
+    )
+    .err()
+    .unwrap();
+    assert!(
+        format!("{:?}", err).contains("defined memories count of 2 exceeds the limit of 1"),
+        "bad error: {:?}",
+        err
+    );
+
+    let engine = Engine::new(&declared_limits_config(2, 2))?;
+    Module::new(&engine, TWO_MEMORIES)?;
+    Module::new(&engine, TWO_TABLES)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_declared_limits_pooling_deserialize() -> Result<()> {
+    let engine = Engine::new(&declared_limits_config(2, 1))?;
+    let module = Module::new(&engine, r#"(module (memory 1) (memory 1))"#)?;
+    let bytes = module.serialize()?;
+
+    let engine = Engine::new(&declared_limits_config(1, 1))?;
+    let err = unsafe { Module::deserialize(&engine, &bytes) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("defined memories count of 2 exceeds the limit of 1"),
+        "bad error: {:?}",
+        err
+    );
+
+    let engine = Engine::new(&declared_limits_config(2, 1))?;
+    unsafe { Module::deserialize(&engine, &bytes)? };
+
+    Ok(())
+}
+
+#[test]
+fn test_huge_tables() -> Result<()> {
+    const HUGE_TABLE: &str = r#"(module (table 10000000 funcref))"#;
+
+    // Under a smaller cap the module is rejected before it's ever
+    // instantiated, without the store needing any limiter.
+    let mut config = Config::new();
+    config.max_table_elements(1_000_000);
+    let err = Module::new(&Engine::new(&config)?, HUGE_TABLE)
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err)
+            .contains("total minimum size of 10000000 elements, exceeding the limit of 1000000"),
+        "bad error: {:?}",
+        err
+    );
+
+    // By default a module's tables may have as many elements in total as a
+    // single table may have.
+    let err = Module::new(
+        &Engine::default(),
+        r#"(module (table 10000000 funcref) (table 1 funcref))"#,
+    )
+    .err()
+    .unwrap();
+    assert!(
+        format!("{:?}", err)
+            .contains("total minimum size of 10000001 elements, exceeding the limit of 10000000"),
+        "bad error: {:?}",
+        err
+    );
+
+    // Without that cap it's the store's limits which stop the table from
+    // being allocated, failing instantiation gracefully.
+    config.max_table_elements(u64::MAX);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, HUGE_TABLE)?;
+    let mut store = Store::new(
+        &engine,
+        StoreLimitsBuilder::new().table_elements(1_000_000).build(),
+    );
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    let err = Instance::new(&mut store, &module, &[]).unwrap_err();
+    assert!(
+        format!("{:?}", err)
+            .contains("table minimum size of 10000000 elements exceeds table limits"),
+        "bad error: {:?}",
+        err
+    );
+
+    // Tables and memories created by the host are validated in the same way.
+    let mut store = Store::new(&Engine::default(), ());
+    let huge = TableType::new(ValType::FuncRef, Limits::new(u32::MAX, Some(u32::MAX)));
+    let err = Table::new(&mut store, huge, Val::FuncRef(None)).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("minimum size of 4294967295 elements exceeds the limit of 10000000"),
+        "bad error: {}",
+        err
+    );
+    let backwards = TableType::new(ValType::FuncRef, Limits::new(2, Some(1)));
+    let err = Table::new(&mut store, backwards, Val::FuncRef(None)).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("minimum size of 2 elements exceeds the maximum of 1"),
+        "bad error: {}",
+        err
+    );
+    let err = Memory::new(&mut store, MemoryType::new(Limits::new(2, Some(1)))).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("minimum size of 2 pages exceeds the maximum of 1"),
+        "bad error: {}",
+        err
+    );
+    let err = Memory::new(&mut store, MemoryType::new(Limits::at_least(0x10001))).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("minimum size of 65537 pages exceeds the limit of 65536"),
+        "bad error: {}",
+        err
+    );
+    let err = Memory::new(&mut store, MemoryType::new(Limits::new(1, Some(0x10001)))).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("maximum size of 65537 pages exceeds the limit of 65536"),
+        "bad error: {}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_func_type_limits() -> Result<()> {
+    const THREE_RESULTS: &str = r#"(module (type (func (param i32) (result i32 i32 i32))))"#;
+
+    let mut config = Config::new();
+    config.max_function_results(2);
+    let engine = Engine::new(&config)?;
+    let err = Module::new(&engine, THREE_RESULTS).err().unwrap();
+    assert!(
+        format!("{:?}", err).contains(
+            "function type has 1 parameters and 3 results, exceeding the limit of 2 results"
+        ),
+        "bad error: {:?}",
+        err
+    );
+
+    config.max_function_results(3);
+    Module::new(&Engine::new(&config)?, THREE_RESULTS)?;
+
+    // Host functions are checked before any trampolines are compiled for
+    // them.
+    let engine = Engine::default();
+    let mut linker = Linker::<()>::new(&engine);
+    let huge = FuncType::new(None, vec![ValType::I32; 65536]);
+    let err = linker
+        .func_new("", "", huge, |_, _, _| Ok(()))
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains(
+            "function type has 0 parameters and 65536 results, exceeding the limit of 1000 results"
+        ),
+        "bad error: {}",
+        err
+    );
+
+    // `Func::try_new` reports the same error instead of panicking.
+    let mut store = Store::new(&engine, ());
+    let huge = FuncType::new(vec![ValType::I64; 1001], None);
+    let err = Func::try_new(&mut store, huge, |_, _, _| Ok(()))
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains(
+            "function type has 1001 parameters and 0 results, exceeding the limit of 1000 parameters"
+        ),
+        "bad error: {}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+#[ignore] // a type with 2^27 results is slow to build and uses lots of memory
+fn test_func_type_too_large_for_trampoline() -> Result<()> {
+    // With the limit raised far enough, a type whose values vector doesn't
+    // fit in the `i32` offsets of the trampolines is still rejected cleanly.
+    let mut config = Config::new();
+    config.max_function_results(u32::max_value());
+    let engine = Engine::new(&config)?;
+    let mut linker = Linker::<()>::new(&engine);
+    let too_large = FuncType::new(None, vec![ValType::I32; 1 << 27]);
+    let err = linker
+        .func_new("", "", too_large, |_, _, _| Ok(()))
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains(
+            "function type with 0 parameters and 134217728 results is too large for a trampoline"
+        ),
+        "bad error: {}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "exceeding the limit of 1000 parameters")]
+fn test_func_new_over_limits() {
+    let mut store = Store::<()>::default();
+    let huge = FuncType::new(vec![ValType::I64; 1001], None);
+    Func::new(&mut store, huge, |_, _, _| Ok(()));
+}
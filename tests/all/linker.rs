@@ -23,6 +23,42 @@ fn link_undefined() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn link_type_mismatch_message() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+    linker.func_wrap("env", "f", |x: i64| x as i32)?;
+    let global = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Var),
+        Val::I32(0),
+    )?;
+    linker.define("env", "g", global)?;
+
+    let module = Module::new(
+        store.engine(),
+        r#"(module (import "env" "f" (func (param i32))))"#,
+    )?;
+    let err = linker.instantiate(&mut store, &module).unwrap_err();
+    assert_eq!(
+        format!("{:#}", err),
+        "incompatible import type for `env::f`: function types incompatible: \
+         expected (func (param i32)) but found (func (param i64) (result i32))"
+    );
+
+    let module = Module::new(
+        store.engine(),
+        r#"(module (import "env" "g" (global i32)))"#,
+    )?;
+    let err = linker.instantiate(&mut store, &module).unwrap_err();
+    assert_eq!(
+        format!("{:#}", err),
+        "incompatible import type for `env::g`: global types incompatible: \
+         expected (global i32) but found (global (mut i32))"
+    );
+    Ok(())
+}
+
 #[test]
 fn link_twice_bad() -> Result<()> {
     let mut store = Store::<()>::default();
@@ -172,6 +208,48 @@ fn module_interposition() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn instance_exports() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+    let a = Module::new(
+        store.engine(),
+        r#"(module
+            (func (export "f") (result i32) (i32.const 1))
+            (memory (export "m") 1)
+            (global (export "g") i32 (i32.const 2))
+            (table (export "t") 1 funcref)
+        )"#,
+    )?;
+    let a = Instance::new(&mut store, &a, &[])?;
+    linker.instance(&mut store, "a", a)?;
+
+    let b = Module::new(
+        store.engine(),
+        r#"(module
+            (import "a" "f" (func $f (result i32)))
+            (import "a" "m" (memory 1))
+            (import "a" "g" (global $g i32))
+            (import "a" "t" (table 1 funcref))
+            (func (export "run") (result i32)
+                (i32.add (call $f) (global.get $g)))
+        )"#,
+    )?;
+    let b = linker.instantiate(&mut store, &b)?;
+    let run = b.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 3);
+
+    // Registering another instance under the same name conflicts.
+    let err = linker.instance(&mut store, "a", a).err().unwrap();
+    assert!(err.to_string().contains("`a::f` defined twice"), "{}", err);
+
+    // Instances from other stores are refused.
+    let mut other = Store::new(store.engine(), ());
+    let err = linker.instance(&mut other, "c", a).err().unwrap();
+    assert!(err.to_string().contains("cross-`Store`"), "{}", err);
+    Ok(())
+}
+
 #[test]
 fn allow_unknown_exports() -> Result<()> {
     let mut store = Store::<()>::default();
@@ -309,6 +387,48 @@ fn alias_one() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn alias_imported_under_two_names() -> Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, 0);
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap("env", "f", |mut caller: Caller<'_, i32>| {
+        *caller.data_mut() += 1;
+    })?;
+    linker.alias("env", "f", "wasi_unstable", "f")?;
+
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "env" "f" (func $env_f))
+                (import "wasi_unstable" "f" (func $wasi_f))
+                (func (export "run")
+                    call $env_f
+                    call $wasi_f))
+        "#,
+    )?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    assert_eq!(*store.data(), 2);
+
+    // Both the source and the destination are checked.
+    let err = linker
+        .alias("env", "g", "wasi_unstable", "g")
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("env::g"), "{}", err);
+    let err = linker
+        .alias("env", "f", "wasi_unstable", "f")
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("defined twice"), "{}", err);
+    linker.allow_shadowing(true);
+    linker.alias("env", "f", "wasi_unstable", "f")?;
+    Ok(())
+}
+
 #[test]
 fn instance_pre() -> Result<()> {
     let engine = Engine::default();
@@ -340,3 +460,390 @@ fn instance_pre() -> Result<()> {
     instance_pre.instantiate(&mut store)?;
     Ok(())
 }
+
+#[test]
+fn instance_pre_reuses_imports() -> Result<()> {
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap("", "a", || 1)?;
+    linker.func_wrap("", "b", |mut caller: Caller<'_, i32>, x: i32| {
+        *caller.data_mut() += x;
+        *caller.data()
+    })?;
+
+    let module = Module::new(
+        &engine,
+        r#"(module
+            (import "" "a" (func $a (result i32)))
+            (import "" "b" (func $b (param i32) (result i32)))
+            (import "" "g" (global $g i32))
+            (import "" "m" (memory 1))
+            (func (export "run") (result i32)
+                (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (call $a)))
+                (call $b (global.get $g)))
+            (func (export "load") (result i32)
+                (i32.load (i32.const 0)))
+        )"#,
+    )?;
+
+    let mut stores = Vec::new();
+    for i in 0..2 {
+        let mut store = Store::new(&engine, 0);
+        let global = Global::new(
+            &mut store,
+            GlobalType::new(ValType::I32, Mutability::Const),
+            (i + 10).into(),
+        )?;
+        let memory = Memory::new(&mut store, MemoryType::new(Limits::new(1, None)))?;
+        linker.allow_shadowing(true);
+        linker.define("", "g", global)?;
+        linker.define("", "m", memory)?;
+        let pre = linker.instantiate_pre(&mut store, &module)?;
+        stores.push((store, pre, i + 10));
+    }
+
+    // Every instance sees the same imports, and instances of each `InstancePre`
+    // are only able to be created in the store its imports come from.
+    for (store, pre, g) in stores.iter_mut() {
+        for n in 1..=10 {
+            let instance = pre.instantiate(&mut *store)?;
+            let run = instance.get_typed_func::<(), i32, _>(&mut *store, "run")?;
+            let load = instance.get_typed_func::<(), i32, _>(&mut *store, "load")?;
+            assert_eq!(run.call(&mut *store, ())?, *g * n);
+            assert_eq!(load.call(&mut *store, ())?, n);
+        }
+    }
+    let (mut store, _, _) = stores.pop().unwrap();
+    let (_, pre, _) = stores.pop().unwrap();
+    assert!(pre.instantiate(&mut store).is_err());
+    Ok(())
+}
+
+#[test]
+fn imports_in_declaration_order() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "b" "z" (func))
+                (import "a" "y" (global i32))
+                (import "b" "x" (memory 1))
+                (import "a" "y" (func))
+                (import "c" "w" (table 1 funcref))
+            )
+        "#,
+    )?;
+    let names = module
+        .imports()
+        .map(|i| (i.module(), i.name().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [("b", "z"), ("a", "y"), ("b", "x"), ("a", "y"), ("c", "w")]
+    );
+    Ok(())
+}
+
+#[test]
+fn instance_new_matches_imports_by_position() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "m" "x" (global $a i32))
+                (import "m" "x" (global $b i32))
+                (func (export "a") (result i32) global.get $a)
+                (func (export "b") (result i32) global.get $b)
+            )
+        "#,
+    )?;
+    let one = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Const),
+        1.into(),
+    )?;
+    let two = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Const),
+        2.into(),
+    )?;
+    let instance = Instance::new(&mut store, &module, &[two.into(), one.into()])?;
+    let a = instance.get_typed_func::<(), i32, _>(&mut store, "a")?;
+    let b = instance.get_typed_func::<(), i32, _>(&mut store, "b")?;
+    assert_eq!(a.call(&mut store, ())?, 2);
+    assert_eq!(b.call(&mut store, ())?, 1);
+    Ok(())
+}
+
+#[test]
+fn duplicate_name_imports_of_different_types() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "m" "x" (func))
+                (import "m" "x" (global i32))
+            )
+        "#,
+    )?;
+
+    // Both imports are given the one definition of `m::x`, whichever is
+    // defined, so the same import fails to typecheck every time.
+    for _ in 0..10 {
+        let mut linker = Linker::new(store.engine());
+        linker.func_wrap("m", "x", || {})?;
+        linker.func_wrap("m", "y", || {})?;
+        let err = linker.instantiate(&mut store, &module).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("expected global, but found func"),
+            "{:#}",
+            err
+        );
+
+        let mut linker = Linker::new(store.engine());
+        let global = Global::new(
+            &mut store,
+            GlobalType::new(ValType::I32, Mutability::Const),
+            0.into(),
+        )?;
+        linker.define("m", "x", global)?;
+        let err = linker.instantiate(&mut store, &module).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("expected func, but found global"),
+            "{:#}",
+            err
+        );
+    }
+
+    // Imports of the same name and type all resolve to the same definition.
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "m" "x" (func $a (result i32)))
+                (import "m" "x" (func $b (result i32)))
+                (func (export "sum") (result i32)
+                    (i32.add (call $a) (call $b)))
+            )
+        "#,
+    )?;
+    let mut linker = Linker::new(store.engine());
+    linker.func_wrap("m", "x", || 21)?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let sum = instance.get_typed_func::<(), i32, _>(&mut store, "sum")?;
+    assert_eq!(sum.call(&mut store, ())?, 42);
+    Ok(())
+}
+
+#[test]
+fn linker_iter_in_definition_order() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+    linker.allow_shadowing(true);
+    for name in ["q", "b", "z", "a", "m", "c", "y"].iter() {
+        linker.func_wrap("host", name, || {})?;
+    }
+    linker.func_wrap("env", "b", || {})?;
+    // Shadowing keeps the original position.
+    linker.func_wrap("host", "z", || 1)?;
+
+    let names = linker
+        .iter(&mut store)
+        .map(|(module, name, _)| format!("{}::{}", module, name))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["host::q", "host::b", "host::z", "host::a", "host::m", "host::c", "host::y", "env::b",]
+    );
+    Ok(())
+}
+
+#[test]
+fn instantiate_graph_diamond() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+
+    // `top` depends on `left` and `right`, which both depend on `base`. Each
+    // module's initializer checks that its dependencies were initialized
+    // first.
+    let base = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (global $init (mut i32) (i32.const 0))
+                (func (export "_initialize") (global.set $init (i32.const 1)))
+                (func (export "value") (result i32) (global.get $init))
+            )
+        "#,
+    )?;
+    let side = |name: &str| {
+        Module::new(
+            store.engine(),
+            &format!(
+                r#"
+                    (module
+                        (import "base" "value" (func $base (result i32)))
+                        (global $init (mut i32) (i32.const 0))
+                        (func (export "_initialize")
+                            call $base
+                            i32.eqz
+                            if unreachable end
+                            (global.set $init (i32.const {})))
+                        (func (export "value") (result i32) (global.get $init))
+                    )
+                "#,
+                name.len()
+            ),
+        )
+    };
+    let left = side("left")?;
+    let right = side("right!")?;
+    let top = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "left" "value" (func $left (result i32)))
+                (import "right" "value" (func $right (result i32)))
+                (func (export "sum") (result i32)
+                    (i32.add (call $left) (call $right)))
+            )
+        "#,
+    )?;
+
+    let instances = linker.instantiate_graph(
+        &mut store,
+        &[
+            ("top", &top),
+            ("right", &right),
+            ("base", &base),
+            ("left", &left),
+        ],
+    )?;
+    assert_eq!(instances.len(), 4);
+    let sum = instances[0].get_typed_func::<(), i32, _>(&mut store, "sum")?;
+    assert_eq!(sum.call(&mut store, ())?, 10);
+
+    // Each instance's exports were registered under its name.
+    let value = linker
+        .get(&mut store, "base", Some("value"))
+        .unwrap()
+        .into_func()
+        .unwrap()
+        .typed::<(), i32, _>(&store)?;
+    assert_eq!(value.call(&mut store, ())?, 1);
+    Ok(())
+}
+
+#[test]
+fn instantiate_graph_cycle() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+    let a = Module::new(
+        store.engine(),
+        r#"(module (import "b" "f" (func)) (func (export "f")))"#,
+    )?;
+    let b = Module::new(
+        store.engine(),
+        r#"(module (import "c" "f" (func)) (func (export "f")))"#,
+    )?;
+    let c = Module::new(
+        store.engine(),
+        r#"(module (import "a" "f" (func)) (func (export "f")))"#,
+    )?;
+    let err = linker
+        .instantiate_graph(&mut store, &[("a", &a), ("b", &b), ("c", &c)])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "modules have a cyclic dependency: `a` -> `b` -> `c` -> `a`"
+    );
+    assert!(linker.get(&mut store, "c", Some("f")).is_none());
+    Ok(())
+}
+
+#[test]
+fn instantiate_graph_ambiguous() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+    let user = Module::new(store.engine(), r#"(module (import "lib" "f" (func)))"#)?;
+    let lib = Module::new(store.engine(), r#"(module (func (export "f")))"#)?;
+    let err = linker
+        .instantiate_graph(&mut store, &[("user", &user), ("lib", &lib), ("lib", &lib)])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "import `lib::f` of module `user` is ambiguous: it's exported by both \
+         module #1 and module #2 named `lib`"
+    );
+    Ok(())
+}
+
+const EMPTY_MODULE_IMPORT: &str = r#"
+    (module
+        (import "" "f" (func $f (result i32)))
+        (func (export "run") (result i32) (call $f)))
+"#;
+
+#[test]
+fn single_level_names() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+    linker.define_name("f", Func::wrap(&mut store, || 1))?;
+
+    // An import with an empty module name is satisfied by a single-level
+    // name.
+    let module = Module::new(store.engine(), EMPTY_MODULE_IMPORT)?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 1);
+
+    // But one with any other module name isn't.
+    let other = Module::new(store.engine(), r#"(module (import "env" "f" (func)))"#)?;
+    let err = linker.instantiate(&mut store, &other).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown import: `env::f` has not been defined"
+    );
+
+    // A definition under the empty module name is distinct from the
+    // single-level name, and takes precedence over it.
+    linker.define("", "f", Func::wrap(&mut store, || 2))?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 2);
+
+    let names = linker
+        .iter(&mut store)
+        .map(|(module, name, _)| format!("{}::{}", module, name))
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["::f", "::f"]);
+    Ok(())
+}
+
+#[test]
+fn single_level_names_in_errors() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+
+    let module = Module::new(store.engine(), r#"(module (import "" "f" (func)))"#)?;
+    let err = linker.instantiate(&mut store, &module).unwrap_err();
+    assert_eq!(err.to_string(), "unknown import: `f` has not been defined");
+
+    linker.define_name("f", Func::wrap(&mut store, |_: i32| {}))?;
+    let err = linker.instantiate(&mut store, &module).unwrap_err();
+    assert!(
+        format!("{:#}", err).starts_with("incompatible import type for `f`: "),
+        "{:#}",
+        err
+    );
+
+    let err = linker
+        .define_name("f", Func::wrap(&mut store, || {}))
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "import of `f` defined twice");
+    Ok(())
+}
@@ -1,8 +1,13 @@
+mod activation_times;
 mod async_functions;
 mod cli_tests;
+mod config_summary;
 mod custom_signal_handler;
 mod debug;
+mod epoch_interruption;
+mod errors;
 mod externals;
+mod feature_usage;
 mod fuel;
 mod func;
 mod funcref;
@@ -25,10 +30,16 @@ mod module_serialize;
 mod name;
 mod native_hooks;
 mod pooling_allocator;
+mod scoped_externref;
 mod stack_overflow;
+mod start_budget;
 mod store;
 mod table;
 mod traps;
+mod wasi_guest_pointers;
+mod wasi_preopens;
+mod wasi_stdio;
+mod wasi_unstable;
 mod wast;
 
 /// A helper to compile a module in a new store with reference types enabled.
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use rayon::prelude::*;
+use std::time::Duration;
 use wasmtime::*;
 
 fn module(engine: &Engine) -> Result<Module> {
@@ -91,13 +92,16 @@ fn offsets_static_dynamic_oh_my() -> Result<()> {
     for &static_memory_maximum_size in sizes.iter() {
         for &guard_size in sizes.iter() {
             for &guard_before_linear_memory in [true, false].iter() {
-                let mut config = Config::new();
-                config.wasm_simd(true);
-                config.static_memory_maximum_size(static_memory_maximum_size);
-                config.dynamic_memory_guard_size(guard_size);
-                config.static_memory_guard_size(guard_size);
-                config.guard_before_linear_memory(guard_before_linear_memory);
-                engines.push(Engine::new(&config)?);
+                for &elide_guarded_bounds_checks in [true, false].iter() {
+                    let mut config = Config::new();
+                    config.wasm_simd(true);
+                    config.static_memory_maximum_size(static_memory_maximum_size);
+                    config.dynamic_memory_guard_size(guard_size);
+                    config.static_memory_guard_size(guard_size);
+                    config.guard_before_linear_memory(guard_before_linear_memory);
+                    config.elide_guarded_bounds_checks(elide_guarded_bounds_checks);
+                    engines.push(Engine::new(&config)?);
+                }
             }
         }
     }
@@ -126,6 +130,64 @@ fn offsets_static_dynamic_oh_my() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn elided_bounds_checks_still_trap() -> Result<()> {
+    const GB: u64 = 1 << 30;
+
+    // A 1 GB static memory with a 2 GB guard region, so constant addresses
+    // whose accesses end within 3 GB don't get a bounds check and rely on the
+    // guard region instead.
+    let mut config = Config::new();
+    config.static_memory_maximum_size(GB);
+    config.static_memory_guard_size(2 * GB);
+    config.elide_guarded_bounds_checks(true);
+    let engine = Engine::new(&config)?;
+
+    let load = |addr: u32, offset: u64| -> Result<Option<i32>> {
+        let module = Module::new(
+            &engine,
+            &format!(
+                r#"
+                    (module
+                        (memory (export "memory") 1 2)
+                        (func (export "load") (result i32)
+                            i32.const {}
+                            i32.load offset={}))
+                "#,
+                addr, offset
+            ),
+        )?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        memory.data_mut(&mut store)[65532] = 42;
+        let load = instance.get_typed_func::<(), i32, _>(&mut store, "load")?;
+        let result = load.call(&mut store, ());
+        if let Err(trap) = &result {
+            assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+        }
+        Ok(result.ok())
+    };
+
+    // The last in-bounds word, and words overlapping the end of memory.
+    assert_eq!(load(65532, 0)?, Some(42));
+    assert_eq!(load(65528, 4)?, Some(42));
+    assert_eq!(load(65533, 0)?, None);
+    assert_eq!(load(0, 65534)?, None);
+
+    // Accesses in the guard region, up to its very end, whose checks are
+    // elided.
+    assert_eq!(load(65536, 0)?, None);
+    assert_eq!(load(0, GB)?, None);
+    assert_eq!(load(0, 3 * GB - 4)?, None);
+
+    // Accesses which extend past the guard region, which are still checked.
+    assert_eq!(load(0, 3 * GB - 3)?, None);
+    assert_eq!(load(u32::MAX, 0)?, None);
+    assert_eq!(load(u32::MAX, u64::from(u32::MAX))?, None);
+    Ok(())
+}
+
 #[test]
 fn guards_present() -> Result<()> {
     const GUARD_SIZE: u64 = 65536;
@@ -234,6 +296,66 @@ fn guards_present_pooling() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn custom_pre_guard_size() -> Result<()> {
+    const GUARD_SIZE: u64 = 65536;
+    const PRE_GUARD_SIZE: u64 = 4 * GUARD_SIZE;
+
+    for &pooling in [false, true].iter() {
+        println!("pooling: {}", pooling);
+        let mut config = Config::new();
+        config.static_memory_maximum_size(1 << 20);
+        config.dynamic_memory_guard_size(GUARD_SIZE);
+        config.static_memory_guard_size(GUARD_SIZE);
+        config.guard_before_linear_memory(true);
+        config.guard_before_linear_memory_size(PRE_GUARD_SIZE);
+        if pooling {
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+                strategy: PoolingAllocationStrategy::default(),
+                module_limits: ModuleLimits {
+                    memory_pages: 10,
+                    ..ModuleLimits::default()
+                },
+                instance_limits: InstanceLimits { count: 2 },
+            });
+        }
+        let engine = Engine::new(&config)?;
+        let mut store = Store::new(&engine, ());
+
+        let module = Module::new(
+            &engine,
+            r#"
+                (module
+                    (memory (export "memory") 1)
+                    (func (export "load") (param i32) (result i32)
+                        (i32.load (local.get 0))))
+            "#,
+        )?;
+        for _ in 0..2 {
+            let instance = Instance::new(&mut store, &module, &[])?;
+            let mem = instance.get_memory(&mut store, "memory").unwrap();
+            unsafe {
+                // The whole region before memory is inaccessible, not just
+                // the size of the guard after it.
+                let base = mem.data_ptr(&store);
+                assert_faults(base.offset(-1));
+                assert_faults(base.offset(-(GUARD_SIZE as isize) - 1));
+                assert_faults(base.offset(-(PRE_GUARD_SIZE as isize)));
+            }
+
+            // Accesses with wrapping addresses are still reported as out of
+            // bounds accesses.
+            let load = instance.get_typed_func::<i32, i32, _>(&mut store, "load")?;
+            for &addr in [-4, -(GUARD_SIZE as i32)].iter() {
+                let trap = load.call(&mut store, addr).unwrap_err();
+                assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 unsafe fn assert_faults(ptr: *mut u8) {
     use std::io::Error;
     #[cfg(unix)]
@@ -280,3 +402,497 @@ unsafe fn assert_faults(ptr: *mut u8) {
         assert_eq!(info.AllocationProtect, PAGE_NOACCESS);
     }
 }
+
+#[test]
+fn host_atomic_notify_and_wait() -> Result<()> {
+    let engine = Engine::new(Config::new().wasm_threads(true))?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "notify") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    memory.atomic.notify)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let notify = instance.get_typed_func::<i32, i32, _>(&mut store, "notify")?;
+
+    // There are never waiters on a memory that isn't shared, from either side.
+    assert_eq!(memory.atomic_notify(&store, 0, 1)?, 0);
+    assert_eq!(memory.atomic_notify(&store, 65532, u32::max_value())?, 0);
+    assert_eq!(notify.call(&mut store, 0)?, 0);
+
+    let code = |trap: Trap| trap.trap_code();
+    assert_eq!(
+        code(memory.atomic_notify(&store, 1, 1).unwrap_err()),
+        Some(TrapCode::HeapMisaligned)
+    );
+    assert_eq!(
+        code(memory.atomic_notify(&store, 65536, 1).unwrap_err()),
+        Some(TrapCode::MemoryOutOfBounds)
+    );
+    assert_eq!(
+        code(
+            memory
+                .atomic_notify(&store, u64::max_value() - 3, 1)
+                .unwrap_err()
+        ),
+        Some(TrapCode::MemoryOutOfBounds)
+    );
+
+    assert_eq!(
+        code(memory.atomic_wait32(&store, 2, 0, None).unwrap_err()),
+        Some(TrapCode::HeapMisaligned)
+    );
+    assert_eq!(
+        code(memory.atomic_wait32(&store, 65536, 0, None).unwrap_err()),
+        Some(TrapCode::MemoryOutOfBounds)
+    );
+    let trap = memory
+        .atomic_wait32(&store, 0, 0, Some(Duration::from_millis(1)))
+        .unwrap_err();
+    assert!(
+        trap.to_string()
+            .contains("atomic wait on non-shared memory"),
+        "{}",
+        trap
+    );
+
+    // Nothing else can reach a shared memory either, so waiting on one only
+    // mismatches or times out.
+    let ty = MemoryType::new(Limits::new(1, Some(1))).with_shared(true);
+    let memory = Memory::new(&mut store, ty)?;
+    memory.write(&mut store, 0, &42u32.to_le_bytes())?;
+    assert_eq!(memory.atomic_notify(&store, 0, 1)?, 0);
+    assert_eq!(
+        memory.atomic_wait32(&store, 0, 0, None)?,
+        WaitResult::Mismatch
+    );
+    assert_eq!(
+        memory.atomic_wait32(&store, 0, 42, Some(Duration::from_millis(1)))?,
+        WaitResult::TimedOut
+    );
+    assert!(memory.atomic_wait32(&store, 0, 42, None).is_err());
+    assert!(Memory::new(
+        &mut store,
+        MemoryType::new(Limits::at_least(1)).with_shared(true)
+    )
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn non_growing_private_memory_traps_out_of_bounds() -> Result<()> {
+    // With a static maximum size of zero this memory would be dynamic, but
+    // since nothing can grow it it's compiled with a constant bound instead.
+    let mut config = Config::new();
+    config.static_memory_maximum_size(0);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 2 10)
+                (func (export "load32") (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+                (func (export "load64 offset") (param i32) (result i64)
+                    local.get 0
+                    i64.load offset=65536)
+                (func (export "size") (result i32)
+                    memory.size)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let load32 = instance.get_typed_func::<u32, i32, _>(&mut store, "load32")?;
+    let load64 = instance.get_typed_func::<u32, i64, _>(&mut store, "load64 offset")?;
+    let size = instance.get_typed_func::<(), i32, _>(&mut store, "size")?;
+
+    assert_eq!(size.call(&mut store, ())?, 2);
+    let code = |result: Result<i64, Trap>| result.unwrap_err().trap_code();
+
+    assert_eq!(load32.call(&mut store, 0)?, 0);
+    assert_eq!(load32.call(&mut store, 2 * 65536 - 4)?, 0);
+    for addr in [2 * 65536 - 3, 2 * 65536, 3 * 65536, u32::max_value()].iter() {
+        assert_eq!(
+            code(load32.call(&mut store, *addr).map(i64::from)),
+            Some(TrapCode::MemoryOutOfBounds)
+        );
+    }
+
+    assert_eq!(load64.call(&mut store, 65536 - 8)?, 0);
+    for addr in [65536 - 7, 65536, u32::max_value()].iter() {
+        assert_eq!(
+            code(load64.call(&mut store, *addr)),
+            Some(TrapCode::MemoryOutOfBounds)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn memory64_and_shared_types() -> Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let ty = MemoryType::new64(Limits::new(1, Some(2)));
+    assert!(ty.is_64());
+    assert!(!ty.is_shared());
+    assert_eq!(ty.to_string(), "(memory i64 1 2)");
+    let ty = ty.with_shared(true);
+    assert!(ty.is_shared());
+    assert_eq!(ty.to_string(), "(memory i64 1 2 shared)");
+    assert!(Memory::new(&mut store, ty).is_err());
+
+    // A 64-bit memory can be created on the host, but can't be imported where
+    // a 32-bit one is expected.
+    let memory = Memory::new(&mut store, MemoryType::new64(Limits::at_least(1)))?;
+    assert_eq!(memory.ty(&store), MemoryType::new64(Limits::at_least(1)));
+    let module = Module::new(&engine, r#"(module (import "" "" (memory 1)))"#)?;
+    let err = Instance::new(&mut store, &module, &[memory.into()]).unwrap_err();
+    let msg = format!("{:?}", err);
+    assert!(
+        msg.contains("expected (memory 1) but found (memory i64 1)"),
+        "{}",
+        msg
+    );
+    Ok(())
+}
+
+#[test]
+fn bulk_read_and_write() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Limits::new(64, None)))?;
+
+    // A few megabytes at an odd offset.
+    let data = (0..3 << 20)
+        .map(|i| (i * 7 + i / 251) as u8)
+        .collect::<Vec<_>>();
+    memory.write(&mut store, 7, &data)?;
+
+    // `read_into` appends to what's in the buffer already.
+    let mut buf = vec![1, 2, 3];
+    memory.read_into(&store, 7, &mut buf, data.len())?;
+    assert_eq!(buf[..3], [1, 2, 3]);
+    assert!(buf[3..] == data[..]);
+
+    // Out of bounds reads leave the buffer untouched.
+    let size = memory.data_size(&store);
+    let mut buf = vec![1, 2, 3];
+    assert!(memory.read_into(&store, size - 1, &mut buf, 2).is_err());
+    assert!(memory.read_into(&store, usize::MAX, &mut buf, 1).is_err());
+    assert_eq!(buf, [1, 2, 3]);
+    memory.read_into(&store, size, &mut buf, 0)?;
+    assert_eq!(buf, [1, 2, 3]);
+    Ok(())
+}
+
+#[test]
+fn typed_read_and_write() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "load32") (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+                (func (export "load64") (param i32) (result i64)
+                    local.get 0
+                    i64.load)
+                (func (export "store32") (param i32 i32)
+                    local.get 0
+                    local.get 1
+                    i32.store)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let load32 = instance.get_typed_func::<u32, u32, _>(&mut store, "load32")?;
+    let load64 = instance.get_typed_func::<u32, u64, _>(&mut store, "load64")?;
+    let store32 = instance.get_typed_func::<(u32, u32), (), _>(&mut store, "store32")?;
+
+    // Values written by the host read the same in wasm, at any alignment.
+    let words = (0..100u32)
+        .map(|i| i.wrapping_mul(0x9e37_79b9))
+        .collect::<Vec<_>>();
+    memory.write_slice_as(&mut store, 13, &words)?;
+    for (i, word) in words.iter().enumerate() {
+        assert_eq!(load32.call(&mut store, 13 + 4 * i as u32)?, *word);
+    }
+    assert_eq!(memory.read_slice_as::<u32>(&store, 13, words.len())?, words);
+
+    let longs = [u64::MAX, 0x0102_0304_0506_0708, 0];
+    memory.write_slice_as(&mut store, 1001, &longs)?;
+    assert_eq!(load64.call(&mut store, 1009)?, longs[1]);
+    assert_eq!(memory.read_slice_as::<u64>(&store, 1001, 3)?, longs);
+
+    // ... and the other way around.
+    store32.call(&mut store, (3, 0xdead_beef))?;
+    assert_eq!(memory.read_slice_as::<u32>(&store, 3, 1)?, [0xdead_beef]);
+    assert_eq!(
+        memory.read_slice_as::<u8>(&store, 3, 4)?,
+        [0xef, 0xbe, 0xad, 0xde]
+    );
+    assert_eq!(
+        memory.read_slice_as::<i16>(&store, 3, 2)?,
+        [0xbeef_u16 as i16, 0xdead_u16 as i16]
+    );
+
+    let floats = [1.5f64, -0.0, f64::INFINITY];
+    memory.write_slice_as(&mut store, 2001, &floats)?;
+    let read = memory.read_slice_as::<f64>(&store, 2001, 3)?;
+    assert_eq!(
+        read.iter().map(|f| f.to_bits()).collect::<Vec<_>>(),
+        floats.iter().map(|f| f.to_bits()).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[test]
+fn typed_read_and_write_out_of_bounds() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Limits::new(1, None)))?;
+    let size = memory.data_size(&store);
+
+    assert!(memory.read_slice_as::<u32>(&store, size - 3, 1).is_err());
+    assert!(memory.read_slice_as::<u32>(&store, size - 4, 1).is_ok());
+    assert!(memory.read_slice_as::<u32>(&store, size, 0)?.is_empty());
+
+    // Counts whose size in bytes overflows a `u32`, or even a `usize`.
+    let count = (u32::MAX as usize) / 4 + 1;
+    assert!(memory.read_slice_as::<u32>(&store, 0, count).is_err());
+    assert!(memory
+        .read_slice_as::<u64>(&store, 0, usize::MAX / 4)
+        .is_err());
+    assert!(memory.read_slice_as::<u8>(&store, 1, usize::MAX).is_err());
+
+    // Writes which don't fit don't write anything.
+    assert!(memory
+        .write_slice_as(&mut store, size - 8, &[1u32, 2, 3])
+        .is_err());
+    assert!(memory.data(&store)[size - 8..].iter().all(|b| *b == 0));
+    Ok(())
+}
+
+#[test]
+fn virtual_pages_are_handled_by_the_host() -> Result<()> {
+    const PAGE: u32 = 65536;
+
+    let mut config = Config::new();
+    config.memory_access_hooks(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 2)
+                (func (export "load32") (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+                (func (export "load8_s") (param i32) (result i64)
+                    local.get 0
+                    i64.load8_s offset=1)
+                (func (export "load_f64") (param i32) (result f64)
+                    local.get 0
+                    f64.load)
+                (func (export "store32") (param i32 i32)
+                    local.get 0
+                    local.get 1
+                    i32.store)
+                (func (export "store16") (param i32 i32)
+                    local.get 0
+                    local.get 1
+                    i32.store16))
+        "#,
+    )?;
+
+    // The device is a single register at the start of page 1, and every
+    // access which reaches the handler is logged.
+    #[derive(Default)]
+    struct Device {
+        register: u64,
+        accesses: Vec<(u64, u8, MemoryAccessKind)>,
+    }
+    let mut store = Store::new(&engine, Device::default());
+    store.memory_access_handler(|device, fault| {
+        device
+            .accesses
+            .push((fault.address, fault.size, fault.kind));
+        match fault.kind {
+            MemoryAccessKind::Load if fault.address == u64::from(PAGE) => {
+                FaultResolution::Supply(device.register)
+            }
+            MemoryAccessKind::Load => FaultResolution::Supply(u64::MAX),
+            MemoryAccessKind::Store(value) if fault.address == u64::from(PAGE) => {
+                device.register = value;
+                FaultResolution::Accept
+            }
+            MemoryAccessKind::Store(_) => FaultResolution::Trap,
+        }
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let load32 = instance.get_typed_func::<u32, i32, _>(&mut store, "load32")?;
+    let load8_s = instance.get_typed_func::<u32, i64, _>(&mut store, "load8_s")?;
+    let load_f64 = instance.get_typed_func::<u32, f64, _>(&mut store, "load_f64")?;
+    let store32 = instance.get_typed_func::<(u32, i32), (), _>(&mut store, "store32")?;
+    let store16 = instance.get_typed_func::<(u32, i32), (), _>(&mut store, "store16")?;
+
+    // Page 1 is a device, and so is page 2 which is beyond the memory.
+    memory.register_virtual_pages(&mut store, 1..3)?;
+    memory.data_mut(&mut store)[PAGE as usize..][..4].copy_from_slice(&[1, 2, 3, 4]);
+
+    store.data_mut().register = 0x1234_5678_9abc_def0;
+    assert_eq!(load32.call(&mut store, PAGE)?, 0x9abc_def0_u32 as i32);
+    assert_eq!(
+        load_f64.call(&mut store, PAGE)?.to_bits(),
+        0x1234_5678_9abc_def0
+    );
+    store32.call(&mut store, (PAGE, 7))?;
+    assert_eq!(store.data().register, 7);
+    assert_eq!(load32.call(&mut store, PAGE)?, 7);
+
+    // Loads are truncated to their size and then extended.
+    assert_eq!(load8_s.call(&mut store, PAGE)?, -1);
+    assert_eq!(load32.call(&mut store, 2 * PAGE + 8)?, -1);
+
+    // The memory underneath the device isn't touched.
+    assert_eq!(&memory.data(&store)[PAGE as usize..][..4], &[1, 2, 3, 4]);
+
+    // An access which straddles the start of the device is handled too.
+    assert_eq!(load32.call(&mut store, PAGE - 2)?, -1);
+
+    // The handler can refuse an access.
+    let trap = store16.call(&mut store, (PAGE + 2, 1)).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+
+    assert_eq!(
+        store.data().accesses,
+        [
+            (u64::from(PAGE), 4, MemoryAccessKind::Load),
+            (u64::from(PAGE), 8, MemoryAccessKind::Load),
+            (u64::from(PAGE), 4, MemoryAccessKind::Store(7)),
+            (u64::from(PAGE), 4, MemoryAccessKind::Load),
+            (u64::from(PAGE) + 1, 1, MemoryAccessKind::Load),
+            (u64::from(2 * PAGE) + 8, 4, MemoryAccessKind::Load),
+            (u64::from(PAGE) - 2, 4, MemoryAccessKind::Load),
+            (u64::from(PAGE) + 2, 2, MemoryAccessKind::Store(1)),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn accesses_outside_virtual_pages_are_unaffected() -> Result<()> {
+    const PAGE: u32 = 65536;
+
+    let mut config = Config::new();
+    config.memory_access_hooks(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 2)
+                (func (export "load32") (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+                (func (export "store32") (param i32 i32)
+                    local.get 0
+                    local.get 1
+                    i32.store))
+        "#,
+    )?;
+    let mut store = Store::new(&engine, 0);
+    store.memory_access_handler(|calls, _| {
+        *calls += 1;
+        FaultResolution::Supply(0)
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let load32 = instance.get_typed_func::<u32, i32, _>(&mut store, "load32")?;
+    let store32 = instance.get_typed_func::<(u32, i32), (), _>(&mut store, "store32")?;
+    memory.register_virtual_pages(&mut store, 1..2)?;
+
+    // Ordinary memory is read and written as usual.
+    memory.data_mut(&mut store)[..4].copy_from_slice(&42i32.to_le_bytes());
+    assert_eq!(load32.call(&mut store, 0)?, 42);
+    store32.call(&mut store, (PAGE - 4, 9))?;
+    assert_eq!(memory.data(&store)[PAGE as usize - 4], 9);
+
+    // Out-of-bounds accesses beyond the device still trap.
+    for addr in [2 * PAGE, 2 * PAGE + 8, u32::MAX].iter().copied() {
+        let trap = load32.call(&mut store, addr).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+        let trap = store32.call(&mut store, (addr, 1)).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+    }
+    assert_eq!(*store.data(), 0);
+
+    // Without a handler, accesses to the device trap.
+    let mut store = Store::new(&engine, 0);
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let load32 = instance.get_typed_func::<u32, i32, _>(&mut store, "load32")?;
+    memory.register_virtual_pages(&mut store, 1..2)?;
+    let trap = load32.call(&mut store, PAGE).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+
+    // Virtual pages need to be enabled in the config, and within 4 GiB.
+    assert!(memory.register_virtual_pages(&mut store, 0..65537).is_err());
+    let mut store = Store::<()>::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Limits::new(1, None)))?;
+    assert!(memory.register_virtual_pages(&mut store, 0..1).is_err());
+    Ok(())
+}
+
+#[test]
+fn panicking_memory_access_handler() -> Result<()> {
+    const PAGE: u32 = 65536;
+    let wat = r#"
+        (module
+            (memory (export "memory") 2)
+            (func (export "load32") (param i32) (result i32)
+                local.get 0
+                i32.load))
+    "#;
+    let run = |policy| -> Result<Result<i32, Trap>> {
+        let mut config = Config::new();
+        config.memory_access_hooks(true);
+        config.host_panic_policy(policy);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        store.memory_access_handler(|_, _| panic!("the device is unplugged"));
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        let load32 = instance.get_typed_func::<u32, i32, _>(&mut store, "load32")?;
+        memory.register_virtual_pages(&mut store, 1..2)?;
+        Ok(load32.call(&mut store, PAGE))
+    };
+
+    // By default the panic is resumed on the other side of wasm.
+    let panic = std::panic::catch_unwind(|| run(HostPanicPolicy::Unwind)).unwrap_err();
+    assert_eq!(
+        panic.downcast_ref::<&'static str>(),
+        Some(&"the device is unplugged")
+    );
+
+    // Otherwise it becomes a trap.
+    let trap = run(HostPanicPolicy::CatchAsTrap)?.unwrap_err();
+    assert!(trap
+        .to_string()
+        .starts_with("host function panicked: the device is unplugged"));
+    Ok(())
+}
@@ -38,12 +38,13 @@ fn caches_across_engines() {
         );
         assert!(res.is_err());
 
-        // differ in cranelift settings
+        // the optimization level is a property of each module, so it may
+        // differ
         let res = Module::deserialize(
             &Engine::new(Config::new().cranelift_opt_level(OptLevel::None)).unwrap(),
             &bytes,
         );
-        assert!(res.is_err());
+        assert_eq!(res.unwrap().opt_level(), OptLevel::Speed);
 
         // Missing required cpu flags
         if cfg!(target_arch = "x86_64") {
@@ -78,3 +79,401 @@ fn aot_compiles() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn validate_agrees_with_compilation() -> Result<()> {
+    fn check(engine: &Engine, wasm: &[u8]) {
+        let err_chain = |e: anyhow::Error| e.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+        let validated = Module::validate(engine, wasm).map_err(err_chain);
+        let compiled = Module::from_binary(engine, wasm)
+            .map(|_| ())
+            .map_err(err_chain);
+        assert_eq!(validated, compiled);
+    }
+
+    let corpus = [
+        "(module)",
+        "(module (func (export \"f\") (param i32) (result i32) local.get 0))",
+        "(module (memory 1) (data (i32.const 0) \"hello\"))",
+        "(module (func (result i32) i64.const 0))",
+        "(module (func (local i32) local.get 1 drop))",
+        "(module (func unreachable) (func (result f32) i32.const 1))",
+        "(module (memory 1 1 shared))",
+        "(module (func (param v128)))",
+    ];
+
+    let engine = Engine::default();
+    let mut invalid = 0;
+    for wat in corpus.iter() {
+        let wasm = wat::parse_str(wat)?;
+        if Module::validate(&engine, &wasm).is_err() {
+            invalid += 1;
+        }
+        check(&engine, &wasm);
+    }
+    assert_eq!(invalid, 5);
+
+    check(&engine, b"\0asm\x02\0\0\0");
+    check(&engine, b"\0asm\x01\0\0\0\x01");
+
+    let mut config = Config::new();
+    config.wasm_simd(true);
+    let engine = Engine::new(&config)?;
+    Module::validate(&engine, &wat::parse_str("(module (func (param v128)))")?)?;
+
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::NextAvailable,
+        module_limits: ModuleLimits {
+            memory_pages: 1,
+            ..Default::default()
+        },
+        instance_limits: InstanceLimits { count: 1 },
+    });
+    config.static_memory_maximum_size(65536);
+    let engine = Engine::new(&config)?;
+    let wasm = wat::parse_str("(module (memory 2))")?;
+    assert!(Module::validate(&engine, &wasm).is_err());
+    check(&engine, &wasm);
+
+    Ok(())
+}
+
+#[test]
+fn import_and_export_indices() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "a" (func $a))
+                (import "" "b" (global $b i32))
+                (func $c)
+                (global $d i32 (i32.const 0))
+                (memory $e 1)
+                (export "a" (func $a))
+                (export "b" (global $b))
+                (export "c" (func $c))
+                (export "d" (global $d))
+                (export "e" (memory $e))
+            )
+        "#,
+    )?;
+
+    let imports = module.imports().map(|i| i.index()).collect::<Vec<_>>();
+    assert_eq!(imports, [Some(0), Some(0)]);
+
+    let exports = module
+        .exports()
+        .map(|e| (e.name(), e.index(), e.is_imported_entity()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        exports,
+        [
+            ("a", Some(0), true),
+            ("b", Some(0), true),
+            ("c", Some(1), false),
+            ("d", Some(1), false),
+            ("e", Some(0), false),
+        ]
+    );
+
+    // The index of a function matches the one reported in backtraces.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "a" (func))
+                (func (export "trap") unreachable)
+            )
+        "#,
+    )?;
+    let index = module.exports().next().unwrap().index().unwrap();
+    let mut store = Store::new(&engine, ());
+    let a = Func::wrap(&mut store, || {});
+    let instance = Instance::new(&mut store, &module, &[a.into()])?;
+    let trap = instance
+        .get_typed_func::<(), (), _>(&mut store, "trap")?
+        .call(&mut store, ())
+        .unwrap_err();
+    assert_eq!(trap.trace()[0].func_index(), index);
+
+    Ok(())
+}
+
+#[test]
+fn truncated_modules_report_offset_and_section() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (type (func (param i32) (result i32)))
+                (import "" "f" (func (type 0)))
+                (memory 1)
+                (global (mut i32) (i32.const 0))
+                (export "g" (func 1))
+                (func (type 0) local.get 0 call 0)
+                (data (i32.const 0) "hello"))
+        "#,
+    )?;
+    let engine = Engine::default();
+
+    // Every prefix after the header either is a valid module itself, or is
+    // rejected with an error pointing into the prefix.
+    for len in 8..wasm.len() {
+        let err = match Module::new(&engine, &wasm[..len]) {
+            Ok(_) => continue,
+            Err(e) => format!("{:?}", e),
+        };
+        assert!(err.contains("while parsing"), "{}", err);
+        let offset = err
+            .split("at offset ")
+            .nth(1)
+            .and_then(|s| s.split(':').next())
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or_else(|| panic!("no offset in {}", err));
+        assert!(
+            offset > 0 && offset <= len,
+            "{} of {}: {}",
+            offset,
+            len,
+            err
+        );
+    }
+
+    // Cutting off the last byte of the code section is reported there, rather
+    // than in the data section which follows it, whose 13 bytes are cut off
+    // too.
+    let len = wasm.len() - 14;
+    let err = format!("{:?}", Module::new(&engine, &wasm[..len]).err().unwrap());
+    assert!(err.contains("while parsing the code section"), "{}", err);
+    Ok(())
+}
+
+/// A module exporting a function for each of `n` distinct signatures.
+fn many_signatures(n: usize) -> String {
+    let tys = ["i32", "i64", "f32", "f64"];
+    let mut wat = String::from("(module");
+    for i in 0..n {
+        // Spell `i` in base 4 as the parameter types, so every function's
+        // signature is different.
+        let mut params = String::new();
+        let mut rest = i;
+        loop {
+            params.push_str(tys[rest % 4]);
+            params.push(' ');
+            rest /= 4;
+            if rest == 0 {
+                break;
+            }
+        }
+        wat.push_str(&format!(
+            "(func (export \"f{}\") (param {}) (result {}) unreachable)",
+            i,
+            params,
+            tys[i % 4]
+        ));
+        wat.push_str(&format!("(func (export \"g{}\") unreachable)", i));
+    }
+    wat.push_str(")");
+    wat
+}
+
+#[test]
+fn trampoline_code_size() -> Result<()> {
+    const SIGNATURES: usize = 200;
+    let wat = many_signatures(SIGNATURES);
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wat)?;
+    let size = module.trampoline_code_size();
+
+    // There's a trampoline for each distinct signature, plus one shared by
+    // the `g` functions. Each one just moves a handful of values, so this is
+    // a generous bound on their average size which should only be exceeded
+    // if something regresses.
+    assert!(size > 0);
+    assert!(
+        size <= (SIGNATURES + 1) * 256,
+        "{} bytes of trampolines for {} signatures",
+        size,
+        SIGNATURES + 1
+    );
+    let code: usize = module
+        .jit_code_ranges()
+        .map(|(start, end)| end - start)
+        .sum();
+    assert!(size < code);
+
+    // On x86-64 the trampolines took 11653 bytes when they were compiled at
+    // the module's opt level, which is also what the fixed size-oriented
+    // level gives, so allow a little slack either way but catch anything
+    // which makes them noticeably bigger.
+    const GOLDEN_X64: usize = 11653;
+    if cfg!(target_arch = "x86_64") {
+        assert!(
+            size >= GOLDEN_X64 * 9 / 10 && size <= GOLDEN_X64 * 11 / 10,
+            "{} bytes of trampolines, expected about {}",
+            size,
+            GOLDEN_X64
+        );
+    }
+
+    // Trampolines are compiled the same way whatever the level the module's
+    // functions are.
+    for level in [OptLevel::None, OptLevel::Speed, OptLevel::SpeedAndSize].iter() {
+        let module = Module::new_with_opt_level(&engine, &wat, *level)?;
+        assert_eq!(module.trampoline_code_size(), size, "{:?}", level);
+    }
+    Ok(())
+}
+
+#[test]
+fn libcalls_used() -> Result<()> {
+    let engine = Engine::default();
+
+    // Pure arithmetic doesn't need any help from the runtime.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (param i32 i64) (result i64)
+                    (i64.mul (i64.extend_i32_u (local.get 0)) (local.get 1)))
+                (func (param f64) (result f64)
+                    (f64.add (local.get 0) (f64.const 1))))
+        "#,
+    )?;
+    if cfg!(target_arch = "x86_64") {
+        assert_eq!(module.libcalls_used().count(), 0);
+        assert!(module.libcalls_used_by_functions().is_empty());
+    }
+
+    let wat = r#"
+        (module
+            (import "" "" (func))
+            (memory 1)
+            (func $copy (param i32 i32 i32)
+                (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+            (func $fill (param i32 i32 i32)
+                (memory.fill (local.get 0) (local.get 1) (local.get 2))
+                (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+            (func $floor (param f64) (result i64)
+                (i64.trunc_sat_f64_s (f64.floor (local.get 0)))))
+    "#;
+    let module = Module::new(&engine, wat)?;
+    let used = module.libcalls_used().collect::<Vec<_>>();
+    assert!(used.contains(&LibCallKind::Builtin("memory_copy")));
+    assert!(used.contains(&LibCallKind::Builtin("memory_fill")));
+
+    // Each function is reported by its index, which counts the import.
+    let by_function = module.libcalls_used_by_functions();
+    assert_eq!(by_function[0].0, 1);
+    assert_eq!(by_function[0].1, [LibCallKind::Builtin("memory_copy")]);
+    assert_eq!(by_function[1].0, 2);
+    assert_eq!(
+        by_function[1].1,
+        [
+            LibCallKind::Builtin("memory_copy"),
+            LibCallKind::Builtin("memory_fill"),
+        ]
+    );
+
+    // Without SSE4.1 rounding floats on x86_64 is done by the runtime.
+    if cfg!(target_arch = "x86_64") {
+        let mut config = Config::new();
+        unsafe {
+            config.cranelift_flag_set("has_sse41", "false")?;
+        }
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wat)?;
+        let used = module.libcalls_used().collect::<Vec<_>>();
+        assert!(used.contains(&LibCallKind::Codegen("wasmtime_f64_floor")));
+
+        // The summary is kept in serialized modules.
+        let bytes = module.serialize()?;
+        let deserialized = unsafe { Module::deserialize(&engine, &bytes)? };
+        assert_eq!(deserialized.libcalls_used().collect::<Vec<_>>(), used);
+        assert_eq!(
+            deserialized.libcalls_used_by_functions(),
+            module.libcalls_used_by_functions()
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn data_init_strategy() -> Result<()> {
+    let engine = Engine::default();
+    let eligible = if cfg!(all(feature = "uffd", target_os = "linux")) {
+        DataInitStrategy::Paged
+    } else {
+        DataInitStrategy::Segmented(SegmentedInitReason::NotEnabled)
+    };
+
+    // Overlapping, unaligned segments and memories of any style can all be
+    // laid out as pages.
+    for wat in [
+        "(module)",
+        r#"(module (memory 1) (data (i32.const 0) "a"))"#,
+        r#"(module (memory 2) (data (i32.const 3) "abc") (data (i32.const 4) "de"))"#,
+        r#"(module (memory 1 1) (data (i32.const 65535) "a"))"#,
+        r#"(module (memory 1 65536) (data (i32.const 100) "a"))"#,
+        r#"(module (memory 1) (data (i32.const 65536) "out of bounds"))"#,
+    ]
+    .iter()
+    {
+        let module = Module::new(&engine, wat)?;
+        assert_eq!(module.data_init_strategy(), eligible, "{}", wat);
+    }
+
+    let module = Module::new(
+        &engine,
+        r#"(module (import "" "m" (memory 1)) (data (i32.const 0) "a"))"#,
+    )?;
+    assert_eq!(
+        module.data_init_strategy(),
+        DataInitStrategy::Segmented(SegmentedInitReason::ImportedMemory)
+    );
+
+    let module = Module::new(
+        &engine,
+        r#"(module (import "" "g" (global i32)) (memory 1) (data (global.get 0) "a"))"#,
+    )?;
+    assert_eq!(
+        module.data_init_strategy(),
+        DataInitStrategy::Segmented(SegmentedInitReason::GlobalOffset)
+    );
+
+    // The first segment which can't be paged is the one reported.
+    let engine = Engine::new(Config::new().wasm_multi_memory(true))?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "g" (global i32))
+                (import "" "m" (memory 1))
+                (memory 1)
+                (data (memory 1) (i32.const 0) "a")
+                (data (memory 1) (global.get 0) "b")
+                (data (memory 0) (i32.const 0) "c"))
+        "#,
+    )?;
+    assert_eq!(
+        module.data_init_strategy(),
+        DataInitStrategy::Segmented(SegmentedInitReason::GlobalOffset)
+    );
+
+    // The strategy is kept in precompiled modules.
+    let module = Module::new(&engine, r#"(module (memory 1) (data (i32.const 0) "a"))"#)?;
+    let module = unsafe { Module::deserialize(&engine, &module.serialize()?)? };
+    assert_eq!(module.data_init_strategy(), eligible);
+    let module = Module::new(
+        &engine,
+        r#"(module (import "" "m" (memory 1)) (data (i32.const 0) "a"))"#,
+    )?;
+    let module = unsafe { Module::deserialize(&engine, &module.serialize()?)? };
+    assert_eq!(
+        module.data_init_strategy(),
+        DataInitStrategy::Segmented(SegmentedInitReason::ImportedMemory)
+    );
+    Ok(())
+}
@@ -58,7 +58,7 @@ fn test_module_serialize_fail() -> Result<()> {
     )?;
 
     let mut config = Config::new();
-    config.cranelift_opt_level(OptLevel::None);
+    config.cranelift_nan_canonicalization(true);
     let mut store = Store::new(&Engine::new(&config)?, ());
     match unsafe { deserialize_and_instantiate(&mut store, &buffer) } {
         Ok(_) => bail!("expected failure at deserialization"),
@@ -66,3 +66,170 @@ fn test_module_serialize_fail() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn test_trailing_data() -> Result<()> {
+    let engine = Engine::default();
+    let buffer = serialize(&engine, "(module (func (export \"run\")))")?;
+    let mut with_trailing = buffer.clone();
+    with_trailing.extend_from_slice(b"embedder metadata");
+    let lenient = DeserializeOptions {
+        allow_trailing_data: true,
+    };
+
+    // Trailing data is rejected by default, with the offset at which it
+    // starts.
+    let err = unsafe { Module::deserialize(&engine, &with_trailing) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "unexpected data at offset {} after the end of the serialized module",
+            buffer.len()
+        )
+    );
+
+    // It can be allowed, in which case where it is is reported.
+    let (module, trailing) =
+        unsafe { Module::deserialize_with_options(&engine, &with_trailing, &lenient)? };
+    assert_eq!(trailing, buffer.len()..with_trailing.len());
+    assert_eq!(&with_trailing[trailing], b"embedder metadata");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance
+        .get_typed_func::<(), (), _>(&mut store, "run")?
+        .call(&mut store, ())?;
+
+    // Without any trailing data the range is empty.
+    let (_, trailing) = unsafe { Module::deserialize_with_options(&engine, &buffer, &lenient)? };
+    assert_eq!(trailing, buffer.len()..buffer.len());
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_middle() -> Result<()> {
+    let engine = Engine::default();
+    let buffer = serialize(&engine, "(module (func (export \"run\")))")?;
+    let lenient = DeserializeOptions {
+        allow_trailing_data: true,
+    };
+
+    // Removing bytes from the middle of the module leaves it shorter than it
+    // says it is, so it's rejected in either mode.
+    let middle = buffer.len() / 2;
+    let mut removed = buffer.clone();
+    removed.drain(middle..middle + 16);
+    let err = unsafe { Module::deserialize(&engine, &removed) }
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "serialized data is truncated");
+    let err = unsafe { Module::deserialize_with_options(&engine, &removed, &lenient) }
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "serialized data is truncated");
+
+    // Inserting bytes pushes the end of the module past where it says it
+    // ends.
+    let mut inserted = buffer.clone();
+    inserted.splice(middle..middle, vec![0xff; 16]);
+    let err = unsafe { Module::deserialize(&engine, &inserted) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "unexpected data at offset {} after the end of the serialized module",
+            buffer.len()
+        )
+    );
+
+    // When trailing data is allowed the inserted bytes are caught by the
+    // checksum instead.
+    let err = unsafe { Module::deserialize_with_options(&engine, &inserted, &lenient) }
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "serialized data is corrupted");
+
+    // Changing bytes in the middle of the module keeps its length the same,
+    // and is caught by the checksum in either mode.
+    let mut changed = buffer.clone();
+    for byte in &mut changed[middle..middle + 16] {
+        *byte ^= 0xff;
+    }
+    let err = unsafe { Module::deserialize(&engine, &changed) }
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "serialized data is corrupted");
+    let err = unsafe { Module::deserialize_with_options(&engine, &changed, &lenient) }
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "serialized data is corrupted");
+
+    // The compatibility checks still apply when trailing data is allowed.
+    let mut config = Config::new();
+    config.cranelift_nan_canonicalization(true);
+    let other = Engine::new(&config)?;
+    let mut with_trailing = buffer.clone();
+    with_trailing.extend_from_slice(b"embedder metadata");
+    assert!(unsafe { Module::deserialize_with_options(&other, &with_trailing, &lenient) }.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_deserialize_file() -> Result<()> {
+    let engine = Engine::default();
+    let buffer = serialize(
+        &engine,
+        "(module (func (export \"run\") (result i32) i32.const 42))",
+    )?;
+    let dir = tempfile::TempDir::new()?;
+    let path = dir.path().join("module.cwasm");
+    std::fs::write(&path, &buffer)?;
+
+    let module = unsafe { Module::deserialize_file(&engine, &path)? };
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 42);
+
+    let missing = dir.path().join("missing.cwasm");
+    let err = unsafe { Module::deserialize_file(&engine, &missing) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("missing.cwasm"),
+        "bad error: {:?}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_debug_info_mismatch() -> Result<()> {
+    const WAT: &str = "(module (func (export \"run\")))";
+    let with_debug_info = Engine::new(Config::new().debug_info(true))?;
+    let without_debug_info = Engine::default();
+
+    let buffer = serialize(&with_debug_info, WAT)?;
+    let err = unsafe { Module::deserialize(&without_debug_info, &buffer) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Module was compiled with debug information support but it is not enabled for the host"
+    );
+
+    let buffer = serialize(&without_debug_info, WAT)?;
+    let err = unsafe { Module::deserialize(&with_debug_info, &buffer) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Module was compiled without debug information support but it is enabled for the host"
+    );
+
+    // Each loads in an engine configured like the one it was compiled with.
+    unsafe { Module::deserialize(&with_debug_info, serialize(&with_debug_info, WAT)?)? };
+    Ok(())
+}
@@ -237,6 +237,184 @@ async fn instantiate_async() -> Result<(), Error> {
     Ok(())
 }
 
+const CALL_CHAIN: &str = r#"
+    (module
+        (import "host" "f" (func $f))
+        (func (export "outer") call $f)
+        (func (export "inner"))
+        (func (export "trap") unreachable))
+"#;
+
+fn call_chain_store(engine: &Engine) -> Store<Vec<CallHook>> {
+    let mut store = Store::new(engine, Vec::new());
+    store.call_hook(|log, hook| {
+        log.push(hook);
+        Ok(())
+    });
+    store
+}
+
+// Calls the export named by the argument from a host function.
+fn call_back_into_wasm(mut caller: Caller<'_, Vec<CallHook>>, name: &str) -> Result<(), Trap> {
+    let export = caller.get_export(name).unwrap().into_func().unwrap();
+    export.call(&mut caller, &[])?;
+    Ok(())
+}
+
+#[test]
+fn call_hook_host_wasm_host_wasm() -> Result<(), Error> {
+    use CallHook::*;
+    let engine = Engine::default();
+    let module = Module::new(&engine, CALL_CHAIN)?;
+    let expected = [
+        CallingWasm,
+        CallingHost,
+        CallingWasm,
+        ReturningFromWasm,
+        ReturningFromHost,
+        ReturningFromWasm,
+    ];
+
+    let mut store = call_chain_store(&engine);
+    let f = Func::wrap(&mut store, |caller: Caller<'_, Vec<CallHook>>| {
+        call_back_into_wasm(caller, "inner")
+    });
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let outer = instance.get_typed_func::<(), (), _>(&mut store, "outer")?;
+    outer.call(&mut store, ())?;
+    assert_eq!(store.data(), &expected);
+
+    // Dynamically typed host functions and calls are the same.
+    let mut store = call_chain_store(&engine);
+    let f = Func::new(&mut store, FuncType::new(None, None), |caller, _, _| {
+        call_back_into_wasm(caller, "inner")
+    });
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let outer = instance.get_func(&mut store, "outer").unwrap();
+    outer.call(&mut store, &[])?;
+    assert_eq!(store.data(), &expected);
+    Ok(())
+}
+
+#[test]
+fn call_hook_sees_traps_return() -> Result<(), Error> {
+    use CallHook::*;
+    let engine = Engine::default();
+    let module = Module::new(&engine, CALL_CHAIN)?;
+    let expected = [
+        CallingWasm,
+        CallingHost,
+        CallingWasm,
+        ReturningFromWasm,
+        ReturningFromHost,
+        ReturningFromWasm,
+    ];
+
+    let mut store = call_chain_store(&engine);
+    let f = Func::wrap(&mut store, |caller: Caller<'_, Vec<CallHook>>| {
+        call_back_into_wasm(caller, "trap")
+    });
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let outer = instance.get_typed_func::<(), (), _>(&mut store, "outer")?;
+    let trap = outer.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    assert_eq!(store.data(), &expected);
+
+    let mut store = call_chain_store(&engine);
+    let f = Func::new(&mut store, FuncType::new(None, None), |caller, _, _| {
+        call_back_into_wasm(caller, "trap")
+    });
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let outer = instance.get_func(&mut store, "outer").unwrap();
+    let err = outer.call(&mut store, &[]).unwrap_err();
+    let trap = err.downcast::<Trap>()?;
+    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    assert_eq!(store.data(), &expected);
+
+    // A trap from the hook itself stops the call.
+    let mut store = call_chain_store(&engine);
+    store.call_hook(|log, hook| {
+        log.push(hook);
+        if hook == CallingHost {
+            return Err(Trap::new("no host calls"));
+        }
+        Ok(())
+    });
+    let f = Func::wrap(&mut store, || panic!("host function called"));
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let outer = instance.get_typed_func::<(), (), _>(&mut store, "outer")?;
+    let trap = outer.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("no host calls"), "{}", trap);
+    assert_eq!(store.data(), &[CallingWasm, CallingHost, ReturningFromWasm]);
+    Ok(())
+}
+
+#[test]
+fn call_hook_sees_panics_return() -> Result<(), Error> {
+    use CallHook::*;
+    let mut config = Config::new();
+    config.host_panic_policy(HostPanicPolicy::CatchAsTrap);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, CALL_CHAIN)?;
+    let expected = [
+        CallingWasm,
+        CallingHost,
+        ReturningFromHost,
+        ReturningFromWasm,
+    ];
+
+    let mut store = call_chain_store(&engine);
+    let f = Func::wrap(&mut store, || -> () { panic!("host panic") });
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let outer = instance.get_typed_func::<(), (), _>(&mut store, "outer")?;
+    let trap = outer.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("host panic"), "{}", trap);
+    assert_eq!(store.data(), &expected);
+
+    let mut store = call_chain_store(&engine);
+    let f = Func::new(&mut store, FuncType::new(None, None), |_, _, _| {
+        panic!("host panic")
+    });
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let outer = instance.get_func(&mut store, "outer").unwrap();
+    let err = outer.call(&mut store, &[]).unwrap_err();
+    assert!(err.to_string().contains("host panic"), "{}", err);
+    assert_eq!(store.data(), &expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_hook_async() -> Result<(), Error> {
+    use CallHook::*;
+    let mut config = Config::new();
+    config.async_support(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, CALL_CHAIN)?;
+    let mut store = call_chain_store(&engine);
+    let f = Func::wrap0_async(&mut store, |mut caller: Caller<'_, Vec<CallHook>>| {
+        Box::new(async move {
+            let inner = caller.get_export("inner").unwrap().into_func().unwrap();
+            inner.call_async(&mut caller, &[]).await?;
+            Ok::<_, Trap>(())
+        })
+    });
+    let instance = Instance::new_async(&mut store, &module, &[f.into()]).await?;
+    let outer = instance.get_typed_func::<(), (), _>(&mut store, "outer")?;
+    outer.call_async(&mut store, ()).await?;
+    assert_eq!(
+        store.data(),
+        &[
+            CallingWasm,
+            CallingHost,
+            CallingWasm,
+            ReturningFromWasm,
+            ReturningFromHost,
+            ReturningFromWasm,
+        ]
+    );
+    Ok(())
+}
+
 enum Context {
     Native,
     Vm,
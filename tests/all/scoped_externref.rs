@@ -0,0 +1,120 @@
+use anyhow::Result;
+use std::panic::{self, AssertUnwindSafe};
+use wasmtime::*;
+
+const MODULE: &str = r#"
+    (module
+        (import "" "len" (func $len (param externref) (result i32)))
+        (global $g (mut externref) (ref.null extern))
+        (table $t 1 externref)
+        (func (export "store_and_use") (param externref) (result i32)
+            (global.set $g (local.get 0))
+            (table.set $t (i32.const 0) (local.get 0))
+            (call $len (local.get 0)))
+        (func (export "use_global") (result i32)
+            (call $len (global.get $g)))
+        (func (export "use_table") (result i32)
+            (call $len (table.get $t (i32.const 0)))))
+"#;
+
+struct Guest {
+    store: Store<()>,
+    store_and_use: TypedFunc<Option<ExternRef>, i32>,
+    use_global: TypedFunc<(), i32>,
+    use_table: TypedFunc<(), i32>,
+}
+
+impl Guest {
+    fn new() -> Result<Guest> {
+        let mut store = Store::<()>::default();
+        let len = Func::wrap(&mut store, |r: Option<ExternRef>| -> Result<i32, Trap> {
+            let len = r.unwrap().with_scoped(|s: &String| s.len() as i32)?;
+            len.ok_or_else(|| Trap::new("not a scoped string"))
+        });
+        let module = Module::new(store.engine(), MODULE)?;
+        let instance = Instance::new(&mut store, &module, &[len.into()])?;
+        Ok(Guest {
+            store_and_use: instance.get_typed_func(&mut store, "store_and_use")?,
+            use_global: instance.get_typed_func(&mut store, "use_global")?,
+            use_table: instance.get_typed_func(&mut store, "use_table")?,
+            store,
+        })
+    }
+}
+
+fn assert_expired(result: Result<i32, Trap>) {
+    let trap = result.unwrap_err();
+    assert!(trap.to_string().contains("expired reference"), "{}", trap);
+}
+
+#[test]
+fn scoped_ref_expires_with_scope() -> Result<()> {
+    let mut guest = Guest::new()?;
+
+    let data = String::from("call-scoped data");
+    Scope::run(|scope| -> Result<()> {
+        let r = scope.externref(&data);
+        assert_eq!(guest.store_and_use.call(&mut guest.store, Some(r))?, 16);
+
+        // Still fine for as long as the scope lasts.
+        assert_eq!(guest.use_global.call(&mut guest.store, ())?, 16);
+        Ok(())
+    })?;
+    drop(data);
+
+    // The reference is still reachable from wasm, but can't be used anymore.
+    assert_expired(guest.use_global.call(&mut guest.store, ()));
+    assert_expired(guest.use_table.call(&mut guest.store, ()));
+
+    // References from a new scope work as usual.
+    let data = String::from("more data");
+    Scope::run(|scope| -> Result<()> {
+        let r = scope.externref(&data);
+        assert_eq!(guest.store_and_use.call(&mut guest.store, Some(r))?, 9);
+        Ok(())
+    })?;
+    assert_expired(guest.use_global.call(&mut guest.store, ()));
+    Ok(())
+}
+
+#[test]
+fn scoped_ref_expires_on_panic() -> Result<()> {
+    let mut guest = Guest::new()?;
+    let data = String::from("data");
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        Scope::run(|scope| {
+            let r = scope.externref(&data);
+            assert_eq!(
+                guest.store_and_use.call(&mut guest.store, Some(r)).unwrap(),
+                4
+            );
+            panic!("oops");
+        })
+    }));
+    assert!(result.is_err());
+    assert_expired(guest.use_global.call(&mut guest.store, ()));
+    Ok(())
+}
+
+#[test]
+fn with_scoped_on_other_refs() -> Result<()> {
+    // Refs which aren't scoped, or are of another type, aren't scoped data.
+    let r = ExternRef::new(String::from("owned"));
+    assert!(r.with_scoped(|s: &String| s.len())?.is_none());
+
+    let data = 42_u32;
+    Scope::run(|scope| -> Result<()> {
+        let r = scope.externref(&data);
+        assert!(r.with_scoped(|s: &String| s.len())?.is_none());
+        assert_eq!(r.with_scoped(|n: &u32| *n)?, Some(42));
+        Ok(())
+    })?;
+
+    let mut guest = Guest::new()?;
+    let err = guest
+        .store_and_use
+        .call(&mut guest.store, Some(ExternRef::new(0_u32)))
+        .unwrap_err();
+    assert!(err.to_string().contains("not a scoped string"), "{}", err);
+    Ok(())
+}
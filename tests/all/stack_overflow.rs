@@ -58,3 +58,127 @@ fn host_always_has_some_stack() -> anyhow::Result<()> {
         consume_some_stack(space.as_mut_ptr() as usize, stack.saturating_sub(1024))
     }
 }
+
+const PING_PONG: &str = r#"
+    (module
+        (import "" "" (func $host (param i32)))
+        (func $run (export "run") (param i32)
+            local.get 0
+            i32.const 1
+            i32.add
+            call $host)
+    )
+"#;
+
+fn assert_ping_pong_trap(trap: &Trap, depth: usize) {
+    assert_eq!(trap.trap_code(), Some(TrapCode::StackOverflow), "{}", trap);
+    let trace = trap.trace();
+    assert!(trace.len() >= depth, "{} < {}", trace.len(), depth);
+    for frame in trace {
+        assert_eq!(frame.func_name(), Some("run"));
+    }
+}
+
+/// Runs wasm which calls the host which calls the wasm again, forever, and
+/// returns how deep it got before trapping.
+fn sync_ping_pong_depth(max_wasm_stack: usize, max_native_stack: usize) -> anyhow::Result<usize> {
+    let mut config = Config::new();
+    config
+        .max_wasm_stack(max_wasm_stack)?
+        .max_native_stack(max_native_stack)?;
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, PING_PONG)?;
+    let mut store = Store::new(&engine, 0);
+    let host = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, usize>, depth: i32| -> Result<(), Trap> {
+            *caller.data_mut() = depth as usize;
+            let run = caller
+                .get_export("run")
+                .unwrap()
+                .into_func()
+                .unwrap()
+                .typed::<i32, (), _>(&caller)?;
+            run.call(&mut caller, depth)
+        },
+    );
+    let instance = Instance::new(&mut store, &module, &[host.into()])?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+    let trap = run.call(&mut store, 0).unwrap_err();
+    let depth = *store.data();
+    assert_ping_pong_trap(&trap, depth);
+    Ok(depth)
+}
+
+#[test]
+fn host_wasm_ping_pong_traps() -> anyhow::Result<()> {
+    // By default all activations share a single `max_wasm_stack` budget.
+    let shared = sync_ping_pong_depth(128 << 10, 128 << 10)?;
+    assert!(shared > 1);
+
+    // Each activation gets its own `max_wasm_stack`, so raising the total
+    // budget lets the recursion go proportionally deeper.
+    let deeper = sync_ping_pong_depth(128 << 10, 1 << 20)?;
+    assert!(deeper > shared * 4, "{} vs {}", deeper, shared);
+
+    // And the depth is predictable for the same configuration, give or take
+    // some slop in where the stack pointer is measured.
+    let again = sync_ping_pong_depth(128 << 10, 1 << 20)?;
+    assert!((again as isize - deeper as isize).abs() <= 1);
+    Ok(())
+}
+
+#[test]
+fn absurd_stack_limits_are_rejected() {
+    let mut config = Config::new();
+    for size in [MAX_STACK_LIMIT + 1, usize::MAX / 2, usize::MAX].iter() {
+        let err = config.max_native_stack(*size).unwrap_err();
+        assert!(err.to_string().contains("cannot exceed"), "{}", err);
+        assert!(config.max_wasm_stack(*size).is_err());
+    }
+    assert!(config.max_native_stack(MAX_STACK_LIMIT).is_ok());
+
+    // The async stack size has to be raised before the wasm stack limit can
+    // be, but the cap applies regardless.
+    let err = config.max_wasm_stack(4 << 20).unwrap_err();
+    assert!(err.to_string().contains("async stack size"), "{}", err);
+    config.async_stack_size(2 * MAX_STACK_LIMIT).unwrap();
+    assert!(config.max_wasm_stack(MAX_STACK_LIMIT).is_ok());
+    let err = config.max_wasm_stack(MAX_STACK_LIMIT + 1).unwrap_err();
+    assert!(err.to_string().contains("cannot exceed"), "{}", err);
+}
+
+#[test]
+fn recursion_depth_follows_stack_limit() -> anyhow::Result<()> {
+    // Returns how many calls deep a recursive function gets with a stack
+    // limit of `size`.
+    fn depth(size: usize) -> anyhow::Result<i32> {
+        let engine = Engine::new(Config::new().max_wasm_stack(size)?)?;
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(
+            &engine,
+            r#"
+                (module
+                    (global $depth (export "depth") (mut i32) (i32.const 0))
+                    (func $recurse (export "recurse")
+                        (global.set $depth (i32.add (global.get $depth) (i32.const 1)))
+                        call $recurse))
+            "#,
+        )?;
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let recurse = instance.get_typed_func::<(), (), _>(&mut store, "recurse")?;
+        let trap = recurse.call(&mut store, ()).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::StackOverflow), "{}", trap);
+        let depth = instance.get_global(&mut store, "depth").unwrap();
+        Ok(depth.get(&mut store).unwrap_i32())
+    }
+
+    let small = depth(64 << 10)?;
+    let large = depth(1 << 20)?;
+    assert!(small > 0);
+    // Frames are the same size either way, so the depth is roughly
+    // proportional to the limit, give or take the slop at entry.
+    assert!(large > small * 12, "{} vs {}", large, small);
+    assert!(large < small * 20, "{} vs {}", large, small);
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use wasmtime::*;
+
+const SPINNING_START: &str = r#"
+    (module
+        (func $spin (loop (br 0)))
+        (start $spin))
+"#;
+
+const NORMAL_START: &str = r#"
+    (module
+        (global $g (export "g") (mut i32) (i32.const 0))
+        (func $start (global.set $g (i32.const 1)))
+        (start $start)
+        (func (export "count") (param i32)
+            (loop
+                (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                (br_if 0 (local.get 0)))))
+"#;
+
+fn fuel_budget(fuel: u64) -> StartBudget {
+    let mut budget = StartBudget::new();
+    budget.fuel(fuel);
+    budget
+}
+
+#[test]
+fn spinning_start_runs_out_of_fuel() -> Result<()> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(10_000)?;
+
+    let module = Module::new(&engine, SPINNING_START)?;
+    let err = Instance::new_with_budget(&mut store, &module, &[], &fuel_budget(1_000)).unwrap_err();
+    let exceeded = err.downcast_ref::<StartBudgetExceeded>().unwrap();
+    assert_eq!(exceeded.limit(), StartLimit::Fuel);
+    assert_eq!(exceeded.trap().trap_code(), Some(TrapCode::OutOfFuel));
+    assert!(exceeded.trap().to_string().contains("all fuel consumed"));
+
+    // Only the budget was consumed, give or take the cost of the last block
+    // before the fuel check.
+    let consumed = store.fuel_consumed().unwrap();
+    assert!(consumed >= 1_000 && consumed < 1_010, "{}", consumed);
+
+    // The store's own fuel is still all there.
+    let module = Module::new(&engine, NORMAL_START)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let count = instance.get_typed_func::<i32, (), _>(&mut store, "count")?;
+    count.call(&mut store, 500)?;
+    assert!(count.call(&mut store, 2_000).is_err());
+    Ok(())
+}
+
+#[test]
+fn normal_start_is_unaffected() -> Result<()> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(100)?;
+
+    let module = Module::new(&engine, NORMAL_START)?;
+    let instance = Instance::new_with_budget(&mut store, &module, &[], &fuel_budget(1_000))?;
+    let g = instance.get_global(&mut store, "g").unwrap();
+    assert_eq!(g.get(&mut store).i32(), Some(1));
+    let consumed = store.fuel_consumed().unwrap();
+    assert!(consumed > 0 && consumed < 10, "{}", consumed);
+
+    // The store's fuel is what it was before instantiation.
+    let count = instance.get_typed_func::<i32, (), _>(&mut store, "count")?;
+    let before = store.fuel_consumed().unwrap();
+    assert!(count.call(&mut store, 1_000).is_err());
+    let used = store.fuel_consumed().unwrap() - before;
+    assert!(used > 100 && used < 110, "{}", used);
+
+    // The budget is also applied with `InstancePre`.
+    let mut store = Store::new(&engine, ());
+    let pre = Linker::new(&engine).instantiate_pre(&mut store, &module)?;
+    pre.instantiate_with_budget(&mut store, &fuel_budget(1_000))?;
+    let module = Module::new(&engine, SPINNING_START)?;
+    let pre = Linker::new(&engine).instantiate_pre(&mut store, &module)?;
+    let err = pre
+        .instantiate_with_budget(&mut store, &fuel_budget(1_000))
+        .unwrap_err();
+    assert!(err.is::<StartBudgetExceeded>());
+    Ok(())
+}
+
+#[test]
+fn spinning_start_runs_out_of_time() -> Result<()> {
+    let engine = Engine::new(Config::new().interruptable(true))?;
+    let mut store = Store::new(&engine, ());
+
+    let module = Module::new(&engine, SPINNING_START)?;
+    let mut budget = StartBudget::new();
+    budget.time_limit(Duration::from_millis(50));
+    let start = Instant::now();
+    let err = Instance::new_with_budget(&mut store, &module, &[], &budget).unwrap_err();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    let exceeded = err.downcast_ref::<StartBudgetExceeded>().unwrap();
+    assert_eq!(exceeded.limit(), StartLimit::Time);
+    assert_eq!(exceeded.trap().trap_code(), Some(TrapCode::Interrupt));
+
+    // Nothing is left interrupted for the next instantiation, even when it
+    // finishes well within the time limit.
+    budget.time_limit(Duration::from_secs(60));
+    let module = Module::new(&engine, NORMAL_START)?;
+    let instance = Instance::new_with_budget(&mut store, &module, &[], &budget)?;
+    let count = instance.get_typed_func::<i32, (), _>(&mut store, "count")?;
+    count.call(&mut store, 1_000)?;
+    Ok(())
+}
+
+#[test]
+fn other_traps_are_not_budget_errors() -> Result<()> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, "(module (func $start unreachable) (start $start))")?;
+    let err = Instance::new_with_budget(&mut store, &module, &[], &fuel_budget(1_000)).unwrap_err();
+    assert!(!err.is::<StartBudgetExceeded>());
+    let trap = err.downcast::<Trap>()?;
+    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+
+    // Even when the trap happens to leave no fuel in the budget.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "" (func $drain))
+                (func $start call $drain)
+                (start $start))
+        "#,
+    )?;
+    let drain = Func::wrap(&mut store, |mut caller: Caller<'_, ()>| {
+        let remaining = caller.fuel_remaining().unwrap();
+        caller.consume_fuel(remaining).unwrap();
+        Err::<(), _>(Trap::new("drained"))
+    });
+    let err = Instance::new_with_budget(&mut store, &module, &[drain.into()], &fuel_budget(1_000))
+        .unwrap_err();
+    assert!(!err.is::<StartBudgetExceeded>());
+    assert!(err.to_string().contains("drained"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn time_limits_of_concurrent_instantiations() -> Result<()> {
+    let mut config = Config::new();
+    config.interruptable(true);
+    let engine = Engine::new(&config)?;
+    let spinning = Module::new(&engine, SPINNING_START)?;
+    let normal = Module::new(&engine, NORMAL_START)?;
+
+    // Each thread's time limit interrupts only its own start function, and
+    // only once its own limit has passed.
+    let threads = (0..4u64)
+        .map(|i| {
+            let engine = engine.clone();
+            let spinning = spinning.clone();
+            let normal = normal.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let mut store = Store::new(&engine, ());
+                let mut budget = StartBudget::new();
+                let limit = Duration::from_millis(20 * (i + 1));
+                budget.time_limit(limit);
+                let start = Instant::now();
+                let err =
+                    Instance::new_with_budget(&mut store, &spinning, &[], &budget).unwrap_err();
+                assert!(start.elapsed() >= limit);
+                let exceeded = err.downcast_ref::<StartBudgetExceeded>().unwrap();
+                assert_eq!(exceeded.limit(), StartLimit::Time);
+
+                budget.time_limit(Duration::from_secs(60));
+                Instance::new_with_budget(&mut store, &normal, &[], &budget)?;
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn budget_requires_config() -> Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, SPINNING_START)?;
+
+    let err = Instance::new_with_budget(&mut store, &module, &[], &fuel_budget(1_000)).unwrap_err();
+    assert!(format!("{:#}", err).contains("fuel is not configured"));
+
+    let mut budget = StartBudget::new();
+    budget.time_limit(Duration::from_secs(1));
+    let err = Instance::new_with_budget(&mut store, &module, &[], &budget).unwrap_err();
+    assert!(format!("{:#}", err).contains("interrupts aren't enabled"));
+    Ok(())
+}
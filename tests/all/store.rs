@@ -1,5 +1,6 @@
-use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
-use wasmtime::{Engine, Store};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use wasmtime::*;
 
 #[test]
 fn into_inner() {
@@ -20,3 +21,246 @@ fn into_inner() {
     Store::new(&engine, A).into_data();
     assert_eq!(HITS.load(SeqCst), 2);
 }
+
+const PREPARED_MODULE: &str = r#"
+    (module $prepared
+        (import "" "gc" (func $gc))
+        (func $run (export "run") (param externref) (result externref)
+            call $gc
+            local.get 0)
+        (func $trap (export "trap")
+            unreachable)
+    )
+"#;
+
+#[test]
+fn prepared_modules_resolve_traps() -> anyhow::Result<()> {
+    let mut config = Config::new();
+    config.wasm_reference_types(true);
+    let engine = Engine::new(&config)?;
+    let prepared_module = Module::new(&engine, PREPARED_MODULE)?;
+    let other = Module::new(
+        &engine,
+        r#"(module $other (func (export "trap") unreachable))"#,
+    )?;
+    let prepared = PreparedModules::new(&engine, &[prepared_module.clone()])?;
+
+    // Create a couple of stores from the same set to make sure that sharing
+    // the registration between them works.
+    for _ in 0..2 {
+        let mut store = Store::new_with_modules(&engine, (), &prepared);
+        let gc = Func::wrap(&mut store, || {});
+        let instance = Instance::new(&mut store, &prepared_module, &[gc.into()])?;
+        let trap = instance
+            .get_typed_func::<(), (), _>(&mut store, "trap")?
+            .call(&mut store, ())
+            .unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+        assert_eq!(trap.trace().len(), 1);
+        assert_eq!(trap.trace()[0].module_name(), Some("prepared"));
+        assert_eq!(trap.trace()[0].func_name(), Some("trap"));
+
+        // Modules outside of the prepared set are registered as usual.
+        let instance = Instance::new(&mut store, &other, &[])?;
+        let trap = instance
+            .get_typed_func::<(), (), _>(&mut store, "trap")?
+            .call(&mut store, ())
+            .unwrap_err();
+        assert_eq!(trap.trace()[0].module_name(), Some("other"));
+
+        let map = store.symbol_map();
+        assert_eq!(map.entries().len(), 3);
+    }
+    Ok(())
+}
+
+#[test]
+fn prepared_modules_resolve_stack_maps() -> anyhow::Result<()> {
+    let mut config = Config::new();
+    config.wasm_reference_types(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, PREPARED_MODULE)?;
+    let prepared = PreparedModules::new(&engine, &[module.clone()])?;
+    let mut store = Store::new_with_modules(&engine, (), &prepared);
+
+    let r = ExternRef::new(42_u32);
+    let held = r.clone();
+    let gc = Func::wrap(&mut store, move |mut caller: Caller<'_, ()>| {
+        // The `externref` is live in the wasm frame below us, which can only
+        // be seen through that frame's stack map. If it weren't found, the
+        // activations table would drop its reference, leaving just `r`,
+        // `held`, and the argument passed to `run`.
+        caller.gc();
+        assert!(held.strong_count() >= 4);
+    });
+    let instance = Instance::new(&mut store, &module, &[gc.into()])?;
+    let run = instance.get_func(&mut store, "run").unwrap();
+    let results = run.call(&mut store, &[Val::ExternRef(Some(r.clone()))])?;
+    let result = results[0].unwrap_externref().unwrap();
+    assert!(result.ptr_eq(&r));
+    drop((result, results));
+
+    store.gc();
+    assert_eq!(r.strong_count(), 2);
+    drop(store);
+    assert_eq!(r.strong_count(), 1);
+    Ok(())
+}
+
+#[test]
+fn prune_modules_of_failed_instantiations() -> anyhow::Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, StoreLimitsBuilder::new().memory_pages(1).build());
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+
+    let module = Module::new(
+        &engine,
+        r#"(module (memory 1) (func (export "f") (result i32) i32.const 42))"#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let baseline = store.symbol_map().entries().len();
+    assert_eq!(baseline, 1);
+
+    // Each of these modules is registered with the store, but its memory is
+    // too large for the store's limits so no instance is ever created.
+    for i in 0..100 {
+        let module = Module::new(
+            &engine,
+            &format!(
+                r#"(module (memory 2) (func (export "f") (result i32) i32.const {}))"#,
+                i
+            ),
+        )?;
+        assert!(Instance::new(&mut store, &module, &[]).is_err());
+    }
+    assert_eq!(store.symbol_map().entries().len(), baseline + 100);
+
+    assert_eq!(store.prune_unused_modules(), 100);
+    assert_eq!(store.symbol_map().entries().len(), baseline);
+    assert_eq!(store.prune_unused_modules(), 0);
+
+    // The remaining instance is unaffected.
+    let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+    assert_eq!(f.call(&mut store, ())?, 42);
+    Instance::new(&mut store, &module, &[])?;
+    assert_eq!(store.prune_unused_modules(), 0);
+    Ok(())
+}
+
+#[test]
+fn store_ids() -> anyhow::Result<()> {
+    let engine = Engine::default();
+    let mut store1 = Store::new(&engine, ());
+    let mut store2 = Store::new(&engine, ());
+    assert_ne!(store1.id(), store2.id());
+    assert_eq!(store1.as_context().id(), store1.id());
+    assert_eq!(store1.as_context_mut().id(), store1.id());
+
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "f"))
+                (global (export "g") i32 (i32.const 0))
+                (memory (export "m") 1)
+                (table (export "t") 1 funcref))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store1, &module, &[])?;
+    assert_eq!(instance.store_id(), store1.id());
+    let exports = instance
+        .exports(&mut store1)
+        .map(|export| export.into_extern())
+        .collect::<Vec<_>>();
+    assert_eq!(exports.len(), 4);
+    for export in exports {
+        assert_eq!(export.store_id(), Some(store1.id()));
+    }
+    let memory = Memory::new(&mut store1, MemoryType::new(Limits::new(1, None)))?;
+    assert_eq!(memory.store_id(), store1.id());
+
+    let func = Func::wrap(&mut store2, || {});
+    assert_eq!(func.store_id(), store2.id());
+    assert_ne!(func.store_id(), store1.id());
+    assert_eq!(Extern::from(module).store_id(), None);
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "object used with the wrong store: it belongs to store")]
+fn wrong_store_panic_names_stores() {
+    let engine = Engine::default();
+    let mut store1 = Store::new(&engine, ());
+    let store2 = Store::new(&engine, ());
+    let memory = Memory::new(&mut store1, MemoryType::new(Limits::new(1, None))).unwrap();
+    memory.size(&store2);
+}
+
+#[test]
+fn reset() -> anyhow::Result<()> {
+    let mut config = Config::new();
+    config.consume_fuel(true).wasm_reference_types(true);
+    let engine = Engine::new(&config)?;
+    let limits = StoreLimitsBuilder::new().instances(1).build();
+    let mut store = Store::new(&engine, limits);
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (global (export "g") (mut externref) (ref.null extern))
+                (func (export "f") (result i32) (i32.const 42))
+                (func (export "take") (param externref)))
+        "#,
+    )?;
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let global = instance.get_global(&mut store, "g").unwrap();
+    let r = ExternRef::new(SetFlagOnDrop(dropped.clone()));
+    global.set(&mut store, Val::ExternRef(Some(r)))?;
+    store.add_fuel(1_000)?;
+    let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+    assert_eq!(f.call(&mut store, ())?, 42);
+    assert!(store.fuel_consumed().unwrap() > 0);
+
+    // Passing an `externref` into wasm roots it in the store until the next GC.
+    let passed = Arc::new(AtomicBool::new(false));
+    let take = instance.get_typed_func::<Option<ExternRef>, (), _>(&mut store, "take")?;
+    take.call(
+        &mut store,
+        Some(ExternRef::new(SetFlagOnDrop(passed.clone()))),
+    )?;
+    assert!(!passed.load(SeqCst));
+
+    let old_id = store.id();
+    store.reset();
+    assert_ne!(store.id(), old_id);
+    assert!(dropped.load(SeqCst));
+    assert!(passed.load(SeqCst));
+    assert_eq!(store.fuel_consumed(), Some(0));
+    assert_eq!(store.fuel_remaining(), Some(0));
+
+    // Objects from before the reset no longer belong to the store.
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = f.call(&mut store, ());
+    }));
+    assert!(result.is_err());
+
+    // The limiter is still in place, but the instance from before the reset
+    // no longer counts against it.
+    let instance = Instance::new(&mut store, &module, &[])?;
+    assert!(Instance::new(&mut store, &module, &[]).is_err());
+    store.add_fuel(1_000)?;
+    let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+    assert_eq!(f.call(&mut store, ())?, 42);
+    Ok(())
+}
+
+struct SetFlagOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetFlagOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, SeqCst);
+    }
+}
@@ -22,7 +22,7 @@ fn fill_wrong() {
             .fill(&mut store, 0, Val::ExternRef(None), 1)
             .map_err(|e| e.to_string())
             .unwrap_err(),
-        "value does not match table element type"
+        "value of type `externref` does not match table element type `funcref`"
     );
 
     let ty = TableType::new(ValType::ExternRef, Limits::new(1, None));
@@ -32,7 +32,7 @@ fn fill_wrong() {
             .fill(&mut store, 0, Val::FuncRef(None), 1)
             .map_err(|e| e.to_string())
             .unwrap_err(),
-        "value does not match table element type"
+        "value of type `funcref` does not match table element type `externref`"
     );
 }
 
@@ -50,3 +50,245 @@ fn copy_wrong() {
         "tables do not have the same element type"
     );
 }
+
+#[test]
+fn new_wrong() {
+    let mut store = Store::<()>::default();
+    let ty = TableType::new(ValType::FuncRef, Limits::new(1, None));
+    assert_eq!(
+        Table::new(&mut store, ty.clone(), Val::I32(0))
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "value of type `i32` does not match table element type `funcref`"
+    );
+    assert_eq!(
+        Table::new(&mut store, ty, Val::ExternRef(None))
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "value of type `externref` does not match table element type `funcref`"
+    );
+
+    let ty = TableType::new(ValType::ExternRef, Limits::new(1, None));
+    let func = Func::wrap(&mut store, || {});
+    assert_eq!(
+        Table::new(&mut store, ty, func.into())
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "value of type `funcref` does not match table element type `externref`"
+    );
+
+    let ty = TableType::new(ValType::I32, Limits::new(1, None));
+    assert_eq!(
+        Table::new(&mut store, ty, Val::I32(0))
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "unsupported table element type `i32`"
+    );
+}
+
+#[test]
+fn set_wrong() {
+    let mut store = Store::<()>::default();
+    let ty = TableType::new(ValType::FuncRef, Limits::new(1, None));
+    let table = Table::new(&mut store, ty, Val::FuncRef(None)).unwrap();
+    assert_eq!(
+        table
+            .set(&mut store, 0, Val::I64(0))
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "value of type `i64` does not match table element type `funcref`"
+    );
+
+    let ty = TableType::new(ValType::ExternRef, Limits::new(1, None));
+    let table = Table::new(&mut store, ty, Val::ExternRef(None)).unwrap();
+    assert_eq!(
+        table
+            .set(&mut store, 0, Val::FuncRef(None))
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "value of type `funcref` does not match table element type `externref`"
+    );
+}
+
+#[test]
+fn grow_wrong() {
+    let mut store = Store::<()>::default();
+    let ty = TableType::new(ValType::FuncRef, Limits::new(1, None));
+    let table = Table::new(&mut store, ty, Val::FuncRef(None)).unwrap();
+    assert_eq!(
+        table
+            .grow(&mut store, 1, Val::ExternRef(None))
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "value of type `externref` does not match table element type `funcref`"
+    );
+    assert_eq!(table.size(&store), 1);
+
+    let ty = TableType::new(ValType::ExternRef, Limits::new(1, None));
+    let table = Table::new(&mut store, ty, Val::ExternRef(None)).unwrap();
+    assert_eq!(
+        table
+            .grow(&mut store, 1, Val::F32(0))
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+        "value of type `f32` does not match table element type `externref`"
+    );
+    assert_eq!(table.size(&store), 1);
+}
+
+#[test]
+fn call_indirect_grown_null() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let ty = TableType::new(ValType::FuncRef, Limits::new(1, None));
+    let table = Table::new(&mut store, ty, Val::FuncRef(None))?;
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (table (import "" "table") 1 funcref)
+                (func $f)
+                (elem (i32.const 0) $f)
+                (func (export "grow") (param i32) (result i32)
+                    ref.null func
+                    local.get 0
+                    table.grow)
+                (func (export "call") (param i32)
+                    local.get 0
+                    call_indirect))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[table.into()])?;
+    let grow = instance.get_typed_func::<u32, i32, _>(&mut store, "grow")?;
+    let call = instance.get_typed_func::<u32, (), _>(&mut store, "call")?;
+
+    call.call(&mut store, 0)?;
+
+    // Slots added by the embedder and by wasm are both null.
+    assert_eq!(table.grow(&mut store, 1, Val::FuncRef(None))?, 1);
+    assert_eq!(grow.call(&mut store, 1)?, 2);
+    for index in 1..3 {
+        assert!(matches!(
+            table.get(&mut store, index),
+            Some(Val::FuncRef(None))
+        ));
+        let trap = call.call(&mut store, index).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::IndirectCallToNull));
+    }
+    Ok(())
+}
+
+/// A scan over a table which counts its non-null entries, and keeps scanning
+/// to the table's current size as it goes.
+const TABLE_SCAN: &str = r#"
+    (module
+        (import "" "grow" (func $grow (param i32)))
+        (table (export "table") 1 funcref)
+        (func $f)
+        (elem declare func $f)
+        (func (export "scan_and_grow") (param $n i32) (result i32)
+            (local $i i32) (local $count i32)
+            (block
+                (loop
+                    (br_if 1 (i32.ge_u (local.get $i) (table.size 0)))
+                    (if (i32.lt_u (local.get $i) (local.get $n))
+                        (drop (table.grow 0 (ref.func $f) (i32.const 1))))
+                    (if (i32.eqz (ref.is_null (table.get 0 (local.get $i))))
+                        (local.set $count (i32.add (local.get $count) (i32.const 1))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br 0)))
+            local.get $count)
+        (func (export "scan_and_call") (param $n i32) (result i32)
+            (local $i i32) (local $count i32)
+            (block
+                (loop
+                    (br_if 1 (i32.ge_u (local.get $i) (table.size 0)))
+                    (call $grow (local.get $n))
+                    (if (i32.eqz (ref.is_null (table.get 0 (local.get $i))))
+                        (local.set $count (i32.add (local.get $count) (i32.const 1))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br 0)))
+            local.get $count))
+"#;
+
+#[test]
+fn scan_sees_table_grown_in_loop() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), TABLE_SCAN)?;
+    let grow = Func::wrap(&mut store, |_: i32| {});
+    let instance = Instance::new(&mut store, &module, &[grow.into()])?;
+    let scan = instance.get_typed_func::<i32, i32, _>(&mut store, "scan_and_grow")?;
+
+    // Each of the first 100 iterations adds an entry which a later one reads.
+    assert_eq!(scan.call(&mut store, 100)?, 100);
+    let table = instance.get_table(&mut store, "table").unwrap();
+    assert_eq!(table.size(&store), 101);
+    Ok(())
+}
+
+#[test]
+fn scan_sees_table_grown_by_callee() -> anyhow::Result<()> {
+    // Callers can't look up tables, so the table is handed over in the
+    // store's data once the module is instantiated.
+    let mut store = Store::<Option<Table>>::default();
+    let module = Module::new(store.engine(), TABLE_SCAN)?;
+    let grow = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, Option<Table>>, n: i32| {
+            let table = caller.data().unwrap();
+            let f = caller
+                .get_export("scan_and_call")
+                .and_then(|e| e.into_func())
+                .unwrap();
+            if table.size(&caller) <= n as u32 {
+                table.grow(&mut caller, 1, f.into()).unwrap();
+            }
+        },
+    );
+    let instance = Instance::new(&mut store, &module, &[grow.into()])?;
+    *store.data_mut() = instance.get_table(&mut store, "table");
+    let scan = instance.get_typed_func::<i32, i32, _>(&mut store, "scan_and_call")?;
+
+    // The host grows the table on each of the first 100 calls, and the scan
+    // reads every new entry.
+    assert_eq!(scan.call(&mut store, 100)?, 100);
+    let table = instance.get_table(&mut store, "table").unwrap();
+    assert_eq!(table.size(&store), 101);
+    Ok(())
+}
+
+#[test]
+fn val_errors() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let ty = TableType::new(ValType::FuncRef, Limits::new(1, None));
+    let table = Table::new(&mut store, ty, Val::FuncRef(None))?;
+
+    let errors = vec![
+        table.set(&mut store, 0, Val::I32(0)).unwrap_err(),
+        table.fill(&mut store, 0, Val::I32(0), 1).unwrap_err(),
+        table.grow(&mut store, 1, Val::I32(0)).unwrap_err(),
+    ];
+    for err in errors {
+        assert_eq!(
+            err.to_string(),
+            "value of type `i32` does not match table element type `funcref`"
+        );
+        match err.downcast_ref::<ValError>() {
+            Some(ValError::TypeMismatch {
+                site: ValSite::TableElement,
+                expected: ValType::FuncRef,
+                found: ValType::I32,
+            }) => {}
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    let mut other_store = Store::new(store.engine(), ());
+    let f = Func::wrap(&mut other_store, || {});
+    let err = table.set(&mut store, 0, f.into()).unwrap_err();
+    assert!(err.to_string().contains("cross-`Store`"));
+    assert!(matches!(
+        err.downcast_ref::<ValError>(),
+        Some(ValError::CrossStore)
+    ));
+    Ok(())
+}
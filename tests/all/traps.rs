@@ -105,6 +105,50 @@ fn test_trap_trace_cb() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_trap_without_backtrace_cb() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let wat = r#"
+        (module $hello_mod
+            (import "" "throw" (func $throw))
+            (func (export "run") (call $hello))
+            (func $hello (call $throw))
+            (func (export "oob") (drop (i32.load (i32.const 0))))
+            (memory 0)
+        )
+    "#;
+
+    let fn_type = FuncType::new(None, None);
+    let fn_func = Func::new(&mut store, fn_type, |_, _, _| {
+        Err(Trap::new_without_backtrace("cb throw"))
+    });
+
+    let module = Module::new(store.engine(), wat)?;
+    let instance = Instance::new(&mut store, &module, &[fn_func.into()])?;
+    let run_func = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+
+    let e = run_func
+        .call(&mut store, ())
+        .err()
+        .expect("error calling function");
+    assert!(e.trace().is_empty());
+    assert_eq!(e.to_string(), "cb throw");
+    assert!(e.trap_code().is_none());
+
+    // Traps from wasm itself still have a backtrace.
+    let oob_func = instance.get_typed_func::<(), (), _>(&mut store, "oob")?;
+    let e = oob_func
+        .call(&mut store, ())
+        .err()
+        .expect("error calling function");
+    if !cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        assert_eq!(e.trace().len(), 1);
+    }
+    assert_eq!(e.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
 fn test_trap_stack_overflow() -> Result<()> {
@@ -219,6 +263,229 @@ wasm backtrace:
     Ok(())
 }
 
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn trap_instance_names() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let wat = r#"
+        (module $tenant
+            (func $die unreachable)
+            (func (export "run") call $die)
+        )
+    "#;
+    let module = Module::new(store.engine(), wat)?;
+    let a = Instance::new(&mut store, &module, &[])?;
+    let b = Instance::new(&mut store, &module, &[])?;
+    assert_ne!(a.id(), b.id());
+    assert_eq!(a.name(&store), None);
+
+    a.set_name(&mut store, "tenant-a");
+    b.set_name(&mut store, "tenant-b");
+    assert_eq!(a.name(&store), Some("tenant-a"));
+    assert_eq!(b.name(&store), Some("tenant-b"));
+
+    for (instance, name) in [(a, "tenant-a"), (b, "tenant-b")].iter() {
+        let id = instance.id();
+        let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+        for _ in 0..2 {
+            let e = run.call(&mut store, ()).unwrap_err();
+            assert_eq!(e.trace().len(), 2);
+            for frame in e.trace() {
+                assert_eq!(frame.instance_name(), Some(*name));
+            }
+            assert!(
+                e.to_string().contains(&format!("[{}] tenant!die", name)),
+                "{}",
+                e
+            );
+            assert_eq!(instance.id(), id);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn trap_instance_names_across_instances() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let wat = r#"
+        (module $tenant
+            (import "" "die" (func $imported))
+            (func $die (export "die") unreachable)
+            (func (export "run") call $imported)
+        )
+    "#;
+    let module = Module::new(store.engine(), wat)?;
+    let die = Func::wrap(&mut store, || {});
+    let a = Instance::new_with_name(&mut store, &module, &[die.into()], "tenant-a")?;
+    let die = a.get_func(&mut store, "die").unwrap();
+    let b = Instance::new_with_name(&mut store, &module, &[die.into()], "tenant-b")?;
+    assert_eq!(b.name(&store), Some("tenant-b"));
+
+    // `b` calls into `a` through its import, and only the frame which wasm
+    // was entered through is known to be `b`'s.
+    let run = b.get_typed_func::<(), (), _>(&mut store, "run")?;
+    let e = run.call(&mut store, ()).unwrap_err();
+    assert_eq!(e.trace().len(), 2);
+    assert_eq!(e.trace()[0].instance_name(), None);
+    assert_eq!(e.trace()[1].instance_name(), Some("tenant-b"));
+    Ok(())
+}
+
+#[test]
+fn resource_limit_error_names_instance() -> Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, StoreLimitsBuilder::new().instances(1).build());
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    let module = Module::new_with_name(&engine, "(module)", "tenant")?;
+    Instance::new_with_name(&mut store, &module, &[], "tenant-a")?;
+    let e = Instance::new_with_name(&mut store, &module, &[], "tenant-b").unwrap_err();
+    assert!(
+        e.to_string()
+            .contains("while instantiating instance `tenant-b` of module `tenant`"),
+        "{}",
+        e
+    );
+    Ok(())
+}
+
+#[test]
+fn defer_on_unwind() -> Result<()> {
+    let mut store = Store::<Vec<&'static str>>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "" "acquire" (func $acquire (param i32)))
+                (func (export "run") (param i32)
+                    i32.const 1
+                    call $acquire
+                    i32.const 2
+                    call $acquire
+                    local.get 0
+                    if
+                        unreachable
+                    end))
+        "#,
+    )?;
+    let acquire = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, Vec<&'static str>>, which: i32| {
+            let name = if which == 1 { "release 1" } else { "release 2" };
+            caller.defer_on_unwind(move |released| released.push(name));
+        },
+    );
+    let instance = Instance::new(&mut store, &module, &[acquire.into()])?;
+    let run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+
+    // Returning normally discards the callbacks...
+    run.call(&mut store, 0)?;
+    assert!(store.data().is_empty());
+
+    // ... and trapping runs them, most recent first.
+    run.call(&mut store, 1).unwrap_err();
+    assert_eq!(*store.data(), ["release 2", "release 1"]);
+
+    // Callbacks from earlier activations don't run again.
+    store.data_mut().clear();
+    run.call(&mut store, 0)?;
+    assert!(store.data().is_empty());
+    Ok(())
+}
+
+#[test]
+fn defer_on_unwind_nested_activation() -> Result<()> {
+    let mut store = Store::<Vec<&'static str>>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "" "acquire" (func $acquire))
+                (import "" "reenter" (func $reenter))
+                (func (export "acquire_and_trap")
+                    call $acquire
+                    unreachable)
+                (func (export "run")
+                    call $reenter))
+        "#,
+    )?;
+    let acquire = Func::wrap(&mut store, |mut caller: Caller<'_, Vec<&'static str>>| {
+        caller.defer_on_unwind(|released| released.push("inner"));
+    });
+    let reenter = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, Vec<&'static str>>| -> Result<(), Trap> {
+            caller.defer_on_unwind(|released| released.push("outer"));
+            let inner = caller
+                .get_export("acquire_and_trap")
+                .unwrap()
+                .into_func()
+                .unwrap()
+                .typed::<(), (), _>(&caller)?;
+            // The inner activation's trap runs only its own callbacks, and
+            // is handled here so the outer activation returns normally.
+            assert!(inner.call(&mut caller, ()).is_err());
+            assert_eq!(*caller.data(), ["inner"]);
+            Ok(())
+        },
+    );
+    let instance = Instance::new(&mut store, &module, &[acquire.into(), reenter.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    assert_eq!(*store.data(), ["inner"]);
+    Ok(())
+}
+
+#[test]
+fn defer_on_unwind_after_panic() -> Result<()> {
+    let mut store = Store::<Vec<&'static str>>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "" "acquire_and_panic" (func $acquire_and_panic))
+                (import "" "reenter" (func $reenter))
+                (func (export "panic")
+                    call $acquire_and_panic)
+                (func (export "run")
+                    call $reenter
+                    unreachable))
+        "#,
+    )?;
+    let acquire_and_panic = Func::wrap(&mut store, |mut caller: Caller<'_, Vec<&'static str>>| {
+        caller.defer_on_unwind(|released| released.push("inner"));
+        panic!("acquire failed");
+    });
+    let reenter = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, Vec<&'static str>>| -> Result<(), Trap> {
+            let inner = caller
+                .get_export("panic")
+                .unwrap()
+                .into_func()
+                .unwrap()
+                .typed::<(), (), _>(&caller)?;
+            // The inner activation the panic unwound through is popped
+            // without running its callbacks...
+            let result = panic::catch_unwind(AssertUnwindSafe(|| inner.call(&mut caller, ())));
+            assert!(result.is_err());
+            assert!(caller.data().is_empty());
+            // ... so that callbacks registered now belong to the outer one.
+            caller.defer_on_unwind(|released| released.push("outer"));
+            Ok(())
+        },
+    );
+    let instance = Instance::new(
+        &mut store,
+        &module,
+        &[acquire_and_panic.into(), reenter.into()],
+    )?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ()).unwrap_err();
+    assert_eq!(*store.data(), ["outer"]);
+    Ok(())
+}
+
 #[test]
 fn trap_start_function_import() -> Result<()> {
     let mut store = Store::<()>::default();
@@ -281,6 +548,49 @@ fn rust_panic_import() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn rust_panic_import_as_trap() -> Result<()> {
+    let mut config = Config::new();
+    config.host_panic_policy(HostPanicPolicy::CatchAsTrap);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    let binary = wat::parse_str(
+        r#"
+            (module $a
+                (import "" "" (func $foo))
+                (import "" "" (func $bar))
+                (func $foo_caller (export "foo") call $foo)
+                (func $bar_caller (export "bar") call $bar)
+            )
+        "#,
+    )?;
+
+    let module = Module::new(store.engine(), &binary)?;
+    let sig = FuncType::new(None, None);
+    let func = Func::new(&mut store, sig, |_, _, _| panic!("this is a panic"));
+    let func2 = Func::wrap(&mut store, || panic!("this is {} panic", "another"));
+    let instance = Instance::new(&mut store, &module, &[func.into(), func2.into()])?;
+
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "foo")?;
+    let trap = func.call(&mut store, ()).unwrap_err();
+    assert!(trap
+        .to_string()
+        .starts_with("host function panicked: this is a panic"));
+    assert_eq!(trap.trace()[0].func_name(), Some("foo_caller"));
+
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "bar")?;
+    let trap = func.call(&mut store, ()).unwrap_err();
+    assert!(trap
+        .to_string()
+        .starts_with("host function panicked: this is another panic"));
+    assert_eq!(trap.trace()[0].func_name(), Some("bar_caller"));
+
+    // The store is still usable after a caught panic.
+    let trap = func.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("this is another panic"));
+    Ok(())
+}
+
 #[test]
 fn rust_panic_start_function() -> Result<()> {
     let mut store = Store::<()>::default();
@@ -330,19 +640,19 @@ fn mismatched_arguments() -> Result<()> {
     let func = instance.get_func(&mut store, "foo").unwrap();
     assert_eq!(
         func.call(&mut store, &[]).unwrap_err().to_string(),
-        "expected 1 arguments, got 0"
+        "expected 1 arguments for (func (param i32)), got 0"
     );
     assert_eq!(
         func.call(&mut store, &[Val::F32(0)])
             .unwrap_err()
             .to_string(),
-        "argument type mismatch: found f32 but expected i32",
+        "argument type mismatch: found f32 but expected i32 for argument 0 of (func (param i32))",
     );
     assert_eq!(
         func.call(&mut store, &[Val::I32(0), Val::I32(1)])
             .unwrap_err()
             .to_string(),
-        "expected 1 arguments, got 2"
+        "expected 1 arguments for (func (param i32)), got 2"
     );
     Ok(())
 }
@@ -634,3 +944,43 @@ fn multithreaded_traps() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn native_backtrace_through_wasm() -> Result<()> {
+    let mut store = Store::<Vec<usize>>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "" "capture" (func $capture))
+                (func $run (export "run") call $capture)
+            )
+        "#,
+    )?;
+    let capture = Func::wrap(&mut store, |mut caller: Caller<'_, Vec<usize>>| {
+        let mut ips = Vec::new();
+        backtrace::trace(|frame| {
+            ips.push(frame.ip() as usize);
+            true
+        });
+        *caller.data_mut() = ips;
+    });
+    let instance = Instance::new(&mut store, &module, &[capture.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+
+    let ranges = module.jit_code_ranges().collect::<Vec<_>>();
+    assert!(!ranges.is_empty());
+    let in_module = |ip: &usize| ranges.iter().any(|(start, end)| start <= ip && ip < end);
+
+    // The native unwinder must have found the wasm frame, and then also kept
+    // going past it back into the host frames which called into wasm.
+    let ips = store.data();
+    let last_wasm_frame = ips
+        .iter()
+        .rposition(in_module)
+        .expect("no frames found within the module's code");
+    assert!(ips.len() > last_wasm_frame + 1);
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use std::path::Path;
+use wasmtime::*;
+use wasmtime_wasi::sync::{ambient_authority, Dir, WasiCtxBuilder};
+use wasmtime_wasi::WasiCtx;
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_FAULT: i32 = 21;
+
+// The guest has a single page of memory.
+const MEMORY_END: i32 = 0x1_0000;
+
+const MODULE: &str = r#"
+    (module
+        (import "wasi_snapshot_preview1" "args_get"
+            (func $args_get (param i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "environ_get"
+            (func $environ_get (param i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_read"
+            (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_readdir"
+            (func $fd_readdir (param i32 i32 i32 i64 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        ;; WASI functions find the memory through the instance calling them,
+        ;; so they're called from wasm rather than exported directly.
+        (func (export "args_get") (param i32 i32) (result i32)
+            (call $args_get (local.get 0) (local.get 1)))
+        (func (export "environ_get") (param i32 i32) (result i32)
+            (call $environ_get (local.get 0) (local.get 1)))
+        (func (export "fd_read") (param i32 i32 i32 i32) (result i32)
+            (call $fd_read (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+        (func (export "fd_write") (param i32 i32 i32 i32) (result i32)
+            (call $fd_write (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+        (func (export "fd_readdir") (param i32 i32 i32 i64 i32) (result i32)
+            (call $fd_readdir
+                (local.get 0) (local.get 1) (local.get 2) (local.get 3) (local.get 4)))
+        (func (export "path_open") (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)
+            (call $path_open
+                (local.get 0) (local.get 1) (local.get 2) (local.get 3) (local.get 4) (local.get 5) (local.get 6) (local.get 7) (local.get 8)))
+    )
+"#;
+
+struct Guest {
+    store: Store<WasiCtx>,
+    memory: Memory,
+    args_get: TypedFunc<(i32, i32), i32>,
+    environ_get: TypedFunc<(i32, i32), i32>,
+    fd_read: TypedFunc<(i32, i32, i32, i32), i32>,
+    fd_write: TypedFunc<(i32, i32, i32, i32), i32>,
+    fd_readdir: TypedFunc<(i32, i32, i32, i64, i32), i32>,
+    path_open: TypedFunc<(i32, i32, i32, i32, i32, i64, i64, i32, i32), i32>,
+}
+
+impl Guest {
+    /// Instantiates the module with `dir` preopened as fd 3.
+    fn new(dir: &Path) -> Result<Guest> {
+        let engine = Engine::default();
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+
+        let wasi = WasiCtxBuilder::new()
+            .arg("guest")?
+            .arg("pointers")?
+            .env("KEY", "value")?
+            .preopened_dir(Dir::open_ambient_dir(dir, ambient_authority())?, "dir")?
+            .build();
+
+        let module = Module::new(&engine, MODULE)?;
+        let mut store = Store::new(&engine, wasi);
+        let instance = linker.instantiate(&mut store, &module)?;
+        Ok(Guest {
+            memory: instance.get_memory(&mut store, "memory").unwrap(),
+            args_get: instance.get_typed_func(&mut store, "args_get")?,
+            environ_get: instance.get_typed_func(&mut store, "environ_get")?,
+            fd_read: instance.get_typed_func(&mut store, "fd_read")?,
+            fd_write: instance.get_typed_func(&mut store, "fd_write")?,
+            fd_readdir: instance.get_typed_func(&mut store, "fd_readdir")?,
+            path_open: instance.get_typed_func(&mut store, "path_open")?,
+            store,
+        })
+    }
+
+    /// Opens (creating if necessary) the file `path` in the preopen.
+    fn open_file(&mut self, path: &str) -> Result<i32> {
+        const OFLAGS_CREAT: i32 = 1;
+        const RIGHTS_ALL: i64 = 0x1fff_ffff;
+        self.memory.write(&mut self.store, 0, path.as_bytes())?;
+        let args = (
+            3,
+            0,
+            0,
+            path.len() as i32,
+            OFLAGS_CREAT,
+            RIGHTS_ALL,
+            RIGHTS_ALL,
+            0,
+            200,
+        );
+        assert_eq!(self.path_open.call(&mut self.store, args)?, ERRNO_SUCCESS);
+        let mut opened = [0; 4];
+        self.memory.read(&self.store, 200, &mut opened)?;
+        Ok(i32::from_le_bytes(opened))
+    }
+
+    /// Writes the `(buf, buf_len)` pairs of `iovs` as an iovec array at
+    /// `offset`.
+    fn write_iovs(&mut self, offset: i32, iovs: &[(i32, i32)]) -> Result<()> {
+        let mut bytes = Vec::new();
+        for (buf, len) in iovs {
+            bytes.extend_from_slice(&buf.to_le_bytes());
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+        self.memory
+            .write(&mut self.store, offset as usize, &bytes)?;
+        Ok(())
+    }
+
+    fn zeroed(&self, offset: i32, len: usize) -> bool {
+        self.memory.data(&self.store)[offset as usize..][..len]
+            .iter()
+            .all(|b| *b == 0)
+    }
+}
+
+#[test]
+fn iovecs_straddling_end_of_memory() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    std::fs::write(dir.path().join("in"), "hello")?;
+    let mut guest = Guest::new(dir.path())?;
+    let input = guest.open_file("in")?;
+    let output = guest.open_file("out")?;
+
+    // A buffer which runs 8 bytes past the end of memory.
+    guest.write_iovs(100, &[(MEMORY_END - 8, 16)])?;
+    let args = (output, 100, 1, 200);
+    assert_eq!(guest.fd_write.call(&mut guest.store, args)?, ERRNO_FAULT);
+    let args = (input, 100, 1, 200);
+    assert_eq!(guest.fd_read.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(MEMORY_END - 8, 8));
+
+    // The iovec array itself straddles the end of memory: its first entry is
+    // in bounds but its second isn't.
+    let iovs = MEMORY_END - 12;
+    guest.write_iovs(iovs, &[(300, 5)])?;
+    let args = (output, iovs, 2, 200);
+    assert_eq!(guest.fd_write.call(&mut guest.store, args)?, ERRNO_FAULT);
+    let args = (input, iovs, 2, 200);
+    assert_eq!(guest.fd_read.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(300, 5));
+
+    // A bad buffer after a good one fails the whole call before any I/O is
+    // done.
+    guest.write_iovs(100, &[(300, 5), (MEMORY_END - 1, 2)])?;
+    let args = (output, 100, 2, 200);
+    assert_eq!(guest.fd_write.call(&mut guest.store, args)?, ERRNO_FAULT);
+    let args = (input, 100, 2, 200);
+    assert_eq!(guest.fd_read.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(300, 5));
+    assert_eq!(std::fs::read(dir.path().join("out"))?, b"");
+
+    // Buffers which end exactly at the end of memory are fine.
+    guest.write_iovs(100, &[(MEMORY_END - 5, 5)])?;
+    let args = (input, 100, 1, 200);
+    assert_eq!(guest.fd_read.call(&mut guest.store, args)?, ERRNO_SUCCESS);
+    let args = (output, 100, 1, 200);
+    assert_eq!(guest.fd_write.call(&mut guest.store, args)?, ERRNO_SUCCESS);
+    assert_eq!(std::fs::read(dir.path().join("out"))?, b"hello");
+    Ok(())
+}
+
+#[test]
+fn iovec_counts_overflowing_size() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut guest = Guest::new(dir.path())?;
+    let output = guest.open_file("out")?;
+    guest.write_iovs(100, &[(300, 5)])?;
+
+    // 2^29 iovecs of 8 bytes each is 2^32 bytes, which overflows a `u32`, as
+    // does the largest possible count.
+    for count in &[1 << 29, -1] {
+        let args = (output, 100, *count, 200);
+        assert_eq!(guest.fd_write.call(&mut guest.store, args)?, ERRNO_FAULT);
+        assert_eq!(guest.fd_read.call(&mut guest.store, args)?, ERRNO_FAULT);
+    }
+
+    // A buffer whose length is the largest possible `u32`.
+    guest.write_iovs(100, &[(300, -1)])?;
+    let args = (output, 100, 1, 200);
+    assert_eq!(guest.fd_write.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert_eq!(std::fs::read(dir.path().join("out"))?, b"");
+    Ok(())
+}
+
+#[test]
+fn string_arrays_straddling_end_of_memory() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut guest = Guest::new(dir.path())?;
+
+    // "guest\0pointers\0" needs 15 bytes, and the two pointers 8 bytes.
+    let args = (100, MEMORY_END - 14);
+    assert_eq!(guest.args_get.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(100, 8));
+    let args = (MEMORY_END - 4, 200);
+    assert_eq!(guest.args_get.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(200, 15));
+
+    // Likewise for "KEY=value\0".
+    let args = (100, MEMORY_END - 9);
+    assert_eq!(guest.environ_get.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(100, 4));
+    let args = (MEMORY_END - 3, 200);
+    assert_eq!(guest.environ_get.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(200, 10));
+
+    let args = (MEMORY_END - 8, MEMORY_END - 23);
+    assert_eq!(guest.args_get.call(&mut guest.store, args)?, ERRNO_SUCCESS);
+    Ok(())
+}
+
+#[test]
+fn readdir_buffer_straddling_end_of_memory() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    std::fs::write(dir.path().join("a"), "")?;
+    let mut guest = Guest::new(dir.path())?;
+
+    let args = (3, MEMORY_END - 100, 200, 0, 300);
+    assert_eq!(guest.fd_readdir.call(&mut guest.store, args)?, ERRNO_FAULT);
+    assert!(guest.zeroed(MEMORY_END - 100, 100));
+
+    let args = (3, MEMORY_END - 100, 100, 0, 300);
+    assert_eq!(
+        guest.fd_readdir.call(&mut guest.store, args)?,
+        ERRNO_SUCCESS
+    );
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use anyhow::Result;
+use std::path::Path;
+use wasmtime::*;
+use wasmtime_wasi::sync::{ambient_authority, Dir, WasiCtxBuilder};
+use wasmtime_wasi::WasiCtx;
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_XDEV: i32 = 75;
+
+const MODULE: &str = r#"
+    (module
+        (import "wasi_snapshot_preview1" "path_rename"
+            (func $path_rename (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "path_link"
+            (func $path_link (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        ;; WASI functions find the memory through the instance calling them,
+        ;; so they're called from wasm rather than exported directly.
+        (func (export "path_rename") (param i32 i32 i32 i32 i32 i32) (result i32)
+            (call $path_rename
+                (local.get 0) (local.get 1) (local.get 2) (local.get 3) (local.get 4) (local.get 5)))
+        (func (export "path_link") (param i32 i32 i32 i32 i32 i32 i32) (result i32)
+            (call $path_link
+                (local.get 0) (local.get 1) (local.get 2) (local.get 3) (local.get 4) (local.get 5) (local.get 6)))
+        (func (export "path_open") (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)
+            (call $path_open
+                (local.get 0) (local.get 1) (local.get 2) (local.get 3) (local.get 4) (local.get 5) (local.get 6) (local.get 7) (local.get 8)))
+    )
+"#;
+
+struct Guest {
+    store: Store<WasiCtx>,
+    memory: Memory,
+    path_rename: TypedFunc<(i32, i32, i32, i32, i32, i32), i32>,
+    path_link: TypedFunc<(i32, i32, i32, i32, i32, i32, i32), i32>,
+    path_open: TypedFunc<(i32, i32, i32, i32, i32, i64, i64, i32, i32), i32>,
+}
+
+impl Guest {
+    /// Instantiates the module with each of `dirs` preopened, in order from
+    /// fd 3.
+    fn new(dirs: &[&Path]) -> Result<Guest> {
+        let engine = Engine::default();
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+
+        let mut builder = WasiCtxBuilder::new();
+        for (i, dir) in dirs.iter().enumerate() {
+            let dir = Dir::open_ambient_dir(dir, ambient_authority())?;
+            builder = builder.preopened_dir(dir, format!("dir{}", i))?;
+        }
+
+        let module = Module::new(&engine, MODULE)?;
+        let mut store = Store::new(&engine, builder.build());
+        let instance = linker.instantiate(&mut store, &module)?;
+        Ok(Guest {
+            memory: instance.get_memory(&mut store, "memory").unwrap(),
+            path_rename: instance.get_typed_func(&mut store, "path_rename")?,
+            path_link: instance.get_typed_func(&mut store, "path_link")?,
+            path_open: instance.get_typed_func(&mut store, "path_open")?,
+            store,
+        })
+    }
+
+    fn rename(&mut self, src_fd: i32, src: &str, dest_fd: i32, dest: &str) -> Result<i32> {
+        self.memory.write(&mut self.store, 0, src.as_bytes())?;
+        self.memory.write(&mut self.store, 100, dest.as_bytes())?;
+        let args = (src_fd, 0, src.len() as i32, dest_fd, 100, dest.len() as i32);
+        Ok(self.path_rename.call(&mut self.store, args)?)
+    }
+
+    fn link(&mut self, src_fd: i32, src: &str, target_fd: i32, target: &str) -> Result<i32> {
+        self.memory.write(&mut self.store, 0, src.as_bytes())?;
+        self.memory.write(&mut self.store, 100, target.as_bytes())?;
+        let args = (
+            src_fd,
+            0,
+            0,
+            src.len() as i32,
+            target_fd,
+            100,
+            target.len() as i32,
+        );
+        Ok(self.path_link.call(&mut self.store, args)?)
+    }
+
+    fn open_dir(&mut self, fd: i32, path: &str) -> Result<i32> {
+        const OFLAGS_DIRECTORY: i32 = 2;
+        const RIGHTS_ALL: i64 = 0x1fff_ffff;
+        self.memory.write(&mut self.store, 0, path.as_bytes())?;
+        let args = (
+            fd,
+            0,
+            0,
+            path.len() as i32,
+            OFLAGS_DIRECTORY,
+            RIGHTS_ALL,
+            RIGHTS_ALL,
+            0,
+            200,
+        );
+        assert_eq!(self.path_open.call(&mut self.store, args)?, ERRNO_SUCCESS);
+        let mut opened = [0; 4];
+        self.memory.read(&self.store, 200, &mut opened)?;
+        Ok(i32::from_le_bytes(opened))
+    }
+}
+
+#[test]
+fn rename_within_preopen() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    std::fs::write(dir.path().join("a"), "hello")?;
+    std::fs::create_dir(dir.path().join("sub"))?;
+
+    let mut guest = Guest::new(&[dir.path()])?;
+    assert_eq!(guest.rename(3, "a", 3, "b")?, ERRNO_SUCCESS);
+
+    // A directory opened beneath the preopen shares its root.
+    let sub = guest.open_dir(3, "sub")?;
+    assert_eq!(guest.rename(3, "b", sub, "c")?, ERRNO_SUCCESS);
+    assert_eq!(guest.link(sub, "c", 3, "d")?, ERRNO_SUCCESS);
+
+    assert_eq!(std::fs::read(dir.path().join("sub").join("c"))?, b"hello");
+    assert_eq!(std::fs::read(dir.path().join("d"))?, b"hello");
+    Ok(())
+}
+
+#[test]
+fn rename_across_preopens() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+
+    // Both preopens are on the same host filesystem, and even share a parent,
+    // but they're still separate capabilities.
+    let one = dir.path().join("one");
+    let two = dir.path().join("two");
+    std::fs::create_dir(&one)?;
+    std::fs::create_dir(&two)?;
+    std::fs::write(one.join("a"), "hello")?;
+
+    let mut guest = Guest::new(&[&one, &two])?;
+    assert_eq!(guest.rename(3, "a", 4, "b")?, ERRNO_XDEV);
+    assert_eq!(guest.link(3, "a", 4, "b")?, ERRNO_XDEV);
+
+    let sub = guest.open_dir(4, ".")?;
+    assert_eq!(guest.rename(3, "a", sub, "b")?, ERRNO_XDEV);
+
+    assert!(one.join("a").exists());
+    assert!(!two.join("b").exists());
+    Ok(())
+}
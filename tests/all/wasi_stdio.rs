@@ -0,0 +1,235 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+use wasmtime_wasi::pipe::{OutputCapture, OutputStream};
+use wasmtime_wasi::sync::WasiCtxBuilder;
+use wasmtime_wasi::WasiCtx;
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_SPIPE: i32 = 70;
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+
+const MODULE: &str = r#"
+    (module
+        (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_fdstat_get"
+            (func $fd_fdstat_get (param i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_filestat_get"
+            (func $fd_filestat_get (param i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_seek"
+            (func $fd_seek (param i32 i64 i32 i32) (result i32)))
+        (memory (export "memory") 32)
+        ;; WASI functions find the memory through the instance calling them,
+        ;; so they're called from wasm rather than exported directly.
+        (func (export "fd_write") (param i32 i32 i32 i32) (result i32)
+            (call $fd_write (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+        (func (export "fd_fdstat_get") (param i32 i32) (result i32)
+            (call $fd_fdstat_get (local.get 0) (local.get 1)))
+        (func (export "fd_filestat_get") (param i32 i32) (result i32)
+            (call $fd_filestat_get (local.get 0) (local.get 1)))
+        (func (export "fd_seek") (param i32 i64 i32 i32) (result i32)
+            (call $fd_seek (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+    )
+"#;
+
+/// Where the guest's data is written in its memory, after the iovecs and
+/// results.
+const DATA: usize = 1024;
+
+struct Guest {
+    store: Store<WasiCtx>,
+    memory: Memory,
+    fd_write: TypedFunc<(i32, i32, i32, i32), i32>,
+    fd_fdstat_get: TypedFunc<(i32, i32), i32>,
+    fd_filestat_get: TypedFunc<(i32, i32), i32>,
+    fd_seek: TypedFunc<(i32, i64, i32, i32), i32>,
+}
+
+impl Guest {
+    fn new(output: &OutputCapture) -> Result<Guest> {
+        let engine = Engine::default();
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+
+        let ctx = WasiCtxBuilder::new().capture_output(output).build();
+        let module = Module::new(&engine, MODULE)?;
+        let mut store = Store::new(&engine, ctx);
+        let instance = linker.instantiate(&mut store, &module)?;
+        Ok(Guest {
+            memory: instance.get_memory(&mut store, "memory").unwrap(),
+            fd_write: instance.get_typed_func(&mut store, "fd_write")?,
+            fd_fdstat_get: instance.get_typed_func(&mut store, "fd_fdstat_get")?,
+            fd_filestat_get: instance.get_typed_func(&mut store, "fd_filestat_get")?,
+            fd_seek: instance.get_typed_func(&mut store, "fd_seek")?,
+            store,
+        })
+    }
+
+    /// Writes `bufs` to `fd` with one `fd_write` call, and returns the number
+    /// of bytes written.
+    fn write(&mut self, fd: i32, bufs: &[&[u8]]) -> Result<usize> {
+        let mut iovs = Vec::new();
+        let mut offset = DATA;
+        for buf in bufs {
+            self.memory.write(&mut self.store, offset, buf)?;
+            iovs.extend_from_slice(&(offset as u32).to_le_bytes());
+            iovs.extend_from_slice(&(buf.len() as u32).to_le_bytes());
+            offset += buf.len();
+        }
+        self.memory.write(&mut self.store, 0, &iovs)?;
+        let args = (fd, 0, bufs.len() as i32, 1000);
+        assert_eq!(self.fd_write.call(&mut self.store, args)?, ERRNO_SUCCESS);
+        let mut written = [0; 4];
+        self.memory.read(&self.store, 1000, &mut written)?;
+        Ok(u32::from_le_bytes(written) as usize)
+    }
+
+    fn print(&mut self, fd: i32, s: &str) -> Result<()> {
+        assert_eq!(self.write(fd, &[s.as_bytes()])?, s.len());
+        Ok(())
+    }
+}
+
+/// Returns an `OutputCapture` recording the calls of both of its callbacks.
+fn recording_capture() -> (
+    OutputCapture,
+    Arc<Mutex<Vec<(OutputStream, String)>>>,
+    Arc<Mutex<Vec<(OutputStream, String)>>>,
+) {
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let output = OutputCapture::new()
+        .on_write({
+            let writes = writes.clone();
+            move |stream, data| {
+                let data = String::from_utf8(data.to_vec()).unwrap();
+                writes.lock().unwrap().push((stream, data));
+            }
+        })
+        .on_line({
+            let lines = lines.clone();
+            move |stream, line| {
+                let line = String::from_utf8(line.to_vec()).unwrap();
+                lines.lock().unwrap().push((stream, line));
+            }
+        });
+    (output, writes, lines)
+}
+
+#[test]
+fn interleaved_lines() -> Result<()> {
+    use OutputStream::{Stderr, Stdout};
+
+    let (output, writes, lines) = recording_capture();
+    let mut guest = Guest::new(&output)?;
+    guest.print(1, "out 1\n")?;
+    guest.print(2, "err 1\n")?;
+    guest.print(1, "out ")?;
+    guest.print(2, "err 2\nerr ")?;
+    guest.print(1, "2\n")?;
+    assert_eq!(guest.write(2, &[&b"3\n"[..], &b"err 4"[..]])?, 7);
+    guest.print(1, "out 3")?;
+
+    assert_eq!(
+        *writes.lock().unwrap(),
+        [
+            (Stdout, "out 1\n".to_string()),
+            (Stderr, "err 1\n".to_string()),
+            (Stdout, "out ".to_string()),
+            (Stderr, "err 2\nerr ".to_string()),
+            (Stdout, "2\n".to_string()),
+            (Stderr, "3\nerr 4".to_string()),
+            (Stdout, "out 3".to_string()),
+        ]
+    );
+
+    // Unterminated lines are held back until they're flushed.
+    let expected_lines = vec![
+        (Stdout, "out 1\n".to_string()),
+        (Stderr, "err 1\n".to_string()),
+        (Stderr, "err 2\n".to_string()),
+        (Stdout, "out 2\n".to_string()),
+        (Stderr, "err 3\n".to_string()),
+    ];
+    assert_eq!(*lines.lock().unwrap(), expected_lines);
+    output.flush_lines();
+    let mut expected_lines = expected_lines;
+    expected_lines.push((Stdout, "out 3".to_string()));
+    expected_lines.push((Stderr, "err 4".to_string()));
+    assert_eq!(*lines.lock().unwrap(), expected_lines);
+
+    assert_eq!(output.contents(Stdout), b"out 1\nout 2\nout 3");
+    assert_eq!(output.contents(Stderr), b"err 1\nerr 2\nerr 3\nerr 4");
+    Ok(())
+}
+
+#[test]
+fn large_writes() -> Result<()> {
+    let (output, writes, lines) = recording_capture();
+    let mut guest = Guest::new(&output)?;
+
+    let big = "x".repeat(1 << 20) + "\n";
+    guest.print(1, &big)?;
+    let halves = [&big.as_bytes()[..1000], &big.as_bytes()[1000..]];
+    assert_eq!(guest.write(2, &halves)?, big.len());
+
+    assert_eq!(output.contents(OutputStream::Stdout), big.as_bytes());
+    assert_eq!(output.contents(OutputStream::Stderr), big.as_bytes());
+    let writes = writes.lock().unwrap();
+    assert_eq!(writes.len(), 2);
+    assert!(writes.iter().all(|(_, data)| *data == big));
+    assert_eq!(lines.lock().unwrap().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn stat_and_seek() -> Result<()> {
+    let output = OutputCapture::new();
+    let mut guest = Guest::new(&output)?;
+    for fd in 1..=2 {
+        assert_eq!(
+            guest.fd_fdstat_get.call(&mut guest.store, (fd, 0))?,
+            ERRNO_SUCCESS
+        );
+        let mut fdstat = [0; 24];
+        guest.memory.read(&guest.store, 0, &mut fdstat)?;
+        assert_eq!(fdstat[0], FILETYPE_CHARACTER_DEVICE);
+
+        assert_eq!(
+            guest.fd_filestat_get.call(&mut guest.store, (fd, 0))?,
+            ERRNO_SUCCESS
+        );
+        let mut filestat = [0; 64];
+        guest.memory.read(&guest.store, 0, &mut filestat)?;
+        assert_eq!(filestat[16], FILETYPE_CHARACTER_DEVICE);
+        assert_eq!(filestat[32..40], [0; 8]);
+
+        assert_eq!(
+            guest.fd_seek.call(&mut guest.store, (fd, 0, 0, 0))?,
+            ERRNO_SPIPE
+        );
+    }
+
+    // Writes still work after the failed seek.
+    guest.print(1, "hello\n")?;
+    assert_eq!(output.contents(OutputStream::Stdout), b"hello\n");
+    Ok(())
+}
+
+#[test]
+fn lines_flushed_on_drop() -> Result<()> {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let output = OutputCapture::new().without_buffering().on_line({
+        let lines = lines.clone();
+        move |_, line| lines.lock().unwrap().push(line.to_vec())
+    });
+    let mut guest = Guest::new(&output)?;
+    guest.print(1, "one\ntwo")?;
+    assert_eq!(*lines.lock().unwrap(), [b"one\n".to_vec()]);
+
+    drop(guest);
+    drop(output);
+    assert_eq!(*lines.lock().unwrap(), [b"one\n".to_vec(), b"two".to_vec()]);
+    Ok(())
+}
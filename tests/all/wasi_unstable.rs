@@ -0,0 +1,230 @@
+use anyhow::Result;
+use wasmtime::*;
+use wasmtime_wasi::sync::{ambient_authority, Dir, WasiCtxBuilder};
+use wasmtime_wasi::WasiCtx;
+
+const ERRNO_SUCCESS: i32 = 0;
+const RIGHTS_ALL: i64 = 0x1fff_ffff;
+
+// `wasi_unstable` and `wasi_snapshot_preview1` order their `whence` variants
+// differently.
+const UNSTABLE_WHENCE_CUR: i32 = 0;
+const UNSTABLE_WHENCE_END: i32 = 1;
+const UNSTABLE_WHENCE_SET: i32 = 2;
+const PREVIEW1_WHENCE_SET: i32 = 0;
+const PREVIEW1_WHENCE_CUR: i32 = 1;
+const PREVIEW1_WHENCE_END: i32 = 2;
+
+// The `wasi_unstable` filestat has a 32-bit link count, which makes it
+// smaller than the `wasi_snapshot_preview1` one.
+const UNSTABLE_FILESTAT_SIZE: usize = 56;
+const PREVIEW1_FILESTAT_SIZE: usize = 64;
+
+const MODULE: &str = r#"
+    (module
+        (import "wasi_unstable" "path_open"
+            (func $unstable_path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_unstable" "fd_seek"
+            (func $unstable_fd_seek (param i32 i64 i32 i32) (result i32)))
+        (import "wasi_unstable" "fd_filestat_get"
+            (func $unstable_fd_filestat_get (param i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $preview1_path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_seek"
+            (func $preview1_fd_seek (param i32 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_filestat_get"
+            (func $preview1_fd_filestat_get (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        ;; WASI functions find the memory through the instance calling them,
+        ;; so they're called from wasm rather than exported directly.
+        (func (export "unstable_path_open") (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)
+            (call $unstable_path_open
+                (local.get 0) (local.get 1) (local.get 2) (local.get 3) (local.get 4) (local.get 5) (local.get 6) (local.get 7) (local.get 8)))
+        (func (export "unstable_fd_seek") (param i32 i64 i32 i32) (result i32)
+            (call $unstable_fd_seek (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+        (func (export "unstable_fd_filestat_get") (param i32 i32) (result i32)
+            (call $unstable_fd_filestat_get (local.get 0) (local.get 1)))
+        (func (export "preview1_path_open") (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)
+            (call $preview1_path_open
+                (local.get 0) (local.get 1) (local.get 2) (local.get 3) (local.get 4) (local.get 5) (local.get 6) (local.get 7) (local.get 8)))
+        (func (export "preview1_fd_seek") (param i32 i64 i32 i32) (result i32)
+            (call $preview1_fd_seek (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+        (func (export "preview1_fd_filestat_get") (param i32 i32) (result i32)
+            (call $preview1_fd_filestat_get (local.get 0) (local.get 1)))
+    )
+"#;
+
+type PathOpen = TypedFunc<(i32, i32, i32, i32, i32, i64, i64, i32, i32), i32>;
+type FdSeek = TypedFunc<(i32, i64, i32, i32), i32>;
+type FdFilestatGet = TypedFunc<(i32, i32), i32>;
+
+/// The functions of one snapshot exported by `MODULE`.
+struct Snapshot {
+    path_open: PathOpen,
+    fd_seek: FdSeek,
+    fd_filestat_get: FdFilestatGet,
+}
+
+struct Guest {
+    store: Store<WasiCtx>,
+    memory: Memory,
+    unstable: Snapshot,
+    preview1: Snapshot,
+}
+
+impl Guest {
+    /// Instantiates the module with `dir` preopened as fd 3.
+    fn new(dir: &std::path::Path) -> Result<Guest> {
+        let engine = Engine::default();
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+
+        let dir = Dir::open_ambient_dir(dir, ambient_authority())?;
+        let ctx = WasiCtxBuilder::new().preopened_dir(dir, "dir")?.build();
+
+        let module = Module::new(&engine, MODULE)?;
+        let mut store = Store::new(&engine, ctx);
+        let instance = linker.instantiate(&mut store, &module)?;
+        let mut snapshot = |name: &str| -> Result<Snapshot> {
+            Ok(Snapshot {
+                path_open: instance.get_typed_func(&mut store, &format!("{}_path_open", name))?,
+                fd_seek: instance.get_typed_func(&mut store, &format!("{}_fd_seek", name))?,
+                fd_filestat_get: instance
+                    .get_typed_func(&mut store, &format!("{}_fd_filestat_get", name))?,
+            })
+        };
+        let unstable = snapshot("unstable")?;
+        let preview1 = snapshot("preview1")?;
+        Ok(Guest {
+            memory: instance.get_memory(&mut store, "memory").unwrap(),
+            unstable,
+            preview1,
+            store,
+        })
+    }
+
+    fn snapshot(&self, unstable: bool) -> &Snapshot {
+        if unstable {
+            &self.unstable
+        } else {
+            &self.preview1
+        }
+    }
+
+    fn open(&mut self, unstable: bool, path: &str) -> Result<i32> {
+        self.memory.write(&mut self.store, 0, path.as_bytes())?;
+        let args = (
+            3,
+            0,
+            0,
+            path.len() as i32,
+            0,
+            RIGHTS_ALL,
+            RIGHTS_ALL,
+            0,
+            100,
+        );
+        let path_open = self.snapshot(unstable).path_open;
+        let errno = path_open.call(&mut self.store, args)?;
+        assert_eq!(errno, ERRNO_SUCCESS);
+        let mut fd = [0; 4];
+        self.memory.read(&self.store, 100, &mut fd)?;
+        Ok(i32::from_le_bytes(fd))
+    }
+
+    fn seek(&mut self, unstable: bool, fd: i32, offset: i64, whence: i32) -> Result<u64> {
+        // The new offset is a `u64`, so it has to be 8-byte aligned.
+        let args = (fd, offset, whence, 104);
+        let fd_seek = self.snapshot(unstable).fd_seek;
+        let errno = fd_seek.call(&mut self.store, args)?;
+        assert_eq!(errno, ERRNO_SUCCESS);
+        let mut offset = [0; 8];
+        self.memory.read(&self.store, 104, &mut offset)?;
+        Ok(u64::from_le_bytes(offset))
+    }
+
+    /// Returns the filestat of `fd` along with the bytes following it, which
+    /// must not have been written.
+    fn filestat(&mut self, unstable: bool, fd: i32) -> Result<Vec<u8>> {
+        self.memory.write(&mut self.store, 200, &[0xff; 128])?;
+        let fd_filestat_get = self.snapshot(unstable).fd_filestat_get;
+        let errno = fd_filestat_get.call(&mut self.store, (fd, 200))?;
+        assert_eq!(errno, ERRNO_SUCCESS);
+        let mut filestat = vec![0; 128];
+        self.memory.read(&self.store, 200, &mut filestat)?;
+        Ok(filestat)
+    }
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[offset..][..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[offset..][..4]);
+    u32::from_le_bytes(buf)
+}
+
+#[test]
+fn seek_whence_is_translated() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    std::fs::write(dir.path().join("a"), "0123456789")?;
+
+    let mut guest = Guest::new(dir.path())?;
+    for &unstable in &[true, false] {
+        let (set, cur, end) = if unstable {
+            (
+                UNSTABLE_WHENCE_SET,
+                UNSTABLE_WHENCE_CUR,
+                UNSTABLE_WHENCE_END,
+            )
+        } else {
+            (
+                PREVIEW1_WHENCE_SET,
+                PREVIEW1_WHENCE_CUR,
+                PREVIEW1_WHENCE_END,
+            )
+        };
+        let fd = guest.open(unstable, "a")?;
+        assert_eq!(guest.seek(unstable, fd, 4, set)?, 4);
+        assert_eq!(guest.seek(unstable, fd, 2, cur)?, 6);
+        assert_eq!(guest.seek(unstable, fd, -3, end)?, 7);
+    }
+    Ok(())
+}
+
+#[test]
+fn filestat_layout_is_translated() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    std::fs::write(dir.path().join("a"), "0123456789")?;
+
+    let mut guest = Guest::new(dir.path())?;
+    let fd = guest.open(true, "a")?;
+    let unstable = guest.filestat(true, fd)?;
+    let preview1 = guest.filestat(false, fd)?;
+
+    // dev, ino and filetype are laid out the same in both.
+    assert_eq!(unstable[..17], preview1[..17]);
+
+    // The unstable link count is 32 bits, and everything after it moves up.
+    assert_eq!(u64::from(u32_at(&unstable, 20)), u64_at(&preview1, 24));
+    assert_eq!(u64_at(&unstable, 24), 10);
+    assert_eq!(u64_at(&preview1, 32), 10);
+    for field in 0..3 {
+        assert_eq!(
+            u64_at(&unstable, 32 + 8 * field),
+            u64_at(&preview1, 40 + 8 * field)
+        );
+    }
+
+    assert!(unstable[UNSTABLE_FILESTAT_SIZE..]
+        .iter()
+        .all(|b| *b == 0xff));
+    assert!(preview1[PREVIEW1_FILESTAT_SIZE..]
+        .iter()
+        .all(|b| *b == 0xff));
+    Ok(())
+}
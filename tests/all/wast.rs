@@ -1,6 +1,8 @@
 use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
 use wasmtime::{
-    Config, Engine, InstanceAllocationStrategy, InstanceLimits, ModuleLimits,
+    Config, Engine, Func, InstanceAllocationStrategy, InstanceLimits, ModuleLimits,
     PoolingAllocationStrategy, Store, Strategy,
 };
 use wasmtime_wast::WastContext;
@@ -77,3 +79,69 @@ fn run_wast(wast: &str, strategy: Strategy, pooling: bool) -> anyhow::Result<()>
     wast_context.run_file(wast)?;
     Ok(())
 }
+
+#[test]
+fn custom_host_imports() -> anyhow::Result<()> {
+    let mut wast_context = WastContext::new(Store::<()>::default());
+    wast_context.register_spectest()?;
+    let sum = Arc::new(AtomicI32::new(0));
+    let add = {
+        let sum = sum.clone();
+        Func::wrap(wast_context.store_mut(), move |x: i32| {
+            sum.fetch_add(x, SeqCst);
+        })
+    };
+    let total = {
+        let sum = sum.clone();
+        Func::wrap(wast_context.store_mut(), move || sum.load(SeqCst))
+    };
+    wast_context
+        .register_module_imports("host", &[("add", add.into()), ("total", total.into())])?;
+    wast_context.run_buffer(
+        "custom_host_imports.wast",
+        br#"
+            (module
+                (import "host" "add" (func $add (param i32)))
+                (import "host" "total" (func $total (result i32)))
+                (import "spectest" "global_i32" (global $g i32))
+                (func (export "run") (result i32)
+                    (call $add (i32.const 1))
+                    (call $add (global.get $g))
+                    (call $total)))
+            (assert_return (invoke "run") (i32.const 667))
+            (assert_unlinkable
+                (module (import "host" "missing" (func)))
+                "unknown import")
+        "#,
+    )?;
+    assert_eq!(sum.load(SeqCst), 667);
+    Ok(())
+}
+
+#[test]
+fn override_spectest_import() -> anyhow::Result<()> {
+    let mut wast_context = WastContext::new(Store::<()>::default());
+    wast_context.register_spectest()?;
+    let printed = Arc::new(Mutex::new(Vec::new()));
+    let print_i32 = {
+        let printed = printed.clone();
+        Func::wrap(wast_context.store_mut(), move |x: i32| {
+            printed.lock().unwrap().push(x);
+        })
+    };
+    wast_context.register_module_imports("spectest", &[("print_i32", print_i32.into())])?;
+    wast_context.run_buffer(
+        "override_spectest_import.wast",
+        br#"
+            (module
+                (import "spectest" "print_i32" (func $print (param i32)))
+                (import "spectest" "print" (func))
+                (func (export "run")
+                    (call $print (i32.const 1))
+                    (call $print (i32.const 2))))
+            (invoke "run")
+        "#,
+    )?;
+    assert_eq!(*printed.lock().unwrap(), [1, 2]);
+    Ok(())
+}
@@ -0,0 +1,64 @@
+;; `table.copy` and `table.init` require matching element types, and the
+;; error names the tables and segments involved.
+
+(assert_invalid
+  (module
+    (table $f 1 funcref)
+    (table $e 1 externref)
+    (func (table.copy $f $e (i32.const 0) (i32.const 0) (i32.const 1))))
+  "`table.copy` from table 1 of type externref to table 0 of type funcref")
+
+(assert_invalid
+  (module
+    (table $f 1 funcref)
+    (table $e 1 externref)
+    (func (table.copy $e $f (i32.const 0) (i32.const 0) (i32.const 1))))
+  "`table.copy` from table 0 of type funcref to table 1 of type externref")
+
+(assert_invalid
+  (module
+    (table $f 1 funcref)
+    (elem $s externref (ref.null extern))
+    (func (table.init $f $s (i32.const 0) (i32.const 0) (i32.const 1))))
+  "`table.init` from element segment 0 of type externref to table 0 of type funcref")
+
+(assert_invalid
+  (module
+    (table $e 1 externref)
+    (elem $s funcref (ref.func 0))
+    (func (table.init $e $s (i32.const 0) (i32.const 0) (i32.const 1))))
+  "`table.init` from element segment 0 of type funcref to table 0 of type externref")
+
+;; Copies between funcref tables of different sizes.
+(module
+  (func $a (result i32) (i32.const 1))
+  (func $b (result i32) (i32.const 2))
+  (func $c (result i32) (i32.const 3))
+
+  (table $small 2 funcref)
+  (table $large 5 funcref)
+  (elem (table $small) (i32.const 0) func $a $b)
+  (elem (table $large) (i32.const 0) func $c $c $c $c $c)
+
+  (type $t (func (result i32)))
+
+  (func (export "small_to_large") (param i32 i32 i32)
+    (table.copy $large $small (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "large_to_small") (param i32 i32 i32)
+    (table.copy $small $large (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "call_small") (param i32) (result i32)
+    (call_indirect $small (type $t) (local.get 0)))
+  (func (export "call_large") (param i32) (result i32)
+    (call_indirect $large (type $t) (local.get 0)))
+)
+
+(assert_return (invoke "small_to_large" (i32.const 3) (i32.const 0) (i32.const 2)))
+(assert_return (invoke "call_large" (i32.const 2)) (i32.const 3))
+(assert_return (invoke "call_large" (i32.const 3)) (i32.const 1))
+(assert_return (invoke "call_large" (i32.const 4)) (i32.const 2))
+(assert_trap (invoke "small_to_large" (i32.const 4) (i32.const 0) (i32.const 2)) "out of bounds")
+
+(assert_return (invoke "large_to_small" (i32.const 1) (i32.const 0) (i32.const 1)))
+(assert_return (invoke "call_small" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "call_small" (i32.const 1)) (i32.const 3))
+(assert_trap (invoke "large_to_small" (i32.const 0) (i32.const 0) (i32.const 3)) "out of bounds")
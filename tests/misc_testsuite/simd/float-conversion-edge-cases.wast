@@ -0,0 +1,63 @@
+;; Lanes at the edges of the float conversion and rounding instructions, which
+;; lowerings commonly get wrong: NaN, infinities, values just past the range
+;; of the integer type, and ties when rounding to nearest.
+
+(module
+  (func (export "i32x4.trunc_sat_f32x4_s") (param v128) (result v128)
+    (i32x4.trunc_sat_f32x4_s (local.get 0)))
+  (func (export "i32x4.trunc_sat_f32x4_u") (param v128) (result v128)
+    (i32x4.trunc_sat_f32x4_u (local.get 0)))
+  (func (export "f32x4.nearest") (param v128) (result v128)
+    (f32x4.nearest (local.get 0)))
+  (func (export "f64x2.nearest") (param v128) (result v128)
+    (f64x2.nearest (local.get 0)))
+  (func (export "f64x2.convert_low_i32x4_u") (param v128) (result v128)
+    (f64x2.convert_low_i32x4_u (local.get 0))))
+
+;; NaN converts to 0 and out-of-range lanes saturate.
+(assert_return (invoke "i32x4.trunc_sat_f32x4_s" (v128.const f32x4 nan -nan inf -inf))
+  (v128.const i32x4 0 0 0x7fffffff 0x80000000))
+(assert_return (invoke "i32x4.trunc_sat_f32x4_s" (v128.const f32x4 nan:0x200000 -nan:0x1 0x1p100 -0x1p100))
+  (v128.const i32x4 0 0 0x7fffffff 0x80000000))
+;; 2147483648.0 is INT_MAX + 1, and 2147483520.0 is the largest float below it.
+(assert_return (invoke "i32x4.trunc_sat_f32x4_s" (v128.const f32x4 2147483648.0 2147483520.0 -2147483648.0 -2147483904.0))
+  (v128.const i32x4 0x7fffffff 2147483520 0x80000000 0x80000000))
+(assert_return (invoke "i32x4.trunc_sat_f32x4_s" (v128.const f32x4 -0.0 0.9 -0.9 -1.5))
+  (v128.const i32x4 0 0 0 -1))
+
+(assert_return (invoke "i32x4.trunc_sat_f32x4_u" (v128.const f32x4 nan -nan inf -inf))
+  (v128.const i32x4 0 0 0xffffffff 0))
+(assert_return (invoke "i32x4.trunc_sat_f32x4_u" (v128.const f32x4 nan:0x200000 -nan:0x1 0x1p100 -0x1p100))
+  (v128.const i32x4 0 0 0xffffffff 0))
+;; 4294967040.0 is the largest float below UINT_MAX + 1.
+(assert_return (invoke "i32x4.trunc_sat_f32x4_u" (v128.const f32x4 2147483648.0 2147483520.0 4294967040.0 4294967296.0))
+  (v128.const i32x4 0x80000000 2147483520 4294967040 0xffffffff))
+(assert_return (invoke "i32x4.trunc_sat_f32x4_u" (v128.const f32x4 -0.0 0.9 -0.9 -1.0))
+  (v128.const i32x4 0 0 0 0))
+(assert_return (invoke "i32x4.trunc_sat_f32x4_u" (v128.const f32x4 3000000000.0 2147483904.0 1.5 4294967295.0))
+  (v128.const i32x4 3000000000 2147483904 1 0xffffffff))
+
+;; Ties round to even regardless of the sign.
+(assert_return (invoke "f32x4.nearest" (v128.const f32x4 0.5 1.5 2.5 3.5))
+  (v128.const f32x4 0.0 2.0 2.0 4.0))
+(assert_return (invoke "f32x4.nearest" (v128.const f32x4 -0.5 -1.5 -2.5 -3.5))
+  (v128.const f32x4 -0.0 -2.0 -2.0 -4.0))
+;; 0x1.fffffep-2 is the largest float below 0.5, and 0x1.fffffep22 is
+;; 8388607.5, a tie just below 2^23 where floats stop having fractions.
+(assert_return (invoke "f32x4.nearest" (v128.const f32x4 0x1.fffffep-2 -0x1.fffffep-2 0x1.fffffep22 0x1.000002p23))
+  (v128.const f32x4 0.0 -0.0 0x1p23 0x1.000002p23))
+(assert_return (invoke "f32x4.nearest" (v128.const f32x4 nan inf -inf -0.0))
+  (v128.const f32x4 nan:arithmetic inf -inf -0.0))
+
+(assert_return (invoke "f64x2.nearest" (v128.const f64x2 2.5 -0.5))
+  (v128.const f64x2 2.0 -0.0))
+(assert_return (invoke "f64x2.nearest" (v128.const f64x2 0x1.fffffffffffffp-2 0x1.fffffffffffffp51))
+  (v128.const f64x2 0.0 0x1p52))
+(assert_return (invoke "f64x2.nearest" (v128.const f64x2 -nan -inf))
+  (v128.const f64x2 nan:arithmetic -inf))
+
+;; Lanes are unsigned, and only the low two are converted.
+(assert_return (invoke "f64x2.convert_low_i32x4_u" (v128.const i32x4 -1 0x80000000 1 2))
+  (v128.const f64x2 4294967295.0 2147483648.0))
+(assert_return (invoke "f64x2.convert_low_i32x4_u" (v128.const i32x4 0 0x7fffffff -1 -1))
+  (v128.const f64x2 0.0 2147483647.0))
@@ -0,0 +1,31 @@
+;; `memory.atomic.notify` and `memory.atomic.wait` on memories which aren't
+;; shared: nothing can be waiting so notify returns 0, and waiting traps.
+
+(module
+  (memory 1 1)
+  (func (export "notify") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    memory.atomic.notify)
+  (func (export "wait32") (param i32 i32 i64) (result i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.atomic.wait32)
+  (func (export "wait64") (param i32 i64 i64) (result i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.atomic.wait64)
+)
+
+(assert_return (invoke "notify" (i32.const 0) (i32.const 1)) (i32.const 0))
+(assert_return (invoke "notify" (i32.const 65532) (i32.const -1)) (i32.const 0))
+(assert_trap (invoke "notify" (i32.const 2) (i32.const 1)) "misaligned memory access")
+(assert_trap (invoke "notify" (i32.const 65536) (i32.const 1)) "out of bounds memory access")
+
+(assert_trap (invoke "wait32" (i32.const 0) (i32.const 0) (i64.const -1)) "atomic wait on non-shared memory")
+(assert_trap (invoke "wait32" (i32.const 0) (i32.const 1) (i64.const 0)) "atomic wait on non-shared memory")
+(assert_trap (invoke "wait32" (i32.const 65536) (i32.const 0) (i64.const 0)) "out of bounds memory access")
+(assert_trap (invoke "wait64" (i32.const 0) (i64.const 0) (i64.const 0)) "atomic wait on non-shared memory")
+(assert_trap (invoke "wait64" (i32.const 65536) (i64.const 0) (i64.const 0)) "out of bounds memory access")
@@ -0,0 +1,43 @@
+;; The threads testsuite's `spectest` `shared_memory`, which nothing but the
+;; importing module can reach, so waiting on it only ever mismatches or times
+;; out.
+
+(module
+  (import "spectest" "shared_memory" (memory 1 2 shared))
+  (func (export "store") (param i32 i32)
+    local.get 0
+    local.get 1
+    i32.atomic.store)
+  (func (export "notify") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    memory.atomic.notify)
+  (func (export "wait32") (param i32 i32 i64) (result i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.atomic.wait32)
+  (func (export "wait64") (param i32 i64 i64) (result i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.atomic.wait64)
+)
+
+(invoke "store" (i32.const 0) (i32.const 42))
+(assert_return (invoke "notify" (i32.const 0) (i32.const 1)) (i32.const 0))
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 0) (i64.const -1)) (i32.const 1))
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 42) (i64.const 0)) (i32.const 2))
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 42) (i64.const 1000)) (i32.const 2))
+(assert_return (invoke "wait64" (i32.const 0) (i64.const 0) (i64.const 0)) (i32.const 1))
+(assert_return (invoke "wait64" (i32.const 0) (i64.const 42) (i64.const 0)) (i32.const 2))
+(assert_trap (invoke "wait32" (i32.const 0) (i32.const 42) (i64.const -1)) "would never be notified")
+(assert_trap (invoke "wait32" (i32.const 2) (i32.const 0) (i64.const 0)) "misaligned memory access")
+(assert_trap (invoke "wait64" (i32.const 65536) (i64.const 0) (i64.const 0)) "out of bounds memory access")
+
+(assert_unlinkable
+  (module (import "spectest" "shared_memory" (memory 2 2 shared)))
+  "incompatible import type")
+(assert_unlinkable
+  (module (import "spectest" "memory" (memory 1 2 shared)))
+  "incompatible import type")
@@ -0,0 +1,42 @@
+;; Prints each environment variable on its own line.
+(module
+  (import "wasi_snapshot_preview1" "environ_sizes_get"
+    (func $__wasi_environ_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "environ_get"
+    (func $__wasi_environ_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $__wasi_fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit"
+    (func $__wasi_proc_exit (param i32)))
+  (func $_start
+    (local $buf i32)
+    (local $size i32)
+    (local $i i32)
+    (if (call $__wasi_environ_sizes_get (i32.const 0) (i32.const 4))
+      (then (call $__wasi_proc_exit (i32.const 1))))
+
+    ;; The pointers go at 1024, followed by the strings themselves.
+    (local.set $buf
+      (i32.add (i32.const 1024) (i32.mul (i32.load (i32.const 0)) (i32.const 4))))
+    (local.set $size (i32.load (i32.const 4)))
+    (if (call $__wasi_environ_get (i32.const 1024) (local.get $buf))
+      (then (call $__wasi_proc_exit (i32.const 1))))
+
+    ;; Replace each string's nul terminator with a newline.
+    (block $done
+      (loop $loop
+        (br_if $done (i32.ge_u (local.get $i) (local.get $size)))
+        (if (i32.eqz (i32.load8_u (i32.add (local.get $buf) (local.get $i))))
+          (then (i32.store8 (i32.add (local.get $buf) (local.get $i)) (i32.const 10))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $loop)))
+
+    (i32.store (i32.const 8) (local.get $buf))
+    (i32.store (i32.const 12) (local.get $size))
+    (if (call $__wasi_fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 16))
+      (then (call $__wasi_proc_exit (i32.const 1))))
+  )
+  (memory 16)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+)
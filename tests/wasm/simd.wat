@@ -0,0 +1,6 @@
+(module
+  (func (export "run") (result i32)
+    (i32x4.extract_lane 3
+      (i32x4.add
+        (i32x4.splat (i32.const 40))
+        (v128.const i32x4 0 0 0 2)))))